@@ -1,36 +1,197 @@
 use std::{
     fmt::{self},
-    io,
+    io::{self, Write},
 };
 
+#[cfg(feature = "readline")]
+use camino::Utf8PathBuf;
+#[cfg(feature = "readline")]
+use log::warn;
+#[cfg(feature = "readline")]
+use rustyline::{DefaultEditor, error::ReadlineError};
+use tabled::{
+    Table, Tabled,
+    settings::{Padding, Style},
+};
 use ultimate_mod_man_rs_utils::{
     types::{
-        AssetSlot, AvailableSlotsToSwapToInfo, PickedNonSwappableResolutionOption,
-        PickedResolutionOption, VariantAndId,
+        ModId, PickedNonSwappableResolutionOption, PickedResolutionOption, PickedSwapOption,
+        VariantAndId,
+    },
+    user_input_delegate::{
+        AvailableSlotToSwapInto, SlotInfo, UserInputDelegate, VariantConflictSummary,
+        parse_item_selection,
     },
-    user_input_delegate::{UserInputDelegate, VariantConflictSummary},
 };
 
+/// A row of a listing of character skin slots.
+#[derive(Debug, Tabled)]
+pub(crate) struct SlotViewTable {
+    #[tabled(rename = "#")]
+    pub(crate) option: usize,
+
+    #[tabled(rename = "Character")]
+    pub(crate) character: String,
+
+    #[tabled(rename = "Slot")]
+    pub(crate) slot: String,
+
+    #[tabled(rename = "Mod")]
+    pub(crate) mod_name: String,
+
+    #[tabled(rename = "Enabled")]
+    pub(crate) enabled: String,
+}
+
 #[derive(Debug)]
 pub(crate) struct CliUserInputDelegate {
     buf: String,
+
+    /// Only set if readline input was asked for and stdin is a terminal.
+    #[cfg(feature = "readline")]
+    line_editor: Option<LineEditor>,
+}
+
+/// Readline-style input (editing and history) that remembers the history
+/// between runs.
+#[cfg(feature = "readline")]
+#[derive(Debug)]
+struct LineEditor {
+    editor: DefaultEditor,
+    history_path: Option<Utf8PathBuf>,
+}
+
+#[cfg(feature = "readline")]
+impl LineEditor {
+    const HISTORY_FILE_NAME: &str = "history.txt";
+
+    fn new() -> Option<Self> {
+        let mut editor = match DefaultEditor::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                warn!(
+                    "Unable to set up readline input ({}). Falling back to plain input.",
+                    err
+                );
+                return None;
+            },
+        };
+
+        let history_path = dirs::data_local_dir()
+            .and_then(|p| Utf8PathBuf::from_path_buf(p).ok())
+            .map(|p| p.join("ultimate-mod-man-rs").join(Self::HISTORY_FILE_NAME));
+
+        // There is no history the first time that we run.
+        if let Some(history_path) = &history_path
+            && history_path.exists()
+            && let Err(err) = editor.load_history(history_path)
+        {
+            warn!(
+                "Unable to load the input history from {:?}: {}",
+                history_path, err
+            );
+        }
+
+        Some(Self {
+            editor,
+            history_path,
+        })
+    }
+
+    /// Returns `None` once there is no more input.
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let line = match self.editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Eof) => return None,
+            // Same as hitting Ctrl-C while reading from plain stdin.
+            Err(ReadlineError::Interrupted) => std::process::exit(130),
+            Err(err) => panic!("Unable to read from stdin: {}", err),
+        };
+
+        if !line.trim().is_empty() {
+            let _ = self.editor.add_history_entry(line.as_str());
+            self.save_history();
+        }
+
+        Some(line)
+    }
+
+    /// Saved after every line since we may exit without unwinding.
+    fn save_history(&mut self) {
+        let Some(history_path) = &self.history_path else {
+            return;
+        };
+
+        let res = history_path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(ReadlineError::from)
+            .and_then(|_| self.editor.save_history(history_path));
+
+        if let Err(err) = res {
+            warn!(
+                "Unable to save the input history to {:?}: {}",
+                history_path, err
+            );
+        }
+    }
 }
 
 impl CliUserInputDelegate {
     pub(crate) fn new() -> Self {
-        Self { buf: String::new() }
+        Self {
+            buf: String::new(),
+            #[cfg(feature = "readline")]
+            line_editor: None,
+        }
     }
 
-    fn read_user_input(&mut self) {
+    /// Reads input with line editing and history instead. Has no effect if
+    /// stdin is not a terminal (eg. when input is piped in).
+    #[cfg(feature = "readline")]
+    pub(crate) fn with_readline(mut self) -> Self {
+        use std::io::IsTerminal;
+
+        if io::stdin().is_terminal() {
+            self.line_editor = LineEditor::new();
+        }
+
+        self
+    }
+
+    /// `prompt` is shown on the same line as the input.
+    fn read_user_input(&mut self, prompt: &str) {
         self.buf.clear();
+
+        #[cfg(feature = "readline")]
+        if let Some(line_editor) = &mut self.line_editor {
+            if let Some(line) = line_editor.read_line(prompt) {
+                self.buf.push_str(&line);
+            }
+
+            return;
+        }
+
+        print!("{}", prompt);
+        let _ = io::stdout().flush();
+
         io::stdin()
             .read_line(&mut self.buf)
             .expect("Unable to read from stdin!");
     }
 
+    /// Prints the rows as a table with aligned columns. CLI only, since other
+    /// frontends have their own ways of showing tabular data.
+    pub(crate) fn display_table<T: Tabled>(&mut self, rows: &[T]) {
+        let mut table = Table::new(rows);
+        table.with(Style::blank()).with(Padding::new(2, 1, 0, 0));
+
+        println!("{}", table);
+    }
+
     fn get_item_index_of_item(&mut self, num_items: usize) -> usize {
         loop {
-            self.read_user_input();
+            self.read_user_input("");
             let input = self.buf.trim();
 
             let n = match input.parse() {
@@ -57,8 +218,7 @@ impl CliUserInputDelegate {
 
 impl UserInputDelegate for CliUserInputDelegate {
     fn get_yes_no_resp(&mut self) -> bool {
-        print!("(y/n)");
-        self.read_user_input();
+        self.read_user_input("(y/n)");
 
         // Assume anything else is a `no` for now.
         let input = self.buf.trim().to_lowercase();
@@ -73,16 +233,170 @@ impl UserInputDelegate for CliUserInputDelegate {
         self.get_item_index_of_item(items.len())
     }
 
+    fn select_items_from_list<T: fmt::Display>(
+        &mut self,
+        items: &[T],
+        preselected: &[usize],
+    ) -> Vec<usize> {
+        for (i, item) in items.iter().enumerate() {
+            let marker = if preselected.contains(&i) { '*' } else { ' ' };
+            println!("{marker} {i} - {item}");
+        }
+
+        println!(
+            "Select items (eg. `1,3-5,8`, `all` or `none`). Leave empty to keep the ones marked \
+             with `*`."
+        );
+
+        loop {
+            self.read_user_input("");
+            let input = self.buf.trim();
+
+            if input.is_empty() {
+                let mut selected = preselected.to_vec();
+                selected.sort_unstable();
+                selected.dedup();
+
+                return selected;
+            }
+
+            match parse_item_selection(input, items.len()) {
+                Ok(selected) => return selected,
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+
+    fn get_free_text_resp(&mut self, prompt: &str) -> String {
+        println!("{}", prompt);
+        self.read_user_input("");
+
+        self.buf.trim().to_string()
+    }
+
     fn display_variant_conflict_summary(&mut self, summary: &VariantConflictSummary) {
-        todo!()
+        match summary.is_same_mod() {
+            false => println!(
+                "{} conflicts with the enabled mod variant {}.",
+                summary.fmt_new_variant(),
+                summary.fmt_existing_variant()
+            ),
+            true => println!(
+                "{} conflicts with {}, which is another variant of the same mod.",
+                summary.fmt_new_variant(),
+                summary.fmt_existing_variant()
+            ),
+        }
+    }
+
+    fn confirm_replace_sibling_variant(
+        &mut self,
+        existing: &VariantAndId,
+        new: &VariantAndId,
+    ) -> bool {
+        println!(
+            "Disable {} and use {} instead? (Otherwise each conflict is resolved separately.)",
+            existing, new
+        );
+        self.get_yes_no_resp()
+    }
+
+    fn display_waiting_for_lock(&mut self, holder_pid: Option<u32>) {
+        match holder_pid {
+            Some(pid) => eprintln!(
+                "Another instance (PID {}) holds the state directory. Waiting...",
+                pid
+            ),
+            None => eprintln!("Another instance holds the state directory. Waiting..."),
+        }
+    }
+
+    fn display_update_check_progress(&mut self, num_checked: usize, total: usize, mod_name: &str) {
+        // Progress goes to `stderr` so that it doesn't end up in any piped output.
+        eprintln!("Checked {}/{}: {}", num_checked, total, mod_name);
+    }
+
+    fn display_sync_progress(
+        &mut self,
+        files_copied: usize,
+        total_files: usize,
+        bytes_copied: u64,
+        total_bytes: u64,
+    ) {
+        // Syncs can copy tens of thousands of files, so only print whenever
+        // another percent is done.
+        let percent = |files| files * 100 / total_files;
+        if files_copied == 1 || percent(files_copied) != percent(files_copied - 1) {
+            eprintln!(
+                "Copied {}/{} files ({}/{} bytes)",
+                files_copied, total_files, bytes_copied, total_bytes
+            );
+        }
+    }
+
+    fn confirm_variant_update(&mut self, key: &VariantAndId) -> bool {
+        println!("An update is available for {}. Update it?", key);
+        self.get_yes_no_resp()
+    }
+
+    fn confirm_mod_install(&mut self, id: ModId, mod_name: &str) -> bool {
+        println!("Install \"{}\" ({})?", mod_name, id);
+        self.get_yes_no_resp()
+    }
+
+    fn confirm_variant_successor(
+        &mut self,
+        installed: &VariantAndId,
+        successor_file_name: &str,
+    ) -> bool {
+        println!(
+            "The installed file of {} is no longer available on GameBanana, but \"{}\" looks like \
+             its successor. Adopt it?",
+            installed, successor_file_name
+        );
+
+        self.get_yes_no_resp()
+    }
+
+    fn choose_slot_to_swap_to(
+        &mut self,
+        slot_info: &SlotInfo,
+        available_slots: &[AvailableSlotToSwapInto],
+    ) -> PickedSwapOption {
+        println!(
+            "Which slot should \"{}\" ({}) be moved to?",
+            slot_info.slot_name(),
+            slot_info.slot_type_name()
+        );
+
+        // Only enabled variants take up a slot.
+        let rows = available_slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| SlotViewTable {
+                option: i,
+                character: slot_info.character().unwrap_or("-").to_string(),
+                slot: format!("c{:02}", slot.slot_idx()),
+                mod_name: slot
+                    .occupied_by()
+                    .map_or_else(|| "-".to_string(), |key| key.to_string()),
+                enabled: match slot.occupied_by() {
+                    Some(_) => "yes".to_string(),
+                    None => "-".to_string(),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        self.display_table(&rows);
+        PickedSwapOption::new(self.get_item_index_of_item(rows.len()))
     }
 
     fn get_variant_conflict_resolution_option_swappable(
         &mut self,
         existing: &VariantAndId,
         new: &VariantAndId,
-        slot: &AssetSlot,
-        available_slots_info: &AvailableSlotsToSwapToInfo,
+        slot_info: &SlotInfo,
+        num_open_slots_available: usize,
     ) -> PickedResolutionOption {
         todo!()
     }
@@ -91,7 +405,7 @@ impl UserInputDelegate for CliUserInputDelegate {
         &mut self,
         existing: &VariantAndId,
         new: &VariantAndId,
-        slot: &AssetSlot,
+        slot_info: &SlotInfo,
     ) -> PickedNonSwappableResolutionOption {
         todo!()
     }
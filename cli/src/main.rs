@@ -1,40 +1,481 @@
+use std::{env, fs, process};
+
+use anyhow::bail;
+use camino::Utf8Path;
+use chrono::Duration;
 use clap::Parser;
 use cli_user_input_delegate::CliUserInputDelegate;
-use prog_args::{ProgArgs, StatusCliArgs};
-use ultimate_mod_man_rs_core::{cmds::status::StatusCmdInfo, mod_manager::ModManager};
+use prog_args::{
+    Command, ConfigCommand, DoctorArgs, NoteArgs, OutputFormat, ProgArgs, StatusCliArgs,
+    TagCommand, TargetCommand, get_os_default_state_dir_path,
+};
+use ultimate_mod_man_rs_core::{
+    cmds::{
+        doctor::CheckStatus,
+        status::{StatusCmdInfo, StatusCmdKind},
+        sync::SyncCopyOutcome,
+    },
+    config::ConfigKey,
+    mod_db::FAVORITE_TAG,
+    mod_manager::{
+        AddConflictPolicy, ModManager, ModManagerOptions, ModManagerView, apply_doctor_fix,
+        read_mod_list_file, run_doctor,
+    },
+    style::ColorMode,
+    sync_targets::SyncTarget,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::{ChecksumPolicy, VariantMatchPolicy};
+use ultimate_mod_man_rs_utils::{
+    types::{CharSkinSlotValue, SkinSlotValue, SwappableAssetSlot},
+    user_input_delegate::UserInputDelegate,
+};
+use user_config::UserConfig;
 
 mod cli_user_input_delegate;
 mod prog_args;
+mod user_config;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let p_args = ProgArgs::parse();
+    let user_config = UserConfig::load()?;
     let user_input_delegate = CliUserInputDelegate::new();
+    #[cfg(feature = "readline")]
+    let user_input_delegate = match p_args.readline {
+        false => user_input_delegate,
+        true => user_input_delegate.with_readline(),
+    };
+
+    let state_dir_path = p_args
+        .state_dir_path
+        .or(user_config.state_dir)
+        .unwrap_or_else(get_os_default_state_dir_path);
+    let default_format = user_config.format.unwrap_or_default();
+
+    // Overrides passed on the command line go last so that they win.
+    let mut config_overrides = Vec::new();
+    if let Some(max_concurrent) = user_config.max_concurrent_downloads {
+        config_overrides.push((ConfigKey::DownloadMaxConcurrent, max_concurrent.to_string()));
+    }
+    config_overrides.extend(p_args.config_overrides);
 
-    let mut mm = ModManager::new(&p_args.state_dir_path, user_input_delegate)?;
+    let options = ModManagerOptions {
+        config_overrides,
+        color: p_args
+            .color
+            .or(user_config.color)
+            .unwrap_or(ColorMode::Auto),
+        init_here: p_args.init_here,
+    };
+
+    // Has to work even if the state directory can not be opened.
+    if let Command::Doctor(doctor_args) = &p_args.command {
+        return doctor(
+            &state_dir_path,
+            &options,
+            doctor_args,
+            default_format,
+            user_input_delegate,
+        )
+        .await;
+    }
+
+    if p_args.command.is_read_only() {
+        let view = ModManagerView::load(&state_dir_path, options)?;
+        return run_read_only_command(&view, p_args.command, default_format).await;
+    }
+
+    let mut mm =
+        ModManager::new_with_options(&state_dir_path, options, user_input_delegate).await?;
 
     match p_args.command {
-        prog_args::Command::Status(status_args) => mm.status(status_args.into())?,
-        prog_args::Command::Add(add_args) => mm.add_mods(add_args.mods.mods).await?,
+        prog_args::Command::Status(_)
+        | prog_args::Command::DiskUsage(_)
+        | prog_args::Command::Occupancy(_)
+        | prog_args::Command::HealthCheck => {
+            unreachable!("Read-only commands are run on a view")
+        },
+        prog_args::Command::Doctor(_) => unreachable!("The doctor is run before opening"),
+        prog_args::Command::Add(add_args) => {
+            let operation_timeout = add_args
+                .operation_timeout
+                .map(std::time::Duration::from_secs);
+            let match_policy = match add_args.exact {
+                false => VariantMatchPolicy::Fuzzy,
+                true => VariantMatchPolicy::Exact,
+            };
+            let checksum_policy = match add_args.skip_checksum {
+                false => ChecksumPolicy::Verify,
+                true => ChecksumPolicy::Skip,
+            };
+            let conflict_policy = match (
+                add_args.replace_existing,
+                add_args.keep_existing,
+                add_args.as_alternative,
+            ) {
+                (true, ..) => AddConflictPolicy::ReplaceExisting,
+                (_, true, _) => AddConflictPolicy::KeepExisting,
+                (.., true) => AddConflictPolicy::KeepAsAlternative,
+                _ => mm.default_add_conflict_policy(),
+            };
+
+            let mut mods = add_args.mods.mods;
+            if let Some(path) = &add_args.from_file {
+                mods.extend(read_mod_list_file(path)?);
+            }
+
+            let outcome = mm
+                .add_mods(
+                    mods,
+                    operation_timeout,
+                    match_policy,
+                    checksum_policy,
+                    conflict_policy,
+                    add_args.asset_types.as_deref(),
+                )
+                .await?;
+            print!("{}", outcome);
+
+            if !outcome.failed.is_empty() {
+                bail!("Unable to add {} of the mod variants", outcome.failed.len());
+            }
+        },
+        prog_args::Command::OpenPage { ident } => mm.open_mod_page(&ident).await?,
+        prog_args::Command::Browse { query, page } => {
+            let conflict_policy = mm.default_add_conflict_policy();
+            let outcome = mm
+                .browse(&query, page, ChecksumPolicy::Verify, conflict_policy)
+                .await?;
+
+            if let Some(outcome) = outcome {
+                print!("{}", outcome);
+
+                if !outcome.failed.is_empty() {
+                    bail!("Unable to add the mod variant");
+                }
+            }
+        },
+        prog_args::Command::ListVariants { ident } => print!("{}", mm.list_variants(&ident).await?),
+        prog_args::Command::Dedupe(dedupe_args) => {
+            let report = mm.find_duplicates()?;
+            let format = dedupe_args.format.unwrap_or(default_format);
+
+            match format {
+                OutputFormat::Table => print!("{}", report),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+
+            if format == OutputFormat::Table && !report.groups.is_empty() {
+                let outcome = mm.remove_duplicates(&report).await?;
+                print!("{}", outcome);
+
+                if !outcome.failed.is_empty() {
+                    bail!(
+                        "Unable to delete {} of the mod variants",
+                        outcome.failed.len()
+                    );
+                }
+            }
+        },
+        prog_args::Command::Gc(gc_args) => {
+            print!("{}", mm.gc(Duration::days(gc_args.older_than_days.into()))?)
+        },
         prog_args::Command::Delete => todo!(),
-        prog_args::Command::CheckForUpdates => todo!(),
-        prog_args::Command::SyncWithSwitch => todo!(),
-        prog_args::Command::EnableDisable(enable_disable_args) => todo!(),
+        prog_args::Command::CheckForUpdates(update_args) => {
+            let report = mm
+                .check_for_updates(
+                    update_args.auto_update,
+                    &update_args.exclude,
+                    update_args.force,
+                )
+                .await?;
+
+            match update_args.format.unwrap_or(default_format) {
+                OutputFormat::Table => print!("{}", report),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+
+            if !report.failed_updates.is_empty() {
+                bail!(
+                    "Unable to update {} of the mod variants",
+                    report.failed_updates.len()
+                );
+            }
+        },
+        prog_args::Command::Update(update_args) => {
+            if let Some(auto) = update_args.save_auto_default {
+                mm.set_auto_update_default(auto)?;
+            }
+
+            let idents = (!update_args.mods.mods.is_empty()).then_some(update_args.mods.mods);
+            let summary = mm
+                .update(idents, update_args.dry_run, update_args.auto)
+                .await?;
+            print!("{}", summary);
+        },
+        prog_args::Command::Tag(tag_cmd) => match tag_cmd {
+            TagCommand::Add { r#mod, tags } => {
+                let added = mm.add_tags(&r#mod, &tags)?;
+                println!("Added tags: {}", added.join(", "));
+            },
+            TagCommand::Remove { r#mod, tags } => {
+                let removed = mm.remove_tags(&r#mod, &tags)?;
+                println!("Removed tags: {}", removed.join(", "));
+            },
+            TagCommand::List { .. } => unreachable!("Read-only commands are run on a view"),
+        },
+        prog_args::Command::Config(config_cmd) => match config_cmd {
+            ConfigCommand::Set { key, value } => mm.config_set(key, &value)?,
+            ConfigCommand::Get { .. } | ConfigCommand::List => {
+                unreachable!("Read-only commands are run on a view")
+            },
+        },
+        prog_args::Command::Overrides { r#mod } => {
+            if let Some(listing) = mm.overrides(r#mod).await? {
+                print!("{}", listing);
+            }
+        },
+        prog_args::Command::Note(note_args) => set_note(&mut mm, note_args).await?,
+        prog_args::Command::Rename(rename_args) => {
+            mm.set_alias(rename_args.r#mod, rename_args.alias.as_deref())
+                .await?
+        },
+        prog_args::Command::RollbackUpdate { r#mod } => mm.rollback_update(r#mod).await?,
+        prog_args::Command::Reclassify { r#mod } => mm.reclassify(r#mod).await?,
+        prog_args::Command::SyncWithSwitch(sync_args) => {
+            let target = sync_args.target.target.as_deref();
+            match sync_args.dry_run {
+                false => report_sync_copies(&mm.sync_with_switch(target, sync_args.dest_layout)?)?,
+                true => print!(
+                    "{}",
+                    mm.plan_sync_with_switch(target, sync_args.dest_layout)?
+                ),
+            }
+        },
+        prog_args::Command::EnableDisable(enable_disable_args) => {
+            let summary = match enable_disable_args.tag {
+                Some(tag) => mm.enable_disable_tagged(&tag, enable_disable_args.enable)?,
+                None => {
+                    mm.enable_disable(enable_disable_args.mods.mods, enable_disable_args.enable)
+                        .await?
+                },
+            };
+            print!("{}", summary);
+        },
         prog_args::Command::ResolveConflicts => todo!(),
-        prog_args::Command::ChangeSlot => todo!(),
-        prog_args::Command::SwitchCompare => todo!(),
+        prog_args::Command::ChangeSlot(change_slot_args) => {
+            let slot = SwappableAssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                change_slot_args.character,
+                SkinSlotValue::new(change_slot_args.slot),
+            ));
+            mm.change_slot(change_slot_args.ident, slot).await?
+        },
+        prog_args::Command::SwapActive(swap_active_args) => {
+            let slot = CharSkinSlotValue::new(
+                swap_active_args.character,
+                SkinSlotValue::new(swap_active_args.slot),
+            );
+            mm.swap_active(slot, swap_active_args.ident).await?
+        },
+        prog_args::Command::SwitchCompare(compare_args) => {
+            let report = mm.switch_compare(
+                compare_args.target.target.as_deref(),
+                compare_args.install_missing,
+            )?;
+            print!("{}", report);
+
+            if let Some(outcome) = report.installed_missing() {
+                report_sync_copies(outcome)?;
+            }
+        },
+        prog_args::Command::Target(target_cmd) => match target_cmd {
+            TargetCommand::Add(add_args) => {
+                let target = SyncTarget::new(
+                    add_args.target_type,
+                    add_args.path.clone(),
+                    add_args.options_map(),
+                    add_args.ignore.clone(),
+                )?;
+                mm.add_sync_target(add_args.name, target, add_args.default)?
+            },
+            TargetCommand::List => print!("{}", mm.list_sync_targets()),
+            TargetCommand::Remove { name } => mm.remove_sync_target(&name)?,
+        },
+        prog_args::Command::WarmCache { names } => print!("{}", mm.warm_name_cache(names).await?),
+        prog_args::Command::PruneCache => {
+            let num_removed = mm.prune_name_resolver_cache()?;
+            println!("Removed {} cached mod name(s).", num_removed);
+        },
+        prog_args::Command::ExportConflicts { output } => mm.export_conflict_report(&output)?,
+    }
+
+    Ok(())
+}
+
+async fn run_read_only_command(
+    view: &ModManagerView,
+    command: Command,
+    default_format: OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Status(status_args) => {
+            let format = status_args.format.unwrap_or(default_format);
+            let report = view.status(status_args.into())?;
+
+            match format {
+                OutputFormat::Table => print!("{}", report),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+        },
+        Command::DiskUsage(disk_usage_args) => match disk_usage_args.by_character {
+            false => print!("{}", view.disk_usage()),
+            true => print!("{}", view.disk_usage_by_character()),
+        },
+        Command::Occupancy(occupancy_args) => {
+            print!("{}", view.occupancy(occupancy_args.conflicts_only))
+        },
+        Command::Tag(TagCommand::List { r#mod }) => {
+            for tag in view.tags(&r#mod)? {
+                println!("{}", tag);
+            }
+        },
+        Command::Config(ConfigCommand::Get { key }) => println!("{}", view.config_get(key).0),
+        Command::Config(ConfigCommand::List) => print!("{}", view.config_list()),
+        Command::HealthCheck => {
+            let report = view.health_check().await?;
+            print!("{}", report);
+
+            if !report.is_healthy() {
+                bail!("GameBanana is not responding the way that the mod manager expects");
+            }
+        },
+        command => unreachable!("{:?} is not a read-only command", command),
+    }
+
+    Ok(())
+}
+
+async fn doctor(
+    state_dir_path: &Utf8Path,
+    options: &ModManagerOptions,
+    args: &DoctorArgs,
+    default_format: OutputFormat,
+    mut user_input_delegate: CliUserInputDelegate,
+) -> anyhow::Result<()> {
+    let format = args.format.unwrap_or(default_format);
+    let report = run_doctor(state_dir_path, options).await;
+
+    match format {
+        OutputFormat::Table => print!("{}", report),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    let mut num_failed = 0;
+    for check in report.checks.iter() {
+        let fixed = match check.fix {
+            Some(fix) if args.fix => {
+                apply_doctor_fix(state_dir_path, fix).await?;
+                true
+            },
+            Some(fix) if format == OutputFormat::Table => {
+                print!("{}? ", fix);
+                let apply = user_input_delegate.get_yes_no_resp();
+                if apply {
+                    apply_doctor_fix(state_dir_path, fix).await?;
+                }
+
+                apply
+            },
+            _ => false,
+        };
+
+        if check.status == CheckStatus::Fail && !fixed {
+            num_failed += 1;
+        }
+    }
+
+    if num_failed > 0 {
+        bail!("{} of the checks failed", num_failed);
+    }
+
+    Ok(())
+}
+
+async fn set_note(mm: &mut ModManager<CliUserInputDelegate>, args: NoteArgs) -> anyhow::Result<()> {
+    match (args.text, args.clear, args.edit) {
+        (Some(text), ..) => mm.set_note(args.r#mod, Some(&text)).await?,
+        (None, true, _) => mm.set_note(args.r#mod, None).await?,
+        (None, false, true) => {
+            let existing = mm.note(args.r#mod.clone()).await?;
+            let text = edit_text_in_editor(existing.as_deref().unwrap_or_default())?;
+            mm.set_note(args.r#mod, Some(&text)).await?
+        },
+        (None, false, false) => mm.prompt_for_note(args.r#mod).await?,
+    }
+
+    Ok(())
+}
+
+fn report_sync_copies(outcome: &SyncCopyOutcome) -> anyhow::Result<()> {
+    print!("{}", outcome);
+
+    if !outcome.failed.is_empty() {
+        bail!(
+            "Unable to copy {} files to the target. Syncing again will retry them.",
+            outcome.failed.len()
+        );
     }
 
     Ok(())
 }
 
+/// Opens `$EDITOR` (or `vi` if it is not set) on a temporary file containing
+/// `initial` and returns what the file contains once the editor exits.
+fn edit_text_in_editor(initial: &str) -> anyhow::Result<String> {
+    let path = env::temp_dir().join(format!("ultimate-mod-man-note-{}.txt", process::id()));
+    fs::write(&path, initial)?;
+
+    // `$EDITOR` may include arguments (eg. `code --wait`).
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut editor_args = editor.split_whitespace();
+    let status = process::Command::new(editor_args.next().unwrap_or("vi"))
+        .args(editor_args)
+        .arg(&path)
+        .status();
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    if !status?.success() {
+        bail!("The editor `{}` exited with an error", editor);
+    }
+
+    Ok(text?)
+}
+
 impl From<StatusCliArgs> for StatusCmdInfo {
     fn from(v: StatusCliArgs) -> Self {
         let no_mods_specified = v.mods.mods.is_empty();
 
-        match no_mods_specified {
-            false => StatusCmdInfo::Generic,
-            true => StatusCmdInfo::Specific(v.mods.mods),
+        let kind = match no_mods_specified {
+            false => StatusCmdKind::Specific(v.mods.mods),
+            true => StatusCmdKind::Generic,
+        };
+
+        let tag = match v.favorites {
+            false => v.tag,
+            true => Some(FAVORITE_TAG.to_string()),
+        };
+
+        StatusCmdInfo {
+            kind,
+            verbose: v.verbose,
+            recently_enabled_within: v
+                .recently_enabled_days
+                .map(|days| Duration::days(days.into())),
+            tag,
+            overrides: v.overrides,
+            sort: v.sort,
+            filter: v.filter.unwrap_or_default(),
         }
     }
 }
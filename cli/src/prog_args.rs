@@ -1,9 +1,18 @@
-use std::env::current_dir;
+use std::{collections::BTreeMap, env::current_dir};
 
 use camino::Utf8PathBuf;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::warn;
-use ultimate_mod_man_rs_utils::types::VariantAndIdentifier;
+use serde::Deserialize;
+use ultimate_mod_man_rs_core::{
+    cmds::{status::StatusSortKey, sync::DestLayout},
+    config::{ConfigKey, parse_config_override},
+    mod_query::ModQuery,
+    style::ColorMode,
+    sync_targets::SyncTargetType,
+};
+use ultimate_mod_man_rs_scraper::mod_file_classifier::AssetType;
+use ultimate_mod_man_rs_utils::types::{ModId, ModIdentifier, VariantAndIdentifier};
 
 /// Tool for managing mods for SSBU.
 ///
@@ -18,8 +27,35 @@ pub(crate) struct ProgArgs {
     pub(crate) command: Command,
 
     /// Path to the directory where the mod manager state and cache is located.
-    #[arg(short = 'p', long, default_value_t = get_os_default_state_dir_path())]
-    pub(crate) state_dir_path: Utf8PathBuf,
+    /// Defaults to `state_dir` in the user config, or the OS cache directory
+    /// if that is not set either.
+    #[arg(short = 'p', long, env = "UMM_STATE_DIR")]
+    pub(crate) state_dir_path: Option<Utf8PathBuf>,
+
+    /// Use the state directory even if it already has files in it that were
+    /// not put there by the mod manager.
+    #[arg(long)]
+    pub(crate) init_here: bool,
+
+    /// Override a config setting for this run only (eg. `-c
+    /// update.max_concurrent_checks=2`). Can be passed multiple times.
+    #[arg(short = 'c', long = "config", value_parser = parse_config_override, global = true)]
+    pub(crate) config_overrides: Vec<(ConfigKey, String)>,
+
+    /// When to color the output:
+    /// - auto: Only when writing to a terminal (and `NO_COLOR` is not set).
+    /// - always: Even when piped.
+    /// - never: Never.
+    ///
+    /// Defaults to `color` in the user config, or `auto` if that is not set.
+    #[arg(long, global = true, verbatim_doc_comment)]
+    pub(crate) color: Option<ColorMode>,
+
+    /// Read prompt answers with line editing and a history that is kept
+    /// between runs. Ignored when stdin is not a terminal.
+    #[cfg(feature = "readline")]
+    #[arg(long, global = true)]
+    pub(crate) readline: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -31,15 +67,96 @@ pub(crate) enum Command {
     /// Add new mods to the manager.
     Add(AddArgs),
 
+    /// Open the GameBanana page of a mod in the default browser.
+    OpenPage {
+        /// The name or ID of the mod on GameBanana.
+        ident: ModIdentifier,
+    },
+
+    /// Search GameBanana for mods and pick one to install. Lists the current
+    /// top mods if no query is given.
+    Browse {
+        /// What to search for.
+        #[arg(default_value = "")]
+        query: String,
+
+        /// Only show this page of the search results (starting at `1`).
+        /// Without it, the first few pages are shown.
+        #[arg(long)]
+        page: Option<usize>,
+    },
+
+    /// List the variants (files) that can be downloaded for a mod.
+    ListVariants {
+        /// The name or ID of the mod on GameBanana.
+        ident: ModIdentifier,
+    },
+
     /// Delete mods added to the manager.  
     Delete,
 
     /// Check if updates are available for any added mods.
-    CheckForUpdates,
+    CheckForUpdates(CheckForUpdatesArgs),
+
+    /// Download and install newer versions of added mods.
+    Update(UpdateArgs),
+
+    /// Show how much disk space each installed mod uses.
+    DiskUsage(DiskUsageArgs),
+
+    /// Show which enabled mods occupy each character, stage and global slot.
+    Occupancy(OccupancyArgs),
+
+    /// Find mods that were installed more than once (from the same archive or
+    /// with nearly identical files) and pick which copies to delete. Nothing
+    /// is deleted unless it is picked.
+    Dedupe(DedupeArgs),
+
+    /// Delete the files of mods that have been disabled for a long time to
+    /// free up disk space. They stay listed and can be reinstalled with `add`.
+    Gc(GcArgs),
+
+    /// Organize installed mods with tags. Mods tagged `favorite` are shown
+    /// with `status --favorites`.
+    #[command(subcommand)]
+    Tag(TagCommand),
+
+    /// View or change the persistent settings in `config.toml`.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
+    /// Attach a note to an installed mod variant (or clear it). Prompts for the
+    /// note if neither the text nor any flags are given.
+    Note(NoteArgs),
+
+    /// Give an installed mod variant an alias that can be used in place of its
+    /// file name (or clear it).
+    Rename(RenameArgs),
+
+    /// Show the slot overrides applied to an installed mod variant.
+    Overrides {
+        /// The mod variant to show the overrides of.
+        r#mod: VariantAndIdentifier,
+    },
+
+    /// Go back to the version of a mod that was installed before it was last
+    /// updated.
+    RollbackUpdate {
+        /// The mod variant to roll back.
+        r#mod: VariantAndIdentifier,
+    },
+
+    /// Classify the files of an installed mod variant again without
+    /// downloading it (eg. after the manager learned about more kinds of
+    /// assets).
+    Reclassify {
+        /// The mod variant to reclassify.
+        r#mod: VariantAndIdentifier,
+    },
 
     /// Update the mods installed on a Switch with the mods that added to the
     /// manager.
-    SyncWithSwitch,
+    SyncWithSwitch(SyncArgs),
 
     /// Enable or disable a given set of mods.
     EnableDisable(EnableDisableArgs),
@@ -48,22 +165,465 @@ pub(crate) enum Command {
     ResolveConflicts,
 
     /// Swap character slots used by a mod.
-    ChangeSlot,
+    ChangeSlot(ChangeSlotArgs),
+
+    /// Enable an inactive alternative for a character skin slot (disabling the
+    /// variant that currently uses the slot and keeping it as an alternative).
+    SwapActive(SwapActiveArgs),
 
     /// Compare the state of a mod to state of the mod installed on a Switch.
-    SwitchCompare,
+    SwitchCompare(SwitchCompareArgs),
+
+    /// Manage the locations (SD cards, emulators, etc.) that mods get synced
+    /// to.
+    #[command(subcommand)]
+    Target(TargetCommand),
+
+    /// Resolve mod names to their IDs ahead of time so that they can be used
+    /// while offline.
+    WarmCache {
+        /// The exact names of the mods on GameBanana.
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+
+    /// Forget the resolved IDs of mod names that are not installed anymore.
+    PruneCache,
+
+    /// Write all current conflicts to a TOML file (useful for bug reports).
+    ExportConflicts {
+        /// Path of the report file to write.
+        #[arg(short = 'o', long)]
+        output: Utf8PathBuf,
+    },
+
+    /// Check that GameBanana is reachable and still responds the way that the
+    /// mod manager expects. Exits with an error if it does not.
+    HealthCheck,
+
+    /// Look for common problems with the state directory and the environment
+    /// (eg. a stale lock or unreadable mods). Offers to fix the problems that
+    /// are safe to fix automatically. Exits with an error if any check fails.
+    Doctor(DoctorArgs),
+}
+
+impl Command {
+    /// Commands that only query the state of the manager. These are run
+    /// without locking the state directory.
+    pub(crate) fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Status(_)
+                | Command::DiskUsage(_)
+                | Command::Occupancy(_)
+                | Command::HealthCheck
+                | Command::Tag(TagCommand::List { .. })
+                | Command::Config(ConfigCommand::Get { .. } | ConfigCommand::List)
+        )
+    }
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DoctorArgs {
+    /// Apply every fix that is offered without asking.
+    #[arg(long)]
+    pub(crate) fix: bool,
+
+    /// How to print the results (use `json` when attaching them to a bug
+    /// report). Fixes are only offered for `table`, unless `--fix` is passed.
+    /// Defaults to `format` in the user config, or `table` if that is not set.
+    #[arg(short = 'f', long, value_enum)]
+    pub(crate) format: Option<OutputFormat>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DedupeArgs {
+    /// How to print the duplicates. Copies to delete are only asked for with
+    /// `table`. Defaults to `format` in the user config, or `table` if that is
+    /// not set.
+    #[arg(short = 'f', long, value_enum)]
+    pub(crate) format: Option<OutputFormat>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct GcArgs {
+    /// Only delete the files of mods that have been disabled for at least this
+    /// many days.
+    #[arg(long, default_value_t = 30)]
+    pub(crate) older_than_days: u32,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DiskUsageArgs {
+    /// Show how much space the enabled skins of each character use instead.
+    #[arg(long)]
+    pub(crate) by_character: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct OccupancyArgs {
+    /// Only show slots that are claimed by more than one mod.
+    #[arg(long)]
+    pub(crate) conflicts_only: bool,
 }
 
 #[derive(Args, Debug)]
 pub(crate) struct StatusCliArgs {
     #[command(flatten)]
     pub(crate) mods: ModIdentifiersList,
+
+    /// Show additional details for each mod variant (eg. when it was last
+    /// enabled/disabled).
+    #[arg(short = 'v', long)]
+    pub(crate) verbose: bool,
+
+    /// Also list the mod variants that were enabled within this many days.
+    #[arg(short = 'r', long)]
+    pub(crate) recently_enabled_days: Option<u32>,
+
+    /// Also list every slot override applied to an installed mod variant.
+    #[arg(long)]
+    pub(crate) overrides: bool,
+
+    /// The order to list installed mods in (one of `name`, `id`, `size`,
+    /// `variant-count` or `enabled`). Ties are listed by name.
+    #[arg(short = 's', long, default_value = "name")]
+    pub(crate) sort: StatusSortKey,
+
+    /// Only show mods with this tag.
+    #[arg(short = 't', long)]
+    pub(crate) tag: Option<String>,
+
+    /// Only show favorite mods (same as `--tag favorite`).
+    #[arg(long, conflicts_with = "tag")]
+    pub(crate) favorites: bool,
+
+    /// Only show the mods and variants that match every filter in this comma
+    /// separated list: `name=<part of the name>`, `char=<character>` (eg.
+    /// `char=mario`), `enabled`, `disabled`, `conflicts`, `overrides`,
+    /// `tag=<tag>` and `after=<date the variant was installed after>` (eg.
+    /// `after=2024-05-01`).
+    #[arg(long)]
+    pub(crate) filter: Option<ModQuery>,
+
+    /// How to print the status. Defaults to `format` in the user config, or
+    /// `table` if that is not set.
+    #[arg(short = 'f', long, value_enum)]
+    pub(crate) format: Option<OutputFormat>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ChangeSlotArgs {
+    /// The mod variant to move to a different slot.
+    pub(crate) ident: VariantAndIdentifier,
+
+    /// The internal name of the character (eg. `mario`).
+    pub(crate) character: String,
+
+    /// The skin slot used by the variant that should be moved (eg. `3` for
+    /// `c03`).
+    pub(crate) slot: u8,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SwapActiveArgs {
+    /// The internal name of the character (eg. `mario`).
+    pub(crate) character: String,
+
+    /// The skin slot to swap the active variant of (eg. `3` for `c03`).
+    pub(crate) slot: u8,
+
+    /// The alternative to make active.
+    pub(crate) ident: VariantAndIdentifier,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct NoteArgs {
+    /// The mod variant to attach the note to.
+    pub(crate) r#mod: VariantAndIdentifier,
+
+    /// The text of the note. Replaces any existing note.
+    #[arg(conflicts_with_all = ["clear", "edit"])]
+    pub(crate) text: Option<String>,
+
+    /// Remove the note from the variant.
+    #[arg(long, conflicts_with = "edit")]
+    pub(crate) clear: bool,
+
+    /// Write the note in `$EDITOR`.
+    #[arg(short = 'e', long)]
+    pub(crate) edit: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct RenameArgs {
+    /// The mod variant to give the alias to.
+    pub(crate) r#mod: VariantAndIdentifier,
+
+    /// The new alias. Must not be the alias or file name of another variant
+    /// of the same mod.
+    #[arg(required_unless_present = "clear", conflicts_with = "clear")]
+    pub(crate) alias: Option<String>,
+
+    /// Remove the alias from the variant.
+    #[arg(long)]
+    pub(crate) clear: bool,
 }
 
 #[derive(Args, Debug)]
 pub(crate) struct AddArgs {
     #[command(flatten)]
     pub(crate) mods: ModIdentifiersList,
+
+    /// Also add the mods listed in this file (one mod per line, in the same
+    /// form as on the command line). Blank lines and lines starting with `#`
+    /// are ignored.
+    #[arg(long)]
+    pub(crate) from_file: Option<Utf8PathBuf>,
+
+    /// Give up on adding a mod if it takes longer than this many seconds. The
+    /// remaining mods are still added.
+    #[arg(long)]
+    pub(crate) operation_timeout: Option<u64>,
+
+    /// Only add a variant if a file on the mod page has exactly its name
+    /// (including case) instead of falling back to fuzzy matching. Never asks
+    /// which file to pick, so this is meant for scripts.
+    #[arg(long)]
+    pub(crate) exact: bool,
+
+    /// Install downloads even if their MD5 checksum does not match the one
+    /// listed on GameBanana. Only use this for sources that you trust!
+    /// Requires `--trust-source`.
+    #[arg(long, requires = "trust_source")]
+    pub(crate) skip_checksum: bool,
+
+    /// Acknowledge that skipping checksum verification may install tampered
+    /// or corrupted files.
+    #[arg(long, requires = "skip_checksum")]
+    pub(crate) trust_source: bool,
+
+    /// Resolve every conflict with an installed mod by replacing it with the
+    /// added mod instead of asking.
+    #[arg(long, conflicts_with = "keep_existing")]
+    pub(crate) replace_existing: bool,
+
+    /// Resolve every conflict with an installed mod by keeping it (and leaving
+    /// the added mod disabled) instead of asking. Without this,
+    /// `--replace-existing` or `--as-alternative`, the `add.conflict_policy`
+    /// setting decides.
+    #[arg(long, conflicts_with = "as_alternative")]
+    pub(crate) keep_existing: bool,
+
+    /// Like `--keep-existing`, but added character skins that conflict are
+    /// kept as inactive alternatives for the slot (see `swap-active`).
+    #[arg(long, conflicts_with = "replace_existing")]
+    pub(crate) as_alternative: bool,
+
+    /// Only install the files that change these kinds of assets (any of
+    /// `skins`, `stages`, `music` or `global`, separated by commas). Useful for
+    /// mods that bundle a stage with a skin when you only want the skin.
+    #[arg(long, value_delimiter = ',')]
+    pub(crate) asset_types: Option<Vec<AssetType>>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SyncTargetArgs {
+    /// Name of the sync target to use. Uses the default target if not
+    /// specified.
+    #[arg(short = 't', long)]
+    pub(crate) target: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SyncArgs {
+    #[command(flatten)]
+    pub(crate) target: SyncTargetArgs,
+
+    /// The directory structure to use on the target:
+    /// - arcropolis: The target path is `sd:/ultimate/mods`.
+    /// - flat: All mod files are written directly into the target path.
+    /// - ultimate-root: The target path is the root of the SD card.
+    #[arg(short = 'l', long, default_value = "arcropolis", verbatim_doc_comment)]
+    pub(crate) dest_layout: DestLayout,
+
+    /// Only print the files that would be copied and deleted without touching
+    /// the target.
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SwitchCompareArgs {
+    #[command(flatten)]
+    pub(crate) target: SyncTargetArgs,
+
+    /// Copy any enabled mods that are missing from the target onto it without
+    /// touching the other mods on the target.
+    #[arg(long)]
+    pub(crate) install_missing: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum TagCommand {
+    /// Add tags to a mod.
+    Add {
+        /// The name or ID of the installed mod.
+        r#mod: ModIdentifier,
+
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// Remove tags from a mod.
+    Remove {
+        /// The name or ID of the installed mod.
+        r#mod: ModIdentifier,
+
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// List the tags of a mod.
+    List {
+        /// The name or ID of the installed mod.
+        r#mod: ModIdentifier,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Print the value of a setting.
+    Get {
+        /// The setting (eg. `update.auto`).
+        key: ConfigKey,
+    },
+
+    /// Change a setting in the config file.
+    Set {
+        /// The setting (eg. `update.auto`).
+        key: ConfigKey,
+        value: String,
+    },
+
+    /// List every setting along with where its value comes from.
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum TargetCommand {
+    /// Add a new sync target.
+    Add(TargetAddArgs),
+
+    /// List all sync targets.
+    List,
+
+    /// Remove a sync target.
+    Remove {
+        /// Name of the sync target to remove.
+        name: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct TargetAddArgs {
+    /// Name used to refer to the target.
+    pub(crate) name: String,
+
+    /// The type of target ("local", "ftp" or "emulator").
+    #[arg(short = 'k', long = "type")]
+    pub(crate) target_type: SyncTargetType,
+
+    /// Path to the mods directory on the target.
+    #[arg(long)]
+    pub(crate) path: Utf8PathBuf,
+
+    /// Options specific to the type of target in the form of `key=value` (eg.
+    /// `address=192.168.1.20:5000` for FTP targets). Any target can also set
+    /// `max_concurrent_copies` (defaults to `4` for local targets, `2` for FTP
+    /// targets and `16` for emulator targets).
+    #[arg(short = 'o', long = "option", value_parser = parse_key_val)]
+    pub(crate) options: Vec<(String, String)>,
+
+    /// Glob patterns of files that should never be synced to this target.
+    #[arg(short = 'i', long)]
+    pub(crate) ignore: Vec<String>,
+
+    /// Make this the default target.
+    #[arg(short = 'd', long)]
+    pub(crate) default: bool,
+}
+
+impl TargetAddArgs {
+    pub(crate) fn options_map(&self) -> BTreeMap<String, String> {
+        self.options.iter().cloned().collect()
+    }
+}
+
+fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("Expected an option in the form of `key=value` but got \"{s}\""))
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CheckForUpdatesArgs {
+    /// Download any available updates without prompting.
+    #[arg(short = 'a', long)]
+    pub(crate) auto_update: bool,
+
+    /// IDs of mods to skip when auto-updating.
+    #[arg(short = 'x', long)]
+    pub(crate) exclude: Vec<ModId>,
+
+    /// Also check mods that were already checked recently (see
+    /// `recheck_interval_hours` in the config).
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// How to print the summary of the check. Defaults to `format` in the user
+    /// config, or `table` if that is not set.
+    #[arg(short = 'f', long, value_enum)]
+    pub(crate) format: Option<OutputFormat>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct UpdateArgs {
+    /// List of mods to update.
+    #[command(flatten)]
+    pub(crate) mods: ModIdentifiersList,
+
+    /// Update every mod that has an update available.
+    #[arg(
+        short = 'a',
+        long,
+        conflicts_with = "mods",
+        required_unless_present_any = ["mods", "auto"]
+    )]
+    pub(crate) all: bool,
+
+    /// Apply updates without asking about each mod. If no mods are given,
+    /// every mod with an update available is updated. Updates that introduce
+    /// new conflicts are left disabled for `resolve-conflicts`.
+    #[arg(long)]
+    pub(crate) auto: bool,
+
+    /// Also use `--auto` by default for future updates (or stop doing so with
+    /// `--save-auto-default=false`).
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) save_auto_default: Option<bool>,
+
+    /// Only print what would be updated.
+    #[arg(short = 'd', long)]
+    pub(crate) dry_run: bool,
 }
 
 #[derive(Args, Debug)]
@@ -81,6 +641,10 @@ pub(crate) struct EnableDisableArgs {
     /// Enable or disable all of the given mods.
     #[arg(short = 'e', long, default_value_t = true)]
     pub(crate) enable: bool,
+
+    /// Enable or disable every mod with this tag instead.
+    #[arg(short = 't', long, conflicts_with = "mods")]
+    pub(crate) tag: Option<String>,
 }
 
 /// Struct is purely just to wrap the Clippy docs in order to avoid duplicating
@@ -92,11 +656,17 @@ pub(crate) struct ModIdentifiersList {
     /// Each mod can be specified with either:
     /// - The ID on GameBanana
     /// - The name of the mod on GameBanana.
+    ///
+    /// followed by `/` (or `:`) and the variant (eg. `9001/variant.zip` or
+    /// `9001:variant.zip`). Whichever of the two comes first separates the mod
+    /// from the variant, so use the ID for mods with either in their name.
+    /// Prefix the mod with `id:` or `name:` to force how it is read (eg.
+    /// `name:1999/a.zip` for a mod that is named `1999`).
     #[clap(verbatim_doc_comment)]
     pub(crate) mods: Vec<VariantAndIdentifier>,
 }
 
-fn get_os_default_state_dir_path() -> Utf8PathBuf {
+pub(crate) fn get_os_default_state_dir_path() -> Utf8PathBuf {
     // TODO: Unwrap for now. Not sure how to handle `Result`s in default Clap
     // args...
     let res = match dirs::cache_dir() {
@@ -0,0 +1,70 @@
+//! Per-user defaults for the CLI. These live in
+//! `ultimate-mod-man/config.toml` inside the OS config directory (eg.
+//! `~/.config` on Linux) and look like this:
+//!
+//! ```toml
+//! state_dir = "/mnt/storage/ssbu-mods"
+//! format = "json"
+//! color = "never"
+//! max_concurrent_downloads = 4
+//! ```
+//!
+//! Every setting is optional. Anything passed on the command line takes
+//! precedence over the file, which in turn takes precedence over the built-in
+//! defaults.
+//!
+//! Not to be confused with the `config.toml` inside the state directory, which
+//! holds the settings of the mod manager itself (see `config list`).
+
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use ultimate_mod_man_rs_core::style::ColorMode;
+use ultimate_mod_man_rs_utils::utils::deserialize_data_from_path;
+
+use crate::prog_args::OutputFormat;
+
+const USER_CONFIG_DIR_NAME: &str = "ultimate-mod-man";
+const USER_CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct UserConfig {
+    /// Used when `-p` is not passed.
+    pub(crate) state_dir: Option<Utf8PathBuf>,
+
+    /// Used by commands that take `--format` when it is not passed.
+    pub(crate) format: Option<OutputFormat>,
+
+    /// Used when `--color` is not passed.
+    pub(crate) color: Option<ColorMode>,
+
+    /// Same as `download.max_concurrent`, but for every state directory. A
+    /// `-c` override still wins.
+    pub(crate) max_concurrent_downloads: Option<usize>,
+}
+
+impl UserConfig {
+    /// Reads the user config if there is one.
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let Some(path) = user_config_path() else {
+            return Ok(Self::default());
+        };
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        deserialize_data_from_path(&path)
+            .with_context(|| format!("Unable to read the user config \"{}\"", path))
+    }
+}
+
+fn user_config_path() -> Option<Utf8PathBuf> {
+    let config_dir = Utf8PathBuf::from_path_buf(dirs::config_dir()?).ok()?;
+    Some(
+        config_dir
+            .join(USER_CONFIG_DIR_NAME)
+            .join(USER_CONFIG_FILE_NAME),
+    )
+}
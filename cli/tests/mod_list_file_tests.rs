@@ -0,0 +1,48 @@
+//! Reads the mod list files that can be passed to `add --from-file`.
+
+use camino::Utf8PathBuf;
+use ultimate_mod_man_rs_core::mod_manager::{ModManagerErr, read_mod_list_file};
+use ultimate_mod_man_rs_utils::types::VariantAndIdentifier;
+
+fn fixture_path(name: &str) -> Utf8PathBuf {
+    Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn m_ident(s: &str) -> VariantAndIdentifier {
+    s.parse().unwrap()
+}
+
+#[test]
+fn mod_list_skips_blank_lines_and_comments() {
+    let mods = read_mod_list_file(&fixture_path("mod_list.txt")).unwrap();
+
+    assert_eq!(
+        mods,
+        vec![
+            m_ident("9001/super_cool_variant.zip"),
+            m_ident("Some Mod Name/variant_2.rar"),
+            m_ident("42/padded.7z"),
+        ]
+    );
+}
+
+#[test]
+fn malformed_line_is_reported_with_its_line_number() {
+    match read_mod_list_file(&fixture_path("malformed_mod_list.txt")) {
+        Err(ModManagerErr::ModListParseError(line_number, raw_line, _)) => {
+            assert_eq!(line_number, 3);
+            assert_eq!(raw_line, "42");
+        },
+        res => panic!("Expected a parse error but got {:?}", res),
+    }
+}
+
+#[test]
+fn missing_mod_list_is_an_error() {
+    assert!(matches!(
+        read_mod_list_file(&fixture_path("does_not_exist.txt")),
+        Err(ModManagerErr::ModListReadError(..))
+    ));
+}
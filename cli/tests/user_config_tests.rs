@@ -0,0 +1,81 @@
+//! Checks that the user config is picked up by the CLI and that flags take
+//! precedence over it.
+
+use std::{fs, process::Command};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tempfile::TempDir;
+
+/// A home directory with a user config that points at its own state
+/// directory.
+struct FixtureHome {
+    dir: TempDir,
+}
+
+impl FixtureHome {
+    fn new() -> Self {
+        let home = Self {
+            dir: TempDir::new().unwrap(),
+        };
+
+        let user_config_dir = home.config_dir().join("ultimate-mod-man");
+        fs::create_dir_all(&user_config_dir).unwrap();
+        fs::create_dir_all(home.configured_state_dir()).unwrap();
+        fs::write(
+            user_config_dir.join("config.toml"),
+            format!("state_dir = {:?}\n", home.configured_state_dir()),
+        )
+        .unwrap();
+
+        home
+    }
+
+    fn path(&self) -> &Utf8Path {
+        Utf8Path::from_path(self.dir.path()).unwrap()
+    }
+
+    fn config_dir(&self) -> Utf8PathBuf {
+        self.path().join(".config")
+    }
+
+    fn configured_state_dir(&self) -> Utf8PathBuf {
+        self.path().join("configured_state")
+    }
+
+    /// Runs `config set download.max_concurrent 5` with `extra_args` before
+    /// the command. Setting a config value is an easy way to see which state
+    /// directory was used, since it writes `config.toml` to it.
+    fn run_config_set(&self, extra_args: &[&str]) {
+        let status = Command::new(env!("CARGO_BIN_EXE_ultimate-mod-man-rs-cli"))
+            .env("HOME", self.path())
+            .env("XDG_CONFIG_HOME", self.config_dir())
+            .env_remove("UMM_STATE_DIR")
+            .args(extra_args)
+            .args(["config", "set", "download.max_concurrent", "5"])
+            .status()
+            .unwrap();
+
+        assert!(status.success());
+    }
+}
+
+#[test]
+fn state_dir_from_the_user_config_is_used_when_no_flag_is_given() {
+    let home = FixtureHome::new();
+
+    home.run_config_set(&[]);
+
+    assert!(home.configured_state_dir().join("config.toml").exists());
+}
+
+#[test]
+fn state_dir_flag_overrides_the_user_config() {
+    let home = FixtureHome::new();
+    let flag_state_dir = home.path().join("flag_state");
+    fs::create_dir_all(&flag_state_dir).unwrap();
+
+    home.run_config_set(&["-p", flag_state_dir.as_str()]);
+
+    assert!(flag_state_dir.join("config.toml").exists());
+    assert!(!home.configured_state_dir().join("config.toml").exists());
+}
@@ -0,0 +1,29 @@
+use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, ModSearchResult};
+
+use crate::mod_manager::ModManagerResult;
+
+/// How many pages of search results are fetched when browsing without asking
+/// for a specific page.
+const MAX_BROWSE_SEARCH_PAGES: usize = 3;
+
+/// Searches for mods matching `query`, or lists the top mods if it is empty.
+/// `page` only fetches that one page of the search results.
+pub(crate) async fn cmd_search_mods(
+    query: &str,
+    page: Option<usize>,
+    scraper: &BananaClient,
+) -> ModManagerResult<Vec<ModSearchResult>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(scraper.list_top_mods().await?);
+    }
+
+    let (first_page, max_pages) = match page {
+        Some(page) => (page.max(1), 1),
+        None => (1, MAX_BROWSE_SEARCH_PAGES),
+    };
+
+    Ok(scraper
+        .search_all_pages(query, first_page, max_pages)
+        .await?)
+}
@@ -0,0 +1,118 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+use ultimate_mod_man_rs_utils::types::{ModIdentifier, VariantAndId};
+
+use super::{fmt_size, write_table};
+use crate::{
+    mod_db::{DuplicateReason, ModDbView},
+    mod_manager::ModManagerResult,
+};
+
+/// Installed variants that look like copies of the same upload.
+#[derive(Debug, Serialize)]
+pub struct DedupeReport {
+    pub groups: Vec<DedupeGroup>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DedupeGroup {
+    pub reason: DuplicateReason,
+    pub copies: Vec<DuplicateCopy>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCopy {
+    pub key: VariantAndId,
+    pub mod_name: String,
+
+    /// Where the copy was installed from (if known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+
+    pub enabled: bool,
+
+    /// `None` if the size was not recorded when the copy was installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+}
+
+impl Display for DedupeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.groups.is_empty() {
+            return writeln!(f, "No duplicate installs found.");
+        }
+
+        for (i, group) in self.groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            match &group.reason {
+                DuplicateReason::SameArchive { md5 } => {
+                    writeln!(f, "Installed from the same archive (MD5 {}):", md5)?
+                },
+                DuplicateReason::SimilarFiles { similarity } => {
+                    writeln!(f, "{:.0}% of the files are identical:", similarity * 100.0)?
+                },
+            }
+
+            write_table(
+                f,
+                &["Mod", "ID", "Variant", "Enabled", "Size", "Source"],
+                group.copies.iter().map(|copy| {
+                    vec![
+                        copy.mod_name.clone(),
+                        copy.key.id.to_string(),
+                        copy.key.variant_name.clone(),
+                        copy.enabled.to_string(),
+                        copy.size_bytes.map(fmt_size).unwrap_or_default(),
+                        copy.source_url.clone().unwrap_or_default(),
+                    ]
+                }),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for DuplicateCopy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.key, self.mod_name)?;
+
+        if let Some(source_url) = &self.source_url {
+            write!(f, " from {}", source_url)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn cmd_dedupe(db: &ModDbView) -> ModManagerResult<DedupeReport> {
+    let groups = db
+        .find_duplicate_variants()?
+        .into_iter()
+        .map(|group| DedupeGroup {
+            reason: group.reason,
+            copies: group
+                .variants
+                .into_iter()
+                .filter_map(|key| {
+                    let mod_info = db.find_installed_mod(&ModIdentifier::Id(key.id))?;
+                    let var_info = mod_info.installed_variants.get(&key.variant_name)?;
+
+                    Some(DuplicateCopy {
+                        mod_name: mod_info.name.clone(),
+                        source_url: mod_info.source_url.clone(),
+                        enabled: var_info.is_enabled(),
+                        size_bytes: var_info.file_size_bytes,
+                        key,
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(DedupeReport { groups })
+}
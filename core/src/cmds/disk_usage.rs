@@ -0,0 +1,109 @@
+use std::{
+    cmp::Reverse,
+    fmt::{self, Display, Formatter},
+};
+
+use ultimate_mod_man_rs_utils::types::ModId;
+
+use super::{fmt_size, write_table};
+use crate::mod_db::ModDbView;
+
+/// How much disk space each installed mod uses.
+#[derive(Debug)]
+pub struct DiskUsageReport {
+    /// Sorted from largest to smallest.
+    pub mods: Vec<ModDiskUsage>,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct ModDiskUsage {
+    pub id: ModId,
+    pub name: String,
+    pub size_bytes: u64,
+}
+
+/// How much disk space the enabled skins of each character use.
+#[derive(Debug)]
+pub struct CharacterDiskUsageReport {
+    /// Sorted from largest to smallest.
+    pub characters: Vec<CharacterDiskUsage>,
+}
+
+#[derive(Debug)]
+pub struct CharacterDiskUsage {
+    pub char_key: String,
+    pub size_bytes: u64,
+}
+
+impl Display for DiskUsageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.mods.is_empty() {
+            return writeln!(f, "No mods installed.");
+        }
+
+        write_table(
+            f,
+            &["Mod", "ID", "Size"],
+            self.mods.iter().map(|mod_usage| {
+                vec![
+                    mod_usage.name.clone(),
+                    mod_usage.id.to_string(),
+                    fmt_size(mod_usage.size_bytes),
+                ]
+            }),
+        )?;
+
+        writeln!(f, "Total: {}", fmt_size(self.total_bytes))
+    }
+}
+
+impl Display for CharacterDiskUsageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.characters.is_empty() {
+            return writeln!(f, "No character skins enabled.");
+        }
+
+        write_table(
+            f,
+            &["Character", "Size"],
+            self.characters.iter().map(|char_usage| {
+                vec![char_usage.char_key.clone(), fmt_size(char_usage.size_bytes)]
+            }),
+        )
+    }
+}
+
+pub(crate) fn cmd_disk_usage(db: &ModDbView) -> DiskUsageReport {
+    let mut mods = db
+        .installed_mods()
+        .map(|mod_info| ModDiskUsage {
+            id: mod_info.id,
+            name: mod_info.name.clone(),
+            size_bytes: db.estimate_disk_usage_for_mod(mod_info.id),
+        })
+        .collect::<Vec<_>>();
+
+    mods.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then(a.id.cmp(&b.id)));
+
+    DiskUsageReport {
+        total_bytes: mods.iter().map(|mod_usage| mod_usage.size_bytes).sum(),
+        mods,
+    }
+}
+
+pub(crate) fn cmd_disk_usage_by_character(db: &ModDbView) -> CharacterDiskUsageReport {
+    let mut characters = db
+        .usage_by_character()
+        .into_iter()
+        .map(|(char_key, size_bytes)| CharacterDiskUsage {
+            char_key,
+            size_bytes,
+        })
+        .collect::<Vec<_>>();
+
+    // Ties stay sorted by character since the usage is keyed by it.
+    characters.sort_by_key(|char_usage| Reverse(char_usage.size_bytes));
+
+    CharacterDiskUsageReport { characters }
+}
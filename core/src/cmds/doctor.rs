@@ -0,0 +1,564 @@
+//! Diagnoses the state directory and the environment that the mod manager runs
+//! in. Nothing is changed while diagnosing, and the state directory is never
+//! loaded the normal way, so this still works when the manager itself can not
+//! be opened. Problems that are safe to fix automatically come with a
+//! `DoctorFix` that can be applied afterwards.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io,
+    time::Duration,
+};
+
+use camino::Utf8Path;
+use log::warn;
+use serde::Serialize;
+use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaScraperResult};
+
+use crate::{
+    cmds::fmt_size,
+    config::{CONFIG_FILE_NAME, Config},
+    in_prog_action::InProgAction,
+    mod_db::{
+        DB_LOCKFILE_NAME, DBLockFile, IN_PROG_ACTION_FILE_NAME, InstalledModProblem, LockState,
+        ModDbResult, find_installed_mod_problems, forget_missing_variants, is_process_running,
+    },
+    mod_manager::ModManagerOptions,
+    mod_name_resolver::{BananaModNameResolver, CACHED_MOD_NAME_RESOLUTION_STATE_NAME},
+    schema::SchemaError,
+    state_dir::{STATE_DIR_MARKER_FILE_NAME, StateDirError, ensure_state_dir},
+    sync_targets::{SYNC_TARGETS_CONFIG_FILE_NAME, SyncTargets},
+};
+
+/// Less free space than this on the disk of the state directory is warned
+/// about.
+const MIN_FREE_DISK_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// How long we wait on GameBanana before calling it unreachable.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fixes that change the state directory give up if another instance does not
+/// release it within this long.
+const FIX_LOCK_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Written (and removed again) to check that the state directory is writable.
+const WRITE_TEST_FILE_NAME: &str = ".umm_doctor";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+
+    /// Something that may cause problems but does not stop the manager from
+    /// working.
+    Warn,
+
+    Fail,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// A fix that is safe to apply without the user having to look at anything
+/// first.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorFix {
+    /// Deletes a lock file left behind by the instance with the PID, which
+    /// exited without releasing it.
+    ClearStaleLock { pid: u32 },
+
+    /// Deletes the name resolution cache. It only saves looking up names again,
+    /// so nothing is lost.
+    ResetNameCache,
+
+    /// Removes the variants whose data is gone from their `mod_info.toml` so
+    /// that the rest of their mod is loaded again.
+    ForgetMissingVariants,
+}
+
+impl Display for DoctorFix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DoctorFix::ClearStaleLock { pid } => {
+                write!(f, "Delete the lock file left behind by PID {}", pid)
+            },
+            DoctorFix::ResetNameCache => write!(f, "Reset the name resolution cache"),
+            DoctorFix::ForgetMissingVariants => {
+                write!(f, "Forget the mod variants that have no data")
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+
+    /// What was found.
+    pub detail: String,
+
+    /// What the user can do about it (only for checks that did not pass).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<DoctorFix>,
+}
+
+impl DoctorCheck {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, detail.into(), None)
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, detail.into(), Some(hint.into()))
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, detail.into(), Some(hint.into()))
+    }
+
+    fn new(name: &'static str, status: CheckStatus, detail: String, hint: Option<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail,
+            hint,
+            fix: None,
+        }
+    }
+
+    fn with_fix(self, fix: DoctorFix) -> Self {
+        Self {
+            fix: Some(fix),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn has_failures(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.status == CheckStatus::Fail)
+    }
+
+    /// Every fix that is offered (each at most once).
+    pub fn fixes(&self) -> impl Iterator<Item = DoctorFix> + '_ {
+        self.checks.iter().filter_map(|check| check.fix)
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for check in self.checks.iter() {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.detail)?;
+
+            if let Some(hint) = &check.hint {
+                writeln!(f, "       {}", hint)?;
+            }
+
+            if let Some(fix) = &check.fix {
+                writeln!(f, "       Fix available: {}", fix)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `scraper` is only `None` if it should be built from the config of the state
+/// directory.
+pub(crate) async fn cmd_doctor(
+    p: &Utf8Path,
+    options: &ModManagerOptions,
+    scraper: Option<BananaClient>,
+) -> DoctorReport {
+    let mut checks = vec![check_state_dir_writable(p)];
+    let mut config = None;
+
+    if p.exists() {
+        let (lock_check, lock_state) = check_lock(p);
+        checks.push(lock_check);
+        checks.push(check_marker(p, options.init_here));
+        checks.push(check_journal(p, lock_state));
+
+        let config_check;
+        (config_check, config) = check_config(p, options);
+        checks.push(config_check);
+
+        checks.push(check_name_cache(p));
+        checks.extend(check_installed_mods(p));
+        checks.extend(check_default_sync_target(p));
+    }
+
+    let scraper = match scraper {
+        Some(scraper) => Ok(scraper),
+        None => BananaClient::new_with_settings(
+            config
+                .map(|config| config.banana_client_settings())
+                .unwrap_or_default(),
+        ),
+    };
+    checks.push(check_gamebanana(scraper).await);
+    checks.push(check_disk_space(p));
+
+    DoctorReport { checks }
+}
+
+/// Applies a fix offered by `cmd_doctor`. Fixes that change the state
+/// directory lock it while doing so.
+pub(crate) async fn cmd_apply_doctor_fix(p: &Utf8Path, fix: DoctorFix) -> ModDbResult<()> {
+    match fix {
+        DoctorFix::ClearStaleLock { pid } => {
+            // The PID may have been reused since the lock was checked, and another
+            // instance may have taken over the lock in the meantime.
+            let lock_path = p.join(DB_LOCKFILE_NAME);
+            let removed = !is_process_running(pid) && DBLockFile::remove_stale(&lock_path, pid);
+            if !removed && lock_path.exists() {
+                warn!(
+                    "Left the lock file at \"{}\" alone since it is not a stale lock of PID {}.",
+                    lock_path, pid
+                );
+            }
+        },
+        DoctorFix::ResetNameCache => {
+            let _lock = DBLockFile::new(p, FIX_LOCK_WAIT_TIMEOUT, |_| ()).await?;
+            remove_file_if_exists(&p.join(CACHED_MOD_NAME_RESOLUTION_STATE_NAME))?;
+        },
+        DoctorFix::ForgetMissingVariants => {
+            let _lock = DBLockFile::new(p, FIX_LOCK_WAIT_TIMEOUT, |_| ()).await?;
+            let (problems, _) = find_installed_mod_problems(p)?;
+
+            for problem in problems {
+                if let InstalledModProblem::MissingVariantDirs { mod_dir, .. } = problem {
+                    forget_missing_variants(&mod_dir)?;
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn remove_file_if_exists(p: &Utf8Path) -> io::Result<()> {
+    match fs::remove_file(p) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+fn check_state_dir_writable(p: &Utf8Path) -> DoctorCheck {
+    const NAME: &str = "state directory";
+
+    if !p.exists() {
+        return DoctorCheck::pass(
+            NAME,
+            format!(
+                "\"{}\" does not exist yet (it is created when first used)",
+                p
+            ),
+        );
+    }
+
+    let test_file_path = p.join(WRITE_TEST_FILE_NAME);
+    match fs::write(&test_file_path, b"").and_then(|_| fs::remove_file(&test_file_path)) {
+        Ok(()) => DoctorCheck::pass(NAME, format!("\"{}\" is writable", p)),
+        Err(err) => DoctorCheck::fail(
+            NAME,
+            format!("\"{}\" is not writable: {}", p, err),
+            "Check the permissions of the directory or use another one with `-p`.",
+        ),
+    }
+}
+
+fn check_lock(p: &Utf8Path) -> (DoctorCheck, Option<LockState>) {
+    const NAME: &str = "lock";
+
+    match DBLockFile::probe(p) {
+        Ok(LockState::Free) => (
+            DoctorCheck::pass(NAME, "Not held by any instance"),
+            Some(LockState::Free),
+        ),
+        Ok(LockState::Held(holder_pid)) => {
+            let check = match holder_pid {
+                Some(pid) if !is_process_running(pid) => DoctorCheck::warn(
+                    NAME,
+                    format!("Held by PID {}, which is no longer running", pid),
+                    "The lock was left behind by an instance that crashed and can be deleted.",
+                )
+                .with_fix(DoctorFix::ClearStaleLock { pid }),
+                Some(pid) => DoctorCheck::warn(
+                    NAME,
+                    format!("Held by PID {}", pid),
+                    "Another instance is using the state directory. Wait for it to finish.",
+                ),
+                None => DoctorCheck::warn(
+                    NAME,
+                    "Held by an unknown process",
+                    "If no other instance is running, the lock was left behind by an instance \
+                     that crashed and can be deleted by hand.",
+                ),
+            };
+
+            (check, Some(LockState::Held(holder_pid)))
+        },
+        Err(err) => (
+            DoctorCheck::fail(
+                NAME,
+                format!("Unable to acquire: {}", err),
+                "Check the permissions of the state directory.",
+            ),
+            None,
+        ),
+    }
+}
+
+fn check_marker(p: &Utf8Path, init_here: bool) -> DoctorCheck {
+    const NAME: &str = "layout version";
+
+    match ensure_state_dir(p, init_here, true) {
+        Ok(()) if p.join(STATE_DIR_MARKER_FILE_NAME).exists() => {
+            DoctorCheck::pass(NAME, "Supported by this version")
+        },
+        Ok(()) => DoctorCheck::pass(
+            NAME,
+            "Not marked yet (the marker is added the next time that the state directory is used)",
+        ),
+        Err(err @ StateDirError::NotAStateDir(_)) => DoctorCheck::fail(
+            NAME,
+            err.to_string(),
+            "Use an empty directory instead, or pass `--init-here` to use this one anyway.",
+        ),
+        Err(err @ StateDirError::LayoutTooNew { .. }) => {
+            DoctorCheck::fail(NAME, err.to_string(), "Update the mod manager.")
+        },
+        Err(err) => DoctorCheck::fail(
+            NAME,
+            format!("Unable to read \"{}\": {}", STATE_DIR_MARKER_FILE_NAME, err),
+            format!(
+                "Delete \"{}\" so that it is written again.",
+                STATE_DIR_MARKER_FILE_NAME
+            ),
+        ),
+    }
+}
+
+/// A journaled action is only orphaned if nobody holds the lock (otherwise it
+/// belongs to the instance that does).
+fn check_journal(p: &Utf8Path, lock_state: Option<LockState>) -> DoctorCheck {
+    const NAME: &str = "journal";
+
+    match InProgAction::load_from_disk_if_present(&p.join(IN_PROG_ACTION_FILE_NAME)) {
+        Ok(None) => DoctorCheck::pass(NAME, "No interrupted actions"),
+        Ok(Some(action)) if matches!(lock_state, Some(LockState::Held(_))) => DoctorCheck::pass(
+            NAME,
+            format!("In progress in another instance ({})", *action),
+        ),
+        Ok(Some(action)) => DoctorCheck::warn(
+            NAME,
+            format!("Found an interrupted action ({})", *action),
+            "It is rolled back by the next command that changes the installed mods (eg. \
+             `enable-disable`).",
+        ),
+        Err(err) => DoctorCheck::fail(
+            NAME,
+            format!("Unable to read \"{}\": {}", IN_PROG_ACTION_FILE_NAME, err),
+            format!(
+                "Check the mod mentioned in \"{}\" by hand and delete the file once it is in a \
+                 good state.",
+                IN_PROG_ACTION_FILE_NAME
+            ),
+        ),
+    }
+}
+
+fn check_config(p: &Utf8Path, options: &ModManagerOptions) -> (DoctorCheck, Option<Config>) {
+    const NAME: &str = "config";
+
+    match options.load_config(p) {
+        Ok(config) => (DoctorCheck::pass(NAME, "Valid"), Some(config)),
+        Err(err) => (
+            DoctorCheck::fail(
+                NAME,
+                err.to_string(),
+                format!(
+                    "Fix or delete \"{}\" (settings that are not set use their defaults).",
+                    CONFIG_FILE_NAME
+                ),
+            ),
+            None,
+        ),
+    }
+}
+
+fn check_name_cache(p: &Utf8Path) -> DoctorCheck {
+    const NAME: &str = "name cache";
+
+    match BananaModNameResolver::verify_cache_file(p) {
+        Ok(()) => DoctorCheck::pass(NAME, "Readable"),
+        Err(err) => DoctorCheck::fail(
+            NAME,
+            err.to_string(),
+            "The cache only saves looking up mod names again, so it is safe to reset.",
+        )
+        .with_fix(DoctorFix::ResetNameCache),
+    }
+}
+
+fn check_installed_mods(p: &Utf8Path) -> Vec<DoctorCheck> {
+    const MODS_NAME: &str = "installed mods";
+    const VARIANTS_NAME: &str = "variant directories";
+
+    let (problems, num_mods) = match find_installed_mod_problems(p) {
+        Ok(res) => res,
+        Err(err) => {
+            return vec![DoctorCheck::fail(
+                MODS_NAME,
+                format!("Unable to read the state directory: {}", err),
+                "Check the permissions of the state directory.",
+            )];
+        },
+    };
+
+    let mut unreadable = Vec::new();
+    let mut any_too_new = false;
+    let mut missing_variants = Vec::new();
+    for problem in problems {
+        match problem {
+            InstalledModProblem::UnreadableModInfo { mod_dir, err } => {
+                any_too_new |= matches!(err, SchemaError::TooNew { .. });
+                unreadable.push(format!("\"{}\" ({})", mod_dir, err));
+            },
+            InstalledModProblem::MissingVariantDirs {
+                mod_dir,
+                variant_names,
+            } => missing_variants.extend(
+                variant_names
+                    .into_iter()
+                    .map(|variant_name| format!("\"{}\"", mod_dir.join(variant_name))),
+            ),
+        }
+    }
+
+    let mods_check = match unreadable.is_empty() {
+        true => DoctorCheck::pass(MODS_NAME, format!("All {} mods can be read", num_mods)),
+        false => DoctorCheck::fail(
+            MODS_NAME,
+            format!(
+                "{} of {} mods can not be read: {}",
+                unreadable.len(),
+                num_mods,
+                unreadable.join(", ")
+            ),
+            match any_too_new {
+                false => "Delete the directories of these mods and add them again.",
+                true => "Update the mod manager.",
+            },
+        ),
+    };
+
+    let variants_check = match missing_variants.is_empty() {
+        true => DoctorCheck::pass(VARIANTS_NAME, "Every installed variant has its data"),
+        false => DoctorCheck::fail(
+            VARIANTS_NAME,
+            format!(
+                "{} variants have no data: {}",
+                missing_variants.len(),
+                missing_variants.join(", ")
+            ),
+            "Their mods are skipped until these variants are forgotten (or the mods are added \
+             again).",
+        )
+        .with_fix(DoctorFix::ForgetMissingVariants),
+    };
+
+    vec![mods_check, variants_check]
+}
+
+/// Only checks the default target, and only if it is on the local filesystem.
+fn check_default_sync_target(p: &Utf8Path) -> Option<DoctorCheck> {
+    const NAME: &str = "sync target";
+
+    let sync_targets = match SyncTargets::load_from_dir(p, false) {
+        Ok(sync_targets) => sync_targets,
+        Err(err) => {
+            return Some(DoctorCheck::fail(
+                NAME,
+                err.to_string(),
+                format!("Fix or delete \"{}\".", SYNC_TARGETS_CONFIG_FILE_NAME),
+            ));
+        },
+    };
+
+    let (name, target) = sync_targets.resolve(None).ok()?;
+    if !target.target_type.is_filesystem() {
+        return None;
+    }
+
+    Some(match target.path.is_dir() {
+        true => DoctorCheck::pass(NAME, format!("\"{}\" is reachable", name)),
+        false => DoctorCheck::warn(
+            NAME,
+            format!("\"{}\" is not reachable at \"{}\"", name, target.path),
+            "Insert or mount the SD card, or point the target somewhere else.",
+        ),
+    })
+}
+
+async fn check_gamebanana(scraper: BananaScraperResult<BananaClient>) -> DoctorCheck {
+    const NAME: &str = "GameBanana";
+    const HINT: &str = "Check the internet connection. If it works, GameBanana may be down \
+                        (`health-check` has more details).";
+
+    let scraper = match scraper {
+        Ok(scraper) => scraper,
+        Err(err) => return DoctorCheck::fail(NAME, err.to_string(), HINT),
+    };
+
+    match scraper.ping(PING_TIMEOUT).await {
+        Ok(latency) => DoctorCheck::pass(NAME, format!("Reachable ({}ms)", latency.as_millis())),
+        Err(err) => DoctorCheck::fail(NAME, format!("Unreachable: {}", err), HINT),
+    }
+}
+
+fn check_disk_space(p: &Utf8Path) -> DoctorCheck {
+    const NAME: &str = "disk space";
+
+    // The state directory may not exist yet.
+    let existing_path = p
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Utf8Path::new("."));
+
+    match fs4::available_space(existing_path) {
+        Ok(available) if available < MIN_FREE_DISK_SPACE_BYTES => DoctorCheck::warn(
+            NAME,
+            format!("Only {} free", fmt_size(available)),
+            format!(
+                "Downloads may fail. Free up some space so that at least {} is available.",
+                fmt_size(MIN_FREE_DISK_SPACE_BYTES)
+            ),
+        ),
+        Ok(available) => DoctorCheck::pass(NAME, format!("{} free", fmt_size(available))),
+        Err(err) => DoctorCheck::warn(
+            NAME,
+            format!("Unable to check: {}", err),
+            "Make sure that there is enough space for downloads.",
+        ),
+    }
+}
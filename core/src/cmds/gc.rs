@@ -0,0 +1,75 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Serialize;
+use ultimate_mod_man_rs_utils::types::{ModIdentifier, VariantAndId};
+
+use super::{fmt_size, write_table};
+use crate::{mod_db::ModDb, mod_manager::ModManagerResult};
+
+/// The variants whose files were removed by gc.
+#[derive(Debug, Serialize)]
+pub struct GcReport {
+    pub collected: Vec<CollectedVariant>,
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CollectedVariant {
+    pub key: VariantAndId,
+    pub mod_name: String,
+    pub reclaimed_bytes: u64,
+}
+
+impl Display for GcReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.collected.is_empty() {
+            return writeln!(f, "Nothing to clean up.");
+        }
+
+        write_table(
+            f,
+            &["Mod", "ID", "Variant", "Reclaimed"],
+            self.collected.iter().map(|collected| {
+                vec![
+                    collected.mod_name.clone(),
+                    collected.key.id.to_string(),
+                    collected.key.variant_name.clone(),
+                    fmt_size(collected.reclaimed_bytes),
+                ]
+            }),
+        )?;
+
+        writeln!(f, "Reclaimed: {}", fmt_size(self.reclaimed_bytes))
+    }
+}
+
+/// Removes the files of every variant that has been disabled since before
+/// `cutoff`.
+pub(crate) fn cmd_gc(db: &mut ModDb, cutoff: DateTime<Utc>) -> ModManagerResult<GcReport> {
+    let mut collected = Vec::new();
+    for key in db.variants_disabled_before(cutoff) {
+        info!("Removing the files of {}...", key);
+
+        let mod_name = db
+            .find_installed_mod(&ModIdentifier::Id(key.id))
+            .map(|mod_info| mod_info.name.clone())
+            .unwrap_or_default();
+        let reclaimed_bytes = db.collect_variant_data(&key)?;
+
+        collected.push(CollectedVariant {
+            key,
+            mod_name,
+            reclaimed_bytes,
+        });
+    }
+
+    Ok(GcReport {
+        reclaimed_bytes: collected
+            .iter()
+            .map(|collected| collected.reclaimed_bytes)
+            .sum(),
+        collected,
+    })
+}
@@ -0,0 +1,46 @@
+use std::fmt::{self, Display, Formatter};
+
+use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, ModFileInfo};
+use ultimate_mod_man_rs_utils::types::ModId;
+
+use super::{fmt_size, write_table};
+use crate::mod_manager::ModManagerResult;
+
+/// Every variant that can be downloaded for a mod.
+#[derive(Debug)]
+pub struct ModVariantsListing {
+    pub id: ModId,
+    pub files: Vec<ModFileInfo>,
+}
+
+impl Display for ModVariantsListing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.files.is_empty() {
+            return writeln!(f, "The mod {} has no files available.", self.id);
+        }
+
+        writeln!(f, "Variants of the mod {} ({}):", self.id, self.files.len())?;
+        write_table(
+            f,
+            &["Variant", "Size", "Added", "Description"],
+            self.files.iter().map(|file| {
+                vec![
+                    file.name.clone(),
+                    fmt_size(file.size_bytes),
+                    file.date_added.format("%Y-%m-%d").to_string(),
+                    file.description.clone().unwrap_or_else(|| "-".to_string()),
+                ]
+            }),
+        )
+    }
+}
+
+pub(crate) async fn cmd_list_variants(
+    id: ModId,
+    scraper: &BananaClient,
+) -> ModManagerResult<ModVariantsListing> {
+    Ok(ModVariantsListing {
+        id,
+        files: scraper.list_mod_files(id).await?,
+    })
+}
@@ -1,2 +1,74 @@
+use std::fmt::{self, Formatter};
+
+use tabled::{
+    builder::Builder,
+    settings::{Padding, Style, object::Columns},
+};
+
 mod add;
+pub(crate) mod browse;
+pub mod dedupe;
+pub mod disk_usage;
+pub mod doctor;
+pub mod gc;
+pub mod list_variants;
+pub mod occupancy;
+pub mod overrides;
 pub mod status;
+pub mod sync;
+pub mod update_check;
+
+/// Writes the rows as an indented table with a header and no borders other
+/// than between the columns.
+pub(crate) fn write_table(
+    f: &mut Formatter<'_>,
+    headers: &[&str],
+    rows: impl Iterator<Item = Vec<String>>,
+) -> fmt::Result {
+    let mut builder = Builder::default();
+    builder.push_record(headers.iter().copied());
+    for row in rows {
+        builder.push_record(row);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::empty().vertical('|'))
+        .modify(Columns::first(), Padding::new(2, 1, 0, 0));
+
+    // The last column is padded to its width as well.
+    for line in table.to_string().lines() {
+        writeln!(f, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// Formats a number of bytes with binary units (eg. `1.5 MiB`).
+pub(crate) fn fmt_size(size_bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = size_bytes as f64;
+    let mut unit_idx = 0;
+    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_idx += 1;
+    }
+
+    match unit_idx {
+        0 => format!("{} {}", size_bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit_idx]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fmt_size;
+
+    #[test]
+    fn sizes_are_formatted_with_binary_units() {
+        assert_eq!(fmt_size(512), "512 B");
+        assert_eq!(fmt_size(4_194_304), "4.0 MiB");
+        assert_eq!(fmt_size(1_572_864), "1.5 MiB");
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::{self, Display, Formatter};
+
+use ultimate_mod_man_rs_scraper::mod_file_classifier::ModFileAssetAssociation;
+use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+use super::write_table;
+use crate::mod_db::ModDbView;
+
+/// Which enabled mod variants occupy each asset slot.
+#[derive(Debug)]
+pub struct OccupancyReport {
+    /// Sorted by slot.
+    pub slots: Vec<SlotOccupancy>,
+
+    /// Only slots claimed by more than one variant were included.
+    pub conflicts_only: bool,
+}
+
+#[derive(Debug)]
+pub struct SlotOccupancy {
+    pub association: ModFileAssetAssociation,
+    pub claimants: Vec<VariantAndId>,
+}
+
+impl Display for OccupancyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.slots.is_empty() {
+            return match self.conflicts_only {
+                false => writeln!(f, "No slots are occupied by enabled mods."),
+                true => writeln!(f, "No slots are contested by enabled mods."),
+            };
+        }
+
+        write_table(
+            f,
+            &["Slot", "Mods"],
+            self.slots.iter().map(|slot| {
+                vec![
+                    association_name(&slot.association),
+                    slot.claimants
+                        .iter()
+                        .map(|key| key.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ]
+            }),
+        )
+    }
+}
+
+fn association_name(assoc: &ModFileAssetAssociation) -> String {
+    match assoc {
+        ModFileAssetAssociation::CharSkinSlot(slot) => slot.to_string(),
+        ModFileAssetAssociation::Stage(slot) => slot.to_string(),
+        ModFileAssetAssociation::Global => "Global files".to_string(),
+        ModFileAssetAssociation::NoEffect => "No effect".to_string(),
+    }
+}
+
+pub(crate) fn cmd_occupancy(db: &ModDbView, conflicts_only: bool) -> OccupancyReport {
+    let mut slots = db
+        .occupancy_report()
+        .into_iter()
+        .filter(|(_, claimants)| !conflicts_only || claimants.len() > 1)
+        .map(|(association, claimants)| SlotOccupancy {
+            association,
+            claimants,
+        })
+        .collect::<Vec<_>>();
+
+    slots.sort_by_cached_key(|slot| association_name(&slot.association));
+
+    OccupancyReport {
+        slots,
+        conflicts_only,
+    }
+}
@@ -0,0 +1,34 @@
+use std::fmt::{self, Display, Formatter};
+
+use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+use crate::mod_db::{ModDb, VariantOverride};
+
+/// The slot overrides applied to an installed variant.
+#[derive(Debug)]
+pub struct VariantOverridesListing {
+    pub key: VariantAndId,
+    pub(crate) overrides: Vec<VariantOverride>,
+}
+
+impl Display for VariantOverridesListing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.overrides.is_empty() {
+            return writeln!(f, "{} has no overrides.", self.key);
+        }
+
+        writeln!(f, "Overrides of {}:", self.key)?;
+        for slot_override in self.overrides.iter() {
+            writeln!(f, "  {}", slot_override)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn cmd_overrides(key: VariantAndId, db: &ModDb) -> VariantOverridesListing {
+    VariantOverridesListing {
+        overrides: db.overrides_for(&key).to_vec(),
+        key,
+    }
+}
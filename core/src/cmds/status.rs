@@ -1,92 +1,685 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
 
-use ptree::TreeBuilder;
-use ultimate_mod_man_rs_utils::types::VariantAndIdentifier;
+use chrono::{DateTime, Duration, Utc};
+use ptree::{TreeBuilder, write_tree};
+use serde::Serialize;
+use tabled::{
+    builder::Builder,
+    settings::{Padding, Style as TableStyle, object::Columns},
+};
+use thiserror::Error;
+use ultimate_mod_man_rs_utils::types::{ModId, VariantAndId, VariantAndIdentifier};
 
-use crate::mod_db::ModDb;
+use crate::{
+    mod_db::{InstalledModInfo, InstalledVariant, ModDbResult, ModDbView, normalize_tag},
+    mod_query::{ModQuery, ModSummary},
+    slot_summary::MAX_SLOT_SUMMARY_LEN,
+    style::Style,
+    sync_targets::SyncTargets,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct InstalledModAndVariantsInfo {
+    id: ModId,
     name: String,
+    tags: Vec<String>,
     variants: Vec<VariantNameAndEnabled>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct VariantNameAndEnabled {
     name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+
     enabled: bool,
+    orphaned_upstream: bool,
+    last_enabled_at: Option<DateTime<Utc>>,
+    last_disabled_at: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+
+    /// The character skin slot that the variant is an inactive alternative
+    /// for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alternative_for: Option<String>,
+
+    /// A compact summary of the slots that the variant uses (eg.
+    /// `cloud: c00-c07; ui`).
+    #[serde(skip_serializing_if = "String::is_empty")]
+    slots: String,
+
+    /// The slot summary cut short so that it fits on a line of the tree.
+    #[serde(skip)]
+    slots_truncated: String,
+}
+
+impl VariantNameAndEnabled {
+    fn display_name(&self) -> String {
+        match &self.alias {
+            Some(alias) => format!("{} ({})", alias, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    fn new(variant: &InstalledVariant) -> Self {
+        let slot_summary = variant.slot_summary();
+
+        Self {
+            name: variant.name.clone(),
+            alias: variant.alias.clone(),
+            enabled: variant.enabled,
+            orphaned_upstream: variant.is_orphaned_upstream(),
+            last_enabled_at: variant.last_enabled_at,
+            last_disabled_at: variant.last_disabled_at,
+            notes: variant.notes.clone(),
+            alternative_for: variant.alternative_for().map(|slot| slot.to_string()),
+            slots: slot_summary.to_string(),
+            slots_truncated: slot_summary.fmt_truncated(MAX_SLOT_SUMMARY_LEN),
+        }
+    }
+
+    /// Notes are too long to fit into the tree, so we just mark that there is
+    /// one.
+    fn fmt_tree_suffix(&self, verbose: bool, style: &Style) -> String {
+        let note_marker = match self.notes.is_some() {
+            false => "",
+            true => " [note]",
+        };
+
+        let slots = match self.slots_truncated.is_empty() {
+            false => format!(" [slots: {}]", self.slots_truncated),
+            true => String::new(),
+        };
+
+        format!(
+            "{}{}{}",
+            self.fmt_suffix(verbose, style),
+            note_marker,
+            slots
+        )
+    }
+
+    fn fmt_orphaned_suffix(&self, style: &Style) -> String {
+        match self.orphaned_upstream {
+            false => String::new(),
+            true => format!(" {}", style.warning("[orphaned upstream]")),
+        }
+    }
+
+    fn fmt_alternative_suffix(&self) -> String {
+        match &self.alternative_for {
+            Some(slot) => format!(" [alternative for {}]", slot),
+            None => String::new(),
+        }
+    }
+
+    fn fmt_verbose_suffix(&self) -> String {
+        format!(
+            " [last enabled: {}, last disabled: {}]",
+            fmt_optional_timestamp(self.last_enabled_at),
+            fmt_optional_timestamp(self.last_disabled_at)
+        )
+    }
+
+    fn fmt_suffix(&self, verbose: bool, style: &Style) -> String {
+        let verbose = match verbose {
+            false => String::new(),
+            true => self.fmt_verbose_suffix(),
+        };
+
+        format!(
+            "{}{}{}",
+            self.fmt_orphaned_suffix(style),
+            self.fmt_alternative_suffix(),
+            verbose
+        )
+    }
+}
+
+fn fmt_optional_timestamp(timestamp: Option<DateTime<Utc>>) -> String {
+    match timestamp {
+        Some(timestamp) => timestamp.to_rfc2822(),
+        None => "never".to_string(),
+    }
+}
+
+/// Renders a note on a single line so that it can not break up the layout of
+/// the output (or mess with the terminal).
+fn fmt_note_single_line(note: &str) -> String {
+    note.lines()
+        .map(|line| line.chars().filter(|c| !c.is_control()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn fmt_tags_suffix(tags: &[String]) -> String {
+    match tags.is_empty() {
+        false => format!(" [tags: {}]", tags.join(", ")),
+        true => String::new(),
+    }
 }
 
 #[derive(Debug)]
-pub enum StatusCmdInfo {
+pub struct StatusCmdInfo {
+    pub kind: StatusCmdKind,
+
+    /// Include additional per-variant details (eg. when it was last toggled).
+    pub verbose: bool,
+
+    /// Also list the variants that were enabled within this duration of now.
+    pub recently_enabled_within: Option<Duration>,
+
+    /// Only include mods with this tag.
+    pub tag: Option<String>,
+
+    /// Also list every slot override applied to an installed variant.
+    pub overrides: bool,
+
+    /// The order that installed mods are listed in (when not asking for
+    /// specific mods).
+    pub sort: StatusSortKey,
+
+    /// Only list the installed mods (and variants) that match (when not asking
+    /// for specific mods).
+    pub filter: ModQuery,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Unknown sort order \"{0}\" (expected one of \"name\", \"id\", \"size\", \"variant-count\" or \
+     \"enabled\")"
+)]
+pub struct StatusSortKeyFromStrErr(String);
+
+/// How installed mods are ordered in the status. Mods that are tied are
+/// ordered by name (and then by ID).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StatusSortKey {
+    #[default]
+    Name,
+    Id,
+
+    /// Largest first.
+    Size,
+
+    /// Most variants first.
+    VariantCount,
+
+    /// Most enabled variants first.
+    Enabled,
+}
+
+impl FromStr for StatusSortKey {
+    type Err = StatusSortKeyFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "name" => Ok(Self::Name),
+            "id" => Ok(Self::Id),
+            "size" => Ok(Self::Size),
+            "variant-count" => Ok(Self::VariantCount),
+            "enabled" => Ok(Self::Enabled),
+            _ => Err(StatusSortKeyFromStrErr(s.to_string())),
+        }
+    }
+}
+
+impl StatusSortKey {
+    fn sort(self, mods: &mut [&InstalledModInfo], db: &ModDbView) {
+        let by_name = |mod_info: &InstalledModInfo| (mod_info.name.to_lowercase(), mod_info.id);
+
+        match self {
+            StatusSortKey::Name => mods.sort_by_cached_key(|m| by_name(m)),
+            StatusSortKey::Id => mods.sort_by_key(|m| m.id),
+            StatusSortKey::Size => mods.sort_by_cached_key(|m| {
+                (Reverse(db.estimate_disk_usage_for_mod(m.id)), by_name(m))
+            }),
+            StatusSortKey::VariantCount => {
+                mods.sort_by_cached_key(|m| (Reverse(m.installed_variants.len()), by_name(m)))
+            },
+            StatusSortKey::Enabled => mods.sort_by_cached_key(|m| {
+                let num_enabled = m.installed_variants.values().filter(|v| v.enabled).count();
+                (Reverse(num_enabled), by_name(m))
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StatusCmdKind {
     Generic,
     Specific(Vec<VariantAndIdentifier>),
 }
 
-pub(crate) fn cmd_status(args: &StatusCmdInfo, db: &ModDb) {
-    match args {
-        StatusCmdInfo::Generic => print!("{}", GenericModStats::new(db)),
-        StatusCmdInfo::Specific(vec) => todo!(),
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum StatusReport {
+    Generic(GenericStatusReport),
+    Specific(SpecificModsStatus),
+}
+
+impl Display for StatusReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusReport::Generic(report) => write!(f, "{}", report),
+            StatusReport::Specific(status) => write!(f, "{}", status),
+        }
     }
 }
 
-#[derive(Debug, Default)]
+/// The query that picks which installed mods are listed: the filter of the
+/// status along with `tag` (both normalized).
+pub(crate) fn status_query(args: &StatusCmdInfo, tag: Option<&str>) -> ModDbResult<ModQuery> {
+    let mut query = args.filter.clone();
+    query.tags = query
+        .tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .chain(tag.map(|tag| Ok(tag.to_string())))
+        .collect::<ModDbResult<_>>()?;
+
+    Ok(query)
+}
+
+/// `tag` is expected to already be normalized. `matching` are the results of
+/// the query from `status_query`. `conflict_count` is the number of installed
+/// variants that are unable to be enabled due to conflicts.
+pub(crate) fn cmd_status(
+    args: &StatusCmdInfo,
+    tag: Option<&str>,
+    db: &ModDbView,
+    matching: &[ModSummary],
+    conflict_count: usize,
+    sync_targets: &SyncTargets,
+    style: Style,
+) -> StatusReport {
+    let has_tag = |mod_info: &InstalledModInfo| tag.is_none_or(|tag| mod_info.has_tag(tag));
+
+    match &args.kind {
+        StatusCmdKind::Generic => StatusReport::Generic(GenericStatusReport {
+            stats: {
+                let matching = matching
+                    .iter()
+                    .map(|summary| (summary.id, summary))
+                    .collect::<HashMap<_, _>>();
+                let mut installed_mods = db
+                    .installed_mods()
+                    .filter(|m| matching.contains_key(&m.id))
+                    .collect::<Vec<_>>();
+                args.sort.sort(&mut installed_mods, db);
+
+                GenericModStats::new(
+                    installed_mods
+                        .into_iter()
+                        .map(|mod_info| (mod_info, matching[&mod_info.id])),
+                    conflict_count,
+                    args.verbose,
+                    style,
+                )
+            },
+            sync_targets: sync_targets.to_string(),
+            recently_enabled: args
+                .recently_enabled_within
+                .map(|since| recently_enabled_variants(db, since)),
+            overrides: args.overrides.then(|| applied_overrides(db)),
+        }),
+        StatusCmdKind::Specific(idents) => {
+            let mut status = SpecificModsStatus {
+                verbose: args.verbose,
+                style,
+                ..Default::default()
+            };
+
+            for ident in idents {
+                let found = db
+                    .find_installed_mod(ident.ident())
+                    .filter(|m| has_tag(m))
+                    .and_then(|m| {
+                        m.get_variant_by_name_or_alias(ident.variant_name())
+                            .map(|variant| (m, variant))
+                    });
+
+                match found {
+                    Some((mod_info, variant)) => status.add_variant(mod_info, variant),
+                    None => status.not_installed.push(ident.to_string()),
+                }
+            }
+
+            StatusReport::Specific(status)
+        },
+    }
+}
+
+fn recently_enabled_variants(db: &ModDbView, since: Duration) -> Vec<RecentlyEnabledVariant> {
+    let mut recently_enabled = db
+        .recently_enabled_variants(since)
+        .map(|(key, var_info)| RecentlyEnabledVariant {
+            key,
+            enabled_at: var_info.last_enabled_at,
+        })
+        .collect::<Vec<_>>();
+
+    recently_enabled.sort_by_key(|variant| variant.enabled_at);
+    recently_enabled.reverse();
+
+    recently_enabled
+}
+
+fn applied_overrides(db: &ModDbView) -> Vec<AppliedOverride> {
+    let unneeded = db.detect_any_overrides_that_are_no_longer_needed();
+
+    db.iter_overrides()
+        .map(|(key, slot_override)| AppliedOverride {
+            no_longer_needed: unneeded.iter().any(|(unneeded_key, unneeded_override)| {
+                *unneeded_key == key && unneeded_override == slot_override
+            }),
+            slot_override: slot_override.to_string(),
+            key,
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct GenericStatusReport {
+    #[serde(flatten)]
+    stats: GenericModStats,
+
+    /// Sync targets already have their own listing, so they are only included
+    /// when printing.
+    #[serde(skip)]
+    sync_targets: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recently_enabled: Option<Vec<RecentlyEnabledVariant>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overrides: Option<Vec<AppliedOverride>>,
+}
+
+impl Display for GenericStatusReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.stats)?;
+        write!(f, "{}", self.sync_targets)?;
+
+        match &self.recently_enabled {
+            Some(recently_enabled) if recently_enabled.is_empty() => {
+                writeln!(f, "No mod variants were enabled recently.")?
+            },
+            Some(recently_enabled) => {
+                writeln!(f, "Recently enabled mod variants:")?;
+                for variant in recently_enabled.iter() {
+                    writeln!(
+                        f,
+                        "  {} ({})",
+                        variant.key,
+                        fmt_optional_timestamp(variant.enabled_at)
+                    )?;
+                }
+            },
+            None => (),
+        }
+
+        match &self.overrides {
+            Some(overrides) if overrides.is_empty() => {
+                writeln!(f, "No slot overrides are applied.")?
+            },
+            Some(overrides) => {
+                writeln!(f, "Slot overrides:")?;
+                for applied in overrides.iter() {
+                    let unneeded_suffix = match applied.no_longer_needed {
+                        false => "",
+                        true => " [no longer needed]",
+                    };
+
+                    writeln!(
+                        f,
+                        "  {}: {}{}",
+                        applied.key, applied.slot_override, unneeded_suffix
+                    )?;
+                }
+            },
+            None => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// An override rendered as `old slot → new slot`.
+#[derive(Debug, Serialize)]
+struct AppliedOverride {
+    key: VariantAndId,
+    slot_override: String,
+
+    /// Nothing uses the original slot anymore, so the override could be
+    /// dropped.
+    no_longer_needed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RecentlyEnabledVariant {
+    key: VariantAndId,
+    enabled_at: Option<DateTime<Utc>>,
+}
+
+/// The status of specifically requested mod variants.
+#[derive(Debug, Default, Serialize)]
+pub struct SpecificModsStatus {
+    mods: Vec<ModStatus>,
+
+    /// The requested variants that are not installed (or do not have the tag
+    /// that we are filtering by).
+    not_installed: Vec<String>,
+
+    #[serde(skip)]
+    verbose: bool,
+
+    #[serde(skip)]
+    style: Style,
+}
+
+#[derive(Debug, Serialize)]
+struct ModStatus {
+    id: ModId,
+    name: String,
+    version: Option<String>,
+    tags: Vec<String>,
+    last_update_check: Option<DateTime<Utc>>,
+    variants: Vec<VariantNameAndEnabled>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+}
+
+impl SpecificModsStatus {
+    /// Groups the variant with any other requested variants of the same mod.
+    fn add_variant(&mut self, mod_info: &InstalledModInfo, variant: &InstalledVariant) {
+        let mod_status = match self.mods.iter_mut().position(|m| m.id == mod_info.id) {
+            Some(idx) => &mut self.mods[idx],
+            None => {
+                self.mods.push(ModStatus {
+                    id: mod_info.id,
+                    name: mod_info.name.clone(),
+                    version: mod_info.version.clone(),
+                    tags: mod_info.tags.clone(),
+                    last_update_check: mod_info.last_update_check,
+                    variants: Vec::new(),
+                    source_url: mod_info.source_url.clone(),
+                });
+                self.mods.last_mut().unwrap()
+            },
+        };
+
+        if mod_status.variants.iter().all(|v| v.name != variant.name) {
+            mod_status
+                .variants
+                .push(VariantNameAndEnabled::new(variant));
+        }
+    }
+}
+
+impl Display for SpecificModsStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for mod_status in self.mods.iter() {
+            writeln!(
+                f,
+                "{} ({})",
+                self.style.heading(&mod_status.name),
+                mod_status.id
+            )?;
+            writeln!(
+                f,
+                "  Version: {}",
+                mod_status.version.as_deref().unwrap_or("unknown")
+            )?;
+
+            let tags = match mod_status.tags.is_empty() {
+                false => mod_status.tags.join(", "),
+                true => "none".to_string(),
+            };
+            writeln!(f, "  Tags: {}", tags)?;
+
+            if let Some(source_url) = &mod_status.source_url {
+                writeln!(f, "  Page: {}", source_url)?;
+            }
+
+            writeln!(
+                f,
+                "  Last checked for updates: {}",
+                fmt_optional_timestamp(mod_status.last_update_check)
+            )?;
+
+            writeln!(f, "  Variants:")?;
+            for variant in mod_status.variants.iter() {
+                writeln!(
+                    f,
+                    "    {} ({}){}",
+                    variant.display_name(),
+                    self.style.enabled_flag(variant.enabled),
+                    variant.fmt_suffix(self.verbose, &self.style)
+                )?;
+
+                if let Some(note) = &variant.notes {
+                    writeln!(f, "      Note: {}", fmt_note_single_line(note))?;
+                }
+            }
+        }
+
+        for ident in self.not_installed.iter() {
+            writeln!(f, "{} is not installed.", self.style.warning(ident))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
 struct GenericModStats {
     scalars: ScalarStats,
     installed_mods: Vec<InstalledModAndVariantsInfo>,
+
+    #[serde(skip)]
+    verbose: bool,
+
+    #[serde(skip)]
+    style: Style,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize)]
 struct ScalarStats {
     num_mods_installed: usize,
     num_variants_installed: usize,
     num_mod_variants_enabled: usize,
+
+    /// Installed variants that are unable to be enabled due to conflicts.
+    conflict_count: usize,
 }
 
 impl Display for ScalarStats {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Number of mods installed: {}", self.num_mods_installed)?;
-        writeln!(
-            f,
-            "Number of mod variants installed: {}",
-            self.num_variants_installed
-        )?;
-        writeln!(
-            f,
-            "Number of mod variants enabled: {}",
-            self.num_mod_variants_enabled
-        )
+        let mut builder = Builder::default();
+        builder.push_record([
+            "Number of mods installed:".to_string(),
+            self.num_mods_installed.to_string(),
+        ]);
+        builder.push_record([
+            "Number of mod variants installed:".to_string(),
+            self.num_variants_installed.to_string(),
+        ]);
+        builder.push_record([
+            "Number of mod variants enabled:".to_string(),
+            self.num_mod_variants_enabled.to_string(),
+        ]);
+        builder.push_record([
+            "Number of conflicts:".to_string(),
+            self.conflict_count.to_string(),
+        ]);
+
+        let mut table = builder.build();
+        table
+            .with(TableStyle::empty())
+            .with(Padding::zero())
+            .modify(Columns::first(), Padding::new(0, 1, 0, 0));
+
+        writeln!(f, "{}", table)
     }
 }
 
 impl GenericModStats {
-    fn new(db: &ModDb) -> Self {
-        let mut generic_stats = GenericModStats::default();
+    /// Only the variants of each mod that are in its summary are included.
+    fn new<'a>(
+        installed_mods: impl Iterator<Item = (&'a InstalledModInfo, &'a ModSummary)>,
+        conflict_count: usize,
+        verbose: bool,
+        style: Style,
+    ) -> Self {
+        let mut generic_stats = GenericModStats {
+            scalars: ScalarStats {
+                conflict_count,
+                ..Default::default()
+            },
+            verbose,
+            style,
+            ..Default::default()
+        };
 
-        for mod_entry in db.installed_mods() {
+        for (mod_entry, summary) in installed_mods {
             generic_stats.scalars.num_mods_installed += 1;
 
             let mut variants = Vec::new();
-            for variant in mod_entry.installed_variants.values() {
+            for variant in mod_entry
+                .installed_variants
+                .values()
+                .filter(|variant| summary.variants.contains(&variant.name))
+            {
                 generic_stats.scalars.num_mods_installed += 1;
 
                 if variant.enabled {
                     generic_stats.scalars.num_mod_variants_enabled += 1;
                 }
 
-                let variant_info = VariantNameAndEnabled {
-                    name: variant.name.clone(),
-                    enabled: variant.enabled,
-                };
-
-                variants.push(variant_info);
+                variants.push(VariantNameAndEnabled::new(variant));
             }
 
             generic_stats
                 .installed_mods
                 .push(InstalledModAndVariantsInfo {
+                    id: mod_entry.id,
                     name: mod_entry.name.clone(),
+                    tags: mod_entry.tags.clone(),
                     variants,
+                    source_url: mod_entry.source_url.clone(),
                 });
         }
 
@@ -102,30 +695,61 @@ impl Display for GenericModStats {
 
         write!(f, "{}", self.scalars)?;
 
-        let mut p_tree = TreeBuilder::new("Installed Mods".to_string());
+        if self.scalars.conflict_count > 0 {
+            writeln!(
+                f,
+                "{}",
+                self.style
+                    .warning("Run 'export-conflicts -o <file>' for details on the conflicts.")
+            )?;
+        }
+
+        let mut p_tree = TreeBuilder::new(self.style.heading("Installed Mods"));
 
         for mod_entry in self.installed_mods.iter() {
+            let page_suffix = match (self.verbose, &mod_entry.source_url) {
+                (true, Some(source_url)) => format!(" [page: {}]", source_url),
+                _ => String::new(),
+            };
+            let mod_label = format!(
+                "{}{}{}",
+                mod_entry.name,
+                fmt_tags_suffix(&mod_entry.tags),
+                page_suffix
+            );
+
             match mod_entry.variants.len() {
                 0 => {
-                    p_tree.add_empty_child(format!("{} (No variants)", mod_entry.name));
+                    p_tree.add_empty_child(format!("{} (No variants)", mod_label));
                 },
                 1 => {
                     let single_variant = &mod_entry.variants[0];
                     p_tree.add_empty_child(format!(
-                        "{} --> {} ({})",
-                        mod_entry.name, single_variant.name, single_variant.enabled
+                        "{} --> {} ({}){}",
+                        mod_label,
+                        single_variant.display_name(),
+                        self.style.enabled_flag(single_variant.enabled),
+                        single_variant.fmt_tree_suffix(self.verbose, &self.style)
                     ));
                 },
                 _ => {
                     for variant in mod_entry.variants.iter() {
-                        p_tree.begin_child(mod_entry.name.clone());
-                        p_tree.add_empty_child(format!("{} ({})", variant.name, variant.enabled));
+                        p_tree.begin_child(mod_label.clone());
+                        p_tree.add_empty_child(format!(
+                            "{} ({}){}",
+                            variant.display_name(),
+                            self.style.enabled_flag(variant.enabled),
+                            variant.fmt_tree_suffix(self.verbose, &self.style)
+                        ));
                         p_tree.end_child();
                     }
                 },
             }
         }
 
-        write!(f, "{}", p_tree.build().text)
+        let mut rendered_tree = Vec::new();
+        write_tree(&p_tree.build(), &mut rendered_tree).map_err(|_| fmt::Error)?;
+
+        write!(f, "{}", String::from_utf8_lossy(&rendered_tree))
     }
 }
@@ -0,0 +1,904 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs, io,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::Utc;
+use glob::Pattern;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ultimate_mod_man_rs_utils::{
+    hashing::hash_file_streaming, types::VariantAndId, user_input_delegate::UserInputDelegate,
+};
+
+use super::fmt_size;
+use crate::{
+    mod_db::{InstalledModInfo, ModDb, ModDbError, validate_variant_name},
+    sync_targets::{SyncTarget, SyncTargetState, SyncTargetType, SyncedVariant},
+};
+
+pub type SyncResult<T> = Result<T, SyncError>;
+
+/// Path of the ARCropolis mods directory relative to the root of the SD card.
+const ULTIMATE_MODS_DIR: &str = "ultimate/mods";
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("Syncing to \"{0}\" targets is not supported yet.")]
+    UnsupportedTargetType(SyncTargetType),
+
+    #[error(transparent)]
+    ModDb(#[from] ModDbError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Unknown destination layout \"{0}\" (expected one of \"arcropolis\", \"flat\" or \
+     \"ultimate-root\")"
+)]
+pub struct DestLayoutFromStrErr(String);
+
+/// How mods are laid out on the target.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DestLayout {
+    /// The target path is the ARCropolis mods directory (`sd:/ultimate/mods`)
+    /// and each variant gets its own directory inside it.
+    #[default]
+    Arcropolis,
+
+    /// The files of every variant are written directly into the target path.
+    Flat,
+
+    /// The target path is the root of the SD card and each variant gets its
+    /// own directory inside of `ultimate/mods`.
+    UltimateRoot,
+}
+
+impl DestLayout {
+    /// The directory (relative to the target path) that a variant's files are
+    /// written under.
+    pub(crate) fn variant_root(&self, variant_dir_name: &str) -> Utf8PathBuf {
+        match self {
+            DestLayout::Arcropolis => variant_dir_name.into(),
+            DestLayout::Flat => Utf8PathBuf::new(),
+            DestLayout::UltimateRoot => Utf8Path::new(ULTIMATE_MODS_DIR).join(variant_dir_name),
+        }
+    }
+
+    /// Maps a file of a variant (relative to the variant's expanded directory)
+    /// to where it should be written (relative to the target path).
+    pub(crate) fn dest_path(
+        &self,
+        variant_dir_name: &str,
+        rel_file_path: &Utf8Path,
+    ) -> Utf8PathBuf {
+        self.variant_root(variant_dir_name).join(rel_file_path)
+    }
+
+    /// The directory (relative to the target path) that contains every
+    /// variant directory. `None` if variants do not get their own directories.
+    fn variants_parent_dir(&self) -> Option<&Utf8Path> {
+        match self {
+            DestLayout::Arcropolis => Some(Utf8Path::new("")),
+            DestLayout::Flat => None,
+            DestLayout::UltimateRoot => Some(Utf8Path::new(ULTIMATE_MODS_DIR)),
+        }
+    }
+}
+
+impl FromStr for DestLayout {
+    type Err = DestLayoutFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "arcropolis" => Ok(Self::Arcropolis),
+            "flat" => Ok(Self::Flat),
+            "ultimate-root" => Ok(Self::UltimateRoot),
+            _ => Err(DestLayoutFromStrErr(s.to_string())),
+        }
+    }
+}
+
+/// Everything that a sync would do to the target. Computed up front so that it
+/// can be shown to the user without touching the target (`--dry-run`).
+#[derive(Debug, Serialize)]
+pub struct SyncPlan {
+    pub target_name: String,
+
+    /// Files that are missing from the target or differ from the installed
+    /// ones. Files that are already identical are left alone.
+    pub copies: Vec<PlannedCopy>,
+
+    /// Files and directories from a previous sync that are no longer needed.
+    pub removals: Vec<PlannedRemoval>,
+
+    #[serde(skip)]
+    target_path: Utf8PathBuf,
+
+    #[serde(skip)]
+    layout: DestLayout,
+
+    #[serde(skip)]
+    max_concurrent_copies: usize,
+
+    /// What the target will hold once the plan is applied.
+    #[serde(skip)]
+    synced_variants: Vec<SyncedVariant>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedCopy {
+    pub key: VariantAndId,
+
+    #[serde(skip)]
+    src: Utf8PathBuf,
+
+    /// Relative to the target path.
+    pub dest: Utf8PathBuf,
+
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedRemoval {
+    /// Relative to the target path.
+    pub path: Utf8PathBuf,
+
+    /// Includes everything inside of it if this is a directory.
+    pub size_bytes: u64,
+
+    pub is_dir: bool,
+}
+
+impl Display for SyncPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.copies.is_empty() && self.removals.is_empty() {
+            return writeln!(f, "\"{}\" is already in sync.", self.target_name);
+        }
+
+        writeln!(f, "Planned changes to \"{}\":", self.target_name)?;
+
+        if !self.copies.is_empty() {
+            let total = self.copies.iter().map(|copy| copy.size_bytes).sum();
+            writeln!(
+                f,
+                "Copy ({} files, {}):",
+                self.copies.len(),
+                fmt_size(total)
+            )?;
+            for copy in self.copies.iter() {
+                writeln!(f, "  + {} ({})", copy.dest, fmt_size(copy.size_bytes))?;
+            }
+        }
+
+        if !self.removals.is_empty() {
+            let total = self.removals.iter().map(|removal| removal.size_bytes).sum();
+            writeln!(
+                f,
+                "Delete ({} paths, {}):",
+                self.removals.len(),
+                fmt_size(total)
+            )?;
+            for removal in self.removals.iter() {
+                let suffix = if removal.is_dir { "/" } else { "" };
+                writeln!(
+                    f,
+                    "  - {}{} ({})",
+                    removal.path,
+                    suffix,
+                    fmt_size(removal.size_bytes)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out what syncing every enabled variant to the target would copy and
+/// remove. Only reads from the target.
+pub(crate) fn cmd_plan_sync(
+    db: &ModDb,
+    target_name: &str,
+    target: &SyncTarget,
+    layout: DestLayout,
+    prev_state: Option<&SyncTargetState>,
+) -> SyncResult<SyncPlan> {
+    if !target.target_type.is_filesystem() {
+        return Err(SyncError::UnsupportedTargetType(target.target_type));
+    }
+
+    let ignore_patterns = target.ignore_patterns();
+    let mut copies = Vec::new();
+    let synced_variants = enabled_variants(db)
+        .map(|(mod_info, key)| {
+            plan_variant(
+                db,
+                target,
+                layout,
+                &ignore_patterns,
+                mod_info,
+                key,
+                &mut copies,
+            )
+        })
+        .collect::<SyncResult<Vec<_>>>()?;
+
+    let mut removals = match prev_state {
+        Some(prev_state) => plan_stale_synced_file_removals(
+            &target.path,
+            &prev_state.synced_variants,
+            &synced_variants,
+        )?,
+        None => Vec::new(),
+    };
+
+    // Directories are read in whatever order the filesystem gives us, which is
+    // hard to follow when printed.
+    copies.sort_by(|a, b| a.dest.cmp(&b.dest));
+    removals.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(SyncPlan {
+        target_name: target_name.to_string(),
+        copies,
+        removals,
+        target_path: target.path.clone(),
+        layout,
+        max_concurrent_copies: target.max_concurrent_copies(),
+        synced_variants,
+    })
+}
+
+/// The files that a sync (or installing missing variants) copied to the
+/// target.
+#[derive(Debug)]
+pub struct SyncCopyOutcome {
+    pub target_name: String,
+    pub num_copied: usize,
+
+    /// A file failing to copy does not stop the rest of the sync. Syncing
+    /// again retries them.
+    pub failed: Vec<FailedCopy>,
+}
+
+#[derive(Debug)]
+pub struct FailedCopy {
+    /// Relative to the target path.
+    pub dest: Utf8PathBuf,
+    pub err: io::Error,
+}
+
+impl Display for SyncCopyOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Copied {} files to \"{}\".",
+            self.num_copied, self.target_name
+        )?;
+
+        if !self.failed.is_empty() {
+            writeln!(f, "Failed to copy {} files:", self.failed.len())?;
+            for failed in self.failed.iter() {
+                writeln!(f, "  ! {}: {}", failed.dest, failed.err)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes every enabled variant to the target and removes any files that we
+/// previously synced but are no longer needed.
+pub(crate) fn cmd_apply_sync_plan(
+    plan: SyncPlan,
+    user_input_delegate: &mut impl UserInputDelegate,
+) -> SyncResult<(SyncTargetState, SyncCopyOutcome)> {
+    fs::create_dir_all(&plan.target_path)?;
+
+    let failed = copy_files(
+        &plan.target_path,
+        &plan.copies,
+        plan.max_concurrent_copies,
+        user_input_delegate,
+    );
+    let outcome = SyncCopyOutcome {
+        target_name: plan.target_name,
+        num_copied: plan.copies.len() - failed.len(),
+        failed,
+    };
+
+    for removal in plan.removals.iter() {
+        let path = plan.target_path.join(&removal.path);
+
+        // A file may already be gone with the directory that it was in.
+        match (removal.is_dir, path.exists()) {
+            (_, false) => (),
+            (false, true) => fs::remove_file(&path)?,
+            (true, true) => fs::remove_dir_all(&path)?,
+        }
+    }
+
+    let state = SyncTargetState {
+        last_sync: Some(Utc::now()),
+        layout: plan.layout,
+        synced_variants: plan.synced_variants,
+    };
+
+    Ok((state, outcome))
+}
+
+/// Writes only the given enabled variants to the target (using the layout of
+/// the last sync). Nothing else on the target is touched, so unlike a full sync
+/// this never removes anything.
+pub(crate) fn cmd_install_missing(
+    db: &ModDb,
+    target_name: &str,
+    target: &SyncTarget,
+    prev_state: Option<&SyncTargetState>,
+    missing: &[VariantAndId],
+    user_input_delegate: &mut impl UserInputDelegate,
+) -> SyncResult<(SyncTargetState, SyncCopyOutcome)> {
+    if !target.target_type.is_filesystem() {
+        return Err(SyncError::UnsupportedTargetType(target.target_type));
+    }
+
+    let layout = prev_state.map(|state| state.layout).unwrap_or_default();
+    let ignore_patterns = target.ignore_patterns();
+
+    let mut copies = Vec::new();
+    let installed = enabled_variants(db)
+        .filter(|(_, key)| missing.contains(key))
+        .map(|(mod_info, key)| {
+            plan_variant(
+                db,
+                target,
+                layout,
+                &ignore_patterns,
+                mod_info,
+                key,
+                &mut copies,
+            )
+        })
+        .collect::<SyncResult<Vec<_>>>()?;
+
+    let failed = copy_files(
+        &target.path,
+        &copies,
+        target.max_concurrent_copies(),
+        user_input_delegate,
+    );
+    let outcome = SyncCopyOutcome {
+        target_name: target_name.to_string(),
+        num_copied: copies.len() - failed.len(),
+        failed,
+    };
+
+    // Variants that are being installed again may have an entry from when
+    // they were last synced.
+    let mut synced_variants = prev_state
+        .map(|state| state.synced_variants.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|synced| !missing.contains(&synced.key))
+        .cloned()
+        .collect::<Vec<_>>();
+    synced_variants.extend(installed);
+
+    let state = SyncTargetState {
+        last_sync: prev_state.and_then(|state| state.last_sync),
+        layout,
+        synced_variants,
+    };
+
+    Ok((state, outcome))
+}
+
+/// Works out where each file of a variant goes on the target. Any file that is
+/// not already there (with the same contents) is added to `copies`.
+fn plan_variant(
+    db: &ModDb,
+    target: &SyncTarget,
+    layout: DestLayout,
+    ignore_patterns: &[Pattern],
+    mod_info: &InstalledModInfo,
+    key: VariantAndId,
+    copies: &mut Vec<PlannedCopy>,
+) -> SyncResult<SyncedVariant> {
+    let dir_name = get_synced_variant_dir_name(&mod_info.name, &key)?;
+    let src_dir = db.get_expanded_variant_path(&key)?;
+    let overrides = db.overrides_for(&key);
+
+    info!(
+        "Checking {} against {:?}...",
+        key,
+        target.path.join(layout.variant_root(&dir_name))
+    );
+
+    let mut files = Vec::new();
+    for rel_path in get_relative_file_paths_recursive(&src_dir)? {
+        if ignore_patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(rel_path.as_std_path()))
+        {
+            continue;
+        }
+
+        // Files of slots that were moved (eg. with `change-slot`) are written to
+        // where they were moved to.
+        let overridden_rel_path = overrides
+            .iter()
+            .find_map(|slot_override| slot_override.apply_to_file_path(&rel_path));
+        let dest_rel_path =
+            layout.dest_path(&dir_name, overridden_rel_path.as_ref().unwrap_or(&rel_path));
+        let src_path = src_dir.join(&rel_path);
+
+        if !is_identical_file(&src_path, &target.path.join(&dest_rel_path))? {
+            copies.push(PlannedCopy {
+                key: key.clone(),
+                size_bytes: fs::metadata(&src_path)?.len(),
+                src: src_path,
+                dest: dest_rel_path.clone(),
+            });
+        }
+        files.push(dest_rel_path);
+    }
+
+    Ok(SyncedVariant {
+        key,
+        root: layout.variant_root(&dir_name),
+        files,
+    })
+}
+
+/// Finds anything from a previous sync that is not written again in the
+/// latest sync.
+fn plan_stale_synced_file_removals(
+    target_path: &Utf8Path,
+    prev_synced: &[SyncedVariant],
+    synced: &[SyncedVariant],
+) -> io::Result<Vec<PlannedRemoval>> {
+    let synced_files = synced
+        .iter()
+        .flat_map(|synced| synced.files.iter())
+        .collect::<HashSet<_>>();
+    let synced_roots = synced
+        .iter()
+        .map(|synced| &synced.root)
+        .collect::<HashSet<_>>();
+
+    let mut removals = Vec::new();
+    for prev in prev_synced {
+        let prev_root_path = target_path.join(&prev.root);
+        if !prev.root.as_str().is_empty()
+            && !synced_roots.contains(&prev.root)
+            && prev_root_path.exists()
+        {
+            info!(
+                "{} is no longer enabled, so it will be removed from the target.",
+                prev.key
+            );
+
+            let size_bytes = get_relative_file_paths_recursive(&prev_root_path)?
+                .into_iter()
+                .map(|rel_path| fs::metadata(prev_root_path.join(rel_path)).map(|m| m.len()))
+                .sum::<io::Result<u64>>()?;
+            removals.push(PlannedRemoval {
+                path: prev.root.clone(),
+                size_bytes,
+                is_dir: true,
+            });
+
+            // Everything inside goes with the directory.
+            continue;
+        }
+
+        for stale_file in prev.files.iter().filter(|f| !synced_files.contains(f)) {
+            if let Ok(metadata) = fs::metadata(target_path.join(stale_file)) {
+                removals.push(PlannedRemoval {
+                    path: stale_file.clone(),
+                    size_bytes: metadata.len(),
+                    is_dir: false,
+                });
+            }
+        }
+    }
+
+    Ok(removals)
+}
+
+/// Compares what is currently on the target to what the manager expects to be
+/// there.
+pub(crate) fn cmd_switch_compare(
+    db: &ModDb,
+    target_name: &str,
+    target: &SyncTarget,
+    state: Option<&SyncTargetState>,
+) -> SyncResult<SwitchCompareReport> {
+    if !target.target_type.is_filesystem() {
+        return Err(SyncError::UnsupportedTargetType(target.target_type));
+    }
+
+    let synced_variants = state
+        .map(|state| state.synced_variants.as_slice())
+        .unwrap_or_default();
+    let layout = state.map(|state| state.layout).unwrap_or_default();
+
+    let mut report = SwitchCompareReport {
+        target_name: target_name.to_string(),
+        ..Default::default()
+    };
+
+    for (_, key) in enabled_variants(db) {
+        let on_target = synced_variants
+            .iter()
+            .find(|synced| synced.key == key)
+            .is_some_and(|synced| synced.files.iter().all(|f| target.path.join(f).exists()));
+
+        match on_target {
+            false => report.not_synced.push(key),
+            true => report.in_sync.push(key),
+        }
+    }
+
+    for synced in synced_variants.iter().filter(|synced| {
+        !report.in_sync.contains(&synced.key) && !report.not_synced.contains(&synced.key)
+    }) {
+        report.no_longer_enabled.push(synced.key.clone());
+    }
+
+    // We can only detect mods that we don't know about if each mod lives in it's
+    // own directory.
+    if let Some(variants_parent_dir) = layout.variants_parent_dir() {
+        let variants_parent_path = target.path.join(variants_parent_dir);
+
+        if variants_parent_path.exists() {
+            for entry in variants_parent_path.read_dir_utf8()? {
+                let entry = entry?;
+                let rel_dir = variants_parent_dir.join(entry.file_name());
+
+                if entry.file_type()?.is_dir()
+                    && !synced_variants.iter().any(|synced| synced.root == rel_dir)
+                {
+                    report.unknown_dirs.push(entry.file_name().to_string());
+                }
+            }
+        } else {
+            warn!(
+                "The mods directory for the target \"{}\" ({:?}) does not exist.",
+                target_name, variants_parent_path
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+impl SwitchCompareReport {
+    /// Variants that are enabled in the manager but not present on the target.
+    pub fn not_synced(&self) -> &[VariantAndId] {
+        &self.not_synced
+    }
+
+    /// What was copied to the target if the missing variants were installed.
+    pub fn installed_missing(&self) -> Option<&SyncCopyOutcome> {
+        self.installed_missing.as_ref()
+    }
+
+    pub(crate) fn set_installed_missing(&mut self, outcome: SyncCopyOutcome) {
+        self.installed_missing = Some(outcome);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SwitchCompareReport {
+    target_name: String,
+    in_sync: Vec<VariantAndId>,
+
+    /// Enabled in the manager but not present on the target.
+    not_synced: Vec<VariantAndId>,
+
+    /// Present on the target but no longer enabled in the manager.
+    no_longer_enabled: Vec<VariantAndId>,
+
+    /// Directories on the target that were not placed there by the manager.
+    unknown_dirs: Vec<String>,
+
+    /// Not part of the comparison itself, so it is left out when displayed.
+    installed_missing: Option<SyncCopyOutcome>,
+}
+
+impl Display for SwitchCompareReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Comparison with target \"{}\":", self.target_name)?;
+        writeln!(f, "In sync: {}", self.in_sync.len())?;
+
+        write_key_list(f, "Enabled but not synced", &self.not_synced)?;
+        write_key_list(f, "Synced but no longer enabled", &self.no_longer_enabled)?;
+        write_key_list(f, "Unknown mods on target", &self.unknown_dirs)
+    }
+}
+
+fn write_key_list<T: Display>(f: &mut Formatter<'_>, header: &str, items: &[T]) -> fmt::Result {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(f, "{} ({}):", header, items.len())?;
+    for item in items {
+        writeln!(f, "  - {item}")?;
+    }
+
+    Ok(())
+}
+
+fn enabled_variants(db: &ModDb) -> impl Iterator<Item = (&InstalledModInfo, VariantAndId)> {
+    db.installed_mods().flat_map(|mod_info| {
+        mod_info
+            .installed_variants
+            .values()
+            .filter(|var_info| var_info.enabled)
+            .map(move |var_info| {
+                (
+                    mod_info,
+                    VariantAndId::new(mod_info.id, var_info.name.clone()),
+                )
+            })
+    })
+}
+
+/// The directory on the target that a variant is synced to. Everything under it
+/// is deleted once the variant is no longer synced, so it must never point
+/// outside of the target. The mod name comes from GameBanana, so any path
+/// separators in it are replaced.
+fn get_synced_variant_dir_name(mod_name: &str, key: &VariantAndId) -> SyncResult<String> {
+    validate_variant_name(&key.variant_name)?;
+    let mod_name = mod_name.replace(['/', '\\', ':', '\0'], "_");
+
+    Ok(format!("{}_{}_{}", mod_name, key.id, key.variant_name))
+}
+
+/// Copies the files to the target with up to `max_concurrent` copies running
+/// at once. Copying lots of small files is dominated by the time spent on each
+/// file rather than by the amount of data, so this is much faster than copying
+/// them one at a time. Returns the copies that failed (sorted by destination).
+fn copy_files(
+    target_path: &Utf8Path,
+    copies: &[PlannedCopy],
+    max_concurrent: usize,
+    user_input_delegate: &mut impl UserInputDelegate,
+) -> Vec<FailedCopy> {
+    let total_files = copies.len();
+    let total_bytes = copies.iter().map(|copy| copy.size_bytes).sum();
+    let mut files_copied = 0;
+    let mut bytes_copied = 0;
+    let mut failed = Vec::new();
+
+    // Every directory is created before any file is copied (parents before their
+    // children) so that the copies never race each other to create them.
+    let dirs = copies
+        .iter()
+        .filter_map(|copy| copy.dest.parent())
+        .collect::<BTreeSet<_>>();
+    let dir_errs = dirs
+        .into_iter()
+        .filter_map(|dir| {
+            fs::create_dir_all(target_path.join(dir))
+                .err()
+                .map(|err| (dir, err))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut pending = Vec::with_capacity(copies.len());
+    for copy in copies {
+        match copy.dest.parent().and_then(|dir| dir_errs.get(dir)) {
+            Some(err) => {
+                files_copied += 1;
+                bytes_copied += copy.size_bytes;
+                failed.push(FailedCopy {
+                    dest: copy.dest.clone(),
+                    err: io::Error::new(err.kind(), err.to_string()),
+                });
+                user_input_delegate.display_sync_progress(
+                    files_copied,
+                    total_files,
+                    bytes_copied,
+                    total_bytes,
+                );
+            },
+            None => pending.push(copy),
+        }
+    }
+
+    let next_idx = AtomicUsize::new(0);
+    let (done_tx, done_rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..max_concurrent.min(pending.len()) {
+            let done_tx = done_tx.clone();
+            let (pending, next_idx) = (&pending, &next_idx);
+
+            s.spawn(move || {
+                while let Some(copy) = pending.get(next_idx.fetch_add(1, Ordering::Relaxed)) {
+                    let res = fs::copy(&copy.src, target_path.join(&copy.dest));
+                    if done_tx.send((*copy, res.err())).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        // Otherwise we would wait forever for this sender to hang up.
+        drop(done_tx);
+
+        // Only this thread talks to the delegate.
+        for (copy, err) in done_rx {
+            files_copied += 1;
+            bytes_copied += copy.size_bytes;
+
+            if let Some(err) = err {
+                failed.push(FailedCopy {
+                    dest: copy.dest.clone(),
+                    err,
+                });
+            }
+
+            user_input_delegate.display_sync_progress(
+                files_copied,
+                total_files,
+                bytes_copied,
+                total_bytes,
+            );
+        }
+    });
+
+    failed.sort_by(|a, b| a.dest.cmp(&b.dest));
+    failed
+}
+
+/// Whether `dest` already has the same contents as `src`. Writing to an SD
+/// card is slow, so files that did not change since the last sync are left
+/// alone.
+fn is_identical_file(src: &Utf8Path, dest: &Utf8Path) -> io::Result<bool> {
+    let dest_len = match fs::metadata(dest) {
+        Ok(metadata) if metadata.is_dir() => return Ok(false),
+        Ok(metadata) => metadata.len(),
+        // Something that is not a directory is in the way of one of its parents.
+        // Copying the file reports this instead.
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            ) =>
+        {
+            return Ok(false);
+        },
+        Err(err) => return Err(err),
+    };
+
+    if dest_len != fs::metadata(src)?.len() {
+        return Ok(false);
+    }
+
+    Ok(hash_file_streaming(src)? == hash_file_streaming(dest)?)
+}
+
+fn get_relative_file_paths_recursive(root: &Utf8Path) -> io::Result<Vec<Utf8PathBuf>> {
+    let mut file_paths = Vec::new();
+    let mut dirs_to_visit = vec![Utf8PathBuf::new()];
+
+    while let Some(rel_dir) = dirs_to_visit.pop() {
+        for entry in root.join(&rel_dir).read_dir_utf8()? {
+            let entry = entry?;
+            let rel_path = rel_dir.join(entry.file_name());
+
+            match entry.file_type()?.is_dir() {
+                false => file_paths.push(rel_path),
+                true => dirs_to_visit.push(rel_path),
+            }
+        }
+    }
+
+    Ok(file_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use tempfile::TempDir;
+    use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+    use super::{DestLayout, get_synced_variant_dir_name, is_identical_file};
+
+    const VARIANT_DIR_NAME: &str = "Cool Mario_9001_cool_mario.zip";
+    const VARIANT_FILE: &str = "fighter/mario/model/body/c00/model.numdlb";
+
+    fn dest_path_for_layout(layout: DestLayout) -> Utf8PathBuf {
+        layout.dest_path(VARIANT_DIR_NAME, Utf8Path::new(VARIANT_FILE))
+    }
+
+    #[test]
+    fn arcropolis_layout_places_variant_in_its_own_dir() {
+        assert_eq!(
+            dest_path_for_layout(DestLayout::Arcropolis),
+            Utf8PathBuf::from(format!("{VARIANT_DIR_NAME}/{VARIANT_FILE}"))
+        );
+    }
+
+    #[test]
+    fn flat_layout_places_files_directly_in_target() {
+        assert_eq!(
+            dest_path_for_layout(DestLayout::Flat),
+            Utf8PathBuf::from(VARIANT_FILE)
+        );
+    }
+
+    #[test]
+    fn ultimate_root_layout_places_variant_under_ultimate_mods() {
+        assert_eq!(
+            dest_path_for_layout(DestLayout::UltimateRoot),
+            Utf8PathBuf::from(format!("ultimate/mods/{VARIANT_DIR_NAME}/{VARIANT_FILE}"))
+        );
+    }
+
+    #[test]
+    fn synced_variant_dir_never_leaves_the_target() {
+        let key = VariantAndId::new(9001, "cool_mario.zip".to_string());
+
+        assert_eq!(
+            get_synced_variant_dir_name("Cool Mario", &key).unwrap(),
+            VARIANT_DIR_NAME
+        );
+        assert_eq!(
+            get_synced_variant_dir_name("../../Cool\\Mario", &key).unwrap(),
+            ".._.._Cool_Mario_9001_cool_mario.zip"
+        );
+
+        let key = VariantAndId::new(9001, "../cool_mario.zip".to_string());
+        assert!(get_synced_variant_dir_name("Cool Mario", &key).is_err());
+    }
+
+    #[test]
+    fn dest_layout_parses_from_cli_names() {
+        assert_eq!(
+            "arcropolis".parse::<DestLayout>().unwrap(),
+            DestLayout::Arcropolis
+        );
+        assert_eq!("flat".parse::<DestLayout>().unwrap(), DestLayout::Flat);
+        assert_eq!(
+            "ultimate-root".parse::<DestLayout>().unwrap(),
+            DestLayout::UltimateRoot
+        );
+        assert!("sd-card".parse::<DestLayout>().is_err());
+    }
+
+    #[test]
+    fn only_files_with_the_same_contents_are_identical() {
+        let dir = TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        let src = root.join("src.bin");
+        fs::write(&src, b"model").unwrap();
+        for (name, contents) in [("same.bin", "model"), ("changed.bin", "modal")] {
+            fs::write(root.join(name), contents).unwrap();
+        }
+
+        assert!(is_identical_file(&src, &root.join("same.bin")).unwrap());
+        assert!(!is_identical_file(&src, &root.join("changed.bin")).unwrap());
+        assert!(!is_identical_file(&src, &root.join("missing.bin")).unwrap());
+    }
+}
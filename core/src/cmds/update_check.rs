@@ -0,0 +1,450 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use serde::Serialize;
+use ultimate_mod_man_rs_scraper::banana_scraper::{
+    BananaClient, UpstreamFileInfo, UpstreamModInfo,
+};
+use ultimate_mod_man_rs_utils::{
+    types::{ModId, VariantAndId},
+    user_input_delegate::UserInputDelegate,
+};
+
+use super::write_table;
+use crate::mod_db::{InstalledModInfo, InstalledVariant, ModDb};
+
+/// The result of checking every installed mod for updates.
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateCheckReport {
+    /// Number of installed variants that are already on the latest version.
+    pub up_to_date: usize,
+
+    pub updates: Vec<UpdateCheckEntry>,
+
+    /// Mods (or individual variants) that we could not check.
+    pub unreachable: Vec<UnreachableMod>,
+
+    /// Mods that were not checked since they were already checked recently.
+    pub recently_checked: Vec<RecentlyCheckedMod>,
+
+    /// Variants that could not be updated when updating automatically.
+    pub failed_updates: Vec<FailedUpdate>,
+
+    /// Mods that were successfully checked, so that the time of the check can
+    /// be recorded.
+    #[serde(skip)]
+    pub(crate) checked_mods: Vec<ModId>,
+
+    /// Installed variants that no longer exist upstream but look like they
+    /// were replaced by a renamed file. The user needs to decide whether to
+    /// adopt the new file before these end up in one of the other sections.
+    #[serde(skip)]
+    pub(crate) successor_candidates: Vec<SuccessorCandidate>,
+
+    /// Up to date variants whose recorded upstream file is missing or no
+    /// longer matches the mod page (eg. the download URL changed).
+    #[serde(skip)]
+    pub(crate) stale_upstream_files: Vec<(VariantAndId, UpstreamFileInfo)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct SuccessorCandidate {
+    /// The entry for the installed variant.
+    pub(crate) entry: UpdateCheckEntry,
+    pub(crate) successor_file_name: String,
+}
+
+/// An installed variant that has a newer version on GameBanana.
+#[derive(Clone, Debug, Serialize)]
+pub struct UpdateCheckEntry {
+    pub key: VariantAndId,
+    pub mod_name: String,
+
+    /// `None` if we never recorded when the installed version was published.
+    pub installed_publish_date: Option<DateTime<Utc>>,
+    pub upstream_publish_date: DateTime<Utc>,
+
+    pub installed_version: Option<String>,
+    pub upstream_version: Option<String>,
+
+    /// The file to download the update from.
+    #[serde(skip)]
+    pub upstream_file: UpstreamFileInfo,
+
+    /// Set if the installed file was renamed upstream and the user chose to
+    /// adopt the new file. The variant is only tracked under the new name once
+    /// it actually gets updated.
+    pub successor_file_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnreachableMod {
+    pub id: ModId,
+    pub mod_name: String,
+
+    /// Set if only this variant of the mod could not be found.
+    pub variant_name: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedUpdate {
+    pub key: VariantAndId,
+    pub mod_name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecentlyCheckedMod {
+    pub id: ModId,
+    pub mod_name: String,
+    pub last_checked: DateTime<Utc>,
+}
+
+impl UpdateCheckReport {
+    fn add_mod_results(&mut self, mod_info: &InstalledModInfo, upstream: &UpstreamModInfo) {
+        // Variants collected by gc get the latest version once they are added again.
+        for var_info in mod_info
+            .installed_variants
+            .values()
+            .filter(|var_info| !var_info.is_data_collected())
+        {
+            let installed_publish_date = var_info
+                .version_info
+                .as_ref()
+                .map(|version_info| version_info.publish_date);
+
+            let key = VariantAndId::new(mod_info.id, var_info.name().to_string());
+            let upstream_file = match upstream.files.get(var_info.name()) {
+                Some(upstream_file) => upstream_file,
+                None => {
+                    self.add_variant_missing_upstream(mod_info, var_info, upstream);
+                    continue;
+                },
+            };
+            let upstream_publish_date = upstream_file.date_added;
+
+            // Variants that we do not know the publish date of are always treated as
+            // having an update.
+            if installed_publish_date.is_some_and(|date| date >= upstream_publish_date) {
+                self.up_to_date += 1;
+
+                // Same version as upstream, so this is where it can be downloaded from now.
+                if installed_publish_date == Some(upstream_publish_date)
+                    && var_info.upstream() != Some(upstream_file)
+                {
+                    self.stale_upstream_files.push((key, upstream_file.clone()));
+                }
+
+                continue;
+            }
+
+            let entry = UpdateCheckEntry {
+                key,
+                mod_name: mod_info.name.clone(),
+                installed_publish_date,
+                upstream_publish_date,
+                installed_version: mod_info.version.clone(),
+                upstream_version: upstream.version.clone(),
+                upstream_file: upstream_file.clone(),
+                successor_file_name: None,
+            };
+
+            self.updates.push(entry);
+        }
+    }
+
+    /// The installed file of the variant is gone, so either it was renamed or
+    /// it was removed entirely.
+    fn add_variant_missing_upstream(
+        &mut self,
+        mod_info: &InstalledModInfo,
+        var_info: &InstalledVariant,
+        upstream: &UpstreamModInfo,
+    ) {
+        let successor = match var_info.is_orphaned_upstream() {
+            false => upstream.find_successor_file(var_info.name()),
+            true => None,
+        }
+        .and_then(|name| Some((name, upstream.files.get(name)?)));
+
+        let (successor_file_name, upstream_file) = match successor {
+            Some(successor) => successor,
+            None => {
+                let reason = match var_info.is_orphaned_upstream() {
+                    false => "No longer available on GameBanana",
+                    true => "Orphaned upstream (the suggested replacement was declined)",
+                };
+
+                self.unreachable.push(UnreachableMod {
+                    id: mod_info.id,
+                    mod_name: mod_info.name.clone(),
+                    variant_name: Some(var_info.name().to_string()),
+                    reason: reason.to_string(),
+                });
+                return;
+            },
+        };
+
+        self.successor_candidates.push(SuccessorCandidate {
+            entry: UpdateCheckEntry {
+                key: VariantAndId::new(mod_info.id, var_info.name().to_string()),
+                mod_name: mod_info.name.clone(),
+                installed_publish_date: var_info
+                    .version_info
+                    .as_ref()
+                    .map(|version_info| version_info.publish_date),
+                upstream_publish_date: upstream_file.date_added,
+                installed_version: mod_info.version.clone(),
+                upstream_version: upstream.version.clone(),
+                upstream_file: upstream_file.clone(),
+                successor_file_name: None,
+            },
+            successor_file_name: successor_file_name.to_string(),
+        });
+    }
+
+    pub(crate) fn sort(&mut self) {
+        let entry_sort_key = |entry: &UpdateCheckEntry| {
+            (
+                entry.mod_name.to_lowercase(),
+                entry.key.variant_name.clone(),
+            )
+        };
+
+        self.updates.sort_by_key(entry_sort_key);
+        self.unreachable
+            .sort_by_key(|unreachable| (unreachable.mod_name.to_lowercase(), unreachable.id));
+        self.recently_checked
+            .sort_by_key(|recent| (recent.mod_name.to_lowercase(), recent.id));
+        self.failed_updates.sort_by_key(|failed| {
+            (
+                failed.mod_name.to_lowercase(),
+                failed.key.variant_name.clone(),
+            )
+        });
+    }
+}
+
+impl Display for UpdateCheckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Up to date: {}", self.up_to_date)?;
+
+        if !self.updates.is_empty() {
+            writeln!(f, "\nUpdates available ({}):", self.updates.len())?;
+            write_table(
+                f,
+                &[
+                    "Mod",
+                    "Variant",
+                    "Installed",
+                    "Upstream",
+                    "Installed version",
+                    "Upstream version",
+                ],
+                self.updates.iter().map(|entry| {
+                    let variant_name = match &entry.successor_file_name {
+                        Some(successor) => format!("{} -> {}", entry.key.variant_name, successor),
+                        None => entry.key.variant_name.clone(),
+                    };
+
+                    vec![
+                        entry.mod_name.clone(),
+                        variant_name,
+                        fmt_optional(entry.installed_publish_date.map(fmt_date)),
+                        fmt_date(entry.upstream_publish_date),
+                        fmt_optional(entry.installed_version.clone()),
+                        fmt_optional(entry.upstream_version.clone()),
+                    ]
+                }),
+            )?;
+        }
+
+        if !self.unreachable.is_empty() {
+            writeln!(f, "\nUnreachable or removed ({}):", self.unreachable.len())?;
+            write_table(
+                f,
+                &["Mod", "Variant", "Reason"],
+                self.unreachable.iter().map(|unreachable| {
+                    vec![
+                        format!("{} ({})", unreachable.mod_name, unreachable.id),
+                        fmt_optional(unreachable.variant_name.clone()),
+                        unreachable.reason.clone(),
+                    ]
+                }),
+            )?;
+        }
+
+        if !self.failed_updates.is_empty() {
+            writeln!(f, "\nFailed to update ({}):", self.failed_updates.len())?;
+            write_table(
+                f,
+                &["Mod", "Variant", "Reason"],
+                self.failed_updates.iter().map(|failed| {
+                    vec![
+                        format!("{} ({})", failed.mod_name, failed.key.id),
+                        failed.key.variant_name.clone(),
+                        failed.reason.clone(),
+                    ]
+                }),
+            )?;
+        }
+
+        if !self.recently_checked.is_empty() {
+            writeln!(
+                f,
+                "\nRecently checked, skipped ({}):",
+                self.recently_checked.len()
+            )?;
+            write_table(
+                f,
+                &["Mod", "Last checked"],
+                self.recently_checked.iter().map(|recent| {
+                    vec![
+                        format!("{} ({})", recent.mod_name, recent.id),
+                        recent.last_checked.format("%Y-%m-%d %H:%M").to_string(),
+                    ]
+                }),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn fmt_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn fmt_optional(val: Option<String>) -> String {
+    val.unwrap_or_else(|| "-".to_string())
+}
+
+/// Checks every installed mod for updates. Mods are checked concurrently, and
+/// any mod that we fail to check (even after the client retried it) ends up in
+/// the unreachable section instead of failing the whole check.
+///
+/// Retrying is left to the client so that every request goes through its
+/// backoff and rate limiting (a retry here would go straight back to
+/// GameBanana even after it told us to slow down).
+///
+/// Mods that were last checked after `skip_checked_since` are not checked
+/// again (and no requests are made for them). At most `max_concurrent_checks`
+/// mod pages are requested from GameBanana at once.
+pub(crate) async fn cmd_check_for_updates(
+    db: &ModDb,
+    scraper: &BananaClient,
+    user_input_delegate: &mut impl UserInputDelegate,
+    skip_checked_since: Option<DateTime<Utc>>,
+    max_concurrent_checks: usize,
+) -> UpdateCheckReport {
+    let mut report = UpdateCheckReport::default();
+
+    let (recently_checked_mods, installed_mods): (Vec<_>, Vec<_>) =
+        db.installed_mods().partition(|mod_info| {
+            mod_info
+                .last_update_check
+                .zip(skip_checked_since)
+                .is_some_and(|(last_checked, since)| last_checked >= since)
+        });
+
+    report.recently_checked = recently_checked_mods
+        .into_iter()
+        .map(|mod_info| RecentlyCheckedMod {
+            id: mod_info.id,
+            mod_name: mod_info.name.clone(),
+            last_checked: mod_info.last_update_check.unwrap(),
+        })
+        .collect();
+
+    let total = installed_mods.len();
+
+    let mut checks = stream::iter(installed_mods)
+        .map(|mod_info| async move { (mod_info, scraper.get_mod_update_info(mod_info.id).await) })
+        .buffer_unordered(max_concurrent_checks);
+
+    let mut num_checked = 0;
+
+    while let Some((mod_info, res)) = checks.next().await {
+        num_checked += 1;
+        user_input_delegate.display_update_check_progress(num_checked, total, &mod_info.name);
+
+        match res {
+            Ok(upstream) => {
+                report.add_mod_results(mod_info, &upstream);
+                report.checked_mods.push(mod_info.id);
+            },
+            Err(err) => report.unreachable.push(UnreachableMod {
+                id: mod_info.id,
+                mod_name: mod_info.name.clone(),
+                variant_name: None,
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    report.sort();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use ultimate_mod_man_rs_scraper::banana_scraper::UpstreamFileInfo;
+    use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+    use super::{UnreachableMod, UpdateCheckEntry, UpdateCheckReport};
+
+    fn m_entry(mod_name: &str, variant_name: &str) -> UpdateCheckEntry {
+        UpdateCheckEntry {
+            key: VariantAndId::new(1, variant_name.to_string()),
+            mod_name: mod_name.to_string(),
+            installed_publish_date: None,
+            upstream_publish_date: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            installed_version: None,
+            upstream_version: Some("1.2".to_string()),
+            upstream_file: UpstreamFileInfo::default(),
+            successor_file_name: None,
+        }
+    }
+
+    #[test]
+    fn report_sections_are_sorted_by_mod_name() {
+        let mut report = UpdateCheckReport {
+            updates: vec![m_entry("zelda", "a.zip"), m_entry("Bully", "b.zip")],
+            ..Default::default()
+        };
+        report.sort();
+
+        let names = report
+            .updates
+            .iter()
+            .map(|entry| entry.mod_name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["Bully", "zelda"]);
+    }
+
+    #[test]
+    fn report_table_lists_every_section() {
+        let report = UpdateCheckReport {
+            up_to_date: 3,
+            updates: vec![m_entry("Bully", "b.zip")],
+            unreachable: vec![UnreachableMod {
+                id: 7,
+                mod_name: "Gone".to_string(),
+                variant_name: None,
+                reason: "timed out".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let table = report.to_string();
+
+        assert!(table.contains("Up to date: 3"));
+        assert!(table.contains("Updates available (1):"));
+        assert!(table.contains("Unreachable or removed (1):"));
+        assert!(table.contains("Bully | b.zip   | -         | 2023-11-14"));
+    }
+}
@@ -0,0 +1,954 @@
+//! User settings for the mod manager that persist between runs. These live in
+//! `config.toml` inside the state directory and look like this:
+//!
+//! ```toml
+//! schema_version = 2
+//!
+//! [update]
+//! auto = true
+//! recheck_interval_hours = 6
+//! max_concurrent_checks = 8
+//!
+//! [download]
+//! max_concurrent = 3
+//!
+//! [lock]
+//! wait_timeout_secs = 30
+//!
+//! [gamebanana]
+//! game_id = 6498
+//! timeout_secs = 30
+//! max_retries = 2
+//!
+//! [resolver]
+//! cache_ttl_hours = 0
+//!
+//! [add]
+//! conflict_policy = "prompt"
+//!
+//! [conflicts]
+//! lenient_extended_slots = true
+//! ```
+//!
+//! Every setting is optional and falls back to its default (see `ConfigKey`)
+//! if it is not set. Any setting can also be overridden for a single run
+//! without touching the file, which takes precedence over the file.
+//!
+//! Keys that we do not know about (eg. ones written by a newer version) only
+//! produce a warning and are kept as is when the file is rewritten.
+//!
+//! The config is read before the state directory is locked, so a config from
+//! an older version is only upgraded in memory. It gets written in the current
+//! format the next time a setting is changed.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io,
+    str::FromStr,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::Duration;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ultimate_mod_man_rs_scraper::banana_scraper::{
+    BananaClientSettings, DEFAULT_MAX_RETRIES, DEFAULT_REQUEST_TIMEOUT, SSBU_GAME_ID,
+};
+use ultimate_mod_man_rs_utils::utils::{DeserializationError, SerializationError};
+
+use crate::{
+    mod_manager::AddConflictPolicy,
+    schema::{
+        DocumentSchema, SchemaError, deserialize_versioned_from_path, no_changes,
+        serialize_versioned,
+    },
+};
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+pub(crate) const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// - v2: Added `schema_version`.
+const CONFIG_SCHEMA: DocumentSchema = DocumentSchema::new(&[no_changes]);
+
+const DEFAULT_UPDATE_AUTO: bool = false;
+const DEFAULT_UPDATE_RECHECK_INTERVAL_HOURS: u32 = 6;
+const DEFAULT_UPDATE_MAX_CONCURRENT_CHECKS: usize = 8;
+const DEFAULT_DOWNLOAD_MAX_CONCURRENT: usize = 3;
+const DEFAULT_LOCK_WAIT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_RESOLVER_CACHE_TTL_HOURS: u32 = 0;
+const DEFAULT_CONFLICTS_LENIENT_EXTENDED_SLOTS: bool = true;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(
+        "Unknown config key \"{0}\". Run `config list` to see every key that can be configured."
+    )]
+    UnknownKey(String),
+
+    #[error("Invalid value \"{1}\" for the config key \"{0}\": {2}")]
+    InvalidValue(ConfigKey, String, String),
+
+    #[error("Expected a config override of the form \"key=value\" but got \"{0}\".")]
+    MalformedOverride(String),
+
+    #[error(transparent)]
+    DeserializationError(#[from] DeserializationError),
+
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+
+    #[error(transparent)]
+    SchemaError(#[from] SchemaError),
+
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+}
+
+/// Every setting that can be configured.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigKey {
+    /// Apply updates without asking about each mod (as if `--auto` was always
+    /// passed). Defaults to `false`.
+    UpdateAuto,
+
+    /// Mods checked for updates within this many hours are not checked again
+    /// unless the check is forced. Keeps frequent scripted checks from
+    /// hammering GameBanana. Defaults to `6`.
+    UpdateRecheckIntervalHours,
+
+    /// How many mods are checked for updates at once. Defaults to `8`.
+    UpdateMaxConcurrentChecks,
+
+    /// How many mods are downloaded at once when adding. Defaults to `3`.
+    DownloadMaxConcurrent,
+
+    /// How many seconds to wait for another instance to release the state
+    /// directory before giving up. Defaults to `30`.
+    LockWaitTimeoutSecs,
+
+    /// Which game mod names are searched for. Defaults to `6498` (Smash
+    /// Ultimate).
+    GameBananaGameId,
+
+    /// How many seconds a request to GameBanana can go without making any
+    /// progress before it is given up on. Defaults to `30`.
+    GameBananaTimeoutSecs,
+
+    /// How many times a request to GameBanana is retried if it fails in a way
+    /// that is likely temporary (eg. a timeout or a `503`). Defaults to `2`.
+    GameBananaMaxRetries,
+
+    /// Resolved mod names older than this many hours are looked up again. `0`
+    /// keeps them forever. Defaults to `0`.
+    ResolverCacheTtlHours,
+
+    /// How conflicts are resolved when adding without `--replace-existing` or
+    /// `--keep-existing`. One of `prompt`, `replace_existing`, `keep_existing`
+    /// or `keep_as_alternative`. Defaults to `prompt`.
+    AddConflictPolicy,
+
+    /// Allow variants to share extended skin slots (`c08`+), only warning
+    /// about it instead of treating it as a conflict. Conflicts on the normal
+    /// slots are never allowed. Defaults to `true`.
+    ConflictsLenientExtendedSlots,
+}
+
+impl ConfigKey {
+    pub const ALL: [ConfigKey; 11] = [
+        ConfigKey::UpdateAuto,
+        ConfigKey::UpdateRecheckIntervalHours,
+        ConfigKey::UpdateMaxConcurrentChecks,
+        ConfigKey::DownloadMaxConcurrent,
+        ConfigKey::LockWaitTimeoutSecs,
+        ConfigKey::GameBananaGameId,
+        ConfigKey::GameBananaTimeoutSecs,
+        ConfigKey::GameBananaMaxRetries,
+        ConfigKey::ResolverCacheTtlHours,
+        ConfigKey::AddConflictPolicy,
+        ConfigKey::ConflictsLenientExtendedSlots,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ConfigKey::UpdateAuto => "update.auto",
+            ConfigKey::UpdateRecheckIntervalHours => "update.recheck_interval_hours",
+            ConfigKey::UpdateMaxConcurrentChecks => "update.max_concurrent_checks",
+            ConfigKey::DownloadMaxConcurrent => "download.max_concurrent",
+            ConfigKey::LockWaitTimeoutSecs => "lock.wait_timeout_secs",
+            ConfigKey::GameBananaGameId => "gamebanana.game_id",
+            ConfigKey::GameBananaTimeoutSecs => "gamebanana.timeout_secs",
+            ConfigKey::GameBananaMaxRetries => "gamebanana.max_retries",
+            ConfigKey::ResolverCacheTtlHours => "resolver.cache_ttl_hours",
+            ConfigKey::AddConflictPolicy => "add.conflict_policy",
+            ConfigKey::ConflictsLenientExtendedSlots => "conflicts.lenient_extended_slots",
+        }
+    }
+}
+
+impl Display for ConfigKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ConfigKey {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ConfigKey::ALL
+            .into_iter()
+            .find(|key| key.name() == s)
+            .ok_or_else(|| ConfigError::UnknownKey(s.to_string()))
+    }
+}
+
+/// Parses a `key=value` override passed on the command line.
+pub fn parse_config_override(s: &str) -> ConfigResult<(ConfigKey, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| ConfigError::MalformedOverride(s.to_string()))?;
+
+    Ok((key.trim().parse()?, value.trim().to_string()))
+}
+
+/// Where the value of a setting came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigValueSource {
+    Default,
+    File,
+    Override,
+}
+
+impl Display for ConfigValueSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValueSource::Default => write!(f, "default"),
+            ConfigValueSource::File => write!(f, "config file"),
+            ConfigValueSource::Override => write!(f, "override"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigListing {
+    pub entries: Vec<(ConfigKey, String, ConfigValueSource)>,
+}
+
+impl Display for ConfigListing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (key, value, source) in self.entries.iter() {
+            writeln!(f, "{} = {} ({})", key, value, source)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct ConfigData {
+    #[serde(default, skip_serializing_if = "UpdateConfig::is_empty")]
+    update: UpdateConfig,
+
+    #[serde(default, skip_serializing_if = "DownloadConfig::is_empty")]
+    download: DownloadConfig,
+
+    #[serde(default, skip_serializing_if = "LockConfig::is_empty")]
+    lock: LockConfig,
+
+    #[serde(default, skip_serializing_if = "GameBananaConfig::is_empty")]
+    gamebanana: GameBananaConfig,
+
+    #[serde(default, skip_serializing_if = "ResolverConfig::is_empty")]
+    resolver: ResolverConfig,
+
+    #[serde(default, skip_serializing_if = "AddConfig::is_empty")]
+    add: AddConfig,
+
+    #[serde(default, skip_serializing_if = "ConflictsConfig::is_empty")]
+    conflicts: ConflictsConfig,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct UpdateConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recheck_interval_hours: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_concurrent_checks: Option<usize>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl UpdateConfig {
+    fn is_empty(&self) -> bool {
+        self.auto.is_none()
+            && self.recheck_interval_hours.is_none()
+            && self.max_concurrent_checks.is_none()
+            && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct DownloadConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_concurrent: Option<usize>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl DownloadConfig {
+    fn is_empty(&self) -> bool {
+        self.max_concurrent.is_none() && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct LockConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wait_timeout_secs: Option<u64>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl LockConfig {
+    fn is_empty(&self) -> bool {
+        self.wait_timeout_secs.is_none() && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct GameBananaConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    game_id: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_retries: Option<u32>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl GameBananaConfig {
+    fn is_empty(&self) -> bool {
+        self.game_id.is_none()
+            && self.timeout_secs.is_none()
+            && self.max_retries.is_none()
+            && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct ResolverConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_ttl_hours: Option<u32>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl ResolverConfig {
+    fn is_empty(&self) -> bool {
+        self.cache_ttl_hours.is_none() && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct AddConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    conflict_policy: Option<AddConflictPolicy>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl AddConfig {
+    fn is_empty(&self) -> bool {
+        self.conflict_policy.is_none() && self.unknown.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct ConflictsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lenient_extended_slots: Option<bool>,
+
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+impl ConflictsConfig {
+    fn is_empty(&self) -> bool {
+        self.lenient_extended_slots.is_none() && self.unknown.is_empty()
+    }
+}
+
+impl ConfigData {
+    fn get(&self, key: ConfigKey) -> Option<String> {
+        match key {
+            ConfigKey::UpdateAuto => self.update.auto.map(|v| v.to_string()),
+            ConfigKey::UpdateRecheckIntervalHours => {
+                self.update.recheck_interval_hours.map(|v| v.to_string())
+            },
+            ConfigKey::UpdateMaxConcurrentChecks => {
+                self.update.max_concurrent_checks.map(|v| v.to_string())
+            },
+            ConfigKey::DownloadMaxConcurrent => self.download.max_concurrent.map(|v| v.to_string()),
+            ConfigKey::LockWaitTimeoutSecs => self.lock.wait_timeout_secs.map(|v| v.to_string()),
+            ConfigKey::GameBananaGameId => self.gamebanana.game_id.map(|v| v.to_string()),
+            ConfigKey::GameBananaTimeoutSecs => self.gamebanana.timeout_secs.map(|v| v.to_string()),
+            ConfigKey::GameBananaMaxRetries => self.gamebanana.max_retries.map(|v| v.to_string()),
+            ConfigKey::ResolverCacheTtlHours => {
+                self.resolver.cache_ttl_hours.map(|v| v.to_string())
+            },
+            ConfigKey::AddConflictPolicy => self.add.conflict_policy.map(|v| v.to_string()),
+            ConfigKey::ConflictsLenientExtendedSlots => {
+                self.conflicts.lenient_extended_slots.map(|v| v.to_string())
+            },
+        }
+    }
+
+    /// Parses and validates the value before setting it.
+    fn set(&mut self, key: ConfigKey, value: &str) -> ConfigResult<()> {
+        let invalid =
+            |reason: &str| ConfigError::InvalidValue(key, value.to_string(), reason.to_string());
+
+        match key {
+            ConfigKey::UpdateAuto => {
+                self.update.auto = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected true or false"))?,
+                )
+            },
+            ConfigKey::UpdateRecheckIntervalHours => {
+                self.update.recheck_interval_hours = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected a number of hours"))?,
+                )
+            },
+            ConfigKey::UpdateMaxConcurrentChecks => {
+                let max_concurrent_checks: usize = value
+                    .parse()
+                    .map_err(|_| invalid("expected a positive number"))?;
+
+                if max_concurrent_checks == 0 {
+                    return Err(invalid("expected a positive number"));
+                }
+
+                self.update.max_concurrent_checks = Some(max_concurrent_checks);
+            },
+            ConfigKey::DownloadMaxConcurrent => {
+                let max_concurrent: usize = value
+                    .parse()
+                    .map_err(|_| invalid("expected a positive number"))?;
+
+                if max_concurrent == 0 {
+                    return Err(invalid("expected a positive number"));
+                }
+
+                self.download.max_concurrent = Some(max_concurrent);
+            },
+            ConfigKey::LockWaitTimeoutSecs => {
+                self.lock.wait_timeout_secs = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected a number of seconds"))?,
+                )
+            },
+            ConfigKey::GameBananaGameId => {
+                self.gamebanana.game_id = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected a GameBanana game ID"))?,
+                )
+            },
+            ConfigKey::GameBananaTimeoutSecs => {
+                let timeout_secs: u64 = value
+                    .parse()
+                    .map_err(|_| invalid("expected a positive number of seconds"))?;
+
+                if timeout_secs == 0 {
+                    return Err(invalid("expected a positive number of seconds"));
+                }
+
+                self.gamebanana.timeout_secs = Some(timeout_secs);
+            },
+            ConfigKey::GameBananaMaxRetries => {
+                self.gamebanana.max_retries = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected a number of retries"))?,
+                )
+            },
+            ConfigKey::ResolverCacheTtlHours => {
+                self.resolver.cache_ttl_hours = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected a number of hours"))?,
+                )
+            },
+            ConfigKey::AddConflictPolicy => {
+                self.add.conflict_policy = Some(value.parse().map_err(|_| {
+                    invalid(
+                        "expected one of prompt, replace_existing, keep_existing or \
+                         keep_as_alternative",
+                    )
+                })?)
+            },
+            ConfigKey::ConflictsLenientExtendedSlots => {
+                self.conflicts.lenient_extended_slots = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("expected true or false"))?,
+                )
+            },
+        }
+
+        Ok(())
+    }
+
+    fn warn_about_unknown_keys(&self, path: &Utf8Path) {
+        let unknown_keys = self
+            .unknown
+            .keys()
+            .cloned()
+            .chain(
+                self.update
+                    .unknown
+                    .keys()
+                    .map(|key| format!("update.{}", key)),
+            )
+            .chain(
+                self.download
+                    .unknown
+                    .keys()
+                    .map(|key| format!("download.{}", key)),
+            )
+            .chain(self.lock.unknown.keys().map(|key| format!("lock.{}", key)))
+            .chain(
+                self.gamebanana
+                    .unknown
+                    .keys()
+                    .map(|key| format!("gamebanana.{}", key)),
+            )
+            .chain(
+                self.resolver
+                    .unknown
+                    .keys()
+                    .map(|key| format!("resolver.{}", key)),
+            )
+            .chain(self.add.unknown.keys().map(|key| format!("add.{}", key)))
+            .chain(
+                self.conflicts
+                    .unknown
+                    .keys()
+                    .map(|key| format!("conflicts.{}", key)),
+            );
+
+        for key in unknown_keys {
+            warn!(
+                "Ignoring the unknown config key \"{}\" in {}. (It may be from a newer version.)",
+                key, path
+            );
+        }
+    }
+}
+
+/// The settings for a state directory. Usually loaded with `load_or_default`,
+/// but a caller can override any setting before handing it to
+/// `ModManager::new_with_config`.
+#[derive(Debug)]
+pub struct Config {
+    path: Utf8PathBuf,
+
+    /// What is written in the config file.
+    file: ConfigData,
+
+    /// Values that take precedence over the file for this run only.
+    overrides: ConfigData,
+}
+
+impl Config {
+    /// Reads the config in the state directory `p`. Every setting is left at
+    /// its default if there is no config file yet.
+    pub fn load_or_default(p: &Utf8Path) -> ConfigResult<Self> {
+        let path = p.join(CONFIG_FILE_NAME);
+
+        let file: ConfigData = match path.exists() {
+            false => ConfigData::default(),
+            true => deserialize_versioned_from_path(&path, &CONFIG_SCHEMA, false)?,
+        };
+        file.warn_about_unknown_keys(&path);
+
+        Ok(Self {
+            path,
+            file,
+            overrides: ConfigData::default(),
+        })
+    }
+
+    pub(crate) fn auto_update(&self) -> bool {
+        self.overrides
+            .update
+            .auto
+            .or(self.file.update.auto)
+            .unwrap_or(DEFAULT_UPDATE_AUTO)
+    }
+
+    pub(crate) fn update_recheck_interval(&self) -> Duration {
+        let hours = self
+            .overrides
+            .update
+            .recheck_interval_hours
+            .or(self.file.update.recheck_interval_hours)
+            .unwrap_or(DEFAULT_UPDATE_RECHECK_INTERVAL_HOURS);
+
+        Duration::hours(hours.into())
+    }
+
+    pub(crate) fn update_max_concurrent_checks(&self) -> usize {
+        self.overrides
+            .update
+            .max_concurrent_checks
+            .or(self.file.update.max_concurrent_checks)
+            .unwrap_or(DEFAULT_UPDATE_MAX_CONCURRENT_CHECKS)
+    }
+
+    pub(crate) fn max_concurrent_downloads(&self) -> usize {
+        self.overrides
+            .download
+            .max_concurrent
+            .or(self.file.download.max_concurrent)
+            .unwrap_or(DEFAULT_DOWNLOAD_MAX_CONCURRENT)
+    }
+
+    pub(crate) fn lock_wait_timeout(&self) -> std::time::Duration {
+        let secs = self
+            .overrides
+            .lock
+            .wait_timeout_secs
+            .or(self.file.lock.wait_timeout_secs)
+            .unwrap_or(DEFAULT_LOCK_WAIT_TIMEOUT_SECS);
+
+        std::time::Duration::from_secs(secs)
+    }
+
+    pub(crate) fn banana_client_settings(&self) -> BananaClientSettings {
+        let gamebanana = |get: fn(&GameBananaConfig) -> Option<u64>| {
+            get(&self.overrides.gamebanana).or(get(&self.file.gamebanana))
+        };
+
+        BananaClientSettings {
+            game_id: gamebanana(|c| c.game_id).unwrap_or(SSBU_GAME_ID),
+            timeout: gamebanana(|c| c.timeout_secs)
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+            max_retries: self
+                .overrides
+                .gamebanana
+                .max_retries
+                .or(self.file.gamebanana.max_retries)
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+        }
+    }
+
+    /// How long a resolved mod name is trusted for, if it ever expires.
+    pub(crate) fn resolver_cache_ttl(&self) -> Option<Duration> {
+        let hours = self
+            .overrides
+            .resolver
+            .cache_ttl_hours
+            .or(self.file.resolver.cache_ttl_hours)
+            .unwrap_or(DEFAULT_RESOLVER_CACHE_TTL_HOURS);
+
+        (hours > 0).then(|| Duration::hours(hours.into()))
+    }
+
+    pub(crate) fn add_conflict_policy(&self) -> AddConflictPolicy {
+        self.overrides
+            .add
+            .conflict_policy
+            .or(self.file.add.conflict_policy)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn lenient_extended_slots(&self) -> bool {
+        self.overrides
+            .conflicts
+            .lenient_extended_slots
+            .or(self.file.conflicts.lenient_extended_slots)
+            .unwrap_or(DEFAULT_CONFLICTS_LENIENT_EXTENDED_SLOTS)
+    }
+
+    /// The value of the setting that is actually used along with where it
+    /// came from.
+    pub fn get(&self, key: ConfigKey) -> (String, ConfigValueSource) {
+        if let Some(value) = self.overrides.get(key) {
+            return (value, ConfigValueSource::Override);
+        }
+
+        if let Some(value) = self.file.get(key) {
+            return (value, ConfigValueSource::File);
+        }
+
+        let default = match key {
+            ConfigKey::UpdateAuto => DEFAULT_UPDATE_AUTO.to_string(),
+            ConfigKey::UpdateRecheckIntervalHours => {
+                DEFAULT_UPDATE_RECHECK_INTERVAL_HOURS.to_string()
+            },
+            ConfigKey::UpdateMaxConcurrentChecks => {
+                DEFAULT_UPDATE_MAX_CONCURRENT_CHECKS.to_string()
+            },
+            ConfigKey::DownloadMaxConcurrent => DEFAULT_DOWNLOAD_MAX_CONCURRENT.to_string(),
+            ConfigKey::LockWaitTimeoutSecs => DEFAULT_LOCK_WAIT_TIMEOUT_SECS.to_string(),
+            ConfigKey::GameBananaGameId => SSBU_GAME_ID.to_string(),
+            ConfigKey::GameBananaTimeoutSecs => DEFAULT_REQUEST_TIMEOUT.as_secs().to_string(),
+            ConfigKey::GameBananaMaxRetries => DEFAULT_MAX_RETRIES.to_string(),
+            ConfigKey::ResolverCacheTtlHours => DEFAULT_RESOLVER_CACHE_TTL_HOURS.to_string(),
+            ConfigKey::AddConflictPolicy => AddConflictPolicy::default().to_string(),
+            ConfigKey::ConflictsLenientExtendedSlots => {
+                DEFAULT_CONFLICTS_LENIENT_EXTENDED_SLOTS.to_string()
+            },
+        };
+
+        (default, ConfigValueSource::Default)
+    }
+
+    pub fn list(&self) -> ConfigListing {
+        ConfigListing {
+            entries: ConfigKey::ALL
+                .into_iter()
+                .map(|key| {
+                    let (value, source) = self.get(key);
+                    (key, value, source)
+                })
+                .collect(),
+        }
+    }
+
+    /// Sets the value in the config file. Nothing is written if the value is
+    /// invalid.
+    pub(crate) fn set(&mut self, key: ConfigKey, value: &str) -> ConfigResult<()> {
+        let mut file = self.file.clone();
+        file.set(key, value)?;
+
+        self.write_atomically(&file)?;
+        self.file = file;
+
+        Ok(())
+    }
+
+    /// Overrides the value for this run without changing the config file.
+    pub fn set_override(&mut self, key: ConfigKey, value: &str) -> ConfigResult<()> {
+        self.overrides.set(key, value)
+    }
+
+    /// Writes to a temporary file first so that an interrupted write can never
+    /// leave behind a half written config.
+    fn write_atomically(&self, data: &ConfigData) -> ConfigResult<()> {
+        let tmp_path = self.path.with_extension("toml.tmp");
+
+        fs::write(&tmp_path, serialize_versioned(data, &CONFIG_SCHEMA)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8Path;
+    use chrono::Duration;
+    use tempfile::TempDir;
+    use ultimate_mod_man_rs_scraper::banana_scraper::BananaClientSettings;
+
+    use super::{Config, ConfigError, ConfigKey, ConfigValueSource, parse_config_override};
+    use crate::mod_manager::AddConflictPolicy;
+
+    fn m_config_with_file_contents(dir: &TempDir, contents: &str) -> Config {
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap();
+        fs::write(dir_path.join("config.toml"), contents).unwrap();
+
+        Config::load_or_default(dir_path).unwrap()
+    }
+
+    #[test]
+    fn missing_config_uses_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::load_or_default(Utf8Path::from_path(dir.path()).unwrap()).unwrap();
+
+        assert!(!config.auto_update());
+        assert_eq!(config.update_recheck_interval(), Duration::hours(6));
+        assert_eq!(config.update_max_concurrent_checks(), 8);
+        assert_eq!(config.max_concurrent_downloads(), 3);
+        assert_eq!(
+            config.banana_client_settings(),
+            BananaClientSettings::default()
+        );
+        assert_eq!(config.resolver_cache_ttl(), None);
+        assert_eq!(config.add_conflict_policy(), AddConflictPolicy::Prompt);
+        assert!(config.lenient_extended_slots());
+        assert!(
+            config
+                .list()
+                .entries
+                .iter()
+                .all(|(_, _, source)| *source == ConfigValueSource::Default)
+        );
+    }
+
+    #[test]
+    fn partial_update_section_keeps_other_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = m_config_with_file_contents(&dir, "[update]\nauto = true\n");
+
+        assert!(config.auto_update());
+        assert_eq!(config.update_recheck_interval(), Duration::hours(6));
+    }
+
+    #[test]
+    fn set_values_round_trip_through_the_file() {
+        let dir = TempDir::new().unwrap();
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut config = Config::load_or_default(dir_path).unwrap();
+        config.set(ConfigKey::UpdateAuto, "true").unwrap();
+        config
+            .set(ConfigKey::UpdateMaxConcurrentChecks, "2")
+            .unwrap();
+
+        let config = Config::load_or_default(dir_path).unwrap();
+        assert!(config.auto_update());
+        assert_eq!(config.update_max_concurrent_checks(), 2);
+        assert_eq!(
+            config.get(ConfigKey::UpdateMaxConcurrentChecks),
+            ("2".to_string(), ConfigValueSource::File)
+        );
+        assert!(!dir_path.join("config.toml.tmp").exists());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected_without_touching_the_file() {
+        let dir = TempDir::new().unwrap();
+        let mut config = m_config_with_file_contents(&dir, "[update]\nauto = true\n");
+
+        assert!(matches!(
+            config.set(ConfigKey::UpdateMaxConcurrentChecks, "0"),
+            Err(ConfigError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            config.set(ConfigKey::UpdateAuto, "maybe"),
+            Err(ConfigError::InvalidValue(..))
+        ));
+
+        let file_contents = fs::read_to_string(dir.path().join("config.toml")).unwrap();
+        assert_eq!(file_contents, "[update]\nauto = true\n");
+    }
+
+    #[test]
+    fn unknown_keys_are_kept_when_rewriting() {
+        let dir = TempDir::new().unwrap();
+        let mut config = m_config_with_file_contents(
+            &dir,
+            "future_setting = 1\n\n[update]\nfuture_update_setting = \"x\"\n",
+        );
+
+        config.set(ConfigKey::UpdateAuto, "true").unwrap();
+
+        let file_contents = fs::read_to_string(dir.path().join("config.toml")).unwrap();
+        assert!(file_contents.contains("future_setting = 1"));
+        assert!(file_contents.contains("future_update_setting = \"x\""));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_the_file() {
+        let dir = TempDir::new().unwrap();
+        let mut config =
+            m_config_with_file_contents(&dir, "[update]\nrecheck_interval_hours = 12\n");
+
+        let (key, value) = parse_config_override("update.recheck_interval_hours=1").unwrap();
+        config.set_override(key, &value).unwrap();
+
+        assert_eq!(config.update_recheck_interval(), Duration::hours(1));
+        assert_eq!(
+            config.get(key),
+            ("1".to_string(), ConfigValueSource::Override)
+        );
+
+        // The file itself is left alone.
+        let file_contents = fs::read_to_string(dir.path().join("config.toml")).unwrap();
+        assert_eq!(file_contents, "[update]\nrecheck_interval_hours = 12\n");
+    }
+
+    #[test]
+    fn client_resolver_and_add_settings_are_read_from_the_file() {
+        let dir = TempDir::new().unwrap();
+        let config = m_config_with_file_contents(
+            &dir,
+            "[gamebanana]\ngame_id = 1\ntimeout_secs = 5\nmax_retries = \
+             0\n\n[resolver]\ncache_ttl_hours = 24\n\n[add]\nconflict_policy = \"keep_existing\"\n",
+        );
+
+        assert_eq!(
+            config.banana_client_settings(),
+            BananaClientSettings {
+                game_id: 1,
+                timeout: std::time::Duration::from_secs(5),
+                max_retries: 0,
+            }
+        );
+        assert_eq!(config.resolver_cache_ttl(), Some(Duration::hours(24)));
+        assert_eq!(
+            config.add_conflict_policy(),
+            AddConflictPolicy::KeepExisting
+        );
+        assert_eq!(
+            config.get(ConfigKey::AddConflictPolicy),
+            ("keep_existing".to_string(), ConfigValueSource::File)
+        );
+    }
+
+    #[test]
+    fn unknown_add_conflict_policy_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let mut config = m_config_with_file_contents(&dir, "");
+
+        assert!(matches!(
+            config.set_override(ConfigKey::AddConflictPolicy, "ask"),
+            Err(ConfigError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            config.set_override(ConfigKey::GameBananaTimeoutSecs, "0"),
+            Err(ConfigError::InvalidValue(..))
+        ));
+    }
+
+    #[test]
+    fn unknown_and_malformed_overrides_are_rejected() {
+        assert!(matches!(
+            parse_config_override("update.nope=1"),
+            Err(ConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            parse_config_override("update.auto"),
+            Err(ConfigError::MalformedOverride(_))
+        ));
+    }
+}
@@ -4,19 +4,27 @@ use std::{
     ops::Deref,
 };
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use ultimate_mod_man_rs_scraper::{
+    banana_scraper::UpstreamFileInfo, mod_file_classifier::AssetType,
+};
 use ultimate_mod_man_rs_utils::{
-    types::VariantAndId,
-    utils::{
-        DeserializationError, SerializationError, deserialize_data_from_path,
-        serialize_data_to_path,
-    },
+    types::{AssetSlot, VariantAndId},
+    utils::{DeserializationError, SerializationError},
+};
+
+use crate::schema::{
+    DocumentSchema, SchemaError, deserialize_versioned_from_path, no_changes,
+    serialize_versioned_to_path,
 };
 
 type InProgActionResult<T> = Result<T, InProgActionError>;
 
+/// - v2: Added `schema_version`.
+const IN_PROG_ACTION_SCHEMA: DocumentSchema = DocumentSchema::new(&[no_changes]);
+
 #[derive(Debug, Error)]
 pub enum InProgActionError {
     #[error(transparent)]
@@ -27,19 +35,78 @@ pub enum InProgActionError {
 
     #[error(transparent)]
     SerializationError(#[from] SerializationError),
+
+    #[error(transparent)]
+    SchemaError(#[from] SchemaError),
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub(crate) enum Action {
-    Add(VariantAndId),
+    /// Downloads are kept in `quarantine_dir` until they are verified, so it
+    /// also needs to be cleaned up if we are interrupted.
+    Add {
+        key: VariantAndId,
+        quarantine_dir: Utf8PathBuf,
+
+        /// Lets the next add download the variant again instead of only
+        /// rolling it back. Not recorded by older versions.
+        #[serde(default)]
+        resume: Option<ResumableDownload>,
+    },
     Remove(VariantAndId),
+    Update {
+        key: VariantAndId,
+        quarantine_dir: Utf8PathBuf,
+
+        /// The renamed upstream file that the update adopts (if any). The
+        /// variant is renamed to it before being updated. Not recorded by older
+        /// versions.
+        #[serde(default)]
+        successor_name: Option<String>,
+    },
+
+    /// Enabling or disabling a batch of variants.
+    SetEnabled {
+        keys: Vec<VariantAndId>,
+        enabled: bool,
+    },
+
+    /// Moving an asset of a variant from one slot to another.
+    SlotChange(VariantAndId, AssetSlot, AssetSlot),
+}
+
+/// Everything needed to download (and install) the variant of an interrupted
+/// add again without going back to the mod page.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct ResumableDownload {
+    pub(crate) mod_name: String,
+    pub(crate) version: Option<String>,
+    pub(crate) upstream: UpstreamFileInfo,
+
+    /// Only files that change these kinds of assets were being installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) asset_types: Option<Vec<AssetType>>,
 }
 
 impl Display for Action {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Action::Add(key) => write!(f, "Add - ({})", key),
+            Action::Add { key, .. } => write!(f, "Add - ({})", key),
             Action::Remove(key) => write!(f, "Remove - ({})", key),
+            Action::Update { key, .. } => write!(f, "Update - ({})", key),
+            Action::SetEnabled { keys, enabled } => {
+                write!(f, "Set enabled ({}) - (", enabled)?;
+                for (i, key) in keys.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", key)?;
+                }
+                write!(f, ")")
+            },
+            Action::SlotChange(key, old_slot, new_slot) => {
+                write!(f, "Slot change - ({}: {} -> {})", key, old_slot, new_slot)
+            },
         }
     }
 }
@@ -65,12 +132,138 @@ impl InProgAction {
     pub(crate) fn load_from_disk_if_present(p: &Utf8Path) -> InProgActionResult<Option<Self>> {
         Ok(match fs::exists(p)? {
             false => None,
-            true => Some(deserialize_data_from_path(p)?),
+            // Only ever loaded while the state directory is locked.
+            true => Some(deserialize_versioned_from_path(
+                p,
+                &IN_PROG_ACTION_SCHEMA,
+                true,
+            )?),
         })
     }
 
     pub(crate) fn sync_to_disk(&self, p: &Utf8Path) -> InProgActionResult<()> {
-        serialize_data_to_path(p, self)?;
+        serialize_versioned_to_path(p, self, &IN_PROG_ACTION_SCHEMA)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8Path;
+    use chrono::DateTime;
+    use tempfile::TempDir;
+    use ultimate_mod_man_rs_scraper::{
+        banana_scraper::UpstreamFileInfo, mod_file_classifier::AssetType,
+    };
+    use ultimate_mod_man_rs_utils::types::{
+        AssetSlot, CharSkinSlotValue, SkinSlotValue, VariantAndId,
+    };
+
+    use super::{Action, InProgAction, ResumableDownload};
+
+    fn m_key() -> VariantAndId {
+        VariantAndId::new(1, "a.zip".to_string())
+    }
+
+    fn m_add_action() -> Action {
+        Action::Add {
+            key: m_key(),
+            quarantine_dir: "/state/quarantine/1_a.zip".into(),
+            resume: Some(ResumableDownload {
+                mod_name: "CoolMario".to_string(),
+                version: Some("1.2".to_string()),
+                upstream: UpstreamFileInfo {
+                    download_url: "https://gamebanana.com/dl/1".to_string(),
+                    md5: "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                    size: Some(1024),
+                    file_id: Some(7),
+                    date_added: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                },
+                asset_types: Some(vec![AssetType::Skins]),
+            }),
+        }
+    }
+
+    fn m_slot_change_action() -> Action {
+        let mario_slot = |idx| {
+            AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                "mario".to_string(),
+                SkinSlotValue::new(idx),
+            ))
+        };
+
+        Action::SlotChange(m_key(), mario_slot(0), mario_slot(3))
+    }
+
+    /// Loads the action the same way that it is loaded from a state
+    /// directory.
+    fn load(contents: &str) -> Action {
+        let dir = TempDir::new().unwrap();
+        let path = Utf8Path::from_path(dir.path())
+            .unwrap()
+            .join("in_prog_action.toml");
+        fs::write(&path, contents).unwrap();
+
+        InProgAction::load_from_disk_if_present(&path)
+            .unwrap()
+            .unwrap()
+            .in_prog
+    }
+
+    #[test]
+    fn add_action_format_is_stable() {
+        insta::assert_toml_snapshot!(InProgAction::new(m_add_action()));
+    }
+
+    #[test]
+    fn slot_change_action_format_is_stable() {
+        insta::assert_toml_snapshot!(InProgAction::new(m_slot_change_action()));
+    }
+
+    #[test]
+    fn actions_written_by_this_version_are_read_back() {
+        for action in [m_add_action(), m_slot_change_action()] {
+            let dir = TempDir::new().unwrap();
+            let path = Utf8Path::from_path(dir.path())
+                .unwrap()
+                .join("in_prog_action.toml");
+            InProgAction::new(action.clone())
+                .sync_to_disk(&path)
+                .unwrap();
+
+            assert_eq!(
+                *InProgAction::load_from_disk_if_present(&path)
+                    .unwrap()
+                    .unwrap(),
+                action
+            );
+        }
+    }
+
+    /// Written by a version that journaled adds before they could be resumed.
+    /// Changing how keys or slots are written must not break reading these.
+    #[test]
+    fn actions_written_by_older_versions_still_load() {
+        assert_eq!(
+            load(
+                "schema_version = 2\n\n[in_prog.Add]\nkey = { id = 1, variant_name = \"a.zip\" \
+                 }\nquarantine_dir = \"/state/quarantine/1_a.zip\"\n"
+            ),
+            Action::Add {
+                key: m_key(),
+                quarantine_dir: "/state/quarantine/1_a.zip".into(),
+                resume: None,
+            }
+        );
+        assert_eq!(
+            load(
+                "schema_version = 2\n\n[in_prog]\nSlotChange = [\n    { id = 1, variant_name = \
+                 \"a.zip\" },\n    { CharacterSkin = { char_key = \"mario\", skin_slot_idx = 0 } \
+                 },\n    { CharacterSkin = { char_key = \"mario\", skin_slot_idx = 3 } },\n]\n"
+            ),
+            m_slot_change_action()
+        );
+    }
+}
@@ -2,5 +2,12 @@ mod in_prog_action;
 pub mod mod_db;
 pub mod mod_manager;
 mod mod_name_resolver;
+pub mod mod_query;
+mod schema;
+mod slot_summary;
+mod state_dir;
+pub mod style;
+pub mod sync_targets;
 
 pub mod cmds;
+pub mod config;
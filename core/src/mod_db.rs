@@ -24,33 +24,55 @@
 //! structure is constructed.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
     fs::{self, create_dir_all},
-    io,
+    io::{self, Write},
+    ops::Deref,
+    path::Path,
+    process,
+    sync::{
+        Arc, PoisonError, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{self, Instant},
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use lockfile::Lockfile;
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
 use thiserror::Error;
 use ultimate_mod_man_rs_scraper::{
-    banana_scraper::ScrapedBananaModData,
+    banana_scraper::{ScrapedBananaModData, UpstreamFileInfo},
     download_artifact_parser::{ModPayloadParseInfo, VariantParseError},
-    mod_file_classifier::{ModFileAssetAssociation, VariantFileInfo},
+    mod_file_classifier::{
+        AssetType, ModFileAssetAssociation, VariantFileInfo, path_in_other_skin_slot,
+        path_with_other_game_path,
+    },
 };
 use ultimate_mod_man_rs_utils::{
+    hashing::{Digest, hash_tree},
     types::{
-        AssetSlot, AvailableSlotsToSwapToInfo, CharSkinSlotValue, ModId,
-        PickedNonSwappableResolutionOption, PickedResolutionOption, SkinSlotIdx, SkinSlotValue,
-        StageSlotIdx, StageSlotValue, SwappableAssetSlot, VariantAndId,
+        AssetSlot, AvailableSlotsToSwapToInfo, CharSkinSlotValue, ModId, ModIdentifier,
+        PickedNonSwappableResolutionOption, PickedResolutionOption, SkinSlotValue, StageSlotValue,
+        SwappableAssetSlot, VariantAndId,
     },
-    user_input_delegate::VariantConflictSummary,
-    utils::{DeserializationError, deserialize_data_from_path},
+    utils::{DeserializationError, SerializationError, serialize_data_to_path},
 };
 
-use crate::in_prog_action::{Action, InProgAction, InProgActionError};
+use crate::{
+    in_prog_action::{Action, InProgAction, InProgActionError},
+    mod_query::{ModQuery, ModSummary, VariantStateFilter},
+    schema::{
+        DocumentSchema, SchemaError, deserialize_versioned_from_path, no_changes,
+        serialize_versioned_to_path,
+    },
+    slot_summary::SlotSummary,
+    state_dir::{StateDirError, ensure_state_dir},
+};
 
 pub type ModDbResult<T> = Result<T, ModDbError>;
 
@@ -62,45 +84,405 @@ pub enum ModDbError {
     #[error(transparent)]
     DeserializationError(#[from] DeserializationError),
 
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+
     #[error(transparent)]
     LockFileError(#[from] DBLockFileError),
 
     #[error(transparent)]
     InProgActionError(#[from] InProgActionError),
 
+    #[error(transparent)]
+    StateDirError(#[from] StateDirError),
+
+    #[error(transparent)]
+    SchemaError(#[from] SchemaError),
+
     #[error(transparent)]
     IoError(#[from] io::Error),
+
+    #[error("The mod variant {0} is already installed")]
+    VariantAlreadyExists(VariantAndId),
+
+    #[error("The files of the mod variant {0} were removed by gc (add it again to reinstall it)")]
+    VariantDataCollected(VariantAndId),
+
+    #[error(
+        "The mod {0} is not installed (it may have been removed by another instance or by hand)"
+    )]
+    UnknownMod(ModId),
+
+    #[error(
+        "The mod variant {0} is not installed (it may have been removed by another instance or by \
+         hand)"
+    )]
+    UnknownVariant(VariantAndId),
+
+    #[error("Tags can not be empty")]
+    EmptyTag,
+
+    #[error("Notes can be at most {MAX_NOTE_LEN} characters long (got {0})")]
+    NoteTooLong(usize),
+
+    #[error(
+        "\"{0}\" can not be used as an alias (it can not be empty or contain \"/\", \":\", \"\\\" \
+         or null bytes)"
+    )]
+    InvalidAlias(String),
+
+    #[error("The alias \"{0}\" is already used by the mod variant {1}")]
+    AliasTaken(String, VariantAndId),
+
+    #[error("The slot is already occupied by the mod variant {0}")]
+    SlotOccupied(VariantAndId),
+
+    #[error("The mod variant {0} does not use the slot \"{1}\"")]
+    SlotNotUsedByVariant(VariantAndId, AssetSlot),
+
+    #[error("Unable to swap \"{0}\" to \"{1}\" since they are different kinds of slots")]
+    MismatchedSlotSwap(AssetSlot, AssetSlot),
+
+    #[error("The mod variant {0} is not an inactive alternative for \"{1}\"")]
+    NotAnAlternative(VariantAndId, CharSkinSlotValue),
+
+    #[error(
+        "Unable to make the mod variant {0} active since it also conflicts with other enabled \
+         variants outside of its slot"
+    )]
+    AlternativeConflictsElsewhere(VariantAndId),
+
+    #[error(
+        "\"{0}\" can not be used as a variant name since it is not a valid directory name (it can \
+         not be empty, \".\" or \"..\" or contain \"/\", \"\\\" or null bytes)"
+    )]
+    InvalidVariantName(String),
+
+    #[error("Unable to resolve the relative state directory path \"{0}\" to an absolute path")]
+    RelativePath(Utf8PathBuf),
+
+    /// Only ever logged as a warning. The lock is acquired as usual after it.
+    #[error(
+        "Removed the lock-file \"{path}\" left behind by an instance (PID {pid}) that is no \
+         longer running"
+    )]
+    StaleLockFileRemoved { path: Utf8PathBuf, pid: u32 },
+}
+
+/// The tag used to mark a mod as a favorite.
+pub const FAVORITE_TAG: &str = "favorite";
+
+/// How similar (`0.0` - `1.0`) the files of two variants need to be for them
+/// to count as duplicates of each other.
+const MIN_DUPLICATE_FILE_SIMILARITY: f32 = 0.95;
+
+/// Installed variants that look like copies of the same upload.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateVariantGroup {
+    pub reason: DuplicateReason,
+    pub variants: Vec<VariantAndId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum DuplicateReason {
+    /// Installed from archives with the same MD5 and size.
+    SameArchive { md5: String },
+
+    /// Each variant has nearly all of its files in common with another one in
+    /// the group. `similarity` is the lowest of these (`0.0` - `1.0`).
+    SimilarFiles { similarity: f32 },
+}
+
+/// Tags are matched case-insensitively and ignore surrounding whitespace, so we
+/// always store them in this form to avoid near-duplicates.
+pub fn normalize_tag(tag: &str) -> ModDbResult<String> {
+    let tag = tag.trim().to_lowercase();
+
+    match tag.is_empty() {
+        false => Ok(tag),
+        true => Err(ModDbError::EmptyTag),
+    }
+}
+
+/// The longest note (in characters) that can be attached to a variant.
+pub const MAX_NOTE_LEN: usize = 500;
+
+/// Normalizes line endings and surrounding whitespace of a note. A note that is
+/// empty after this is treated as no note at all.
+pub fn normalize_note(note: &str) -> ModDbResult<Option<String>> {
+    let note = note.replace("\r\n", "\n").trim().to_string();
+    let len = note.chars().count();
+
+    match len {
+        0 => Ok(None),
+        _ if len > MAX_NOTE_LEN => Err(ModDbError::NoteTooLong(len)),
+        _ => Ok(Some(note)),
+    }
+}
+
+/// Trims the alias. Aliases are typed in place of a variant name, so they can
+/// not contain anything that separates a mod from its variant.
+fn normalize_alias(alias: &str) -> ModDbResult<String> {
+    let alias = alias.trim();
+    let is_invalid = alias.is_empty() || alias.contains(['/', ':', '\\', '\0']);
+
+    match is_invalid {
+        false => Ok(alias.to_string()),
+        true => Err(ModDbError::InvalidAlias(alias.to_string())),
+    }
+}
+
+/// Variant names are used as the name of the directory that the variant is
+/// installed into, so they must not be able to point anywhere else.
+pub(crate) fn validate_variant_name(name: &str) -> ModDbResult<()> {
+    let is_invalid = matches!(name, "" | "." | "..") || name.contains(['/', '\\', '\0']);
+
+    match is_invalid {
+        false => Ok(()),
+        true => Err(ModDbError::InvalidVariantName(name.to_string())),
+    }
 }
 
-static MOD_INFO_FILE_NAME: &str = "mod_info.toml";
+pub(crate) static MOD_INFO_FILE_NAME: &str = "mod_info.toml";
+
+/// - v2: Added `schema_version`.
+const MOD_INFO_SCHEMA: DocumentSchema = DocumentSchema::new(&[no_changes]);
 static EXPANDED_MOD_INFO_DIR_NAME: &str = "expanded";
+static INCOMING_MOD_INFO_DIR_NAME: &str = "incoming";
+static PREVIOUS_MOD_INFO_DIR_NAME: &str = "previous";
 static DOWNLOAD_CACHE_UNPACKED_DATA_DIR: &str = "data";
-static IN_PROG_ACTION_FILE_NAME: &str = "in_prog_action.toml";
-static DB_LOCKFILE_NAME: &str = ".lockfile";
+pub(crate) static IN_PROG_ACTION_FILE_NAME: &str = "in_prog_action.toml";
+pub(crate) static QUARANTINE_DIR_NAME: &str = "quarantine";
+pub(crate) static DB_LOCKFILE_NAME: &str = ".lockfile";
 
 type DBLockFileResult<T> = Result<T, DBLockFileError>;
 
 #[derive(Debug, Error)]
-#[error(transparent)]
-pub struct DBLockFileError(#[from] lockfile::Error);
+pub enum DBLockFileError {
+    #[error(transparent)]
+    LockFileError(#[from] lockfile::Error),
+
+    #[error(
+        "Gave up after waiting {}s for another instance{} to release the state directory. If no \
+         other instance is running, delete \"{}\".",
+        .waited.as_secs(),
+        fmt_holder_pid(*.holder_pid),
+        .path
+    )]
+    TimedOut {
+        path: Utf8PathBuf,
+        waited: time::Duration,
+        holder_pid: Option<u32>,
+    },
+}
+
+fn fmt_holder_pid(holder_pid: Option<u32>) -> String {
+    match holder_pid {
+        Some(pid) => format!(" (PID {})", pid),
+        None => String::new(),
+    }
+}
+
+const LOCK_RETRY_INITIAL_DELAY: time::Duration = time::Duration::from_millis(50);
+const LOCK_RETRY_MAX_DELAY: time::Duration = time::Duration::from_secs(1);
 
 // TODO: Add pending writes to the lock file to get transaction like behavior...
 // Need to ignore the unused field because we actually "use" this field when the
 // struct gets dropped.
 #[allow(dead_code)]
 #[derive(Debug)]
-struct DBLockFile(Lockfile);
+pub(crate) struct DBLockFile(Lockfile);
+
+/// Whether anyone holds the lock on a state directory.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum LockState {
+    Free,
+
+    /// The PID of the holder (if it wrote it).
+    Held(Option<u32>),
+}
 
 impl DBLockFile {
-    fn new(p: &Utf8Path) -> DBLockFileResult<Self> {
-        Ok(Self(Lockfile::create(p.join(DB_LOCKFILE_NAME))?))
+    /// Acquires the lock-file, retrying with backoff while another instance
+    /// holds it. `on_wait` is called once (with the PID of the holder if known)
+    /// if we have to wait.
+    pub(crate) async fn new(
+        p: &Utf8Path,
+        timeout: time::Duration,
+        mut on_wait: impl FnMut(Option<u32>),
+    ) -> DBLockFileResult<Self> {
+        let path = p.join(DB_LOCKFILE_NAME);
+        let start = Instant::now();
+        let mut delay = LOCK_RETRY_INITIAL_DELAY;
+        let mut reported_wait = false;
+
+        loop {
+            match Lockfile::create(&path) {
+                Ok(mut lock_file) => {
+                    // Only informational, so it's not a problem if this fails.
+                    let _ = write!(lock_file, "{}", process::id());
+                    return Ok(Self(lock_file));
+                },
+                Err(lockfile::Error::LockTaken) => (),
+                Err(err) => return Err(err.into()),
+            }
+
+            let holder_pid = Self::read_holder_pid(&path);
+
+            // An instance that crashed never got to remove its lock-file.
+            if let Some(pid) = holder_pid
+                && !is_process_running(pid)
+                && Self::remove_stale(&path, pid)
+            {
+                warn!(
+                    "{}",
+                    ModDbError::StaleLockFileRemoved {
+                        path: path.clone(),
+                        pid
+                    }
+                );
+                continue;
+            }
+
+            let waited = start.elapsed();
+            if waited >= timeout {
+                return Err(DBLockFileError::TimedOut {
+                    path,
+                    waited,
+                    holder_pid,
+                });
+            }
+
+            if !reported_wait {
+                on_wait(holder_pid);
+                reported_wait = true;
+            }
+
+            tokio::time::sleep(delay.min(timeout - waited)).await;
+            delay = (delay * 2).min(LOCK_RETRY_MAX_DELAY);
+        }
+    }
+
+    /// Checks if the lock-file could be acquired right now without waiting.
+    /// If it could, it is released again straight away.
+    pub(crate) fn probe(p: &Utf8Path) -> DBLockFileResult<LockState> {
+        let path = p.join(DB_LOCKFILE_NAME);
+
+        match Lockfile::create(&path) {
+            Ok(_) => Ok(LockState::Free),
+            Err(lockfile::Error::LockTaken) => Ok(LockState::Held(Self::read_holder_pid(&path))),
+            Err(err) => Err(err.into()),
+        }
     }
+
+    /// The state directory that the lock-file is in.
+    fn dir_path(&self) -> &Utf8Path {
+        // Always created from a UTF-8 path joined with the lock-file name.
+        Utf8Path::from_path(self.0.path().parent().unwrap()).unwrap()
+    }
+
+    /// The holder may not have written its PID yet (or may be an older
+    /// version that never writes it).
+    fn read_holder_pid(path: &Utf8Path) -> Option<u32> {
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    /// Removes the lock-file of the dead process `pid`. Returns `false` if it
+    /// could not be removed (eg. because another instance already replaced it
+    /// with its own).
+    ///
+    /// Another instance may take over the stale lock-file between us reading
+    /// the PID and removing it, so the lock-file is first moved out of the way
+    /// (which only one instance can do) and the PID is checked again. If it
+    /// turns out to belong to someone else, it is put back (unless yet
+    /// another lock-file was created in the meantime).
+    pub(crate) fn remove_stale(path: &Utf8Path, pid: u32) -> bool {
+        static NUM_TAKEOVERS: AtomicUsize = AtomicUsize::new(0);
+
+        let taken_path = Utf8PathBuf::from(format!(
+            "{}.stale-{}-{}",
+            path,
+            process::id(),
+            NUM_TAKEOVERS.fetch_add(1, Ordering::Relaxed)
+        ));
+        if fs::rename(path, &taken_path).is_err() {
+            return false;
+        }
+
+        let was_stale = Self::read_holder_pid(&taken_path) == Some(pid);
+        if !was_stale {
+            let _ = fs::hard_link(&taken_path, path);
+        }
+        let _ = fs::remove_file(&taken_path);
+
+        was_stale
+    }
+}
+
+/// Whether a process with the PID exists.
+pub(crate) fn is_process_running(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut sys = System::new();
+    sys.refresh_processes(ProcessesToUpdate::Some(&[pid]), false);
+
+    sys.process(pid).is_some()
 }
 
+/// Whatever is keeping the database directory reserved for us until we are
+/// dropped.
 #[derive(Debug)]
-pub(crate) enum UnableToEnableReason {
-    Conflicts(VariantConflictInfo),
+enum DbDirGuard {
+    LockFile(DBLockFile),
+
+    /// Scratch directory that gets deleted on drop. Nothing else can know about
+    /// it, so there is no need for a lock-file.
+    #[cfg(test)]
+    TempDir(tempfile::TempDir),
+}
+
+impl DbDirGuard {
+    /// The database directory that is reserved.
+    fn dir_path(&self) -> Utf8PathBuf {
+        match self {
+            Self::LockFile(lock_file) => lock_file.dir_path().to_path_buf(),
+            #[cfg(test)]
+            Self::TempDir(temp_dir) => {
+                Utf8PathBuf::from_path_buf(temp_dir.path().to_path_buf()).unwrap()
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum EnableVariantOutcome {
+    Enabled,
+
+    /// Enabled, but shares these extended skin slots (`c08`+) with other
+    /// enabled variants.
+    EnabledWithWarnings(Vec<AssetSlot>),
+
     AlreadyEnabled,
+    Conflicts(VariantConflictInfo),
+
+    /// The files of the variant were removed by gc.
+    DataCollected,
+}
+
+impl EnableVariantOutcome {
+    pub(crate) fn is_enabled(&self) -> bool {
+        matches!(
+            self,
+            EnableVariantOutcome::Enabled | EnableVariantOutcome::EnabledWithWarnings(_)
+        )
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum DisableVariantOutcome {
+    Disabled,
+    WasAlreadyDisabled,
+    NotInstalled,
 }
 
 #[derive(Debug)]
@@ -118,84 +500,240 @@ pub struct ConflictingModVariant {
     pub slots: Vec<AssetSlot>,
 }
 
+/// A dump of every conflict currently in the database. Intended to be attached
+/// to bug reports.
+#[derive(Debug, Serialize)]
+pub(crate) struct ConflictReport {
+    generated_at: DateTime<Utc>,
+    conflicts: Vec<ConflictReportEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ConflictReportEntry {
+    /// The variant that is unable to be enabled.
+    variant: VariantAndId,
+
+    /// The enabled variant that is occupying the slots.
+    conflicting_variant: VariantAndId,
+
+    slots: Vec<AssetSlot>,
+
+    /// Whether every conflicting slot can be resolved by swapping to another
+    /// slot.
+    swappable: bool,
+}
+
+impl ConflictReport {
+    fn new(conflicts: Vec<VariantConflictInfo>) -> Self {
+        let conflicts = conflicts
+            .into_iter()
+            .flat_map(|var_conflicts| {
+                let variant = var_conflicts.key;
+
+                var_conflicts
+                    .conflicts
+                    .into_iter()
+                    .map(move |conflict| ConflictReportEntry {
+                        variant: variant.clone(),
+                        conflicting_variant: conflict.key,
+                        swappable: conflict.slots.iter().all(|slot| slot.is_swappable()),
+                        slots: conflict.slots,
+                    })
+            })
+            .collect();
+
+        Self {
+            generated_at: Utc::now(),
+            conflicts,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ModDb {
     // persisted_state:
-    directory_contents: ModDbDirectory,
+    view: ModDbView,
 
     mod_file_associations: EnabledModFileAssociations,
 
+    /// Every variant that is part of a conflict. Computed on demand and cleared
+    /// whenever anything that could change the conflicts is modified.
+    conflicting_variants_cache: RwLock<Option<Arc<HashSet<VariantAndId>>>>,
+
     /// We hold the lock-file until the entire program exits.
-    _lock_file: DBLockFile,
+    _dir_guard: DbDirGuard,
+}
+
+/// The installed mods without anything needed to modify them. Every query on a
+/// `ModDb` is also available here.
+#[derive(Debug)]
+pub(crate) struct ModDbView {
+    directory_contents: ModDbDirectory,
+}
+
+impl Deref for ModDb {
+    type Target = ModDbView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
 }
 
 impl ModDb {
-    pub(crate) fn load_from_path(p: &Utf8Path) -> ModDbResult<Self> {
+    /// Creates the state directory if it does not exist yet and returns its
+    /// absolute path. Every path into the directory is built from this, so a
+    /// relative path would point somewhere else if the working directory
+    /// changed.
+    ///
+    /// An existing directory that does not look like a state directory is
+    /// refused unless `init_here` is set (see `state_dir`).
+    pub(crate) fn resolve_dir_path(p: &Utf8Path, init_here: bool) -> ModDbResult<Utf8PathBuf> {
         if !p.exists() {
             info!("Data directory does not exist at \"{p:?}\". Creating...");
             create_dir_all(p)?;
         }
 
-        let mut mod_file_associations = EnabledModFileAssociations::new();
-        let mut installed_mods = HashMap::new();
+        let p = absolute_dir_path(p)?;
+        ensure_state_dir(&p, init_here, false)?;
+
+        Ok(p)
+    }
+
+    /// Waits up to `lock_timeout` for any other instance to release the
+    /// directory. `on_lock_wait` is called if we need to wait.
+    ///
+    /// `lenient_extended_slots` allows enabled variants to share extended skin
+    /// slots (`c08`+).
+    pub(crate) async fn load_from_path(
+        p: &Utf8Path,
+        lock_timeout: time::Duration,
+        lenient_extended_slots: bool,
+        on_lock_wait: impl FnMut(Option<u32>),
+    ) -> ModDbResult<Self> {
+        let p = &Self::resolve_dir_path(p, false)?;
 
         // TODO: If there is a clean cross-platform way to access a in memory directory
         // (eg. `/tmp` on Linux), place the lockfile there instead.
-        let _lock_file = DBLockFile::new(p)?;
+        let lock_file = DBLockFile::new(p, lock_timeout, on_lock_wait).await?;
+        let mut db = Self::new_empty(DbDirGuard::LockFile(lock_file), lenient_extended_slots);
 
-        for entry in Utf8Path::read_dir_utf8(p)? {
-            let installed_mod_dir = entry?;
+        for installed_mod in read_installed_mods(p, true)? {
+            db.register_installed_mod(installed_mod);
+        }
 
-            // There should only be directories in the mod folder.
-            if !installed_mod_dir.file_type()?.is_dir() {
-                let unexpected_entry_name = installed_mod_dir.file_name();
-                warn!(
-                    "Found something other than a directory in the mod manager state directory at \
-                     \"{p:?}\" ({unexpected_entry_name:?})"
-                );
+        Ok(db)
+    }
+
+    /// Reads the installed mods without locking the directory, so this never
+    /// waits on (or blocks) another instance. Since nothing is locked, the
+    /// result may be inconsistent if another instance is writing at the same
+    /// time. Variants are returned exactly as they are on disk (ie. enabled
+    /// variants that conflict are not disabled).
+    pub(crate) fn load_read_only(p: &Utf8Path, init_here: bool) -> ModDbResult<ModDbView> {
+        // Nothing is installed yet, and a view never creates the directory.
+        if !p.exists() {
+            return Ok(ModDbView {
+                directory_contents: ModDbDirectory {
+                    dir_path: p.into(),
+                    entries: BTreeMap::new(),
+                },
+            });
+        }
+
+        let p = absolute_dir_path(p)?;
+        ensure_state_dir(&p, init_here, true)?;
+
+        let entries = read_installed_mods(&p, false)?
+            .into_iter()
+            .map(|installed_mod| (installed_mod.id, installed_mod))
+            .collect();
+
+        Ok(ModDbView {
+            directory_contents: ModDbDirectory {
+                dir_path: p,
+                entries,
+            },
+        })
+    }
+
+    /// A database backed by a temporary directory that is deleted once the
+    /// database is dropped. Mostly for unit testing the enable/disable and
+    /// conflict logic without needing to set up a state directory.
+    #[cfg(test)]
+    pub(crate) fn new_in_memory() -> Self {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        Self::new_empty(DbDirGuard::TempDir(temp_dir), true)
+    }
+
+    /// Adds an already installed mod to an in-memory database.
+    #[cfg(test)]
+    pub(crate) fn insert_installed_mod(&mut self, installed_mod: InstalledModInfo) {
+        fs::create_dir_all(
+            self.view
+                .directory_contents
+                .dir_path
+                .join(get_mod_directory_name(
+                    installed_mod.id,
+                    &installed_mod.name,
+                )),
+        )
+        .unwrap();
+
+        self.register_installed_mod(installed_mod);
+    }
 
+    fn new_empty(dir_guard: DbDirGuard, lenient_extended_slots: bool) -> Self {
+        Self {
+            view: ModDbView {
+                directory_contents: ModDbDirectory {
+                    dir_path: dir_guard.dir_path(),
+                    entries: BTreeMap::new(),
+                },
+            },
+            mod_file_associations: EnabledModFileAssociations::new(lenient_extended_slots),
+            conflicting_variants_cache: RwLock::default(),
+            _dir_guard: dir_guard,
+        }
+    }
+
+    /// Adds a mod that was read from disk to the database.
+    ///
+    /// We are assuming that any serialized enabled mods do not conflict with
+    /// each other, since we should only serialize mods that have no conflicts.
+    /// If that's not the case, any variant that conflicts gets disabled.
+    fn register_installed_mod(&mut self, mut installed_mod: InstalledModInfo) {
+        self.invalidate_conflict_cache();
+
+        for var_info in installed_mod.installed_variants.values_mut() {
+            if !var_info.enabled {
                 continue;
             }
 
-            // We are assuming that any serialized enabled mods do not conflict with each
-            // other, since we should only serialize mods that have no conflicts. This will
-            // panic if that's not the case.
-            if let Some(mut installed_mod) =
-                InstalledModInfo::read_installed_mod_contents_dir(installed_mod_dir.path())?
+            let key = VariantAndId::new(installed_mod.id, var_info.name.clone());
+            if self
+                .mod_file_associations
+                .add_mod_info_to_global_lookup(&key, var_info)
+                .is_some()
             {
-                for var_info in installed_mod.installed_variants.values_mut() {
-                    if mod_file_associations
-                        .add_mod_info_to_global_lookup(&var_info.file_info)
-                        .is_some()
-                    {
-                        warn!(
-                            "Mod variant ({}) that was serialized as enabled is unable to be \
-                             enabled due to conflicts. This is either a bug in the manager or the \
-                             serialized data may have been modified outside of this tool. \
-                             Disabling...",
-                            var_info.name
-                        )
-                    }
-
-                    var_info.enabled = false;
-                }
+                warn!(
+                    "Mod variant ({}) that was serialized as enabled is unable to be enabled due \
+                     to conflicts. This is either a bug in the manager or the serialized data may \
+                     have been modified outside of this tool. Disabling...",
+                    var_info.name
+                );
 
-                installed_mods.insert(installed_mod.id, installed_mod);
+                var_info.enabled = false;
             }
         }
 
-        Ok(Self {
-            directory_contents: ModDbDirectory {
-                dir_path: p.into(),
-                entries: installed_mods,
-            },
-            mod_file_associations,
-            _lock_file,
-        })
+        self.view
+            .directory_contents
+            .entries
+            .insert(installed_mod.id, installed_mod);
     }
 
     pub(crate) fn journal_action_as_in_prog(&self, action: Action) -> ModDbResult<()> {
-        let in_prog_action_file_path = self.directory_contents.get_in_prog_action_path();
+        let in_prog_action_file_path = self.view.directory_contents.get_in_prog_action_path();
         assert!(!fs::exists(&in_prog_action_file_path)?);
 
         let in_prog = InProgAction::new(action);
@@ -205,30 +743,109 @@ impl ModDb {
     }
 
     pub(crate) fn get_in_prog_action_if_any(&self) -> ModDbResult<Option<InProgAction>> {
-        let in_prog_action_file_path = self.directory_contents.get_in_prog_action_path();
+        let in_prog_action_file_path = self.view.directory_contents.get_in_prog_action_path();
         let res = InProgAction::load_from_disk_if_present(&in_prog_action_file_path)?;
 
         Ok(res)
     }
 
     pub(crate) fn remove_in_prog_action(&self) -> ModDbResult<()> {
-        let in_prog_action_file_path = self.directory_contents.get_in_prog_action_path();
+        let in_prog_action_file_path = self.view.directory_contents.get_in_prog_action_path();
         fs::remove_file(in_prog_action_file_path)?;
 
         Ok(())
     }
 
+    /// Where the download of a variant is kept until it has been verified.
+    /// Nothing in here is ever treated as installed.
+    pub(crate) fn get_quarantine_dir_path(&self, key: &VariantAndId) -> Utf8PathBuf {
+        self.view.directory_contents.get_quarantine_dir_path(key)
+    }
+
+    /// Creates an empty quarantine directory (removing anything left over from
+    /// a previous attempt).
+    pub(crate) fn create_quarantine_dir(&self, p: &Utf8Path) -> ModDbResult<()> {
+        remove_dir_if_exists(p)?;
+        fs::create_dir_all(p)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn remove_quarantine_dir(&self, p: &Utf8Path) -> ModDbResult<()> {
+        remove_dir_if_exists(p)?;
+        Ok(())
+    }
+
+    /// Removes every quarantine directory. Only safe to call when no action is
+    /// in progress, since anything in quarantine is then just a leftover.
+    pub(crate) fn remove_all_quarantine_dirs(&self) -> ModDbResult<()> {
+        remove_dir_if_exists(
+            &self
+                .view
+                .directory_contents
+                .dir_path
+                .join(QUARANTINE_DIR_NAME),
+        )?;
+        Ok(())
+    }
+
+    /// `payload` is expected to point at a download in quarantine. The archive
+    /// is only moved into the mod directory once we know that it is safe to
+    /// expand.
+    ///
+    /// If `asset_types` is given, only the files that change those kinds of
+    /// assets are kept.
     pub(crate) fn add_variant(
         &mut self,
         key: &VariantAndId,
         payload: ScrapedBananaModData,
+        asset_types: Option<&[AssetType]>,
     ) -> ModDbResult<()> {
-        let mod_dir_path = self.directory_contents.get_path_to_mod(key.id);
+        // The archive is moved into a directory named after the variant before the
+        // mod info ever sees the name.
+        validate_variant_name(&key.variant_name)?;
+
+        // Never overwrite the files of an installed variant. Variants whose files were
+        // removed by gc are reinstalled in place.
+        let data_collected = match self.get_variant(key) {
+            Some(var_info) if !var_info.data_collected => {
+                return Err(ModDbError::VariantAlreadyExists(key.clone()));
+            },
+            Some(_) => true,
+            None => false,
+        };
 
-        let compressed_path = self.add_compressed_archive(
-            &mod_dir_path,
-            &key.variant_name,
+        ModPayloadParseInfo::new(&payload.variant_download_artifact)?;
+
+        self.invalidate_conflict_cache();
+
+        let mod_dir_path = match self.view.directory_contents.entries.contains_key(&key.id) {
+            false => self
+                .view
+                .directory_contents
+                .dir_path
+                .join(get_mod_directory_name(key.id, &payload.mod_name)),
+            true => self.view.directory_contents.get_path_to_mod(key.id)?,
+        };
+        let mod_variant_path = mod_dir_path.join(&key.variant_name);
+
+        // The variant is not installed (or gc left its directory behind empty), so
+        // anything in its directory was left behind by an add that was interrupted
+        // while expanding the archive.
+        if fs::exists(&mod_variant_path)? {
+            if !data_collected {
+                warn!(
+                    "Clearing out a partially installed copy of {} before installing it again...",
+                    key
+                );
+            }
+            fs::remove_dir_all(&mod_variant_path)?;
+        }
+
+        let compressed_path = move_archive_out_of_quarantine(
             &payload.variant_download_artifact,
+            &mod_variant_path,
+            &key.variant_name,
         )?;
 
         let mod_info_path = mod_dir_path.join(MOD_INFO_FILE_NAME);
@@ -236,59 +853,349 @@ impl ModDb {
         // Load mod info from disk.
         let mut mod_info: InstalledModInfo = match fs::exists(mod_info_path)? {
             false => InstalledModInfo::new(key.id, payload.mod_name, payload.version),
-            true => deserialize_data_from_path(&mod_dir_path.join(MOD_INFO_FILE_NAME))?,
+            true => deserialize_versioned_from_path(
+                &mod_dir_path.join(MOD_INFO_FILE_NAME),
+                &MOD_INFO_SCHEMA,
+                true,
+            )?,
         };
+        mod_info.source_url = Some(payload.source_url);
+        let collected_var_info = mod_info.installed_variants.remove(&key.variant_name);
+
+        let res = mod_info.add_variant(
+            key.variant_name.clone(),
+            mod_dir_path,
+            compressed_path,
+            payload.upstream,
+            asset_types,
+        );
 
-        mod_info.add_variant(key.variant_name.clone(), mod_dir_path, compressed_path)?;
+        if let Err(err) = res {
+            remove_dir_if_exists(&mod_variant_path)?;
 
-        Ok(())
+            // A variant directory going missing makes the whole mod fail to load.
+            if data_collected {
+                fs::create_dir_all(&mod_variant_path)?;
+            }
+
+            return Err(err);
+        }
+
+        if let Some(collected_var_info) = collected_var_info
+            && let Some(var_info) = mod_info.installed_variants.get_mut(&key.variant_name)
+        {
+            var_info.restore_user_state(collected_var_info);
+        }
+
+        self.register_installed_mod(mod_info);
+        self.sync_mod_info_to_disk(key.id)
     }
 
-    /// It's pretty annoying, but we need to write the compressed archive to
-    /// disk in some cases (looking at `unrar`) before we can parse it.
-    fn add_compressed_archive(
+    /// Replaces the files of an installed variant with a newly downloaded
+    /// version of it. Any overrides and whether or not the variant was enabled
+    /// are kept.
+    ///
+    /// The version being replaced is retained on disk until the next update so
+    /// that a bad update can be rolled back with `rollback_variant_update`.
+    /// Whatever was retained by an earlier update must already have been
+    /// dropped with `drop_previous_variant_version`.
+    ///
+    /// If the new version of an enabled variant now conflicts with another
+    /// enabled variant, it gets disabled and the conflicts are returned.
+    pub(crate) fn update_variant(
         &mut self,
-        mod_dir_path: &Utf8Path,
-        variant_name: &str,
-        compressed_payload: &[u8],
-    ) -> ModDbResult<Utf8PathBuf> {
-        let mod_artifact_path = mod_dir_path.join(variant_name);
-        fs::write(&mod_artifact_path, compressed_payload)?;
+        key: &VariantAndId,
+        payload: ScrapedBananaModData,
+    ) -> ModDbResult<Option<VariantConflictInfo>> {
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+        let expanded_mod_dir_path = mod_variant_path.join(EXPANDED_MOD_INFO_DIR_NAME);
+        let incoming_mod_dir_path = mod_variant_path.join(INCOMING_MOD_INFO_DIR_NAME);
+        let previous_mod_dir_path = mod_variant_path.join(PREVIOUS_MOD_INFO_DIR_NAME);
 
-        Ok(mod_artifact_path)
-    }
+        // Expand the new version next to the installed one first so that nothing
+        // installed is touched if the archive turns out to be bad.
+        let compressed_path = move_archive_out_of_quarantine(
+            &payload.variant_download_artifact,
+            &mod_variant_path,
+            &key.variant_name,
+        )?;
 
-    pub(crate) fn get_variant(&self, key: &VariantAndId) -> Option<&InstalledVariant> {
-        todo!()
+        remove_dir_if_exists(&incoming_mod_dir_path)?;
+        fs::create_dir(&incoming_mod_dir_path)?;
+
+        let parse_info = ModPayloadParseInfo::new(&compressed_path)?;
+        parse_info.expand_archive_to_disk(&incoming_mod_dir_path)?;
+        fs::remove_file(&compressed_path)?;
+
+        let new_file_info = VariantFileInfo::from_uncompressed_path(&incoming_mod_dir_path)?;
+        let new_size = dir_size(&mod_variant_path)?;
+
+        fs::rename(&expanded_mod_dir_path, &previous_mod_dir_path)?;
+        fs::rename(&incoming_mod_dir_path, &expanded_mod_dir_path)?;
+
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        let new_version_info = ModVariantVersioningInfo::new(&payload.upstream);
+
+        var_info.previous_version = Some(PreviousVariantVersion {
+            file_info: std::mem::replace(&mut var_info.file_info, new_file_info),
+            version_info: var_info.version_info.replace(new_version_info),
+            upstream: var_info.upstream.replace(payload.upstream),
+        });
+        var_info.file_size_bytes = Some(new_size);
+
+        // Mods installed before the page was recorded pick it up here.
+        self.view
+            .directory_contents
+            .get_mod_mut(key.id)?
+            .source_url
+            .get_or_insert(payload.source_url);
+
+        let conflicts = self.reregister_variant_assets(key)?;
+        self.sync_mod_info_to_disk(key.id)?;
+
+        Ok(conflicts)
     }
 
-    pub(crate) fn exists(&self, key: &VariantAndId) -> bool {
-        self.get_variant(key).is_some()
+    /// Restores the version of the variant that was installed before the last
+    /// update. Returns `false` if there is no previous version to restore.
+    pub(crate) fn rollback_variant_update(&mut self, key: &VariantAndId) -> ModDbResult<bool> {
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+        let expanded_mod_dir_path = mod_variant_path.join(EXPANDED_MOD_INFO_DIR_NAME);
+        let previous_mod_dir_path = mod_variant_path.join(PREVIOUS_MOD_INFO_DIR_NAME);
+
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        let previous_version = match var_info.previous_version.take() {
+            Some(previous_version) if fs::exists(&previous_mod_dir_path)? => previous_version,
+            _ => return Ok(false),
+        };
+
+        var_info.file_info = previous_version.file_info;
+        var_info.version_info = previous_version.version_info;
+        var_info.upstream = previous_version.upstream;
+
+        remove_dir_if_exists(&expanded_mod_dir_path)?;
+        fs::rename(&previous_mod_dir_path, &expanded_mod_dir_path)?;
+        self.view
+            .directory_contents
+            .get_variant_mut(key)?
+            .file_size_bytes = Some(dir_size(&mod_variant_path)?);
+
+        self.reregister_variant_assets(key)?;
+        self.sync_mod_info_to_disk(key.id)?;
+
+        Ok(true)
     }
 
-    /// Removes a variant from the database. This also removes all files
-    /// associated with the variant.
+    /// Classifies the expanded files of an installed variant again so that it
+    /// picks up any changes to the classification rules since it was
+    /// installed. Nothing is downloaded. Overrides and whether or not the
+    /// variant was enabled are kept.
     ///
-    /// Because we need to handle the case where a remove may have not fully
-    /// completed and only do part of the work, we need to keep going if we
-    /// discover that some state is not there (eg. variant doesn't exist in
-    /// database).
-    pub(crate) fn remove_variant(
+    /// If the variant is enabled and now conflicts with another enabled
+    /// variant, it gets disabled and the conflicts are returned.
+    pub(crate) fn reclassify_variant(
         &mut self,
         key: &VariantAndId,
-    ) -> ModDbResult<Option<InstalledVariant>> {
-        let mod_info = self.directory_contents.get_mod_mut_expected(key.id);
+    ) -> ModDbResult<Option<VariantConflictInfo>> {
+        let expanded_mod_dir_path = self.get_expanded_variant_path(key)?;
 
-        let var_info = mod_info.installed_variants.remove(&key.variant_name);
-        if var_info.is_none() {
-            warn!(
-                "Tried removing a mod variant that was not installed! ({})",
-                key
-            );
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if var_info.data_collected {
+            return Err(ModDbError::VariantDataCollected(key.clone()));
+        }
+
+        var_info.file_info = VariantFileInfo::from_uncompressed_path(&expanded_mod_dir_path)?;
+
+        let conflicts = self.reregister_variant_assets(key)?;
+        self.sync_mod_info_to_disk(key.id)?;
+
+        Ok(conflicts)
+    }
+
+    /// Cleans up after an add that did not finish, including any partially
+    /// expanded archive. If the variant was the first one of its mod, the mod
+    /// is not registered yet, so its directory can only be found with
+    /// `mod_name` (when it is known).
+    pub(crate) fn discard_incomplete_variant_add(
+        &mut self,
+        key: &VariantAndId,
+        mod_name: Option<&str>,
+    ) -> ModDbResult<()> {
+        // Reinstalling a variant whose files were removed by gc must not lose what we
+        // still know about it.
+        if self
+            .get_variant(key)
+            .is_some_and(|var_info| var_info.data_collected)
+        {
+            let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+            remove_dir_contents(&mod_variant_path)?;
+            return Ok(());
+        }
+
+        if self.view.directory_contents.entries.contains_key(&key.id) {
+            self.remove_variant(key)?;
+            return Ok(());
+        }
+
+        let Some(mod_name) = mod_name else {
+            return Ok(());
+        };
+
+        // Without a `mod_info.toml`, nothing but the interrupted add ever put anything
+        // in the directory.
+        let mod_dir_path = self
+            .view
+            .directory_contents
+            .dir_path
+            .join(get_mod_directory_name(key.id, mod_name));
+        match fs::exists(mod_dir_path.join(MOD_INFO_FILE_NAME))? {
+            false => remove_dir_if_exists(&mod_dir_path)?,
+            true => remove_dir_if_exists(&mod_dir_path.join(&key.variant_name))?,
+        }
+
+        Ok(())
+    }
+
+    /// Drops the version of a variant that was retained by its last update.
+    /// This needs to happen before an update starts so that if the update does
+    /// not finish, any retained version is known to be the one it replaced.
+    pub(crate) fn drop_previous_variant_version(&mut self, key: &VariantAndId) -> ModDbResult<()> {
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+        remove_dir_if_exists(&mod_variant_path.join(PREVIOUS_MOD_INFO_DIR_NAME))?;
+
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if var_info.previous_version.take().is_some() {
+            var_info.file_size_bytes = Some(dir_size(&mod_variant_path)?);
+            self.sync_mod_info_to_disk(key.id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cleans up after an update that did not finish. Since the previous
+    /// version is dropped before an update starts, a retained version can only
+    /// be the one that the update replaced, so it is put back along with what
+    /// was recorded about it.
+    pub(crate) fn discard_incomplete_variant_update(
+        &mut self,
+        key: &VariantAndId,
+    ) -> ModDbResult<()> {
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+        let expanded_mod_dir_path = mod_variant_path.join(EXPANDED_MOD_INFO_DIR_NAME);
+        let incoming_mod_dir_path = mod_variant_path.join(INCOMING_MOD_INFO_DIR_NAME);
+        let previous_mod_dir_path = mod_variant_path.join(PREVIOUS_MOD_INFO_DIR_NAME);
+
+        remove_dir_if_exists(&incoming_mod_dir_path)?;
+
+        let compressed_path = mod_variant_path.join(&key.variant_name);
+        if fs::exists(&compressed_path)? {
+            fs::remove_file(&compressed_path)?;
+        }
+
+        // The installed version was never moved out of the way.
+        if !fs::exists(&previous_mod_dir_path)? {
+            return Ok(());
+        }
+
+        // We may have been interrupted anywhere between moving the installed
+        // version out of the way and writing `mod_info.toml`.
+        if fs::exists(&expanded_mod_dir_path)? {
+            fs::rename(&expanded_mod_dir_path, &incoming_mod_dir_path)?;
+        }
+        fs::rename(&previous_mod_dir_path, &expanded_mod_dir_path)?;
+        remove_dir_if_exists(&incoming_mod_dir_path)?;
+
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if let Some(previous_version) = var_info.previous_version.take() {
+            var_info.file_info = previous_version.file_info;
+            var_info.version_info = previous_version.version_info;
+            var_info.upstream = previous_version.upstream;
+        }
+        var_info.file_size_bytes = Some(dir_size(&mod_variant_path)?);
+
+        self.reregister_variant_assets(key)?;
+        self.sync_mod_info_to_disk(key.id)?;
+
+        Ok(())
+    }
+
+    /// The assets used by a variant may change when its files change, so an
+    /// enabled variant needs to be removed and added back to the global lookup.
+    /// If it now conflicts with another enabled variant, it gets disabled.
+    fn reregister_variant_assets(
+        &mut self,
+        key: &VariantAndId,
+    ) -> ModDbResult<Option<VariantConflictInfo>> {
+        self.invalidate_conflict_cache();
+
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if !var_info.enabled {
+            return Ok(None);
+        }
+
+        self.mod_file_associations
+            .remove_variant_from_global_lookup(key);
+
+        let conflicts = self
+            .mod_file_associations
+            .add_mod_info_to_global_lookup(key, var_info);
+
+        if conflicts.is_some() {
+            var_info.set_enabled(false);
+        }
+
+        Ok(conflicts)
+    }
+
+    /// Writes the `mod_info.toml` of the mod to disk.
+    pub(crate) fn sync_mod_info_to_disk(&self, id: ModId) -> ModDbResult<()> {
+        let mod_info_path = self
+            .view
+            .directory_contents
+            .get_path_to_mod(id)?
+            .join(MOD_INFO_FILE_NAME);
+
+        serialize_versioned_to_path(
+            &mod_info_path,
+            self.view.directory_contents.get_mod(id)?,
+            &MOD_INFO_SCHEMA,
+        )?;
+        Ok(())
+    }
+
+    /// Removes a variant from the database. This also removes all files
+    /// associated with the variant.
+    ///
+    /// Because we need to handle the case where a remove may have not fully
+    /// completed and only do part of the work, we need to keep going if we
+    /// discover that some state is not there (eg. variant doesn't exist in
+    /// database).
+    pub(crate) fn remove_variant(
+        &mut self,
+        key: &VariantAndId,
+    ) -> ModDbResult<Option<InstalledVariant>> {
+        // Happens if we were interrupted while adding the first variant of a mod.
+        if !self.view.directory_contents.entries.contains_key(&key.id) {
+            warn!(
+                "Tried removing a mod variant of a mod that was not installed! ({})",
+                key
+            );
+            return Ok(None);
+        }
+
+        self.invalidate_conflict_cache();
+        let mod_info = self.view.directory_contents.get_mod_mut(key.id)?;
+
+        let var_info = mod_info.installed_variants.remove(&key.variant_name);
+        if var_info.is_none() {
+            warn!(
+                "Tried removing a mod variant that was not installed! ({})",
+                key
+            );
         }
 
         // Check if the directory exists anyways.
-        let mod_variant_path = self.directory_contents.get_path_to_mod_variant(key);
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
         if fs::exists(&mod_variant_path)? {
             fs::remove_dir_all(&mod_variant_path)?;
         }
@@ -300,8 +1207,47 @@ impl ModDb {
         todo!()
     }
 
-    pub(crate) fn installed_mods(&self) -> impl Iterator<Item = &InstalledModInfo> {
-        self.directory_contents.entries.values()
+    /// Adds tags to an installed mod and writes the change to disk. Returns
+    /// the tags that the mod did not already have.
+    pub(crate) fn add_tags(&mut self, id: ModId, tags: &[String]) -> ModDbResult<Vec<String>> {
+        let tags = tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<ModDbResult<Vec<_>>>()?;
+
+        let mod_info = self.view.directory_contents.get_mod_mut(id)?;
+        let mut added = Vec::new();
+        for tag in tags {
+            if !mod_info.tags.contains(&tag) {
+                mod_info.tags.push(tag.clone());
+                added.push(tag);
+            }
+        }
+
+        mod_info.tags.sort();
+        self.sync_mod_info_to_disk(id)?;
+
+        Ok(added)
+    }
+
+    /// Removes tags from an installed mod and writes the change to disk.
+    /// Returns the tags that the mod actually had.
+    pub(crate) fn remove_tags(&mut self, id: ModId, tags: &[String]) -> ModDbResult<Vec<String>> {
+        let tags = tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<ModDbResult<Vec<_>>>()?;
+
+        let mod_info = self.view.directory_contents.get_mod_mut(id)?;
+        let removed = tags
+            .into_iter()
+            .filter(|tag| mod_info.tags.contains(tag))
+            .collect::<Vec<_>>();
+        mod_info.tags.retain(|tag| !removed.contains(tag));
+
+        self.sync_mod_info_to_disk(id)?;
+
+        Ok(removed)
     }
 
     /// Like `remove_variant` except it expects that things may be randomly
@@ -320,423 +1266,3565 @@ impl ModDb {
     pub(crate) fn enable_variant(
         &mut self,
         key: &VariantAndId,
-    ) -> ModDbResult<Option<UnableToEnableReason>> {
-        let var_info = self.directory_contents.get_variant_mut_expected(key);
+    ) -> ModDbResult<EnableVariantOutcome> {
+        self.invalidate_conflict_cache();
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
 
         if var_info.enabled {
-            return Ok(Some(UnableToEnableReason::AlreadyEnabled));
+            return Ok(EnableVariantOutcome::AlreadyEnabled);
+        }
+
+        if var_info.data_collected {
+            return Ok(EnableVariantOutcome::DataCollected);
         }
 
+        let shared_slots = self
+            .mod_file_associations
+            .get_shared_extended_slots(key, var_info);
+
         if let Some(conflicts) = self
             .mod_file_associations
-            .add_mod_info_to_global_lookup(&var_info.file_info)
+            .add_mod_info_to_global_lookup(key, var_info)
         {
-            return Ok(Some(UnableToEnableReason::Conflicts(conflicts)));
+            return Ok(EnableVariantOutcome::Conflicts(conflicts));
         }
 
-        var_info.enabled = true;
+        var_info.set_enabled(true);
+        var_info.alternative_for = None;
 
-        Ok(None)
+        Ok(match shared_slots.is_empty() {
+            false => {
+                warn!(
+                    "{} shares extended skin slots with other enabled variants. Only one of them \
+                     will show up in game.",
+                    key
+                );
+                EnableVariantOutcome::EnabledWithWarnings(shared_slots)
+            },
+            true => EnableVariantOutcome::Enabled,
+        })
     }
 
-    pub(crate) fn disable_variant(&mut self, key: VariantAndId) {
-        let var_info = self.directory_contents.get_variant_mut_expected(&key);
-        debug_assert!(
-            !var_info.enabled,
-            "Tried disabled a mod variant that was already disabled! ({})",
-            key
-        );
-
-        var_info.enabled = false;
+    /// Records when the mod was last checked for updates and writes it to
+    /// disk.
+    pub(crate) fn set_last_update_check(
+        &mut self,
+        id: ModId,
+        checked_at: DateTime<Utc>,
+    ) -> ModDbResult<()> {
+        self.view
+            .directory_contents
+            .get_mod_mut(id)?
+            .last_update_check = Some(checked_at);
+        self.sync_mod_info_to_disk(id)
     }
 
-    pub(crate) fn get_variant_conflict_summary(
-        &self,
+    /// Records where the installed version of a variant can be downloaded from
+    /// (eg. after GameBanana moved the file). Returns `false` if the variant is
+    /// not installed.
+    pub(crate) fn set_variant_upstream_file(
+        &mut self,
         key: &VariantAndId,
-    ) -> Option<VariantConflictSummary> {
-        todo!()
+        upstream: UpstreamFileInfo,
+    ) -> ModDbResult<bool> {
+        self.modify_variant_and_sync(key, |var_info| var_info.upstream = Some(upstream))
     }
 
-    pub(crate) fn resolve_conflict(
-        &self,
-        conflict: &ConflictingModVariant,
-    ) -> ModDbConflictResolver {
-        todo!()
+    /// Marks a variant as no longer existing on GameBanana. Returns `false` if
+    /// the variant is not installed.
+    pub(crate) fn set_variant_orphaned_upstream(
+        &mut self,
+        key: &VariantAndId,
+        orphaned: bool,
+    ) -> ModDbResult<bool> {
+        self.modify_variant_and_sync(key, |var_info| var_info.orphaned_upstream = orphaned)
     }
 
-    pub(crate) fn detect_any_overrides_that_are_no_longer_needed(
-        &self,
-    ) -> Vec<(VariantAndId, VariantOverride)> {
-        todo!()
-    }
+    /// Sets (or clears with `None`) the note of a variant. Returns `false` if
+    /// the variant is not installed.
+    pub(crate) fn set_variant_notes(
+        &mut self,
+        key: &VariantAndId,
+        notes: Option<&str>,
+    ) -> ModDbResult<bool> {
+        let notes = match notes {
+            Some(notes) => normalize_note(notes)?,
+            None => None,
+        };
 
-    pub(crate) fn get_available_slots_to_swap_to(
-        &self,
-        slot: &SwappableAssetSlot,
-    ) -> AvailableSlotsToSwapToInfo {
-        todo!()
+        self.modify_variant_and_sync(key, |var_info| var_info.notes = notes)
     }
-}
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ModDbDirectory {
-    dir_path: Utf8PathBuf,
-    entries: HashMap<ModId, InstalledModInfo>,
-}
+    /// Sets (or clears with `None`) the alias that the variant can be referred
+    /// to by instead of its file name. An alias can not be the alias or file
+    /// name of any other variant of the mod. Returns `false` if the variant is
+    /// not installed.
+    pub(crate) fn set_variant_alias(
+        &mut self,
+        key: &VariantAndId,
+        alias: Option<&str>,
+    ) -> ModDbResult<bool> {
+        let alias = alias.map(normalize_alias).transpose()?;
+
+        if let Some(alias) = &alias
+            && let Some(mod_info) = self.view.directory_contents.entries.get(&key.id)
+            && let Some(taken_by) = mod_info.installed_variants.values().find(|var_info| {
+                var_info.name != key.variant_name
+                    && (var_info.name == *alias || var_info.alias.as_ref() == Some(alias))
+            })
+        {
+            return Err(ModDbError::AliasTaken(
+                alias.clone(),
+                VariantAndId::new(key.id, taken_by.name.clone()),
+            ));
+        }
 
-impl ModDbDirectory {
-    fn get_mod_mut_expected(&mut self, key: ModId) -> &mut InstalledModInfo {
-        self.entries
-            .get_mut(&key)
-            .unwrap_or_else(|| panic!("Expected to have a mod for mod ID {}", key))
+        self.modify_variant_and_sync(key, |var_info| var_info.alias = alias)
     }
 
-    fn get_variant_mut_expected(&mut self, key: &VariantAndId) -> &mut InstalledVariant {
-        let mod_info = self.get_mod_mut_expected(key.id);
-        mod_info.get_variant_mut_expected(&key.variant_name)
-    }
+    fn modify_variant_and_sync(
+        &mut self,
+        key: &VariantAndId,
+        modify_f: impl FnOnce(&mut InstalledVariant),
+    ) -> ModDbResult<bool> {
+        let var_info = match self
+            .view
+            .directory_contents
+            .entries
+            .get_mut(&key.id)
+            .and_then(|mod_info| mod_info.installed_variants.get_mut(&key.variant_name))
+        {
+            Some(var_info) => var_info,
+            None => return Ok(false),
+        };
 
-    fn get_path_to_mod(&self, id: ModId) -> Utf8PathBuf {
-        let mod_name = &self.get_mod_name_expected(id);
-        self.dir_path.join(get_mod_directory_name(id, mod_name))
-    }
+        modify_f(var_info);
+        self.sync_mod_info_to_disk(key.id)?;
 
-    fn get_path_to_mod_variant(&self, key: &VariantAndId) -> Utf8PathBuf {
-        let mod_name = &self.get_mod_name_expected(key.id);
-        self.dir_path.join(get_path_section_from_key(key, mod_name))
+        Ok(true)
     }
 
-    fn get_mod_name_expected(&self, id: ModId) -> &str {
-        self.entries
-            .get(&id)
-            .expect(
-                "Missing mod entry when constructing path to it's directory! This should never \
-                 happen!",
-            )
-            .name
-            .as_str()
-    }
+    /// Changes the file name that an installed variant is tracked under (eg.
+    /// when the author re-uploads the mod under a different file name). Fails
+    /// if a variant with the new name is already installed.
+    pub(crate) fn rename_variant(
+        &mut self,
+        key: &VariantAndId,
+        new_name: &str,
+    ) -> ModDbResult<VariantAndId> {
+        let new_key = VariantAndId::new(key.id, new_name.to_string());
+        if self.exists(&new_key) {
+            return Err(ModDbError::VariantAlreadyExists(new_key));
+        }
 
-    fn get_in_prog_action_path(&self) -> Utf8PathBuf {
-        self.dir_path.join(IN_PROG_ACTION_FILE_NAME)
-    }
-}
+        self.invalidate_conflict_cache();
 
-fn get_path_section_from_key(key: &VariantAndId, mod_name: &str) -> Utf8PathBuf {
-    let mod_dir_name = get_mod_directory_name(key.id, mod_name);
-    format!("{}/{}", mod_dir_name, key.variant_name).into()
-}
+        fs::rename(
+            self.view.directory_contents.get_path_to_mod_variant(key)?,
+            self.view
+                .directory_contents
+                .get_path_to_mod_variant(&new_key)?,
+        )?;
 
-fn get_mod_directory_name(id: ModId, mod_name: &str) -> String {
-    format!("{}_{}", mod_name, id)
-}
+        let mod_info = self.view.directory_contents.get_mod_mut(key.id)?;
+        let mut var_info = mod_info
+            .installed_variants
+            .remove(&key.variant_name)
+            .ok_or_else(|| ModDbError::UnknownVariant(key.clone()))?;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub(crate) struct InstalledModInfo {
-    /// The ID of the mod on GameBanana.
-    pub id: ModId,
+        var_info.name = new_name.to_string();
+        var_info.orphaned_upstream = false;
+        mod_info
+            .installed_variants
+            .insert(new_name.to_string(), var_info);
 
-    /// The name of the mod on GameBanana.
-    pub name: String,
+        self.mod_file_associations
+            .rename_variant_in_lookup(key, &new_key);
+        self.sync_mod_info_to_disk(key.id)?;
 
-    /// Because there can be different variants available to download for a
-    /// given mod, we need to also be able to specify which one we are using.
-    pub installed_variants: HashMap<String, InstalledVariant>,
+        Ok(new_key)
+    }
 
-    // TODO: Determine if the mod itself or the variant should hold the version info...
-    /// The version that we have in the mod manager.
-    pub version: Option<String>,
-}
+    /// Makes the inactive alternative `key` the enabled variant of `slot`.
+    /// Whatever variant was enabled in the slot is disabled and becomes an
+    /// alternative for it instead. Returns the variant that was replaced (if
+    /// any).
+    pub(crate) fn swap_active_alternative(
+        &mut self,
+        slot: &CharSkinSlotValue,
+        key: &VariantAndId,
+    ) -> ModDbResult<Option<VariantAndId>> {
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if var_info.alternative_for.as_ref() != Some(slot) {
+            return Err(ModDbError::NotAnAlternative(key.clone(), slot.clone()));
+        }
 
-impl InstalledModInfo {
-    fn new(id: ModId, name: String, version: Option<String>) -> Self {
-        Self {
-            id,
-            name,
-            installed_variants: HashMap::default(),
-            version,
+        if var_info.data_collected {
+            return Err(ModDbError::VariantDataCollected(key.clone()));
         }
-    }
 
-    fn add_variant(
-        &mut self,
-        var_name: String,
-        mod_dir_path: Utf8PathBuf,
-        compressed_path: Utf8PathBuf,
-    ) -> ModDbResult<()> {
-        let mod_variant_path = mod_dir_path.join(&var_name);
-        fs::create_dir(&mod_variant_path)?;
+        let active = self
+            .mod_file_associations
+            .get_any_mod_associated_with_asset(&AssetSlot::CharacterSkin(slot.clone()))
+            .cloned();
 
-        let expanded_mod_dir_path = mod_variant_path.join(EXPANDED_MOD_INFO_DIR_NAME);
-        fs::create_dir(&expanded_mod_dir_path)?;
+        if let Some(active) = &active {
+            self.disable_variant(active);
+        }
 
-        let parse_info = ModPayloadParseInfo::new(&compressed_path)?;
-        parse_info.expand_archive_to_disk(&expanded_mod_dir_path)?;
+        if !self.enable_variant(key)?.is_enabled() {
+            // Put the previously active variant back.
+            if let Some(active) = &active {
+                self.enable_variant(active)?;
+            }
 
-        let variant_file_info = VariantFileInfo::from_uncompressed_path(&expanded_mod_dir_path);
+            return Err(ModDbError::AlternativeConflictsElsewhere(key.clone()));
+        }
 
-        let installed_var = InstalledVariant::new(var_name.clone(), variant_file_info);
-        self.installed_variants.insert(var_name, installed_var);
+        if let Some(active) = &active {
+            self.view
+                .directory_contents
+                .get_variant_mut(active)?
+                .alternative_for = Some(slot.clone());
+            self.sync_mod_info_to_disk(active.id)?;
+        }
+        self.sync_mod_info_to_disk(key.id)?;
 
-        Ok(())
+        Ok(active)
     }
 
-    fn get_variant_mut_expected(&mut self, key: &str) -> &mut InstalledVariant {
-        self.installed_variants
-            .get_mut(key)
-            .unwrap_or_else(|| panic!("Expected to have a variant for the key {}", key))
-    }
-}
+    pub(crate) fn disable_variant(&mut self, key: &VariantAndId) -> DisableVariantOutcome {
+        self.invalidate_conflict_cache();
 
-impl InstalledModInfo {
-    fn read_installed_mod_contents_dir(installed_mod_path: &Utf8Path) -> ModDbResult<Option<Self>> {
-        let mod_info_path: Utf8PathBuf = installed_mod_path.join(MOD_INFO_FILE_NAME);
+        let var_info = match self
+            .view
+            .directory_contents
+            .entries
+            .get_mut(&key.id)
+            .and_then(|mod_info| mod_info.installed_variants.get_mut(&key.variant_name))
+        {
+            Some(var_info) => var_info,
+            None => return DisableVariantOutcome::NotInstalled,
+        };
 
-        // To keep things simple (at least for now), we're going to assume that the
-        // directory structure inside each installed mod directory is always valid. If
-        // it's not, we are just going to warn the user at a minimum since validation is
-        // going to add a lot of complexity and should only happen if the user does
-        // manual intervention.
-        if !mod_info_path.exists() {
-            warn!(
-                "Mod {mod_info_path:?} has no \"{MOD_INFO_FILE_NAME}\" file inside it's \
-                 directory. This should never happen. Consider deleting this mod and adding \
-                 again. Skipping..."
-            );
+        if !var_info.enabled {
+            return DisableVariantOutcome::WasAlreadyDisabled;
+        }
 
-            // TODO: Consider asking the user if we should remove this entry?
+        self.mod_file_associations
+            .remove_variant_from_global_lookup(key);
+        var_info.set_enabled(false);
 
-            return Ok(None);
+        DisableVariantOutcome::Disabled
+    }
+
+    /// Removes the files of a disabled variant while keeping its entry in
+    /// `mod_info.toml`, so that it can be reinstalled with add later. Returns
+    /// how many bytes were freed.
+    pub(crate) fn collect_variant_data(&mut self, key: &VariantAndId) -> ModDbResult<u64> {
+        let mod_variant_path = self.view.directory_contents.get_path_to_mod_variant(key)?;
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if var_info.enabled || var_info.data_collected {
+            return Ok(0);
         }
 
-        let mod_info: InstalledModInfo = deserialize_data_from_path(&mod_info_path)?;
+        let reclaimed_bytes = match fs::exists(&mod_variant_path)? {
+            false => 0,
+            true => dir_size(&mod_variant_path)?,
+        };
 
-        // Quick simple verification check for the installed mod variants.
-        for installed_variant_name in mod_info.installed_variants.keys() {
-            let mod_variant_dir_path = mod_info_path.join(installed_variant_name);
+        // Recorded before anything is removed so that an interrupted gc never leaves
+        // behind a variant that looks like it can be enabled.
+        var_info.data_collected = true;
+        var_info.file_size_bytes = Some(0);
+        var_info.previous_version = None;
+        self.sync_mod_info_to_disk(key.id)?;
 
-            if !mod_variant_dir_path.exists() {
-                warn!(
-                    "Found an installed mod variant that we do not actually have installed data \
-                     for ({mod_variant_dir_path:?})! Will maybe add automatic resolution support \
-                     in the future, but for now try deleting this mod and installing again. \
-                     Skipping..."
-                );
-                return Ok(None);
+        // The directory itself is kept since a mod with a missing variant directory
+        // is not loaded.
+        remove_dir_contents(&mod_variant_path)?;
+
+        Ok(reclaimed_bytes)
+    }
+
+    /// The installed mods (and their variants) that match the query, in ID
+    /// order. Uses the cached conflicts if the query asks for them.
+    pub(crate) fn query(&self, query: &ModQuery) -> Vec<ModSummary> {
+        let conflicting = query.has_conflicts.then(|| self.conflicting_variants());
+        self.view
+            .query_with_conflicting(query, conflicting.as_deref())
+    }
+
+    /// Gets the conflicts of every installed variant that is unable to be
+    /// enabled because it conflicts with an enabled variant.
+    pub(crate) fn get_all_conflicts(&self) -> Vec<VariantConflictInfo> {
+        self.view
+            .get_all_conflicts_with_lookup(&self.mod_file_associations)
+    }
+
+    /// The variants that `key` is in a conflict with, either as the variant
+    /// that is unable to be enabled or as the enabled variant that blocks
+    /// another.
+    pub(crate) fn conflict_partners(&self, key: &VariantAndId) -> HashSet<VariantAndId> {
+        let mut partners = HashSet::new();
+
+        for var_conflicts in self.get_all_conflicts() {
+            for conflict in var_conflicts.conflicts {
+                if var_conflicts.key == *key {
+                    partners.insert(conflict.key);
+                } else if conflict.key == *key {
+                    partners.insert(var_conflicts.key.clone());
+                }
             }
         }
 
-        Ok(Some(mod_info))
+        partners
     }
-}
 
-/// Info that we can use to detect version changes.
-///
-/// There is really not much data available to detect version changes on
-/// GameBanana, so likely in most cases we are going to have to rely on file
-/// publish dates.
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
-struct ModVariantVersioningInfo {
-    publish_date: DateTime<Utc>,
-}
+    fn conflicting_variants(&self) -> Arc<HashSet<VariantAndId>> {
+        if let Some(cached) = self
+            .conflicting_variants_cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            return cached.clone();
+        }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+        let conflicting = Arc::new(keys_in_conflicts(self.get_all_conflicts()));
+
+        *self
+            .conflicting_variants_cache
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(conflicting.clone());
+
+        conflicting
+    }
+
+    fn invalidate_conflict_cache(&mut self) {
+        *self
+            .conflicting_variants_cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Writes every current conflict to a TOML file at the given path.
+    pub(crate) fn export_conflict_report(&self, path: &Utf8Path) -> ModDbResult<()> {
+        let report = ConflictReport::new(self.get_all_conflicts());
+        serialize_data_to_path(path, &report)?;
+
+        Ok(())
+    }
+
+    /// Starts resolving the conflicts that `new_key` has with a single enabled
+    /// variant. Nothing changes until the resolver is committed.
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        new_key: &VariantAndId,
+        conflict: &ConflictingModVariant,
+    ) -> ModDbConflictResolver<'_> {
+        ModDbConflictResolver {
+            db: self,
+            new_key: new_key.clone(),
+            existing_key: conflict.key.clone(),
+            // Conflicts are popped off of the end.
+            conflicts_remaining: conflict.slots.iter().rev().cloned().collect(),
+            resolving: None,
+            pending_changes: HashMap::new(),
+        }
+    }
+
+    /// The slots that `slot` could be moved to without conflicting with an
+    /// enabled variant. Extended skin slots (`c08`+) are only offered if the
+    /// ARCropolis config of an enabled variant declares them, since the game
+    /// is unable to load them otherwise.
+    pub(crate) fn get_available_slots_to_swap_to(
+        &self,
+        slot: &SwappableAssetSlot,
+    ) -> AvailableSlotsToSwapToInfo {
+        match slot {
+            SwappableAssetSlot::CharacterSkin(char_slot) => {
+                AvailableSlotsToSwapToInfo::CharacterSkin(
+                    SkinSlotValue::normal_skin_slots()
+                        .chain(
+                            self.mod_file_associations
+                                .declared_extra_slots_of_char(char_slot.char_key()),
+                        )
+                        .filter(|skin_slot| *skin_slot != char_slot.skin_slot_idx())
+                        .filter(|skin_slot| {
+                            let slot = AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                                char_slot.char_key().to_string(),
+                                *skin_slot,
+                            ));
+
+                            self.mod_file_associations
+                                .get_any_mod_associated_with_asset(&slot)
+                                .is_none()
+                        })
+                        .collect(),
+                )
+            },
+        }
+    }
+
+    /// Moves an asset of a variant from `old_slot` to `new_slot` by adding an
+    /// override to the variant and writes the change to disk. An enabled
+    /// variant is taken out of the global lookup while the override is applied
+    /// and is added back in with the new slot.
+    pub(crate) fn swap_variant_slot(
+        &mut self,
+        key: &VariantAndId,
+        old_slot: AssetSlot,
+        new_slot: AssetSlot,
+    ) -> ModDbResult<()> {
+        let slot_override = VariantOverride::new(&old_slot, &new_slot)
+            .ok_or_else(|| ModDbError::MismatchedSlotSwap(old_slot.clone(), new_slot.clone()))?;
+
+        if let Some(existing_key) = self
+            .mod_file_associations
+            .get_any_mod_associated_with_asset(&new_slot)
+            && existing_key != key
+        {
+            return Err(ModDbError::SlotOccupied(existing_key.clone()));
+        }
+
+        self.invalidate_conflict_cache();
+        let var_info = self.view.directory_contents.get_variant_mut(key)?;
+        if !var_info.asset_slots().contains(&old_slot) {
+            return Err(ModDbError::SlotNotUsedByVariant(key.clone(), old_slot));
+        }
+
+        if let AssetSlot::CharacterSkin(new_char_slot) = &new_slot
+            && !new_char_slot.skin_slot_idx().is_normal_skin_slot()
+            && !self
+                .mod_file_associations
+                .is_extra_slot_declared(new_char_slot)
+            && !var_info
+                .file_info
+                .declared_extra_slots()
+                .any(|slot| slot == *new_char_slot)
+        {
+            warn!(
+                "No enabled mod declares \"{}\" in its ARCropolis config, so the game may be \
+                 unable to load {} from it.",
+                new_char_slot, key
+            );
+        }
+
+        if var_info.enabled {
+            self.mod_file_associations
+                .remove_variant_from_global_lookup(key);
+        }
+
+        var_info.overrides.push(slot_override);
+
+        if var_info.enabled
+            && let Some(conflicts) = self
+                .mod_file_associations
+                .add_mod_info_to_global_lookup(key, var_info)
+        {
+            // Put the variant back the way it was.
+            var_info.overrides.pop();
+            self.mod_file_associations
+                .add_mod_info_to_global_lookup(key, var_info);
+
+            return Err(ModDbError::SlotOccupied(conflicts.conflicts[0].key.clone()));
+        }
+
+        self.sync_mod_info_to_disk(key.id)
+    }
+}
+
+impl ModDbView {
+    pub(crate) fn get_variant(&self, key: &VariantAndId) -> Option<&InstalledVariant> {
+        self.directory_contents
+            .entries
+            .get(&key.id)
+            .and_then(|mod_info| mod_info.installed_variants.get(&key.variant_name))
+    }
+
+    /// The slot overrides applied to a variant. Empty if the variant is not
+    /// installed.
+    pub(crate) fn overrides_for(&self, key: &VariantAndId) -> &[VariantOverride] {
+        self.get_variant(key)
+            .map(|var_info| var_info.overrides.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Path to the directory containing the expanded files of the variant.
+    pub(crate) fn get_expanded_variant_path(&self, key: &VariantAndId) -> ModDbResult<Utf8PathBuf> {
+        Ok(self
+            .directory_contents
+            .get_path_to_mod_variant(key)?
+            .join(EXPANDED_MOD_INFO_DIR_NAME))
+    }
+
+    pub(crate) fn exists(&self, key: &VariantAndId) -> bool {
+        self.get_variant(key).is_some()
+    }
+
+    pub(crate) fn installed_mods(&self) -> impl Iterator<Item = &InstalledModInfo> {
+        self.directory_contents.entries.values()
+    }
+
+    /// Finds an installed mod by its ID or its exact name.
+    pub(crate) fn find_installed_mod(&self, ident: &ModIdentifier) -> Option<&InstalledModInfo> {
+        match ident {
+            ModIdentifier::Id(id) => self.directory_contents.entries.get(id),
+            ModIdentifier::Name(name) => self.installed_mods().find(|m| m.name == *name),
+        }
+    }
+
+    /// The key of the installed variant that uses the variant name of `key` as
+    /// its alias. `key` is returned as is if no variant of the mod has the
+    /// alias (ie. it is a file name).
+    pub(crate) fn resolve_variant_alias(&self, key: VariantAndId) -> VariantAndId {
+        let aliased = self
+            .directory_contents
+            .entries
+            .get(&key.id)
+            .and_then(|mod_info| mod_info.find_variant_by_alias(&key.variant_name));
+
+        match aliased {
+            Some(var_info) => VariantAndId::new(key.id, var_info.name.clone()),
+            None => key,
+        }
+    }
+
+    /// Variants that have been disabled since before `cutoff` and still have
+    /// their files on disk. Variants without a recorded disable time are never
+    /// included.
+    pub(crate) fn variants_disabled_before(&self, cutoff: DateTime<Utc>) -> Vec<VariantAndId> {
+        self.installed_variants()
+            .filter(|(_, var_info)| !var_info.enabled && !var_info.data_collected)
+            .filter(|(_, var_info)| {
+                var_info
+                    .last_disabled_at
+                    .is_some_and(|disabled_at| disabled_at <= cutoff)
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// The total number of bytes used by every installed mod.
+    pub(crate) fn estimate_disk_usage(&self) -> u64 {
+        self.installed_mods()
+            .map(|mod_info| self.estimate_disk_usage_for_mod(mod_info.id))
+            .sum()
+    }
+
+    /// The number of bytes used by the variants of a mod. Uses the size that
+    /// was recorded when each variant was installed and only walks the files
+    /// of variants where it was not.
+    pub(crate) fn estimate_disk_usage_for_mod(&self, id: ModId) -> u64 {
+        let mod_info = match self.directory_contents.entries.get(&id) {
+            Some(mod_info) => mod_info,
+            None => return 0,
+        };
+
+        mod_info
+            .installed_variants
+            .values()
+            .map(|var_info| self.estimate_disk_usage_for_variant(mod_info, var_info))
+            .sum()
+    }
+
+    /// The number of bytes used by the enabled skins of each character (keyed
+    /// by the character key, eg. `mario`). A variant with skins for several
+    /// characters counts towards each of them.
+    pub(crate) fn usage_by_character(&self) -> BTreeMap<String, u64> {
+        let mut usage: BTreeMap<String, u64> = BTreeMap::new();
+        for mod_info in self.installed_mods() {
+            for var_info in mod_info.installed_variants.values() {
+                if !var_info.enabled {
+                    continue;
+                }
+
+                let char_keys = var_info
+                    .asset_slots()
+                    .into_iter()
+                    .filter_map(|slot| match slot {
+                        AssetSlot::CharacterSkin(char_slot) => {
+                            Some(char_slot.char_key().to_string())
+                        },
+                        _ => None,
+                    })
+                    .collect::<BTreeSet<_>>();
+
+                if char_keys.is_empty() {
+                    continue;
+                }
+
+                let size = self.estimate_disk_usage_for_variant(mod_info, var_info);
+                for char_key in char_keys {
+                    *usage.entry(char_key).or_default() += size;
+                }
+            }
+        }
+
+        usage
+    }
+
+    fn estimate_disk_usage_for_variant(
+        &self,
+        mod_info: &InstalledModInfo,
+        var_info: &InstalledVariant,
+    ) -> u64 {
+        if let Some(size) = var_info.file_size_bytes {
+            return size;
+        }
+
+        let key = VariantAndId::new(mod_info.id, var_info.name.clone());
+        let variant_path = self
+            .directory_contents
+            .dir_path
+            .join(get_path_section_from_key(&key, &mod_info.name));
+
+        dir_size(&variant_path).unwrap_or_else(|err| {
+            warn!("Unable to get the size of {}: {}", variant_path, err);
+            0
+        })
+    }
+
+    /// Groups the installed variants that look like copies of the same upload:
+    /// ones installed from archives with the same checksum and size, and ones
+    /// whose expanded files are nearly all identical (eg. a repost with a
+    /// different readme). Variants that already share an archive are not
+    /// compared by their files. Nothing is ever removed.
+    ///
+    /// There is no record of the hashes of the installed files, so every
+    /// variant that is compared by its files gets all of them read here.
+    pub(crate) fn find_duplicate_variants(&self) -> ModDbResult<Vec<DuplicateVariantGroup>> {
+        let mut keys = Vec::new();
+        let mut by_archive: BTreeMap<(String, Option<u64>), Vec<VariantAndId>> = BTreeMap::new();
+        for mod_info in self.installed_mods() {
+            // There are no files left to compare for variants collected by gc.
+            for var_info in mod_info
+                .installed_variants
+                .values()
+                .filter(|var_info| !var_info.data_collected)
+            {
+                let key = VariantAndId::new(mod_info.id, var_info.name.clone());
+
+                match var_info
+                    .upstream
+                    .as_ref()
+                    .filter(|upstream| !upstream.md5.is_empty())
+                {
+                    Some(upstream) => by_archive
+                        .entry((upstream.md5.to_lowercase(), upstream.size))
+                        .or_default()
+                        .push(key),
+                    None => keys.push(key),
+                }
+            }
+        }
+
+        let mut groups = Vec::new();
+        for ((md5, _), variants) in by_archive {
+            match variants.len() {
+                1 => keys.extend(variants),
+                _ => groups.push(DuplicateVariantGroup {
+                    reason: DuplicateReason::SameArchive { md5 },
+                    variants,
+                }),
+            }
+        }
+
+        keys.sort_by(|a, b| (a.id, &a.variant_name).cmp(&(b.id, &b.variant_name)));
+        let trees = keys
+            .iter()
+            .map(|key| Ok(hash_file_tree(&self.get_expanded_variant_path(key)?)?))
+            .collect::<ModDbResult<Vec<_>>>()?;
+
+        // Every variant starts out in its own group, and groups are merged whenever
+        // two of their variants are similar enough.
+        let mut group_idxs = (0..keys.len()).collect::<Vec<_>>();
+        let mut min_similarities = vec![None::<f32>; keys.len()];
+        for i in 0..keys.len() {
+            for j in i + 1..keys.len() {
+                let similarity = file_tree_similarity(&trees[i], &trees[j]);
+                if similarity < MIN_DUPLICATE_FILE_SIMILARITY {
+                    continue;
+                }
+
+                let (keep, merged) = (group_idxs[i], group_idxs[j]);
+                for group_idx in group_idxs.iter_mut().filter(|idx| **idx == merged) {
+                    *group_idx = keep;
+                }
+
+                min_similarities[keep] = [min_similarities[keep], min_similarities[merged]]
+                    .into_iter()
+                    .flatten()
+                    .fold(similarity, f32::min)
+                    .into();
+            }
+        }
+
+        for (group_idx, similarity) in min_similarities.into_iter().enumerate() {
+            let Some(similarity) = similarity else {
+                continue;
+            };
+
+            let variants = keys
+                .iter()
+                .zip(group_idxs.iter())
+                .filter(|(_, idx)| **idx == group_idx)
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+
+            // Groups that were merged into another one still have their old similarity.
+            if variants.len() > 1 {
+                groups.push(DuplicateVariantGroup {
+                    reason: DuplicateReason::SimilarFiles { similarity },
+                    variants,
+                });
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Every installed variant of the mods that have the (normalized) tag.
+    pub(crate) fn variants_with_tag(&self, tag: &str) -> Vec<VariantAndId> {
+        let mut keys = self
+            .installed_mods()
+            .filter(|mod_info| mod_info.has_tag(tag))
+            .flat_map(|mod_info| {
+                mod_info
+                    .installed_variants
+                    .keys()
+                    .map(|variant_name| VariantAndId::new(mod_info.id, variant_name.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        keys.sort_by(|a, b| (a.id, &a.variant_name).cmp(&(b.id, &b.variant_name)));
+        keys
+    }
+
+    /// All installed variants that were enabled within the given duration of
+    /// now.
+    pub(crate) fn recently_enabled_variants(
+        &self,
+        since: Duration,
+    ) -> impl Iterator<Item = (VariantAndId, &InstalledVariant)> {
+        let cutoff = Utc::now() - since;
+
+        self.installed_variants()
+            .filter(move |(_, var_info)| var_info.enabled_since(cutoff))
+    }
+
+    /// Overrides that could be dropped to move the assets back to their
+    /// original slots, since nothing uses those slots anymore. A slot is still
+    /// in use if another variant (enabled or not) has an override into it.
+    pub(crate) fn detect_any_overrides_that_are_no_longer_needed(
+        &self,
+    ) -> Vec<(VariantAndId, VariantOverride)> {
+        self.installed_variants()
+            .flat_map(|(key, var_info)| {
+                let moves = var_info.overridden_slots();
+
+                var_info
+                    .overrides
+                    .iter()
+                    .enumerate()
+                    .filter(move |(override_idx, _)| {
+                        moves.iter().filter(|(idx, ..)| idx == override_idx).all(
+                            |(_, old_slot, _)| {
+                                !self.is_slot_used_by_enabled_variant(old_slot)
+                                    && !self.has_any_override_for_slot(old_slot)
+                            },
+                        )
+                    })
+                    .map(move |(_, slot_override)| (key.clone(), slot_override.clone()))
+            })
+            .collect()
+    }
+
+    fn is_slot_used_by_enabled_variant(&self, slot: &AssetSlot) -> bool {
+        self.installed_variants()
+            .any(|(_, var_info)| var_info.enabled && var_info.asset_slots().contains(slot))
+    }
+
+    /// Every slot override applied to an installed variant (enabled or not).
+    pub(crate) fn iter_overrides(&self) -> impl Iterator<Item = (VariantAndId, &VariantOverride)> {
+        self.installed_variants().flat_map(|(key, var_info)| {
+            var_info
+                .overrides
+                .iter()
+                .map(move |slot_override| (key.clone(), slot_override))
+        })
+    }
+
+    /// Whether any installed variant (enabled or not) has an override that
+    /// moves one of its assets into `slot`.
+    pub(crate) fn has_any_override_for_slot(&self, slot: &AssetSlot) -> bool {
+        self.installed_variants().any(|(_, var_info)| {
+            var_info
+                .overridden_slots()
+                .iter()
+                .any(|(_, _, new_slot)| new_slot == slot)
+        })
+    }
+
+    /// Same as `ModDb::get_all_conflicts`, but works out which assets the
+    /// enabled variants use from scratch since a view does not keep track of
+    /// that.
+    pub(crate) fn compute_all_conflicts(
+        &self,
+        lenient_extended_slots: bool,
+    ) -> Vec<VariantConflictInfo> {
+        let mut lookup = EnabledModFileAssociations::new(lenient_extended_slots);
+        for (key, var_info) in self
+            .installed_variants()
+            .filter(|(_, var_info)| var_info.enabled)
+        {
+            lookup.add_mod_info_to_global_lookup(&key, var_info);
+        }
+
+        self.get_all_conflicts_with_lookup(&lookup)
+    }
+
+    /// Like `ModDb::query`, but any conflicts that the query asks for are found
+    /// by building a lookup of the enabled variants.
+    pub(crate) fn query_computing_conflicts(
+        &self,
+        query: &ModQuery,
+        lenient_extended_slots: bool,
+    ) -> Vec<ModSummary> {
+        let conflicting = query
+            .has_conflicts
+            .then(|| keys_in_conflicts(self.compute_all_conflicts(lenient_extended_slots)));
+
+        self.query_with_conflicting(query, conflicting.as_ref())
+    }
+
+    /// `conflicting` must be given if the query asks for conflicts.
+    fn query_with_conflicting(
+        &self,
+        query: &ModQuery,
+        conflicting: Option<&HashSet<VariantAndId>>,
+    ) -> Vec<ModSummary> {
+        let name = query.name.as_deref().map(str::to_lowercase);
+
+        self.installed_mods()
+            .filter(|mod_info| {
+                name.as_deref()
+                    .is_none_or(|name| mod_info.name.to_lowercase().contains(name))
+            })
+            .filter(|mod_info| query.tags.iter().all(|tag| mod_info.has_tag(tag)))
+            .filter_map(|mod_info| {
+                let variants = mod_info
+                    .installed_variants
+                    .values()
+                    .filter(|var_info| {
+                        var_info.matches_query(
+                            &VariantAndId::new(mod_info.id, var_info.name.clone()),
+                            query,
+                            conflicting,
+                        )
+                    })
+                    .map(|var_info| var_info.name.clone())
+                    .collect::<Vec<_>>();
+
+                // Mods without any variants still match queries that only look at the mod.
+                if variants.is_empty() && query.filters_variants() {
+                    return None;
+                }
+
+                Some(ModSummary {
+                    id: mod_info.id,
+                    name: mod_info.name.clone(),
+                    tags: mod_info.tags.clone(),
+                    variants,
+                })
+            })
+            .collect()
+    }
+
+    fn get_all_conflicts_with_lookup(
+        &self,
+        lookup: &EnabledModFileAssociations,
+    ) -> Vec<VariantConflictInfo> {
+        self.installed_variants()
+            .filter(|(_, var_info)| !var_info.enabled)
+            .filter_map(|(key, var_info)| lookup.get_conflicts_with_lookup(&key, var_info))
+            .collect()
+    }
+
+    pub(crate) fn installed_variants(
+        &self,
+    ) -> impl Iterator<Item = (VariantAndId, &InstalledVariant)> {
+        self.installed_mods().flat_map(|mod_info| {
+            mod_info.installed_variants.values().map(|var_info| {
+                (
+                    VariantAndId::new(mod_info.id, var_info.name.clone()),
+                    var_info,
+                )
+            })
+        })
+    }
+
+    /// Maps every asset association that is claimed by an enabled variant to
+    /// the enabled variants claiming it. More than one claimant means the
+    /// association is contested. Note that every variant touching any global
+    /// file claims `Global`, even if they touch different files.
+    pub(crate) fn occupancy_report(&self) -> HashMap<ModFileAssetAssociation, Vec<VariantAndId>> {
+        let mut occupancy: HashMap<_, Vec<_>> = HashMap::new();
+
+        for (key, var_info) in self
+            .installed_variants()
+            .filter(|(_, var_info)| var_info.enabled)
+        {
+            for slot in var_info.asset_slots() {
+                let assoc = match slot {
+                    AssetSlot::CharacterSkin(slot) => ModFileAssetAssociation::CharSkinSlot(slot),
+                    AssetSlot::StageSkin(slot) => ModFileAssetAssociation::Stage(slot),
+                    AssetSlot::Global(_) => ModFileAssetAssociation::Global,
+                };
+
+                // Every global file of the variant maps to `Global`.
+                let claimants = occupancy.entry(assoc).or_default();
+                if claimants.last() != Some(&key) {
+                    claimants.push(key.clone());
+                }
+            }
+        }
+
+        for claimants in occupancy.values_mut() {
+            claimants.sort_by(|a, b| a.id.cmp(&b.id).then(a.variant_name.cmp(&b.variant_name)));
+        }
+
+        occupancy
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ModDbDirectory {
+    dir_path: Utf8PathBuf,
+
+    /// Ordered so that mods are always listed (and written) by ID.
+    entries: BTreeMap<ModId, InstalledModInfo>,
+}
+
+impl ModDbDirectory {
+    // Mods can disappear from under us (eg. another instance removed them or
+    // their directory was deleted by hand), so none of these assume that the
+    // mod is still there.
+
+    fn get_mod(&self, id: ModId) -> ModDbResult<&InstalledModInfo> {
+        self.entries.get(&id).ok_or(ModDbError::UnknownMod(id))
+    }
+
+    fn get_mod_mut(&mut self, id: ModId) -> ModDbResult<&mut InstalledModInfo> {
+        self.entries.get_mut(&id).ok_or(ModDbError::UnknownMod(id))
+    }
+
+    fn get_variant_mut(&mut self, key: &VariantAndId) -> ModDbResult<&mut InstalledVariant> {
+        self.get_mod_mut(key.id)?
+            .installed_variants
+            .get_mut(&key.variant_name)
+            .ok_or_else(|| ModDbError::UnknownVariant(key.clone()))
+    }
+
+    fn get_path_to_mod(&self, id: ModId) -> ModDbResult<Utf8PathBuf> {
+        let mod_name = self.get_mod_name(id)?;
+        Ok(self.dir_path.join(get_mod_directory_name(id, mod_name)))
+    }
+
+    fn get_path_to_mod_variant(&self, key: &VariantAndId) -> ModDbResult<Utf8PathBuf> {
+        let mod_name = self.get_mod_name(key.id)?;
+        Ok(self.dir_path.join(get_path_section_from_key(key, mod_name)))
+    }
+
+    fn get_mod_name(&self, id: ModId) -> ModDbResult<&str> {
+        Ok(self.get_mod(id)?.name.as_str())
+    }
+
+    fn get_in_prog_action_path(&self) -> Utf8PathBuf {
+        self.dir_path.join(IN_PROG_ACTION_FILE_NAME)
+    }
+
+    fn get_quarantine_dir_path(&self, key: &VariantAndId) -> Utf8PathBuf {
+        self.dir_path
+            .join(QUARANTINE_DIR_NAME)
+            .join(format!("{}_{}", key.id, key.variant_name))
+    }
+}
+
+/// `p` must exist.
+fn absolute_dir_path(p: &Utf8Path) -> ModDbResult<Utf8PathBuf> {
+    match p.is_absolute() {
+        false => p
+            .canonicalize_utf8()
+            .map_err(|_| ModDbError::RelativePath(p.to_path_buf())),
+        true => Ok(p.to_path_buf()),
+    }
+}
+
+/// Reads every installed mod in the state directory. `mod_info.toml` files
+/// from older versions are only rewritten if `rewrite_upgraded` is set.
+fn read_installed_mods(p: &Utf8Path, rewrite_upgraded: bool) -> ModDbResult<Vec<InstalledModInfo>> {
+    let mut installed_mods = Vec::new();
+
+    for entry in Utf8Path::read_dir_utf8(p)? {
+        let installed_mod_dir = entry?;
+
+        if installed_mod_dir.file_name() == QUARANTINE_DIR_NAME {
+            continue;
+        }
+
+        // There should only be directories in the mod folder.
+        if !installed_mod_dir.file_type()?.is_dir() {
+            let unexpected_entry_name = installed_mod_dir.file_name();
+            warn!(
+                "Found something other than a directory in the mod manager state directory at \
+                 \"{p:?}\" ({unexpected_entry_name:?})"
+            );
+
+            continue;
+        }
+
+        if let Some(installed_mod) = InstalledModInfo::read_installed_mod_contents_dir(
+            installed_mod_dir.path(),
+            rewrite_upgraded,
+        )? {
+            installed_mods.push(installed_mod);
+        }
+    }
+
+    Ok(installed_mods)
+}
+
+/// Something wrong with an installed mod that keeps it from being loaded.
+#[derive(Debug)]
+pub(crate) enum InstalledModProblem {
+    /// The `mod_info.toml` can not be read (or was written by a newer
+    /// version). Loading the state directory fails because of this.
+    UnreadableModInfo {
+        mod_dir: Utf8PathBuf,
+        err: SchemaError,
+    },
+
+    /// Variants that are listed in the `mod_info.toml` without having a
+    /// directory. The whole mod is skipped when loading because of this.
+    MissingVariantDirs {
+        mod_dir: Utf8PathBuf,
+        variant_names: Vec<String>,
+    },
+}
+
+/// Reads every installed mod in the state directory (without changing
+/// anything) and returns the problems that were found along with the number
+/// of mods that were checked.
+pub(crate) fn find_installed_mod_problems(
+    p: &Utf8Path,
+) -> io::Result<(Vec<InstalledModProblem>, usize)> {
+    let mut problems = Vec::new();
+    let mut num_mods = 0;
+
+    for entry in Utf8Path::read_dir_utf8(p)? {
+        let entry = entry?;
+        let mod_info_path = entry.path().join(MOD_INFO_FILE_NAME);
+
+        if entry.file_name() == QUARANTINE_DIR_NAME
+            || !entry.file_type()?.is_dir()
+            || !mod_info_path.exists()
+        {
+            continue;
+        }
+
+        num_mods += 1;
+        let mod_info: InstalledModInfo =
+            match deserialize_versioned_from_path(&mod_info_path, &MOD_INFO_SCHEMA, false) {
+                Ok(mod_info) => mod_info,
+                Err(err) => {
+                    problems.push(InstalledModProblem::UnreadableModInfo {
+                        mod_dir: entry.path().to_path_buf(),
+                        err,
+                    });
+                    continue;
+                },
+            };
+
+        let variant_names = missing_variant_dirs(entry.path(), &mod_info);
+        if !variant_names.is_empty() {
+            problems.push(InstalledModProblem::MissingVariantDirs {
+                mod_dir: entry.path().to_path_buf(),
+                variant_names,
+            });
+        }
+    }
+
+    Ok((problems, num_mods))
+}
+
+/// Removes the variants that no longer have a directory from the
+/// `mod_info.toml` of the mod so that the rest of the mod can be loaded again.
+/// Only do this while holding the lock on the state directory.
+pub(crate) fn forget_missing_variants(mod_dir: &Utf8Path) -> ModDbResult<Vec<String>> {
+    let mod_info_path = mod_dir.join(MOD_INFO_FILE_NAME);
+    let mut mod_info: InstalledModInfo =
+        deserialize_versioned_from_path(&mod_info_path, &MOD_INFO_SCHEMA, false)?;
+
+    let variant_names = missing_variant_dirs(mod_dir, &mod_info);
+    for variant_name in variant_names.iter() {
+        info!(
+            "Forgetting the mod variant \"{}\" of \"{}\" since its data is gone...",
+            variant_name, mod_info.name
+        );
+        mod_info.installed_variants.remove(variant_name);
+    }
+
+    serialize_versioned_to_path(&mod_info_path, &mod_info, &MOD_INFO_SCHEMA)?;
+
+    Ok(variant_names)
+}
+
+fn missing_variant_dirs(mod_dir: &Utf8Path, mod_info: &InstalledModInfo) -> Vec<String> {
+    mod_info
+        .installed_variants
+        .keys()
+        .filter(|variant_name| !mod_dir.join(variant_name).exists())
+        .cloned()
+        .collect()
+}
+
+fn get_path_section_from_key(key: &VariantAndId, mod_name: &str) -> Utf8PathBuf {
+    let mod_dir_name = get_mod_directory_name(key.id, mod_name);
+    format!("{}/{}", mod_dir_name, key.variant_name).into()
+}
+
+fn get_mod_directory_name(id: ModId, mod_name: &str) -> String {
+    format!("{}_{}", mod_name, id)
+}
+
+/// The hash of every file under the directory keyed by its path relative to
+/// it. Empty if the directory does not exist.
+fn hash_file_tree(p: &Utf8Path) -> io::Result<BTreeMap<Utf8PathBuf, Digest>> {
+    match fs::exists(p)? {
+        false => Ok(BTreeMap::new()),
+        true => hash_tree(p),
+    }
+}
+
+/// How many of the files (`0.0` - `1.0`) are identical (same path and
+/// contents) out of the files of the variant with the most of them.
+fn file_tree_similarity(
+    a: &BTreeMap<Utf8PathBuf, Digest>,
+    b: &BTreeMap<Utf8PathBuf, Digest>,
+) -> f32 {
+    let num_files = a.len().max(b.len());
+    if num_files == 0 || a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let num_identical = a
+        .iter()
+        .filter(|(path, hash)| b.get(*path) == Some(*hash))
+        .count();
+
+    num_identical as f32 / num_files as f32
+}
+
+/// The total size of every file under the directory.
+fn dir_size(p: impl AsRef<Path>) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(p)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        size += match metadata.is_dir() {
+            false => metadata.len(),
+            true => dir_size(entry.path())?,
+        };
+    }
+
+    Ok(size)
+}
+
+/// Moves a verified download into `dest_dir` (creating it if needed). We need
+/// the compressed archive on disk in some cases (looking at `unrar`) in order
+/// to expand it.
+fn move_archive_out_of_quarantine(
+    quarantined_path: &Utf8Path,
+    dest_dir: &Utf8Path,
+    variant_name: &str,
+) -> io::Result<Utf8PathBuf> {
+    fs::create_dir_all(dest_dir)?;
+
+    let dest_path = dest_dir.join(variant_name);
+    fs::rename(quarantined_path, &dest_path)?;
+
+    Ok(dest_path)
+}
+
+/// Removes everything inside of the directory but not the directory itself.
+fn remove_dir_contents(p: &Utf8Path) -> io::Result<()> {
+    if !fs::exists(p)? {
+        return Ok(());
+    }
+
+    for entry in Utf8Path::read_dir_utf8(p)? {
+        let entry = entry?;
+        match entry.file_type()?.is_dir() {
+            false => fs::remove_file(entry.path())?,
+            true => fs::remove_dir_all(entry.path())?,
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_dir_if_exists(p: &Utf8Path) -> io::Result<()> {
+    if fs::exists(p)? {
+        fs::remove_dir_all(p)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InstalledModInfo {
+    /// The ID of the mod on GameBanana.
+    pub id: ModId,
+
+    /// The name of the mod on GameBanana.
+    pub name: String,
+
+    /// Because there can be different variants available to download for a
+    /// given mod, we need to also be able to specify which one we are using.
+    /// Ordered by name so that `mod_info.toml` diffs stay readable.
+    pub installed_variants: BTreeMap<String, InstalledVariant>,
+
+    // TODO: Determine if the mod itself or the variant should hold the version info...
+    /// The version that we have in the mod manager.
+    pub version: Option<String>,
+
+    /// When we last successfully checked GameBanana for updates to the mod.
+    #[serde(default)]
+    pub last_update_check: Option<DateTime<Utc>>,
+
+    /// User defined tags for organizing mods (always normalized).
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// The page of the mod on GameBanana. Not known for mods that were
+    /// installed before it was recorded (until they are updated).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+}
+
+impl InstalledModInfo {
+    fn new(id: ModId, name: String, version: Option<String>) -> Self {
+        Self {
+            id,
+            name,
+            installed_variants: BTreeMap::default(),
+            version,
+            last_update_check: None,
+            tags: Vec::new(),
+            source_url: None,
+        }
+    }
+
+    /// `tag` is expected to already be normalized.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Looks up a variant by its alias first and then by its file name.
+    pub fn get_variant_by_name_or_alias(&self, name: &str) -> Option<&InstalledVariant> {
+        self.find_variant_by_alias(name)
+            .or_else(|| self.installed_variants.get(name))
+    }
+
+    fn find_variant_by_alias(&self, alias: &str) -> Option<&InstalledVariant> {
+        self.installed_variants
+            .values()
+            .find(|var_info| var_info.alias.as_deref() == Some(alias))
+    }
+
+    fn add_variant(
+        &mut self,
+        var_name: String,
+        mod_dir_path: Utf8PathBuf,
+        compressed_path: Utf8PathBuf,
+        upstream: UpstreamFileInfo,
+        asset_types: Option<&[AssetType]>,
+    ) -> ModDbResult<()> {
+        validate_variant_name(&var_name)?;
+
+        // Already exists since the archive was moved into it.
+        let mod_variant_path = mod_dir_path.join(&var_name);
+        fs::create_dir_all(&mod_variant_path)?;
+
+        let expanded_mod_dir_path = mod_variant_path.join(EXPANDED_MOD_INFO_DIR_NAME);
+        fs::create_dir(&expanded_mod_dir_path)?;
+
+        let parse_info = ModPayloadParseInfo::new(&compressed_path)?;
+        parse_info.expand_archive_to_disk(&expanded_mod_dir_path)?;
+
+        let variant_file_info =
+            VariantFileInfo::from_uncompressed_path_filtered(&expanded_mod_dir_path, asset_types)?;
+
+        if let Some(asset_types) = asset_types
+            && !variant_file_info.affects_any_assets()
+        {
+            warn!(
+                "None of the files of {} change any {}, so installing it will not change anything \
+                 in game.",
+                var_name,
+                asset_types
+                    .iter()
+                    .map(|asset_type| asset_type.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let mut installed_var = InstalledVariant::new(var_name.clone(), variant_file_info);
+        installed_var.version_info = Some(ModVariantVersioningInfo::new(&upstream));
+        installed_var.upstream = Some(upstream);
+        installed_var.file_size_bytes = Some(dir_size(&mod_variant_path)?);
+        installed_var.installed_at = Some(Utc::now());
+        self.installed_variants.insert(var_name, installed_var);
+
+        Ok(())
+    }
+}
+
+impl InstalledModInfo {
+    fn read_installed_mod_contents_dir(
+        installed_mod_path: &Utf8Path,
+        rewrite_upgraded: bool,
+    ) -> ModDbResult<Option<Self>> {
+        let mod_info_path: Utf8PathBuf = installed_mod_path.join(MOD_INFO_FILE_NAME);
+
+        // To keep things simple (at least for now), we're going to assume that the
+        // directory structure inside each installed mod directory is always valid. If
+        // it's not, we are just going to warn the user at a minimum since validation is
+        // going to add a lot of complexity and should only happen if the user does
+        // manual intervention.
+        if !mod_info_path.exists() {
+            warn!(
+                "Mod {mod_info_path:?} has no \"{MOD_INFO_FILE_NAME}\" file inside it's \
+                 directory. This should never happen. Consider deleting this mod and adding \
+                 again. Skipping..."
+            );
+
+            // TODO: Consider asking the user if we should remove this entry?
+
+            return Ok(None);
+        }
+
+        let mod_info: InstalledModInfo =
+            deserialize_versioned_from_path(&mod_info_path, &MOD_INFO_SCHEMA, rewrite_upgraded)?;
+
+        // Quick simple verification check for the installed mod variants.
+        for installed_variant_name in mod_info.installed_variants.keys() {
+            let mod_variant_dir_path = installed_mod_path.join(installed_variant_name);
+
+            if !mod_variant_dir_path.exists() {
+                warn!(
+                    "Found an installed mod variant that we do not actually have installed data \
+                     for ({mod_variant_dir_path:?})! Will maybe add automatic resolution support \
+                     in the future, but for now try deleting this mod and installing again. \
+                     Skipping..."
+                );
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(mod_info))
+    }
+}
+
+/// Info that we can use to detect version changes.
+///
+/// There is really not much data available to detect version changes on
+/// GameBanana, so likely in most cases we are going to have to rely on file
+/// publish dates.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct ModVariantVersioningInfo {
+    pub(crate) publish_date: DateTime<Utc>,
+}
+
+impl ModVariantVersioningInfo {
+    fn new(upstream: &UpstreamFileInfo) -> Self {
+        Self {
+            publish_date: upstream.date_added,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct InstalledVariant {
-    // TODO: Duplicate data now that these entries are keyed with this in a `HashMap`?
+    // TODO: Duplicate data now that these entries are keyed with this in a `BTreeMap`?
     /// The installed variant name is just the file name on GameBanana.   
     pub(crate) name: String,
 
-    /// Metadata describing the file associations between files and mod assets.
-    pub(crate) file_info: VariantFileInfo,
+    /// Metadata describing the file associations between files and mod assets.
+    pub(crate) file_info: VariantFileInfo,
+
+    /// Overrides in use by the variant.
+    pub(crate) overrides: Vec<VariantOverride>,
+
+    /// Whether or not the mod is enabled.
+    pub(crate) enabled: bool,
+
+    /// The last time that the variant was enabled.
+    #[serde(default)]
+    pub(crate) last_enabled_at: Option<DateTime<Utc>>,
+
+    /// The last time that the variant was disabled.
+    #[serde(default)]
+    pub(crate) last_disabled_at: Option<DateTime<Utc>>,
+
+    /// When the installed version of the variant was published.
+    #[serde(default)]
+    pub(crate) version_info: Option<ModVariantVersioningInfo>,
+
+    /// The version that was installed before the last update (if any).
+    #[serde(default)]
+    pub(crate) previous_version: Option<PreviousVariantVersion>,
+
+    /// The file of the variant was removed from GameBanana and the user
+    /// declined to adopt the file that looked like its replacement.
+    #[serde(default)]
+    pub(crate) orphaned_upstream: bool,
+
+    /// How much space the files of the variant take up on disk. Not recorded
+    /// for variants installed by older versions.
+    #[serde(default)]
+    pub(crate) file_size_bytes: Option<u64>,
+
+    /// Free-text note that the user attached to the variant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) notes: Option<String>,
+
+    /// A name picked by the user that can be used in place of the file name.
+    /// Unique within the mod.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) alias: Option<String>,
+
+    /// Where the installed version was downloaded from, so that it can be
+    /// downloaded again without scraping the mod page. Not recorded for
+    /// variants installed by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) upstream: Option<UpstreamFileInfo>,
+
+    /// The character skin slot that the variant is kept around for as an
+    /// inactive alternative to whatever variant is enabled in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) alternative_for: Option<CharSkinSlotValue>,
+
+    /// The files of the variant were removed by gc since it was disabled for a
+    /// long time. It needs to be added again before it can be enabled.
+    #[serde(default)]
+    pub(crate) data_collected: bool,
+
+    /// When the variant was first installed. Not recorded for variants
+    /// installed by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) installed_at: Option<DateTime<Utc>>,
+}
+
+/// What we need to restore a variant to the version it had before an update.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct PreviousVariantVersion {
+    file_info: VariantFileInfo,
+    version_info: Option<ModVariantVersioningInfo>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upstream: Option<UpstreamFileInfo>,
+}
+
+impl InstalledVariant {
+    /// The name of the file on GameBanana that the variant was installed from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_orphaned_upstream(&self) -> bool {
+        self.orphaned_upstream
+    }
+
+    pub fn is_data_collected(&self) -> bool {
+        self.data_collected
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_deref()
+    }
+
+    pub fn upstream(&self) -> Option<&UpstreamFileInfo> {
+        self.upstream.as_ref()
+    }
+
+    pub fn alternative_for(&self) -> Option<&CharSkinSlotValue> {
+        self.alternative_for.as_ref()
+    }
+
+    fn new(name: String, file_info: VariantFileInfo) -> Self {
+        Self {
+            name,
+            file_info,
+            overrides: Vec::default(),
+            enabled: false,
+            last_enabled_at: None,
+            last_disabled_at: None,
+            version_info: None,
+            previous_version: None,
+            orphaned_upstream: false,
+            file_size_bytes: None,
+            notes: None,
+            alias: None,
+            upstream: None,
+            alternative_for: None,
+            data_collected: false,
+            installed_at: None,
+        }
+    }
+
+    /// Carries over everything the user set on a variant whose files were
+    /// removed by gc to the freshly installed copy of it.
+    fn restore_user_state(&mut self, collected: InstalledVariant) {
+        self.overrides = collected.overrides;
+        self.last_enabled_at = collected.last_enabled_at;
+        self.last_disabled_at = collected.last_disabled_at;
+        self.notes = collected.notes;
+        self.alias = collected.alias;
+        self.alternative_for = collected.alternative_for;
+        self.installed_at = collected.installed_at.or(self.installed_at);
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        match enabled {
+            false => self.last_disabled_at = Some(Utc::now()),
+            true => self.last_enabled_at = Some(Utc::now()),
+        }
+    }
+
+    /// Whether the variant matches every filter of the query that applies to a
+    /// single variant.
+    fn matches_query(
+        &self,
+        key: &VariantAndId,
+        query: &ModQuery,
+        conflicting: Option<&HashSet<VariantAndId>>,
+    ) -> bool {
+        let state_matches = query.state.is_none_or(|state| match state {
+            VariantStateFilter::Enabled => self.enabled,
+            VariantStateFilter::Disabled => !self.enabled,
+        });
+
+        let installed_after = query.installed_after.is_none_or(|cutoff| {
+            self.installed_at
+                .is_some_and(|installed_at| installed_at > cutoff)
+        });
+
+        let touches_character = || {
+            query.character.as_deref().is_none_or(|char_key| {
+                self.asset_slots().iter().any(|slot| {
+                    matches!(
+                        slot,
+                        AssetSlot::CharacterSkin(char_slot)
+                            if char_slot.char_key().eq_ignore_ascii_case(char_key)
+                    )
+                })
+            })
+        };
+
+        state_matches
+            && installed_after
+            && (!query.has_overrides || !self.overrides.is_empty())
+            && (!query.has_conflicts || conflicting.is_some_and(|keys| keys.contains(key)))
+            && touches_character()
+    }
+
+    fn enabled_since(&self, cutoff: DateTime<Utc>) -> bool {
+        self.last_enabled_at
+            .is_some_and(|enabled_at| enabled_at >= cutoff)
+    }
+
+    /// Every slot that an asset of the variant was moved out of and into,
+    /// along with the index of the override that moved it. An asset that was
+    /// moved more than once shows up once per override.
+    fn overridden_slots(&self) -> Vec<(usize, AssetSlot, AssetSlot)> {
+        let mut moves = Vec::new();
+        for slot in self
+            .file_info
+            .asset_associations()
+            .flat_map(|(assoc, files)| asset_slots_from_association(assoc, files))
+        {
+            let mut slot = slot;
+            for (idx, slot_override) in self.overrides.iter().enumerate() {
+                let new_slot = slot_override.apply(slot.clone());
+                if new_slot != slot {
+                    moves.push((idx, slot, new_slot.clone()));
+                }
+
+                slot = new_slot;
+            }
+        }
+
+        moves
+    }
+
+    /// The assets used by the variant after its overrides have been applied.
+    fn asset_slots(&self) -> Vec<AssetSlot> {
+        self.file_info
+            .asset_associations()
+            .flat_map(|(assoc, files)| asset_slots_from_association(assoc, files))
+            .map(|slot| {
+                self.overrides
+                    .iter()
+                    .fold(slot, |slot, slot_override| slot_override.apply(slot))
+            })
+            .collect()
+    }
+
+    /// A compact summary of the slots used by the variant after its overrides
+    /// have been applied.
+    pub(crate) fn slot_summary(&self) -> SlotSummary {
+        let overridden = self
+            .overridden_slots()
+            .into_iter()
+            .map(|(_, _, new_slot)| new_slot)
+            .collect::<Vec<_>>();
+
+        SlotSummary::new(&self.asset_slots(), &overridden)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) enum VariantOverride {
+    CharacterSkin(Override<SkinSlotValue>),
+    StageSkin(Override<StageSlotValue>),
+    Global(Override<Utf8PathBuf>),
+}
+
+impl VariantOverride {
+    /// Returns `None` if the slots are not of the same kind (or are skins of
+    /// different characters).
+    fn new(old_slot: &AssetSlot, new_slot: &AssetSlot) -> Option<Self> {
+        let slot_override = match (old_slot, new_slot) {
+            (AssetSlot::CharacterSkin(old), AssetSlot::CharacterSkin(new))
+                if old.char_key() == new.char_key() =>
+            {
+                VariantOverride::CharacterSkin(Override {
+                    old: old.skin_slot_idx(),
+                    new: new.skin_slot_idx(),
+                })
+            },
+            (AssetSlot::StageSkin(old), AssetSlot::StageSkin(new)) => {
+                VariantOverride::StageSkin(Override {
+                    old: *old,
+                    new: *new,
+                })
+            },
+            (AssetSlot::Global(old), AssetSlot::Global(new)) => VariantOverride::Global(Override {
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => return None,
+        };
+
+        Some(slot_override)
+    }
+
+    /// Where a file of the variant (relative to its expanded directory) ends
+    /// up once the override is applied. `None` if the override does not
+    /// affect the file.
+    pub(crate) fn apply_to_file_path(&self, rel_path: &Utf8Path) -> Option<Utf8PathBuf> {
+        match self {
+            VariantOverride::CharacterSkin(o) => path_in_other_skin_slot(rel_path, o.old, o.new),
+            // Files are never classified as belonging to a stage slot, so there is
+            // nothing to move.
+            VariantOverride::StageSkin(_) => None,
+            VariantOverride::Global(o) => path_with_other_game_path(rel_path, &o.old, &o.new),
+        }
+    }
+
+    /// Gets the slot that `slot` ends up in once the override is applied.
+    fn apply(&self, slot: AssetSlot) -> AssetSlot {
+        match (self, slot) {
+            (VariantOverride::CharacterSkin(o), AssetSlot::CharacterSkin(s))
+                if s.skin_slot_idx() == o.old =>
+            {
+                AssetSlot::CharacterSkin(CharSkinSlotValue::new(s.char_key().to_string(), o.new))
+            },
+            (VariantOverride::StageSkin(o), AssetSlot::StageSkin(s)) if s == o.old => {
+                AssetSlot::StageSkin(o.new)
+            },
+            (VariantOverride::Global(o), AssetSlot::Global(p)) if p == o.old => {
+                AssetSlot::Global(o.new.clone())
+            },
+            (_, slot) => slot,
+        }
+    }
+}
+
+impl Display for VariantOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantOverride::CharacterSkin(slot_override) => write!(f, "{}", slot_override),
+            VariantOverride::StageSkin(slot_override) => write!(f, "{}", slot_override),
+            VariantOverride::Global(slot_override) => write!(f, "{}", slot_override),
+        }
+    }
+}
+
+/// A slot for a variant that has been overridden. This contains the mapping of
+/// the original slot to the set slot.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct Override<T> {
+    old: T,
+    new: T,
+}
+
+impl<T: Display> Display for Override<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} → {}", self.old, self.new)
+    }
+}
+
+/// We also need to perform "global" lookups to detect conflicts. Specifically,
+/// we need to be able to quickly detect if a given file in a mod is already
+/// occupied with another mod.
+///
+/// This is constructed each time during startup and is not serialized (may
+/// change in the future).
+///
+/// Also note that this lookup contains all mod files after overrides have been
+/// applied.
+#[derive(Debug)]
+struct EnabledModFileAssociations {
+    /// Global files are tracked per file, so two variants only conflict on
+    /// global files if they both ship the same file.
+    association_lookup: HashMap<AssetSlot, VariantAndId>,
+
+    /// Any other variants that use an extended skin slot (`c08`+) that is
+    /// already in `association_lookup`. Only possible if
+    /// `lenient_extended_slots` is set.
+    shared_extended_slots: HashMap<AssetSlot, Vec<VariantAndId>>,
+
+    /// Whether variants that only collide on extended skin slots are allowed
+    /// to be enabled together. Collisions on any other slot are always
+    /// conflicts.
+    lenient_extended_slots: bool,
+
+    /// The extended skin slots (`c08`+) that the ARCropolis configs of
+    /// enabled variants add to the game, along with every variant declaring
+    /// each one. Declaring a slot does not occupy it.
+    declared_extra_slots: HashMap<CharSkinSlotValue, Vec<VariantAndId>>,
+}
+
+impl EnabledModFileAssociations {
+    fn new(lenient_extended_slots: bool) -> Self {
+        Self {
+            association_lookup: HashMap::default(),
+            shared_extended_slots: HashMap::default(),
+            lenient_extended_slots,
+            declared_extra_slots: HashMap::default(),
+        }
+    }
+
+    /// Unless the serialized state is manipulated, enabling a mod should never
+    /// have any conflicts. If it does, then it will not be added to this.
+    fn add_mod_info_to_global_lookup(
+        &mut self,
+        key: &VariantAndId,
+        var_info: &InstalledVariant,
+    ) -> Option<VariantConflictInfo> {
+        if let Some(conflicts) = self.get_conflicts_with_lookup(key, var_info) {
+            return Some(conflicts);
+        }
+
+        for slot in var_info.asset_slots() {
+            match self.association_lookup.get(&slot) {
+                Some(existing_key) if existing_key != key => self
+                    .shared_extended_slots
+                    .entry(slot)
+                    .or_default()
+                    .push(key.clone()),
+                _ => {
+                    self.association_lookup.insert(slot, key.clone());
+                },
+            }
+        }
+
+        for slot in var_info.file_info.declared_extra_slots() {
+            self.declared_extra_slots
+                .entry(slot)
+                .or_default()
+                .push(key.clone());
+        }
+
+        None
+    }
+
+    /// Checks if any of the assets used by the variant are already occupied by
+    /// another variant in the lookup.
+    fn get_conflicts_with_lookup(
+        &self,
+        key: &VariantAndId,
+        var_info: &InstalledVariant,
+    ) -> Option<VariantConflictInfo> {
+        let conflicts = self
+            .get_collisions_with_lookup(key, var_info)
+            .into_iter()
+            .filter(|collision| !self.is_lenient_collision(collision))
+            .collect::<Vec<_>>();
+
+        (!conflicts.is_empty()).then(|| VariantConflictInfo {
+            key: key.clone(),
+            conflicts,
+        })
+    }
+
+    /// The extended skin slots that the variant would share with other enabled
+    /// variants without it being a conflict.
+    fn get_shared_extended_slots(
+        &self,
+        key: &VariantAndId,
+        var_info: &InstalledVariant,
+    ) -> Vec<AssetSlot> {
+        self.get_collisions_with_lookup(key, var_info)
+            .into_iter()
+            .filter(|collision| self.is_lenient_collision(collision))
+            .flat_map(|collision| collision.slots)
+            .collect()
+    }
+
+    /// A collision with a variant is only let through if every slot the two
+    /// share is an extended skin slot.
+    fn is_lenient_collision(&self, collision: &ConflictingModVariant) -> bool {
+        self.lenient_extended_slots
+            && collision.slots.iter().all(|slot| {
+                matches!(slot, AssetSlot::CharacterSkin(char_slot) if !char_slot.skin_slot_idx().is_normal_skin_slot())
+            })
+    }
+
+    /// Every enabled variant that uses any of the same assets as the variant,
+    /// along with the assets they share.
+    fn get_collisions_with_lookup(
+        &self,
+        key: &VariantAndId,
+        var_info: &InstalledVariant,
+    ) -> Vec<ConflictingModVariant> {
+        let mut conflicts: Vec<ConflictingModVariant> = Vec::new();
+
+        for slot in var_info.asset_slots() {
+            let existing_key = match self.get_any_mod_associated_with_asset(&slot) {
+                Some(existing_key) if existing_key != key => existing_key,
+                _ => continue,
+            };
+
+            match conflicts.iter_mut().find(|c| &c.key == existing_key) {
+                Some(conflict) => conflict.slots.push(slot),
+                None => conflicts.push(ConflictingModVariant {
+                    key: existing_key.clone(),
+                    slots: vec![slot],
+                }),
+            }
+        }
+
+        conflicts
+    }
+
+    fn remove_variant_from_global_lookup(&mut self, key: &VariantAndId) {
+        self.shared_extended_slots.retain(|_, shared_by| {
+            shared_by.retain(|existing_key| existing_key != key);
+            !shared_by.is_empty()
+        });
+
+        let shared_extended_slots = &mut self.shared_extended_slots;
+        self.association_lookup.retain(|slot, existing_key| {
+            if existing_key != key {
+                return true;
+            }
+
+            // Hand the slot over to the next variant that shares it.
+            let Some(shared_by) = shared_extended_slots.get_mut(slot) else {
+                return false;
+            };
+            *existing_key = shared_by.remove(0);
+            if shared_by.is_empty() {
+                shared_extended_slots.remove(slot);
+            }
+
+            true
+        });
+
+        self.declared_extra_slots.retain(|_, declared_by| {
+            declared_by.retain(|existing_key| existing_key != key);
+            !declared_by.is_empty()
+        });
+    }
+
+    fn rename_variant_in_lookup(&mut self, old_key: &VariantAndId, new_key: &VariantAndId) {
+        for existing_key in self
+            .association_lookup
+            .values_mut()
+            .chain(self.shared_extended_slots.values_mut().flatten())
+            .chain(self.declared_extra_slots.values_mut().flatten())
+        {
+            if existing_key == old_key {
+                *existing_key = new_key.clone();
+            }
+        }
+    }
+
+    /// Whether the ARCropolis config of any enabled variant adds the extended
+    /// skin slot to the game.
+    fn is_extra_slot_declared(&self, slot: &CharSkinSlotValue) -> bool {
+        self.declared_extra_slots.contains_key(slot)
+    }
+
+    /// The extended skin slots of the character that are declared by an
+    /// enabled variant, in ascending order.
+    fn declared_extra_slots_of_char(&self, char_key: &str) -> Vec<SkinSlotValue> {
+        let mut slots = self
+            .declared_extra_slots
+            .keys()
+            .filter(|slot| slot.char_key() == char_key)
+            .map(|slot| slot.skin_slot_idx())
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|slot| slot.idx());
+
+        slots
+    }
+
+    fn get_any_mod_associated_with_asset(&self, slot: &AssetSlot) -> Option<&VariantAndId> {
+        self.association_lookup.get(slot)
+    }
+}
+
+/// Every variant that is part of at least one of the conflicts (on either
+/// side).
+fn keys_in_conflicts(conflicts: Vec<VariantConflictInfo>) -> HashSet<VariantAndId> {
+    conflicts
+        .into_iter()
+        .flat_map(|var_conflicts| {
+            std::iter::once(var_conflicts.key).chain(
+                var_conflicts
+                    .conflicts
+                    .into_iter()
+                    .map(|conflict| conflict.key),
+            )
+        })
+        .collect()
+}
+
+fn asset_slots_from_association(
+    assoc: &ModFileAssetAssociation,
+    files: &[Utf8PathBuf],
+) -> Vec<AssetSlot> {
+    match assoc {
+        ModFileAssetAssociation::CharSkinSlot(slot) => vec![AssetSlot::CharacterSkin(slot.clone())],
+        ModFileAssetAssociation::Stage(slot) => vec![AssetSlot::StageSkin(*slot)],
+        ModFileAssetAssociation::Global => files.iter().cloned().map(AssetSlot::Global).collect(),
+        ModFileAssetAssociation::NoEffect => Vec::new(),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ModDbConflictResolver<'a> {
+    db: &'a mut ModDb,
+
+    /// The variant that is being enabled.
+    new_key: VariantAndId,
+
+    /// The enabled variant that the new variant conflicts with.
+    existing_key: VariantAndId,
+
+    pending_changes: HashMap<AssetSlot, AssetSlotChange>,
+    conflicts_remaining: Vec<AssetSlot>,
+
+    /// The conflict that was last handed out along with the slots that were
+    /// offered to swap into.
+    resolving: Option<(AssetSlot, Vec<SkinSlotValue>)>,
+}
+
+impl<'a> ModDbConflictResolver<'a> {
+    /// Every conflict that is returned needs to be resolved with
+    /// `resolve_conflict` before asking for the next one.
+    pub(crate) fn get_next_conflict_to_resolve(&mut self) -> Option<AssetConflict> {
+        let slot = self.conflicts_remaining.pop()?;
+
+        let (conflict, possible_resolutions) = match &slot {
+            AssetSlot::CharacterSkin(existing) => {
+                let possible_resolutions = self.open_skin_slots(existing);
+                let conflict = AssetConflict::Swappable(SwappableAssetConflict::CharacterSkin(
+                    CharacterSkinConflict {
+                        existing: existing.clone(),
+                        possible_resolutions: possible_resolutions.clone(),
+                    },
+                ));
+
+                (conflict, possible_resolutions)
+            },
+            AssetSlot::StageSkin(existing) => (
+                AssetConflict::NonSwappable(NonSwappableAssetConflict::Stage(StageSlotConflict {
+                    existing: *existing,
+                })),
+                Vec::new(),
+            ),
+            AssetSlot::Global(existing) => (
+                AssetConflict::NonSwappable(NonSwappableAssetConflict::Global(GlobalConflict {
+                    existing: existing.clone(),
+                })),
+                Vec::new(),
+            ),
+        };
+
+        self.resolving = Some((slot, possible_resolutions));
+        Some(conflict)
+    }
+
+    /// Resolves the conflict that was last returned by
+    /// `get_next_conflict_to_resolve`. A swap is the index of the slot to swap
+    /// into out of the slots that were offered.
+    pub(crate) fn resolve_conflict(&mut self, resolution: PickedResolutionOption) {
+        let (slot, possible_resolutions) = self
+            .resolving
+            .take()
+            .expect("Tried to resolve a conflict before getting one to resolve");
+
+        let change = match (resolution, &slot) {
+            (
+                PickedResolutionOption::NonSwapOption(
+                    PickedNonSwappableResolutionOption::KeepExisting,
+                ),
+                _,
+            ) => AssetSlotChange::KeepExisting,
+            (
+                PickedResolutionOption::NonSwapOption(PickedNonSwappableResolutionOption::Replace),
+                _,
+            ) => AssetSlotChange::Replace,
+            (PickedResolutionOption::Swap(idx), AssetSlot::CharacterSkin(existing)) => {
+                AssetSlotChange::MoveTo(AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                    existing.char_key().to_string(),
+                    possible_resolutions[idx],
+                )))
+            },
+            (PickedResolutionOption::Swap(_), _) => {
+                unreachable!("Only character skin conflicts can be resolved with a swap")
+            },
+            (PickedResolutionOption::KeepAsAlternative, AssetSlot::CharacterSkin(_)) => {
+                AssetSlotChange::KeepAsAlternative
+            },
+            (PickedResolutionOption::KeepAsAlternative, _) => {
+                unreachable!("Only character skin conflicts can be kept as alternatives")
+            },
+        };
+
+        self.pending_changes.insert(slot, change);
+    }
+
+    /// Applies the resolutions and writes any changes to disk. The new variant
+    /// is left disabled (and nothing else is changed) if the existing variant
+    /// kept any of the slots, and is registered as an alternative if it was
+    /// kept around for one of them. Returns whether the new variant was
+    /// enabled, which it may also not be if it still conflicts with other
+    /// variants.
+    pub(crate) fn commit(self) -> ModDbResult<bool> {
+        // If more than one slot was kept as an alternative, the lowest one is
+        // the one that the variant is an alternative for.
+        let alternative_for = self
+            .pending_changes
+            .iter()
+            .filter_map(|(slot, change)| match (slot, change) {
+                (AssetSlot::CharacterSkin(slot), AssetSlotChange::KeepAsAlternative) => Some(slot),
+                _ => None,
+            })
+            .min_by_key(|slot| (slot.char_key().to_string(), slot.skin_slot_idx().idx()))
+            .cloned();
+
+        if let Some(slot) = alternative_for {
+            self.db.modify_variant_and_sync(&self.new_key, |var_info| {
+                var_info.alternative_for = Some(slot)
+            })?;
+            return Ok(false);
+        }
+
+        if self
+            .pending_changes
+            .values()
+            .any(|change| matches!(change, AssetSlotChange::KeepExisting))
+        {
+            return Ok(false);
+        }
+
+        if self
+            .pending_changes
+            .values()
+            .any(|change| matches!(change, AssetSlotChange::Replace))
+        {
+            self.db.disable_variant(&self.existing_key);
+            self.db.sync_mod_info_to_disk(self.existing_key.id)?;
+        }
+
+        for (slot, change) in self.pending_changes {
+            if let AssetSlotChange::MoveTo(new_slot) = change {
+                self.db.swap_variant_slot(&self.new_key, slot, new_slot)?;
+            }
+        }
+
+        let enabled = self.db.enable_variant(&self.new_key)?.is_enabled();
+        self.db.sync_mod_info_to_disk(self.new_key.id)?;
+
+        Ok(enabled)
+    }
+
+    /// The skin slots of the character that can be swapped to and are not used
+    /// by the new variant itself or an earlier swap.
+    fn open_skin_slots(&self, existing: &CharSkinSlotValue) -> Vec<SkinSlotValue> {
+        let AvailableSlotsToSwapToInfo::CharacterSkin(open_slots) = self
+            .db
+            .get_available_slots_to_swap_to(&SwappableAssetSlot::CharacterSkin(existing.clone()));
+
+        let mut taken = self
+            .db
+            .get_variant(&self.new_key)
+            .map(|var_info| var_info.asset_slots())
+            .unwrap_or_default();
+        taken.extend(
+            self.pending_changes
+                .values()
+                .filter_map(|change| match change {
+                    AssetSlotChange::MoveTo(slot) => Some(slot.clone()),
+                    _ => None,
+                }),
+        );
+
+        open_slots
+            .into_iter()
+            .filter(|skin_slot| {
+                !taken.contains(&AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                    existing.char_key().to_string(),
+                    *skin_slot,
+                )))
+            })
+            .collect()
+    }
+}
+
+/// How a single conflicting slot was resolved.
+#[derive(Debug)]
+pub(crate) enum AssetSlotChange {
+    /// The existing variant keeps the slot, so the new variant can not be
+    /// enabled.
+    KeepExisting,
+
+    /// The existing variant is disabled so that the new variant can take the
+    /// slot.
+    Replace,
+
+    /// The asset of the new variant is moved to a different slot.
+    MoveTo(AssetSlot),
+
+    /// Like `KeepExisting`, but the new variant is registered as an inactive
+    /// alternative for the slot.
+    KeepAsAlternative,
+}
+
+/// Stores information about a conflict and options to resolve it.
+#[derive(Debug)]
+pub(crate) enum AssetConflict {
+    Swappable(SwappableAssetConflict),
+    NonSwappable(NonSwappableAssetConflict),
+}
+
+impl AssetConflict {
+    pub(crate) fn slot(&self) -> &AssetSlot {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub enum NonSwappableAssetConflict {
+    Stage(StageSlotConflict),
+    Global(GlobalConflict),
+}
+
+#[derive(Debug)]
+pub enum SwappableAssetConflict {
+    CharacterSkin(CharacterSkinConflict),
+}
+
+impl SwappableAssetConflict {
+    pub(crate) fn existing(&self) -> SwappableAssetSlot {
+        match self {
+            SwappableAssetConflict::CharacterSkin(character_skin_conflict) => {
+                SwappableAssetSlot::CharacterSkin(character_skin_conflict.existing.clone())
+            },
+        }
+    }
+
+    pub(crate) fn slots_available_to_swap_into(&self) -> AvailableSlotsToSwapToInfo {
+        match self {
+            // TODO: Remove clone once we figure out API...
+            Self::CharacterSkin(character_skin_conflict) => {
+                AvailableSlotsToSwapToInfo::CharacterSkin(
+                    character_skin_conflict.possible_resolutions.clone(),
+                )
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct CharacterSkinConflict {
+    existing: CharSkinSlotValue,
+    possible_resolutions: Vec<SkinSlotValue>,
+}
+
+impl CharacterSkinConflict {
+    pub(crate) fn existing(&self) -> &CharSkinSlotValue {
+        &self.existing
+    }
+
+    fn resolve(self, res: PickedNonSwappableResolutionOption) -> CharSkinSlotResolution {
+        todo!()
+    }
+}
+
+pub(crate) struct CharSkinSlotResolution {
+    res: PickedResolutionOption,
+}
+
+#[derive(Debug)]
+pub(crate) struct StageSlotConflict {
+    existing: StageSlotValue,
+}
+
+impl StageSlotConflict {
+    pub(crate) fn existing(&self) -> &StageSlotValue {
+        &self.existing
+    }
+
+    pub(crate) fn resolve(
+        self,
+        res: PickedNonSwappableResolutionOption,
+    ) -> StageSkinSlotResolution {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct StageSkinSlotResolution {
+    res: PickedNonSwappableResolutionOption,
+}
+
+#[derive(Debug)]
+pub(crate) struct GlobalConflict {
+    existing: Utf8PathBuf,
+}
+
+impl GlobalConflict {
+    pub(crate) fn existing(&self) -> &Utf8Path {
+        &self.existing
+    }
+
+    pub(crate) fn resolve(self, res: PickedNonSwappableResolutionOption) -> GlobalResolution {
+        todo!()
+    }
+}
+
+pub(crate) struct GlobalResolution {
+    res: PickedNonSwappableResolutionOption,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fs, process, time,
+    };
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use chrono::{Duration, Utc};
+    use tokio::sync::mpsc;
+    use ultimate_mod_man_rs_scraper::{
+        banana_scraper::{ScrapedBananaModData, UpstreamFileInfo},
+        mod_file_classifier::{ModFileAssetAssociation, VariantFileInfo},
+    };
+    use ultimate_mod_man_rs_utils::{
+        types::{
+            AssetSlot, AvailableSlotsToSwapToInfo, CharSkinSlotValue, SkinSlotValue,
+            StageSlotValue, SwappableAssetSlot, VariantAndId,
+        },
+        user_input_delegate::SlotInfo,
+        utils::deserialize_data_from_path,
+    };
+
+    use super::{
+        CharacterSkinConflict, DB_LOCKFILE_NAME, DBLockFile, DBLockFileError,
+        DisableVariantOutcome, EnableVariantOutcome, GlobalConflict, InstalledModInfo,
+        InstalledVariant, MAX_NOTE_LEN, MOD_INFO_FILE_NAME, ModDb, ModDbError,
+        NonSwappableAssetConflict, Override, StageSlotConflict, SwappableAssetConflict,
+        VariantOverride, normalize_note, validate_variant_name,
+    };
+    use crate::{
+        cmds::overrides::cmd_overrides,
+        mod_query::{ModQuery, VariantStateFilter},
+    };
+
+    const SHARED_GLOBAL_FILE: &str = "ui/param/database/ui_chara_db.prc";
+
+    fn m_installed_variant() -> InstalledVariant {
+        InstalledVariant::new("variant.zip".to_string(), VariantFileInfo::default())
+    }
+
+    /// A mod with a single variant that only touches the given global file.
+    fn m_installed_mod(id: u64, enabled: bool, global_file: &str) -> InstalledModInfo {
+        let file_info: VariantFileInfo = toml::from_str(&format!(
+            "mod_type = []\n[[owned_files]]\nassociation = \"Global\"\nfiles = \
+             [\"{global_file}\"]\n"
+        ))
+        .unwrap();
+
+        let mut var_info = InstalledVariant::new("variant.zip".to_string(), file_info);
+        var_info.enabled = enabled;
+
+        InstalledModInfo {
+            id,
+            name: format!("Mod{id}"),
+            installed_variants: BTreeMap::from([(var_info.name.clone(), var_info)]),
+            version: None,
+            last_update_check: None,
+            tags: Vec::new(),
+            source_url: None,
+        }
+    }
+
+    fn m_key(id: u64) -> VariantAndId {
+        VariantAndId::new(id, "variant.zip".to_string())
+    }
+
+    /// A mod with a single variant made up of the given (empty) files.
+    fn m_installed_mod_from_files(id: u64, files: &[&str]) -> InstalledModInfo {
+        let files = files.iter().map(|file| (*file, "")).collect::<Vec<_>>();
+        m_installed_mod_from_file_contents(id, &files)
+    }
+
+    /// A mod with a single variant made up of the given files and contents.
+    fn m_installed_mod_from_file_contents(id: u64, files: &[(&str, &str)]) -> InstalledModInfo {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        for (file, contents) in files {
+            let file_path = root.join(file);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, contents).unwrap();
+        }
+
+        let file_info = VariantFileInfo::from_uncompressed_path(root).unwrap();
+        let var_info = InstalledVariant::new("variant.zip".to_string(), file_info);
+
+        InstalledModInfo {
+            id,
+            name: format!("Mod{id}"),
+            installed_variants: BTreeMap::from([(var_info.name.clone(), var_info)]),
+            version: None,
+            last_update_check: None,
+            tags: Vec::new(),
+            source_url: None,
+        }
+    }
+
+    #[test]
+    fn installed_mods_are_listed_in_id_order() {
+        let mut db = ModDb::new_in_memory();
+        for id in [30, 1, 200, 4] {
+            db.insert_installed_mod(m_installed_mod(id, false, SHARED_GLOBAL_FILE));
+        }
+
+        let ids = db.installed_mods().map(|m| m.id).collect::<Vec<_>>();
+
+        assert_eq!(ids, [1, 4, 30, 200]);
+    }
+
+    #[test]
+    fn in_memory_db_enables_and_disables_variants() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, false, SHARED_GLOBAL_FILE));
+
+        assert!(db.enable_variant(&m_key(1)).unwrap().is_enabled());
+        assert!(db.get_variant(&m_key(1)).unwrap().enabled);
+        assert!(matches!(
+            db.enable_variant(&m_key(1)).unwrap(),
+            EnableVariantOutcome::AlreadyEnabled
+        ));
+
+        assert_eq!(
+            db.disable_variant(&m_key(1)),
+            DisableVariantOutcome::Disabled
+        );
+        assert!(!db.get_variant(&m_key(1)).unwrap().enabled);
+        assert_eq!(
+            db.disable_variant(&m_key(1)),
+            DisableVariantOutcome::WasAlreadyDisabled
+        );
+        assert_eq!(
+            db.disable_variant(&m_key(2)),
+            DisableVariantOutcome::NotInstalled
+        );
+    }
+
+    #[test]
+    fn in_memory_db_reports_conflicts_until_conflicting_variant_is_disabled() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, false, SHARED_GLOBAL_FILE));
+
+        match db.enable_variant(&m_key(2)).unwrap() {
+            EnableVariantOutcome::Conflicts(conflict_info) => {
+                assert_eq!(conflict_info.conflicts.len(), 1);
+                assert_eq!(conflict_info.conflicts[0].key, m_key(1));
+            },
+            res => panic!("Expected a conflict but got {:?}", res),
+        }
+
+        db.disable_variant(&m_key(1));
+        assert!(db.enable_variant(&m_key(2)).unwrap().is_enabled());
+    }
+
+    #[test]
+    fn conflict_query_is_empty_once_conflicts_are_resolved() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, false, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(3, false, "ui/message/msg_name.msbt"));
+
+        let conflicts = ModQuery {
+            has_conflicts: true,
+            ..Default::default()
+        };
+        assert_eq!(query_ids(&db, &conflicts), [1, 2]);
+        // Served from the cache this time.
+        assert_eq!(query_ids(&db, &conflicts), [1, 2]);
+
+        db.disable_variant(&m_key(1));
+        assert!(query_ids(&db, &conflicts).is_empty());
+    }
+
+    #[test]
+    fn conflict_report_lists_every_conflict() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, false, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(3, false, "ui/message/msg_name.msbt"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let report_path = Utf8Path::from_path(dir.path())
+            .unwrap()
+            .join("conflicts.toml");
+        db.export_conflict_report(&report_path).unwrap();
+
+        let report: toml::Value =
+            toml::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+        let conflicts = report["conflicts"].as_array().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0]["variant"]["id"].as_integer(), Some(2));
+        assert_eq!(
+            conflicts[0]["conflicting_variant"]["id"].as_integer(),
+            Some(1)
+        );
+        assert_eq!(conflicts[0]["swappable"].as_bool(), Some(false));
+    }
+
+    #[test]
+    fn conflict_partners_include_both_sides_of_a_conflict() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, false, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(3, false, "ui/message/msg_name.msbt"));
+
+        assert_eq!(db.conflict_partners(&m_key(1)), HashSet::from([m_key(2)]));
+        assert_eq!(db.conflict_partners(&m_key(2)), HashSet::from([m_key(1)]));
+        assert!(db.conflict_partners(&m_key(3)).is_empty());
+    }
+
+    #[test]
+    fn char_mods_on_different_slots_conflict_on_shared_global_files() {
+        const MOTION_FILE: &str = "fighter/mario/motion/body/motion_list.bin";
+
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_files(
+            1,
+            &[
+                "Mario A/fighter/mario/model/body/c00/model.numdlb",
+                &format!("Mario A/{MOTION_FILE}"),
+            ],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            2,
+            &[
+                "Mario B/fighter/mario/model/body/c01/model.numdlb",
+                &format!("Mario B/{MOTION_FILE}"),
+            ],
+        ));
+
+        assert!(db.enable_variant(&m_key(1)).unwrap().is_enabled());
+
+        match db.enable_variant(&m_key(2)).unwrap() {
+            EnableVariantOutcome::Conflicts(conflict_info) => {
+                assert_eq!(conflict_info.conflicts.len(), 1);
+                assert_eq!(conflict_info.conflicts[0].key, m_key(1));
+                assert_eq!(
+                    conflict_info.conflicts[0].slots,
+                    vec![AssetSlot::Global(MOTION_FILE.into())]
+                );
+            },
+            res => panic!("Expected a global conflict but got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn char_mods_on_different_slots_without_shared_files_do_not_conflict() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_files(
+            1,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            2,
+            &["fighter/mario/model/body/c01/model.numdlb"],
+        ));
+
+        assert!(db.enable_variant(&m_key(1)).unwrap().is_enabled());
+        assert!(db.enable_variant(&m_key(2)).unwrap().is_enabled());
+    }
+
+    /// A db with a mod for each set of skin slots used by Mario.
+    fn m_db_with_mario_skins(slots: &[&[u8]]) -> ModDb {
+        let mut db = ModDb::new_in_memory();
+        for (id, slots) in (1..).zip(slots) {
+            let files = slots
+                .iter()
+                .map(|slot| format!("fighter/mario/model/body/c{slot:02}/model.numdlb"))
+                .collect::<Vec<_>>();
+
+            db.insert_installed_mod(m_installed_mod_from_files(
+                id,
+                &files.iter().map(String::as_str).collect::<Vec<_>>(),
+            ));
+        }
+
+        db
+    }
+
+    fn m_mario_skin_slot(slot: u8) -> AssetSlot {
+        AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(slot),
+        ))
+    }
+
+    #[test]
+    fn usage_by_character_only_counts_enabled_skins() {
+        let mut db = ModDb::new_in_memory();
+        for (id, file, size, enable) in [
+            (1, "fighter/mario/model/body/c00/model.numdlb", 300, true),
+            (2, "fighter/mario/model/body/c01/model.numdlb", 200, true),
+            (3, "fighter/luigi/model/body/c00/model.numdlb", 100, true),
+            (4, "fighter/luigi/model/body/c01/model.numdlb", 1000, false),
+            (5, SHARED_GLOBAL_FILE, 5000, true),
+        ] {
+            let mut installed_mod = m_installed_mod_from_files(id, &[file]);
+            installed_mod
+                .installed_variants
+                .get_mut("variant.zip")
+                .unwrap()
+                .file_size_bytes = Some(size);
+            db.insert_installed_mod(installed_mod);
+
+            if enable {
+                db.enable_variant(&m_key(id)).unwrap();
+            }
+        }
+
+        assert_eq!(
+            db.usage_by_character(),
+            BTreeMap::from([("luigi".to_string(), 100), ("mario".to_string(), 500)])
+        );
+    }
+
+    #[test]
+    fn skins_on_the_same_normal_slot_conflict() {
+        let mut db = m_db_with_mario_skins(&[&[2], &[2]]);
+
+        assert!(matches!(
+            db.enable_variant(&m_key(1)).unwrap(),
+            EnableVariantOutcome::Enabled
+        ));
+        match db.enable_variant(&m_key(2)).unwrap() {
+            EnableVariantOutcome::Conflicts(conflict_info) => {
+                assert_eq!(conflict_info.conflicts[0].slots, [m_mario_skin_slot(2)]);
+            },
+            res => panic!("Expected a conflict but got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn skins_colliding_on_normal_and_extended_slots_conflict_on_both() {
+        let mut db = m_db_with_mario_skins(&[&[2, 8], &[2, 8]]);
+
+        db.enable_variant(&m_key(1)).unwrap();
+        match db.enable_variant(&m_key(2)).unwrap() {
+            EnableVariantOutcome::Conflicts(conflict_info) => {
+                let slots = &conflict_info.conflicts[0].slots;
+
+                assert_eq!(slots.len(), 2);
+                assert!(slots.contains(&m_mario_skin_slot(2)));
+                assert!(slots.contains(&m_mario_skin_slot(8)));
+            },
+            res => panic!("Expected a conflict but got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn skin_only_colliding_on_the_extended_slot_of_a_normal_skin_is_enabled_with_warnings() {
+        let mut db = m_db_with_mario_skins(&[&[2, 8], &[8]]);
+
+        db.enable_variant(&m_key(1)).unwrap();
+        match db.enable_variant(&m_key(2)).unwrap() {
+            EnableVariantOutcome::EnabledWithWarnings(shared_slots) => {
+                assert_eq!(shared_slots, [m_mario_skin_slot(8)]);
+            },
+            res => panic!("Expected warnings but got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn skins_on_the_same_extended_slot_are_enabled_with_warnings() {
+        let mut db = m_db_with_mario_skins(&[&[8], &[8], &[8]]);
+
+        db.enable_variant(&m_key(1)).unwrap();
+        assert!(matches!(
+            db.enable_variant(&m_key(2)).unwrap(),
+            EnableVariantOutcome::EnabledWithWarnings(_)
+        ));
+        assert!(db.get_all_conflicts().is_empty());
+
+        // The slot is still taken by the remaining variant once the first one
+        // is disabled.
+        db.mod_file_associations.lenient_extended_slots = false;
+        db.disable_variant(&m_key(1));
+        match db.enable_variant(&m_key(3)).unwrap() {
+            EnableVariantOutcome::Conflicts(conflict_info) => {
+                assert_eq!(conflict_info.conflicts[0].key, m_key(2));
+            },
+            res => panic!("Expected a conflict but got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn skins_on_the_same_extended_slot_conflict_when_not_lenient() {
+        let mut db = m_db_with_mario_skins(&[&[8], &[8]]);
+        db.mod_file_associations.lenient_extended_slots = false;
+
+        db.enable_variant(&m_key(1)).unwrap();
+        assert!(matches!(
+            db.enable_variant(&m_key(2)).unwrap(),
+            EnableVariantOutcome::Conflicts(_)
+        ));
+    }
+
+    #[test]
+    fn in_memory_db_disables_conflicting_variants_on_insert() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, true, SHARED_GLOBAL_FILE));
+
+        assert!(db.get_variant(&m_key(1)).unwrap().enabled);
+        assert!(!db.get_variant(&m_key(2)).unwrap().enabled);
+    }
+
+    #[test]
+    fn toggling_variant_sets_timestamps() {
+        let mut var_info = m_installed_variant();
+        assert!(var_info.last_enabled_at.is_none());
+        assert!(var_info.last_disabled_at.is_none());
+
+        var_info.set_enabled(true);
+        assert!(var_info.enabled);
+        assert!(var_info.last_enabled_at.is_some());
+        assert!(var_info.last_disabled_at.is_none());
+
+        var_info.set_enabled(false);
+        assert!(!var_info.enabled);
+        assert!(var_info.last_disabled_at.is_some());
+    }
+
+    #[test]
+    fn toggle_timestamps_persist() {
+        let mut var_info = m_installed_variant();
+        var_info.set_enabled(true);
+        var_info.set_enabled(false);
+
+        let deserialized: InstalledVariant =
+            toml::from_str(&toml::to_string(&var_info).unwrap()).unwrap();
+
+        assert_eq!(deserialized.last_enabled_at, var_info.last_enabled_at);
+        assert_eq!(deserialized.last_disabled_at, var_info.last_disabled_at);
+    }
+
+    #[test]
+    fn source_url_persists() {
+        let mut mod_info = m_installed_mod(1, false, SHARED_GLOBAL_FILE);
+        mod_info.source_url = Some("https://gamebanana.com/mods/1".to_string());
+
+        let deserialized: InstalledModInfo =
+            toml::from_str(&toml::to_string(&mod_info).unwrap()).unwrap();
 
-    /// Overrides in use by the variant.
-    pub(crate) overrides: Vec<VariantOverride>,
+        assert_eq!(deserialized.source_url, mod_info.source_url);
+    }
 
-    /// Whether or not the mod is enabled.
-    pub(crate) enabled: bool,
-}
+    #[test]
+    fn mods_serialized_without_a_source_url_still_load() {
+        let mod_info = m_installed_mod(1, false, SHARED_GLOBAL_FILE);
+        let serialized = toml::to_string(&mod_info).unwrap();
+        assert!(!serialized.contains("source_url"));
 
-impl InstalledVariant {
-    fn new(name: String, file_info: VariantFileInfo) -> Self {
-        Self {
-            name,
-            file_info,
-            overrides: Vec::default(),
-            enabled: false,
+        let deserialized: InstalledModInfo = toml::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized.source_url, None);
+    }
+
+    #[test]
+    fn variants_serialized_without_timestamps_still_load() {
+        let deserialized: InstalledVariant = toml::from_str(
+            r#"
+            name = "variant.zip"
+            overrides = []
+            enabled = true
+
+            [file_info]
+            mod_type = []
+            owned_files = []
+            "#,
+        )
+        .unwrap();
+
+        assert!(deserialized.last_enabled_at.is_none());
+        assert!(deserialized.last_disabled_at.is_none());
+    }
+
+    #[test]
+    fn notes_are_normalized_and_capped() {
+        assert_eq!(normalize_note("  \r\n ").unwrap(), None);
+        assert_eq!(
+            normalize_note(" line 1\r\nline 2 \n").unwrap().as_deref(),
+            Some("line 1\nline 2")
+        );
+        assert!(normalize_note(&"a".repeat(MAX_NOTE_LEN)).is_ok());
+        assert!(matches!(
+            normalize_note(&"a".repeat(MAX_NOTE_LEN + 1)),
+            Err(ModDbError::NoteTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn variant_names_that_are_not_valid_dir_names_are_rejected() {
+        // Every invalid char at the start, middle and end of a name.
+        let names_with_invalid_chars = ['/', '\\', '\0'].into_iter().flat_map(|c| {
+            [
+                c.to_string(),
+                format!("{c}a.zip"),
+                format!("a{c}b.zip"),
+                format!("a.zip{c}"),
+                format!("..{c}escape.zip"),
+            ]
+        });
+        let special_names = ["", ".", ".."].map(str::to_string);
+
+        for name in names_with_invalid_chars.chain(special_names) {
+            assert!(
+                matches!(
+                    validate_variant_name(&name),
+                    Err(ModDbError::InvalidVariantName(n)) if n == name
+                ),
+                "{:?} should have been rejected",
+                name
+            );
         }
     }
-}
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub(crate) enum VariantOverride {
-    CharacterSkin(Override<SkinSlotValue>),
-    StageSkin(Override<StageSlotValue>),
-    Global(Override<Utf8PathBuf>),
-}
+    #[test]
+    fn unicode_and_dotted_variant_names_are_accepted() {
+        for name in [
+            "a.zip",
+            "...",
+            ".hidden.zip",
+            "マリオ (Alt).7z",
+            "Ünïcödé – v1.2.rar",
+            "emoji 🎮.zip",
+            "spaces and\ttabs.zip",
+        ] {
+            assert!(
+                validate_variant_name(name).is_ok(),
+                "{:?} should be valid",
+                name
+            );
+        }
+    }
 
-/// A slot for a variant that has been overridden. This contains the mapping of
-/// the original slot to the set slot.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub(crate) struct Override<T> {
-    old: T,
-    new: T,
-}
+    #[test]
+    fn notes_with_special_chars_persist() {
+        let mut var_info = m_installed_variant();
+        var_info.notes = Some("uses \"quotes\"\nand = signs".to_string());
 
-/// We also need to perform "global" lookups to detect conflicts. Specifically,
-/// we need to be able to quickly detect if a given file in a mod is already
-/// occupied with another mod.
-///
-/// This is constructed each time during startup and is not serialized (may
-/// change in the future).
-///
-/// Also note that this lookup contains all mod files after overrides have been
-/// applied.
-#[derive(Debug)]
-struct EnabledModFileAssociations {
-    association_lookup: HashMap<ModFileAssetAssociation, ModId>,
-}
+        let deserialized: InstalledVariant =
+            toml::from_str(&toml::to_string(&var_info).unwrap()).unwrap();
 
-impl EnabledModFileAssociations {
-    fn new() -> Self {
-        todo!()
+        assert_eq!(deserialized.notes, var_info.notes);
     }
 
-    /// Unless the serialized state is manipulated, enabling a mod should never
-    /// have any conflicts. If it does, then it will not be added to this.
-    fn add_mod_info_to_global_lookup(
-        &mut self,
-        var_info: &VariantFileInfo,
-    ) -> Option<VariantConflictInfo> {
-        todo!()
+    #[test]
+    fn enabled_since_respects_cutoff() {
+        let mut var_info = m_installed_variant();
+        assert!(!var_info.enabled_since(Utc::now() - Duration::days(1)));
+
+        var_info.set_enabled(true);
+        assert!(var_info.enabled_since(Utc::now() - Duration::days(1)));
+
+        var_info.last_enabled_at = Some(Utc::now() - Duration::days(2));
+        assert!(!var_info.enabled_since(Utc::now() - Duration::days(1)));
     }
 
-    fn get_any_mod_associated_with_asset(
-        &self,
-        assoc_type: &ModFileAssetAssociation,
-    ) -> Option<ModId> {
-        todo!()
+    #[test]
+    fn renamed_variant_is_tracked_under_new_name() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        fs::create_dir_all(
+            db.directory_contents
+                .get_path_to_mod_variant(&m_key(1))
+                .unwrap(),
+        )
+        .unwrap();
+        db.set_variant_orphaned_upstream(&m_key(1), true).unwrap();
+
+        let new_key = db.rename_variant(&m_key(1), "variant_v2.zip").unwrap();
+
+        assert!(db.get_variant(&m_key(1)).is_none());
+        let var_info = db.get_variant(&new_key).unwrap();
+        assert_eq!(var_info.name, "variant_v2.zip");
+        assert!(!var_info.is_orphaned_upstream());
+        assert!(
+            db.directory_contents
+                .get_path_to_mod_variant(&new_key)
+                .unwrap()
+                .exists()
+        );
     }
-}
 
-#[derive(Debug)]
-pub(crate) struct ModDbConflictResolver<'a> {
-    db: &'a mut ModDb,
-    pending_changes: HashMap<AssetSlot, AssetSlotChange>,
-    conflicts_remaining: Vec<AssetSlot>,
-}
+    #[test]
+    fn renaming_onto_an_installed_variant_is_an_error() {
+        let mut db = ModDb::new_in_memory();
+        let mut mod_info = m_installed_mod(1, false, SHARED_GLOBAL_FILE);
+        let mut other_var_info = mod_info.installed_variants["variant.zip"].clone();
+        other_var_info.name = "variant_v2.zip".to_string();
+        mod_info
+            .installed_variants
+            .insert(other_var_info.name.clone(), other_var_info);
+        db.insert_installed_mod(mod_info);
+
+        assert!(matches!(
+            db.rename_variant(&m_key(1), "variant_v2.zip"),
+            Err(ModDbError::VariantAlreadyExists(key)) if key.variant_name == "variant_v2.zip"
+        ));
+        assert!(db.get_variant(&m_key(1)).is_some());
+    }
 
-impl<'a> ModDbConflictResolver<'a> {
-    pub(crate) fn get_next_conflict_to_resolve(&mut self) -> Option<AssetConflict> {
-        todo!()
+    #[tokio::test]
+    async fn lock_is_acquired_once_the_other_holder_releases_it() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap().to_path_buf();
+
+        let held = DBLockFile::new(&dir_path, time::Duration::ZERO, |_| ())
+            .await
+            .unwrap();
+        let (waiting_tx, mut waiting_rx) = mpsc::unbounded_channel();
+
+        let waiter = tokio::spawn(async move {
+            let timeout = time::Duration::from_secs(10);
+            ModDb::load_from_path(&dir_path, timeout, true, |holder_pid| {
+                waiting_tx.send(holder_pid).unwrap()
+            })
+            .await
+            .map(|_| ())
+        });
+
+        assert_eq!(waiting_rx.recv().await.unwrap(), Some(process::id()));
+        drop(held);
+
+        waiter.await.unwrap().unwrap();
     }
 
-    pub(crate) fn resolve_conflict(&mut self, resolution: PickedResolutionOption) {
-        todo!()
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stale_lock_file_of_a_crashed_instance_is_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap().to_path_buf();
+
+        // The PID of a process that already exited and was reaped.
+        let mut child = process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        fs::write(dir_path.join(DB_LOCKFILE_NAME), dead_pid.to_string()).unwrap();
+
+        let _db = ModDb::load_from_path(&dir_path, time::Duration::ZERO, true, |_| {
+            panic!("Waited on the lock of a process that is no longer running")
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir_path.join(DB_LOCKFILE_NAME)).unwrap(),
+            process::id().to_string()
+        );
     }
 
-    pub(crate) fn commit(self) {
-        todo!()
+    #[test]
+    fn lock_file_taken_over_by_another_instance_is_left_alone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap().to_path_buf();
+        let lock_path = dir_path.join(DB_LOCKFILE_NAME);
+
+        // Another instance replaced the stale lock-file of `u32::MAX` with its own
+        // before we got to removing it.
+        fs::write(&lock_path, process::id().to_string()).unwrap();
+
+        assert!(!DBLockFile::remove_stale(&lock_path, u32::MAX));
+        assert_eq!(
+            fs::read_to_string(&lock_path).unwrap(),
+            process::id().to_string()
+        );
+        assert_eq!(fs::read_dir(&dir_path).unwrap().count(), 1);
     }
-}
 
-#[derive(Debug)]
-pub(crate) enum AssetSlotChange {
-    CharacterSkin(SkinSlotIdx),
-    StageSkin(StageSlotIdx),
-    Global(Utf8PathBuf),
-}
+    /// Loads the db at `p` (relative to the working directory of the test,
+    /// which is the crate root) and returns the path that it ended up using.
+    async fn loaded_dir_path(p: &Utf8Path) -> Utf8PathBuf {
+        ModDb::load_from_path(p, time::Duration::from_secs(10), true, |_| ())
+            .await
+            .unwrap()
+            .directory_contents
+            .dir_path
+            .clone()
+    }
 
-/// Stores information about a conflict and options to resolve it.
-#[derive(Debug)]
-pub(crate) enum AssetConflict {
-    Swappable(SwappableAssetConflict),
-    NonSwappable(NonSwappableAssetConflict),
-}
+    #[tokio::test]
+    async fn relative_and_absolute_dir_paths_resolve_to_the_same_dir() {
+        let dir = tempfile::TempDir::new_in(".").unwrap();
+        let abs_path = Utf8Path::from_path(dir.path())
+            .unwrap()
+            .canonicalize_utf8()
+            .unwrap();
+        let rel_path = Utf8Path::new(".").join(abs_path.file_name().unwrap());
+
+        assert_eq!(loaded_dir_path(&rel_path).await, abs_path);
+        assert_eq!(loaded_dir_path(&abs_path).await, abs_path);
+    }
 
-impl AssetConflict {
-    pub(crate) fn slot(&self) -> &AssetSlot {
-        todo!()
+    #[tokio::test]
+    async fn missing_relative_dir_is_created_and_made_absolute() {
+        let dir = tempfile::TempDir::new_in(".").unwrap();
+        let rel_path = Utf8Path::from_path(dir.path()).unwrap().join("state");
+
+        let dir_path = loaded_dir_path(&rel_path).await;
+
+        assert!(dir_path.is_absolute());
+        assert_eq!(dir_path, rel_path.canonicalize_utf8().unwrap());
     }
-}
 
-#[derive(Debug)]
-pub enum NonSwappableAssetConflict {
-    Stage(StageSlotConflict),
-    Global(GlobalConflict),
-}
+    #[tokio::test]
+    async fn read_only_view_of_relative_dir_is_made_absolute() {
+        let dir = tempfile::TempDir::new_in(".").unwrap();
+        let rel_path = Utf8Path::from_path(dir.path()).unwrap().join("state");
+        let abs_path = loaded_dir_path(&rel_path).await;
 
-#[derive(Debug)]
-pub enum SwappableAssetConflict {
-    CharacterSkin(CharacterSkinConflict),
-}
+        let view = ModDb::load_read_only(&rel_path, false).unwrap();
 
-impl SwappableAssetConflict {
-    pub(crate) fn existing(&self) -> SwappableAssetSlot {
-        match self {
-            SwappableAssetConflict::CharacterSkin(character_skin_conflict) => {
-                SwappableAssetSlot::CharacterSkin(character_skin_conflict.existing.clone())
+        assert_eq!(view.directory_contents.dir_path, abs_path);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_lock_gives_up_after_timeout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir_path = Utf8Path::from_path(dir.path()).unwrap().to_path_buf();
+
+        let _held = DBLockFile::new(&dir_path, time::Duration::ZERO, |_| ())
+            .await
+            .unwrap();
+
+        match DBLockFile::new(&dir_path, time::Duration::from_millis(200), |_| ()).await {
+            Err(DBLockFileError::TimedOut { holder_pid, .. }) => {
+                assert_eq!(holder_pid, Some(process::id()))
             },
+            res => panic!("Expected the wait to time out but got {:?}", res),
         }
     }
 
-    pub(crate) fn slots_available_to_swap_into(&self) -> AvailableSlotsToSwapToInfo {
-        match self {
-            // TODO: Remove clone once we figure out API...
-            Self::CharacterSkin(character_skin_conflict) => {
-                AvailableSlotsToSwapToInfo::CharacterSkin(
-                    character_skin_conflict.possible_resolutions.clone(),
-                )
+    #[tokio::test]
+    async fn read_only_view_reads_a_locked_dir_as_is() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+        db.insert_installed_mod(m_installed_mod(2, false, SHARED_GLOBAL_FILE));
+
+        // Pretend that something outside of the tool enabled both of the conflicting
+        // variants.
+        db.view
+            .directory_contents
+            .get_variant_mut(&m_key(2))
+            .unwrap()
+            .enabled = true;
+        for id in [1, 2] {
+            fs::create_dir_all(
+                db.directory_contents
+                    .get_path_to_mod_variant(&m_key(id))
+                    .unwrap(),
+            )
+            .unwrap();
+            db.sync_mod_info_to_disk(id).unwrap();
+        }
+
+        let dir_path = db.directory_contents.dir_path.clone();
+        let _held = DBLockFile::new(&dir_path, time::Duration::ZERO, |_| ())
+            .await
+            .unwrap();
+
+        let view = ModDb::load_read_only(&dir_path, false).unwrap();
+        assert_eq!(view.installed_mods().count(), 2);
+        assert!(view.get_variant(&m_key(1)).unwrap().enabled);
+        assert!(view.get_variant(&m_key(2)).unwrap().enabled);
+    }
+
+    #[test]
+    fn adding_installed_variant_errors_without_touching_it() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod(1, true, SHARED_GLOBAL_FILE));
+
+        let payload = ScrapedBananaModData {
+            mod_name: "Mod1".to_string(),
+            variant_name: "variant.zip".to_string(),
+            version: None,
+            source_url: String::new(),
+            upstream: UpstreamFileInfo {
+                download_url: String::new(),
+                md5: String::new(),
+                size: None,
+                file_id: None,
+                date_added: Utc::now(),
             },
+            variant_download_artifact: Utf8PathBuf::new(),
+        };
+
+        assert!(matches!(
+            db.add_variant(&m_key(1), payload, None),
+            Err(ModDbError::VariantAlreadyExists(key)) if key == m_key(1)
+        ));
+        assert!(db.get_variant(&m_key(1)).unwrap().enabled);
+    }
+
+    #[test]
+    fn overrides_are_rendered_as_old_to_new_slots() {
+        let mut installed_mod = m_installed_mod(1, false, SHARED_GLOBAL_FILE);
+        installed_mod
+            .installed_variants
+            .get_mut("variant.zip")
+            .unwrap()
+            .overrides = vec![
+            VariantOverride::CharacterSkin(Override {
+                old: SkinSlotValue::new(0),
+                new: SkinSlotValue::new(3),
+            }),
+            VariantOverride::Global(Override {
+                old: SHARED_GLOBAL_FILE.into(),
+                new: "ui/param/database/ui_chara_db_alt.prc".into(),
+            }),
+        ];
+
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(installed_mod);
+
+        assert_eq!(
+            cmd_overrides(m_key(1), &db).to_string(),
+            "Overrides of 1/variant.zip:\n  Skin slot c00 → Skin slot c03\n  \
+             ui/param/database/ui_chara_db.prc → ui/param/database/ui_chara_db_alt.prc\n"
+        );
+        assert!(db.overrides_for(&m_key(2)).is_empty());
+    }
+
+    /// Mod 1 moved its Mario skin from `c00` (which mod 2 now uses) to `c03`
+    /// and the disabled mod 3 moved a global file that nothing else uses.
+    fn m_db_with_overrides() -> ModDb {
+        let with_variant = |mut installed_mod: InstalledModInfo, enabled, overrides| {
+            let var_info = installed_mod
+                .installed_variants
+                .get_mut("variant.zip")
+                .unwrap();
+            var_info.enabled = enabled;
+            var_info.overrides = overrides;
+
+            installed_mod
+        };
+
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(with_variant(
+            m_installed_mod_from_files(1, &["fighter/mario/model/body/c00/model.numdlb"]),
+            true,
+            vec![VariantOverride::CharacterSkin(Override {
+                old: SkinSlotValue::new(0),
+                new: SkinSlotValue::new(3),
+            })],
+        ));
+        db.insert_installed_mod(with_variant(
+            m_installed_mod_from_files(2, &["fighter/mario/model/body/c00/model.numdlb"]),
+            true,
+            Vec::new(),
+        ));
+        db.insert_installed_mod(with_variant(
+            m_installed_mod(3, false, SHARED_GLOBAL_FILE),
+            false,
+            vec![VariantOverride::Global(Override {
+                old: SHARED_GLOBAL_FILE.into(),
+                new: "ui/param/database/ui_chara_db_alt.prc".into(),
+            })],
+        ));
+
+        db
+    }
+
+    #[test]
+    fn overrides_of_every_mod_are_listed_with_their_variant() {
+        let db = m_db_with_overrides();
+        let mario_slot = |idx| {
+            AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+                "mario".to_string(),
+                SkinSlotValue::new(idx),
+            ))
+        };
+
+        assert_eq!(
+            db.iter_overrides()
+                .map(|(key, slot_override)| (key, slot_override.to_string()))
+                .collect::<Vec<_>>(),
+            [
+                (m_key(1), "Skin slot c00 → Skin slot c03".to_string()),
+                (
+                    m_key(3),
+                    "ui/param/database/ui_chara_db.prc → ui/param/database/ui_chara_db_alt.prc"
+                        .to_string()
+                ),
+            ]
+        );
+        assert!(db.has_any_override_for_slot(&mario_slot(3)));
+        assert!(!db.has_any_override_for_slot(&mario_slot(0)));
+        assert!(db.has_any_override_for_slot(&AssetSlot::Global(
+            "ui/param/database/ui_chara_db_alt.prc".into()
+        )));
+    }
+
+    #[test]
+    fn overrides_away_from_slots_that_are_still_used_are_needed() {
+        let mut db = m_db_with_overrides();
+
+        let unneeded = |db: &ModDb| {
+            db.detect_any_overrides_that_are_no_longer_needed()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(unneeded(&db), [m_key(3)]);
+
+        db.disable_variant(&m_key(2));
+        assert_eq!(unneeded(&db), [m_key(1), m_key(3)]);
+    }
+
+    #[test]
+    fn only_extended_slots_declared_by_enabled_variants_are_available() {
+        // A reslotted Mario that only ships `c08` but also adds `c09`.
+        const RESLOT_CONFIG: &str = r#"{
+            "new-dir-infos": ["fighter/mario/c08", "fighter/mario/c09"],
+            "share-to-added": {
+                "fighter/mario/model/body/c00/def_mario_001_col.nutexb": [
+                    "fighter/mario/model/body/c08/def_mario_001_col.nutexb",
+                    "fighter/mario/model/body/c09/def_mario_001_col.nutexb"
+                ]
+            }
+        }"#;
+
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_file_contents(
+            1,
+            &[
+                ("Reslot/config.json", RESLOT_CONFIG),
+                ("Reslot/fighter/mario/model/body/c08/model.numdlb", ""),
+            ],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            2,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+        db.enable_variant(&m_key(2)).unwrap();
+
+        let mario_c00 = SwappableAssetSlot::CharacterSkin(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(0),
+        ));
+        let available_idxs = |db: &ModDb| {
+            let AvailableSlotsToSwapToInfo::CharacterSkin(slots) =
+                db.get_available_slots_to_swap_to(&mario_c00);
+            slots.iter().map(|slot| slot.idx()).collect::<Vec<_>>()
+        };
+
+        // Nothing is declared until the reslot is enabled.
+        assert_eq!(available_idxs(&db), [1, 2, 3, 4, 5, 6, 7]);
+
+        db.enable_variant(&m_key(1)).unwrap();
+        assert_eq!(available_idxs(&db), [1, 2, 3, 4, 5, 6, 7, 9]);
+
+        db.disable_variant(&m_key(1));
+        assert_eq!(available_idxs(&db), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn occupancy_report_maps_each_slot_to_the_enabled_variants_claiming_it() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_files(
+            1,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            2,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            3,
+            &["fighter/mario/model/body/c01/model.numdlb"],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            4,
+            &["fighter/mario/model/body/c02/model.numdlb"],
+        ));
+
+        // Enabling 2 normally fails, but a state dir edited by hand can still
+        // have both claimants of a slot enabled.
+        for id in [1, 2, 3] {
+            db.view
+                .directory_contents
+                .get_variant_mut(&m_key(id))
+                .unwrap()
+                .enabled = true;
         }
+
+        let mario_slot = |idx| CharSkinSlotValue::new("mario".to_string(), SkinSlotValue::new(idx));
+
+        let occupancy = db.occupancy_report();
+        assert_eq!(
+            occupancy,
+            HashMap::from([
+                (
+                    ModFileAssetAssociation::CharSkinSlot(mario_slot(0)),
+                    vec![m_key(1), m_key(2)]
+                ),
+                (
+                    ModFileAssetAssociation::CharSkinSlot(mario_slot(1)),
+                    vec![m_key(3)]
+                ),
+            ])
+        );
     }
-}
 
-#[derive(Debug)]
-pub(crate) struct CharacterSkinConflict {
-    existing: CharSkinSlotValue,
-    possible_resolutions: Vec<SkinSlotValue>,
-}
+    fn m_mario_slot(idx: u8) -> AssetSlot {
+        AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(idx),
+        ))
+    }
 
-impl CharacterSkinConflict {
-    fn resolve(self, res: PickedNonSwappableResolutionOption) -> CharSkinSlotResolution {
-        todo!()
+    #[test]
+    fn swapping_a_slot_moves_the_variant_and_persists_the_override() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_files(
+            1,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+        db.insert_installed_mod(m_installed_mod_from_files(
+            2,
+            &["fighter/mario/model/body/c01/model.numdlb"],
+        ));
+        assert!(db.enable_variant(&m_key(1)).unwrap().is_enabled());
+        assert!(db.enable_variant(&m_key(2)).unwrap().is_enabled());
+
+        assert!(matches!(
+            db.swap_variant_slot(&m_key(1), m_mario_slot(0), m_mario_slot(1)),
+            Err(ModDbError::SlotOccupied(key)) if key == m_key(2)
+        ));
+        assert!(db.overrides_for(&m_key(1)).is_empty());
+
+        db.swap_variant_slot(&m_key(1), m_mario_slot(0), m_mario_slot(2))
+            .unwrap();
+
+        assert!(db.get_variant(&m_key(1)).unwrap().enabled);
+        assert_eq!(db.overrides_for(&m_key(1)).len(), 1);
+
+        // The slot that was moved out of can now be used by another variant.
+        db.swap_variant_slot(&m_key(2), m_mario_slot(1), m_mario_slot(0))
+            .unwrap();
+
+        let mod_info_path = db
+            .directory_contents
+            .get_path_to_mod(1)
+            .unwrap()
+            .join(MOD_INFO_FILE_NAME);
+        let persisted: InstalledModInfo = deserialize_data_from_path(&mod_info_path).unwrap();
+        assert_eq!(
+            persisted.installed_variants["variant.zip"].overrides.len(),
+            1
+        );
     }
-}
 
-pub(crate) struct CharSkinSlotResolution {
-    res: PickedResolutionOption,
-}
+    #[test]
+    fn slots_can_only_be_swapped_to_a_slot_of_the_same_kind() {
+        let mut db = ModDb::new_in_memory();
+        db.insert_installed_mod(m_installed_mod_from_files(
+            1,
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        ));
+
+        assert!(matches!(
+            db.swap_variant_slot(
+                &m_key(1),
+                m_mario_slot(0),
+                AssetSlot::StageSkin(StageSlotValue::new(1))
+            ),
+            Err(ModDbError::MismatchedSlotSwap(..))
+        ));
+        assert!(matches!(
+            db.swap_variant_slot(&m_key(1), m_mario_slot(3), m_mario_slot(4)),
+            Err(ModDbError::SlotNotUsedByVariant(..))
+        ));
+    }
 
-#[derive(Debug)]
-pub(crate) struct StageSlotConflict {
-    existing: StageSlotValue,
-}
+    #[test]
+    fn character_skin_conflicts_are_described_by_their_slot() {
+        let conflict = SwappableAssetConflict::CharacterSkin(CharacterSkinConflict {
+            existing: CharSkinSlotValue::new("mario".to_string(), SkinSlotValue::new(2)),
+            possible_resolutions: Vec::new(),
+        });
 
-impl StageSlotConflict {
-    pub(crate) fn resolve(
-        self,
-        res: PickedNonSwappableResolutionOption,
-    ) -> StageSkinSlotResolution {
-        todo!()
+        let slot_info = SlotInfo::from(conflict);
+        assert_eq!(slot_info.slot_name(), "mario - Skin slot c02");
+        assert_eq!(slot_info.slot_type_name(), "Character skin");
     }
-}
 
-#[derive(Debug)]
-pub(crate) struct StageSkinSlotResolution {
-    res: PickedNonSwappableResolutionOption,
-}
+    #[test]
+    fn stage_conflicts_are_described_by_their_slot() {
+        let conflict = NonSwappableAssetConflict::Stage(StageSlotConflict {
+            existing: StageSlotValue::new(1),
+        });
 
-#[derive(Debug)]
-pub(crate) struct GlobalConflict {
-    existing: Utf8PathBuf,
-}
+        let slot_info = SlotInfo::from(conflict);
+        assert_eq!(slot_info.slot_name(), "Stage slot 1");
+        assert_eq!(slot_info.slot_type_name(), "Stage skin");
+    }
 
-impl GlobalConflict {
-    pub(crate) fn resolve(self, res: PickedNonSwappableResolutionOption) -> GlobalResolution {
-        todo!()
+    #[test]
+    fn global_conflicts_are_described_by_their_file() {
+        let conflict = NonSwappableAssetConflict::Global(GlobalConflict {
+            existing: SHARED_GLOBAL_FILE.into(),
+        });
+
+        let slot_info = SlotInfo::from(conflict);
+        assert_eq!(slot_info.slot_name(), SHARED_GLOBAL_FILE);
+        assert_eq!(slot_info.slot_type_name(), "Global file");
     }
-}
 
-pub(crate) struct GlobalResolution {
-    res: PickedNonSwappableResolutionOption,
+    const QUERY_CHARS: [&str; 3] = ["mario", "luigi", "cloud"];
+
+    /// 36 mods with a single Mario, Luigi or Cloud skin each (in that order by
+    /// ID). The skins of mods 25 - 36 use the same slots as the ones of mods
+    /// 1 - 12, so the even ones (which are the only enabled ones in 1 - 24)
+    /// conflict. Every 4th mod is named after Cloud, every 5th mod is a
+    /// favorite, every 7th mod has an override and mods past 6 know when they
+    /// were installed (`id` days after `installed_base`).
+    fn m_query_db(installed_base: chrono::DateTime<Utc>) -> ModDb {
+        let mut db = ModDb::new_in_memory();
+
+        for id in 1..=36_u64 {
+            let char_key = QUERY_CHARS[id as usize % 3];
+            let slot = (id / 3) % 8;
+            let mut installed_mod = m_installed_mod_from_files(
+                id,
+                &[&format!(
+                    "fighter/{char_key}/model/body/c0{slot}/model.numdlb"
+                )],
+            );
+
+            if id % 4 == 0 {
+                installed_mod.name = format!("Cloud Costume {id}");
+            }
+            if id % 5 == 0 {
+                installed_mod.tags.push("favorite".to_string());
+            }
+
+            let var_info = installed_mod
+                .installed_variants
+                .get_mut("variant.zip")
+                .unwrap();
+            if id % 7 == 0 {
+                // Does not change any slot that the variant uses.
+                var_info.overrides.push(VariantOverride::Global(Override {
+                    old: "ui/unused.prc".into(),
+                    new: "ui/unused_alt.prc".into(),
+                }));
+            }
+            if id > 6 {
+                var_info.installed_at = Some(installed_base + Duration::days(id as i64));
+            }
+
+            db.insert_installed_mod(installed_mod);
+            if id <= 24 && id % 2 == 0 {
+                assert!(db.enable_variant(&m_key(id)).unwrap().is_enabled());
+            }
+        }
+
+        db
+    }
+
+    fn query_ids(db: &ModDb, query: &ModQuery) -> Vec<u64> {
+        let summaries = db.query(query);
+
+        // Finding the conflicts from scratch has to agree with the cached ones.
+        assert_eq!(summaries, db.query_computing_conflicts(query, false));
+
+        summaries.into_iter().map(|summary| summary.id).collect()
+    }
+
+    #[test]
+    fn an_empty_query_matches_every_mod_and_variant() {
+        let db = m_query_db(Utc::now());
+        let summaries = db.query(&ModQuery::default());
+
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|summary| summary.id)
+                .collect::<Vec<_>>(),
+            (1..=36).collect::<Vec<_>>()
+        );
+        assert!(
+            summaries
+                .iter()
+                .all(|summary| summary.variants == ["variant.zip"])
+        );
+    }
+
+    #[test]
+    fn name_character_and_state_filters_are_combined() {
+        let db = m_query_db(Utc::now());
+        let query = ModQuery {
+            name: Some("CLOUD costume".to_string()),
+            character: Some("mario".to_string()),
+            state: Some(VariantStateFilter::Enabled),
+            ..Default::default()
+        };
+
+        assert_eq!(query_ids(&db, &query), [12, 24]);
+
+        let query = ModQuery {
+            state: Some(VariantStateFilter::Disabled),
+            ..query
+        };
+        assert_eq!(query_ids(&db, &query), [36]);
+    }
+
+    #[test]
+    fn conflict_state_and_tag_filters_are_combined() {
+        let db = m_query_db(Utc::now());
+
+        let conflicts = ModQuery {
+            has_conflicts: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            query_ids(&db, &conflicts),
+            [2, 4, 6, 8, 10, 12, 26, 28, 30, 32, 34, 36]
+        );
+
+        let query = ModQuery {
+            state: Some(VariantStateFilter::Disabled),
+            tags: vec!["favorite".to_string()],
+            ..conflicts
+        };
+        assert_eq!(query_ids(&db, &query), [30]);
+    }
+
+    #[test]
+    fn install_time_and_override_filters_are_combined() {
+        let installed_base = Utc::now() - Duration::days(100);
+        let db = m_query_db(installed_base);
+
+        let query = ModQuery {
+            character: Some("luigi".to_string()),
+            state: Some(VariantStateFilter::Enabled),
+            installed_after: Some(installed_base + Duration::days(20)),
+            ..Default::default()
+        };
+        assert_eq!(query_ids(&db, &query), [22]);
+
+        // Mods installed before the install time was recorded never match.
+        let query = ModQuery {
+            installed_after: Some(installed_base - Duration::days(1)),
+            ..Default::default()
+        };
+        assert_eq!(query_ids(&db, &query), (7..=36).collect::<Vec<_>>());
+
+        let query = ModQuery {
+            has_overrides: true,
+            state: Some(VariantStateFilter::Enabled),
+            ..Default::default()
+        };
+        assert_eq!(query_ids(&db, &query), [14]);
+    }
+
+    #[test]
+    fn mods_without_variants_only_match_queries_on_the_mod_itself() {
+        let mut db = m_query_db(Utc::now());
+        let mut empty_mod = m_installed_mod(37, false, SHARED_GLOBAL_FILE);
+        empty_mod.name = "Cloud Costume Leftovers".to_string();
+        empty_mod.installed_variants.clear();
+        db.insert_installed_mod(empty_mod);
+
+        let by_name = ModQuery {
+            name: Some("leftovers".to_string()),
+            ..Default::default()
+        };
+        let summaries = db.query(&by_name);
+        assert_eq!(summaries.len(), 1);
+        assert!(summaries[0].variants.is_empty());
+
+        let disabled = ModQuery {
+            state: Some(VariantStateFilter::Disabled),
+            ..by_name
+        };
+        assert!(db.query(&disabled).is_empty());
+    }
 }
@@ -1,25 +1,72 @@
-use std::ops::Deref;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    ops::Deref,
+    panic,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
 use log::{info, warn};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaScraperError};
+use tokio::{sync::Semaphore, task::JoinSet};
+use ultimate_mod_man_rs_scraper::{
+    banana_scraper::{
+        BananaClient, BananaScraperError, ChecksumPolicy, ScrapedBananaModData, VariantMatchPolicy,
+    },
+    health_check::HealthCheckReport,
+    mod_file_classifier::AssetType,
+};
 use ultimate_mod_man_rs_utils::{
     types::{
-        AssetSlot, AvailableSlotsToSwapToInfo, PickedResolutionOption, SkinSlotValue,
-        SwappableAssetSlot, VariantAndId, VariantAndIdentifier,
+        AssetSlot, AvailableSlotsToSwapToInfo, CharSkinSlotValue, ModId, ModIdentifier,
+        PickedNonSwappableResolutionOption, PickedResolutionOption, SwappableAssetSlot,
+        VariantAndId, VariantAndIdentifier, VariantAndIdentifierStrError,
+    },
+    user_input_delegate::{
+        AvailableSlotToSwapInto, SlotInfo, UserInputDelegate, VariantConflictSummary,
     },
-    user_input_delegate::{SlotInfo, UserInputDelegate},
 };
 
 use crate::{
-    cmds::status::StatusCmdInfo,
-    in_prog_action::{Action, InProgAction},
+    cmds::{
+        browse::cmd_search_mods,
+        dedupe::{DedupeReport, cmd_dedupe},
+        disk_usage::{
+            CharacterDiskUsageReport, DiskUsageReport, cmd_disk_usage, cmd_disk_usage_by_character,
+        },
+        doctor::{DoctorFix, DoctorReport, cmd_apply_doctor_fix, cmd_doctor},
+        fmt_size,
+        gc::{GcReport, cmd_gc},
+        list_variants::{ModVariantsListing, cmd_list_variants},
+        occupancy::{OccupancyReport, cmd_occupancy},
+        overrides::{VariantOverridesListing, cmd_overrides},
+        status::{StatusCmdInfo, StatusReport, cmd_status, status_query},
+        sync::{
+            DestLayout, SwitchCompareReport, SyncCopyOutcome, SyncError, SyncPlan,
+            cmd_apply_sync_plan, cmd_install_missing, cmd_plan_sync, cmd_switch_compare,
+        },
+        update_check::{
+            FailedUpdate, SuccessorCandidate, UnreachableMod, UpdateCheckEntry, UpdateCheckReport,
+            cmd_check_for_updates,
+        },
+    },
+    config::{Config, ConfigError, ConfigKey, ConfigListing, ConfigValueSource},
+    in_prog_action::{Action, InProgAction, ResumableDownload},
     mod_db::{
-        AssetConflict, ModDb, ModDbError, SwappableAssetConflict, UnableToEnableReason,
-        VariantConflictInfo,
+        AssetConflict, DisableVariantOutcome, EnableVariantOutcome, InstalledModInfo,
+        InstalledVariant, ModDb, ModDbError, ModDbView, NonSwappableAssetConflict,
+        SwappableAssetConflict, VariantConflictInfo, normalize_tag,
     },
     mod_name_resolver::{BananaModNameResolver, ModNameResolverError},
+    mod_query::{ModQuery, ModSummary},
+    style::{ColorMode, Style},
+    sync_targets::{SyncTarget, SyncTargetError, SyncTargets},
 };
 
 pub type ModManagerResult<T> = Result<T, ModManagerErr>;
@@ -34,6 +81,234 @@ pub enum ModManagerErr {
 
     #[error(transparent)]
     ModNameResolverError(#[from] ModNameResolverError),
+
+    #[error(transparent)]
+    SyncTargetError(#[from] SyncTargetError),
+
+    #[error(transparent)]
+    SyncError(#[from] SyncError),
+
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error("Unable to open the mod page in a browser: {0}")]
+    OpenFailed(io::Error),
+
+    #[error("The mod {0} is not installed")]
+    ModNotInstalled(ModIdentifier),
+
+    #[error("The mod variant {0} is not installed")]
+    VariantNotInstalled(VariantAndId),
+
+    #[error("Unable to read the mod list file \"{0}\": {1}")]
+    ModListReadError(Utf8PathBuf, io::Error),
+
+    #[error("Unable to parse line {0} (\"{1}\") of the mod list file: {2}")]
+    ModListParseError(usize, String, VariantAndIdentifierStrError),
+}
+
+/// Diagnoses the state directory at `p` and the environment around it. Nothing
+/// is changed and the state directory is not locked, so this also works when
+/// the manager can not be opened.
+pub async fn run_doctor(p: &Utf8Path, options: &ModManagerOptions) -> DoctorReport {
+    cmd_doctor(p, options, None).await
+}
+
+/// lib-api: Like `run_doctor`, but checks GameBanana through the given client.
+pub async fn run_doctor_with_client(
+    p: &Utf8Path,
+    options: &ModManagerOptions,
+    scraper: BananaClient,
+) -> DoctorReport {
+    cmd_doctor(p, options, Some(scraper)).await
+}
+
+/// Applies a fix offered by `run_doctor` to the state directory at `p`.
+pub async fn apply_doctor_fix(p: &Utf8Path, fix: DoctorFix) -> ModManagerResult<()> {
+    Ok(cmd_apply_doctor_fix(p, fix).await?)
+}
+
+/// Reads a list of mod variants from a file with one variant per line (in the
+/// same `ident/variant` form used on the command line). Blank lines and lines
+/// starting with `#` are skipped.
+pub fn read_mod_list_file(path: &Utf8Path) -> ModManagerResult<Vec<VariantAndIdentifier>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| ModManagerErr::ModListReadError(path.to_path_buf(), err))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            line.parse()
+                .map_err(|err| ModManagerErr::ModListParseError(line_number, line.to_string(), err))
+        })
+        .collect()
+}
+
+/// Settings that need to be known before the state directory is opened.
+#[derive(Debug, Default)]
+pub struct ModManagerOptions {
+    /// Config overrides for this run only (see `ModManager::override_config`).
+    pub config_overrides: Vec<(ConfigKey, String)>,
+
+    /// Whether the reports that are returned color their output.
+    pub color: ColorMode,
+
+    /// Use the state directory even if it is not empty and does not look like
+    /// a state directory.
+    pub init_here: bool,
+}
+
+impl ModManagerOptions {
+    pub(crate) fn load_config(&self, cache_dir_path: &Utf8Path) -> ModManagerResult<Config> {
+        let mut config = Config::load_or_default(cache_dir_path)?;
+        for (key, value) in self.config_overrides.iter() {
+            config.set_override(*key, value)?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Read-only access to the state of the manager for commands that only query
+/// it. The state directory is not locked, so this never waits on an instance
+/// that is modifying it (but may see some of its changes half done).
+#[derive(Debug)]
+pub struct ModManagerView {
+    db: ModDbView,
+    sync_targets: SyncTargets,
+    config: Config,
+    style: Style,
+}
+
+impl ModManagerView {
+    pub fn load(cache_dir_path: &Utf8Path, options: ModManagerOptions) -> ModManagerResult<Self> {
+        Ok(Self {
+            db: ModDb::load_read_only(cache_dir_path, options.init_here)?,
+            sync_targets: SyncTargets::load_from_dir(cache_dir_path, false)?,
+            config: options.load_config(cache_dir_path)?,
+            style: options.color.style(),
+        })
+    }
+
+    fn queries(&self) -> StateQueries<'_> {
+        StateQueries {
+            db: &self.db,
+            sync_targets: &self.sync_targets,
+            config: &self.config,
+            style: self.style,
+        }
+    }
+
+    pub fn status(&self, info: StatusCmdInfo) -> ModManagerResult<StatusReport> {
+        self.queries().status(info)
+    }
+
+    /// How much disk space each installed mod uses, largest first.
+    pub fn disk_usage(&self) -> DiskUsageReport {
+        self.queries().disk_usage()
+    }
+
+    /// How much disk space the enabled skins of each character use, largest
+    /// first.
+    pub fn disk_usage_by_character(&self) -> CharacterDiskUsageReport {
+        self.queries().disk_usage_by_character()
+    }
+
+    /// Which enabled mod variants occupy each asset slot. With
+    /// `conflicts_only`, only slots claimed by more than one variant are
+    /// reported.
+    pub fn occupancy(&self, conflicts_only: bool) -> OccupancyReport {
+        self.queries().occupancy(conflicts_only)
+    }
+
+    /// Installed variants that look like copies of the same upload.
+    pub fn find_duplicates(&self) -> ModManagerResult<DedupeReport> {
+        self.queries().find_duplicates()
+    }
+
+    pub fn tags(&self, ident: &ModIdentifier) -> ModManagerResult<&[String]> {
+        self.queries().tags(ident)
+    }
+
+    pub fn config_get(&self, key: ConfigKey) -> (String, ConfigValueSource) {
+        self.queries().config_get(key)
+    }
+
+    pub fn config_list(&self) -> ConfigListing {
+        self.queries().config_list()
+    }
+
+    /// Checks that GameBanana is reachable and still responds the way that
+    /// the scraper expects (using the `gamebanana.*` settings).
+    pub async fn health_check(&self) -> ModManagerResult<HealthCheckReport> {
+        let scraper = BananaClient::new_with_settings(self.config.banana_client_settings())?;
+        Ok(scraper.health_check().await)
+    }
+}
+
+/// The state that the read-only commands query. Both `ModManager` and
+/// `ModManagerView` answer these through here so that they always agree.
+struct StateQueries<'a> {
+    db: &'a ModDbView,
+    sync_targets: &'a SyncTargets,
+    config: &'a Config,
+    style: Style,
+}
+
+impl<'a> StateQueries<'a> {
+    /// Conflicts are found from the enabled variants rather than from the
+    /// cache of a locked database, since a view does not have one.
+    fn status(&self, info: StatusCmdInfo) -> ModManagerResult<StatusReport> {
+        let tag = info.tag.as_deref().map(normalize_tag).transpose()?;
+        let query = status_query(&info, tag.as_deref())?;
+        let lenient_extended_slots = self.config.lenient_extended_slots();
+
+        Ok(cmd_status(
+            &info,
+            tag.as_deref(),
+            self.db,
+            &self
+                .db
+                .query_computing_conflicts(&query, lenient_extended_slots),
+            self.db.compute_all_conflicts(lenient_extended_slots).len(),
+            self.sync_targets,
+            self.style,
+        ))
+    }
+
+    fn disk_usage(&self) -> DiskUsageReport {
+        cmd_disk_usage(self.db)
+    }
+
+    fn disk_usage_by_character(&self) -> CharacterDiskUsageReport {
+        cmd_disk_usage_by_character(self.db)
+    }
+
+    fn occupancy(&self, conflicts_only: bool) -> OccupancyReport {
+        cmd_occupancy(self.db, conflicts_only)
+    }
+
+    fn find_duplicates(&self) -> ModManagerResult<DedupeReport> {
+        cmd_dedupe(self.db)
+    }
+
+    fn tags(&self, ident: &ModIdentifier) -> ModManagerResult<&'a [String]> {
+        self.db
+            .find_installed_mod(ident)
+            .map(|mod_info| mod_info.tags.as_slice())
+            .ok_or_else(|| ModManagerErr::ModNotInstalled(ident.clone()))
+    }
+
+    fn config_get(&self, key: ConfigKey) -> (String, ConfigValueSource) {
+        self.config.get(key)
+    }
+
+    fn config_list(&self) -> ConfigListing {
+        self.config.list()
+    }
 }
 
 #[derive(Debug)]
@@ -41,260 +316,1778 @@ pub struct ModManager<U: UserInputDelegate> {
     db: ModDb,
     scraper: BananaClient,
     mod_resolution_cache: BananaModNameResolver,
+    sync_targets: SyncTargets,
+    config: Config,
+    style: Style,
     user_input_delegate: U,
 }
 
 impl<U: UserInputDelegate> ModManager<U> {
-    pub fn new(cache_dir_path: &Utf8Path, user_input_delegate: U) -> ModManagerResult<Self> {
+    pub async fn new(cache_dir_path: &Utf8Path, user_input_delegate: U) -> ModManagerResult<Self> {
+        let config = Config::load_or_default(&ModDb::resolve_dir_path(cache_dir_path, false)?)?;
+        Self::new_with_config(cache_dir_path, user_input_delegate, config).await
+    }
+
+    /// lib-api: Like `new`, but with a config that the caller has already
+    /// loaded (and possibly overridden). The config is used for the whole
+    /// run, including how we talk to GameBanana.
+    pub async fn new_with_config(
+        cache_dir_path: &Utf8Path,
+        user_input_delegate: U,
+        config: Config,
+    ) -> ModManagerResult<Self> {
+        let scraper = BananaClient::new_with_settings(config.banana_client_settings())?;
+        Self::open(
+            cache_dir_path,
+            scraper,
+            config,
+            ColorMode::default(),
+            user_input_delegate,
+        )
+        .await
+    }
+
+    pub async fn new_with_options(
+        cache_dir_path: &Utf8Path,
+        options: ModManagerOptions,
+        user_input_delegate: U,
+    ) -> ModManagerResult<Self> {
+        let config =
+            options.load_config(&ModDb::resolve_dir_path(cache_dir_path, options.init_here)?)?;
+        let scraper = BananaClient::new_with_settings(config.banana_client_settings())?;
+        Self::open(
+            cache_dir_path,
+            scraper,
+            config,
+            options.color,
+            user_input_delegate,
+        )
+        .await
+    }
+
+    /// lib-api: Like `new`, but talks to GameBanana through the given client
+    /// (eg. one pointed at a mirror or a local server). The client is used as
+    /// is, so the `gamebanana.*` settings do not apply to it.
+    pub async fn new_with_client(
+        cache_dir_path: &Utf8Path,
+        scraper: BananaClient,
+        user_input_delegate: U,
+    ) -> ModManagerResult<Self> {
+        let config = Config::load_or_default(&ModDb::resolve_dir_path(cache_dir_path, false)?)?;
+        Self::open(
+            cache_dir_path,
+            scraper,
+            config,
+            ColorMode::default(),
+            user_input_delegate,
+        )
+        .await
+    }
+
+    async fn open(
+        cache_dir_path: &Utf8Path,
+        scraper: BananaClient,
+        config: Config,
+        color: ColorMode,
+        mut user_input_delegate: U,
+    ) -> ModManagerResult<Self> {
+        let cache_dir_path = &ModDb::resolve_dir_path(cache_dir_path, false)?;
+
+        // The config decides how long we wait for the lock, so it has to be
+        // loaded before this.
+        let db = ModDb::load_from_path(
+            cache_dir_path,
+            config.lock_wait_timeout(),
+            config.lenient_extended_slots(),
+            |holder_pid| user_input_delegate.display_waiting_for_lock(holder_pid),
+        )
+        .await?;
+
+        let mod_resolution_cache =
+            match BananaModNameResolver::new(cache_dir_path, config.resolver_cache_ttl()) {
+                Err(ModNameResolverError::CacheCorrupted(err)) => {
+                    warn!(
+                        "Starting with an empty mod name resolution cache since the existing one \
+                         could not be read ({}). Mod names will be looked up again as needed.",
+                        err
+                    );
+                    BananaModNameResolver::new_empty(cache_dir_path, config.resolver_cache_ttl())
+                },
+                res => res?,
+            };
+
         Ok(Self {
-            db: ModDb::load_from_path(cache_dir_path)?,
-            scraper: BananaClient::new()?,
-            mod_resolution_cache: BananaModNameResolver::new(cache_dir_path)?,
+            db,
+            scraper,
+            mod_resolution_cache,
+            sync_targets: SyncTargets::load_from_dir(cache_dir_path, true)?,
+            config,
+            style: color.style(),
             user_input_delegate,
         })
     }
 
-    pub fn status(&self, info: StatusCmdInfo) -> ModManagerResult<()> {
-        todo!()
+    fn queries(&self) -> StateQueries<'_> {
+        StateQueries {
+            db: &self.db,
+            sync_targets: &self.sync_targets,
+            config: &self.config,
+            style: self.style,
+        }
     }
 
-    pub async fn add_mods<I: IntoIterator<Item = VariantAndIdentifier>>(
-        &mut self,
-        idents: I,
-    ) -> ModManagerResult<()> {
-        self.cleanup_any_incomplete_in_prog_action()?;
-
-        for ident_and_variant in idents {
-            let key = self
-                .mod_resolution_cache
-                .resolve_key(ident_and_variant.clone(), &self.scraper)
-                .await?;
+    pub fn status(&self, info: StatusCmdInfo) -> ModManagerResult<StatusReport> {
+        self.queries().status(info)
+    }
 
-            if self.db.exists(&key) {
-                info!(
-                    "Skipping adding the mod variant {} since it was already installed. (If you \
-                     want to check for mod updates, run the update command.)",
-                    ident_and_variant
-                );
-                return Ok(());
-            }
+    /// lib-api: Iterates over every mod installed in the manager. Intended for
+    /// frontends that embed the manager and need to show what is installed.
+    pub fn list_installed(&self) -> impl Iterator<Item = &InstalledModInfo> {
+        self.db.installed_mods()
+    }
 
-            self.db
-                .journal_action_as_in_prog(Action::Add(key.clone()))?;
+    /// lib-api: The installed mods (and their variants) that match the query,
+    /// in ID order. Tags in the query are expected to already be normalized.
+    pub fn query(&self, query: &ModQuery) -> Vec<ModSummary> {
+        self.db.query(query)
+    }
 
-            // Mod is not installed.
-            let downloaded_mod_variant = self
-                .scraper
-                .download_mod_variant(&mut self.user_input_delegate, &key)
-                .await?;
+    /// lib-api: Iterates over the installed variants of a single mod. Yields
+    /// nothing if the mod is not installed.
+    pub fn list_variants_for_mod(&self, id: ModId) -> impl Iterator<Item = &InstalledVariant> {
+        self.db
+            .installed_mods()
+            .filter(move |mod_info| mod_info.id == id)
+            .flat_map(|mod_info| mod_info.installed_variants.values())
+    }
 
-            self.db.add_variant(&key, downloaded_mod_variant)?;
+    /// How much disk space each installed mod uses, largest first.
+    pub fn disk_usage(&self) -> DiskUsageReport {
+        self.queries().disk_usage()
+    }
 
-            if let Some(reason) = self.db.enable_variant(&key)? {
-                match reason {
-                    UnableToEnableReason::Conflicts(conflicts) => {
-                        info!("Conflicts detected when trying to enable {}!", key);
-                        self.handle_variant_add_conflicts(&key, &conflicts);
-                    },
-                    UnableToEnableReason::AlreadyEnabled => unreachable!(),
-                }
-            }
+    /// How much disk space the enabled skins of each character use, largest
+    /// first.
+    pub fn disk_usage_by_character(&self) -> CharacterDiskUsageReport {
+        self.queries().disk_usage_by_character()
+    }
 
-            self.db.remove_in_prog_action()?;
-        }
+    /// Which enabled mod variants occupy each asset slot. With
+    /// `conflicts_only`, only slots claimed by more than one variant are
+    /// reported.
+    pub fn occupancy(&self, conflicts_only: bool) -> OccupancyReport {
+        self.queries().occupancy(conflicts_only)
+    }
 
-        Ok(())
+    /// Installed variants that look like copies of the same upload (same
+    /// archive or nearly identical files). Nothing is removed.
+    pub fn find_duplicates(&self) -> ModManagerResult<DedupeReport> {
+        self.queries().find_duplicates()
     }
 
-    fn handle_variant_add_conflicts(
+    /// Asks which copies of each group in the report should be deleted (none
+    /// are picked by default) and deletes them. Copies are only ever deleted
+    /// if the user picks them.
+    pub async fn remove_duplicates(
         &mut self,
-        key: &VariantAndId,
-        variant_conflicts: &VariantConflictInfo,
-    ) {
-        let summary = self
-            .db
-            .get_variant_conflict_summary(key)
-            .unwrap_or_else(|| {
-                panic!(
-                    "Expected to find conflicts for mod variant {} but none were found! This is a \
-                     bug!",
-                    key
-                )
-            });
+        report: &DedupeReport,
+    ) -> ModManagerResult<BatchOutcome> {
+        let mut to_delete = Vec::new();
+        for group in report.groups.iter() {
+            let picked = self
+                .user_input_delegate
+                .select_items_from_list(&group.copies, &[]);
 
-        self.user_input_delegate
-            .display_variant_conflict_summary(&summary);
+            to_delete.extend(picked.into_iter().map(|idx| {
+                let key = &group.copies[idx].key;
+                VariantAndIdentifier {
+                    ident: ModIdentifier::Id(key.id),
+                    variant_name: key.variant_name.clone(),
+                }
+            }));
+        }
 
-        // The mod that we want to enable has one or more conflicts with other mods.
-        for variant_conflict in variant_conflicts.conflicts.iter() {
-            // Start a "transaction" of configuring mods that takes effect once all
-            // conflicts are resolved.
-            let mut mod_db_txn = self.db.resolve_conflict(variant_conflict);
+        match to_delete.is_empty() {
+            false => self.delete_variants(to_delete).await,
+            true => Ok(BatchOutcome::default()),
+        }
+    }
 
-            while let Some(sub_conflict) = mod_db_txn.get_next_conflict_to_resolve() {
-                // let slot_conflict = sub_conflict.slot();
+    /// Removes the files of every variant that has been disabled for longer
+    /// than `older_than`. The variants stay listed as installed (but can not be
+    /// enabled) until they are added again, which downloads them again.
+    pub fn gc(&mut self, older_than: chrono::Duration) -> ModManagerResult<GcReport> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+        cmd_gc(&mut self.db, Utc::now() - older_than)
+    }
 
-                let swappable_conflict = match sub_conflict {
-                    // Asset is swappable.
-                    AssetConflict::Swappable(info) => {
-                        // We can swap this.
-                        let swappable_slot = info.existing();
-                        let available_slots = info.slots_available_to_swap_into();
+    /// lib-api: The total number of bytes used by every installed mod.
+    pub fn estimate_disk_usage(&self) -> u64 {
+        self.db.estimate_disk_usage()
+    }
 
-                        self.user_input_delegate
-                            .get_variant_conflict_resolution_option_swappable(
-                                &variant_conflict.key,
-                                key,
-                                &info.into(),
-                                available_slots.num_slot_open(),
-                            )
-                    },
-                    AssetConflict::NonSwappable(info) => {
-                        // Not swappable.
-                        let res = self
-                            .user_input_delegate
-                            .get_variant_conflict_resolution_option_non_swappable(
-                                &variant_conflict.key,
-                                key,
-                                todo!(),
-                            );
-                        PickedResolutionOption::NonSwapOption(res)
-                    },
-                };
+    /// lib-api: The number of bytes used by a single mod. `0` if the mod is not
+    /// installed.
+    pub fn estimate_disk_usage_for_mod(&self, id: ModId) -> u64 {
+        self.db.estimate_disk_usage_for_mod(id)
+    }
 
-                mod_db_txn.resolve_conflict(swappable_conflict);
-            }
+    /// Gets the slot overrides applied to an installed variant. Returns `None`
+    /// if the variant is not installed.
+    pub async fn overrides(
+        &mut self,
+        ident: VariantAndIdentifier,
+    ) -> ModManagerResult<Option<VariantOverridesListing>> {
+        let key = self.resolve_key(ident).await?;
 
-            // All conflicts have been resolved. Commit the changes to the DB.
-            mod_db_txn.commit();
+        if !self.db.exists(&key) {
+            info!("The mod variant {} is not installed.", key);
+            return Ok(None);
         }
+
+        Ok(Some(cmd_overrides(key, &self.db)))
     }
 
-    pub async fn delete_variants<I: IntoIterator<Item = VariantAndIdentifier>>(
+    /// Gets the ID of a mod. Installed mods are looked up locally so that no
+    /// request needs to be made for them.
+    pub async fn resolve_mod_id(&mut self, ident: &ModIdentifier) -> ModManagerResult<ModId> {
+        if let Some(installed) = self.db.find_installed_mod(ident) {
+            return Ok(installed.id);
+        }
+
+        Ok(self
+            .mod_resolution_cache
+            .resolve_mod_ident(&self.scraper, ident)
+            .await?)
+    }
+
+    /// Resolves every name to its mod ID so that later runs can use the names
+    /// without being online. Names that can not be resolved are reported
+    /// instead of stopping the rest from being resolved.
+    pub async fn warm_name_cache<I: IntoIterator<Item = String>>(
         &mut self,
-        idents: I,
-    ) -> ModManagerResult<()> {
-        self.cleanup_any_incomplete_in_prog_action()?;
+        names: I,
+    ) -> ModManagerResult<WarmCacheReport> {
+        let mut report = WarmCacheReport::default();
 
-        for ident in idents {
-            let key = self
+        for name in names {
+            match self
                 .mod_resolution_cache
-                .resolve_key(ident.clone(), &self.scraper)
-                .await?;
-
-            if !self.db.exists(&key) {
-                info!(
-                    "Skipping deleting the mod variant {} since it was not installed.",
-                    ident
-                );
-                return Ok(());
+                .resolve_mod_name_to_id(&self.scraper, &name)
+                .await
+            {
+                Ok(id) => report.resolved.push((name, id)),
+                Err(ModNameResolverError::BananaScraperError(err)) => {
+                    report.failed.push((name, err))
+                },
+                Err(err) => return Err(err.into()),
             }
-
-            self.db
-                .journal_action_as_in_prog(Action::Remove(key.clone()))?;
-            self.db.remove_variant(&key)?;
-            self.db.remove_in_prog_action()?;
         }
 
-        Ok(())
+        Ok(report)
     }
 
-    pub fn sync_with_switch() -> ModManagerResult<()> {
-        todo!()
+    /// Removes the cached names of mods that are no longer installed. Returns
+    /// how many were removed.
+    pub fn prune_name_resolver_cache(&mut self) -> ModManagerResult<usize> {
+        let installed_ids = self
+            .db
+            .installed_mods()
+            .map(|mod_info| mod_info.id)
+            .collect::<HashSet<_>>();
+
+        Ok(self
+            .mod_resolution_cache
+            .retain(|_, id| installed_ids.contains(&id))?)
     }
 
-    pub fn check_for_updates(&mut self) -> ModManagerResult<()> {
-        todo!()
+    /// Resolves the identifier to a key. The variant can also be given by the
+    /// alias of an installed variant.
+    async fn resolve_key(&mut self, ident: VariantAndIdentifier) -> ModManagerResult<VariantAndId> {
+        let key = self
+            .mod_resolution_cache
+            .resolve_key(ident, &self.scraper)
+            .await?;
+
+        Ok(self.db.resolve_variant_alias(key))
     }
 
-    pub async fn enable_disable<I: IntoIterator<Item = VariantAndIdentifier>>(
+    /// Resolves every identifier to its key and drops any that refer to the
+    /// same variant as an earlier one. This can only be done after resolving,
+    /// since a mod name and a mod ID can refer to the same mod.
+    async fn resolve_unique_keys<I: IntoIterator<Item = VariantAndIdentifier>>(
         &mut self,
         idents: I,
-        enable: bool,
-    ) -> ModManagerResult<()> {
+    ) -> ModManagerResult<Vec<(VariantAndIdentifier, VariantAndId)>> {
+        let mut seen = HashSet::new();
+        let mut keys = Vec::new();
+
         for ident in idents {
+            let id = self.resolve_mod_id(ident.ident()).await?;
             let key = self
-                .mod_resolution_cache
-                .resolve_key(ident.clone(), &self.scraper)
-                .await?;
-
-            if !self.db.exists(&key) {
-                info!(
-                    "Skipping enabling the mod variant {} since it was not installed.",
-                    key
-                );
-                continue;
-            }
+                .db
+                .resolve_variant_alias(VariantAndId::new_from_identifier(
+                    ident.ident(),
+                    id,
+                    ident.variant_name().to_string(),
+                ));
 
-            match enable {
-                false => {
-                    self.db.disable_variant(key);
-                },
-                true => {
-                    if let Some(reason) = self.db.enable_variant(&key)? {
-                        match reason {
-                            UnableToEnableReason::Conflicts(variant_conflicts) => {
-                                self.handle_variant_add_conflicts(&key, &variant_conflicts);
-                            },
-                            UnableToEnableReason::AlreadyEnabled => {
-                                info!(
-                                    "Skipping enabling the mod {} because it was already enabled.",
-                                    key
-                                );
-                                continue;
-                            },
-                        }
-                    }
-                },
+            match seen.insert(key.clone()) {
+                false => warn!(
+                    "Ignoring {} since it refers to the mod variant {} which was already given.",
+                    ident, key
+                ),
+                true => keys.push((ident, key)),
             }
         }
 
-        Ok(())
+        Ok(keys)
     }
 
-    pub async fn change_slot(
+    /// Adds tags to an installed mod. Returns the tags that the mod did not
+    /// already have.
+    pub fn add_tags(
         &mut self,
-        ident: VariantAndIdentifier,
-        slot: SwappableAssetSlot,
-    ) -> ModManagerResult<()> {
-        let key = self
-            .mod_resolution_cache
-            .resolve_key(ident, &self.scraper)
-            .await?;
+        ident: &ModIdentifier,
+        tags: &[String],
+    ) -> ModManagerResult<Vec<String>> {
+        let id = self.installed_mod_id(ident)?;
+        Ok(self.db.add_tags(id, tags)?)
+    }
 
-        if !self.db.exists(&key) {
-            warn!("Mod variant {} does not exist!", key);
-        }
+    /// Removes tags from an installed mod. Returns the tags that the mod
+    /// actually had.
+    pub fn remove_tags(
+        &mut self,
+        ident: &ModIdentifier,
+        tags: &[String],
+    ) -> ModManagerResult<Vec<String>> {
+        let id = self.installed_mod_id(ident)?;
+        Ok(self.db.remove_tags(id, tags)?)
+    }
 
-        let available_slots = self.db.get_available_slots_to_swap_to(&slot);
-        self.user_input_delegate
-            .choose_slot_to_swap_to(slot, &available_slots);
+    pub fn tags(&self, ident: &ModIdentifier) -> ModManagerResult<&[String]> {
+        self.queries().tags(ident)
+    }
 
-        Ok(())
+    fn installed_mod_id(&self, ident: &ModIdentifier) -> ModManagerResult<ModId> {
+        self.db
+            .find_installed_mod(ident)
+            .map(|mod_info| mod_info.id)
+            .ok_or_else(|| ModManagerErr::ModNotInstalled(ident.clone()))
     }
 
-    pub fn switch_compare(&self) -> ModManagerResult<()> {
-        todo!()
+    /// Opens the GameBanana page of a mod in the default browser.
+    pub async fn open_mod_page(&mut self, ident: &ModIdentifier) -> ModManagerResult<()> {
+        let url = self.mod_page_url(ident).await?;
+        info!("Opening {}...", url);
+
+        open::that(&url).map_err(ModManagerErr::OpenFailed)
     }
 
-    fn cleanup_any_incomplete_in_prog_action(&mut self) -> ModManagerResult<()> {
-        if let Some(in_prog_act) = self.db.get_in_prog_action_if_any()? {
-            self.handle_incomplete_in_prog_action(in_prog_act)?;
+    /// The GameBanana page of a mod. An installed mod that is given by name
+    /// uses the page that was recorded when it was installed, so the name does
+    /// not need to be resolved.
+    pub async fn mod_page_url(&mut self, ident: &ModIdentifier) -> ModManagerResult<String> {
+        if let ModIdentifier::Name(_) = ident
+            && let Some(url) = self
+                .db
+                .find_installed_mod(ident)
+                .and_then(|mod_info| mod_info.source_url.clone())
+        {
+            return Ok(url);
         }
 
-        Ok(())
+        let id = self.resolve_mod_id(ident).await?;
+        Ok(self.scraper.mod_page_url(id))
     }
 
-    fn handle_incomplete_in_prog_action(&mut self, action: InProgAction) -> ModManagerResult<()> {
-        match action.deref() {
-            Action::Add(key) => {
-                // Remove the mod that is partially enabled.
-                self.db.remove_variant(key)?;
-            },
-            Action::Remove(key) => {
-                // Continue with the deletion of the mod variant.
+    /// Lets the user pick a mod out of the ones on GameBanana that match
+    /// `query` (or the top mods if it is empty) and then one of its variants
+    /// to install. Returns `None` if nothing was installed because nothing
+    /// matched or the user changed their mind.
+    pub async fn browse(
+        &mut self,
+        query: &str,
+        page: Option<usize>,
+        checksum_policy: ChecksumPolicy,
+        conflict_policy: AddConflictPolicy,
+    ) -> ModManagerResult<Option<BatchOutcome>> {
+        let results = cmd_search_mods(query, page, &self.scraper).await?;
+        if results.is_empty() {
+            info!("No mods on GameBanana matched \"{}\".", query);
+            return Ok(None);
+        }
+
+        let picked = &results[self.user_input_delegate.select_item_from_list(&results)];
+        if !self
+            .user_input_delegate
+            .confirm_mod_install(picked.id, &picked.name)
+        {
+            return Ok(None);
+        }
+
+        let files = self.scraper.list_mod_files(picked.id).await?;
+        let file = match files.len() {
+            0 => {
+                warn!("The mod {} has no files available to install.", picked);
+                return Ok(None);
+            },
+            1 => &files[0],
+            _ => {
+                let choices = files
+                    .iter()
+                    .map(|file| format!("{} ({})", file.name, fmt_size(file.size_bytes)))
+                    .collect::<Vec<_>>();
+
+                &files[self.user_input_delegate.select_item_from_list(&choices)]
+            },
+        };
+
+        let ident = VariantAndIdentifier {
+            ident: ModIdentifier::Id(picked.id),
+            variant_name: file.name.clone(),
+        };
+
+        self.add_mods(
+            [ident],
+            None,
+            VariantMatchPolicy::Fuzzy,
+            checksum_policy,
+            conflict_policy,
+            None,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Lists the variants that can be downloaded for a mod on GameBanana.
+    pub async fn list_variants(
+        &mut self,
+        ident: &ModIdentifier,
+    ) -> ModManagerResult<ModVariantsListing> {
+        let id = self
+            .mod_resolution_cache
+            .resolve_mod_ident(&self.scraper, ident)
+            .await?;
+
+        cmd_list_variants(id, &self.scraper).await
+    }
+
+    /// Downloads and installs the given mod variants. Up to
+    /// `download.max_concurrent` variants are downloaded at once, but they are
+    /// installed one at a time in the order that they were given.
+    ///
+    /// If `operation_timeout` is set, adding any single variant that takes
+    /// longer than it is aborted (and anything it left behind is cleaned up).
+    /// The remaining variants are still added.
+    ///
+    /// A variant that fails to be added does not stop the others either. Its
+    /// error ends up in the `failed` of the returned outcome instead.
+    ///
+    /// If a previous add was interrupted while installing a variant, that
+    /// variant is downloaded again and installed first (as part of the
+    /// returned outcome).
+    ///
+    /// With `VariantMatchPolicy::Exact`, a variant is only added if a file on
+    /// the mod page has exactly its name, and the user is never asked to pick
+    /// one from the fuzzy matches.
+    ///
+    /// `checksum_policy` should only ever skip checksum verification if the
+    /// user explicitly asked for it.
+    ///
+    /// If `asset_types` is given, only the files of each variant that change
+    /// those kinds of assets are installed (eg. just the skins of a mod that
+    /// also has a stage).
+    pub async fn add_mods<I: IntoIterator<Item = VariantAndIdentifier>>(
+        &mut self,
+        idents: I,
+        operation_timeout: Option<Duration>,
+        match_policy: VariantMatchPolicy,
+        checksum_policy: ChecksumPolicy,
+        conflict_policy: AddConflictPolicy,
+        asset_types: Option<&[AssetType]>,
+    ) -> ModManagerResult<BatchOutcome> {
+        let interrupted_add = match self.db.get_in_prog_action_if_any()?.as_deref() {
+            Some(Action::Add {
+                key,
+                resume: Some(resume),
+                ..
+            }) => Some((key.clone(), resume.clone())),
+            _ => None,
+        };
+
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let mut outcome = BatchOutcome::default();
+
+        if let Some((key, resume)) = interrupted_add {
+            info!("Resuming the interrupted add of {}...", key);
+
+            let ident_and_variant = VariantAndIdentifier {
+                ident: ModIdentifier::Id(key.id),
+                variant_name: key.variant_name.clone(),
+            };
+
+            match self
+                .resume_interrupted_add(&key, resume, conflict_policy)
+                .await
+            {
+                Ok(resolved) => outcome.record_added(ident_and_variant, &key, resolved),
+                Err(err) => outcome.failed.push((ident_and_variant, err)),
+            }
+        }
+
+        let keys = self.resolve_unique_keys(idents).await?;
+        let downloads = self
+            .download_variants(&keys, operation_timeout, match_policy, checksum_policy)
+            .await?;
+
+        for ((ident_and_variant, key), download) in keys.into_iter().zip(downloads) {
+            match download {
+                DownloadedVariant::AlreadyInstalled => outcome
+                    .skipped
+                    .push((ident_and_variant, SkipReason::AlreadyInstalled)),
+                DownloadedVariant::TimedOut => {
+                    warn!(
+                        "Adding the mod variant {} did not finish within {:?}. Aborting it...",
+                        ident_and_variant,
+                        operation_timeout.unwrap()
+                    );
+
+                    self.db
+                        .remove_quarantine_dir(&self.db.get_quarantine_dir_path(&key))?;
+                    outcome
+                        .skipped
+                        .push((ident_and_variant, SkipReason::TimedOut));
+                },
+                DownloadedVariant::Failed(err) => {
+                    self.db
+                        .remove_quarantine_dir(&self.db.get_quarantine_dir_path(&key))?;
+                    outcome.failed.push((ident_and_variant, err));
+                },
+                DownloadedVariant::Downloaded(payload) => {
+                    info!("Installing mod variant {}...", ident_and_variant);
+
+                    match self.install_downloaded_variant(
+                        &key,
+                        payload,
+                        conflict_policy,
+                        asset_types,
+                    ) {
+                        Ok(resolved) => outcome.record_added(ident_and_variant, &key, resolved),
+                        Err(err) => outcome.failed.push((ident_and_variant, err)),
+                    }
+                },
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Downloads every variant that is not installed yet into quarantine. The
+    /// file to download is picked on the current task (since the user may need
+    /// to be asked), while the downloads themselves run in parallel. The
+    /// results are in the same order as `keys`.
+    async fn download_variants(
+        &mut self,
+        keys: &[(VariantAndIdentifier, VariantAndId)],
+        operation_timeout: Option<Duration>,
+        match_policy: VariantMatchPolicy,
+        checksum_policy: ChecksumPolicy,
+    ) -> ModManagerResult<Vec<DownloadedVariant>> {
+        let permits = Arc::new(Semaphore::new(self.config.max_concurrent_downloads()));
+        let mut tasks = JoinSet::new();
+        let mut downloads = Vec::with_capacity(keys.len());
+
+        for (i, (ident_and_variant, key)) in keys.iter().enumerate() {
+            // `add_variant` also refuses to overwrite an installed variant, but checking
+            // here avoids downloading it for nothing. Variants whose files were removed by
+            // gc are downloaded again.
+            if self
+                .db
+                .get_variant(key)
+                .is_some_and(|var_info| !var_info.is_data_collected())
+            {
+                info!(
+                    "Skipping adding the mod variant {} since it was already installed. (If you \
+                     want to check for mod updates, run the update command.)",
+                    key
+                );
+                downloads.push(Some(DownloadedVariant::AlreadyInstalled));
+                continue;
+            }
+
+            info!(
+                "Downloading mod variant {} ({}/{})...",
+                ident_and_variant,
+                i + 1,
+                keys.len()
+            );
+
+            let started_at = Instant::now();
+            let find_fut =
+                self.scraper
+                    .find_mod_variant(&mut self.user_input_delegate, key, match_policy);
+
+            let found = match run_with_timeout(operation_timeout, find_fut).await {
+                Some(Ok(found)) => found,
+                Some(Err(err)) => {
+                    downloads.push(Some(DownloadedVariant::Failed(err.into())));
+                    continue;
+                },
+                None => {
+                    downloads.push(Some(DownloadedVariant::TimedOut));
+                    continue;
+                },
+            };
+
+            let quarantine_dir = self.db.get_quarantine_dir_path(key);
+            self.db.create_quarantine_dir(&quarantine_dir)?;
+            let dest_path = quarantine_dir.join(&key.variant_name);
+
+            // Time spent waiting for a permit does not count against the variant.
+            let scraper = self.scraper.clone();
+            let permits = permits.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await.unwrap();
+                let remaining_time =
+                    operation_timeout.map(|t| t.saturating_sub(started_at.elapsed()));
+                let download_fut =
+                    scraper.download_found_variant(found, checksum_policy, &dest_path);
+
+                (i, run_with_timeout(remaining_time, download_fut).await)
+            });
+
+            downloads.push(None);
+        }
+
+        while let Some(joined) = tasks.join_next().await {
+            let (i, res) = joined.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+
+            downloads[i] = Some(match res {
+                Some(Ok(payload)) => DownloadedVariant::Downloaded(payload),
+                Some(Err(err)) => DownloadedVariant::Failed(err.into()),
+                None => DownloadedVariant::TimedOut,
+            });
+        }
+
+        Ok(downloads.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// Downloads the variant of an add that was rolled back after being
+    /// interrupted from where it was originally downloaded, and installs it.
+    /// Returns how the conflicts that it had were resolved.
+    async fn resume_interrupted_add(
+        &mut self,
+        key: &VariantAndId,
+        resume: ResumableDownload,
+        conflict_policy: AddConflictPolicy,
+    ) -> ModManagerResult<ResolvedAddConflicts> {
+        let asset_types = resume.asset_types;
+        let quarantine_dir = self.db.get_quarantine_dir_path(key);
+        self.db.create_quarantine_dir(&quarantine_dir)?;
+        let dest_path = quarantine_dir.join(&key.variant_name);
+
+        let res = self
+            .scraper
+            .download_file(
+                &key.variant_name,
+                &resume.mod_name,
+                &resume.upstream,
+                ChecksumPolicy::Verify,
+                &dest_path,
+            )
+            .await;
+
+        if let Err(err) = res {
+            self.db.remove_quarantine_dir(&quarantine_dir)?;
+            return Err(err.into());
+        }
+
+        let payload = ScrapedBananaModData {
+            mod_name: resume.mod_name,
+            variant_name: key.variant_name.clone(),
+            version: resume.version,
+            source_url: self.scraper.mod_page_url(key.id),
+            upstream: resume.upstream,
+            variant_download_artifact: dest_path,
+        };
+
+        self.install_downloaded_variant(key, payload, conflict_policy, asset_types.as_deref())
+    }
+
+    /// Returns how the conflicts of the variant were resolved to install it.
+    fn install_downloaded_variant(
+        &mut self,
+        key: &VariantAndId,
+        payload: ScrapedBananaModData,
+        conflict_policy: AddConflictPolicy,
+        asset_types: Option<&[AssetType]>,
+    ) -> ModManagerResult<ResolvedAddConflicts> {
+        let quarantine_dir = self.db.get_quarantine_dir_path(key);
+        self.db.journal_action_as_in_prog(Action::Add {
+            key: key.clone(),
+            quarantine_dir: quarantine_dir.clone(),
+            resume: Some(ResumableDownload {
+                mod_name: payload.mod_name.clone(),
+                version: payload.version.clone(),
+                upstream: payload.upstream.clone(),
+                asset_types: asset_types.map(<[_]>::to_vec),
+            }),
+        })?;
+
+        let resolved = match self.add_downloaded_variant(key, payload, conflict_policy, asset_types)
+        {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                // Don't leave anything from a partially installed download behind. The rest
+                // of the batch is still waiting in quarantine, so only this add is undone.
+                self.undo_any_in_prog_action()?;
+                return Err(err);
+            },
+        };
+
+        self.db.remove_quarantine_dir(&quarantine_dir)?;
+        self.db.remove_in_prog_action()?;
+
+        Ok(resolved)
+    }
+
+    fn add_downloaded_variant(
+        &mut self,
+        key: &VariantAndId,
+        payload: ScrapedBananaModData,
+        conflict_policy: AddConflictPolicy,
+        asset_types: Option<&[AssetType]>,
+    ) -> ModManagerResult<ResolvedAddConflicts> {
+        self.db.add_variant(key, payload, asset_types)?;
+
+        let mut resolved = ResolvedAddConflicts::default();
+        match self.db.enable_variant(key)? {
+            EnableVariantOutcome::Enabled | EnableVariantOutcome::EnabledWithWarnings(_) => (),
+            EnableVariantOutcome::Conflicts(conflicts) => {
+                info!("Conflicts detected when trying to enable {}!", key);
+                resolved = self.handle_variant_add_conflicts(key, &conflicts, conflict_policy)?;
+            },
+            EnableVariantOutcome::AlreadyEnabled | EnableVariantOutcome::DataCollected => {
+                unreachable!()
+            },
+        }
+
+        self.db.sync_mod_info_to_disk(key.id)?;
+
+        Ok(resolved)
+    }
+
+    /// Returns how many of the conflicting variants the conflicts were resolved
+    /// with, along with any other variants of the same mod that were replaced.
+    fn handle_variant_add_conflicts(
+        &mut self,
+        key: &VariantAndId,
+        variant_conflicts: &VariantConflictInfo,
+        conflict_policy: AddConflictPolicy,
+    ) -> ModManagerResult<ResolvedAddConflicts> {
+        let mut resolved = ResolvedAddConflicts {
+            num_resolved: variant_conflicts.conflicts.len(),
+            ..Default::default()
+        };
+
+        // The mod that we want to enable has one or more conflicts with other mods.
+        for variant_conflict in variant_conflicts.conflicts.iter() {
+            let alias_of = |key: &VariantAndId| {
+                self.db
+                    .get_variant(key)
+                    .and_then(|var_info| var_info.alias().map(str::to_string))
+            };
+            let summary = VariantConflictSummary::new(key.clone(), variant_conflict.key.clone())
+                .with_aliases(alias_of(key), alias_of(&variant_conflict.key));
+            self.user_input_delegate
+                .display_variant_conflict_summary(&summary);
+
+            // Variants of the same mod often overlap on purpose (eg. an "all slots" and a
+            // "c02 only" variant), so the user most likely just wants one instead of the
+            // other.
+            let replace_sibling = conflict_policy == AddConflictPolicy::Prompt
+                && summary.is_same_mod()
+                && self
+                    .user_input_delegate
+                    .confirm_replace_sibling_variant(&variant_conflict.key, key);
+
+            // Start a "transaction" of configuring mods that takes effect once all
+            // conflicts are resolved.
+            let mut mod_db_txn = self.db.resolve_conflict(key, variant_conflict);
+
+            while let Some(sub_conflict) = mod_db_txn.get_next_conflict_to_resolve() {
+                // let slot_conflict = sub_conflict.slot();
+
+                if replace_sibling {
+                    mod_db_txn.resolve_conflict(PickedResolutionOption::NonSwapOption(
+                        PickedNonSwappableResolutionOption::Replace,
+                    ));
+                    continue;
+                }
+
+                if let Some(resolution) = conflict_policy.preset_resolution(&sub_conflict) {
+                    mod_db_txn.resolve_conflict(resolution);
+                    continue;
+                }
+
+                let swappable_conflict = match sub_conflict {
+                    // Asset is swappable.
+                    AssetConflict::Swappable(info) => {
+                        // We can swap this.
+                        let swappable_slot = info.existing();
+                        let available_slots = info.slots_available_to_swap_into();
+
+                        self.user_input_delegate
+                            .get_variant_conflict_resolution_option_swappable(
+                                &variant_conflict.key,
+                                key,
+                                &info.into(),
+                                available_slots.num_slot_open(),
+                            )
+                    },
+                    AssetConflict::NonSwappable(info) => {
+                        // Not swappable.
+                        let res = self
+                            .user_input_delegate
+                            .get_variant_conflict_resolution_option_non_swappable(
+                                &variant_conflict.key,
+                                key,
+                                &info.into(),
+                            );
+                        PickedResolutionOption::NonSwapOption(res)
+                    },
+                };
+
+                mod_db_txn.resolve_conflict(swappable_conflict);
+            }
+
+            // All conflicts have been resolved. Commit the changes to the DB.
+            if mod_db_txn.commit()? {
+                info!("Enabled {} after resolving its conflicts.", key);
+            }
+
+            if replace_sibling {
+                info!(
+                    "Disabled {} in favor of {} from the same mod.",
+                    variant_conflict.key, key
+                );
+                resolved
+                    .replaced_siblings
+                    .push(variant_conflict.key.clone());
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Deletes the given mod variants. Variants that are not installed are
+    /// skipped.
+    pub async fn delete_variants<I: IntoIterator<Item = VariantAndIdentifier>>(
+        &mut self,
+        idents: I,
+    ) -> ModManagerResult<BatchOutcome> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let mut outcome = BatchOutcome::default();
+        for (ident, key) in self.resolve_unique_keys(idents).await? {
+            if !self.db.exists(&key) {
+                info!(
+                    "Skipping deleting the mod variant {} since it was not installed.",
+                    ident
+                );
+                outcome.skipped.push((ident, SkipReason::NotInstalled));
+                continue;
+            }
+
+            self.db
+                .journal_action_as_in_prog(Action::Remove(key.clone()))?;
+            self.db.remove_variant(&key)?;
+            self.db.remove_in_prog_action()?;
+
+            outcome.succeeded.push(ident);
+        }
+
+        Ok(outcome)
+    }
+
+    /// Syncs all enabled mods to the given target (or the default target if no
+    /// target is given). Files that fail to copy do not stop the sync, but end
+    /// up in the `failed` of the returned outcome.
+    pub fn sync_with_switch(
+        &mut self,
+        target_name: Option<&str>,
+        layout: DestLayout,
+    ) -> ModManagerResult<SyncCopyOutcome> {
+        let plan = self.plan_sync_with_switch(target_name, layout)?;
+        let target_name = plan.target_name.clone();
+        let (new_state, outcome) = cmd_apply_sync_plan(plan, &mut self.user_input_delegate)?;
+
+        info!(
+            "Synced {} mod variants to \"{}\".",
+            new_state.synced_variants.len(),
+            target_name
+        );
+
+        self.sync_targets.update_state(&target_name, new_state)?;
+
+        Ok(outcome)
+    }
+
+    /// Works out what `sync_with_switch` would copy to and remove from the
+    /// target without writing anything.
+    pub fn plan_sync_with_switch(
+        &self,
+        target_name: Option<&str>,
+        layout: DestLayout,
+    ) -> ModManagerResult<SyncPlan> {
+        let (target_name, target) = self.sync_targets.resolve(target_name)?;
+
+        Ok(cmd_plan_sync(
+            &self.db,
+            target_name,
+            target,
+            layout,
+            self.sync_targets.state(target_name),
+        )?)
+    }
+
+    pub fn add_sync_target(
+        &mut self,
+        name: String,
+        target: SyncTarget,
+        make_default: bool,
+    ) -> ModManagerResult<()> {
+        self.sync_targets.add(name, target, make_default)?;
+        Ok(())
+    }
+
+    pub fn remove_sync_target(&mut self, name: &str) -> ModManagerResult<()> {
+        self.sync_targets.remove(name)?;
+        Ok(())
+    }
+
+    pub fn list_sync_targets(&self) -> &SyncTargets {
+        &self.sync_targets
+    }
+
+    /// Checks every installed variant for a newer version on GameBanana.
+    ///
+    /// Mods that were checked within the recheck interval from the config are
+    /// skipped (and reported as recently checked) unless `force` is set.
+    ///
+    /// If `auto_update` is set, every variant with an update available
+    /// (that is not part of a mod in `exclude`) gets updated without asking
+    /// the user. Failing to update one variant does not stop the others from
+    /// being updated, and any failures are included in the report.
+    pub async fn check_for_updates(
+        &mut self,
+        auto_update: bool,
+        exclude: &[ModId],
+        force: bool,
+    ) -> ModManagerResult<UpdateCheckReport> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let skip_checked_since =
+            (!force).then(|| Utc::now() - self.config.update_recheck_interval());
+        let mut report = self
+            .check_installed_for_updates(true, skip_checked_since, false)
+            .await?;
+
+        if auto_update {
+            for update in report.updates.iter() {
+                if exclude.contains(&update.key.id) {
+                    info!("Skipping update for {} since it was excluded.", update.key);
+                    continue;
+                }
+
+                match self.update_mod(update).await {
+                    Ok(_) => info!("Updated {}.", update.key),
+                    Err(err) => {
+                        warn!("Failed to update {}: {}", update.key, err);
+                        report.failed_updates.push(FailedUpdate {
+                            key: update.key.clone(),
+                            mod_name: update.mod_name.clone(),
+                            reason: err.to_string(),
+                        });
+                    },
+                }
+            }
+
+            report.sort();
+        }
+
+        Ok(report)
+    }
+
+    /// The value of a setting along with where it came from.
+    pub fn config_get(&self, key: ConfigKey) -> (String, ConfigValueSource) {
+        self.queries().config_get(key)
+    }
+
+    /// Every setting along with its current value.
+    pub fn config_list(&self) -> ConfigListing {
+        self.queries().config_list()
+    }
+
+    /// Validates the value and writes it to the config file.
+    pub fn config_set(&mut self, key: ConfigKey, value: &str) -> ModManagerResult<()> {
+        Ok(self.config.set(key, value)?)
+    }
+
+    /// Overrides a setting for the lifetime of the manager without changing the
+    /// config file (eg. from a command line flag). Settings that are only read
+    /// when the manager is opened (`lock.*`, `gamebanana.*` and `resolver.*`)
+    /// need to be overridden through `new_with_config` instead.
+    pub fn override_config(&mut self, key: ConfigKey, value: &str) -> ModManagerResult<()> {
+        Ok(self.config.set_override(key, value)?)
+    }
+
+    /// How conflicts should be resolved when adding if the caller has no
+    /// preference of its own.
+    pub fn default_add_conflict_policy(&self) -> AddConflictPolicy {
+        self.config.add_conflict_policy()
+    }
+
+    /// Sets whether updates are applied without asking about each variant by
+    /// default.
+    pub fn set_auto_update_default(&mut self, auto: bool) -> ModManagerResult<()> {
+        Ok(self.config.set(ConfigKey::UpdateAuto, &auto.to_string())?)
+    }
+
+    /// Updates installed variants that have a newer version on GameBanana. If
+    /// `idents` is `None`, every outdated variant gets updated.
+    ///
+    /// Failing to update one variant does not stop the others from being
+    /// updated. Any failure is instead reported in the returned summary.
+    ///
+    /// Unless `auto` is set (or auto updating is enabled in the config), the
+    /// user is asked before each variant is updated. Auto updating never makes
+    /// any conflict decisions: a variant whose new version conflicts with
+    /// another enabled variant is left disabled for the user to resolve.
+    pub async fn update<I: IntoIterator<Item = VariantAndIdentifier>>(
+        &mut self,
+        idents: Option<I>,
+        dry_run: bool,
+        auto: bool,
+    ) -> ModManagerResult<UpdateSummary> {
+        let auto = auto || self.config.auto_update();
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let mut selected_keys = None;
+        if let Some(idents) = idents {
+            let mut keys = Vec::new();
+            for ident in idents {
+                keys.push(self.resolve_key(ident).await?);
+            }
+
+            selected_keys = Some(keys);
+        }
+
+        let is_selected = |key: &VariantAndId| {
+            selected_keys
+                .as_ref()
+                .is_none_or(|keys: &Vec<VariantAndId>| keys.contains(key))
+        };
+
+        let report = self
+            .check_installed_for_updates(!auto, None, dry_run)
+            .await?;
+        let mut summary = UpdateSummary::default();
+
+        for update in report.updates {
+            if !is_selected(&update.key) {
+                continue;
+            }
+
+            if !dry_run && !auto && !self.user_input_delegate.confirm_variant_update(&update.key) {
+                summary
+                    .outcomes
+                    .push((update, VariantUpdateOutcome::Declined));
+                continue;
+            }
+
+            // Each update is journaled, so being interrupted part way through the batch
+            // only ever leaves the variant being updated to be rolled back.
+            let outcome = match dry_run {
+                false => match self.update_mod(&update).await {
+                    Ok(None) => VariantUpdateOutcome::Updated,
+                    Ok(Some(new_conflicts)) => {
+                        VariantUpdateOutcome::UpdatedButDisabled(new_conflicts)
+                    },
+                    Err(err) => {
+                        warn!("Failed to update {}: {}", update.key, err);
+                        VariantUpdateOutcome::Failed(err.to_string())
+                    },
+                },
+                true => VariantUpdateOutcome::WouldUpdate,
+            };
+
+            summary.outcomes.push((update, outcome));
+        }
+
+        Ok(summary)
+    }
+
+    /// If `prompt_for_successors` is not set, variants that look like they
+    /// were renamed upstream are reported as unreachable instead of asking the
+    /// user whether to adopt the new file. During a `dry_run`, declining a
+    /// successor is not remembered.
+    async fn check_installed_for_updates(
+        &mut self,
+        prompt_for_successors: bool,
+        skip_checked_since: Option<DateTime<Utc>>,
+        dry_run: bool,
+    ) -> ModManagerResult<UpdateCheckReport> {
+        let mut report = cmd_check_for_updates(
+            &self.db,
+            &self.scraper,
+            &mut self.user_input_delegate,
+            skip_checked_since,
+            self.config.update_max_concurrent_checks(),
+        )
+        .await;
+
+        let checked_at = Utc::now();
+        for id in std::mem::take(&mut report.checked_mods) {
+            self.db.set_last_update_check(id, checked_at)?;
+        }
+
+        for (key, upstream_file) in std::mem::take(&mut report.stale_upstream_files) {
+            self.db.set_variant_upstream_file(&key, upstream_file)?;
+        }
+
+        for candidate in std::mem::take(&mut report.successor_candidates) {
+            match prompt_for_successors {
+                false => report.unreachable.push(UnreachableMod {
+                    id: candidate.entry.key.id,
+                    mod_name: candidate.entry.mod_name,
+                    variant_name: Some(candidate.entry.key.variant_name),
+                    reason: format!(
+                        "Possibly renamed upstream to \"{}\" (run the update without `--auto` to \
+                         adopt it)",
+                        candidate.successor_file_name
+                    ),
+                }),
+                true => self.resolve_successor_candidate(candidate, &mut report, dry_run)?,
+            }
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    /// Asks the user if the file that looks like the replacement of an
+    /// installed variant should be adopted. If it is, the variant gets updated
+    /// like any other variant (and is tracked under the new file name from
+    /// then on). Otherwise it is marked as orphaned so that we stop asking.
+    fn resolve_successor_candidate(
+        &mut self,
+        candidate: SuccessorCandidate,
+        report: &mut UpdateCheckReport,
+        dry_run: bool,
+    ) -> ModManagerResult<()> {
+        let SuccessorCandidate {
+            mut entry,
+            successor_file_name,
+        } = candidate;
+
+        // The new file may have been added as its own variant already.
+        if self.db.exists(&VariantAndId::new(
+            entry.key.id,
+            successor_file_name.clone(),
+        )) {
+            report.unreachable.push(UnreachableMod {
+                id: entry.key.id,
+                mod_name: entry.mod_name,
+                variant_name: Some(entry.key.variant_name),
+                reason: format!(
+                    "Renamed upstream to \"{}\", which is already installed as another variant",
+                    successor_file_name
+                ),
+            });
+
+            return Ok(());
+        }
+
+        if !self
+            .user_input_delegate
+            .confirm_variant_successor(&entry.key, &successor_file_name)
+        {
+            if !dry_run {
+                self.db.set_variant_orphaned_upstream(&entry.key, true)?;
+            }
+
+            report.unreachable.push(UnreachableMod {
+                id: entry.key.id,
+                mod_name: entry.mod_name,
+                variant_name: Some(entry.key.variant_name),
+                reason: "Orphaned upstream (the suggested replacement was declined)".to_string(),
+            });
+
+            return Ok(());
+        }
+
+        entry.successor_file_name = Some(successor_file_name);
+        report.updates.push(entry);
+
+        Ok(())
+    }
+
+    /// Downloads the latest version of an installed variant and replaces the
+    /// installed one with it. If the new version conflicts with an enabled
+    /// variant, it is left disabled and the variants that it did not already
+    /// conflict with before the update are returned.
+    ///
+    /// If the update adopts a renamed upstream file, the variant is only left
+    /// renamed if the update goes through.
+    async fn update_mod(
+        &mut self,
+        update: &UpdateCheckEntry,
+    ) -> ModManagerResult<Option<Vec<VariantAndId>>> {
+        let key = &match &update.successor_file_name {
+            Some(successor_file_name) => {
+                VariantAndId::new(update.key.id, successor_file_name.clone())
+            },
+            None => update.key.clone(),
+        };
+        if *key != update.key && self.db.exists(key) {
+            return Err(ModDbError::VariantAlreadyExists(key.clone()).into());
+        }
+
+        let conflict_partners_before = self.db.conflict_partners(&update.key);
+        let quarantine_dir = self.db.get_quarantine_dir_path(key);
+        self.db.drop_previous_variant_version(&update.key)?;
+        self.db.journal_action_as_in_prog(Action::Update {
+            key: update.key.clone(),
+            quarantine_dir: quarantine_dir.clone(),
+            successor_name: update.successor_file_name.clone(),
+        })?;
+
+        let res = self
+            .adopt_successor_and_apply_update(update, key, &quarantine_dir)
+            .await;
+        if res.is_err() {
+            // Put back whatever was installed (and what it was called) before we
+            // started.
+            self.discard_incomplete_update(&update.key, update.successor_file_name.as_deref())?;
+        }
+
+        self.db.remove_quarantine_dir(&quarantine_dir)?;
+        self.db.remove_in_prog_action()?;
+
+        let Some(conflicts) = res? else {
+            return Ok(None);
+        };
+
+        warn!(
+            "The updated version of {} conflicts with {} other enabled variant(s). Disabling...",
+            key,
+            conflicts.conflicts.len()
+        );
+
+        Ok(Some(
+            conflicts
+                .conflicts
+                .into_iter()
+                .map(|conflict| conflict.key)
+                .filter(|conflict_key| !conflict_partners_before.contains(conflict_key))
+                .collect(),
+        ))
+    }
+
+    /// Renames the variant to `key` first if the update adopts a renamed
+    /// upstream file.
+    async fn adopt_successor_and_apply_update(
+        &mut self,
+        update: &UpdateCheckEntry,
+        key: &VariantAndId,
+        quarantine_dir: &Utf8Path,
+    ) -> ModManagerResult<Option<VariantConflictInfo>> {
+        if *key != update.key {
+            info!(
+                "Adopting \"{}\" as the successor of {}.",
+                key.variant_name, update.key
+            );
+            self.db.rename_variant(&update.key, &key.variant_name)?;
+        }
+
+        self.download_and_apply_update(update, key, quarantine_dir)
+            .await
+    }
+
+    /// Undoes an update of `key` that did not finish. If the update was
+    /// adopting a renamed upstream file, the variant also gets its old name
+    /// back.
+    fn discard_incomplete_update(
+        &mut self,
+        key: &VariantAndId,
+        successor_name: Option<&str>,
+    ) -> ModManagerResult<()> {
+        let renamed_key = successor_name.map(|name| VariantAndId::new(key.id, name.to_string()));
+
+        match renamed_key {
+            Some(renamed_key) if self.db.exists(&renamed_key) => {
+                self.db.discard_incomplete_variant_update(&renamed_key)?;
+                self.db.rename_variant(&renamed_key, &key.variant_name)?;
+            },
+            _ => self.db.discard_incomplete_variant_update(key)?,
+        }
+
+        Ok(())
+    }
+
+    /// The update check already found the file to download, so the mod page
+    /// is not scraped again.
+    async fn download_and_apply_update(
+        &mut self,
+        update: &UpdateCheckEntry,
+        key: &VariantAndId,
+        quarantine_dir: &Utf8Path,
+    ) -> ModManagerResult<Option<VariantConflictInfo>> {
+        self.db.create_quarantine_dir(quarantine_dir)?;
+
+        let dest_path = quarantine_dir.join(&key.variant_name);
+        self.scraper
+            .download_file(
+                &key.variant_name,
+                &update.mod_name,
+                &update.upstream_file,
+                ChecksumPolicy::Verify,
+                &dest_path,
+            )
+            .await?;
+
+        let payload = ScrapedBananaModData {
+            mod_name: update.mod_name.clone(),
+            variant_name: key.variant_name.clone(),
+            version: update.upstream_version.clone(),
+            source_url: self.scraper.mod_page_url(key.id),
+            upstream: update.upstream_file.clone(),
+            variant_download_artifact: dest_path,
+        };
+
+        Ok(self.db.update_variant(key, payload)?)
+    }
+
+    /// Restores the version of a variant that was installed before it was last
+    /// updated.
+    pub async fn rollback_update(&mut self, ident: VariantAndIdentifier) -> ModManagerResult<()> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let key = self.resolve_key(ident).await?;
+
+        match self.db.rollback_variant_update(&key)? {
+            false => warn!("No previous version of {} to roll back to.", key),
+            true => info!("Rolled back {} to its previous version.", key),
+        }
+
+        Ok(())
+    }
+
+    /// Classifies the files of an installed variant again without downloading
+    /// it, so that variants installed before the classifier learned about an
+    /// asset pick it up. If the variant now conflicts with another enabled
+    /// variant, it gets disabled.
+    pub async fn reclassify(&mut self, ident: VariantAndIdentifier) -> ModManagerResult<()> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let key = self.resolve_key(ident).await?;
+
+        match self.db.reclassify_variant(&key)? {
+            Some(conflicts) => warn!(
+                "After being reclassified, {} conflicts with {} other enabled variant(s). \
+                 Disabling...",
+                key,
+                conflicts.conflicts.len()
+            ),
+            None => info!("Reclassified the files of {}.", key),
+        }
+
+        Ok(())
+    }
+
+    /// Gets the note attached to an installed variant (if any).
+    pub async fn note(&mut self, ident: VariantAndIdentifier) -> ModManagerResult<Option<String>> {
+        let key = self.resolve_key(ident).await?;
+
+        self.db
+            .get_variant(&key)
+            .map(|var_info| var_info.notes.clone())
+            .ok_or(ModManagerErr::VariantNotInstalled(key))
+    }
+
+    /// Attaches a note to an installed variant, replacing any existing one.
+    /// Passing `None` (or a blank note) clears it.
+    pub async fn set_note(
+        &mut self,
+        ident: VariantAndIdentifier,
+        note: Option<&str>,
+    ) -> ModManagerResult<()> {
+        let key = self.resolve_key(ident).await?;
+
+        match self.db.set_variant_notes(&key, note)? {
+            false => Err(ModManagerErr::VariantNotInstalled(key)),
+            true => Ok(()),
+        }
+    }
+
+    /// Gives an installed variant an alias that can be used anywhere in place
+    /// of its file name. Passing `None` clears it.
+    pub async fn set_alias(
+        &mut self,
+        ident: VariantAndIdentifier,
+        alias: Option<&str>,
+    ) -> ModManagerResult<()> {
+        let key = self.resolve_key(ident).await?;
+
+        match self.db.set_variant_alias(&key, alias)? {
+            false => Err(ModManagerErr::VariantNotInstalled(key)),
+            true => Ok(()),
+        }
+    }
+
+    /// Asks the user for the note of a variant through the input delegate.
+    pub async fn prompt_for_note(&mut self, ident: VariantAndIdentifier) -> ModManagerResult<()> {
+        let prompt = format!(
+            "Enter a note for {} (leave empty to clear it):",
+            ident.variant_name
+        );
+        let note = self.user_input_delegate.get_free_text_resp(&prompt);
+
+        self.set_note(ident, Some(&note)).await
+    }
+
+    pub async fn enable_disable<I: IntoIterator<Item = VariantAndIdentifier>>(
+        &mut self,
+        idents: I,
+        enable: bool,
+    ) -> ModManagerResult<EnableDisableSummary> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let keys = self
+            .resolve_unique_keys(idents)
+            .await?
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect();
+
+        self.journal_and_apply_enable_disable(keys, enable)
+    }
+
+    /// Enables or disables every variant of every mod with the tag.
+    pub fn enable_disable_tagged(
+        &mut self,
+        tag: &str,
+        enable: bool,
+    ) -> ModManagerResult<EnableDisableSummary> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let tag = normalize_tag(tag)?;
+        let keys = self.db.variants_with_tag(&tag);
+        if keys.is_empty() {
+            info!("No installed mods have the tag \"{}\".", tag);
+        }
+
+        self.journal_and_apply_enable_disable(keys, enable)
+    }
+
+    fn journal_and_apply_enable_disable(
+        &mut self,
+        keys: Vec<VariantAndId>,
+        enable: bool,
+    ) -> ModManagerResult<EnableDisableSummary> {
+        // Journal the entire batch so that if we crash part way through, we can finish
+        // writing the state of every mod touched to disk on the next run.
+        self.db.journal_action_as_in_prog(Action::SetEnabled {
+            keys: keys.clone(),
+            enabled: enable,
+        })?;
+        let summary = self.apply_enable_disable(keys, enable)?;
+        self.db.remove_in_prog_action()?;
+
+        Ok(summary)
+    }
+
+    fn apply_enable_disable(
+        &mut self,
+        keys: Vec<VariantAndId>,
+        enable: bool,
+    ) -> ModManagerResult<EnableDisableSummary> {
+        let mut summary = EnableDisableSummary {
+            style: self.style,
+            ..Default::default()
+        };
+        let mut touched_mods = HashSet::new();
+
+        for key in keys {
+            let outcome = match self.db.exists(&key) {
+                false => {
+                    info!(
+                        "Skipping {} the mod variant {} since it was not installed.",
+                        enable_str(enable),
+                        key
+                    );
+                    VariantToggleOutcome::NotInstalled
+                },
+                true => match enable {
+                    false => match self.db.disable_variant(&key) {
+                        DisableVariantOutcome::Disabled => VariantToggleOutcome::Disabled,
+                        DisableVariantOutcome::WasAlreadyDisabled => {
+                            VariantToggleOutcome::AlreadyDisabled
+                        },
+                        DisableVariantOutcome::NotInstalled => VariantToggleOutcome::NotInstalled,
+                    },
+                    true => match self.db.enable_variant(&key)? {
+                        EnableVariantOutcome::Enabled => VariantToggleOutcome::Enabled,
+                        EnableVariantOutcome::EnabledWithWarnings(shared_slots) => {
+                            VariantToggleOutcome::EnabledWithWarnings(shared_slots)
+                        },
+                        EnableVariantOutcome::AlreadyEnabled => {
+                            VariantToggleOutcome::AlreadyEnabled
+                        },
+                        EnableVariantOutcome::DataCollected => {
+                            info!(
+                                "Unable to enable {} since its files were removed by gc. Add it \
+                                 again to reinstall it.",
+                                key
+                            );
+                            VariantToggleOutcome::DataCollected
+                        },
+                        EnableVariantOutcome::Conflicts(conflicts) => {
+                            info!("Conflicts detected when trying to enable {}!", key);
+                            self.handle_variant_add_conflicts(
+                                &key,
+                                &conflicts,
+                                self.config.add_conflict_policy(),
+                            )?;
+
+                            // The conflicts may have been resolved by keeping what was already
+                            // enabled instead.
+                            match self.db.get_variant(&key).is_some_and(|v| v.is_enabled()) {
+                                false => VariantToggleOutcome::Conflicts(
+                                    conflicts.conflicts.into_iter().map(|c| c.key).collect(),
+                                ),
+                                true => VariantToggleOutcome::Enabled,
+                            }
+                        },
+                    },
+                },
+            };
+
+            if outcome.changed_state() {
+                touched_mods.insert(key.id);
+            }
+
+            summary.outcomes.push((key, outcome));
+        }
+
+        for id in touched_mods {
+            self.db.sync_mod_info_to_disk(id)?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Moves a slot used by a mod variant to an open slot that the user picks.
+    pub async fn change_slot(
+        &mut self,
+        ident: VariantAndIdentifier,
+        slot: SwappableAssetSlot,
+    ) -> ModManagerResult<()> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let key = self.resolve_key(ident).await?;
+
+        if !self.db.exists(&key) {
+            return Err(ModManagerErr::VariantNotInstalled(key));
+        }
+
+        let slot_info = SlotInfo::from(&slot);
+        let AvailableSlotsToSwapToInfo::CharacterSkin(open_slots) =
+            self.db.get_available_slots_to_swap_to(&slot);
+
+        if open_slots.is_empty() {
+            warn!(
+                "There are no open slots to move \"{}\" of {} to.",
+                slot_info.slot_name(),
+                key
+            );
+            return Ok(());
+        }
+
+        let available_slots = open_slots
+            .iter()
+            .map(|skin_slot| AvailableSlotToSwapInto::new(skin_slot.idx().into(), None))
+            .collect::<Vec<_>>();
+        let picked = self
+            .user_input_delegate
+            .choose_slot_to_swap_to(&slot_info, &available_slots);
+
+        let SwappableAssetSlot::CharacterSkin(old_slot) = slot;
+        let new_slot =
+            CharSkinSlotValue::new(old_slot.char_key().to_string(), open_slots[picked.idx()]);
+        info!("Moving \"{}\" of {} to \"{}\".", old_slot, key, new_slot);
+
+        let old_slot = AssetSlot::CharacterSkin(old_slot);
+        let new_slot = AssetSlot::CharacterSkin(new_slot);
+
+        self.db.journal_action_as_in_prog(Action::SlotChange(
+            key.clone(),
+            old_slot.clone(),
+            new_slot.clone(),
+        ))?;
+        self.db.swap_variant_slot(&key, old_slot, new_slot)?;
+        self.db.remove_in_prog_action()?;
+
+        Ok(())
+    }
+
+    /// Enables the inactive alternative `ident` in the character skin `slot`.
+    /// The variant that was enabled in the slot is disabled and kept as an
+    /// alternative for it.
+    pub async fn swap_active(
+        &mut self,
+        slot: CharSkinSlotValue,
+        ident: VariantAndIdentifier,
+    ) -> ModManagerResult<()> {
+        self.cleanup_any_incomplete_in_prog_action()?;
+
+        let key = self.resolve_key(ident).await?;
+
+        if !self.db.exists(&key) {
+            return Err(ModManagerErr::VariantNotInstalled(key));
+        }
+
+        match self.db.swap_active_alternative(&slot, &key)? {
+            Some(prev) => info!(
+                "Enabled {} in \"{}\" (keeping {} as an alternative).",
+                key, slot, prev
+            ),
+            None => info!("Enabled {} in \"{}\".", key, slot),
+        }
+
+        Ok(())
+    }
+
+    /// lib-api: Writes a report of every current conflict to a TOML file, eg.
+    /// to attach to a bug report.
+    pub fn export_conflict_report(&self, path: &Utf8Path) -> ModManagerResult<()> {
+        self.db.export_conflict_report(path)?;
+        info!("Wrote conflict report to {:?}.", path);
+
+        Ok(())
+    }
+
+    /// Compares the mods on the given target (or the default target if no
+    /// target is given) to the mods enabled in the manager. If
+    /// `install_missing` is set, any enabled variants that are not on the
+    /// target are then copied over to it (leaving everything else on the
+    /// target alone), and what was copied is included in the report.
+    pub fn switch_compare(
+        &mut self,
+        target_name: Option<&str>,
+        install_missing: bool,
+    ) -> ModManagerResult<SwitchCompareReport> {
+        let (target_name, target) = self.sync_targets.resolve(target_name)?;
+        let prev_state = self.sync_targets.state(target_name);
+        let mut report = cmd_switch_compare(&self.db, target_name, target, prev_state)?;
+
+        if !install_missing || report.not_synced().is_empty() {
+            return Ok(report);
+        }
+
+        let (new_state, outcome) = cmd_install_missing(
+            &self.db,
+            target_name,
+            target,
+            prev_state,
+            report.not_synced(),
+            &mut self.user_input_delegate,
+        )?;
+        info!(
+            "Installed {} missing mod variants on \"{}\".",
+            report.not_synced().len(),
+            target_name
+        );
+
+        let target_name = target_name.to_string();
+        self.sync_targets.update_state(&target_name, new_state)?;
+        report.set_installed_missing(outcome);
+
+        Ok(report)
+    }
+
+    /// Must only be called before a command starts making changes, since it
+    /// also clears out quarantine.
+    fn cleanup_any_incomplete_in_prog_action(&mut self) -> ModManagerResult<()> {
+        self.undo_any_in_prog_action()?;
+
+        // Concurrent downloads are only journaled once they get installed, so anything
+        // still in quarantine is from a run that was interrupted while downloading.
+        self.db.remove_all_quarantine_dirs()?;
+
+        Ok(())
+    }
+
+    fn undo_any_in_prog_action(&mut self) -> ModManagerResult<()> {
+        if let Some(in_prog_act) = self.db.get_in_prog_action_if_any()? {
+            self.handle_incomplete_in_prog_action(in_prog_act)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_incomplete_in_prog_action(&mut self, action: InProgAction) -> ModManagerResult<()> {
+        match action.deref() {
+            Action::Add {
+                key,
+                quarantine_dir,
+                resume,
+            } => {
+                // Remove the mod that is partially enabled (or expanded). (`add_mods` picks
+                // the add back up if it can.)
+                self.db.discard_incomplete_variant_add(
+                    key,
+                    resume.as_ref().map(|resume| resume.mod_name.as_str()),
+                )?;
+                self.db.remove_quarantine_dir(quarantine_dir)?;
+            },
+            Action::Remove(key) => {
+                // Continue with the deletion of the mod variant.
                 self.db.remove_variant(key)?;
             },
+            Action::Update {
+                key,
+                quarantine_dir,
+                successor_name,
+            } => {
+                // Puts back the version that was installed (and what was recorded about it)
+                // if the update got as far as moving it out of the way.
+                self.discard_incomplete_update(key, successor_name.as_deref())?;
+                self.db.remove_quarantine_dir(quarantine_dir)?;
+            },
+            Action::SetEnabled { keys, enabled } => {
+                // Finish applying the batch. The state was just read back from disk, so
+                // anything that was already applied is a no-op and only the mods that still
+                // change get written.
+                self.apply_enable_disable(keys.clone(), *enabled)?;
+            },
+            Action::SlotChange(key, old_slot, new_slot) => {
+                // The override is written to `mod_info.toml` in one go, so if the variant is
+                // no longer in the old slot then the swap already went through.
+                if self.db.exists(key) {
+                    match self
+                        .db
+                        .swap_variant_slot(key, old_slot.clone(), new_slot.clone())
+                    {
+                        Ok(()) | Err(ModDbError::SlotNotUsedByVariant(..)) => (),
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+            },
         }
 
         // We finished cleaning up the in progress action, so now we can remove it from
@@ -305,8 +2098,429 @@ impl<U: UserInputDelegate> ModManager<U> {
     }
 }
 
+/// Why a variant in a batch add or delete was left alone.
+#[derive(Debug, Eq, PartialEq)]
+pub enum SkipReason {
+    AlreadyInstalled,
+    NotInstalled,
+
+    /// Took longer than the operation timeout and was aborted.
+    TimedOut,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::AlreadyInstalled => write!(f, "Already installed"),
+            SkipReason::NotInstalled => write!(f, "Not installed"),
+            SkipReason::TimedOut => write!(f, "Timed out"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Unknown add conflict policy \"{0}\" (expected one of \"prompt\", \"replace_existing\", \
+     \"keep_existing\" or \"keep_as_alternative\")"
+)]
+pub struct AddConflictPolicyFromStrErr(String);
+
+/// How conflicts between an added variant and the installed ones are resolved.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddConflictPolicy {
+    /// Ask the user about each conflict.
+    #[default]
+    Prompt,
+
+    /// The added variant always wins.
+    ReplaceExisting,
+
+    /// The installed variant always wins (leaving the added one disabled).
+    KeepExisting,
+
+    /// Like `KeepExisting`, but an added character skin is kept around as an
+    /// inactive alternative for the slot that it conflicted on.
+    KeepAsAlternative,
+}
+
+impl AddConflictPolicy {
+    /// The resolution to use for `conflict`, if the user should not be asked.
+    fn preset_resolution(self, conflict: &AssetConflict) -> Option<PickedResolutionOption> {
+        let resolution = match (self, conflict) {
+            (AddConflictPolicy::Prompt, _) => return None,
+            (AddConflictPolicy::ReplaceExisting, _) => PickedNonSwappableResolutionOption::Replace,
+            (AddConflictPolicy::KeepAsAlternative, AssetConflict::Swappable(_)) => {
+                return Some(PickedResolutionOption::KeepAsAlternative);
+            },
+            (AddConflictPolicy::KeepExisting | AddConflictPolicy::KeepAsAlternative, _) => {
+                PickedNonSwappableResolutionOption::KeepExisting
+            },
+        };
+
+        Some(PickedResolutionOption::NonSwapOption(resolution))
+    }
+}
+
+impl Display for AddConflictPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AddConflictPolicy::Prompt => write!(f, "prompt"),
+            AddConflictPolicy::ReplaceExisting => write!(f, "replace_existing"),
+            AddConflictPolicy::KeepExisting => write!(f, "keep_existing"),
+            AddConflictPolicy::KeepAsAlternative => write!(f, "keep_as_alternative"),
+        }
+    }
+}
+
+impl FromStr for AddConflictPolicy {
+    type Err = AddConflictPolicyFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prompt" => Ok(Self::Prompt),
+            "replace_existing" => Ok(Self::ReplaceExisting),
+            "keep_existing" => Ok(Self::KeepExisting),
+            "keep_as_alternative" => Ok(Self::KeepAsAlternative),
+            _ => Err(AddConflictPolicyFromStrErr(s.to_string())),
+        }
+    }
+}
+
+/// How the conflicts of a variant being added were resolved.
+#[derive(Debug, Default)]
+struct ResolvedAddConflicts {
+    num_resolved: usize,
+
+    /// Other variants of the same mod that were disabled in favor of it.
+    replaced_siblings: Vec<VariantAndId>,
+}
+
+/// Where a variant ended up after the download step of an add.
+enum DownloadedVariant {
+    AlreadyInstalled,
+    TimedOut,
+    Failed(ModManagerErr),
+    Downloaded(ScrapedBananaModData),
+}
+
+/// Runs `fut` to completion, or gives up and returns `None` if a timeout is
+/// given and it runs out first.
+async fn run_with_timeout<F: Future>(timeout: Option<Duration>, fut: F) -> Option<F::Output> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// What happened to each variant of a batch add or delete. Each variant is
+/// in exactly one of `succeeded`, `skipped` or `failed`, in the order that it
+/// was given.
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    /// The variants that were added (or deleted).
+    pub succeeded: Vec<VariantAndIdentifier>,
+    pub skipped: Vec<(VariantAndIdentifier, SkipReason)>,
+
+    /// A variant failing does not stop the rest of the batch.
+    pub failed: Vec<(VariantAndIdentifier, ModManagerErr)>,
+
+    /// How many conflicts with installed variants were resolved along the way.
+    pub conflicts_resolved: usize,
+
+    /// Variants that were disabled in favor of an added variant of the same
+    /// mod, along with the variant that replaced them.
+    pub replaced_siblings: Vec<(VariantAndId, VariantAndId)>,
+}
+
+impl BatchOutcome {
+    fn record_added(
+        &mut self,
+        ident: VariantAndIdentifier,
+        key: &VariantAndId,
+        resolved: ResolvedAddConflicts,
+    ) {
+        self.succeeded.push(ident);
+        self.conflicts_resolved += resolved.num_resolved;
+        self.replaced_siblings.extend(
+            resolved
+                .replaced_siblings
+                .into_iter()
+                .map(|sibling| (sibling, key.clone())),
+        );
+    }
+}
+
+impl Display for BatchOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for ident in self.succeeded.iter() {
+            writeln!(f, "{} - Done", ident)?;
+        }
+
+        for (ident, reason) in self.skipped.iter() {
+            writeln!(f, "{} - Skipped ({})", ident, reason)?;
+        }
+
+        for (ident, err) in self.failed.iter() {
+            writeln!(f, "{} - Failed: {}", ident, err)?;
+        }
+
+        if self.conflicts_resolved > 0 {
+            writeln!(f, "Resolved {} conflict(s).", self.conflicts_resolved)?;
+        }
+
+        for (sibling, added) in self.replaced_siblings.iter() {
+            writeln!(
+                f,
+                "Disabled {} in favor of {} (another variant of the same mod).",
+                sibling, added
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The names that `warm_name_cache` was (and was not) able to resolve.
+#[derive(Debug, Default)]
+pub struct WarmCacheReport {
+    pub resolved: Vec<(String, ModId)>,
+    pub failed: Vec<(String, BananaScraperError)>,
+}
+
+impl Display for WarmCacheReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, id) in self.resolved.iter() {
+            writeln!(f, "\"{}\" - {}", name, id)?;
+        }
+
+        for (name, err) in self.failed.iter() {
+            writeln!(f, "\"{}\" - Failed: {}", name, err)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What happened to a single variant when enabling/disabling.
+#[derive(Debug, Eq, PartialEq)]
+pub enum VariantToggleOutcome {
+    Enabled,
+
+    /// Enabled, but shares these extended skin slots with other enabled
+    /// variants, so only one of them shows up in game.
+    EnabledWithWarnings(Vec<AssetSlot>),
+
+    Disabled,
+    AlreadyEnabled,
+    AlreadyDisabled,
+    NotInstalled,
+
+    /// Could not be enabled because its files were removed by gc.
+    DataCollected,
+
+    /// Could not be enabled because it conflicts with these enabled variants.
+    Conflicts(Vec<VariantAndId>),
+}
+
+impl VariantToggleOutcome {
+    fn changed_state(&self) -> bool {
+        matches!(
+            self,
+            VariantToggleOutcome::Enabled
+                | VariantToggleOutcome::EnabledWithWarnings(_)
+                | VariantToggleOutcome::Disabled
+        )
+    }
+}
+
+impl Display for VariantToggleOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantToggleOutcome::Enabled => write!(f, "Enabled"),
+            VariantToggleOutcome::EnabledWithWarnings(shared_slots) => {
+                write!(f, "Enabled, but shares the extended slots ")?;
+                for (i, slot) in shared_slots.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", slot)?;
+                }
+
+                write!(f, " with other enabled variants")
+            },
+            VariantToggleOutcome::Disabled => write!(f, "Disabled"),
+            VariantToggleOutcome::AlreadyEnabled => write!(f, "Already enabled"),
+            VariantToggleOutcome::AlreadyDisabled => write!(f, "Already disabled"),
+            VariantToggleOutcome::NotInstalled => write!(f, "Not installed"),
+            VariantToggleOutcome::DataCollected => {
+                write!(f, "Files removed by gc (add it again to reinstall it)")
+            },
+            VariantToggleOutcome::Conflicts(conflicting_keys) => {
+                write!(f, "Conflicts with ")?;
+                for (i, key) in conflicting_keys.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", key)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EnableDisableSummary {
+    pub outcomes: Vec<(VariantAndId, VariantToggleOutcome)>,
+    style: Style,
+}
+
+impl Display for EnableDisableSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (key, outcome) in self.outcomes.iter() {
+            let outcome = match outcome {
+                VariantToggleOutcome::EnabledWithWarnings(_)
+                | VariantToggleOutcome::Conflicts(_) => self.style.warning(outcome),
+                _ => outcome.to_string(),
+            };
+
+            writeln!(f, "{} - {}", key, outcome)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What happened to a single variant when updating.
+#[derive(Debug, Eq, PartialEq)]
+pub enum VariantUpdateOutcome {
+    Updated,
+
+    /// Only reported when doing a dry run.
+    WouldUpdate,
+
+    /// Was updated but had to be disabled since the new version conflicts with
+    /// enabled variants. Only holds the variants that the previous version did
+    /// not already conflict with.
+    UpdatedButDisabled(Vec<VariantAndId>),
+
+    /// The user chose not to update it.
+    Declined,
+
+    Failed(String),
+}
+
+impl Display for VariantUpdateOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantUpdateOutcome::Updated => write!(f, "Updated"),
+            VariantUpdateOutcome::WouldUpdate => write!(f, "Would update"),
+            VariantUpdateOutcome::Declined => write!(f, "Skipped"),
+            VariantUpdateOutcome::UpdatedButDisabled(conflicting_keys) => {
+                write!(f, "Updated but disabled due to conflicts")?;
+                for (i, key) in conflicting_keys.iter().enumerate() {
+                    match i {
+                        0 => write!(f, " with ")?,
+                        _ => write!(f, ", ")?,
+                    }
+                    write!(f, "{}", key)?;
+                }
+
+                Ok(())
+            },
+            VariantUpdateOutcome::Failed(err) => write!(f, "Failed ({})", err),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UpdateSummary {
+    pub outcomes: Vec<(UpdateCheckEntry, VariantUpdateOutcome)>,
+}
+
+impl Display for UpdateSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.outcomes.is_empty() {
+            return writeln!(f, "Everything is up to date.");
+        }
+
+        for (update, outcome) in self.outcomes.iter() {
+            let installed_str = match update.installed_publish_date {
+                Some(date) => date.to_rfc2822(),
+                None => "unknown".to_string(),
+            };
+
+            writeln!(
+                f,
+                "{} ({} --> {}) - {}",
+                update.key,
+                installed_str,
+                update.upstream_publish_date.to_rfc2822(),
+                outcome
+            )?;
+        }
+
+        let num_newly_conflicting = self.count_outcomes(|outcome| {
+            matches!(outcome, VariantUpdateOutcome::UpdatedButDisabled(_))
+        });
+
+        writeln!(
+            f,
+            "\nUpdated: {}, newly conflicting: {}, failed: {}",
+            self.count_outcomes(|outcome| matches!(outcome, VariantUpdateOutcome::Updated)),
+            num_newly_conflicting,
+            self.count_outcomes(|outcome| matches!(outcome, VariantUpdateOutcome::Failed(_)))
+        )?;
+
+        if num_newly_conflicting > 0 {
+            writeln!(
+                f,
+                "Variants that now conflict with other enabled variants were left disabled. Run \
+                 `resolve-conflicts` to decide what to keep."
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UpdateSummary {
+    fn count_outcomes(&self, pred: impl Fn(&VariantUpdateOutcome) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| pred(outcome))
+            .count()
+    }
+}
+
+fn enable_str(enable: bool) -> &'static str {
+    match enable {
+        false => "disabling",
+        true => "enabling",
+    }
+}
+
 impl From<SwappableAssetConflict> for SlotInfo {
     fn from(value: SwappableAssetConflict) -> Self {
-        todo!()
+        match value {
+            SwappableAssetConflict::CharacterSkin(c) => {
+                SlotInfo::new(c.existing().to_string(), "Character skin".to_string())
+            },
+        }
+    }
+}
+
+impl From<NonSwappableAssetConflict> for SlotInfo {
+    fn from(value: NonSwappableAssetConflict) -> Self {
+        match value {
+            NonSwappableAssetConflict::Stage(c) => {
+                SlotInfo::new(c.existing().to_string(), "Stage skin".to_string())
+            },
+            NonSwappableAssetConflict::Global(c) => {
+                SlotInfo::new(c.existing().to_string(), "Global file".to_string())
+            },
+        }
     }
 }
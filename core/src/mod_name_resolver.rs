@@ -1,15 +1,35 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, io, mem};
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Duration, Utc};
 use log::info;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use toml::{Table, Value};
 use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaScraperError};
-use ultimate_mod_man_rs_utils::types::{ModId, ModIdentifier, VariantAndId, VariantAndIdentifier};
+use ultimate_mod_man_rs_utils::{
+    types::{ModId, ModIdentifier, VariantAndId, VariantAndIdentifier},
+    utils::DeserializationError,
+};
+
+use crate::schema::{
+    DocumentSchema, SchemaError, deserialize_versioned_from_path, serialize_versioned_to_path,
+};
 
 pub type ModNameResolverResult<T> = Result<T, ModNameResolverError>;
 
-const CACHED_MOD_NAME_RESOLUTION_STATE_NAME: &str = "mod_name_resolution_cache.toml";
+pub(crate) const CACHED_MOD_NAME_RESOLUTION_STATE_NAME: &str = "mod_name_resolution_cache.toml";
+
+/// - v2: Moved the cached names under `names`.
+const MOD_NAME_RESOLUTION_CACHE_SCHEMA: DocumentSchema =
+    DocumentSchema::new(&[move_names_into_table]);
+
+/// v1 was just the cached names at the top level, which left no room for
+/// anything else (like the schema version).
+fn move_names_into_table(doc: &mut Table) {
+    let names = mem::take(doc);
+    doc.insert("names".to_string(), Value::Table(names));
+}
 
 #[derive(Debug, Error)]
 pub enum ModNameResolverError {
@@ -18,29 +38,105 @@ pub enum ModNameResolverError {
 
     #[error(transparent)]
     ModResolutionDeserializationError(#[from] toml::de::Error),
+
+    /// The cache on disk could not be parsed. Nothing in it is needed to run,
+    /// so callers can start over with `BananaModNameResolver::new_empty`.
+    #[error("The mod name resolution cache is corrupted: {0}")]
+    CacheCorrupted(toml::de::Error),
+
+    #[error(transparent)]
+    ModResolutionSerializationError(#[from] toml::ser::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    SchemaError(#[from] SchemaError),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct BananaModNameResolver {
+    #[serde(rename = "names")]
     local_cache: HashMap<String, ModId>,
+
+    /// When each cached name was resolved. Names cached by older versions
+    /// have no entry here and are treated as if they were resolved when the
+    /// cache was loaded.
+    #[serde(default)]
+    resolved_at: HashMap<String, DateTime<Utc>>,
+
+    #[serde(skip)]
+    path: Utf8PathBuf,
+
+    /// Cached names older than this are resolved again. Kept forever if
+    /// `None`.
+    #[serde(skip)]
+    ttl: Option<Duration>,
 }
 
 impl BananaModNameResolver {
-    pub(crate) fn new(p: &Utf8Path) -> ModNameResolverResult<Self> {
+    pub(crate) fn new(p: &Utf8Path, ttl: Option<Duration>) -> ModNameResolverResult<Self> {
+        let path = p.join(CACHED_MOD_NAME_RESOLUTION_STATE_NAME);
+
+        if !path.exists() {
+            info!(
+                "No mod id resolution cache found at {:?}. Will create one during this run.",
+                path
+            );
+
+            return Ok(Self::new_empty(p, ttl));
+        }
+
+        // Only ever loaded while the state directory is locked.
+        let mut resolver = Self::read_cache_file(&path, true)?;
+
+        let now = Utc::now();
+        for name in resolver.local_cache.keys() {
+            resolver.resolved_at.entry(name.clone()).or_insert(now);
+        }
+
+        Ok(Self {
+            path,
+            ttl,
+            ..resolver
+        })
+    }
+
+    /// Checks that the cache in the state directory `p` (if there is one) can
+    /// be read. Nothing is written, so the state directory does not need to be
+    /// locked.
+    pub(crate) fn verify_cache_file(p: &Utf8Path) -> ModNameResolverResult<()> {
         let path = p.join(CACHED_MOD_NAME_RESOLUTION_STATE_NAME);
+        if path.exists() {
+            Self::read_cache_file(&path, false)?;
+        }
+
+        Ok(())
+    }
 
-        let local_cache = match fs::read_to_string(path.clone()) {
-            Ok(cache_data_str) => toml::from_str(&cache_data_str)?,
-            Err(_) => {
-                info!(
-                    "No mod id resolution cache found at {:?}. Will create one during this run.",
-                    path
-                );
-                HashMap::default()
+    fn read_cache_file(path: &Utf8Path, rewrite_upgraded: bool) -> ModNameResolverResult<Self> {
+        match deserialize_versioned_from_path(
+            path,
+            &MOD_NAME_RESOLUTION_CACHE_SCHEMA,
+            rewrite_upgraded,
+        ) {
+            Ok(resolver) => Ok(resolver),
+            Err(SchemaError::DeserializationError(DeserializationError::Deserialization(err))) => {
+                Err(ModNameResolverError::CacheCorrupted(err))
             },
-        };
+            Err(err) => Err(err.into()),
+        }
+    }
 
-        Ok(Self { local_cache })
+    /// A resolver with nothing cached. Whatever is on disk gets replaced the
+    /// next time that a name is resolved.
+    pub(crate) fn new_empty(p: &Utf8Path, ttl: Option<Duration>) -> Self {
+        Self {
+            local_cache: HashMap::default(),
+            resolved_at: HashMap::default(),
+            path: p.join(CACHED_MOD_NAME_RESOLUTION_STATE_NAME),
+            ttl,
+        }
     }
 
     pub(crate) async fn resolve_mod_ident(
@@ -54,18 +150,60 @@ impl BananaModNameResolver {
         }
     }
 
-    async fn resolve_mod_name_to_id(
+    /// Only goes to GameBanana if the name is not already cached (or if its
+    /// cache entry has expired). Anything that it resolves is written to the
+    /// cache on disk right away so that it can be used by later (possibly
+    /// offline) runs.
+    pub(crate) async fn resolve_mod_name_to_id(
         &mut self,
         scraper: &BananaClient,
         name: &str,
     ) -> ModNameResolverResult<ModId> {
-        let id = match self.local_cache.get(name) {
-            Some(id) => *id,
-            None => scraper.resolve_mod_name(name).await?,
-        };
+        if let Some(id) = self.local_cache.get(name)
+            && !self.is_expired(name)
+        {
+            return Ok(*id);
+        }
+
+        let id = scraper.resolve_mod_name(name).await?;
+        self.local_cache.insert(name.to_string(), id);
+        self.resolved_at.insert(name.to_string(), Utc::now());
+        self.sync_to_disk()?;
 
         Ok(id)
     }
+
+    /// Removes every cached name that `keep` returns `false` for and returns
+    /// how many were removed. The cache on disk is only rewritten if anything
+    /// was removed.
+    pub(crate) fn retain(
+        &mut self,
+        mut keep: impl FnMut(&str, ModId) -> bool,
+    ) -> ModNameResolverResult<usize> {
+        let num_cached = self.local_cache.len();
+        self.local_cache.retain(|name, id| keep(name, *id));
+        self.resolved_at
+            .retain(|name, _| self.local_cache.contains_key(name));
+
+        let num_removed = num_cached - self.local_cache.len();
+        if num_removed > 0 {
+            self.sync_to_disk()?;
+        }
+
+        Ok(num_removed)
+    }
+
+    fn is_expired(&self, name: &str) -> bool {
+        match (self.ttl, self.resolved_at.get(name)) {
+            (Some(ttl), Some(resolved_at)) => Utc::now() - *resolved_at > ttl,
+            _ => false,
+        }
+    }
+
+    fn sync_to_disk(&self) -> ModNameResolverResult<()> {
+        serialize_versioned_to_path(&self.path, self, &MOD_NAME_RESOLUTION_CACHE_SCHEMA)?;
+        Ok(())
+    }
 }
 
 impl BananaModNameResolver {
@@ -74,9 +212,102 @@ impl BananaModNameResolver {
         key: VariantAndIdentifier,
         scraper: &BananaClient,
     ) -> ModNameResolverResult<VariantAndId> {
-        Ok(VariantAndId {
-            id: self.resolve_mod_ident(scraper, &key.ident).await?,
-            variant_name: key.variant_name,
-        })
+        let id = self.resolve_mod_ident(scraper, key.ident()).await?;
+
+        Ok(VariantAndId::new_from_identifier(
+            key.ident(),
+            id,
+            key.variant_name().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8Path;
+    use chrono::{Duration, Utc};
+    use tempfile::TempDir;
+    use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaClientSettings};
+
+    use super::{BananaModNameResolver, ModNameResolverError};
+
+    /// A cache holding a single name that was resolved two days ago.
+    fn m_cache_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let resolved_at = (Utc::now() - Duration::days(2)).to_rfc3339();
+        fs::write(
+            dir.path().join("mod_name_resolution_cache.toml"),
+            format!(
+                "schema_version = 2\n\n[names]\n\"Bully Maguire\" = \
+                 378330\n\n[resolved_at]\n\"Bully Maguire\" = \"{resolved_at}\"\n"
+            ),
+        )
+        .unwrap();
+
+        dir
+    }
+
+    /// Nothing listens here, so any name that is not served from the cache
+    /// fails to resolve.
+    fn m_unreachable_client() -> BananaClient {
+        let settings = BananaClientSettings {
+            max_retries: 0,
+            ..Default::default()
+        };
+
+        BananaClient::with_root_and_settings("http://127.0.0.1:1", settings).unwrap()
+    }
+
+    #[test]
+    fn corrupted_cache_is_reported() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("mod_name_resolution_cache.toml"),
+            "[names\n\"Bully Maguire\" = ",
+        )
+        .unwrap();
+
+        let res = BananaModNameResolver::new(Utf8Path::from_path(dir.path()).unwrap(), None);
+
+        assert!(
+            matches!(res, Err(ModNameResolverError::CacheCorrupted(_))),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_names_are_kept_forever_without_a_ttl() {
+        let dir = m_cache_dir();
+        let mut resolver =
+            BananaModNameResolver::new(Utf8Path::from_path(dir.path()).unwrap(), None).unwrap();
+
+        let res = resolver
+            .resolve_mod_name_to_id(&m_unreachable_client(), "Bully Maguire")
+            .await;
+
+        assert!(matches!(res, Ok(378330)), "{:?}", res);
+    }
+
+    #[tokio::test]
+    async fn expired_names_are_resolved_again() {
+        let dir = m_cache_dir();
+        let mut resolver = BananaModNameResolver::new(
+            Utf8Path::from_path(dir.path()).unwrap(),
+            Some(Duration::hours(1)),
+        )
+        .unwrap();
+
+        let res = resolver
+            .resolve_mod_name_to_id(&m_unreachable_client(), "Bully Maguire")
+            .await;
+
+        assert!(
+            matches!(res, Err(ModNameResolverError::BananaScraperError(_))),
+            "{:?}",
+            res
+        );
     }
 }
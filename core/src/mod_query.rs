@@ -0,0 +1,214 @@
+//! Structured queries over the installed mods (eg. for `status --filter` and
+//! for frontends).
+
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use thiserror::Error;
+use ultimate_mod_man_rs_utils::types::ModId;
+
+#[derive(Debug, Error)]
+pub enum ModQueryFromStrErr {
+    #[error(
+        "Unknown filter \"{0}\" (expected one of \"name=\", \"char=\", \"enabled\", \"disabled\", \
+         \"conflicts\", \"overrides\", \"tag=\" or \"after=\")"
+    )]
+    UnknownFilter(String),
+
+    #[error("The filter \"{0}\" needs a value (eg. \"{0}=...\")")]
+    MissingValue(String),
+
+    #[error("The filter \"{0}\" does not take a value")]
+    UnexpectedValue(String),
+
+    #[error("\"enabled\" and \"disabled\" can not be used together")]
+    ConflictingStates,
+
+    #[error("Unable to parse \"{0}\" as a date (expected eg. \"2024-05-01\")")]
+    InvalidDate(String),
+}
+
+/// Whether a variant is enabled or not.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VariantStateFilter {
+    Enabled,
+    Disabled,
+}
+
+/// Every filter that is set must match (ie. they are combined with AND). The
+/// filters on variants must all match the same variant, and only the variants
+/// that match are included in the results. A query with no filters matches
+/// every installed mod.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModQuery {
+    /// Part of the mod name (case-insensitive).
+    pub name: Option<String>,
+
+    /// The internal name of a character (eg. `mario`) that the variant has a
+    /// skin for.
+    pub character: Option<String>,
+
+    pub state: Option<VariantStateFilter>,
+
+    /// Only variants that are part of a conflict.
+    pub has_conflicts: bool,
+
+    /// Only variants that have at least one slot override.
+    pub has_overrides: bool,
+
+    /// Tags that the mod must have (normalized).
+    pub tags: Vec<String>,
+
+    /// Only variants installed after this. Variants installed before the
+    /// install time was recorded never match.
+    pub installed_after: Option<DateTime<Utc>>,
+}
+
+impl ModQuery {
+    /// Whether any of the filters that apply to single variants are set.
+    pub(crate) fn filters_variants(&self) -> bool {
+        self.character.is_some()
+            || self.state.is_some()
+            || self.has_conflicts
+            || self.has_overrides
+            || self.installed_after.is_some()
+    }
+}
+
+/// Parses a comma separated list of filters (eg.
+/// `name=cloud,char=cloud,enabled,after=2024-05-01`).
+impl FromStr for ModQuery {
+    type Err = ModQueryFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Self::default();
+
+        for filter in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (key, val) = match filter.split_once('=') {
+                Some((key, val)) => (key.trim().to_lowercase(), Some(val.trim())),
+                None => (filter.to_lowercase(), None),
+            };
+
+            let need_val = || {
+                val.filter(|val| !val.is_empty())
+                    .ok_or_else(|| ModQueryFromStrErr::MissingValue(key.clone()))
+            };
+            let no_val = || match val {
+                Some(_) => Err(ModQueryFromStrErr::UnexpectedValue(key.clone())),
+                None => Ok(()),
+            };
+
+            match key.as_str() {
+                "name" => query.name = Some(need_val()?.to_string()),
+                "char" | "character" => query.character = Some(need_val()?.to_lowercase()),
+                "enabled" | "disabled" => {
+                    no_val()?;
+                    let state = match key.as_str() {
+                        "enabled" => VariantStateFilter::Enabled,
+                        _ => VariantStateFilter::Disabled,
+                    };
+
+                    if query.state.is_some_and(|existing| existing != state) {
+                        return Err(ModQueryFromStrErr::ConflictingStates);
+                    }
+                    query.state = Some(state);
+                },
+                "conflicts" => {
+                    no_val()?;
+                    query.has_conflicts = true;
+                },
+                "overrides" => {
+                    no_val()?;
+                    query.has_overrides = true;
+                },
+                "tag" => query.tags.push(need_val()?.to_string()),
+                "after" => query.installed_after = Some(parse_date(need_val()?)?),
+                _ => return Err(ModQueryFromStrErr::UnknownFilter(filter.to_string())),
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+/// Either a full timestamp or just a date (taken as the start of the day in
+/// UTC).
+fn parse_date(s: &str) -> Result<DateTime<Utc>, ModQueryFromStrErr> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| ModQueryFromStrErr::InvalidDate(s.to_string()))
+}
+
+/// An installed mod that matched a query.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ModSummary {
+    pub id: ModId,
+    pub name: String,
+    pub tags: Vec<String>,
+
+    /// The names of the variants that matched (ordered by name).
+    pub variants: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{ModQuery, ModQueryFromStrErr, VariantStateFilter};
+
+    #[test]
+    fn filters_are_parsed_and_combined() {
+        let filters = [
+            "name=Cloud",
+            " char=CLOUD",
+            "enabled",
+            "conflicts",
+            "overrides",
+            "tag=a",
+            "tag=b",
+            "after=2024-05-01",
+        ];
+        let query = filters.join(",").parse::<ModQuery>().unwrap();
+
+        assert_eq!(
+            query,
+            ModQuery {
+                name: Some("Cloud".to_string()),
+                character: Some("cloud".to_string()),
+                state: Some(VariantStateFilter::Enabled),
+                has_conflicts: true,
+                has_overrides: true,
+                tags: vec!["a".to_string(), "b".to_string()],
+                installed_after: Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()),
+            }
+        );
+        assert_eq!("".parse::<ModQuery>().unwrap(), ModQuery::default());
+    }
+
+    #[test]
+    fn bad_filters_are_rejected() {
+        for (filter, expected) in [
+            ("bogus", "UnknownFilter"),
+            ("name=", "MissingValue"),
+            ("enabled=yes", "UnexpectedValue"),
+            ("enabled,disabled", "ConflictingStates"),
+            ("after=yesterday", "InvalidDate"),
+        ] {
+            let err = filter.parse::<ModQuery>().unwrap_err();
+            let matched = match err {
+                ModQueryFromStrErr::UnknownFilter(_) => "UnknownFilter",
+                ModQueryFromStrErr::MissingValue(_) => "MissingValue",
+                ModQueryFromStrErr::UnexpectedValue(_) => "UnexpectedValue",
+                ModQueryFromStrErr::ConflictingStates => "ConflictingStates",
+                ModQueryFromStrErr::InvalidDate(_) => "InvalidDate",
+            };
+
+            assert_eq!(matched, expected, "{}", filter);
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Versioning of the documents that we persist in the state directory.
+//!
+//! Every document has a top-level `schema_version`. Documents written before
+//! versioning existed do not have one and are treated as version `1`. When a
+//! document is loaded, it is upgraded one version at a time (on the raw TOML,
+//! so that nothing that the current structs do not know about is lost) before
+//! it is deserialized. Documents written by a newer version than we understand
+//! are refused outright rather than risking losing their data.
+
+use std::fs;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::info;
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+use toml::{Table, Value};
+use ultimate_mod_man_rs_utils::utils::{DeserializationError, SerializationError};
+
+pub type SchemaResult<T> = Result<T, SchemaError>;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// The version of documents that do not have a `schema_version`.
+const UNVERSIONED_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error(
+        "\"{path}\" was written by a newer version of the mod manager (schema version {found}, \
+         but this version only understands up to {supported}). Update the mod manager to use this \
+         state directory."
+    )]
+    TooNew {
+        path: Utf8PathBuf,
+        found: u32,
+        supported: u32,
+    },
+
+    #[error("\"{0}\" has an invalid \"{SCHEMA_VERSION_KEY}\" (expected a positive integer)")]
+    InvalidVersion(Utf8PathBuf),
+
+    #[error(transparent)]
+    DeserializationError(#[from] DeserializationError),
+
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+}
+
+/// Upgrades a document by one version in place.
+pub(crate) type Migration = fn(&mut Table);
+
+/// The steps needed to bring a kind of document up to the current version.
+/// `migrations[i]` upgrades a document from version `i + 1` to `i + 2`, so
+/// adding a version is just a matter of appending a migration.
+#[derive(Debug)]
+pub(crate) struct DocumentSchema {
+    migrations: &'static [Migration],
+}
+
+impl DocumentSchema {
+    pub(crate) const fn new(migrations: &'static [Migration]) -> Self {
+        Self { migrations }
+    }
+
+    pub(crate) fn current_version(&self) -> u32 {
+        UNVERSIONED_SCHEMA_VERSION + self.migrations.len() as u32
+    }
+}
+
+/// For versions that only add `schema_version` itself (which is handled here).
+pub(crate) fn no_changes(_: &mut Table) {}
+
+/// Reads a document, upgrading it to the current version of `schema` if it is
+/// older. If `rewrite_upgraded` is set, an upgraded document is also written
+/// back to disk. Only do this while holding the lock on the state directory.
+pub(crate) fn deserialize_versioned_from_path<T: DeserializeOwned>(
+    p: &Utf8Path,
+    schema: &DocumentSchema,
+    rewrite_upgraded: bool,
+) -> SchemaResult<T> {
+    let mut doc: Table =
+        toml::from_str(&fs::read_to_string(p).map_err(DeserializationError::from)?)
+            .map_err(DeserializationError::from)?;
+
+    let version = match doc.remove(SCHEMA_VERSION_KEY) {
+        None => UNVERSIONED_SCHEMA_VERSION,
+        Some(Value::Integer(v)) if v >= UNVERSIONED_SCHEMA_VERSION as i64 => v
+            .try_into()
+            .map_err(|_| SchemaError::InvalidVersion(p.to_path_buf()))?,
+        Some(_) => return Err(SchemaError::InvalidVersion(p.to_path_buf())),
+    };
+
+    if version > schema.current_version() {
+        return Err(SchemaError::TooNew {
+            path: p.to_path_buf(),
+            found: version,
+            supported: schema.current_version(),
+        });
+    }
+
+    let migrations = &schema.migrations[(version - UNVERSIONED_SCHEMA_VERSION) as usize..];
+    for migration in migrations {
+        migration(&mut doc);
+    }
+
+    if !migrations.is_empty() && rewrite_upgraded {
+        info!(
+            "Upgrading \"{}\" from schema version {} to {}...",
+            p,
+            version,
+            schema.current_version()
+        );
+        write_versioned_table(p, doc.clone(), schema)?;
+    }
+
+    Ok(T::deserialize(Value::Table(doc)).map_err(DeserializationError::from)?)
+}
+
+/// Writes a document stamped with the current version of `schema`.
+pub(crate) fn serialize_versioned_to_path<T: Serialize>(
+    p: &Utf8Path,
+    v: &T,
+    schema: &DocumentSchema,
+) -> SchemaResult<()> {
+    fs::write(p, serialize_versioned(v, schema)?).map_err(SerializationError::from)?;
+    Ok(())
+}
+
+/// Serializes a document stamped with the current version of `schema`.
+pub(crate) fn serialize_versioned<T: Serialize>(
+    v: &T,
+    schema: &DocumentSchema,
+) -> SchemaResult<String> {
+    let doc = Table::try_from(v).map_err(SerializationError::from)?;
+    Ok(versioned_table_to_string(doc, schema)?)
+}
+
+fn write_versioned_table(p: &Utf8Path, doc: Table, schema: &DocumentSchema) -> SchemaResult<()> {
+    fs::write(p, versioned_table_to_string(doc, schema)?).map_err(SerializationError::from)?;
+    Ok(())
+}
+
+fn versioned_table_to_string(
+    doc: Table,
+    schema: &DocumentSchema,
+) -> Result<String, SerializationError> {
+    // Tables are written in key order, so write the version ourselves to keep
+    // it at the top of the file where it is easy to spot.
+    Ok(format!(
+        "{} = {}\n\n{}",
+        SCHEMA_VERSION_KEY,
+        schema.current_version(),
+        toml::to_string(&doc)?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time};
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use chrono::{Duration, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+    use toml::{Table, Value};
+    use ultimate_mod_man_rs_scraper::banana_scraper::BananaClient;
+    use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+    use super::{
+        DocumentSchema, SchemaError, deserialize_versioned_from_path, serialize_versioned_to_path,
+    };
+    use crate::{
+        cmds::sync::DestLayout,
+        config::Config,
+        in_prog_action::{Action, InProgAction},
+        mod_db::ModDb,
+        mod_name_resolver::BananaModNameResolver,
+        sync_targets::SyncTargets,
+    };
+
+    /// v2 renamed `old_name` to `name`, and v3 added `count`.
+    const TEST_SCHEMA: DocumentSchema = DocumentSchema::new(&[rename_old_name, add_count]);
+
+    fn rename_old_name(doc: &mut Table) {
+        if let Some(name) = doc.remove("old_name") {
+            doc.insert("name".to_string(), name);
+        }
+    }
+
+    fn add_count(doc: &mut Table) {
+        doc.insert("count".to_string(), Value::Integer(0));
+    }
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct TestDoc {
+        name: String,
+        count: u32,
+    }
+
+    fn write_doc(dir: &TempDir, contents: &str) -> Utf8PathBuf {
+        let path = Utf8Path::from_path(dir.path()).unwrap().join("doc.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn m_test_doc(name: &str, count: u32) -> TestDoc {
+        TestDoc {
+            name: name.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn unversioned_doc_goes_through_every_migration() {
+        let dir = TempDir::new().unwrap();
+        let path = write_doc(&dir, "old_name = \"a\"\n");
+
+        let doc: TestDoc = deserialize_versioned_from_path(&path, &TEST_SCHEMA, false).unwrap();
+
+        assert_eq!(doc, m_test_doc("a", 0));
+    }
+
+    #[test]
+    fn doc_only_goes_through_the_migrations_after_its_version() {
+        let dir = TempDir::new().unwrap();
+        let path = write_doc(&dir, "schema_version = 2\nname = \"a\"\nold_name = \"b\"\n");
+
+        let doc: TestDoc = deserialize_versioned_from_path(&path, &TEST_SCHEMA, false).unwrap();
+
+        assert_eq!(doc, m_test_doc("a", 0));
+    }
+
+    #[test]
+    fn upgraded_doc_is_only_rewritten_when_asked() {
+        let dir = TempDir::new().unwrap();
+        let path = write_doc(&dir, "old_name = \"a\"\nunknown = true\n");
+
+        deserialize_versioned_from_path::<TestDoc>(&path, &TEST_SCHEMA, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "old_name = \"a\"\nunknown = true\n"
+        );
+
+        deserialize_versioned_from_path::<TestDoc>(&path, &TEST_SCHEMA, true).unwrap();
+        let rewritten: Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(rewritten["schema_version"].as_integer(), Some(3));
+        assert_eq!(rewritten["name"].as_str(), Some("a"));
+        assert_eq!(rewritten["count"].as_integer(), Some(0));
+
+        // Keys that the structs do not know about survive the upgrade.
+        assert_eq!(rewritten["unknown"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn saved_doc_is_stamped_with_the_current_version() {
+        let dir = TempDir::new().unwrap();
+        let path = write_doc(&dir, "");
+
+        serialize_versioned_to_path(&path, &m_test_doc("a", 1), &TEST_SCHEMA).unwrap();
+
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("schema_version = 3\n")
+        );
+        assert_eq!(
+            deserialize_versioned_from_path::<TestDoc>(&path, &TEST_SCHEMA, false).unwrap(),
+            m_test_doc("a", 1)
+        );
+    }
+
+    #[test]
+    fn doc_from_a_newer_version_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let path = write_doc(&dir, "schema_version = 4\nname = \"a\"\ncount = 1\n");
+
+        match deserialize_versioned_from_path::<TestDoc>(&path, &TEST_SCHEMA, true) {
+            Err(
+                err @ SchemaError::TooNew {
+                    found: 4,
+                    supported: 3,
+                    ..
+                },
+            ) => assert!(err.to_string().contains("newer version")),
+            res => panic!("Expected the doc to be refused but got {:?}", res),
+        }
+
+        // Nothing is touched.
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("schema_version = 4")
+        );
+    }
+
+    #[test]
+    fn invalid_version_is_an_error() {
+        let dir = TempDir::new().unwrap();
+
+        for contents in ["schema_version = 0\n", "schema_version = \"2\"\n"] {
+            let path = write_doc(&dir, contents);
+
+            assert!(matches!(
+                deserialize_versioned_from_path::<TestDoc>(&path, &TEST_SCHEMA, false),
+                Err(SchemaError::InvalidVersion(_))
+            ));
+        }
+    }
+
+    /// Copies a committed fixture that was written before documents had a
+    /// schema version to `rel_path` inside of `dir`.
+    fn copy_v1_fixture(dir: &TempDir, name: &str, rel_path: &str) -> Utf8PathBuf {
+        let fixture_path = Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/schema_v1")
+            .join(name);
+        let path = Utf8Path::from_path(dir.path()).unwrap().join(rel_path);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::copy(fixture_path, &path).unwrap();
+
+        path
+    }
+
+    fn read_doc(p: &Utf8Path) -> Table {
+        toml::from_str(&fs::read_to_string(p).unwrap()).unwrap()
+    }
+
+    fn schema_version_on_disk(p: &Utf8Path) -> Option<i64> {
+        read_doc(p)
+            .get("schema_version")
+            .and_then(Value::as_integer)
+    }
+
+    #[tokio::test]
+    async fn v1_mod_info_is_upgraded_and_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let path = copy_v1_fixture(&dir, "mod_info.toml", "OldMario_1/mod_info.toml");
+        fs::create_dir(path.parent().unwrap().join("a.zip")).unwrap();
+
+        let db = ModDb::load_from_path(
+            path.parent().unwrap().parent().unwrap(),
+            time::Duration::ZERO,
+            true,
+            |_| (),
+        )
+        .await
+        .unwrap();
+        let mod_info = db.installed_mods().find(|m| m.id == 1).unwrap();
+        let variant = &mod_info.installed_variants["a.zip"];
+
+        assert_eq!(mod_info.name, "OldMario");
+        assert_eq!(mod_info.version.as_deref(), Some("1.0"));
+        assert!(mod_info.tags.is_empty());
+        assert!(mod_info.last_update_check.is_none());
+        assert!(variant.enabled);
+        assert!(variant.notes.is_none());
+        assert!(variant.previous_version.is_none());
+        assert_eq!(schema_version_on_disk(&path), Some(2));
+    }
+
+    #[test]
+    fn v1_in_prog_action_is_upgraded_and_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let path = copy_v1_fixture(&dir, "in_prog_action.toml", "in_prog_action.toml");
+
+        let action = InProgAction::load_from_disk_if_present(&path)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            *action,
+            Action::SetEnabled {
+                keys: vec![VariantAndId::new(1, "a.zip".to_string())],
+                enabled: false,
+            }
+        );
+        assert_eq!(schema_version_on_disk(&path), Some(2));
+    }
+
+    #[test]
+    fn v1_config_is_upgraded_without_being_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let path = copy_v1_fixture(&dir, "config.toml", "config.toml");
+
+        let config = Config::load_or_default(path.parent().unwrap()).unwrap();
+
+        assert!(config.auto_update());
+        assert_eq!(config.update_recheck_interval(), Duration::hours(12));
+        assert_eq!(config.lock_wait_timeout(), time::Duration::from_secs(5));
+        assert_eq!(schema_version_on_disk(&path), None);
+    }
+
+    #[test]
+    fn v1_sync_targets_are_upgraded_and_only_the_state_is_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let config_path = copy_v1_fixture(&dir, "targets.toml", "targets.toml");
+        let state_path = copy_v1_fixture(&dir, "targets_state.toml", "targets_state.toml");
+
+        let targets = SyncTargets::load_from_dir(config_path.parent().unwrap(), true).unwrap();
+        let (name, target) = targets.resolve(None).unwrap();
+        let state = targets.state("sd_card").unwrap();
+
+        assert_eq!(name, "sd_card");
+        assert_eq!(target.path, "/media/sd/ultimate/mods");
+        assert_eq!(target.ignore, ["*.bak"]);
+        assert_eq!(
+            state.last_sync,
+            Some(Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap())
+        );
+        assert_eq!(state.layout, DestLayout::Flat);
+        assert_eq!(state.synced_variants.len(), 1);
+        assert_eq!(
+            state.synced_variants[0].key,
+            VariantAndId::new(1, "a.zip".to_string())
+        );
+
+        // The file that the user edits is left alone.
+        assert_eq!(schema_version_on_disk(&config_path), None);
+        assert_eq!(schema_version_on_disk(&state_path), Some(2));
+        assert!(read_doc(&state_path)["targets"].get("sd_card").is_some());
+    }
+
+    #[tokio::test]
+    async fn v1_mod_name_resolution_cache_is_upgraded_and_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let path = copy_v1_fixture(
+            &dir,
+            "mod_name_resolution_cache.toml",
+            "mod_name_resolution_cache.toml",
+        );
+
+        let mut resolver = BananaModNameResolver::new(path.parent().unwrap(), None).unwrap();
+
+        // Both are cached, so nothing is sent to GameBanana.
+        let scraper = BananaClient::new().unwrap();
+        for (name, id) in [("Bully Maguire", 378330), ("OldMario", 1)] {
+            assert_eq!(
+                resolver
+                    .resolve_mod_name_to_id(&scraper, name)
+                    .await
+                    .unwrap(),
+                id
+            );
+        }
+
+        assert_eq!(schema_version_on_disk(&path), Some(2));
+        assert_eq!(
+            read_doc(&path)["names"]["Bully Maguire"].as_integer(),
+            Some(378330)
+        );
+    }
+}
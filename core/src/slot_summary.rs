@@ -0,0 +1,291 @@
+//! Compact, human readable summaries of the slots that a variant occupies
+//! (eg. `cloud: c00-c07; joker: c02*; ui`).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+use camino::Utf8Path;
+use ultimate_mod_man_rs_utils::types::AssetSlot;
+
+/// Summaries longer than this are cut short and end with a count of the parts
+/// that were left out instead.
+pub(crate) const MAX_SLOT_SUMMARY_LEN: usize = 60;
+
+const PART_SEP: &str = "; ";
+
+/// Content that is not tied to a skin slot. The order here is the order that
+/// the markers are shown in.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum NonSlotMarker {
+    Stage,
+    Ui,
+    Fx,
+    Bgm,
+    Other,
+}
+
+impl NonSlotMarker {
+    fn for_global_path(path: &Utf8Path) -> Self {
+        let path = path.as_str();
+        let starts_with_dir = |dir: &str| {
+            path.strip_prefix(dir)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+
+        if starts_with_dir("sound/bgm") || starts_with_dir("stream;/sound/bgm") {
+            Self::Bgm
+        } else if starts_with_dir("ui") {
+            Self::Ui
+        } else if starts_with_dir("effect") {
+            Self::Fx
+        } else if starts_with_dir("stage") {
+            Self::Stage
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl Display for NonSlotMarker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NonSlotMarker::Stage => "stage",
+            NonSlotMarker::Ui => "ui",
+            NonSlotMarker::Fx => "fx",
+            NonSlotMarker::Bgm => "bgm",
+            NonSlotMarker::Other => "other",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// The slots used by a variant, grouped by character and with any non slot
+/// content reduced to a marker.
+#[derive(Debug, Default)]
+pub(crate) struct SlotSummary {
+    /// For every character, each skin slot used and whether or not an override
+    /// moved an asset into it.
+    char_slots: BTreeMap<String, BTreeMap<u8, bool>>,
+    markers: BTreeSet<NonSlotMarker>,
+}
+
+impl SlotSummary {
+    /// Builds the summary from the slots a variant uses after its overrides
+    /// have been applied. `overridden` holds the slots that an override moved
+    /// an asset into.
+    pub(crate) fn new<'a>(
+        slots: impl IntoIterator<Item = &'a AssetSlot>,
+        overridden: &[AssetSlot],
+    ) -> Self {
+        let mut summary = Self::default();
+
+        for slot in slots {
+            match slot {
+                AssetSlot::CharacterSkin(char_slot) => {
+                    let was_overridden = overridden.contains(slot);
+                    let entry = summary
+                        .char_slots
+                        .entry(char_slot.char_key().to_string())
+                        .or_default()
+                        .entry(char_slot.skin_slot_idx().idx())
+                        .or_default();
+                    *entry |= was_overridden;
+                },
+                AssetSlot::StageSkin(_) => {
+                    summary.markers.insert(NonSlotMarker::Stage);
+                },
+                AssetSlot::Global(path) => {
+                    summary.markers.insert(NonSlotMarker::for_global_path(path));
+                },
+            }
+        }
+
+        summary
+    }
+
+    /// The summary, cut short if it is longer than `max_len`.
+    pub(crate) fn fmt_truncated(&self, max_len: usize) -> String {
+        let parts = self.parts();
+
+        let mut out = String::new();
+        for (i, part) in parts.iter().enumerate() {
+            let num_left_after = parts.len() - i - 1;
+            let sep_len = if out.is_empty() { 0 } else { PART_SEP.len() };
+
+            // Always leave room for the count of the parts that get left out,
+            // unless this is the last part.
+            let reserved = match num_left_after {
+                0 => 0,
+                n => PART_SEP.len() + fmt_num_more(n).len(),
+            };
+
+            if out.len() + sep_len + part.len() + reserved > max_len && !out.is_empty() {
+                out.push_str(PART_SEP);
+                out.push_str(&fmt_num_more(parts.len() - i));
+                return out;
+            }
+
+            if !out.is_empty() {
+                out.push_str(PART_SEP);
+            }
+            out.push_str(part);
+        }
+
+        out
+    }
+
+    fn parts(&self) -> Vec<String> {
+        self.char_slots
+            .iter()
+            .map(|(char_key, slots)| format!("{}: {}", char_key, fmt_slot_ranges(slots)))
+            .chain(self.markers.iter().map(|marker| marker.to_string()))
+            .collect()
+    }
+}
+
+impl Display for SlotSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.parts().join(PART_SEP))
+    }
+}
+
+fn fmt_num_more(n: usize) -> String {
+    format!("+{} more", n)
+}
+
+/// Collapses runs of contiguous slots into ranges (eg. `c00-c03,c05`).
+/// Overridden slots are always shown on their own so that they can be marked.
+fn fmt_slot_ranges(slots: &BTreeMap<u8, bool>) -> String {
+    let mut ranges: Vec<(u8, u8, bool)> = Vec::new();
+    for (&idx, &overridden) in slots.iter() {
+        match ranges.last_mut() {
+            Some((_, end, false)) if !overridden && end.checked_add(1) == Some(idx) => *end = idx,
+            _ => ranges.push((idx, idx, overridden)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end, overridden)| {
+            let marker = if overridden { "*" } else { "" };
+            match start == end {
+                false => format!("c{:02}-c{:02}", start, end),
+                true => format!("c{:02}{}", start, marker),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use ultimate_mod_man_rs_utils::types::{
+        AssetSlot, CharSkinSlotValue, SkinSlotValue, StageSlotValue,
+    };
+
+    use super::{MAX_SLOT_SUMMARY_LEN, SlotSummary};
+
+    fn m_skin(char_key: &str, idx: u8) -> AssetSlot {
+        AssetSlot::CharacterSkin(CharSkinSlotValue::new(
+            char_key.to_string(),
+            SkinSlotValue::new(idx),
+        ))
+    }
+
+    fn m_global(path: &str) -> AssetSlot {
+        AssetSlot::Global(path.into())
+    }
+
+    #[test]
+    fn contiguous_slots_are_collapsed_into_ranges() {
+        let slots = (0..8).map(|idx| m_skin("cloud", idx)).collect::<Vec<_>>();
+        assert_eq!(SlotSummary::new(&slots, &[]).to_string(), "cloud: c00-c07");
+
+        let slots = [0, 1, 2, 5, 7, 8]
+            .into_iter()
+            .map(|idx| m_skin("cloud", idx))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SlotSummary::new(&slots, &[]).to_string(),
+            "cloud: c00-c02,c05,c07-c08"
+        );
+    }
+
+    #[test]
+    fn characters_are_ordered_and_slots_are_not_repeated() {
+        let slots = [
+            m_skin("mario", 3),
+            m_skin("cloud", 1),
+            m_skin("mario", 3),
+            m_skin("cloud", 0),
+        ];
+
+        assert_eq!(
+            SlotSummary::new(&slots, &[]).to_string(),
+            "cloud: c00-c01; mario: c03"
+        );
+    }
+
+    #[test]
+    fn overridden_slots_are_marked() {
+        let slots = [m_skin("joker", 1), m_skin("joker", 2), m_skin("joker", 3)];
+
+        assert_eq!(
+            SlotSummary::new(&slots, &[m_skin("joker", 2)]).to_string(),
+            "joker: c01,c02*,c03"
+        );
+    }
+
+    #[test]
+    fn non_slot_content_is_shown_as_markers() {
+        let slots = [
+            m_global("ui/replace/chara/chara_0/chara_0_joker_02.bntx"),
+            m_skin("joker", 2),
+            m_global("effect/fighter/joker/ef_joker.eff"),
+            m_global("stream;/sound/bgm/bgm_crs_01_battlefield.nus3audio"),
+            m_global("ui/param/database/ui_chara_db.prc"),
+            AssetSlot::StageSkin(StageSlotValue::new(0)),
+            m_global("fighter/common/param/fighter_param.prc"),
+        ];
+
+        assert_eq!(
+            SlotSummary::new(&slots, &[]).to_string(),
+            "joker: c02; stage; ui; fx; bgm; other"
+        );
+    }
+
+    #[test]
+    fn long_summaries_are_truncated_with_a_count() {
+        let slots = [
+            "bayonetta",
+            "captain",
+            "cloud",
+            "daisy",
+            "joker",
+            "mario",
+            "pikachu",
+            "samus",
+        ]
+        .into_iter()
+        .map(|char_key| m_skin(char_key, 0))
+        .collect::<Vec<_>>();
+        let summary = SlotSummary::new(&slots, &[]);
+
+        let truncated = summary.fmt_truncated(MAX_SLOT_SUMMARY_LEN);
+        assert!(truncated.len() <= MAX_SLOT_SUMMARY_LEN, "{}", truncated);
+        assert_eq!(
+            truncated,
+            "bayonetta: c00; captain: c00; cloud: c00; +5 more"
+        );
+
+        // Short summaries are left alone.
+        let summary = SlotSummary::new(&slots[..2], &[]);
+        assert_eq!(
+            summary.fmt_truncated(MAX_SLOT_SUMMARY_LEN),
+            summary.to_string()
+        );
+    }
+}
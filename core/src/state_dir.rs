@@ -0,0 +1,157 @@
+//! Keeps the mod manager from treating a random directory (eg. `Downloads` or
+//! an ARCropolis mods folder) as its state directory. Every state directory
+//! gets a small marker file when it is first initialized, and a directory
+//! without one is only used if it is empty (or the user asks us to adopt it).
+
+use std::{fs, io};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ultimate_mod_man_rs_utils::utils::{
+    DeserializationError, SerializationError, deserialize_data_from_path, serialize_data_to_path,
+};
+
+use crate::{config, mod_db, mod_name_resolver, sync_targets};
+
+pub(crate) type StateDirResult<T> = Result<T, StateDirError>;
+
+pub(crate) const STATE_DIR_MARKER_FILE_NAME: &str = ".umm_state";
+
+/// The version of the layout of the files in the state directory. Bump this
+/// whenever the layout changes in a way that needs a migration.
+const STATE_DIR_LAYOUT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum StateDirError {
+    #[error(
+        "\"{0}\" does not look like a mod manager state directory (it is not empty and has no \
+         \"{STATE_DIR_MARKER_FILE_NAME}\" file). Pick an empty directory instead, or pass \
+         `--init-here` if you really want to keep the mod manager state in this directory."
+    )]
+    NotAStateDir(Utf8PathBuf),
+
+    #[error(
+        "\"{path}\" was set up by a newer version of the mod manager (layout version {found}, but \
+         this version only understands up to {supported}). Update the mod manager to use this \
+         state directory."
+    )]
+    LayoutTooNew {
+        path: Utf8PathBuf,
+        found: u32,
+        supported: u32,
+    },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    DeserializationError(#[from] DeserializationError),
+
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+}
+
+/// Written to the root of every state directory.
+#[derive(Debug, Deserialize, Serialize)]
+struct StateDirMarker {
+    layout_version: u32,
+    created_at: DateTime<Utc>,
+}
+
+/// What an existing directory without a marker looks like.
+enum UnmarkedDirKind {
+    Empty,
+
+    /// Only has things that the mod manager puts in its state directory.
+    /// State directories created before the marker existed look like this.
+    Legacy,
+
+    Foreign,
+}
+
+/// Makes sure that `p` is a state directory. A directory without a marker is
+/// only accepted if it is empty, was created by an older version of the mod
+/// manager or `init_here` is set, in which case the marker is written (unless
+/// `read_only` is set).
+pub(crate) fn ensure_state_dir(
+    p: &Utf8Path,
+    init_here: bool,
+    read_only: bool,
+) -> StateDirResult<()> {
+    let marker_path = p.join(STATE_DIR_MARKER_FILE_NAME);
+    if marker_path.exists() {
+        let marker: StateDirMarker = deserialize_data_from_path(&marker_path)?;
+        if marker.layout_version > STATE_DIR_LAYOUT_VERSION {
+            return Err(StateDirError::LayoutTooNew {
+                path: p.to_path_buf(),
+                found: marker.layout_version,
+                supported: STATE_DIR_LAYOUT_VERSION,
+            });
+        }
+
+        return Ok(());
+    }
+
+    match (classify_unmarked_dir(p)?, init_here) {
+        (UnmarkedDirKind::Foreign, false) => return Err(StateDirError::NotAStateDir(p.into())),
+        (UnmarkedDirKind::Foreign, true) => {
+            info!("Using \"{}\" as the state directory as requested.", p)
+        },
+        (UnmarkedDirKind::Legacy, _) => info!("Marking \"{}\" as a state directory...", p),
+        (UnmarkedDirKind::Empty, _) => (),
+    }
+
+    if read_only {
+        return Ok(());
+    }
+
+    let marker = StateDirMarker {
+        layout_version: STATE_DIR_LAYOUT_VERSION,
+        created_at: Utc::now(),
+    };
+    serialize_data_to_path(&marker_path, &marker)?;
+
+    Ok(())
+}
+
+fn classify_unmarked_dir(p: &Utf8Path) -> io::Result<UnmarkedDirKind> {
+    let mut is_empty = true;
+
+    for entry in p.read_dir_utf8()? {
+        let entry = entry?;
+        is_empty = false;
+
+        if !is_state_dir_entry(entry.path(), entry.file_type()?.is_dir()) {
+            return Ok(UnmarkedDirKind::Foreign);
+        }
+    }
+
+    Ok(match is_empty {
+        false => UnmarkedDirKind::Legacy,
+        true => UnmarkedDirKind::Empty,
+    })
+}
+
+/// Whether the mod manager could have put this in its state directory.
+fn is_state_dir_entry(path: &Utf8Path, is_dir: bool) -> bool {
+    let name = path.file_name().unwrap_or_default();
+
+    match is_dir {
+        false => [
+            config::CONFIG_FILE_NAME,
+            mod_db::DB_LOCKFILE_NAME,
+            mod_db::IN_PROG_ACTION_FILE_NAME,
+            mod_name_resolver::CACHED_MOD_NAME_RESOLUTION_STATE_NAME,
+            sync_targets::SYNC_TARGETS_CONFIG_FILE_NAME,
+            sync_targets::SYNC_TARGETS_STATE_FILE_NAME,
+        ]
+        .contains(&name),
+        true => {
+            name == mod_db::QUARANTINE_DIR_NAME
+                || fs::exists(path.join(mod_db::MOD_INFO_FILE_NAME)).unwrap_or(false)
+        },
+    }
+}
@@ -0,0 +1,117 @@
+//! Optional ANSI coloring of the output of commands.
+
+use std::{
+    env,
+    fmt::Display,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+
+#[derive(Debug, Error)]
+#[error("Unknown color mode \"{0}\" (expected one of \"auto\", \"always\" or \"never\")")]
+pub struct ColorModeFromStrErr(String);
+
+/// When output should be colored.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Only color when `stdout` is a terminal (and `NO_COLOR` is not set).
+    Auto,
+
+    Always,
+
+    /// Frontends that embed the manager get plain output unless they ask for
+    /// color.
+    #[default]
+    Never,
+}
+
+impl ColorMode {
+    pub fn style(self) -> Style {
+        let color = match self {
+            ColorMode::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        };
+
+        Style { color }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = ColorModeFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(ColorModeFromStrErr(s.to_string())),
+        }
+    }
+}
+
+/// Decides how text with a given meaning is rendered. When color is off, text
+/// is passed through untouched.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Style {
+    color: bool,
+}
+
+impl Style {
+    /// Mod and section names.
+    pub fn heading(&self, text: impl Display) -> String {
+        self.paint(BOLD, text)
+    }
+
+    pub fn enabled(&self, text: impl Display) -> String {
+        self.paint(GREEN, text)
+    }
+
+    pub fn disabled(&self, text: impl Display) -> String {
+        self.paint(RED, text)
+    }
+
+    /// Anything that needs the user's attention (eg. conflicts).
+    pub fn warning(&self, text: impl Display) -> String {
+        self.paint(YELLOW, text)
+    }
+
+    /// Colors `enabled` based on its value.
+    pub fn enabled_flag(&self, enabled: bool) -> String {
+        match enabled {
+            false => self.disabled(enabled),
+            true => self.enabled(enabled),
+        }
+    }
+
+    fn paint(&self, code: &str, text: impl Display) -> String {
+        match self.color {
+            false => text.to_string(),
+            true => format!("{}{}{}", code, text, RESET),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorMode;
+
+    #[test]
+    fn only_colored_styles_emit_escape_codes() {
+        assert_eq!(ColorMode::Never.style().warning("conflict"), "conflict");
+        assert_eq!(
+            ColorMode::Always.style().warning("conflict"),
+            "\x1b[33mconflict\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,678 @@
+//! Named locations that the enabled mods get synced to (eg. an SD card, an
+//! emulator install or a Switch running an FTP server).
+//!
+//! The targets themselves are defined by the user in `targets.toml` inside the
+//! state directory and look like this:
+//!
+//! ```toml
+//! default_target = "sd_card"
+//!
+//! [targets.sd_card]
+//! type = "local"
+//! path = "/media/sd/ultimate/mods"
+//! ignore = ["*.bak"]
+//!
+//! [targets.switch]
+//! type = "ftp"
+//! path = "/ultimate/mods"
+//! options = { address = "192.168.1.20:5000", max_concurrent_copies = "2" }
+//! ```
+//!
+//! Anything that changes as a result of syncing (eg. what was synced and when)
+//! is stored separately in `targets_state.toml` so that the file the user edits
+//! is never rewritten behind their back. For the same reason, a `targets.toml`
+//! from an older version is only upgraded in memory until a target is added or
+//! removed.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    mem,
+    str::FromStr,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use toml::{Table, Value};
+use ultimate_mod_man_rs_utils::{
+    types::VariantAndId,
+    utils::{DeserializationError, SerializationError},
+};
+
+use crate::{
+    cmds::sync::DestLayout,
+    schema::{
+        DocumentSchema, SchemaError, deserialize_versioned_from_path, no_changes,
+        serialize_versioned_to_path,
+    },
+};
+
+pub type SyncTargetResult<T> = Result<T, SyncTargetError>;
+
+pub(crate) const SYNC_TARGETS_CONFIG_FILE_NAME: &str = "targets.toml";
+pub(crate) const SYNC_TARGETS_STATE_FILE_NAME: &str = "targets_state.toml";
+
+/// - v2: Added `schema_version`.
+const SYNC_TARGETS_CONFIG_SCHEMA: DocumentSchema = DocumentSchema::new(&[no_changes]);
+
+/// - v2: Moved the state of each target under `targets`.
+const SYNC_TARGETS_STATE_SCHEMA: DocumentSchema =
+    DocumentSchema::new(&[move_target_states_into_table]);
+
+/// v1 was just the state of each target at the top level, which left no room
+/// for anything else (like the schema version).
+fn move_target_states_into_table(doc: &mut Table) {
+    let target_states = mem::take(doc);
+    doc.insert("targets".to_string(), Value::Table(target_states));
+}
+
+const FTP_ADDRESS_OPTION_KEY: &str = "address";
+const MAX_CONCURRENT_COPIES_OPTION_KEY: &str = "max_concurrent_copies";
+
+#[derive(Debug, Error)]
+pub enum SyncTargetError {
+    #[error("No sync target named \"{0}\" exists. Targets can be added with `target add`.")]
+    TargetNotFound(String),
+
+    #[error("A sync target named \"{0}\" already exists.")]
+    TargetAlreadyExists(String),
+
+    #[error(
+        "No sync target was specified and no default target is configured. Either pass `--target \
+         <name>` or set a default target with `target add --default`."
+    )]
+    NoDefaultTarget,
+
+    #[error(
+        "The default sync target \"{0}\" does not exist in \"{SYNC_TARGETS_CONFIG_FILE_NAME}\"."
+    )]
+    DefaultTargetDoesNotExist(String),
+
+    #[error(transparent)]
+    DeserializationError(#[from] DeserializationError),
+
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+
+    #[error(transparent)]
+    SchemaError(#[from] SchemaError),
+}
+
+/// Errors from validating the definition of a single target.
+#[derive(Debug, Error)]
+pub enum SyncTargetConfigError {
+    #[error(
+        "Unknown sync target type \"{0}\" (expected one of \"local\", \"ftp\" or \"emulator\")"
+    )]
+    UnknownTargetType(String),
+
+    #[error("Sync target is missing a \"path\"")]
+    MissingPath,
+
+    #[error(
+        "FTP sync targets require the \"{FTP_ADDRESS_OPTION_KEY}\" option (eg. \
+         \"{FTP_ADDRESS_OPTION_KEY}=192.168.1.20:5000\")"
+    )]
+    MissingFtpAddress,
+
+    #[error("The ignore pattern \"{0}\" is not a valid glob pattern")]
+    InvalidIgnorePattern(String, #[source] glob::PatternError),
+
+    #[error(
+        "The \"{MAX_CONCURRENT_COPIES_OPTION_KEY}\" option must be a number greater than 0 (got \
+         \"{0}\")"
+    )]
+    InvalidMaxConcurrentCopies(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncTargetType {
+    /// A directory on this machine (eg. a mounted SD card).
+    Local,
+
+    /// A Switch (or anything else) running an FTP server.
+    Ftp,
+
+    /// The SD card directory of an emulator install.
+    Emulator,
+}
+
+impl SyncTargetType {
+    /// Whether or not the target can be accessed directly through the local
+    /// filesystem.
+    pub fn is_filesystem(&self) -> bool {
+        matches!(self, SyncTargetType::Local | SyncTargetType::Emulator)
+    }
+
+    /// How many files are copied to the target at once unless the target
+    /// sets `max_concurrent_copies`. Syncing lots of small files is mostly
+    /// spent waiting on each file, but SD cards (and the Switch's FTP server)
+    /// slow down if too many are written at once.
+    fn default_max_concurrent_copies(&self) -> usize {
+        match self {
+            SyncTargetType::Local => 4,
+            SyncTargetType::Ftp => 2,
+            SyncTargetType::Emulator => 16,
+        }
+    }
+}
+
+impl FromStr for SyncTargetType {
+    type Err = SyncTargetConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "ftp" => Ok(Self::Ftp),
+            "emulator" => Ok(Self::Emulator),
+            _ => Err(SyncTargetConfigError::UnknownTargetType(s.to_string())),
+        }
+    }
+}
+
+impl Display for SyncTargetType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncTargetType::Local => write!(f, "local"),
+            SyncTargetType::Ftp => write!(f, "ftp"),
+            SyncTargetType::Emulator => write!(f, "emulator"),
+        }
+    }
+}
+
+/// A single location that mods can be synced to.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "RawSyncTarget", into = "RawSyncTarget")]
+pub struct SyncTarget {
+    pub target_type: SyncTargetType,
+
+    /// The mods directory on the target. For FTP targets, this is the path on
+    /// the remote server.
+    pub path: Utf8PathBuf,
+
+    /// Additional options that are specific to the type of target.
+    pub options: BTreeMap<String, String>,
+
+    /// Glob patterns (relative to each mod directory) of files that should
+    /// never be synced to this target.
+    pub ignore: Vec<String>,
+}
+
+impl SyncTarget {
+    pub fn new(
+        target_type: SyncTargetType,
+        path: Utf8PathBuf,
+        options: BTreeMap<String, String>,
+        ignore: Vec<String>,
+    ) -> Result<Self, SyncTargetConfigError> {
+        if target_type == SyncTargetType::Ftp && !options.contains_key(FTP_ADDRESS_OPTION_KEY) {
+            return Err(SyncTargetConfigError::MissingFtpAddress);
+        }
+
+        for pattern in ignore.iter() {
+            Pattern::new(pattern)
+                .map_err(|err| SyncTargetConfigError::InvalidIgnorePattern(pattern.clone(), err))?;
+        }
+
+        if let Some(max_concurrent_copies) = options.get(MAX_CONCURRENT_COPIES_OPTION_KEY)
+            && !matches!(max_concurrent_copies.parse::<usize>(), Ok(n) if n > 0)
+        {
+            return Err(SyncTargetConfigError::InvalidMaxConcurrentCopies(
+                max_concurrent_copies.clone(),
+            ));
+        }
+
+        Ok(Self {
+            target_type,
+            path,
+            options,
+            ignore,
+        })
+    }
+
+    pub(crate) fn ignore_patterns(&self) -> Vec<Pattern> {
+        // Patterns are validated on construction.
+        self.ignore
+            .iter()
+            .map(|pattern| Pattern::new(pattern).unwrap())
+            .collect()
+    }
+
+    /// How many files are copied to the target at once.
+    pub(crate) fn max_concurrent_copies(&self) -> usize {
+        // Also validated on construction.
+        self.options
+            .get(MAX_CONCURRENT_COPIES_OPTION_KEY)
+            .map(|n| n.parse().unwrap())
+            .unwrap_or_else(|| self.target_type.default_max_concurrent_copies())
+    }
+}
+
+impl Display for SyncTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.path, self.target_type)?;
+
+        if let Some(address) = self.options.get(FTP_ADDRESS_OPTION_KEY) {
+            write!(f, " @ {address}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The on-disk representation of a target before validation.
+#[derive(Debug, Deserialize, Serialize)]
+struct RawSyncTarget {
+    #[serde(rename = "type")]
+    target_type: String,
+
+    path: Option<Utf8PathBuf>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    options: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ignore: Vec<String>,
+}
+
+impl TryFrom<RawSyncTarget> for SyncTarget {
+    type Error = SyncTargetConfigError;
+
+    fn try_from(v: RawSyncTarget) -> Result<Self, Self::Error> {
+        let target_type = SyncTargetType::from_str(&v.target_type)?;
+        let path = v.path.ok_or(SyncTargetConfigError::MissingPath)?;
+
+        SyncTarget::new(target_type, path, v.options, v.ignore)
+    }
+}
+
+impl From<SyncTarget> for RawSyncTarget {
+    fn from(v: SyncTarget) -> Self {
+        Self {
+            target_type: v.target_type.to_string(),
+            path: Some(v.path),
+            options: v.options,
+            ignore: v.ignore,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+struct SyncTargetsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_target: Option<String>,
+
+    #[serde(default)]
+    targets: BTreeMap<String, SyncTarget>,
+}
+
+/// The contents of `targets_state.toml`.
+#[derive(Deserialize, Serialize)]
+struct SyncTargetsStateFile<T> {
+    targets: T,
+}
+
+/// Everything that we need to remember about a target between syncs.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct SyncTargetState {
+    pub(crate) last_sync: Option<DateTime<Utc>>,
+
+    /// The directory layout used during the last sync.
+    #[serde(default)]
+    pub(crate) layout: DestLayout,
+
+    /// The variants that were written to the target during the last sync.
+    #[serde(default)]
+    pub(crate) synced_variants: Vec<SyncedVariant>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct SyncedVariant {
+    pub(crate) key: VariantAndId,
+
+    /// The directory (relative to the target path) that the variant was
+    /// written to. Empty if the variant was written directly into the target
+    /// path.
+    pub(crate) root: Utf8PathBuf,
+
+    /// Every file (relative to the target path) that was written for the
+    /// variant.
+    #[serde(default)]
+    pub(crate) files: Vec<Utf8PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct SyncTargets {
+    config_path: Utf8PathBuf,
+    state_path: Utf8PathBuf,
+    config: SyncTargetsConfig,
+    state: BTreeMap<String, SyncTargetState>,
+}
+
+impl SyncTargets {
+    /// `targets_state.toml` from an older version is only rewritten if
+    /// `rewrite_upgraded` is set.
+    pub(crate) fn load_from_dir(p: &Utf8Path, rewrite_upgraded: bool) -> SyncTargetResult<Self> {
+        let config_path = p.join(SYNC_TARGETS_CONFIG_FILE_NAME);
+        let state_path = p.join(SYNC_TARGETS_STATE_FILE_NAME);
+
+        let config: SyncTargetsConfig = match config_path.exists() {
+            false => SyncTargetsConfig::default(),
+            true => {
+                deserialize_versioned_from_path(&config_path, &SYNC_TARGETS_CONFIG_SCHEMA, false)?
+            },
+        };
+
+        if let Some(default_target) = &config.default_target
+            && !config.targets.contains_key(default_target)
+        {
+            return Err(SyncTargetError::DefaultTargetDoesNotExist(
+                default_target.clone(),
+            ));
+        }
+
+        let state = match state_path.exists() {
+            false => BTreeMap::default(),
+            true => {
+                deserialize_versioned_from_path::<SyncTargetsStateFile<_>>(
+                    &state_path,
+                    &SYNC_TARGETS_STATE_SCHEMA,
+                    rewrite_upgraded,
+                )?
+                .targets
+            },
+        };
+
+        Ok(Self {
+            config_path,
+            state_path,
+            config,
+            state,
+        })
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        name: String,
+        target: SyncTarget,
+        make_default: bool,
+    ) -> SyncTargetResult<()> {
+        if self.config.targets.contains_key(&name) {
+            return Err(SyncTargetError::TargetAlreadyExists(name));
+        }
+
+        // The first target added is always the default since it's the only one that
+        // can be used.
+        if make_default || self.config.targets.is_empty() {
+            self.config.default_target = Some(name.clone());
+        }
+
+        self.config.targets.insert(name, target);
+        self.sync_config_to_disk()
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> SyncTargetResult<SyncTarget> {
+        let target = self
+            .config
+            .targets
+            .remove(name)
+            .ok_or_else(|| SyncTargetError::TargetNotFound(name.to_string()))?;
+
+        if self.config.default_target.as_deref() == Some(name) {
+            self.config.default_target = None;
+        }
+
+        self.sync_config_to_disk()?;
+
+        if self.state.remove(name).is_some() {
+            self.sync_state_to_disk()?;
+        }
+
+        Ok(target)
+    }
+
+    /// Gets the target with the given name or the default target if no name is
+    /// given.
+    pub(crate) fn resolve<'a>(
+        &'a self,
+        name: Option<&'a str>,
+    ) -> SyncTargetResult<(&'a str, &'a SyncTarget)> {
+        let name = name
+            .or(self.config.default_target.as_deref())
+            .ok_or(SyncTargetError::NoDefaultTarget)?;
+
+        let target = self
+            .config
+            .targets
+            .get(name)
+            .ok_or_else(|| SyncTargetError::TargetNotFound(name.to_string()))?;
+
+        Ok((name, target))
+    }
+
+    pub(crate) fn state(&self, name: &str) -> Option<&SyncTargetState> {
+        self.state.get(name)
+    }
+
+    /// Replaces the state of a target and writes all target state to disk.
+    pub(crate) fn update_state(
+        &mut self,
+        name: &str,
+        state: SyncTargetState,
+    ) -> SyncTargetResult<()> {
+        self.state.insert(name.to_string(), state);
+        self.sync_state_to_disk()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.config.targets.is_empty()
+    }
+
+    fn sync_config_to_disk(&self) -> SyncTargetResult<()> {
+        serialize_versioned_to_path(&self.config_path, &self.config, &SYNC_TARGETS_CONFIG_SCHEMA)?;
+        Ok(())
+    }
+
+    fn sync_state_to_disk(&self) -> SyncTargetResult<()> {
+        serialize_versioned_to_path(
+            &self.state_path,
+            &SyncTargetsStateFile {
+                targets: &self.state,
+            },
+            &SYNC_TARGETS_STATE_SCHEMA,
+        )?;
+        Ok(())
+    }
+}
+
+impl Display for SyncTargets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No sync targets configured.");
+        }
+
+        writeln!(f, "Sync targets:")?;
+        for (name, target) in self.config.targets.iter() {
+            let default_str = match self.config.default_target.as_deref() == Some(name) {
+                false => "",
+                true => " [default]",
+            };
+
+            let last_sync_str = match self.state(name).and_then(|state| state.last_sync) {
+                Some(last_sync) => last_sync.to_rfc2822(),
+                None => "never".to_string(),
+            };
+
+            writeln!(
+                f,
+                "  {name}{default_str}: {target} (last synced: {last_sync_str})"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{SyncTarget, SyncTargetConfigError, SyncTargetType, SyncTargetsConfig};
+
+    fn m_sync_target(target_type: SyncTargetType, path: &str) -> SyncTarget {
+        let mut options = BTreeMap::new();
+        if target_type == SyncTargetType::Ftp {
+            options.insert("address".to_string(), "192.168.1.20:5000".to_string());
+        }
+
+        SyncTarget::new(target_type, path.into(), options, vec!["*.bak".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn sync_targets_config_round_trips() {
+        let mut targets = BTreeMap::new();
+        targets.insert(
+            "sd_card".to_string(),
+            m_sync_target(SyncTargetType::Local, "/media/sd/ultimate/mods"),
+        );
+        targets.insert(
+            "switch".to_string(),
+            m_sync_target(SyncTargetType::Ftp, "/ultimate/mods"),
+        );
+        targets.insert(
+            "ryujinx".to_string(),
+            m_sync_target(SyncTargetType::Emulator, "/ryujinx/sdcard/ultimate/mods"),
+        );
+
+        let config = SyncTargetsConfig {
+            default_target: Some("sd_card".to_string()),
+            targets,
+        };
+
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: SyncTargetsConfig = toml::from_str(&serialized).unwrap();
+
+        assert_eq!(config, deserialized);
+    }
+
+    #[test]
+    fn sync_target_parses_hand_written_config() {
+        let config: SyncTargetsConfig = toml::from_str(
+            r#"
+            default_target = "switch"
+
+            [targets.switch]
+            type = "FTP"
+            path = "/ultimate/mods"
+            options = { address = "192.168.1.20:5000" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.targets["switch"],
+            SyncTarget::new(
+                SyncTargetType::Ftp,
+                "/ultimate/mods".into(),
+                BTreeMap::from([("address".to_string(), "192.168.1.20:5000".to_string())]),
+                Vec::new(),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn sync_target_errors_on_unknown_type() {
+        let err = toml::from_str::<SyncTargetsConfig>(
+            r#"
+            [targets.sd_card]
+            type = "floppy"
+            path = "/media/sd"
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(
+            err.message()
+                .contains("Unknown sync target type \"floppy\"")
+        );
+    }
+
+    #[test]
+    fn sync_target_errors_on_missing_path() {
+        let err = toml::from_str::<SyncTargetsConfig>(
+            r#"
+            [targets.sd_card]
+            type = "local"
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.message().contains("missing a \"path\""));
+    }
+
+    #[test]
+    fn sync_target_errors_on_ftp_without_address() {
+        assert!(matches!(
+            SyncTarget::new(
+                SyncTargetType::Ftp,
+                "/ultimate/mods".into(),
+                BTreeMap::new(),
+                Vec::new()
+            ),
+            Err(SyncTargetConfigError::MissingFtpAddress)
+        ));
+    }
+
+    #[test]
+    fn max_concurrent_copies_defaults_by_target_type() {
+        assert_eq!(
+            m_sync_target(SyncTargetType::Local, "/media/sd").max_concurrent_copies(),
+            4
+        );
+        assert_eq!(
+            m_sync_target(SyncTargetType::Ftp, "/ultimate/mods").max_concurrent_copies(),
+            2
+        );
+
+        let target = SyncTarget::new(
+            SyncTargetType::Emulator,
+            "/ryujinx/sdcard/ultimate/mods".into(),
+            BTreeMap::from([("max_concurrent_copies".to_string(), "32".to_string())]),
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(target.max_concurrent_copies(), 32);
+    }
+
+    #[test]
+    fn sync_target_errors_on_invalid_max_concurrent_copies() {
+        for value in ["0", "lots"] {
+            assert!(matches!(
+                SyncTarget::new(
+                    SyncTargetType::Local,
+                    "/media/sd".into(),
+                    BTreeMap::from([("max_concurrent_copies".to_string(), value.to_string())]),
+                    Vec::new()
+                ),
+                Err(SyncTargetConfigError::InvalidMaxConcurrentCopies(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn sync_target_errors_on_invalid_ignore_pattern() {
+        assert!(matches!(
+            SyncTarget::new(
+                SyncTargetType::Local,
+                "/media/sd".into(),
+                BTreeMap::new(),
+                vec!["[".to_string()]
+            ),
+            Err(SyncTargetConfigError::InvalidIgnorePattern(..))
+        ));
+    }
+}
@@ -0,0 +1,269 @@
+mod common;
+
+use std::{
+    fs,
+    io::{Cursor, Write},
+    time::Duration,
+};
+
+use common::{FixtureMod, FixtureStateDir, m_mod};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use ultimate_mod_man_rs_core::{
+    mod_db::ModDbError,
+    mod_manager::{AddConflictPolicy, ModManagerErr, SkipReason},
+};
+use ultimate_mod_man_rs_scraper::{
+    banana_scraper::{BananaScraperError, ChecksumPolicy, VariantMatchPolicy},
+    download_artifact_parser::VariantParseError,
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+fn m_mod_a() -> FixtureMod {
+    m_mod(1, "ModA", "a.zip", true)
+}
+
+/// Starts a server that accepts connections but never responds to anything.
+/// Returns the root URL of the server.
+async fn start_unresponsive_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut open_conns = Vec::new();
+        while let Ok((conn, _)) = listener.accept().await {
+            open_conns.push(conn);
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn slow_add_times_out_and_is_cleaned_up() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let root = start_unresponsive_server().await;
+
+    let outcome = state_dir
+        .open_manager_with_root(&root)
+        .await
+        .add_mods(
+            ["1/b.zip".parse().unwrap(), "1/a.zip".parse().unwrap()],
+            Some(Duration::from_millis(200)),
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+            None,
+        )
+        .await
+        .unwrap();
+
+    // The mod after the one that timed out should still be attempted.
+    assert_eq!(
+        outcome.skipped,
+        [
+            ("1/b.zip".parse().unwrap(), SkipReason::TimedOut),
+            ("1/a.zip".parse().unwrap(), SkipReason::AlreadyInstalled)
+        ]
+    );
+
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+    assert!(
+        !state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("b.zip")
+            .exists()
+    );
+    assert!(
+        state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("a.zip")
+            .exists()
+    );
+}
+
+/// Serves a mod page for `b.zip` that lists `md5` as its checksum, along with
+/// `payload` as its download. Returns the root URL of the server.
+async fn start_server(payload: Vec<u8>, md5: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let root = format!("http://{}", listener.local_addr().unwrap());
+
+    let mod_page = format!(
+        r#"{{
+            "_sName": "ModA",
+            "_sVersion": "",
+            "_aFiles": [{{
+                "_sFile": "b.zip",
+                "_tsDateAdded": 0,
+                "_sDownloadUrl": "{root}/dl",
+                "_sMd5Checksum": "{md5}"
+            }}]
+        }}"#
+    );
+
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = listener.accept().await {
+            let mut req = vec![0; 4096];
+            let n = conn.read(&mut req).await.unwrap();
+            let req = String::from_utf8_lossy(&req[..n]);
+
+            let body = match req.starts_with("GET /dl ") {
+                false => mod_page.as_bytes(),
+                true => &payload,
+            };
+
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            conn.write_all(header.as_bytes()).await.unwrap();
+            conn.write_all(body).await.unwrap();
+        }
+    });
+
+    root
+}
+
+fn m_zip(entry_paths: &[&str]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry_path in entry_paths {
+        writer
+            .start_file(*entry_path, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"model").unwrap();
+    }
+
+    writer.finish().unwrap().into_inner()
+}
+
+/// Adds `b.zip` and returns the error that adding it failed with.
+async fn add_b_err(state_dir: &FixtureStateDir, payload: Vec<u8>, md5: String) -> ModManagerErr {
+    let root = start_server(payload, md5).await;
+
+    let mut outcome = state_dir
+        .open_manager_with_root(&root)
+        .await
+        .add_mods(
+            ["1/b.zip".parse().unwrap()],
+            None,
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+            None,
+        )
+        .await
+        .unwrap();
+
+    match outcome.failed.len() {
+        1 => outcome.failed.remove(0).1,
+        _ => panic!("Expected only b.zip to fail but got {:?}", outcome),
+    }
+}
+
+fn assert_nothing_left_behind(state_dir: &FixtureStateDir, mod_a: &FixtureMod) {
+    assert!(
+        !state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("b.zip")
+            .exists()
+    );
+    assert!(!state_dir.path().join("quarantine/1_b.zip").exists());
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+}
+
+#[tokio::test]
+async fn download_with_mismatched_checksum_leaves_nothing_behind() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let payload = m_zip(&["fighter/mario/model.numdlb"]);
+
+    let err = add_b_err(&state_dir, payload, "0".repeat(32)).await;
+
+    assert!(matches!(
+        err,
+        ModManagerErr::BananaScraperError(BananaScraperError::VariantMd5CheckSumMismatch(..))
+    ));
+    assert_nothing_left_behind(&state_dir, &mod_a);
+}
+
+#[tokio::test]
+async fn archive_with_escaping_entries_is_rejected_before_reaching_the_mod_dir() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let payload = m_zip(&["fighter/mario/model.numdlb", "../../outside"]);
+    let md5 = format!("{:x}", md5::compute(&payload));
+
+    let err = add_b_err(&state_dir, payload, md5).await;
+
+    assert!(matches!(
+        err,
+        ModManagerErr::ModDbError(ModDbError::VariantParseError(
+            VariantParseError::UnsafeArchivePath(..)
+        ))
+    ));
+    assert_nothing_left_behind(&state_dir, &mod_a);
+}
+
+#[tokio::test]
+async fn exact_add_fails_if_only_a_fuzzy_match_exists() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let root = start_server(m_zip(&["fighter/mario/model.numdlb"]), "0".repeat(32)).await;
+
+    let mut outcome = state_dir
+        .open_manager_with_root(&root)
+        .await
+        .add_mods(
+            ["1/B.zip".parse().unwrap()],
+            None,
+            VariantMatchPolicy::Exact,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.failed.len(), 1, "{:?}", outcome);
+    let err = outcome.failed.remove(0).1;
+    assert!(
+        matches!(
+            &err,
+            ModManagerErr::BananaScraperError(BananaScraperError::NoExactVariantMatch {
+                variant_name,
+                available,
+                ..
+            }) if variant_name == "B.zip" && available == &["b.zip"]
+        ),
+        "{:?}",
+        err
+    );
+    assert!(err.to_string().contains("Available files: b.zip"));
+    assert_nothing_left_behind(&state_dir, &mod_a);
+}
+
+#[tokio::test]
+async fn leftover_downloads_in_quarantine_are_cleared_by_any_command() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    // As if a run was interrupted while it was still downloading (so before
+    // anything got journaled).
+    let quarantine_dir = state_dir.path().join("quarantine/1_b.zip");
+    fs::create_dir_all(&quarantine_dir).unwrap();
+    fs::write(quarantine_dir.join("b.zip"), b"partial download").unwrap();
+
+    state_dir
+        .open_manager()
+        .await
+        .enable_disable(["1/a.zip".parse().unwrap()], false)
+        .await
+        .unwrap();
+
+    assert!(!state_dir.path().join("quarantine").exists());
+}
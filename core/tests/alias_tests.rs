@@ -0,0 +1,109 @@
+mod common;
+
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, m_ident, m_status_info};
+use ultimate_mod_man_rs_core::{
+    cmds::status::StatusCmdKind,
+    mod_db::ModDbError,
+    mod_manager::{ModManagerErr, VariantToggleOutcome},
+};
+use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+fn m_mod() -> FixtureMod {
+    FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![
+            FixtureVariant::new("tobeymaguire_85f71.zip", true),
+            FixtureVariant::new("b.zip", false),
+        ],
+        last_update_check: None,
+    }
+}
+
+#[tokio::test]
+async fn aliases_can_be_used_in_place_of_the_file_name() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    state_dir
+        .open_manager()
+        .await
+        .set_alias(m_ident("1/tobeymaguire_85f71.zip"), Some(" Tobey "))
+        .await
+        .unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    let summary = mm
+        .enable_disable([m_ident("1:Tobey")], false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            VariantAndId::new(1, "tobeymaguire_85f71.zip".to_string()),
+            VariantToggleOutcome::Disabled
+        )]
+    );
+
+    let specific = mm
+        .status(m_status_info(StatusCmdKind::Specific(vec![m_ident(
+            "1/Tobey",
+        )])))
+        .unwrap()
+        .to_string();
+    assert!(specific.contains("Tobey (tobeymaguire_85f71.zip) (false)"));
+
+    mm.set_alias(m_ident("1/Tobey"), None).await.unwrap();
+    assert!(
+        mm.list_variants_for_mod(1)
+            .all(|var_info| var_info.alias().is_none())
+    );
+}
+
+#[tokio::test]
+async fn aliases_must_be_unique_within_the_mod() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let mut mm = state_dir.open_manager().await;
+    mm.set_alias(m_ident("1/b.zip"), Some("Tobey"))
+        .await
+        .unwrap();
+
+    for alias in ["Tobey", "b.zip"] {
+        let res = mm
+            .set_alias(m_ident("1/tobeymaguire_85f71.zip"), Some(alias))
+            .await;
+
+        assert!(
+            matches!(
+                &res,
+                Err(ModManagerErr::ModDbError(ModDbError::AliasTaken(_, key)))
+                    if key.variant_name == "b.zip"
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    // Giving a variant the alias it already has is fine.
+    mm.set_alias(m_ident("1/b.zip"), Some("Tobey"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn invalid_aliases_are_rejected() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let mut mm = state_dir.open_manager().await;
+
+    for alias in ["  ", "a/b", "a:b"] {
+        let res = mm.set_alias(m_ident("1/b.zip"), Some(alias)).await;
+
+        assert!(
+            matches!(
+                res,
+                Err(ModManagerErr::ModDbError(ModDbError::InvalidAlias(_)))
+            ),
+            "{:?}",
+            res
+        );
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use std::fs;
+
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, m_ident};
+use ultimate_mod_man_rs_utils::types::{CharSkinSlotValue, SkinSlotValue, SwappableAssetSlot};
+
+/// A mod with a single variant that uses Mario's `c02`.
+fn m_mod() -> FixtureMod {
+    FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![FixtureVariant {
+            skin_slots: &[("mario", 2)],
+            ..FixtureVariant::new("a.zip", true)
+        }],
+        last_update_check: None,
+    }
+}
+
+fn m_mario_slot(idx: u8) -> SwappableAssetSlot {
+    SwappableAssetSlot::CharacterSkin(CharSkinSlotValue::new(
+        "mario".to_string(),
+        SkinSlotValue::new(idx),
+    ))
+}
+
+#[tokio::test]
+async fn changing_a_slot_moves_the_variant_to_the_picked_slot() {
+    let mods = [m_mod()];
+    let state_dir = FixtureStateDir::new(&mods);
+
+    state_dir
+        .open_manager()
+        .await
+        .change_slot(m_ident("1/a.zip"), m_mario_slot(2))
+        .await
+        .unwrap();
+
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+
+    // The first open slot is picked, which is `c00`.
+    let mod_info_str = fs::read_to_string(state_dir.mod_info_path(&mods[0])).unwrap();
+    let mod_info_val: toml::Value = toml::from_str(&mod_info_str).unwrap();
+    let overrides = mod_info_val["installed_variants"]["a.zip"]["overrides"]
+        .as_array()
+        .unwrap();
+
+    assert_eq!(overrides.len(), 1);
+    assert_eq!(overrides[0]["CharacterSkin"]["old"].as_integer(), Some(2));
+    assert_eq!(overrides[0]["CharacterSkin"]["new"].as_integer(), Some(0));
+}
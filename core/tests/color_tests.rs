@@ -0,0 +1,71 @@
+mod common;
+
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, m_ident, m_status_info};
+use ultimate_mod_man_rs_core::{
+    cmds::status::StatusCmdKind,
+    mod_manager::{ModManagerOptions, ModManagerView},
+    style::ColorMode,
+};
+
+fn m_mods() -> Vec<FixtureMod> {
+    vec![FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![
+            FixtureVariant::new("a.zip", true),
+            FixtureVariant::new("b.zip", false),
+        ],
+        last_update_check: None,
+    }]
+}
+
+fn status_output(state_dir: &FixtureStateDir, color: ColorMode, kind: StatusCmdKind) -> String {
+    let options = ModManagerOptions {
+        color,
+        ..Default::default()
+    };
+
+    ModManagerView::load(state_dir.path(), options)
+        .unwrap()
+        .status(m_status_info(kind))
+        .unwrap()
+        .to_string()
+}
+
+fn specific_kind() -> StatusCmdKind {
+    StatusCmdKind::Specific(vec![m_ident("1/a.zip")])
+}
+
+#[test]
+fn never_produces_output_without_escape_codes() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+
+    for kind in [StatusCmdKind::Generic, specific_kind()] {
+        let output = status_output(&state_dir, ColorMode::Never, kind);
+        assert!(!output.contains('\x1b'), "{:?}", output);
+    }
+}
+
+#[test]
+fn always_produces_output_with_escape_codes() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+
+    let generic = status_output(&state_dir, ColorMode::Always, StatusCmdKind::Generic);
+    assert!(
+        generic.contains("a.zip (\x1b[32mtrue\x1b[0m)"),
+        "{:?}",
+        generic
+    );
+    assert!(
+        generic.contains("b.zip (\x1b[31mfalse\x1b[0m)"),
+        "{:?}",
+        generic
+    );
+
+    let specific = status_output(&state_dir, ColorMode::Always, specific_kind());
+    assert!(
+        specific.contains("\x1b[1mModA\x1b[0m (1)"),
+        "{:?}",
+        specific
+    );
+}
@@ -0,0 +1,106 @@
+//! Tiny mod archives that are generated at test time. The files in them are
+//! laid out like real mods (a directory named after the mod with the game
+//! directories inside of it) so that they go through the same classification
+//! as a real download.
+
+use std::io::{Cursor, Write};
+
+use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// RAR is not included since there is no way to create one without the
+/// proprietary `rar` tool.
+#[derive(Clone, Copy, Debug)]
+pub enum ArchiveKind {
+    Zip,
+    SevenZip,
+    Tar,
+}
+
+/// The files that make up a mod. Paths are relative to the root of the
+/// archive.
+#[derive(Clone, Debug, Default)]
+pub struct FixtureArchive {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl FixtureArchive {
+    /// A Mario skin for the given skin slot (eg. `3` for `c03`).
+    pub fn mario_skin(mod_name: &str, slot: u8) -> Self {
+        Self::default()
+            .with_file(
+                format!("{mod_name}/fighter/mario/model/body/c0{slot}/model.numdlb"),
+                b"model",
+            )
+            .with_file(
+                format!("{mod_name}/fighter/mario/model/body/c0{slot}/def_mario_001_col.nutexb"),
+                b"texture",
+            )
+            .with_file(format!("{mod_name}/preview.png"), b"preview")
+    }
+
+    /// A Battlefield stage mod. Stage files are not tied to a slot, so every
+    /// stage mod for the same stage conflicts with each other.
+    pub fn battlefield_stage(mod_name: &str) -> Self {
+        Self::default()
+            .with_file(
+                format!("{mod_name}/stage/battlefield/normal/model/stc_main/model.numdlb"),
+                b"stage",
+            )
+            .with_file(format!("{mod_name}/README.txt"), b"readme")
+    }
+
+    pub fn with_file(mut self, path: impl Into<String>, contents: &[u8]) -> Self {
+        self.files.push((path.into(), contents.to_vec()));
+        self
+    }
+
+    pub fn build(&self, kind: ArchiveKind) -> Vec<u8> {
+        match kind {
+            ArchiveKind::Zip => self.build_zip(),
+            ArchiveKind::SevenZip => self.build_7z(),
+            ArchiveKind::Tar => self.build_tar(),
+        }
+    }
+
+    fn build_zip(&self) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in self.files.iter() {
+            writer
+                .start_file(path.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn build_7z(&self) -> Vec<u8> {
+        let mut writer = SevenZWriter::new(Cursor::new(Vec::new())).unwrap();
+        for (path, contents) in self.files.iter() {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = path.clone();
+            entry.has_stream = true;
+            writer
+                .push_archive_entry(entry, Some(contents.as_slice()))
+                .unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn build_tar(&self) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in self.files.iter() {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_slice())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+}
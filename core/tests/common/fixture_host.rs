@@ -0,0 +1,215 @@
+//! A fake mod host that serves profile pages and downloads for fixture mods
+//! from a local port.
+
+use std::{
+    fmt::Write,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use ultimate_mod_man_rs_utils::types::ModId;
+
+/// A mod that the host serves. Every file is a downloadable variant.
+pub struct HostedMod {
+    pub id: ModId,
+    pub name: &'static str,
+    pub files: Vec<HostedFile>,
+}
+
+pub struct HostedFile {
+    pub name: &'static str,
+    pub payload: Vec<u8>,
+}
+
+/// Serves `/apiv11/Mod/{id}/ProfilePage` for each hosted mod along with the
+/// downloads that the pages link to. Searches only ever find hosted mods whose
+/// name is an exact match, and every hosted mod is a top mod. Anything else is
+/// a `404`.
+pub struct FixtureHost {
+    root: String,
+    downloads: Arc<DownloadCounter>,
+}
+
+/// Tracks how many downloads are being served at once.
+#[derive(Default)]
+struct DownloadCounter {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl FixtureHost {
+    pub async fn start(mods: Vec<HostedMod>) -> Self {
+        Self::start_with_download_delay(mods, Duration::ZERO).await
+    }
+
+    /// Like `start`, but every download takes at least `delay` to be served.
+    /// Useful for getting downloads to overlap.
+    pub async fn start_with_download_delay(mods: Vec<HostedMod>, delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let root = format!("http://{}", listener.local_addr().unwrap());
+        let mods = Arc::new(mods);
+        let downloads = Arc::new(DownloadCounter::default());
+
+        let server_root = Arc::new(root.clone());
+        let server_downloads = downloads.clone();
+        tokio::spawn(async move {
+            while let Ok((conn, _)) = listener.accept().await {
+                tokio::spawn(serve(
+                    conn,
+                    mods.clone(),
+                    server_root.clone(),
+                    server_downloads.clone(),
+                    delay,
+                ));
+            }
+        });
+
+        Self { root, downloads }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// The most downloads that were ever being served at the same time.
+    pub fn max_concurrent_downloads(&self) -> usize {
+        self.downloads.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+async fn serve(
+    mut conn: TcpStream,
+    mods: Arc<Vec<HostedMod>>,
+    root: Arc<String>,
+    downloads: Arc<DownloadCounter>,
+    delay: Duration,
+) {
+    let mut req = vec![0; 4096];
+    let n = conn.read(&mut req).await.unwrap();
+    let req = String::from_utf8_lossy(&req[..n]);
+    let path = req.split_whitespace().nth(1).unwrap_or_default();
+
+    let is_download = path.starts_with("/dl/");
+    if is_download {
+        let in_flight = downloads.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        downloads
+            .max_in_flight
+            .fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(delay).await;
+    }
+
+    let (status, content_type, body) = match route(&mods, &root, path) {
+        Some((content_type, body)) => ("200 OK", content_type, body),
+        None => ("404 Not Found", "text/plain", b"Not found".to_vec()),
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let _ = conn.write_all(header.as_bytes()).await;
+    let _ = conn.write_all(&body).await;
+
+    if is_download {
+        downloads.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn route(mods: &[HostedMod], root: &str, path: &str) -> Option<(&'static str, Vec<u8>)> {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+
+    match segments.as_slice() {
+        ["apiv11", "Util", "Search", "Results"] => {
+            Some(("application/json", search_results(mods, query).into_bytes()))
+        },
+        ["apiv11", "Game", _, "TopSubs"] => Some(("application/json", top_subs(mods).into_bytes())),
+        ["apiv11", "Mod", id, "ProfilePage"] => {
+            let hosted = find_mod(mods, id)?;
+            Some(("application/json", profile_page(hosted, root).into_bytes()))
+        },
+        ["dl", id, idx] => {
+            let file = find_mod(mods, id)?.files.get(idx.parse::<usize>().ok()?)?;
+            Some(("application/octet-stream", file.payload.clone()))
+        },
+        _ => None,
+    }
+}
+
+fn find_mod<'a>(mods: &'a [HostedMod], id: &str) -> Option<&'a HostedMod> {
+    let id = id.parse::<ModId>().ok()?;
+    mods.iter().find(|m| m.id == id)
+}
+
+fn search_results(mods: &[HostedMod], query: &str) -> String {
+    let search_str = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("_sSearchString="))
+        .unwrap_or_default()
+        .replace("%20", " ");
+
+    let records = mods
+        .iter()
+        .filter(|m| m.name == search_str)
+        .map(|m| format!(r#"{{ "_idRow": {}, "_sName": "{}" }}"#, m.id, m.name))
+        .collect::<Vec<_>>();
+
+    format!(
+        r#"{{ "_aMetadata": {{ "_nRecordCount": {} }}, "_aRecords": [{}] }}"#,
+        records.len(),
+        records.join(",")
+    )
+}
+
+fn top_subs(mods: &[HostedMod]) -> String {
+    let items = mods
+        .iter()
+        .map(|m| {
+            format!(
+                r#"{{ "_idRow": {}, "_sModelName": "Mod", "_sName": "{}" }}"#,
+                m.id, m.name
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!("[{}]", items.join(","))
+}
+
+fn profile_page(hosted: &HostedMod, root: &str) -> String {
+    let mut files = String::new();
+    for (idx, file) in hosted.files.iter().enumerate() {
+        if idx > 0 {
+            files.push(',');
+        }
+
+        write!(
+            files,
+            r#"{{
+                "_sFile": "{}",
+                "_tsDateAdded": 0,
+                "_nFilesize": {},
+                "_sDownloadUrl": "{root}/dl/{}/{idx}",
+                "_sMd5Checksum": "{:x}"
+            }}"#,
+            file.name,
+            file.payload.len(),
+            hosted.id,
+            md5::compute(&file.payload)
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"{{ "_sName": "{}", "_sVersion": "1.0", "_aFiles": [{files}] }}"#,
+        hosted.name
+    )
+}
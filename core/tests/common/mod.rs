@@ -0,0 +1,217 @@
+//! Helpers for building mod manager state directories on disk for tests.
+
+// Each integration test binary only uses some of the helpers.
+#![allow(dead_code)]
+
+pub mod fixture_archives;
+pub mod fixture_host;
+
+use std::fs;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use tempfile::TempDir;
+use ultimate_mod_man_rs_core::{
+    cmds::status::{StatusCmdInfo, StatusCmdKind, StatusSortKey},
+    mod_manager::ModManager,
+    mod_query::ModQuery,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::BananaClient;
+pub use ultimate_mod_man_rs_utils::test_support::DummyDelegate;
+use ultimate_mod_man_rs_utils::types::{ModId, VariantAndIdentifier};
+
+/// A variant to write into a fixture state directory.
+pub struct FixtureVariant {
+    pub name: &'static str,
+    pub enabled: bool,
+
+    /// Files of the variant that are not associated with any slot.
+    pub global_files: &'static [&'static str],
+
+    /// Skin slots (character and slot index) that the variant has a model
+    /// file for.
+    pub skin_slots: &'static [(&'static str, usize)],
+}
+
+impl FixtureVariant {
+    /// A variant without any files.
+    pub fn new(name: &'static str, enabled: bool) -> Self {
+        Self {
+            name,
+            enabled,
+            global_files: &[],
+            skin_slots: &[],
+        }
+    }
+}
+
+/// A mod to write into a fixture state directory.
+pub struct FixtureMod {
+    pub id: ModId,
+    pub name: &'static str,
+    pub variants: Vec<FixtureVariant>,
+
+    /// When the mod was last checked for updates.
+    pub last_update_check: Option<DateTime<Utc>>,
+}
+
+impl FixtureMod {
+    pub fn dir_name(&self) -> String {
+        format!("{}_{}", self.name, self.id)
+    }
+
+    fn mod_info_toml(&self) -> String {
+        let mut toml_str = format!("id = {}\nname = \"{}\"\n", self.id, self.name);
+        if let Some(checked_at) = self.last_update_check {
+            toml_str.push_str(&format!(
+                "last_update_check = \"{}\"\n",
+                checked_at.to_rfc3339()
+            ));
+        }
+
+        for variant in self.variants.iter() {
+            let table = format!("installed_variants.\"{}\"", variant.name);
+            toml_str.push_str(&format!(
+                "\n[{table}]\nname = \"{}\"\noverrides = []\nenabled = \
+                 {}\n\n[{table}.file_info]\nmod_type = []\n",
+                variant.name, variant.enabled
+            ));
+
+            for (char_key, skin_slot_idx) in variant.skin_slots.iter() {
+                toml_str.push_str(&format!(
+                    "\n[[{table}.file_info.owned_files]]\nassociation = {{ CharSkinSlot = {{ \
+                     char_key = \"{char_key}\", skin_slot_idx = {skin_slot_idx} }} }}\nfiles = \
+                     [\"fighter/{char_key}/model/body/c{skin_slot_idx:02}/model.numdlb\"]\n"
+                ));
+            }
+
+            if !variant.global_files.is_empty() {
+                let files = variant
+                    .global_files
+                    .iter()
+                    .map(|f| format!("\"{f}\""))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                toml_str.push_str(&format!(
+                    "\n[[{table}.file_info.owned_files]]\nassociation = \"Global\"\nfiles = \
+                     [{files}]\n"
+                ));
+            } else if variant.skin_slots.is_empty() {
+                toml_str.push_str("owned_files = []\n");
+            }
+        }
+
+        toml_str
+    }
+}
+
+/// A mod with a single variant that has no files.
+pub fn m_mod(
+    id: ModId,
+    name: &'static str,
+    variant_name: &'static str,
+    enabled: bool,
+) -> FixtureMod {
+    FixtureMod {
+        id,
+        name,
+        variants: vec![FixtureVariant::new(variant_name, enabled)],
+        last_update_check: None,
+    }
+}
+
+pub fn m_ident(s: &str) -> VariantAndIdentifier {
+    s.parse().unwrap()
+}
+
+/// Status command options that show every mod sorted by name.
+pub fn m_status_info(kind: StatusCmdKind) -> StatusCmdInfo {
+    StatusCmdInfo {
+        kind,
+        verbose: false,
+        recently_enabled_within: None,
+        tag: None,
+        overrides: false,
+        sort: StatusSortKey::Name,
+        filter: ModQuery::default(),
+    }
+}
+
+/// A state directory on disk that is deleted when dropped.
+pub struct FixtureStateDir {
+    dir: TempDir,
+}
+
+impl FixtureStateDir {
+    pub fn new(mods: &[FixtureMod]) -> Self {
+        let dir = TempDir::new().unwrap();
+        let fixture = Self { dir };
+
+        for mod_info in mods {
+            let mod_dir = fixture.path().join(mod_info.dir_name());
+            fs::create_dir_all(&mod_dir).unwrap();
+            fs::write(mod_dir.join("mod_info.toml"), mod_info.mod_info_toml()).unwrap();
+
+            for variant in mod_info.variants.iter() {
+                fs::create_dir_all(mod_dir.join(variant.name).join("expanded")).unwrap();
+            }
+        }
+
+        fixture
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        Utf8Path::from_path(self.dir.path()).unwrap()
+    }
+
+    pub fn mod_info_path(&self, mod_info: &FixtureMod) -> Utf8PathBuf {
+        self.path().join(mod_info.dir_name()).join("mod_info.toml")
+    }
+
+    /// Reads the persisted enabled flag of a variant directly from disk.
+    pub fn is_variant_enabled_on_disk(&self, mod_info: &FixtureMod, variant_name: &str) -> bool {
+        let mod_info_str = fs::read_to_string(self.mod_info_path(mod_info)).unwrap();
+        let mod_info_val: toml::Value = toml::from_str(&mod_info_str).unwrap();
+
+        mod_info_val["installed_variants"][variant_name]["enabled"]
+            .as_bool()
+            .unwrap()
+    }
+
+    pub async fn open_manager(&self) -> ModManager<DummyDelegate> {
+        ModManager::new(self.path(), DummyDelegate::default())
+            .await
+            .unwrap()
+    }
+
+    /// Opens a manager that answers prompts with `delegate`.
+    pub async fn open_manager_answering(
+        &self,
+        delegate: DummyDelegate,
+    ) -> ModManager<DummyDelegate> {
+        ModManager::new(self.path(), delegate).await.unwrap()
+    }
+
+    /// Opens a manager that sends its GameBanana requests to `root` instead.
+    pub async fn open_manager_with_root(&self, root: &str) -> ModManager<DummyDelegate> {
+        self.open_manager_with_delegate(root, DummyDelegate::default())
+            .await
+    }
+
+    /// Opens a manager that sends its GameBanana requests to `root` and
+    /// answers prompts with `delegate`.
+    pub async fn open_manager_with_delegate(
+        &self,
+        root: &str,
+        delegate: DummyDelegate,
+    ) -> ModManager<DummyDelegate> {
+        ModManager::new_with_client(
+            self.path(),
+            BananaClient::with_root(root).unwrap(),
+            delegate,
+        )
+        .await
+        .unwrap()
+    }
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::{
+    DummyDelegate, FixtureStateDir,
+    fixture_archives::{ArchiveKind, FixtureArchive},
+    fixture_host::{FixtureHost, HostedFile, HostedMod},
+};
+use ultimate_mod_man_rs_core::{
+    mod_db::DuplicateReason,
+    mod_manager::{AddConflictPolicy, ModManager},
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::{ChecksumPolicy, VariantMatchPolicy};
+use ultimate_mod_man_rs_utils::types::{ModId, VariantAndId};
+
+fn m_hosted_mod(id: ModId, name: &'static str, payload: Vec<u8>) -> HostedMod {
+    HostedMod {
+        id,
+        name,
+        files: vec![HostedFile {
+            name: "a.zip",
+            payload,
+        }],
+    }
+}
+
+/// A skin pack with a lot of textures and a readme that differs between
+/// uploads.
+fn m_skin_pack(readme: &[u8]) -> Vec<u8> {
+    (0..40)
+        .fold(FixtureArchive::default(), |archive, i| {
+            archive.with_file(
+                format!("SkinPack/fighter/mario/model/body/c02/tex_{i}.nutexb"),
+                format!("texture {i}").as_bytes(),
+            )
+        })
+        .with_file("SkinPack/README.txt", readme)
+        .build(ArchiveKind::Zip)
+}
+
+async fn add_all(mm: &mut ModManager<DummyDelegate>, ids: &[ModId]) {
+    let outcome = mm
+        .add_mods(
+            ids.iter().map(|id| format!("{id}/a.zip").parse().unwrap()),
+            None,
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::KeepExisting,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.succeeded.len(), ids.len());
+}
+
+fn grouped_keys(mm: &ModManager<DummyDelegate>) -> Vec<(DuplicateReason, Vec<VariantAndId>)> {
+    mm.find_duplicates()
+        .unwrap()
+        .groups
+        .into_iter()
+        .map(|group| {
+            let keys = group.copies.into_iter().map(|copy| copy.key).collect();
+            (group.reason, keys)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn mods_installed_from_the_same_archive_are_duplicates() {
+    let payload = FixtureArchive::mario_skin("Reupload", 2).build(ArchiveKind::Zip);
+    let md5 = format!("{:x}", md5::compute(&payload));
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(1, "Original", payload.clone()),
+        m_hosted_mod(2, "Reupload", payload),
+        m_hosted_mod(
+            3,
+            "Battlefield",
+            FixtureArchive::battlefield_stage("Battlefield").build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add_all(&mut mm, &[1, 2, 3]).await;
+
+    assert_eq!(
+        grouped_keys(&mm),
+        [(
+            DuplicateReason::SameArchive { md5 },
+            vec![
+                VariantAndId::new(1, "a.zip".to_string()),
+                VariantAndId::new(2, "a.zip".to_string())
+            ]
+        )]
+    );
+}
+
+#[tokio::test]
+async fn mods_with_nearly_identical_files_are_duplicates() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(1, "Original", m_skin_pack(b"By someone")),
+        m_hosted_mod(2, "Repost", m_skin_pack(b"Reposted by someone else")),
+        m_hosted_mod(
+            3,
+            "Battlefield",
+            FixtureArchive::battlefield_stage("Battlefield").build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add_all(&mut mm, &[1, 2, 3]).await;
+
+    let groups = grouped_keys(&mm);
+    assert_eq!(groups.len(), 1);
+
+    let (reason, keys) = &groups[0];
+    assert!(
+        matches!(reason, DuplicateReason::SimilarFiles { similarity } if *similarity >= 0.95 && *similarity < 1.0)
+    );
+    assert_eq!(
+        keys,
+        &[
+            VariantAndId::new(1, "a.zip".to_string()),
+            VariantAndId::new(2, "a.zip".to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn duplicates_are_only_removed_when_picked() {
+    let payload = FixtureArchive::mario_skin("Reupload", 2).build(ArchiveKind::Zip);
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(1, "Original", payload.clone()),
+        m_hosted_mod(2, "Reupload", payload),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add_all(&mut mm, &[1, 2]).await;
+
+    // The delegate only picks what was preselected, which is nothing.
+    let report = mm.find_duplicates().unwrap();
+    let outcome = mm.remove_duplicates(&report).await.unwrap();
+
+    assert!(outcome.succeeded.is_empty());
+    assert_eq!(mm.list_variants_for_mod(1).count(), 1);
+    assert_eq!(mm.list_variants_for_mod(2).count(), 1);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use std::fs;
+
+use common::{FixtureStateDir, m_mod};
+
+#[tokio::test]
+async fn disk_usage_is_measured_for_variants_without_a_recorded_size() {
+    let mods = [
+        m_mod(1, "Small", "s.zip", true),
+        m_mod(2, "Large", "l.zip", true),
+    ];
+    let state_dir = FixtureStateDir::new(&mods);
+
+    for (mod_info, size) in mods.iter().zip([100, 2048]) {
+        let variant_dir = state_dir
+            .path()
+            .join(mod_info.dir_name())
+            .join(mod_info.variants[0].name)
+            .join("expanded");
+        fs::write(variant_dir.join("file.bin"), vec![0; size]).unwrap();
+    }
+
+    let mm = state_dir.open_manager().await;
+    assert_eq!(mm.estimate_disk_usage_for_mod(1), 100);
+    assert_eq!(mm.estimate_disk_usage_for_mod(3), 0);
+    assert_eq!(mm.estimate_disk_usage(), 2148);
+
+    let report = mm.disk_usage();
+    let names = report
+        .mods
+        .iter()
+        .map(|mod_usage| mod_usage.name.as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["Large", "Small"]);
+    assert_eq!(report.total_bytes, 2148);
+}
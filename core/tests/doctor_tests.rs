@@ -0,0 +1,185 @@
+mod common;
+
+use std::{fs, process};
+
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, fixture_host::FixtureHost};
+use ultimate_mod_man_rs_core::{
+    cmds::doctor::{CheckStatus, DoctorCheck, DoctorFix, DoctorReport},
+    mod_manager::{ModManagerOptions, apply_doctor_fix, run_doctor_with_client},
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaClientSettings};
+
+fn m_mod() -> FixtureMod {
+    FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![
+            FixtureVariant::new("a.zip", true),
+            FixtureVariant::new("b.zip", false),
+        ],
+        last_update_check: None,
+    }
+}
+
+async fn diagnose_with_client(state_dir: &FixtureStateDir, scraper: BananaClient) -> DoctorReport {
+    run_doctor_with_client(state_dir.path(), &ModManagerOptions::default(), scraper).await
+}
+
+async fn diagnose(state_dir: &FixtureStateDir) -> DoctorReport {
+    let host = FixtureHost::start(Vec::new()).await;
+    diagnose_with_client(state_dir, BananaClient::with_root(host.root()).unwrap()).await
+}
+
+fn check<'a>(report: &'a DoctorReport, name: &str) -> &'a DoctorCheck {
+    report
+        .checks
+        .iter()
+        .find(|check| check.name == name)
+        .unwrap_or_else(|| panic!("No \"{}\" check in:\n{}", name, report))
+}
+
+#[tokio::test]
+async fn healthy_state_dir_passes() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+
+    let report = diagnose(&state_dir).await;
+
+    // How much space the machine running the tests has is out of our hands.
+    assert!(
+        report
+            .checks
+            .iter()
+            .filter(|check| check.name != "disk space")
+            .all(|check| check.status == CheckStatus::Pass),
+        "{}",
+        report
+    );
+    assert!(!report.has_failures());
+    assert_eq!(report.fixes().count(), 0);
+}
+
+#[tokio::test]
+async fn stale_lock_can_be_cleared() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    fs::write(state_dir.path().join(".lockfile"), "4194304").unwrap();
+
+    let report = diagnose(&state_dir).await;
+    let lock_check = check(&report, "lock");
+
+    assert_eq!(lock_check.status, CheckStatus::Warn);
+    assert!(lock_check.detail.contains("4194304"));
+    assert_eq!(
+        lock_check.fix,
+        Some(DoctorFix::ClearStaleLock { pid: 4194304 })
+    );
+
+    apply_doctor_fix(state_dir.path(), DoctorFix::ClearStaleLock { pid: 4194304 })
+        .await
+        .unwrap();
+
+    assert!(!state_dir.path().join(".lockfile").exists());
+}
+
+#[tokio::test]
+async fn lock_of_a_running_instance_is_never_cleared() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let lock_path = state_dir.path().join(".lockfile");
+    fs::write(&lock_path, process::id().to_string()).unwrap();
+
+    let report = diagnose(&state_dir).await;
+    let lock_check = check(&report, "lock");
+
+    assert_eq!(lock_check.status, CheckStatus::Warn);
+    assert_eq!(lock_check.fix, None);
+
+    // Even if the fix is applied anyway.
+    apply_doctor_fix(
+        state_dir.path(),
+        DoctorFix::ClearStaleLock { pid: process::id() },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&lock_path).unwrap(),
+        process::id().to_string()
+    );
+}
+
+#[tokio::test]
+async fn corrupted_name_cache_can_be_reset() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let cache_path = state_dir.path().join("mod_name_resolution_cache.toml");
+    fs::write(&cache_path, "names = [not toml").unwrap();
+
+    let report = diagnose(&state_dir).await;
+    let cache_check = check(&report, "name cache");
+
+    assert_eq!(cache_check.status, CheckStatus::Fail);
+    assert_eq!(cache_check.fix, Some(DoctorFix::ResetNameCache));
+
+    apply_doctor_fix(state_dir.path(), DoctorFix::ResetNameCache)
+        .await
+        .unwrap();
+
+    assert!(!cache_path.exists());
+    assert!(!diagnose(&state_dir).await.has_failures());
+}
+
+#[tokio::test]
+async fn variants_without_data_can_be_forgotten() {
+    let fixture_mod = m_mod();
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    fs::remove_dir_all(state_dir.path().join(fixture_mod.dir_name()).join("b.zip")).unwrap();
+
+    // The whole mod is skipped while one of its variants has no data.
+    assert_eq!(state_dir.open_manager().await.list_installed().count(), 0);
+
+    let report = diagnose(&state_dir).await;
+    let variants_check = check(&report, "variant directories");
+
+    assert_eq!(variants_check.status, CheckStatus::Fail);
+    assert!(variants_check.detail.contains("b.zip"));
+    assert_eq!(variants_check.fix, Some(DoctorFix::ForgetMissingVariants));
+
+    apply_doctor_fix(state_dir.path(), DoctorFix::ForgetMissingVariants)
+        .await
+        .unwrap();
+
+    let mm = state_dir.open_manager().await;
+    let installed = mm.list_installed().collect::<Vec<_>>();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(
+        installed[0].installed_variants.keys().collect::<Vec<_>>(),
+        ["a.zip"]
+    );
+}
+
+#[tokio::test]
+async fn unreadable_mod_info_is_reported_without_a_fix() {
+    let fixture_mod = m_mod();
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    fs::write(state_dir.mod_info_path(&fixture_mod), "id = \"one\"").unwrap();
+
+    let report = diagnose(&state_dir).await;
+    let mods_check = check(&report, "installed mods");
+
+    assert_eq!(mods_check.status, CheckStatus::Fail);
+    assert!(mods_check.detail.contains(&fixture_mod.dir_name()));
+    assert_eq!(mods_check.fix, None);
+    assert!(report.has_failures());
+}
+
+#[tokio::test]
+async fn unreachable_gamebanana_fails() {
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let settings = BananaClientSettings {
+        max_retries: 0,
+        ..Default::default()
+    };
+    let scraper = BananaClient::with_root_and_settings("http://127.0.0.1:1", settings).unwrap();
+
+    let report = diagnose_with_client(&state_dir, scraper).await;
+
+    assert_eq!(check(&report, "GameBanana").status, CheckStatus::Fail);
+}
@@ -0,0 +1,991 @@
+//! Runs whole commands against a fixture mod host, from the download all the
+//! way to what ends up in the state directory.
+
+mod common;
+
+use std::{fs, time::Duration};
+
+use camino::Utf8PathBuf;
+use common::{
+    DummyDelegate, FixtureStateDir,
+    fixture_archives::{ArchiveKind, FixtureArchive},
+    fixture_host::{FixtureHost, HostedFile, HostedMod},
+    m_ident,
+};
+use ultimate_mod_man_rs_core::{
+    config::ConfigKey,
+    mod_db::ModDbError,
+    mod_manager::{AddConflictPolicy, BatchOutcome, ModManager, ModManagerErr, SkipReason},
+};
+use ultimate_mod_man_rs_scraper::{
+    banana_scraper::{ChecksumPolicy, VariantMatchPolicy},
+    mod_file_classifier::AssetType,
+};
+use ultimate_mod_man_rs_utils::types::{
+    CharSkinSlotValue, ModId, ModIdentifier, PickedNonSwappableResolutionOption,
+    PickedResolutionOption, SkinSlotValue, VariantAndId,
+};
+
+fn m_hosted_mod(
+    id: ModId,
+    name: &'static str,
+    file_name: &'static str,
+    payload: Vec<u8>,
+) -> HostedMod {
+    HostedMod {
+        id,
+        name,
+        files: vec![HostedFile {
+            name: file_name,
+            payload,
+        }],
+    }
+}
+
+async fn add(mm: &mut ModManager<DummyDelegate>, idents: &[&str]) -> BatchOutcome {
+    add_with_policy(mm, idents, AddConflictPolicy::Prompt).await
+}
+
+async fn add_with_policy(
+    mm: &mut ModManager<DummyDelegate>,
+    idents: &[&str],
+    conflict_policy: AddConflictPolicy,
+) -> BatchOutcome {
+    mm.add_mods(
+        idents.iter().map(|s| m_ident(s)),
+        None,
+        VariantMatchPolicy::Fuzzy,
+        ChecksumPolicy::Verify,
+        conflict_policy,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+fn is_enabled(mm: &ModManager<DummyDelegate>, id: ModId, variant_name: &str) -> bool {
+    mm.list_variants_for_mod(id)
+        .find(|v| v.name() == variant_name)
+        .unwrap()
+        .is_enabled()
+}
+
+fn upstream_download_url(
+    mm: &ModManager<DummyDelegate>,
+    id: ModId,
+    variant_name: &str,
+) -> Option<String> {
+    mm.list_variants_for_mod(id)
+        .find(|v| v.name() == variant_name)
+        .unwrap()
+        .upstream()
+        .map(|upstream| upstream.download_url.clone())
+}
+
+/// Everything about the installed mods that should survive a reload.
+fn snapshot(mm: &ModManager<DummyDelegate>) -> (Vec<(ModId, String, String, bool)>, String) {
+    let mut variants = mm
+        .list_installed()
+        .flat_map(|mod_info| {
+            mod_info.installed_variants.values().map(|v| {
+                (
+                    mod_info.id,
+                    mod_info.name.clone(),
+                    v.name().to_string(),
+                    v.is_enabled(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    variants.sort();
+
+    (variants, mm.occupancy(false).to_string())
+}
+
+#[tokio::test]
+async fn clean_add_installs_and_enables_every_archive_kind() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "ZipMario",
+            "z.zip",
+            FixtureArchive::mario_skin("ZipMario", 1).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "SevenMario",
+            "s.7z",
+            FixtureArchive::mario_skin("SevenMario", 2).build(ArchiveKind::SevenZip),
+        ),
+        m_hosted_mod(
+            3,
+            "TarMario",
+            "t.tar",
+            FixtureArchive::mario_skin("TarMario", 3).build(ArchiveKind::Tar),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let outcome = add(&mut mm, &["1/z.zip", "2/s.7z", "3/t.tar"]).await;
+
+    assert_eq!(outcome.succeeded.len(), 3);
+    for (id, name, variant_name) in [
+        (1, "ZipMario", "z.zip"),
+        (2, "SevenMario", "s.7z"),
+        (3, "TarMario", "t.tar"),
+    ] {
+        let mod_dir = state_dir.path().join(format!("{name}_{id}"));
+
+        assert!(mod_dir.join("mod_info.toml").exists());
+        assert!(mod_dir.join(variant_name).join("expanded").exists());
+        assert!(is_enabled(&mm, id, variant_name));
+    }
+
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+    assert!(!state_dir.path().join("quarantine/1_z.zip").exists());
+}
+
+#[tokio::test]
+async fn conflicting_skin_is_moved_to_the_scripted_slot() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "FirstMario",
+            "a.zip",
+            FixtureArchive::mario_skin("FirstMario", 3).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "SecondMario",
+            "b.zip",
+            FixtureArchive::mario_skin("SecondMario", 3).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir
+        .open_manager_with_delegate(
+            host.root(),
+            DummyDelegate::with_conflict_resolutions([PickedResolutionOption::Swap(0)]),
+        )
+        .await;
+
+    add(&mut mm, &["1/a.zip", "2/b.zip"]).await;
+
+    assert!(is_enabled(&mm, 1, "a.zip"));
+    assert!(is_enabled(&mm, 2, "b.zip"));
+
+    let mod_info_str =
+        fs::read_to_string(state_dir.path().join("SecondMario_2/mod_info.toml")).unwrap();
+    let mod_info_val: toml::Value = toml::from_str(&mod_info_str).unwrap();
+    let overrides = mod_info_val["installed_variants"]["b.zip"]["overrides"]
+        .as_array()
+        .unwrap();
+
+    // `c00` is the first slot that is still open.
+    assert_eq!(overrides.len(), 1);
+    assert_eq!(overrides[0]["CharacterSkin"]["old"].as_integer(), Some(3));
+    assert_eq!(overrides[0]["CharacterSkin"]["new"].as_integer(), Some(0));
+}
+
+#[tokio::test]
+async fn browsing_installs_the_picked_mod() {
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 1,
+        name: "CoolMario",
+        files: vec![
+            HostedFile {
+                name: "a.zip",
+                payload: FixtureArchive::mario_skin("CoolMario", 1).build(ArchiveKind::Zip),
+            },
+            HostedFile {
+                name: "b.zip",
+                payload: FixtureArchive::mario_skin("CoolMario", 2).build(ArchiveKind::Zip),
+            },
+        ],
+    }])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let outcome = mm
+        .browse(
+            "CoolMario",
+            None,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The delegate picks the first variant.
+    assert_eq!(outcome.succeeded, [m_ident("1/a.zip")]);
+    assert!(is_enabled(&mm, 1, "a.zip"));
+    assert_eq!(mm.list_variants_for_mod(1).count(), 1);
+}
+
+#[tokio::test]
+async fn browsing_without_a_query_lists_the_top_mods() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "FirstMario",
+            "a.zip",
+            FixtureArchive::mario_skin("FirstMario", 1).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "SecondMario",
+            "b.zip",
+            FixtureArchive::mario_skin("SecondMario", 2).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let outcome = mm
+        .browse("", None, ChecksumPolicy::Verify, AddConflictPolicy::Prompt)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(outcome.succeeded, [m_ident("1/a.zip")]);
+}
+
+#[tokio::test]
+async fn browsing_without_matches_installs_nothing() {
+    let host = FixtureHost::start(Vec::new()).await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let outcome = mm
+        .browse(
+            "Nothing",
+            Some(2),
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+        )
+        .await
+        .unwrap();
+
+    assert!(outcome.is_none());
+    assert_eq!(mm.list_installed().count(), 0);
+}
+
+#[tokio::test]
+async fn alternatives_for_a_skin_slot_can_be_swapped_in() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "FirstMario",
+            "a.zip",
+            FixtureArchive::mario_skin("FirstMario", 3).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "SecondMario",
+            "b.zip",
+            FixtureArchive::mario_skin("SecondMario", 3).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            3,
+            "ThirdMario",
+            "c.zip",
+            FixtureArchive::mario_skin("ThirdMario", 3).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir
+        .open_manager_with_delegate(
+            host.root(),
+            DummyDelegate::with_conflict_resolutions([PickedResolutionOption::KeepAsAlternative]),
+        )
+        .await;
+    let slot = CharSkinSlotValue::new("mario".to_string(), SkinSlotValue::new(3));
+    let alternative_for = |mm: &ModManager<DummyDelegate>, id| {
+        mm.list_variants_for_mod(id)
+            .next()
+            .unwrap()
+            .alternative_for()
+            .cloned()
+    };
+
+    add(&mut mm, &["1/a.zip", "2/b.zip"]).await;
+    add_with_policy(&mut mm, &["3/c.zip"], AddConflictPolicy::KeepAsAlternative).await;
+
+    assert!(is_enabled(&mm, 1, "a.zip"));
+    assert!(!is_enabled(&mm, 2, "b.zip"));
+    assert!(!is_enabled(&mm, 3, "c.zip"));
+    assert_eq!(alternative_for(&mm, 1), None);
+    assert_eq!(alternative_for(&mm, 2), Some(slot.clone()));
+    assert_eq!(alternative_for(&mm, 3), Some(slot.clone()));
+
+    mm.swap_active(slot.clone(), m_ident("2/b.zip"))
+        .await
+        .unwrap();
+
+    assert!(!is_enabled(&mm, 1, "a.zip"));
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert!(!is_enabled(&mm, 3, "c.zip"));
+    assert_eq!(alternative_for(&mm, 1), Some(slot.clone()));
+    assert_eq!(alternative_for(&mm, 2), None);
+
+    // The swap sticks after reloading from disk.
+    drop(mm);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert_eq!(alternative_for(&mm, 1), Some(slot.clone()));
+
+    // Only registered alternatives can be swapped in.
+    let res = mm.swap_active(slot, m_ident("2/b.zip")).await;
+    assert!(matches!(
+        res,
+        Err(ModManagerErr::ModDbError(ModDbError::NotAnAlternative(..)))
+    ));
+}
+
+#[tokio::test]
+async fn conflicting_stage_replaces_the_existing_variant_when_scripted() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "OldStage",
+            "a.zip",
+            FixtureArchive::battlefield_stage("OldStage").build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "NewStage",
+            "b.7z",
+            FixtureArchive::battlefield_stage("NewStage").build(ArchiveKind::SevenZip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir
+        .open_manager_with_delegate(
+            host.root(),
+            DummyDelegate::with_conflict_resolutions([PickedResolutionOption::NonSwapOption(
+                PickedNonSwappableResolutionOption::Replace,
+            )]),
+        )
+        .await;
+
+    add(&mut mm, &["1/a.zip", "2/b.7z"]).await;
+
+    assert!(!is_enabled(&mm, 1, "a.zip"));
+    assert!(is_enabled(&mm, 2, "b.7z"));
+}
+
+#[tokio::test]
+async fn conflicting_variant_of_the_same_mod_replaces_its_sibling_in_one_step() {
+    let all_slots = (0..8).fold(FixtureArchive::default(), |archive, slot| {
+        archive.with_file(
+            format!("AllSlots/fighter/mario/model/body/c0{slot}/model.numdlb"),
+            b"model",
+        )
+    });
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 1,
+        name: "Mario",
+        files: vec![
+            HostedFile {
+                name: "all_slots.zip",
+                payload: all_slots.build(ArchiveKind::Zip),
+            },
+            HostedFile {
+                name: "c02.zip",
+                payload: FixtureArchive::mario_skin("C02", 2).build(ArchiveKind::Zip),
+            },
+        ],
+    }])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir
+        .open_manager_with_delegate(host.root(), DummyDelegate::replacing_sibling_variants())
+        .await;
+
+    let outcome = add(&mut mm, &["1/all_slots.zip", "1/c02.zip"]).await;
+
+    assert!(!is_enabled(&mm, 1, "all_slots.zip"));
+    assert!(is_enabled(&mm, 1, "c02.zip"));
+    assert_eq!(
+        outcome.replaced_siblings,
+        [(
+            VariantAndId::new(1, "all_slots.zip".to_string()),
+            VariantAndId::new(1, "c02.zip".to_string())
+        )]
+    );
+    assert!(outcome.to_string().contains(
+        "Disabled 1/all_slots.zip in favor of 1/c02.zip (another variant of the same mod)."
+    ));
+}
+
+#[tokio::test]
+async fn conflicting_add_keeps_the_existing_variant_by_default() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "OldStage",
+            "a.zip",
+            FixtureArchive::battlefield_stage("OldStage").build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "NewStage",
+            "b.tar",
+            FixtureArchive::battlefield_stage("NewStage").build(ArchiveKind::Tar),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    add(&mut mm, &["1/a.zip", "2/b.tar"]).await;
+
+    assert!(is_enabled(&mm, 1, "a.zip"));
+    assert!(!is_enabled(&mm, 2, "b.tar"));
+}
+
+/// Two stages that fight over Battlefield and two skins that fight over
+/// Mario's `c03`.
+async fn start_conflicting_host() -> FixtureHost {
+    FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "OldStage",
+            "a.zip",
+            FixtureArchive::battlefield_stage("OldStage").build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "NewStage",
+            "b.zip",
+            FixtureArchive::battlefield_stage("NewStage").build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            3,
+            "OldMario",
+            "c.zip",
+            FixtureArchive::mario_skin("OldMario", 3).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            4,
+            "NewMario",
+            "d.zip",
+            FixtureArchive::mario_skin("NewMario", 3).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await
+}
+
+#[tokio::test]
+async fn replace_existing_gives_every_contested_slot_to_the_added_variant() {
+    let host = start_conflicting_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    // The delegate would keep the existing variants if it was asked.
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add(&mut mm, &["1/a.zip", "3/c.zip"]).await;
+
+    add_with_policy(
+        &mut mm,
+        &["2/b.zip", "4/d.zip"],
+        AddConflictPolicy::ReplaceExisting,
+    )
+    .await;
+
+    assert!(!is_enabled(&mm, 1, "a.zip"));
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert!(!is_enabled(&mm, 3, "c.zip"));
+    assert!(is_enabled(&mm, 4, "d.zip"));
+}
+
+#[tokio::test]
+async fn keep_existing_leaves_every_contested_slot_with_the_installed_variant() {
+    let host = start_conflicting_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    // The delegate would replace the existing variants if it was asked.
+    let mut mm = state_dir
+        .open_manager_with_delegate(
+            host.root(),
+            DummyDelegate::with_conflict_resolutions([
+                PickedResolutionOption::NonSwapOption(PickedNonSwappableResolutionOption::Replace),
+                PickedResolutionOption::NonSwapOption(PickedNonSwappableResolutionOption::Replace),
+            ]),
+        )
+        .await;
+    add(&mut mm, &["1/a.zip", "3/c.zip"]).await;
+
+    add_with_policy(
+        &mut mm,
+        &["2/b.zip", "4/d.zip"],
+        AddConflictPolicy::KeepExisting,
+    )
+    .await;
+
+    assert!(is_enabled(&mm, 1, "a.zip"));
+    assert!(!is_enabled(&mm, 2, "b.zip"));
+    assert!(is_enabled(&mm, 3, "c.zip"));
+    assert!(!is_enabled(&mm, 4, "d.zip"));
+}
+
+#[tokio::test]
+async fn mixed_batch_add_reports_what_happened_to_each_variant() {
+    let host = start_conflicting_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add(&mut mm, &["1/a.zip"]).await;
+
+    let outcome = add_with_policy(
+        &mut mm,
+        &["1/a.zip", "2/b.zip", "5/e.zip"],
+        AddConflictPolicy::ReplaceExisting,
+    )
+    .await;
+
+    assert_eq!(outcome.succeeded, [m_ident("2/b.zip")]);
+    assert_eq!(
+        outcome.skipped,
+        [(m_ident("1/a.zip"), SkipReason::AlreadyInstalled)]
+    );
+    assert_eq!(outcome.failed.len(), 1);
+    assert_eq!(outcome.failed[0].0, m_ident("5/e.zip"));
+    assert!(matches!(
+        outcome.failed[0].1,
+        ModManagerErr::BananaScraperError(_)
+    ));
+    assert_eq!(outcome.conflicts_resolved, 1);
+}
+
+#[tokio::test]
+async fn downloads_run_in_parallel_up_to_the_configured_limit() {
+    let names = ["AMario", "BMario", "CMario", "DMario"];
+    let mods = names
+        .iter()
+        .zip(1..)
+        .map(|(name, id)| {
+            m_hosted_mod(
+                id,
+                name,
+                "a.zip",
+                FixtureArchive::mario_skin(name, id as u8).build(ArchiveKind::Zip),
+            )
+        })
+        .collect();
+    let host = FixtureHost::start_with_download_delay(mods, Duration::from_millis(200)).await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    mm.override_config(ConfigKey::DownloadMaxConcurrent, "2")
+        .unwrap();
+
+    let outcome = add(&mut mm, &["1/a.zip", "2/a.zip", "3/a.zip", "4/a.zip"]).await;
+
+    assert_eq!(outcome.succeeded.len(), 4);
+    assert_eq!(host.max_concurrent_downloads(), 2);
+    for id in 1..=4 {
+        assert!(is_enabled(&mm, id, "a.zip"));
+    }
+}
+
+#[tokio::test]
+async fn deleted_variant_is_removed_from_disk_and_the_db() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "KeptMario",
+            "a.zip",
+            FixtureArchive::mario_skin("KeptMario", 1).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "GoneMario",
+            "b.zip",
+            FixtureArchive::mario_skin("GoneMario", 2).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add(&mut mm, &["1/a.zip", "2/b.zip"]).await;
+
+    // A variant that is not installed should not stop the rest from being deleted.
+    let outcome = mm
+        .delete_variants([m_ident("1/x.zip"), m_ident("2/b.zip")])
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.succeeded, [m_ident("2/b.zip")]);
+    assert_eq!(
+        outcome.skipped,
+        [(m_ident("1/x.zip"), SkipReason::NotInstalled)]
+    );
+    assert_eq!(mm.list_variants_for_mod(2).count(), 0);
+    assert!(!state_dir.path().join("GoneMario_2/b.zip").exists());
+    assert!(state_dir.path().join("KeptMario_1/a.zip/expanded").exists());
+
+    drop(mm);
+    let mm = state_dir.open_manager().await;
+    assert_eq!(mm.list_variants_for_mod(2).count(), 0);
+    assert!(is_enabled(&mm, 1, "a.zip"));
+}
+
+#[tokio::test]
+async fn add_interrupted_before_finishing_is_rolled_back_on_next_run() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "KeptMario",
+            "a.zip",
+            FixtureArchive::mario_skin("KeptMario", 1).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "CrashMario",
+            "b.zip",
+            FixtureArchive::mario_skin("CrashMario", 2).build(ArchiveKind::Zip),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add(
+        &mut state_dir.open_manager_with_root(host.root()).await,
+        &["1/a.zip", "2/b.zip"],
+    )
+    .await;
+
+    // Pretend that we crashed right before the add of `b.zip` was marked as done.
+    let quarantine_dir = state_dir.path().join("quarantine/2_b.zip");
+    fs::create_dir_all(&quarantine_dir).unwrap();
+    fs::write(quarantine_dir.join("b.zip"), b"leftover download").unwrap();
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Add]\nkey = {{ id = 2, variant_name = \"b.zip\" }}\nquarantine_dir = {:?}\n",
+            quarantine_dir
+        ),
+    )
+    .unwrap();
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    let outcome = add(&mut mm, &["1/a.zip"]).await;
+
+    assert_eq!(
+        outcome.skipped,
+        [(m_ident("1/a.zip"), SkipReason::AlreadyInstalled)]
+    );
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+    assert!(!quarantine_dir.exists());
+    assert!(!state_dir.path().join("CrashMario_2/b.zip").exists());
+    assert_eq!(mm.list_variants_for_mod(2).count(), 0);
+    assert!(is_enabled(&mm, 1, "a.zip"));
+}
+
+#[tokio::test]
+async fn interrupted_add_with_a_recorded_download_is_finished_on_next_run() {
+    let payload = FixtureArchive::mario_skin("CrashMario", 2).build(ArchiveKind::Zip);
+    let md5 = format!("{:x}", md5::compute(&payload));
+    let size = payload.len();
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "KeptMario",
+            "a.zip",
+            FixtureArchive::mario_skin("KeptMario", 1).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(2, "CrashMario", "b.zip", payload),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add(
+        &mut state_dir.open_manager_with_root(host.root()).await,
+        &["1/a.zip"],
+    )
+    .await;
+
+    // Pretend that we crashed part way through installing `b.zip`.
+    let quarantine_dir = state_dir.path().join("quarantine/2_b.zip");
+    fs::create_dir_all(&quarantine_dir).unwrap();
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Add]\nkey = {{ id = 2, variant_name = \"b.zip\" }}\nquarantine_dir = \
+             {quarantine_dir:?}\n\n[in_prog.Add.resume]\nmod_name = \
+             \"CrashMario\"\n\n[in_prog.Add.resume.upstream]\ndownload_url = \"{}/dl/2/0\"\nmd5 = \
+             \"{md5}\"\nsize = {size}\ndate_added = \"1970-01-01T00:00:00Z\"\n",
+            host.root()
+        ),
+    )
+    .unwrap();
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    let outcome = add(&mut mm, &[]).await;
+
+    assert_eq!(outcome.succeeded, [m_ident("2/b.zip")]);
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert!(
+        state_dir
+            .path()
+            .join("CrashMario_2/b.zip/expanded")
+            .exists()
+    );
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+    assert!(!quarantine_dir.exists());
+}
+
+/// Leaves behind what an add of `b.zip` of `CrashMario` (ID `2`) that was cut
+/// off while expanding the archive would have.
+async fn m_partial_expansion(state_dir: &FixtureStateDir) -> Utf8PathBuf {
+    // Without a `mod_info.toml` next to it, an unmarked state directory would look
+    // like it was not ours.
+    drop(state_dir.open_manager().await);
+
+    let variant_dir = state_dir.path().join("CrashMario_2/b.zip");
+    let partial_dir = variant_dir.join("expanded/fighter/mario/model/body/c02");
+    fs::create_dir_all(&partial_dir).unwrap();
+    fs::write(variant_dir.join("b.zip"), b"archive").unwrap();
+    fs::write(partial_dir.join("half_written.nutexb"), b"half").unwrap();
+
+    partial_dir.join("half_written.nutexb")
+}
+
+#[tokio::test]
+async fn interrupted_archive_expansion_is_cleared_before_resuming_the_add() {
+    let payload = FixtureArchive::mario_skin("CrashMario", 2).build(ArchiveKind::Zip);
+    let md5 = format!("{:x}", md5::compute(&payload));
+    let size = payload.len();
+    let host = FixtureHost::start(vec![m_hosted_mod(2, "CrashMario", "b.zip", payload)]).await;
+    let state_dir = FixtureStateDir::new(&[]);
+
+    let partial_file = m_partial_expansion(&state_dir).await;
+    let quarantine_dir = state_dir.path().join("quarantine/2_b.zip");
+    fs::create_dir_all(&quarantine_dir).unwrap();
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Add]\nkey = {{ id = 2, variant_name = \"b.zip\" }}\nquarantine_dir = \
+             {quarantine_dir:?}\n\n[in_prog.Add.resume]\nmod_name = \
+             \"CrashMario\"\n\n[in_prog.Add.resume.upstream]\ndownload_url = \"{}/dl/2/0\"\nmd5 = \
+             \"{md5}\"\nsize = {size}\ndate_added = \"1970-01-01T00:00:00Z\"\n",
+            host.root()
+        ),
+    )
+    .unwrap();
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    let outcome = add(&mut mm, &[]).await;
+
+    assert_eq!(outcome.succeeded, [m_ident("2/b.zip")]);
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert!(!partial_file.exists());
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+}
+
+#[tokio::test]
+async fn add_clears_a_partial_expansion_that_was_not_journaled() {
+    let host = FixtureHost::start(vec![m_hosted_mod(
+        2,
+        "CrashMario",
+        "b.zip",
+        FixtureArchive::mario_skin("CrashMario", 2).build(ArchiveKind::Zip),
+    )])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let partial_file = m_partial_expansion(&state_dir).await;
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    let outcome = add(&mut mm, &["2/b.zip"]).await;
+
+    assert_eq!(outcome.succeeded, [m_ident("2/b.zip")]);
+    assert!(is_enabled(&mm, 2, "b.zip"));
+    assert!(!partial_file.exists());
+}
+
+#[tokio::test]
+async fn added_variant_records_where_it_was_downloaded_from() {
+    let payload = FixtureArchive::mario_skin("FirstMario", 1).build(ArchiveKind::Zip);
+    let host = FixtureHost::start(vec![m_hosted_mod(
+        1,
+        "FirstMario",
+        "a.zip",
+        payload.clone(),
+    )])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    add(&mut mm, &["1/a.zip"]).await;
+    drop(mm);
+
+    let mm = state_dir.open_manager().await;
+    let variant = mm.list_variants_for_mod(1).next().unwrap();
+    let upstream = variant.upstream().unwrap();
+
+    assert_eq!(upstream.download_url, format!("{}/dl/1/0", host.root()));
+    assert_eq!(upstream.md5, format!("{:x}", md5::compute(&payload)));
+    assert_eq!(upstream.size, Some(payload.len() as u64));
+}
+
+#[tokio::test]
+async fn update_check_refreshes_a_stale_download_url() {
+    let host = FixtureHost::start(vec![m_hosted_mod(
+        1,
+        "FirstMario",
+        "a.zip",
+        FixtureArchive::mario_skin("FirstMario", 1).build(ArchiveKind::Zip),
+    )])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add(
+        &mut state_dir.open_manager_with_root(host.root()).await,
+        &["1/a.zip"],
+    )
+    .await;
+
+    // Pretend that the file has moved since it was added.
+    let mod_info_path = state_dir.path().join("FirstMario_1/mod_info.toml");
+    let mod_info_str = fs::read_to_string(&mod_info_path)
+        .unwrap()
+        .replace(host.root(), "http://127.0.0.1:9");
+    fs::write(&mod_info_path, mod_info_str).unwrap();
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    assert_eq!(
+        upstream_download_url(&mm, 1, "a.zip"),
+        Some("http://127.0.0.1:9/dl/1/0".to_string())
+    );
+
+    let report = mm.check_for_updates(false, &[], true).await.unwrap();
+
+    assert_eq!(report.up_to_date, 1);
+    assert_eq!(
+        upstream_download_url(&mm, 1, "a.zip"),
+        Some(format!("{}/dl/1/0", host.root()))
+    );
+}
+
+#[tokio::test]
+async fn reloading_from_disk_gives_back_the_same_state() {
+    let host = FixtureHost::start(vec![
+        m_hosted_mod(
+            1,
+            "FirstMario",
+            "a.zip",
+            FixtureArchive::mario_skin("FirstMario", 3).build(ArchiveKind::Zip),
+        ),
+        m_hosted_mod(
+            2,
+            "SecondMario",
+            "b.7z",
+            FixtureArchive::mario_skin("SecondMario", 3).build(ArchiveKind::SevenZip),
+        ),
+        m_hosted_mod(
+            3,
+            "Stage",
+            "c.tar",
+            FixtureArchive::battlefield_stage("Stage").build(ArchiveKind::Tar),
+        ),
+    ])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir
+        .open_manager_with_delegate(
+            host.root(),
+            DummyDelegate::with_conflict_resolutions([PickedResolutionOption::Swap(0)]),
+        )
+        .await;
+    add(&mut mm, &["1/a.zip", "2/b.7z", "3/c.tar"]).await;
+
+    let before = snapshot(&mm);
+    drop(mm);
+    let after = snapshot(&state_dir.open_manager().await);
+
+    assert_eq!(before.0.len(), 3);
+    assert_eq!(before, after);
+}
+
+#[tokio::test]
+async fn filtering_by_asset_type_only_installs_the_matching_files() {
+    let archive = FixtureArchive::mario_skin("Mixed", 2).with_file(
+        "Mixed/stage/battlefield/normal/model/stc_main/model.numdlb",
+        b"stage",
+    );
+    let host = FixtureHost::start(vec![m_hosted_mod(
+        1,
+        "Mixed",
+        "a.zip",
+        archive.build(ArchiveKind::Zip),
+    )])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let outcome = mm
+        .add_mods(
+            [m_ident("1/a.zip")],
+            None,
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::Prompt,
+            Some(&[AssetType::Skins]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(outcome.succeeded.len(), 1);
+    assert!(is_enabled(&mm, 1, "a.zip"));
+
+    let mod_info_str = fs::read_to_string(state_dir.path().join("Mixed_1/mod_info.toml")).unwrap();
+    let mod_info_val: toml::Value = toml::from_str(&mod_info_str).unwrap();
+    let associations = mod_info_val["installed_variants"]["a.zip"]["file_info"]["owned_files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["association"].to_string())
+        .collect::<Vec<_>>();
+
+    // The preview has no effect on the game, so it is always kept.
+    assert_eq!(associations.len(), 2, "{:?}", associations);
+    assert!(associations.iter().any(|a| a.contains("CharSkinSlot")));
+    assert!(associations.iter().any(|a| a.contains("NoEffect")));
+    assert!(
+        !state_dir
+            .path()
+            .join("Mixed_1/a.zip/expanded/Mixed/stage")
+            .join("battlefield/normal/model/stc_main/model.numdlb")
+            .exists()
+    );
+}
+
+#[tokio::test]
+async fn added_mods_remember_their_page() {
+    let host = FixtureHost::start(vec![m_hosted_mod(
+        1,
+        "PageMario",
+        "a.zip",
+        FixtureArchive::mario_skin("PageMario", 1).build(ArchiveKind::Zip),
+    )])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add(&mut mm, &["1/a.zip"]).await;
+    drop(mm);
+
+    let page_url = format!("{}/mods/1", host.root());
+    let mod_info_str =
+        fs::read_to_string(state_dir.path().join("PageMario_1/mod_info.toml")).unwrap();
+    let mod_info_val: toml::Value = toml::from_str(&mod_info_str).unwrap();
+    assert_eq!(mod_info_val["source_url"].as_str(), Some(page_url.as_str()));
+
+    // Nothing is listening at this root, so the name is never resolved.
+    let url = state_dir
+        .open_manager_with_root("http://127.0.0.1:9")
+        .await
+        .mod_page_url(&ModIdentifier::Name("PageMario".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(url, page_url);
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::{DummyDelegate, FixtureMod, FixtureStateDir, FixtureVariant, m_ident};
+use ultimate_mod_man_rs_core::mod_manager::VariantToggleOutcome;
+use ultimate_mod_man_rs_utils::types::{
+    PickedNonSwappableResolutionOption, PickedResolutionOption, VariantAndId,
+};
+
+const SHARED_GLOBAL_FILE: &str = "ui/param/database/ui_chara_db.prc";
+
+fn m_mod_a() -> FixtureMod {
+    FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![FixtureVariant {
+            global_files: &[SHARED_GLOBAL_FILE],
+            ..FixtureVariant::new("a.zip", true)
+        }],
+        last_update_check: None,
+    }
+}
+
+fn m_mod_b() -> FixtureMod {
+    FixtureMod {
+        id: 2,
+        name: "ModB",
+        variants: vec![FixtureVariant {
+            global_files: &[SHARED_GLOBAL_FILE],
+            ..FixtureVariant::new("b.zip", false)
+        }],
+        last_update_check: None,
+    }
+}
+
+fn m_key(id: u64, variant_name: &str) -> VariantAndId {
+    VariantAndId::new(id, variant_name.to_string())
+}
+
+#[tokio::test]
+async fn disabling_enabled_variant_persists() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a(), m_mod_b()]);
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("1/a.zip")], false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(1, "a.zip"), VariantToggleOutcome::Disabled)]
+    );
+    assert!(!state_dir.is_variant_enabled_on_disk(&m_mod_a(), "a.zip"));
+
+    // Disabling again should be a no-op.
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("1/a.zip")], false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(1, "a.zip"), VariantToggleOutcome::AlreadyDisabled)]
+    );
+}
+
+#[tokio::test]
+async fn enabling_already_enabled_variant_is_reported() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("1/a.zip")], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(1, "a.zip"), VariantToggleOutcome::AlreadyEnabled)]
+    );
+    assert!(state_dir.is_variant_enabled_on_disk(&m_mod_a(), "a.zip"));
+}
+
+#[tokio::test]
+async fn enabling_conflicting_variant_is_reported_and_not_persisted() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a(), m_mod_b()]);
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("2/b.zip")], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            m_key(2, "b.zip"),
+            VariantToggleOutcome::Conflicts(vec![m_key(1, "a.zip")])
+        )]
+    );
+    assert!(!state_dir.is_variant_enabled_on_disk(&m_mod_b(), "b.zip"));
+}
+
+#[tokio::test]
+async fn enabling_conflicting_variant_resolves_the_conflicts() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a(), m_mod_b()]);
+
+    let summary = state_dir
+        .open_manager_answering(DummyDelegate::with_conflict_resolutions([
+            PickedResolutionOption::NonSwapOption(PickedNonSwappableResolutionOption::Replace),
+        ]))
+        .await
+        .enable_disable([m_ident("2/b.zip")], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(2, "b.zip"), VariantToggleOutcome::Enabled)]
+    );
+    assert!(!state_dir.is_variant_enabled_on_disk(&m_mod_a(), "a.zip"));
+    assert!(state_dir.is_variant_enabled_on_disk(&m_mod_b(), "b.zip"));
+}
+
+#[tokio::test]
+async fn enabling_after_disabling_conflicting_variant_works() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a(), m_mod_b()]);
+    let mut mm = state_dir.open_manager().await;
+
+    mm.enable_disable([m_ident("1/a.zip")], false)
+        .await
+        .unwrap();
+    let summary = mm.enable_disable([m_ident("2/b.zip")], true).await.unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(2, "b.zip"), VariantToggleOutcome::Enabled)]
+    );
+    assert!(!state_dir.is_variant_enabled_on_disk(&m_mod_a(), "a.zip"));
+    assert!(state_dir.is_variant_enabled_on_disk(&m_mod_b(), "b.zip"));
+}
+
+#[tokio::test]
+async fn toggling_missing_variant_is_reported() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("1/missing.zip"), m_ident("9001/other.zip")], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![
+            (m_key(1, "missing.zip"), VariantToggleOutcome::NotInstalled),
+            (m_key(9001, "other.zip"), VariantToggleOutcome::NotInstalled),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn variant_given_by_both_id_and_name_is_only_toggled_once() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable([m_ident("1/a.zip"), m_ident("ModA/a.zip")], false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(m_key(1, "a.zip"), VariantToggleOutcome::Disabled)]
+    );
+}
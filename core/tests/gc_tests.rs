@@ -0,0 +1,137 @@
+mod common;
+
+use std::fs;
+
+use camino::Utf8PathBuf;
+use chrono::{Duration, Utc};
+use common::{
+    DummyDelegate, FixtureStateDir,
+    fixture_archives::{ArchiveKind, FixtureArchive},
+    fixture_host::{FixtureHost, HostedFile, HostedMod},
+    m_ident,
+};
+use ultimate_mod_man_rs_core::mod_manager::{AddConflictPolicy, ModManager, VariantToggleOutcome};
+use ultimate_mod_man_rs_scraper::banana_scraper::{ChecksumPolicy, VariantMatchPolicy};
+use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+async fn start_host() -> FixtureHost {
+    FixtureHost::start(vec![HostedMod {
+        id: 1,
+        name: "Mario",
+        files: vec![HostedFile {
+            name: "a.zip",
+            payload: FixtureArchive::mario_skin("Mario", 2).build(ArchiveKind::Zip),
+        }],
+    }])
+    .await
+}
+
+fn variant_dir(state_dir: &FixtureStateDir) -> Utf8PathBuf {
+    state_dir.path().join("Mario_1").join("a.zip")
+}
+
+async fn add(mm: &mut ModManager<DummyDelegate>) {
+    let outcome = mm
+        .add_mods(
+            [m_ident("1/a.zip")],
+            None,
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            AddConflictPolicy::KeepExisting,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.succeeded.len(), 1);
+}
+
+/// Moves the time the variant was disabled back by `days`.
+fn age_disabled_at(state_dir: &FixtureStateDir, days: i64) {
+    let mod_info_path = state_dir.path().join("Mario_1").join("mod_info.toml");
+    let mut mod_info_val: toml::Value =
+        toml::from_str(&fs::read_to_string(&mod_info_path).unwrap()).unwrap();
+
+    let disabled_at = Utc::now() - Duration::days(days);
+    mod_info_val["installed_variants"]["a.zip"]["last_disabled_at"] =
+        toml::Value::String(disabled_at.to_rfc3339());
+
+    fs::write(&mod_info_path, toml::to_string(&mod_info_val).unwrap()).unwrap();
+}
+
+async fn add_and_disable(state_dir: &FixtureStateDir, host: &FixtureHost) {
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    add(&mut mm).await;
+    mm.enable_disable([m_ident("1/a.zip")], false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn gc_removes_files_of_long_disabled_variants_but_keeps_their_metadata() {
+    let host = start_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add_and_disable(&state_dir, &host).await;
+    age_disabled_at(&state_dir, 60);
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    let report = mm.gc(Duration::days(30)).unwrap();
+
+    assert_eq!(report.collected.len(), 1);
+    assert_eq!(
+        report.collected[0].key,
+        VariantAndId::new(1, "a.zip".to_string())
+    );
+    assert!(report.reclaimed_bytes > 0);
+
+    assert!(!variant_dir(&state_dir).join("expanded").exists());
+    assert!(
+        state_dir
+            .path()
+            .join("Mario_1")
+            .join("mod_info.toml")
+            .exists()
+    );
+    assert!(
+        mm.list_variants_for_mod(1)
+            .any(|var_info| var_info.is_data_collected())
+    );
+
+    let summary = mm.enable_disable([m_ident("1/a.zip")], true).await.unwrap();
+    assert_eq!(summary.outcomes[0].1, VariantToggleOutcome::DataCollected);
+}
+
+#[tokio::test]
+async fn gc_keeps_recently_disabled_variants() {
+    let host = start_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add_and_disable(&state_dir, &host).await;
+    age_disabled_at(&state_dir, 10);
+
+    let report = state_dir
+        .open_manager_with_root(host.root())
+        .await
+        .gc(Duration::days(30))
+        .unwrap();
+
+    assert!(report.collected.is_empty());
+    assert!(variant_dir(&state_dir).join("expanded").exists());
+}
+
+#[tokio::test]
+async fn collected_variants_are_reinstalled_by_add() {
+    let host = start_host().await;
+    let state_dir = FixtureStateDir::new(&[]);
+    add_and_disable(&state_dir, &host).await;
+    age_disabled_at(&state_dir, 60);
+
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+    mm.gc(Duration::days(30)).unwrap();
+    add(&mut mm).await;
+
+    assert!(variant_dir(&state_dir).join("expanded").exists());
+    assert!(
+        mm.list_variants_for_mod(1)
+            .all(|var_info| var_info.is_enabled() && !var_info.is_data_collected())
+    );
+}
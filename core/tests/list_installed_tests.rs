@@ -0,0 +1,194 @@
+mod common;
+
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, m_mod, m_status_info};
+use ultimate_mod_man_rs_core::{
+    cmds::status::{StatusCmdInfo, StatusCmdKind, StatusSortKey},
+    mod_manager::{ModManagerOptions, ModManagerView},
+};
+
+const SHARED_GLOBAL_FILE: &str = "ui/param/database/ui_chara_db.prc";
+
+fn m_mods() -> [FixtureMod; 2] {
+    [
+        FixtureMod {
+            id: 1,
+            name: "ModA",
+            variants: vec![
+                FixtureVariant::new("a.zip", true),
+                FixtureVariant::new("a_alt.zip", false),
+            ],
+            last_update_check: None,
+        },
+        m_mod(2, "ModB", "b.zip", false),
+    ]
+}
+
+#[tokio::test]
+async fn list_installed_yields_every_installed_mod() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+    let mm = state_dir.open_manager().await;
+
+    let mut ids = mm
+        .list_installed()
+        .map(|mod_info| mod_info.id)
+        .collect::<Vec<_>>();
+    ids.sort();
+
+    assert_eq!(ids, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn list_variants_for_mod_yields_only_variants_of_that_mod() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+    let mm = state_dir.open_manager().await;
+
+    let mut names = mm
+        .list_variants_for_mod(1)
+        .map(|var_info| var_info.name())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    assert_eq!(names, vec!["a.zip", "a_alt.zip"]);
+    assert_eq!(mm.list_variants_for_mod(2).count(), 1);
+    assert_eq!(mm.list_variants_for_mod(3).count(), 0);
+}
+
+#[tokio::test]
+async fn status_lists_mods_in_the_requested_order() {
+    // Inserted out of name order so that sorting by name and by ID differ.
+    let mods = [
+        m_mod(1, "Zelda", "z.zip", false),
+        FixtureMod {
+            id: 2,
+            name: "Mario",
+            variants: vec![
+                FixtureVariant::new("m.zip", true),
+                FixtureVariant::new("m_alt.zip", false),
+            ],
+            last_update_check: None,
+        },
+        m_mod(3, "Link", "l.zip", false),
+    ];
+    let state_dir = FixtureStateDir::new(&mods);
+    let mm = state_dir.open_manager().await;
+
+    let listed_order = |sort| {
+        let status = mm
+            .status(StatusCmdInfo {
+                sort,
+                ..m_status_info(StatusCmdKind::Generic)
+            })
+            .unwrap()
+            .to_string();
+
+        let mut names = ["Zelda", "Mario", "Link"];
+        names.sort_by_key(|name| status.find(name).unwrap());
+        names
+    };
+
+    assert_eq!(
+        listed_order(StatusSortKey::Name),
+        ["Link", "Mario", "Zelda"]
+    );
+    assert_eq!(listed_order(StatusSortKey::Id), ["Zelda", "Mario", "Link"]);
+    assert_eq!(
+        listed_order(StatusSortKey::VariantCount),
+        ["Mario", "Link", "Zelda"]
+    );
+}
+
+/// ModB is unable to be enabled since ModA already changes the same file.
+fn m_conflicting_mods() -> [FixtureMod; 2] {
+    [
+        FixtureMod {
+            id: 1,
+            name: "ModA",
+            variants: vec![FixtureVariant {
+                global_files: &[SHARED_GLOBAL_FILE],
+                ..FixtureVariant::new("a.zip", true)
+            }],
+            last_update_check: None,
+        },
+        FixtureMod {
+            id: 2,
+            name: "ModB",
+            variants: vec![FixtureVariant {
+                global_files: &[SHARED_GLOBAL_FILE],
+                ..FixtureVariant::new("b.zip", false)
+            }],
+            last_update_check: None,
+        },
+    ]
+}
+
+fn conflict_count_line(status: &str) -> Vec<&str> {
+    status
+        .lines()
+        .find(|line| line.starts_with("Number of conflicts:"))
+        .unwrap()
+        .split_whitespace()
+        .collect()
+}
+
+#[tokio::test]
+async fn status_counts_conflicts() {
+    let state_dir = FixtureStateDir::new(&m_conflicting_mods());
+    let read_only_status = ModManagerView::load(state_dir.path(), ModManagerOptions::default())
+        .unwrap()
+        .status(m_status_info(StatusCmdKind::Generic))
+        .unwrap()
+        .to_string();
+    let status = state_dir
+        .open_manager()
+        .await
+        .status(m_status_info(StatusCmdKind::Generic))
+        .unwrap()
+        .to_string();
+
+    for status in [status, read_only_status] {
+        assert_eq!(
+            conflict_count_line(&status),
+            ["Number", "of", "conflicts:", "1"]
+        );
+        assert!(status.contains("Run 'export-conflicts -o <file>' for details"));
+    }
+}
+
+#[tokio::test]
+async fn status_only_mentions_conflict_details_if_there_are_any() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+    let status = state_dir
+        .open_manager()
+        .await
+        .status(m_status_info(StatusCmdKind::Generic))
+        .unwrap()
+        .to_string();
+
+    assert_eq!(
+        conflict_count_line(&status),
+        ["Number", "of", "conflicts:", "0"]
+    );
+    assert!(!status.contains("export-conflicts"));
+}
+
+#[tokio::test]
+async fn status_only_lists_what_matches_the_filter() {
+    let state_dir = FixtureStateDir::new(&m_conflicting_mods());
+    let view = ModManagerView::load(state_dir.path(), ModManagerOptions::default()).unwrap();
+    let mm = state_dir.open_manager().await;
+
+    for filter in ["conflicts,disabled", "name=modb"] {
+        let info = || StatusCmdInfo {
+            filter: filter.parse().unwrap(),
+            ..m_status_info(StatusCmdKind::Generic)
+        };
+
+        for status in [
+            mm.status(info()).unwrap().to_string(),
+            view.status(info()).unwrap().to_string(),
+        ] {
+            assert!(status.contains("b.zip"), "{}", status);
+            assert!(!status.contains("a.zip"), "{}", status);
+        }
+    }
+}
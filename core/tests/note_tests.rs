@@ -0,0 +1,88 @@
+mod common;
+
+use common::{FixtureMod, FixtureStateDir, m_ident, m_mod, m_status_info};
+use ultimate_mod_man_rs_core::{
+    cmds::status::StatusCmdKind,
+    mod_db::{MAX_NOTE_LEN, ModDbError},
+    mod_manager::ModManagerErr,
+};
+
+fn m_mod_a() -> FixtureMod {
+    m_mod(1, "ModA", "a.zip", true)
+}
+
+#[tokio::test]
+async fn notes_persist_and_can_be_cleared() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    state_dir
+        .open_manager()
+        .await
+        .set_note(m_ident("1/a.zip"), Some("  Use with the alt costume\r\n"))
+        .await
+        .unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    assert_eq!(
+        mm.note(m_ident("1/a.zip")).await.unwrap().as_deref(),
+        Some("Use with the alt costume")
+    );
+
+    mm.set_note(m_ident("1/a.zip"), None).await.unwrap();
+    assert_eq!(mm.note(m_ident("1/a.zip")).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn notes_that_are_too_long_are_rejected() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let note = "a".repeat(MAX_NOTE_LEN + 1);
+
+    let res = state_dir
+        .open_manager()
+        .await
+        .set_note(m_ident("1/a.zip"), Some(&note))
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(ModManagerErr::ModDbError(ModDbError::NoteTooLong(_)))
+    ));
+}
+
+#[tokio::test]
+async fn notes_on_missing_variants_are_an_error() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let res = state_dir
+        .open_manager()
+        .await
+        .set_note(m_ident("1/missing.zip"), Some("note"))
+        .await;
+
+    assert!(matches!(res, Err(ModManagerErr::VariantNotInstalled(_))));
+}
+
+#[tokio::test]
+async fn multi_line_notes_are_shown_on_one_line_in_status() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let mut mm = state_dir.open_manager().await;
+    mm.set_note(m_ident("1/a.zip"), Some("line 1\nline \u{1b}[31m2"))
+        .await
+        .unwrap();
+
+    let generic = mm
+        .status(m_status_info(StatusCmdKind::Generic))
+        .unwrap()
+        .to_string();
+    assert!(generic.contains("a.zip (true) [note]"));
+    assert!(!generic.contains("line 1"));
+
+    let specific = mm
+        .status(m_status_info(StatusCmdKind::Specific(vec![m_ident(
+            "1/a.zip",
+        )])))
+        .unwrap()
+        .to_string();
+    assert!(specific.contains("Note: line 1 | line [31m2\n"));
+}
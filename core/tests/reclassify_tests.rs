@@ -0,0 +1,117 @@
+mod common;
+
+use std::fs;
+
+use common::{FixtureMod, FixtureStateDir, m_ident, m_mod};
+use ultimate_mod_man_rs_core::{
+    mod_db::ModDbError,
+    mod_manager::{ModManagerErr, VariantToggleOutcome},
+};
+use ultimate_mod_man_rs_scraper::mod_file_classifier::ModFileAssetAssociation;
+use ultimate_mod_man_rs_utils::types::{CharSkinSlotValue, SkinSlotValue, VariantAndId};
+
+const SKIN_FILE: &str = "fighter/mario/model/body/c03/model.numdlb";
+
+/// Puts a file into the expanded files of the variant that it was not
+/// classified with when it was installed.
+fn write_unclassified_skin_file(state_dir: &FixtureStateDir, mod_info: &FixtureMod) {
+    let path = state_dir
+        .path()
+        .join(mod_info.dir_name())
+        .join("a.zip/expanded")
+        .join(SKIN_FILE);
+
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, b"model").unwrap();
+}
+
+fn m_skin_assoc() -> ModFileAssetAssociation {
+    ModFileAssetAssociation::CharSkinSlot(CharSkinSlotValue::new(
+        "mario".to_string(),
+        SkinSlotValue::new(3),
+    ))
+}
+
+#[tokio::test]
+async fn reclassifying_picks_up_files_that_were_not_classified() {
+    let mod_info = m_mod(1, "ModA", "a.zip", true);
+    let state_dir = FixtureStateDir::new(&[m_mod(1, "ModA", "a.zip", true)]);
+    write_unclassified_skin_file(&state_dir, &mod_info);
+
+    let mut mm = state_dir.open_manager().await;
+    assert!(mm.occupancy(false).slots.is_empty());
+
+    mm.reclassify(m_ident("1/a.zip")).await.unwrap();
+
+    let occupancy = mm.occupancy(false);
+    assert_eq!(occupancy.slots.len(), 1);
+    assert_eq!(occupancy.slots[0].association, m_skin_assoc());
+    assert_eq!(
+        occupancy.slots[0].claimants,
+        vec![VariantAndId::new(1, "a.zip".to_string())]
+    );
+    assert!(state_dir.is_variant_enabled_on_disk(&mod_info, "a.zip"));
+
+    // The new classification is persisted.
+    drop(mm);
+    assert_eq!(
+        state_dir.open_manager().await.occupancy(false).slots.len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn reclassifying_into_a_conflict_disables_the_variant() {
+    let mod_a = m_mod(1, "ModA", "a.zip", true);
+    let mod_b = m_mod(2, "ModB", "a.zip", false);
+    let state_dir = FixtureStateDir::new(&[
+        m_mod(1, "ModA", "a.zip", true),
+        m_mod(2, "ModB", "a.zip", false),
+    ]);
+    write_unclassified_skin_file(&state_dir, &mod_a);
+    write_unclassified_skin_file(&state_dir, &mod_b);
+
+    let mut mm = state_dir.open_manager().await;
+    mm.reclassify(m_ident("1/a.zip")).await.unwrap();
+    let summary = mm.enable_disable([m_ident("2/a.zip")], true).await.unwrap();
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            VariantAndId::new(2, "a.zip".to_string()),
+            VariantToggleOutcome::Enabled
+        )]
+    );
+
+    // Mod B only finds out that it uses the same skin slot as mod A once it is
+    // reclassified.
+    mm.reclassify(m_ident("2/a.zip")).await.unwrap();
+    assert!(!state_dir.is_variant_enabled_on_disk(&mod_b, "a.zip"));
+    assert!(state_dir.is_variant_enabled_on_disk(&mod_a, "a.zip"));
+}
+
+#[tokio::test]
+async fn collected_variants_can_not_be_reclassified() {
+    let mod_info = m_mod(1, "ModA", "a.zip", false);
+    let state_dir = FixtureStateDir::new(&[m_mod(1, "ModA", "a.zip", false)]);
+
+    // As if gc had removed the files of the variant.
+    let mod_info_path = state_dir.mod_info_path(&mod_info);
+    let mod_info_str = fs::read_to_string(&mod_info_path).unwrap().replace(
+        "enabled = false\n",
+        "enabled = false\ndata_collected = true\n",
+    );
+    fs::write(&mod_info_path, mod_info_str).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    let res = mm.reclassify(m_ident("1/a.zip")).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ModManagerErr::ModDbError(ModDbError::VariantDataCollected(key)))
+                if key.id == 1
+        ),
+        "{:?}",
+        res
+    );
+    assert!(!state_dir.is_variant_enabled_on_disk(&mod_info, "a.zip"));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::fs;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use common::{DummyDelegate, FixtureStateDir, m_mod};
+use tempfile::TempDir;
+use ultimate_mod_man_rs_core::{
+    mod_db::ModDbError,
+    mod_manager::{ModManager, ModManagerErr, ModManagerOptions, ModManagerResult},
+};
+
+const MARKER_FILE_NAME: &str = ".umm_state";
+
+async fn open(p: &Utf8Path, init_here: bool) -> ModManagerResult<ModManager<DummyDelegate>> {
+    let options = ModManagerOptions {
+        init_here,
+        ..Default::default()
+    };
+
+    ModManager::new_with_options(p, options, DummyDelegate::default()).await
+}
+
+/// Looks like someone pointed us at their ARCropolis mods folder.
+fn m_foreign_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("Cool Mario/fighter/mario")).unwrap();
+    fs::write(dir.path().join("holiday.jpg"), b"photo").unwrap();
+
+    dir
+}
+
+fn path(dir: &TempDir) -> &Utf8Path {
+    Utf8Path::from_path(dir.path()).unwrap()
+}
+
+fn layout_version(p: &Utf8Path) -> i64 {
+    let marker_str = fs::read_to_string(p.join(MARKER_FILE_NAME)).unwrap();
+    let marker: toml::Value = toml::from_str(&marker_str).unwrap();
+
+    marker["layout_version"].as_integer().unwrap()
+}
+
+#[tokio::test]
+async fn fresh_dir_gets_a_marker() {
+    let dir = TempDir::new().unwrap();
+    let state_dir_path = path(&dir).join("state");
+
+    open(&state_dir_path, false).await.unwrap();
+
+    assert_eq!(layout_version(&state_dir_path), 1);
+}
+
+#[tokio::test]
+async fn marked_dir_is_used() {
+    let dir = TempDir::new().unwrap();
+    let state_dir_path = Utf8PathBuf::from(path(&dir));
+    drop(open(&state_dir_path, false).await.unwrap());
+    fs::write(state_dir_path.join("notes.txt"), b"mine").unwrap();
+
+    assert!(open(&state_dir_path, false).await.is_ok());
+}
+
+#[tokio::test]
+async fn state_dir_from_before_the_marker_is_adopted() {
+    let state_dir = FixtureStateDir::new(&[m_mod(1, "ModA", "a.zip", true)]);
+
+    let mm = open(state_dir.path(), false).await.unwrap();
+
+    assert_eq!(mm.list_installed().count(), 1);
+    assert_eq!(layout_version(state_dir.path()), 1);
+}
+
+#[tokio::test]
+async fn foreign_dir_is_refused_without_touching_it() {
+    let dir = m_foreign_dir();
+
+    let res = open(path(&dir), false).await;
+
+    assert!(
+        matches!(
+            res,
+            Err(ModManagerErr::ModDbError(ModDbError::StateDirError(_)))
+        ),
+        "{:?}",
+        res.err()
+    );
+    assert!(!path(&dir).join(MARKER_FILE_NAME).exists());
+    assert!(!path(&dir).join(".lockfile").exists());
+}
+
+#[tokio::test]
+async fn foreign_dir_is_adopted_when_forced() {
+    let dir = m_foreign_dir();
+
+    open(path(&dir), true).await.unwrap();
+
+    assert_eq!(layout_version(path(&dir)), 1);
+    assert!(path(&dir).join("holiday.jpg").exists());
+    assert!(path(&dir).join("Cool Mario/fighter/mario").exists());
+
+    // Once adopted, it is a state directory like any other.
+    assert!(open(path(&dir), false).await.is_ok());
+}
@@ -0,0 +1,331 @@
+mod common;
+
+use std::{collections::BTreeMap, fs};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use common::{FixtureMod, FixtureStateDir, FixtureVariant, m_ident, m_mod};
+use tempfile::TempDir;
+use ultimate_mod_man_rs_core::{
+    cmds::sync::DestLayout,
+    sync_targets::{SyncTarget, SyncTargetType},
+};
+use ultimate_mod_man_rs_utils::types::{
+    CharSkinSlotValue, SkinSlotValue, SwappableAssetSlot, VariantAndId,
+};
+
+const TARGET_NAME: &str = "sd_card";
+
+#[tokio::test]
+async fn install_missing_only_copies_variants_missing_from_the_target() {
+    let mods = [
+        m_mod(1, "Kept", "k.zip", true),
+        m_mod(2, "Missing", "m.zip", true),
+    ];
+    let state_dir = FixtureStateDir::new(&mods);
+
+    for mod_info in mods.iter() {
+        let variant_dir = state_dir
+            .path()
+            .join(mod_info.dir_name())
+            .join(mod_info.variants[0].name)
+            .join("expanded");
+        fs::write(variant_dir.join("file.bin"), mod_info.name).unwrap();
+    }
+
+    let target_dir = TempDir::new().unwrap();
+    let target_path = Utf8Path::from_path(target_dir.path()).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    let target = SyncTarget::new(
+        SyncTargetType::Local,
+        target_path.to_path_buf(),
+        BTreeMap::new(),
+        vec![],
+    )
+    .unwrap();
+    mm.add_sync_target(TARGET_NAME.to_string(), target, true)
+        .unwrap();
+    mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    let kept_file = target_path.join("Kept_1_k.zip/file.bin");
+    let missing_dir = target_path.join("Missing_2_m.zip");
+    let unknown_dir = target_path.join("SomeOtherMod");
+
+    // Something on the target that a full sync would overwrite or remove.
+    fs::write(&kept_file, "Edited on the device").unwrap();
+    fs::create_dir(&unknown_dir).unwrap();
+    fs::remove_dir_all(&missing_dir).unwrap();
+
+    let report = mm.switch_compare(Some(TARGET_NAME), true).unwrap();
+
+    assert_eq!(
+        report.not_synced(),
+        [VariantAndId::new(2, "m.zip".to_string())]
+    );
+    assert_eq!(report.installed_missing().unwrap().num_copied, 1);
+
+    assert_eq!(
+        fs::read_to_string(missing_dir.join("file.bin")).unwrap(),
+        "Missing"
+    );
+    assert_eq!(
+        fs::read_to_string(&kept_file).unwrap(),
+        "Edited on the device"
+    );
+    assert!(unknown_dir.exists());
+}
+
+/// Every file under `root` along with its contents.
+fn snapshot_dir(root: &Utf8Path) -> BTreeMap<Utf8PathBuf, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    let mut dirs_to_visit = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs_to_visit.pop() {
+        for entry in dir.read_dir_utf8().unwrap() {
+            let entry = entry.unwrap();
+            match entry.file_type().unwrap().is_dir() {
+                false => {
+                    files.insert(
+                        entry.path().strip_prefix(root).unwrap().to_path_buf(),
+                        fs::read(entry.path()).unwrap(),
+                    );
+                },
+                true => dirs_to_visit.push(entry.path().to_path_buf()),
+            }
+        }
+    }
+
+    files
+}
+
+#[tokio::test]
+async fn dry_run_lists_planned_operations_without_touching_the_target() {
+    let mods = [m_mod(1, "Kept", "k.zip", true)];
+    let state_dir = FixtureStateDir::new(&mods);
+    let variant_dir = state_dir
+        .path()
+        .join(mods[0].dir_name())
+        .join(mods[0].variants[0].name)
+        .join("expanded");
+    fs::write(variant_dir.join("changed.bin"), "old").unwrap();
+    fs::write(variant_dir.join("removed.bin"), "gone soon").unwrap();
+    fs::write(variant_dir.join("same.bin"), "same").unwrap();
+
+    let target_dir = TempDir::new().unwrap();
+    let target_path = Utf8Path::from_path(target_dir.path()).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    let target = SyncTarget::new(
+        SyncTargetType::Local,
+        target_path.to_path_buf(),
+        BTreeMap::new(),
+        vec![],
+    )
+    .unwrap();
+    mm.add_sync_target(TARGET_NAME.to_string(), target, true)
+        .unwrap();
+    mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    fs::write(variant_dir.join("changed.bin"), "newer").unwrap();
+    fs::remove_file(variant_dir.join("removed.bin")).unwrap();
+    fs::write(variant_dir.join("added.bin"), "new file").unwrap();
+    let before = snapshot_dir(target_path);
+
+    let plan = mm
+        .plan_sync_with_switch(None, DestLayout::Arcropolis)
+        .unwrap();
+
+    assert_eq!(snapshot_dir(target_path), before);
+    assert_eq!(
+        plan.copies
+            .iter()
+            .map(|copy| (copy.dest.as_str(), copy.size_bytes))
+            .collect::<Vec<_>>(),
+        [
+            ("Kept_1_k.zip/added.bin", 8),
+            ("Kept_1_k.zip/changed.bin", 5)
+        ]
+    );
+    assert_eq!(
+        plan.removals
+            .iter()
+            .map(|removal| (removal.path.as_str(), removal.size_bytes, removal.is_dir))
+            .collect::<Vec<_>>(),
+        [("Kept_1_k.zip/removed.bin", 9, false)]
+    );
+
+    // A real sync does exactly what was planned.
+    mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+    assert_eq!(
+        snapshot_dir(target_path).keys().collect::<Vec<_>>(),
+        [
+            "Kept_1_k.zip/added.bin",
+            "Kept_1_k.zip/changed.bin",
+            "Kept_1_k.zip/same.bin"
+        ]
+    );
+}
+
+fn m_local_target(target_path: &Utf8Path, max_concurrent_copies: &str) -> SyncTarget {
+    SyncTarget::new(
+        SyncTargetType::Local,
+        target_path.to_path_buf(),
+        BTreeMap::from([(
+            "max_concurrent_copies".to_string(),
+            max_concurrent_copies.to_string(),
+        )]),
+        vec![],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn concurrent_sync_copies_every_file_of_a_large_tree() {
+    let mods = [m_mod(1, "Big", "b.zip", true)];
+    let state_dir = FixtureStateDir::new(&mods);
+    let variant_dir = state_dir
+        .path()
+        .join(mods[0].dir_name())
+        .join(mods[0].variants[0].name)
+        .join("expanded");
+
+    let mut expected = BTreeMap::new();
+    for i in 0..300 {
+        let rel_path = format!("ui/replace/chara/chara_{}/file_{i}.bntx", i % 7);
+        fs::create_dir_all(variant_dir.join(&rel_path).parent().unwrap()).unwrap();
+        fs::write(variant_dir.join(&rel_path), i.to_string()).unwrap();
+        expected.insert(
+            Utf8PathBuf::from(format!("Big_1_b.zip/{rel_path}")),
+            i.to_string().into_bytes(),
+        );
+    }
+
+    let target_dir = TempDir::new().unwrap();
+    let target_path = Utf8Path::from_path(target_dir.path()).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    mm.add_sync_target(
+        TARGET_NAME.to_string(),
+        m_local_target(target_path, "8"),
+        true,
+    )
+    .unwrap();
+    let outcome = mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    assert_eq!(outcome.num_copied, 300);
+    assert!(outcome.failed.is_empty(), "{:?}", outcome.failed);
+    assert_eq!(snapshot_dir(target_path), expected);
+}
+
+#[tokio::test]
+async fn moved_slots_are_synced_to_the_slot_they_were_moved_to() {
+    const MODEL_FILE: &str = "Mario/fighter/mario/model/body/c02/model.numdlb";
+
+    let mods = [FixtureMod {
+        id: 1,
+        name: "ModA",
+        variants: vec![FixtureVariant {
+            skin_slots: &[("mario", 2)],
+            ..FixtureVariant::new("a.zip", true)
+        }],
+        last_update_check: None,
+    }];
+    let state_dir = FixtureStateDir::new(&mods);
+    let model_path = state_dir
+        .path()
+        .join(mods[0].dir_name())
+        .join("a.zip/expanded")
+        .join(MODEL_FILE);
+    fs::create_dir_all(model_path.parent().unwrap()).unwrap();
+    fs::write(&model_path, "model").unwrap();
+
+    let target_dir = TempDir::new().unwrap();
+    let target_path = Utf8Path::from_path(target_dir.path()).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    // The first open slot is picked, which is `c00`.
+    mm.change_slot(
+        m_ident("1/a.zip"),
+        SwappableAssetSlot::CharacterSkin(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(2),
+        )),
+    )
+    .await
+    .unwrap();
+    mm.add_sync_target(
+        TARGET_NAME.to_string(),
+        m_local_target(target_path, "1"),
+        true,
+    )
+    .unwrap();
+    mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    assert_eq!(
+        snapshot_dir(target_path),
+        BTreeMap::from([(
+            Utf8PathBuf::from("ModA_1_a.zip/Mario/fighter/mario/model/body/c00/model.numdlb"),
+            b"model".to_vec()
+        )])
+    );
+}
+
+#[tokio::test]
+async fn files_that_fail_to_copy_do_not_stop_the_sync() {
+    let mods = [m_mod(1, "Kept", "k.zip", true)];
+    let state_dir = FixtureStateDir::new(&mods);
+    let variant_dir = state_dir
+        .path()
+        .join(mods[0].dir_name())
+        .join(mods[0].variants[0].name)
+        .join("expanded");
+    for rel_path in ["a.bin", "blocked.bin", "sub/c.bin", "sub/d.bin"] {
+        fs::create_dir_all(variant_dir.join(rel_path).parent().unwrap()).unwrap();
+        fs::write(variant_dir.join(rel_path), "old").unwrap();
+    }
+
+    let target_dir = TempDir::new().unwrap();
+    let target_path = Utf8Path::from_path(target_dir.path()).unwrap();
+
+    let mut mm = state_dir.open_manager().await;
+    mm.add_sync_target(
+        TARGET_NAME.to_string(),
+        m_local_target(target_path, "2"),
+        true,
+    )
+    .unwrap();
+    mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    for rel_path in ["a.bin", "blocked.bin", "sub/c.bin", "sub/d.bin"] {
+        fs::write(variant_dir.join(rel_path), "newer").unwrap();
+    }
+
+    // Permissions do not stop root, so something is put in the way instead. A
+    // directory where a file goes fails the copy itself, and a file where a
+    // directory goes fails creating the directory.
+    let variant_target_path = target_path.join("Kept_1_k.zip");
+    fs::remove_file(variant_target_path.join("blocked.bin")).unwrap();
+    fs::create_dir(variant_target_path.join("blocked.bin")).unwrap();
+    fs::remove_dir_all(variant_target_path.join("sub")).unwrap();
+    fs::write(variant_target_path.join("sub"), "in the way").unwrap();
+
+    let outcome = mm.sync_with_switch(None, DestLayout::Arcropolis).unwrap();
+
+    assert_eq!(outcome.num_copied, 1);
+    assert_eq!(
+        outcome
+            .failed
+            .iter()
+            .map(|failed| failed.dest.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "Kept_1_k.zip/blocked.bin",
+            "Kept_1_k.zip/sub/c.bin",
+            "Kept_1_k.zip/sub/d.bin"
+        ]
+    );
+    assert_eq!(
+        fs::read_to_string(variant_target_path.join("a.bin")).unwrap(),
+        "newer"
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::fs;
+
+use common::{FixtureMod, FixtureStateDir, m_mod, m_status_info};
+use ultimate_mod_man_rs_core::{
+    cmds::status::{StatusCmdInfo, StatusCmdKind},
+    mod_manager::VariantToggleOutcome,
+};
+use ultimate_mod_man_rs_utils::types::{ModIdentifier, VariantAndId};
+
+fn m_mods() -> [FixtureMod; 2] {
+    [
+        m_mod(1, "ModA", "a.zip", false),
+        m_mod(2, "ModB", "b.zip", false),
+    ]
+}
+
+fn m_tagged_status_info(kind: StatusCmdKind, tag: &str) -> StatusCmdInfo {
+    StatusCmdInfo {
+        tag: Some(tag.to_string()),
+        ..m_status_info(kind)
+    }
+}
+
+#[tokio::test]
+async fn tags_are_normalized_and_persisted() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+
+    let added = state_dir
+        .open_manager()
+        .await
+        .add_tags(
+            &ModIdentifier::Name("ModA".to_string()),
+            &[" Tournament ".to_string(), "tournament".to_string()],
+        )
+        .unwrap();
+    assert_eq!(added, vec!["tournament".to_string()]);
+
+    let mm = state_dir.open_manager().await;
+    assert_eq!(
+        mm.tags(&ModIdentifier::Id(1)).unwrap(),
+        &["tournament".to_string()]
+    );
+    assert!(mm.tags(&ModIdentifier::Id(2)).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn removing_tags_matches_case_insensitively() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+    let ident = ModIdentifier::Id(1);
+
+    let mut mm = state_dir.open_manager().await;
+    mm.add_tags(&ident, &["favorite".to_string(), "casual".to_string()])
+        .unwrap();
+
+    let removed = mm
+        .remove_tags(&ident, &["FAVORITE".to_string(), "missing".to_string()])
+        .unwrap();
+    assert_eq!(removed, vec!["favorite".to_string()]);
+    assert_eq!(mm.tags(&ident).unwrap(), &["casual".to_string()]);
+}
+
+#[tokio::test]
+async fn old_mod_info_without_tags_still_loads() {
+    let mods = m_mods();
+    let state_dir = FixtureStateDir::new(&mods);
+
+    let mod_info = fs::read_to_string(state_dir.mod_info_path(&mods[0])).unwrap();
+    assert!(!mod_info.contains("tags"));
+
+    assert!(
+        state_dir
+            .open_manager()
+            .await
+            .tags(&ModIdentifier::Id(1))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn status_only_includes_tagged_mods() {
+    let state_dir = FixtureStateDir::new(&m_mods());
+
+    let mut mm = state_dir.open_manager().await;
+    mm.add_tags(&ModIdentifier::Id(2), &["favorite".to_string()])
+        .unwrap();
+
+    let generic = mm
+        .status(m_tagged_status_info(StatusCmdKind::Generic, "Favorite"))
+        .unwrap()
+        .to_string();
+    assert!(generic.contains("ModB [tags: favorite]"));
+    assert!(!generic.contains("ModA"));
+
+    let specific = mm
+        .status(m_tagged_status_info(
+            StatusCmdKind::Specific(vec!["2/b.zip".parse().unwrap()]),
+            "favorite",
+        ))
+        .unwrap()
+        .to_string();
+    assert!(specific.contains("Tags: favorite"));
+}
+
+#[tokio::test]
+async fn enabling_by_tag_toggles_every_tagged_mod() {
+    let mods = m_mods();
+    let state_dir = FixtureStateDir::new(&mods);
+
+    let mut mm = state_dir.open_manager().await;
+    mm.add_tags(&ModIdentifier::Id(1), &["tournament".to_string()])
+        .unwrap();
+
+    let summary = mm.enable_disable_tagged("TOURNAMENT", true).unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            VariantAndId::new(1, "a.zip".to_string()),
+            VariantToggleOutcome::Enabled
+        )]
+    );
+    assert!(state_dir.is_variant_enabled_on_disk(&mods[0], "a.zip"));
+    assert!(!state_dir.is_variant_enabled_on_disk(&mods[1], "b.zip"));
+}
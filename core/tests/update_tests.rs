@@ -0,0 +1,378 @@
+mod common;
+
+use std::fs;
+
+use chrono::{DateTime, Utc};
+use common::{
+    FixtureMod, FixtureStateDir,
+    fixture_archives::{ArchiveKind, FixtureArchive},
+    fixture_host::{FixtureHost, HostedFile, HostedMod},
+    m_mod,
+};
+use ultimate_mod_man_rs_core::{
+    cmds::update_check::UpdateCheckEntry,
+    mod_db::ModDbError,
+    mod_manager::{ModManagerErr, UpdateSummary, VariantToggleOutcome, VariantUpdateOutcome},
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::UpstreamFileInfo;
+use ultimate_mod_man_rs_utils::types::VariantAndId;
+
+fn m_mod_a() -> FixtureMod {
+    m_mod(1, "ModA", "a.zip", true)
+}
+
+#[tokio::test]
+async fn interrupted_update_restores_previous_version() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let variant_dir = state_dir.path().join(mod_a.dir_name()).join("a.zip");
+
+    // Simulate being interrupted after the installed version was moved out of the
+    // way but before the new version was moved in.
+    fs::rename(variant_dir.join("expanded"), variant_dir.join("previous")).unwrap();
+    fs::create_dir(variant_dir.join("incoming")).unwrap();
+    fs::write(variant_dir.join("a.zip"), b"partial download").unwrap();
+
+    let quarantine_dir = state_dir.path().join("quarantine/1_a.zip");
+    fs::create_dir_all(&quarantine_dir).unwrap();
+    fs::write(quarantine_dir.join("a.zip"), b"partial download").unwrap();
+
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Update]\nkey = {{ id = 1, variant_name = \"a.zip\" }}\nquarantine_dir = \
+             {:?}\n",
+            quarantine_dir
+        ),
+    )
+    .unwrap();
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable(["1/a.zip".parse().unwrap()], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            VariantAndId::new(1, "a.zip".to_string()),
+            VariantToggleOutcome::AlreadyEnabled
+        )]
+    );
+
+    assert!(variant_dir.join("expanded").exists());
+    assert!(!variant_dir.join("previous").exists());
+    assert!(!variant_dir.join("incoming").exists());
+    assert!(!variant_dir.join("a.zip").exists());
+    assert!(!quarantine_dir.exists());
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+}
+
+#[tokio::test]
+async fn interrupted_update_restores_previous_version_after_the_new_one_was_moved_in() {
+    let mod_a = m_mod_a();
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+    let variant_dir = state_dir.path().join(mod_a.dir_name()).join("a.zip");
+
+    // Simulate being interrupted after both directories were swapped but before
+    // `mod_info.toml` was written.
+    fs::write(variant_dir.join("expanded/old.txt"), b"old").unwrap();
+    fs::rename(variant_dir.join("expanded"), variant_dir.join("previous")).unwrap();
+    fs::create_dir(variant_dir.join("expanded")).unwrap();
+    fs::write(variant_dir.join("expanded/new.txt"), b"new").unwrap();
+
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Update]\nkey = {{ id = 1, variant_name = \"a.zip\" }}\nquarantine_dir = \
+             {:?}\n",
+            state_dir.path().join("quarantine/1_a.zip")
+        ),
+    )
+    .unwrap();
+
+    state_dir
+        .open_manager()
+        .await
+        .enable_disable(["1/a.zip".parse().unwrap()], true)
+        .await
+        .unwrap();
+
+    assert!(variant_dir.join("expanded/old.txt").exists());
+    assert!(!variant_dir.join("expanded/new.txt").exists());
+    assert!(!variant_dir.join("previous").exists());
+    assert!(!variant_dir.join("incoming").exists());
+    assert!(!state_dir.path().join("in_prog_action.toml").exists());
+}
+
+#[tokio::test]
+async fn rolling_back_a_mod_that_is_not_installed_is_an_error() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let res = state_dir
+        .open_manager()
+        .await
+        .rollback_update("2/a.zip".parse().unwrap())
+        .await;
+
+    assert!(
+        matches!(
+            res,
+            Err(ModManagerErr::ModDbError(ModDbError::UnknownMod(2)))
+        ),
+        "{:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn rolling_back_a_variant_that_is_not_installed_is_an_error() {
+    let state_dir = FixtureStateDir::new(&[m_mod_a()]);
+
+    let res = state_dir
+        .open_manager()
+        .await
+        .rollback_update("1/b.zip".parse().unwrap())
+        .await;
+
+    match res {
+        Err(ModManagerErr::ModDbError(ModDbError::UnknownVariant(key))) => {
+            assert_eq!(key, VariantAndId::new(1, "b.zip".to_string()))
+        },
+        res => panic!("Expected an unknown variant error but got {:?}", res),
+    }
+}
+
+fn m_update_entry(variant_name: &str) -> UpdateCheckEntry {
+    UpdateCheckEntry {
+        key: VariantAndId::new(1, variant_name.to_string()),
+        mod_name: "ModA".to_string(),
+        installed_publish_date: None,
+        upstream_publish_date: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        installed_version: None,
+        upstream_version: None,
+        upstream_file: UpstreamFileInfo::default(),
+        successor_file_name: None,
+    }
+}
+
+#[test]
+fn update_summary_counts_outcomes_and_points_at_resolve_conflicts() {
+    let summary = UpdateSummary {
+        outcomes: vec![
+            (m_update_entry("a.zip"), VariantUpdateOutcome::Updated),
+            (
+                m_update_entry("c.zip"),
+                VariantUpdateOutcome::UpdatedButDisabled(vec![VariantAndId::new(
+                    2,
+                    "other.zip".to_string(),
+                )]),
+            ),
+            (
+                m_update_entry("d.zip"),
+                VariantUpdateOutcome::Failed("timed out".to_string()),
+            ),
+        ],
+    };
+
+    let summary_str = summary.to_string();
+
+    assert!(summary_str.contains("Updated: 1, newly conflicting: 1, failed: 1"));
+    assert!(summary_str.contains("resolve-conflicts"));
+}
+
+#[tokio::test]
+async fn recently_checked_mods_are_not_checked_again_unless_forced() {
+    let state_dir = FixtureStateDir::new(&[FixtureMod {
+        last_update_check: Some(Utc::now()),
+        ..m_mod_a()
+    }]);
+
+    // Nothing listens here, so any mod that actually gets checked is unreachable.
+    let mut manager = state_dir.open_manager_with_root("http://127.0.0.1:1").await;
+
+    let report = manager.check_for_updates(false, &[], false).await.unwrap();
+    assert_eq!(report.recently_checked.len(), 1);
+    assert!(report.unreachable.is_empty());
+
+    let report = manager.check_for_updates(false, &[], true).await.unwrap();
+    assert!(report.recently_checked.is_empty());
+    assert_eq!(report.unreachable.len(), 1);
+}
+
+#[tokio::test]
+async fn adopting_a_renamed_file_only_renames_the_variant_once_it_is_updated() {
+    let m_mod = || m_mod(1, "ModA", "mario_skin_v1.zip", false);
+    let mod_a = m_mod();
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 1,
+        name: "ModA",
+        files: vec![HostedFile {
+            name: "mario_skin_v2.zip",
+            payload: FixtureArchive::mario_skin("ModA", 3).build(ArchiveKind::Zip),
+        }],
+    }])
+    .await;
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let summary = mm.update(None::<Vec<_>>, true, false).await.unwrap();
+    let (entry, outcome) = &summary.outcomes[0];
+    assert_eq!(
+        entry.key,
+        VariantAndId::new(1, "mario_skin_v1.zip".to_string())
+    );
+    assert_eq!(
+        entry.successor_file_name.as_deref(),
+        Some("mario_skin_v2.zip")
+    );
+    assert_eq!(*outcome, VariantUpdateOutcome::WouldUpdate);
+    assert!(
+        state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("mario_skin_v1.zip")
+            .exists()
+    );
+
+    let summary = mm.update(None::<Vec<_>>, false, false).await.unwrap();
+    assert_eq!(summary.outcomes[0].1, VariantUpdateOutcome::Updated);
+    assert!(
+        state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("mario_skin_v2.zip/expanded")
+            .exists()
+    );
+    assert!(
+        !state_dir
+            .path()
+            .join(mod_a.dir_name())
+            .join("mario_skin_v1.zip")
+            .exists()
+    );
+}
+
+#[tokio::test]
+async fn failing_to_adopt_a_renamed_file_keeps_the_old_name() {
+    let m_mod = || m_mod(1, "ModA", "mario_skin_v1.zip", false);
+    let mod_a = m_mod();
+    let state_dir = FixtureStateDir::new(&[m_mod()]);
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 1,
+        name: "ModA",
+        files: vec![HostedFile {
+            name: "mario_skin_v2.zip",
+            payload: b"not an archive".to_vec(),
+        }],
+    }])
+    .await;
+    let mut mm = state_dir.open_manager_with_root(host.root()).await;
+
+    let summary = mm.update(None::<Vec<_>>, false, false).await.unwrap();
+    assert!(matches!(
+        summary.outcomes[0].1,
+        VariantUpdateOutcome::Failed(_)
+    ));
+
+    let mod_dir = state_dir.path().join(mod_a.dir_name());
+    assert!(mod_dir.join("mario_skin_v1.zip/expanded").exists());
+    assert!(!mod_dir.join("mario_skin_v2.zip").exists());
+    assert_eq!(
+        mm.list_variants_for_mod(1)
+            .map(|var_info| var_info.name())
+            .collect::<Vec<_>>(),
+        vec!["mario_skin_v1.zip"]
+    );
+}
+
+#[tokio::test]
+async fn interrupted_adoption_of_a_renamed_file_restores_the_old_name() {
+    let mod_a = m_mod(1, "ModA", "mario_skin_v2.zip", true);
+    let state_dir = FixtureStateDir::new(&[m_mod(1, "ModA", "mario_skin_v2.zip", true)]);
+
+    // Simulate being interrupted after the variant was renamed to the file that it
+    // was being updated to.
+    fs::write(
+        state_dir.path().join("in_prog_action.toml"),
+        format!(
+            "[in_prog.Update]\nkey = {{ id = 1, variant_name = \"mario_skin_v1.zip\" \
+             }}\nquarantine_dir = {:?}\nsuccessor_name = \"mario_skin_v2.zip\"\n",
+            state_dir.path().join("quarantine/1_mario_skin_v2.zip")
+        ),
+    )
+    .unwrap();
+
+    let summary = state_dir
+        .open_manager()
+        .await
+        .enable_disable(["1/mario_skin_v1.zip".parse().unwrap()], true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary.outcomes,
+        vec![(
+            VariantAndId::new(1, "mario_skin_v1.zip".to_string()),
+            VariantToggleOutcome::AlreadyEnabled
+        )]
+    );
+
+    let mod_dir = state_dir.path().join(mod_a.dir_name());
+    assert!(mod_dir.join("mario_skin_v1.zip/expanded").exists());
+    assert!(!mod_dir.join("mario_skin_v2.zip").exists());
+}
+
+#[tokio::test]
+async fn failing_to_auto_update_one_variant_still_updates_the_others() {
+    let mod_b = m_mod(2, "ModB", "skin.zip", false);
+    let state_dir = FixtureStateDir::new(&[
+        m_mod(1, "ModA", "skin.zip", false),
+        m_mod(2, "ModB", "skin.zip", false),
+    ]);
+    let host = FixtureHost::start(vec![
+        HostedMod {
+            id: 1,
+            name: "ModA",
+            files: vec![HostedFile {
+                name: "skin.zip",
+                payload: b"not an archive".to_vec(),
+            }],
+        },
+        HostedMod {
+            id: 2,
+            name: "ModB",
+            files: vec![HostedFile {
+                name: "skin.zip",
+                payload: FixtureArchive::mario_skin("ModB", 3).build(ArchiveKind::Zip),
+            }],
+        },
+    ])
+    .await;
+
+    let report = state_dir
+        .open_manager_with_root(host.root())
+        .await
+        .check_for_updates(true, &[], true)
+        .await
+        .unwrap();
+
+    assert_eq!(report.failed_updates.len(), 1);
+    assert_eq!(
+        report.failed_updates[0].key,
+        VariantAndId::new(1, "skin.zip".to_string())
+    );
+    assert!(
+        fs::read_dir(
+            state_dir
+                .path()
+                .join(mod_b.dir_name())
+                .join("skin.zip/expanded")
+        )
+        .unwrap()
+        .next()
+        .is_some()
+    );
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+
+use common::{
+    FixtureStateDir,
+    fixture_host::{FixtureHost, HostedMod},
+    m_mod,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::BananaScraperError;
+use ultimate_mod_man_rs_utils::types::ModIdentifier;
+
+#[tokio::test]
+async fn warming_the_cache_persists_resolved_names_and_reports_the_rest() {
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 42,
+        name: "Cool Mario",
+        files: vec![],
+    }])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+
+    let report = state_dir
+        .open_manager_with_root(host.root())
+        .await
+        .warm_name_cache(["Cool Mario".to_string(), "Missing Mod".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(report.resolved, vec![("Cool Mario".to_string(), 42)]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "Missing Mod");
+    assert!(matches!(
+        report.failed[0].1,
+        BananaScraperError::ModNameNotFound(_)
+    ));
+
+    let cache_str =
+        fs::read_to_string(state_dir.path().join("mod_name_resolution_cache.toml")).unwrap();
+    let cache: toml::Value = toml::from_str(&cache_str).unwrap();
+    assert_eq!(cache["names"]["Cool Mario"].as_integer(), Some(42));
+    assert!(cache["names"].get("Missing Mod").is_none());
+
+    // Nothing is listening at this root, so this can only come from the cache.
+    let id = state_dir
+        .open_manager_with_root("http://127.0.0.1:9")
+        .await
+        .resolve_mod_id(&ModIdentifier::Name("Cool Mario".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(id, 42);
+}
+
+#[tokio::test]
+async fn corrupted_cache_is_replaced_instead_of_failing() {
+    let host = FixtureHost::start(vec![HostedMod {
+        id: 42,
+        name: "Cool Mario",
+        files: vec![],
+    }])
+    .await;
+    let state_dir = FixtureStateDir::new(&[]);
+    let cache_path = state_dir.path().join("mod_name_resolution_cache.toml");
+    fs::write(&cache_path, "[names\n\"Cool Mario\" = ").unwrap();
+
+    let id = state_dir
+        .open_manager_with_root(host.root())
+        .await
+        .resolve_mod_id(&ModIdentifier::Name("Cool Mario".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(id, 42);
+
+    let cache: toml::Value = toml::from_str(&fs::read_to_string(&cache_path).unwrap()).unwrap();
+    assert_eq!(cache["names"]["Cool Mario"].as_integer(), Some(42));
+}
+
+#[tokio::test]
+async fn pruning_the_cache_removes_names_of_mods_that_are_not_installed() {
+    let installed =
+        [(1, "Mod1"), (2, "Mod2"), (3, "Mod3")].map(|(id, name)| m_mod(id, name, "a.zip", false));
+    let state_dir = FixtureStateDir::new(&installed);
+    let cache_path = state_dir.path().join("mod_name_resolution_cache.toml");
+    fs::write(
+        &cache_path,
+        "schema_version = 2\n\n[names]\nMod1 = 1\nMod2 = 2\nMod3 = 3\n\"Deleted A\" = \
+         4\n\"Deleted B\" = 5\n",
+    )
+    .unwrap();
+
+    let num_removed = state_dir
+        .open_manager()
+        .await
+        .prune_name_resolver_cache()
+        .unwrap();
+    assert_eq!(num_removed, 2);
+
+    let cache: toml::Value = toml::from_str(&fs::read_to_string(&cache_path).unwrap()).unwrap();
+    let mut names = cache["names"]
+        .as_table()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["Mod1", "Mod2", "Mod3"]);
+}
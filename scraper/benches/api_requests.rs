@@ -0,0 +1,84 @@
+//! Measures how long a run of sequential API requests takes when the server
+//! keeps connections alive (so the client can reuse them) compared to when it
+//! closes the connection after every response.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Builder,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::BananaClient;
+
+const NUM_REQUESTS: usize = 10;
+
+const MOD_NAME: &str = "Mario";
+const SEARCH_RESP: &str =
+    r#"{"_aMetadata":{"_nRecordCount":1},"_aRecords":[{"_idRow":1,"_sName":"Mario"}]}"#;
+
+/// Responds to every request on the connection with a search result until
+/// either side closes it.
+async fn serve_conn(mut conn: TcpStream, keep_alive: bool) {
+    let mut req = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+            match conn.read(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => req.extend_from_slice(&buf[..n]),
+            }
+        }
+        req.clear();
+
+        // Sent in one write so that a small body is not held back waiting on
+        // the ACK of the header.
+        let resp = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: \
+             {}\r\nConnection: {}\r\n\r\n{}",
+            SEARCH_RESP.len(),
+            if keep_alive { "keep-alive" } else { "close" },
+            SEARCH_RESP
+        );
+        if conn.write_all(resp.as_bytes()).await.is_err() || !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Returns the root URL of the server.
+async fn start_server(keep_alive: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let root = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        while let Ok((conn, _)) = listener.accept().await {
+            tokio::spawn(serve_conn(conn, keep_alive));
+        }
+    });
+
+    root
+}
+
+fn sequential_requests(c: &mut Criterion) {
+    let rt = Builder::new_current_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("sequential_requests");
+
+    for (name, keep_alive) in [("with_keep_alive", true), ("without_keep_alive", false)] {
+        let client = rt
+            .block_on(async { BananaClient::with_root(&start_server(keep_alive).await).unwrap() });
+
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| async {
+                for _ in 0..NUM_REQUESTS {
+                    client.resolve_mod_name(MOD_NAME).await.unwrap();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, sequential_requests);
+criterion_main!(benches);
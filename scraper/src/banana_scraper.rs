@@ -1,8 +1,21 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use float_ord::FloatOrd;
 use log::{debug, warn};
-use reqwest::{Client, ClientBuilder};
-use serde::Deserialize;
+use reqwest::{
+    Client, ClientBuilder, IntoUrl, Response, StatusCode,
+    header::{CONTENT_TYPE, HeaderMap, RETRY_AFTER},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use thiserror::Error;
 use ultimate_mod_man_rs_utils::{
     types::{ModId, VariantAndId},
@@ -18,9 +31,49 @@ pub type BananaScraperResult<T> = Result<T, BananaScraperError>;
 
 static BANANA_ROOT: &str = "https://gamebanana.com";
 
+/// Sent with every request so that GameBanana can tell who we are (generic
+/// user agents are more likely to get rate limited or blocked).
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "ultimate-mod-man-rs/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/BGluth/ultimate-mod-man-rs)"
+);
+
+/// The ID of Super Smash Bros. Ultimate on GameBanana.
+pub const SSBU_GAME_ID: u64 = 6498;
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// How long an idle connection is probed to keep it from being dropped.
+/// Commands like `add` make many requests in a row, and reusing one connection
+/// saves a new handshake for every one of them.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// How many idle connections to GameBanana are kept around for reuse.
+const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 5;
+
+/// How long to wait before the first retry. Doubles with every retry after
+/// that.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest that we are willing to wait when GameBanana asks us to slow
+/// down. Anything longer is reported to the user instead of leaving them
+/// staring at a command that looks stuck.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
 /// I think this is hard coded into the web interface and can't be changed?
 const NUM_SEARCH_RESULTS_PER_PAGE: usize = 15;
 
+/// How similar (`0.0` - `1.0`) a file name needs to be to an installed file
+/// name that no longer exists before we consider it to be a renamed version of
+/// it.
+const MIN_SUCCESSOR_FILE_MATCH_SCORE: f32 = 0.4;
+
+/// How much of the body of a failed (or unexpected) response is kept for the
+/// error message.
+const MAX_HTTP_ERROR_BODY_LEN: usize = 256;
+
 #[derive(Debug, Error)]
 pub enum BananaScraperError {
     #[error(
@@ -32,17 +85,221 @@ pub enum BananaScraperError {
     #[error("The mod variant {0} was not found for the mod {1}.")]
     ModVariantDoesNotFound(String, String),
 
+    #[error(
+        "The mod {mod_name} has no file named exactly \"{variant_name}\" (file names are case \
+         sensitive). Available files: {}",
+        .available.join(", ")
+    )]
+    NoExactVariantMatch {
+        variant_name: String,
+        mod_name: String,
+        available: Vec<String>,
+    },
+
     #[error(
         "Got a different MD5 checksum for the artifact {0} of the mod {1}: (Expected: {2}, Ours: \
          {3}). This file has likely been tampered with!"
     )]
     VariantMd5CheckSumMismatch(String, String, String, String),
 
+    #[error(
+        "Got a different size for the artifact {0} of the mod {1}: (Expected: {2} bytes, Ours: \
+         {3} bytes). The download was likely cut short or has been tampered with!"
+    )]
+    VariantSizeMismatch(String, String, u64, u64),
+
+    #[error(
+        "The download of the artifact {file_name} of the mod {mod_name} ended early (Expected: \
+         {expected} bytes, Got: {got} bytes). GameBanana may be having issues, so try again later."
+    )]
+    IncompleteDownload {
+        file_name: String,
+        mod_name: String,
+        expected: u64,
+        got: u64,
+    },
+
+    #[error("GameBanana responded with the HTTP status {0}: {1}")]
+    HttpError(u16, String),
+
+    #[error(
+        "GameBanana is rate limiting requests. {} Making fewer requests at once (eg. by lowering \
+         `download.max_concurrent` or `update.max_concurrent_checks`) makes this less likely.",
+        fmt_retry_after(retry_after)
+    )]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error(
+        "Expected JSON from GameBanana but got something else (HTTP status {0}). GameBanana may \
+         be down or rate limiting requests: {1}"
+    )]
+    UnexpectedResponse(u16, String),
+
     #[error(transparent)]
     JsonDeserializationError(#[from] serde_json::Error),
 
     #[error("Error during Reqwest client initialization")]
     ClientError(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The state of a mod on GameBanana.
+#[derive(Debug)]
+pub struct UpstreamModInfo {
+    pub mod_name: String,
+    pub version: Option<String>,
+
+    /// Every file available for the mod keyed by the file name.
+    pub files: HashMap<String, UpstreamFileInfo>,
+}
+
+impl UpstreamModInfo {
+    /// Authors often delete the old file of a mod and upload the new version
+    /// under a different name. If an installed file is no longer available,
+    /// this finds the file that is most likely its replacement (if we are
+    /// confident enough that there is one).
+    pub fn find_successor_file(&self, installed_file_name: &str) -> Option<&str> {
+        if self.files.contains_key(installed_file_name) {
+            return None;
+        }
+
+        let file_names = self
+            .files
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+
+        match fuzzy_search_strings_and_return_one_or_many_depending_on_perfect_match(
+            &file_names,
+            installed_file_name,
+        ) {
+            FuzzySearchMatchRes::Perfect(idx) => Some(file_names[idx]),
+            FuzzySearchMatchRes::Multiple(sorted_matches) => sorted_matches
+                .first()
+                .filter(|best| best.score >= FloatOrd(MIN_SUCCESSOR_FILE_MATCH_SCORE))
+                .map(|best| file_names[best.idx]),
+            FuzzySearchMatchRes::None => None,
+        }
+    }
+}
+
+impl From<ModPageResp> for UpstreamModInfo {
+    fn from(mod_page_resp: ModPageResp) -> Self {
+        let version = mod_page_resp.version();
+
+        Self {
+            mod_name: mod_page_resp.s_name,
+            version,
+            files: mod_page_resp
+                .a_files
+                .into_iter()
+                .map(|file| {
+                    let upstream_file = UpstreamFileInfo::from(&file);
+                    (file.s_file, upstream_file)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A mod that was found by a search (or listed as a top mod).
+#[derive(Clone, Debug, Serialize)]
+pub struct ModSearchResult {
+    pub id: ModId,
+    pub name: String,
+}
+
+impl Display for ModSearchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+impl From<SearchRespItem> for ModSearchResult {
+    fn from(item: SearchRespItem) -> Self {
+        Self {
+            id: item.id_row,
+            name: item.s_name,
+        }
+    }
+}
+
+/// A file (variant) that can be downloaded for a mod.
+#[derive(Clone, Debug)]
+pub struct ModFileInfo {
+    pub name: String,
+    pub size_bytes: u64,
+    pub date_added: DateTime<Utc>,
+    pub description: Option<String>,
+}
+
+impl From<ModDownloadEntries> for ModFileInfo {
+    fn from(entry: ModDownloadEntries) -> Self {
+        Self {
+            name: entry.s_file,
+            size_bytes: entry.n_filesize,
+            date_added: DateTime::from_timestamp(entry.ts_date_added as i64, 0).unwrap_or_default(),
+            description: (!entry.s_description.is_empty()).then_some(entry.s_description),
+        }
+    }
+}
+
+/// Everything needed to download a file (variant) again and verify it without
+/// going back to the mod page.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UpstreamFileInfo {
+    pub download_url: String,
+
+    /// Lowercase hex string.
+    pub md5: String,
+
+    /// `None` if the mod page does not list the size of the file.
+    pub size: Option<u64>,
+
+    /// The ID of the file on GameBanana (if the mod page lists it).
+    pub file_id: Option<u64>,
+
+    pub date_added: DateTime<Utc>,
+}
+
+impl From<&ModDownloadEntries> for UpstreamFileInfo {
+    fn from(entry: &ModDownloadEntries) -> Self {
+        Self {
+            download_url: entry.s_download_url.clone(),
+            md5: entry.s_md5_checksum.clone(),
+            size: (entry.n_filesize != 0).then_some(entry.n_filesize),
+            file_id: entry.id_row,
+            date_added: DateTime::from_timestamp(entry.ts_date_added as i64, 0).unwrap_or_default(),
+        }
+    }
+}
+
+/// Whether the MD5 checksum of a downloaded variant is compared against the one
+/// listed on the mod page.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ChecksumPolicy {
+    #[default]
+    Verify,
+
+    /// Install the download even if the checksum does not match. Only meant
+    /// for sources that the user trusts.
+    Skip,
+}
+
+/// How the variant name given by the user is matched against the file names on
+/// the mod page.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum VariantMatchPolicy {
+    /// Fall back to fuzzy matching (and ask the user to pick one if there are
+    /// several candidates) if no file name matches exactly.
+    #[default]
+    Fuzzy,
+
+    /// Only accept a file whose name matches exactly (including case). Never
+    /// asks the user anything, so it is safe to use in scripts.
+    Exact,
 }
 
 #[derive(Debug)]
@@ -50,19 +307,262 @@ pub struct ScrapedBananaModData {
     pub mod_name: String,
     pub variant_name: String,
     pub version: Option<String>,
-    pub variant_download_artifact: Vec<u8>,
+
+    /// The page of the mod on GameBanana.
+    pub source_url: String,
+
+    /// Where the file was downloaded from (which also says when it was
+    /// uploaded to GameBanana).
+    pub upstream: UpstreamFileInfo,
+
+    /// Where the downloaded (and verified) archive was written to.
+    pub variant_download_artifact: Utf8PathBuf,
 }
 
+/// A file on a mod page that is ready to be downloaded.
 #[derive(Debug)]
+pub struct FoundModVariant {
+    key: VariantAndId,
+    mod_name: String,
+    version: Option<String>,
+    file_name: String,
+    upstream: UpstreamFileInfo,
+}
+
+/// How the client talks to GameBanana.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BananaClientSettings {
+    /// Searches are limited to mods for this game.
+    pub game_id: u64,
+
+    /// How long to wait on a connection (or for the next chunk of a response)
+    /// before giving up. Large downloads are fine as long as they keep
+    /// making progress.
+    pub timeout: Duration,
+
+    /// How many times a request is retried after a connection error, a
+    /// timeout or a `5xx`/`429` status. Anything else is never retried.
+    pub max_retries: u32,
+}
+
+impl Default for BananaClientSettings {
+    fn default() -> Self {
+        Self {
+            game_id: SSBU_GAME_ID,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct BananaClient {
     client: Client,
+    root: String,
+    game_id: u64,
+    max_retries: u32,
+
+    /// No requests are sent before this once GameBanana rate limits us. Shared
+    /// between clones so that every request in flight backs off, not just
+    /// the one that got rate limited.
+    rate_limited_until: Arc<Mutex<Option<Instant>>>,
 }
 
 impl BananaClient {
     pub fn new() -> BananaScraperResult<Self> {
-        let client = ClientBuilder::default().build()?;
+        Self::with_root(BANANA_ROOT)
+    }
+
+    /// Same as `new` but identifies itself with `ua` instead of
+    /// `DEFAULT_USER_AGENT`.
+    pub fn new_with_user_agent(ua: &str) -> BananaScraperResult<Self> {
+        Self::build(BANANA_ROOT, ua, BananaClientSettings::default())
+    }
+
+    /// Same as `new` but with something other than the default settings.
+    pub fn new_with_settings(settings: BananaClientSettings) -> BananaScraperResult<Self> {
+        Self::build(BANANA_ROOT, DEFAULT_USER_AGENT, settings)
+    }
+
+    /// Creates a client that sends its requests to `root` instead of
+    /// GameBanana. Mostly useful for testing against a local server.
+    pub fn with_root(root: &str) -> BananaScraperResult<Self> {
+        Self::with_root_and_settings(root, BananaClientSettings::default())
+    }
+
+    pub fn with_root_and_settings(
+        root: &str,
+        settings: BananaClientSettings,
+    ) -> BananaScraperResult<Self> {
+        Self::build(root, DEFAULT_USER_AGENT, settings)
+    }
+
+    fn build(root: &str, ua: &str, settings: BananaClientSettings) -> BananaScraperResult<Self> {
+        let client = ClientBuilder::default()
+            .user_agent(ua)
+            .connect_timeout(settings.timeout)
+            .read_timeout(settings.timeout)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .pool_max_idle_per_host(MAX_IDLE_CONNECTIONS_PER_HOST)
+            .build()?;
+
+        Ok(Self {
+            client,
+            root: root.trim_end_matches('/').to_string(),
+            game_id: settings.game_id,
+            max_retries: settings.max_retries,
+            rate_limited_until: Arc::default(),
+        })
+    }
+
+    /// The URL of the page for the mod that a user would visit in a browser.
+    pub fn mod_page_url(&self, id: ModId) -> String {
+        format!("{}/mods/{}", self.root, id)
+    }
+
+    /// Sends a `GET` request and turns any non-success status into an error
+    /// (instead of trying to parse whatever error page we got back). Failures
+    /// that are likely to go away on their own are retried with backoff.
+    ///
+    /// If we are rate limited, every request made through this client (or its
+    /// clones) waits for as long as GameBanana asks (`Retry-After`) before
+    /// trying again.
+    async fn get(&self, url: impl IntoUrl) -> BananaScraperResult<Response> {
+        let url = url.into_url()?;
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+        let mut retries_left = self.max_retries;
+
+        loop {
+            self.wait_out_rate_limit().await;
+            let res = self.client.get(url.clone()).send().await;
+
+            if let Ok(resp) = &res
+                && resp.status() == StatusCode::TOO_MANY_REQUESTS
+            {
+                let retry_after = parse_retry_after(resp.headers());
+                let wait = retry_after.unwrap_or(retry_delay);
+
+                if retries_left == 0 || wait > MAX_RATE_LIMIT_WAIT {
+                    return Err(BananaScraperError::RateLimited { retry_after });
+                }
+
+                warn!(
+                    "GameBanana is rate limiting requests. Waiting {:?} before trying again...",
+                    wait
+                );
+
+                self.back_off_all_requests(wait);
+                retry_delay *= 2;
+                retries_left -= 1;
+                continue;
+            }
+
+            let is_transient = match &res {
+                Ok(resp) => resp.status().is_server_error(),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+
+            if is_transient && retries_left > 0 {
+                debug!(
+                    "Request to {} failed ({}). Retrying in {:?}...",
+                    url,
+                    match &res {
+                        Ok(resp) => resp.status().to_string(),
+                        Err(err) => err.to_string(),
+                    },
+                    retry_delay
+                );
+
+                tokio::time::sleep(retry_delay).await;
+                retry_delay *= 2;
+                retries_left -= 1;
+                continue;
+            }
+
+            let resp = res?;
+            let status = resp.status();
+            if !status.is_success() {
+                let body = resp.text().await.unwrap_or_default();
+
+                return Err(BananaScraperError::HttpError(
+                    status.as_u16(),
+                    body_snippet(&body),
+                ));
+            }
+
+            return Ok(resp);
+        }
+    }
+
+    async fn wait_out_rate_limit(&self) {
+        // Another request may push the limit back further while we wait.
+        loop {
+            let until = *self
+                .rate_limited_until
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            match until.map(|until| until.saturating_duration_since(Instant::now())) {
+                Some(remaining) if !remaining.is_zero() => tokio::time::sleep(remaining).await,
+                _ => return,
+            }
+        }
+    }
+
+    fn back_off_all_requests(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut rate_limited_until = self
+            .rate_limited_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *rate_limited_until = Some(rate_limited_until.map_or(until, |prev| prev.max(until)));
+    }
+
+    /// Sends a single `HEAD` request to GameBanana (without any retries) and
+    /// returns how long it took to respond. Only a `5xx` status counts as
+    /// GameBanana being down.
+    pub async fn ping(&self, timeout: Duration) -> BananaScraperResult<Duration> {
+        let start = Instant::now();
+        let resp = self.client.head(&self.root).timeout(timeout).send().await?;
+
+        let status = resp.status();
+        if status.is_server_error() {
+            return Err(BananaScraperError::HttpError(
+                status.as_u16(),
+                String::new(),
+            ));
+        }
+
+        Ok(start.elapsed())
+    }
+
+    /// Sends a `GET` request to an API endpoint and parses the JSON that it
+    /// responds with. During outages GameBanana sometimes serves an HTML error
+    /// page with a success status, so we check that we actually got JSON before
+    /// trying to parse it.
+    pub(crate) async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: impl IntoUrl,
+    ) -> BananaScraperResult<T> {
+        let resp = self.get(url).await?;
+
+        let status = resp.status();
+        let is_json_content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|content_type| content_type.to_str().unwrap_or_default().contains("json"));
+        let body = resp.text().await?;
 
-        Ok(Self { client })
+        let looks_like_json = body.trim_start().starts_with(['{', '[']);
+        if is_json_content_type == Some(false) || !looks_like_json {
+            return Err(BananaScraperError::UnexpectedResponse(
+                status.as_u16(),
+                body_snippet(&body),
+            ));
+        }
+
+        Ok(serde_json::from_str(&body)?)
     }
 
     async fn get_mod_page_for_mod_id(&self, name: &str) -> BananaScraperResult<String> {
@@ -72,12 +572,7 @@ impl BananaClient {
     pub async fn resolve_mod_name(&self, name: &str) -> BananaScraperResult<ModId> {
         debug!("Resolving mod name \"{}\" to it's ID...", name);
 
-        let search_req = format!(
-            "{BANANA_ROOT}/apiv11/Util/Search/Results?_sModelName=Mod&_sOrder=best_match&\
-             _idGameRow=6498&_sSearchString={name}&_csvFields=name&_nPage=1"
-        );
-        let search_resp: SearchResp =
-            serde_json::from_str(&self.client.get(search_req).send().await?.text().await?)?;
+        let search_resp: SearchResp = self.get_json(self.search_api_url(name, 1)).await?;
 
         // We are going to enforce that searching by a name MUST match the name of the
         // mod exactly, including case.
@@ -103,82 +598,409 @@ impl BananaClient {
         }
     }
 
+    /// Gets what we need from the mod page to check if any installed variants
+    /// of the mod are out of date.
+    pub async fn get_mod_update_info(&self, id: ModId) -> BananaScraperResult<UpstreamModInfo> {
+        debug!("Getting update info for mod {}...", id);
+
+        Ok(self.get_mod_page(id).await?.into())
+    }
+
+    /// Lists every file (variant) that can be downloaded for the mod.
+    pub async fn list_mod_files(&self, id: ModId) -> BananaScraperResult<Vec<ModFileInfo>> {
+        debug!("Listing files of mod {}...", id);
+
+        Ok(self
+            .get_mod_page(id)
+            .await?
+            .a_files
+            .into_iter()
+            .map(ModFileInfo::from)
+            .collect())
+    }
+
+    async fn get_mod_page(&self, id: ModId) -> BananaScraperResult<ModPageResp> {
+        self.get_json(self.mod_page_api_url(id)).await
+    }
+
+    /// Searches for mods whose names match `query`. Pages of results are
+    /// fetched starting at `first_page` (the first page is `1`) until every
+    /// match was fetched or `max_pages` pages were.
+    pub async fn search_all_pages(
+        &self,
+        query: &str,
+        first_page: usize,
+        max_pages: usize,
+    ) -> BananaScraperResult<Vec<ModSearchResult>> {
+        debug!("Searching for mods matching \"{}\"...", query);
+
+        let mut results = Vec::new();
+        for page in first_page..first_page + max_pages {
+            let search_resp: SearchResp = self.get_json(self.search_api_url(query, page)).await?;
+            let num_records = search_resp.a_records.len();
+            results.extend(search_resp.a_records.into_iter().map(ModSearchResult::from));
+
+            if num_records < NUM_SEARCH_RESULTS_PER_PAGE
+                || page * NUM_SEARCH_RESULTS_PER_PAGE >= search_resp.a_meta_data.n_record_count
+            {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// The mods that GameBanana currently features as the top submissions for
+    /// the game.
+    pub async fn list_top_mods(&self) -> BananaScraperResult<Vec<ModSearchResult>> {
+        debug!("Listing the top mods...");
+
+        let top_subs: Vec<TopSubItem> = self
+            .get_json(format!(
+                "{}/apiv11/Game/{}/TopSubs",
+                self.root, self.game_id
+            ))
+            .await?;
+
+        // Other kinds of submissions (eg. tutorials) can show up here too.
+        Ok(top_subs
+            .into_iter()
+            .filter(|item| item.s_model_name == "Mod")
+            .map(|item| ModSearchResult {
+                id: item.id_row,
+                name: item.s_name,
+            })
+            .collect())
+    }
+
+    /// `page` starts at `1`.
+    pub(crate) fn search_api_url(&self, name: &str, page: usize) -> String {
+        let root = &self.root;
+        let game_id = self.game_id;
+
+        format!(
+            "{root}/apiv11/Util/Search/Results?_sModelName=Mod&_sOrder=best_match&\
+             _idGameRow={game_id}&_sSearchString={name}&_csvFields=name&_nPage={page}"
+        )
+    }
+
+    pub(crate) fn mod_page_api_url(&self, id: ModId) -> String {
+        format!("{}/apiv11/Mod/{}/ProfilePage", self.root, id)
+    }
+
+    /// Downloads a variant to `dest_path`. The file is only left behind if its
+    /// checksum and size match what the mod page lists (or if verification is
+    /// skipped), so callers should point this at a quarantine location that
+    /// nothing else reads from.
     pub async fn download_mod_variant(
         &self,
         user_input_delegate: &mut impl UserInputDelegate,
         key: &VariantAndId,
+        match_policy: VariantMatchPolicy,
+        checksum_policy: ChecksumPolicy,
+        dest_path: &Utf8Path,
     ) -> BananaScraperResult<ScrapedBananaModData> {
-        debug!("Downloading mod {}...", key);
+        let found = self
+            .find_mod_variant(user_input_delegate, key, match_policy)
+            .await?;
+        self.download_found_variant(found, checksum_policy, dest_path)
+            .await
+    }
 
-        let mod_page_req = format!("{}/apiv11/Mod/{}/ProfilePage", BANANA_ROOT, key.id);
-        let mod_page_resp: ModPageResp =
-            serde_json::from_str(&self.client.get(mod_page_req).send().await?.text().await?)?;
+    /// Finds the file on the mod page that `key` refers to. The user may be
+    /// asked to pick one if the variant name is not an exact match, unless
+    /// `match_policy` requires one.
+    pub async fn find_mod_variant(
+        &self,
+        user_input_delegate: &mut impl UserInputDelegate,
+        key: &VariantAndId,
+        match_policy: VariantMatchPolicy,
+    ) -> BananaScraperResult<FoundModVariant> {
+        let mut mod_page_resp = self.get_mod_page(key.id).await?;
 
-        // We're not going to require an exact match here, but will use fuzzy matching
-        // instead.
         let mod_file_names = mod_page_resp
             .a_files
             .iter()
             .map(|file| file.s_file.as_str())
             .collect::<Vec<_>>();
 
-        let match_idx = match fuzzy_search_strings_and_return_one_or_many_depending_on_perfect_match(
-            &mod_file_names,
-            &key.variant_name,
-        ) {
-            FuzzySearchMatchRes::Perfect(idx) => idx,
-            FuzzySearchMatchRes::Multiple(sorted_matches) => {
-                let sorted_matches_massaged = sorted_matches
-                    .into_iter()
-                    .map(|x| MatchedFileVariant {
-                        variant_name: mod_file_names[x.idx],
-                        intern: x,
-                    })
-                    .collect::<Vec<_>>();
-
-                user_input_delegate.select_item_from_list(&sorted_matches_massaged)
-            },
-            FuzzySearchMatchRes::None => {
-                return Err(BananaScraperError::ModVariantDoesNotFound(
-                    key.variant_name.to_string(),
-                    mod_page_resp.s_name,
-                ));
+        let match_idx = match match_policy {
+            VariantMatchPolicy::Exact => mod_file_names
+                .iter()
+                .position(|file_name| *file_name == key.variant_name)
+                .ok_or_else(|| BananaScraperError::NoExactVariantMatch {
+                    variant_name: key.variant_name.to_string(),
+                    mod_name: mod_page_resp.s_name.clone(),
+                    available: mod_file_names.iter().map(|s| s.to_string()).collect(),
+                })?,
+            // Otherwise we're not going to require an exact match, but will use fuzzy
+            // matching instead.
+            VariantMatchPolicy::Fuzzy => {
+                match fuzzy_search_strings_and_return_one_or_many_depending_on_perfect_match(
+                    &mod_file_names,
+                    &key.variant_name,
+                ) {
+                    FuzzySearchMatchRes::Perfect(idx) => idx,
+                    FuzzySearchMatchRes::Multiple(sorted_matches) => {
+                        let sorted_matches_massaged = sorted_matches
+                            .into_iter()
+                            .map(|x| MatchedFileVariant {
+                                variant_name: mod_file_names[x.idx],
+                                intern: x,
+                            })
+                            .collect::<Vec<_>>();
+
+                        user_input_delegate.select_item_from_list(&sorted_matches_massaged)
+                    },
+                    FuzzySearchMatchRes::None => {
+                        return Err(BananaScraperError::ModVariantDoesNotFound(
+                            key.variant_name.to_string(),
+                            mod_page_resp.s_name,
+                        ));
+                    },
+                }
             },
         };
 
-        let version = mod_page_resp
-            .s_version
-            .is_empty()
-            .then_some(mod_page_resp.s_version);
-
-        let selected_variant = &mod_page_resp.a_files[match_idx];
-        let payload_download_url = selected_variant.s_download_url.as_str();
-        let variant_download_artifact = self
-            .client
-            .get(payload_download_url)
-            .send()
-            .await?
-            .bytes()
-            .await?
-            .to_vec();
-
-        // Verify that the MD5 hash matches (idk why they are using MD5 instead od
-        // something like SHA256...)
-        let calculated_md5 = format!("{:x}", md5::compute(&variant_download_artifact));
-        if calculated_md5 != selected_variant.s_md5_checksum {
-            return Err(BananaScraperError::VariantMd5CheckSumMismatch(
-                selected_variant.s_file.clone(),
-                mod_page_resp.s_name,
-                selected_variant.s_md5_checksum.clone(),
-                calculated_md5,
-            ));
-        }
+        let version = mod_page_resp.version();
+        let file = mod_page_resp.a_files.swap_remove(match_idx);
 
-        Ok(ScrapedBananaModData {
+        Ok(FoundModVariant {
+            key: key.clone(),
             mod_name: mod_page_resp.s_name,
-            variant_name: selected_variant.s_file.clone(),
             version,
-            variant_download_artifact,
+            upstream: UpstreamFileInfo::from(&file),
+            file_name: file.s_file,
+        })
+    }
+
+    /// Downloads a variant that was found with `find_mod_variant`. Never asks
+    /// the user anything, so many of these can run at once.
+    pub async fn download_found_variant(
+        &self,
+        found: FoundModVariant,
+        checksum_policy: ChecksumPolicy,
+        dest_path: &Utf8Path,
+    ) -> BananaScraperResult<ScrapedBananaModData> {
+        debug!("Downloading mod {}...", found.key);
+
+        if checksum_policy == ChecksumPolicy::Skip {
+            warn!(
+                "!!! Checksum verification is DISABLED for {}. A tampered or corrupted download \
+                 will be installed without complaint. Only do this for sources that you trust! !!!",
+                found.key
+            );
+        }
+
+        self.download_file(
+            &found.file_name,
+            &found.mod_name,
+            &found.upstream,
+            checksum_policy,
+            dest_path,
+        )
+        .await?;
+
+        Ok(ScrapedBananaModData {
+            source_url: self.mod_page_url(found.key.id),
+            mod_name: found.mod_name,
+            variant_name: found.file_name,
+            version: found.version,
+            upstream: found.upstream,
+            variant_download_artifact: dest_path.to_path_buf(),
+        })
+    }
+
+    /// Downloads a file that we already know the upstream info of to
+    /// `dest_path`. Like `download_found_variant`, the file is only left behind
+    /// if it matches what the info says that we should get (or if verification
+    /// is skipped). `file_name` and `mod_name` are only used for errors.
+    pub async fn download_file(
+        &self,
+        file_name: &str,
+        mod_name: &str,
+        file: &UpstreamFileInfo,
+        checksum_policy: ChecksumPolicy,
+        dest_path: &Utf8Path,
+    ) -> BananaScraperResult<()> {
+        let res = self
+            .download_to_file(&file.download_url, dest_path)
+            .await
+            .and_then(|digest| {
+                verify_download(file_name, mod_name, file, &digest, checksum_policy)
+            });
+
+        if let Err(err) = res {
+            // Never leave a partial or unverified download around.
+            if let Err(rm_err) = fs::remove_file(dest_path)
+                && rm_err.kind() != io::ErrorKind::NotFound
+            {
+                warn!(
+                    "Unable to remove the rejected download \"{}\": {}",
+                    dest_path, rm_err
+                );
+            }
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Streams the response to `dest_path`, hashing the payload as it arrives
+    /// so that it never needs to be held in memory or read a second time.
+    async fn download_to_file(
+        &self,
+        url: &str,
+        dest_path: &Utf8Path,
+    ) -> BananaScraperResult<DownloadDigest> {
+        let mut resp = self.get(url).await?;
+
+        let mut writer = Md5HashingWriter::new(File::create(dest_path)?);
+        while let Some(chunk) = resp.chunk().await? {
+            writer.push_chunk(&chunk)?;
+        }
+
+        Ok(writer.finish()?)
+    }
+}
+
+/// The start of a response body that is small enough to put in an error
+/// message.
+fn body_snippet(body: &str) -> String {
+    body.chars().take(MAX_HTTP_ERROR_BODY_LEN).collect()
+}
+
+/// Reads how long GameBanana wants us to wait from a `Retry-After` header,
+/// which is either a number of seconds or an HTTP date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    DateTime::parse_from_rfc2822(value).ok().map(|date| {
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+    })
+}
+
+fn fmt_retry_after(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(retry_after) => format!(
+            "Try again in {} seconds.",
+            retry_after.as_secs_f64().ceil() as u64
+        ),
+        None => "Try again in a few minutes.".to_string(),
+    }
+}
+
+/// Checks a finished download against what the mod page says that we should
+/// have gotten.
+fn verify_download(
+    file_name: &str,
+    mod_name: &str,
+    file: &UpstreamFileInfo,
+    digest: &DownloadDigest,
+    checksum_policy: ChecksumPolicy,
+) -> BananaScraperResult<()> {
+    // A short (or empty) body would otherwise show up as a checksum mismatch
+    // or as an archive that can't be read, neither of which says what actually
+    // went wrong. Never useful to install, so this is checked even if
+    // checksums are skipped.
+    if let Some(size) = file.size
+        && digest.size_bytes < size
+    {
+        return Err(BananaScraperError::IncompleteDownload {
+            file_name: file_name.to_string(),
+            mod_name: mod_name.to_string(),
+            expected: size,
+            got: digest.size_bytes,
+        });
+    }
+
+    // Verify that the MD5 hash matches (idk why they are using MD5 instead od
+    // something like SHA256...)
+    if digest.md5 != file.md5 {
+        match checksum_policy {
+            ChecksumPolicy::Verify => {
+                return Err(BananaScraperError::VariantMd5CheckSumMismatch(
+                    file_name.to_string(),
+                    mod_name.to_string(),
+                    file.md5.clone(),
+                    digest.md5.clone(),
+                ));
+            },
+            ChecksumPolicy::Skip => warn!(
+                "!!! The MD5 checksum of {} does not match (Expected: {}, Ours: {}). Installing \
+                 it anyways since checksum verification is disabled. !!!",
+                file_name, file.md5, digest.md5
+            ),
+        }
+    }
+
+    // Not every mod page lists the size of its files.
+    if let Some(size) = file.size
+        && digest.size_bytes != size
+    {
+        match checksum_policy {
+            ChecksumPolicy::Verify => {
+                return Err(BananaScraperError::VariantSizeMismatch(
+                    file_name.to_string(),
+                    mod_name.to_string(),
+                    size,
+                    digest.size_bytes,
+                ));
+            },
+            ChecksumPolicy::Skip => warn!(
+                "!!! The size of {} does not match (Expected: {} bytes, Ours: {} bytes). \
+                 Installing it anyways since checksum verification is disabled. !!!",
+                file_name, size, digest.size_bytes
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct DownloadDigest {
+    /// Lowercase hex string.
+    md5: String,
+    size_bytes: u64,
+}
+
+/// Writes a payload that arrives in chunks while incrementally computing its
+/// MD5 checksum and size.
+struct Md5HashingWriter<W: Write> {
+    ctx: md5::Context,
+    size_bytes: u64,
+    inner: W,
+}
+
+impl<W: Write> Md5HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            ctx: md5::Context::new(),
+            size_bytes: 0,
+            inner,
+        }
+    }
+
+    fn push_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.ctx.consume(chunk);
+        self.size_bytes += chunk.len() as u64;
+        self.inner.write_all(chunk)
+    }
+
+    fn finish(mut self) -> io::Result<DownloadDigest> {
+        self.inner.flush()?;
+
+        Ok(DownloadDigest {
+            md5: format!("{:x}", self.ctx.compute()),
+            size_bytes: self.size_bytes,
         })
     }
 }
@@ -220,6 +1042,18 @@ struct SearchRespItem {
     s_name: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct TopSubItem {
+    #[serde(rename = "_idRow")]
+    id_row: ModId,
+
+    #[serde(rename = "_sModelName")]
+    s_model_name: String,
+
+    #[serde(rename = "_sName")]
+    s_name: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct ModPageResp {
     #[serde(rename = "_sName")]
@@ -232,17 +1066,383 @@ struct ModPageResp {
     a_files: Vec<ModDownloadEntries>,
 }
 
+impl ModPageResp {
+    fn version(&self) -> Option<String> {
+        (!self.s_version.is_empty()).then(|| self.s_version.clone())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ModDownloadEntries {
+    #[serde(rename = "_idRow", default)]
+    id_row: Option<u64>,
+
     #[serde(rename = "_sFile")]
     s_file: String,
 
+    #[serde(rename = "_nFilesize", default)]
+    n_filesize: u64,
+
     #[serde(rename = "_tsDateAdded")]
     ts_date_added: u64,
 
+    #[serde(rename = "_sDescription", default)]
+    s_description: String,
+
     #[serde(rename = "_sDownloadUrl")]
     s_download_url: String,
 
     #[serde(rename = "_sMd5Checksum")]
     s_md5_checksum: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use chrono::Utc;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::{
+        BananaClient, BananaClientSettings, BananaScraperError, DEFAULT_USER_AGENT,
+        Md5HashingWriter, ModFileInfo, ModPageResp, UpstreamModInfo, parse_retry_after,
+    };
+
+    fn m_renamed_files_upstream_info() -> UpstreamModInfo {
+        let mod_page_resp: ModPageResp = serde_json::from_str(include_str!(
+            "../tests/fixtures/renamed_files_profile_page.json"
+        ))
+        .unwrap();
+
+        mod_page_resp.into()
+    }
+
+    #[test]
+    fn mod_page_url_points_at_the_mod() {
+        assert_eq!(
+            BananaClient::new().unwrap().mod_page_url(378330),
+            "https://gamebanana.com/mods/378330"
+        );
+    }
+
+    /// Sends a single request with a client made by `m_client` and returns the
+    /// raw request that the server received.
+    async fn capture_request(m_client: impl FnOnce(&str) -> BananaClient) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let root = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut req = vec![0; 4096];
+            let n = conn.read(&mut req).await.unwrap();
+            conn.write_all(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+            String::from_utf8_lossy(&req[..n]).to_lowercase()
+        });
+
+        let _ = m_client(&root).get_mod_update_info(1).await;
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_identify_the_mod_manager_by_default() {
+        let req = capture_request(|root| BananaClient::with_root(root).unwrap()).await;
+
+        assert!(
+            DEFAULT_USER_AGENT
+                .starts_with(concat!("ultimate-mod-man-rs/", env!("CARGO_PKG_VERSION")))
+        );
+        assert!(req.contains(&format!(
+            "user-agent: {}\r\n",
+            DEFAULT_USER_AGENT.to_lowercase()
+        )));
+    }
+
+    #[tokio::test]
+    async fn custom_user_agent_replaces_the_default() {
+        let req = capture_request(|root| {
+            BananaClient::build(root, "my-fork/1.0", BananaClientSettings::default()).unwrap()
+        })
+        .await;
+
+        assert!(req.contains("user-agent: my-fork/1.0\r\n"));
+    }
+
+    /// Answers each connection with the next of `resps` and returns the raw
+    /// requests that were received.
+    async fn serve_in_order(resps: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let root = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut reqs = Vec::new();
+            for resp in resps {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut req = vec![0; 4096];
+                let n = conn.read(&mut req).await.unwrap();
+                conn.write_all(resp.as_bytes()).await.unwrap();
+
+                reqs.push(String::from_utf8_lossy(&req[..n]).to_string());
+            }
+
+            reqs
+        });
+
+        (root, server)
+    }
+
+    const SERVICE_UNAVAILABLE_RESP: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const NO_SEARCH_RESULTS_RESP: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 53\r\nConnection: \
+         close\r\n\r\n{\"_aMetadata\": {\"_nRecordCount\": 0}, \"_aRecords\": []}";
+
+    #[tokio::test]
+    async fn searches_are_limited_to_the_configured_game() {
+        let (root, server) = serve_in_order(vec![NO_SEARCH_RESULTS_RESP]).await;
+        let settings = BananaClientSettings {
+            game_id: 1234,
+            ..Default::default()
+        };
+
+        let res = BananaClient::with_root_and_settings(&root, settings)
+            .unwrap()
+            .resolve_mod_name("x")
+            .await;
+
+        assert!(matches!(res, Err(BananaScraperError::ModNameNotFound(_))));
+        assert!(server.await.unwrap()[0].contains("_idGameRow=1234&"));
+    }
+
+    /// A page of search results with mods `ids` out of `total` matches.
+    fn m_search_page_resp(ids: impl Iterator<Item = u64>, total: usize) -> &'static str {
+        let records = ids
+            .map(|id| format!(r#"{{"_idRow": {id}, "_sName": "Mod {id}"}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let body =
+            format!(r#"{{"_aMetadata": {{"_nRecordCount": {total}}}, "_aRecords": [{records}]}}"#);
+
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: \
+             {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .leak()
+    }
+
+    #[tokio::test]
+    async fn searches_fetch_pages_until_every_match_is_found() {
+        let (root, server) = serve_in_order(vec![
+            m_search_page_resp(0..15, 17),
+            m_search_page_resp(15..17, 17),
+        ])
+        .await;
+
+        let results = BananaClient::with_root(&root)
+            .unwrap()
+            .search_all_pages("mod", 1, 5)
+            .await
+            .unwrap();
+        let reqs = server.await.unwrap();
+
+        assert_eq!(results.len(), 17);
+        assert_eq!(results[16].id, 16);
+        assert!(reqs[0].contains("_nPage=1 "));
+        assert!(reqs[1].contains("_nPage=2 "));
+    }
+
+    #[tokio::test]
+    async fn unavailable_server_is_retried_until_it_responds() {
+        let (root, server) = serve_in_order(vec![
+            SERVICE_UNAVAILABLE_RESP,
+            SERVICE_UNAVAILABLE_RESP,
+            NO_SEARCH_RESULTS_RESP,
+        ])
+        .await;
+
+        let res = BananaClient::with_root(&root)
+            .unwrap()
+            .resolve_mod_name("x")
+            .await;
+
+        assert!(matches!(res, Err(BananaScraperError::ModNameNotFound(_))));
+        assert_eq!(server.await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_give_up_after_the_configured_amount() {
+        let (root, server) = serve_in_order(vec![SERVICE_UNAVAILABLE_RESP; 2]).await;
+        let settings = BananaClientSettings {
+            max_retries: 1,
+            ..Default::default()
+        };
+
+        let res = BananaClient::with_root_and_settings(&root, settings)
+            .unwrap()
+            .resolve_mod_name("x")
+            .await;
+
+        assert!(matches!(res, Err(BananaScraperError::HttpError(503, _))));
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_waits_as_long_as_asked() {
+        const RATE_LIMITED_RESP: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: \
+                                         1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let (root, server) = serve_in_order(vec![RATE_LIMITED_RESP, NO_SEARCH_RESULTS_RESP]).await;
+
+        let start = Instant::now();
+        let res = BananaClient::with_root(&root)
+            .unwrap()
+            .resolve_mod_name("x")
+            .await;
+
+        assert!(matches!(res, Err(BananaScraperError::ModNameNotFound(_))));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn long_rate_limit_is_reported_instead_of_waited_out() {
+        const RATE_LIMITED_RESP: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: \
+                                         3600\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let (root, server) = serve_in_order(vec![RATE_LIMITED_RESP]).await;
+
+        let res = BananaClient::with_root(&root)
+            .unwrap()
+            .resolve_mod_name("x")
+            .await;
+
+        match res {
+            Err(err @ BananaScraperError::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Some(Duration::from_secs(3600)));
+                assert!(err.to_string().contains("Try again in 3600 seconds."));
+            },
+            res => panic!("Expected to be rate limited but got {:?}", res),
+        }
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_holds_back_requests_from_every_clone() {
+        let (root, server) = serve_in_order(vec![NO_SEARCH_RESULTS_RESP]).await;
+        let client = BananaClient::with_root(&root).unwrap();
+        let other_client = client.clone();
+
+        client.back_off_all_requests(Duration::from_secs(1));
+        let start = Instant::now();
+        let res = other_client.resolve_mod_name("x").await;
+
+        assert!(matches!(res, Err(BananaScraperError::ModNameNotFound(_))));
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retry_after_is_read_as_seconds_or_a_date() {
+        let m_headers = |value: &str| {
+            HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from_str(value).unwrap())])
+        };
+
+        assert_eq!(
+            parse_retry_after(&m_headers("120")),
+            Some(Duration::from_secs(120))
+        );
+
+        let date = (Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        let from_date = parse_retry_after(&m_headers(&date)).unwrap();
+        assert!(from_date > Duration::from_secs(110) && from_date <= Duration::from_secs(120));
+
+        assert_eq!(parse_retry_after(&m_headers("soon")), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn renamed_file_is_found_as_successor() {
+        let upstream = m_renamed_files_upstream_info();
+
+        assert_eq!(
+            upstream.find_successor_file("bully_maguire_v1.zip"),
+            Some("bully_maguire_v2_final_REAL.zip")
+        );
+    }
+
+    #[test]
+    fn existing_file_has_no_successor() {
+        let upstream = m_renamed_files_upstream_info();
+
+        assert_eq!(
+            upstream.find_successor_file("bully_maguire_v2_final_REAL.zip"),
+            None
+        );
+    }
+
+    #[test]
+    fn unrelated_file_has_no_successor() {
+        let upstream = m_renamed_files_upstream_info();
+
+        assert_eq!(upstream.find_successor_file("peppino_over_wario.rar"), None);
+    }
+
+    #[test]
+    fn mod_files_are_listed_from_mod_page() {
+        let mod_page_resp: ModPageResp = serde_json::from_str(include_str!(
+            "../tests/fixtures/renamed_files_profile_page.json"
+        ))
+        .unwrap();
+
+        let files = mod_page_resp
+            .a_files
+            .into_iter()
+            .map(ModFileInfo::from)
+            .collect::<Vec<_>>();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].name, "bully_maguire_v2_final_REAL.zip");
+        assert_eq!(files[0].size_bytes, 4_194_304);
+        assert_eq!(files[0].date_added.timestamp(), 1_735_689_600);
+        assert_eq!(files[0].description, None);
+        assert_eq!(files[1].description.as_deref(), Some("Voice lines only"));
+    }
+
+    #[test]
+    fn upstream_files_are_read_from_mod_page() {
+        let upstream = m_renamed_files_upstream_info();
+        let file = &upstream.files["bully_maguire_v2_final_REAL.zip"];
+
+        assert_eq!(file.download_url, "https://gamebanana.com/dl/1300001");
+        assert_eq!(file.md5, "0cc175b9c0f1b6a831c399e269772661");
+        assert_eq!(file.size, Some(4_194_304));
+        assert_eq!(file.file_id, Some(1_300_001));
+        assert_eq!(file.date_added.timestamp(), 1_735_689_600);
+    }
+
+    #[test]
+    fn chunked_md5_matches_one_shot_md5() {
+        let payload = (0..10_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        let mut assembled_payload = Vec::new();
+        let mut writer = Md5HashingWriter::new(&mut assembled_payload);
+        for chunk in payload.chunks(777) {
+            writer.push_chunk(chunk).unwrap();
+        }
+        let digest = writer.finish().unwrap();
+
+        assert_eq!(digest.md5, format!("{:x}", md5::compute(&payload)));
+        assert_eq!(digest.size_bytes, payload.len() as u64);
+        assert_eq!(assembled_payload, payload);
+    }
+}
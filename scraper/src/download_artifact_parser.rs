@@ -1,19 +1,50 @@
 use std::{
+    fmt::{self, Display, Formatter},
     fs::{self, File},
-    io::{self, Cursor, Read, Write},
+    io::{self, BufReader, Read, Seek, Write},
     ops::Deref,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::{Condvar, Mutex},
 };
 
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use log::warn;
 use thiserror::Error;
 use ultimate_mod_man_rs_utils::types::CharSkinSlotValue;
 use unrar::{Archive, error::UnrarError};
 use zip::{ZipArchive, result::ZipError};
 
-const MAGIC_NUMBER_BYTE_READ_AMOUNT: usize = 100;
+use crate::mod_file_classifier::get_game_root_offset;
+
+/// Large enough for a whole tar header block, since the tar magic number is
+/// not at the start of the file.
+const MAGIC_NUMBER_BYTE_READ_AMOUNT: usize = 512;
+
+/// Mask and value of the file type bits of a Unix mode for a symlink.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+const UNIX_SYMLINK_FILE_TYPE: u32 = 0o120000;
+
+/// Windows file attributes that are relevant for detecting links.
+const WINDOWS_REPARSE_POINT_ATTRIBUTE: u32 = 0x400;
+const WINDOWS_UNIX_EXTENSION_ATTRIBUTE: u32 = 0x8000;
+
+/// Files and directories that archivers add on their own. Never part of a mod.
+const ARCHIVER_JUNK_NAMES: &[&str] = &["__MACOSX", ".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Entry paths longer than this are flagged before expansion. The mod
+/// directory is prepended to them once they are on disk, so anything longer is
+/// likely to go past the path length limits of the SD card or of Windows.
+const MAX_ARCHIVE_ENTRY_PATH_LEN: usize = 255;
+
+/// The most archives that may be open through a native library (ie. `unrar`)
+/// at once. Every open handle holds onto a file and a native buffer, so adding
+/// a large batch of mods at once should not open all of them at the same time.
+const MAX_CONCURRENT_NATIVE_ARCHIVE_OPS: usize = 2;
+
+/// Shared by every archive being parsed in the process (eg. when several mods
+/// are added in parallel).
+static NATIVE_ARCHIVE_PERMITS: Semaphore = Semaphore::new(MAX_CONCURRENT_NATIVE_ARCHIVE_OPS);
 
 pub type VariantParseResult<T> = Result<T, VariantParseError>;
 type ArchiveExpansionResult<T> = Result<T, ArchiveExpansionError>;
@@ -28,6 +59,12 @@ pub enum VariantParseError {
     #[error("Unable to determine the archive type from it's magic number ({0})")]
     VariantPayloadNotARecognizableArchive(String, #[source] CompressionTypeFromExtStrErr),
 
+    #[error(
+        "The archive {0} contains the entry \"{1}\" which would be written outside of the mod \
+         directory"
+    )]
+    UnsafeArchivePath(String, Utf8PathBuf),
+
     #[error(transparent)]
     InternArchiveParseError(#[from] InternArchiveParserErr),
 
@@ -57,6 +94,9 @@ pub enum InternArchiveParserErr {
     #[error(transparent)]
     Rar(#[from] UnrarError),
 
+    #[error("The archive contains the entry {0:?} which does not have a valid UTF-8 path")]
+    NonUtf8Path(PathBuf),
+
     #[error(transparent)]
     Sevenz(#[from] sevenz_rust::Error),
 
@@ -64,22 +104,83 @@ pub enum InternArchiveParserErr {
     Tar(#[from] io::Error),
 }
 
+/// Something questionable about the paths of the entries of an archive that is
+/// found before anything is expanded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArchivePathWarning {
+    /// The entry would be written outside of the directory that the archive is
+    /// expanded into (eg. `../foo` or an absolute path).
+    PathTraversal(Utf8PathBuf),
+
+    /// An entry does not have a valid UTF-8 path. Nothing after it in the
+    /// archive could be checked.
+    NonUtf8Path,
+
+    /// The entry path along with its length in bytes.
+    PathTooLong(Utf8PathBuf, usize),
+}
+
+impl Display for ArchivePathWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchivePathWarning::PathTraversal(path) => write!(
+                f,
+                "The entry \"{}\" would be written outside of the mod directory",
+                path
+            ),
+            ArchivePathWarning::NonUtf8Path => {
+                write!(f, "An entry does not have a valid UTF-8 path")
+            },
+            ArchivePathWarning::PathTooLong(path, len) => write!(
+                f,
+                "The entry \"{}\" has a path that is {} bytes long (more than {})",
+                path, len, MAX_ARCHIVE_ENTRY_PATH_LEN
+            ),
+        }
+    }
+}
+
 pub struct ModPayloadParseInfo {
     expandable_archive: Box<dyn ExpandableArchive>,
+
+    /// Problems with the paths in the archive that were not bad enough to
+    /// reject it.
+    pub warnings: Vec<ArchivePathWarning>,
 }
 
 impl ModPayloadParseInfo {
+    /// Sniffs the format of the archive and checks that every entry in it would
+    /// stay inside of the directory that it is expanded into. Nothing is
+    /// written to disk and the archive is read from disk as it is needed
+    /// instead of being loaded into memory.
     pub fn new(archive_path: &Utf8Path) -> VariantParseResult<Self> {
         let variant_name = archive_path.file_name().unwrap();
-
-        let archive_h = File::open(archive_path)?;
         let mut expandable_archive = Self::open_archive(variant_name, archive_path)?;
 
-        let all_archive_file_paths = expandable_archive
-            .get_paths_of_all_files()?
-            .collect::<Vec<_>>();
+        let _permit = expandable_archive
+            .uses_native_handle()
+            .then(|| NATIVE_ARCHIVE_PERMITS.acquire());
+        let warnings = expandable_archive.validate_paths()?;
+
+        // Anything that could write outside of the mod directory is never let
+        // through.
+        for warning in warnings.iter() {
+            if let ArchivePathWarning::PathTraversal(unsafe_path) = warning {
+                return Err(VariantParseError::UnsafeArchivePath(
+                    variant_name.to_string(),
+                    unsafe_path.clone(),
+                ));
+            }
+        }
+
+        for warning in warnings.iter() {
+            warn!("{} (in the archive {}).", warning, variant_name);
+        }
 
-        Ok(Self { expandable_archive })
+        Ok(Self {
+            expandable_archive,
+            warnings,
+        })
     }
 
     fn open_archive(
@@ -139,8 +240,10 @@ impl ModPayloadParseInfo {
         comp_type: CompressionType,
     ) -> VariantParseResult<Box<dyn ExpandableArchive + 'a>> {
         let h: Box<dyn ExpandableArchive> = match comp_type {
-            CompressionType::Zip => Box::new(ZipParser::new(fs::read(archive_path)?)?),
-            CompressionType::Rar => Box::new(RarParser::new(archive_path.to_path_buf())?),
+            CompressionType::Zip => {
+                Box::new(ZipParser::new(BufReader::new(File::open(archive_path)?))?)
+            },
+            CompressionType::Rar => Box::new(RarParser::new(archive_path)?),
             CompressionType::SevenZip => Box::new(SevenZipParser::new(archive_path.to_path_buf())),
             CompressionType::Tar => Box::new(TarParser::new(archive_path)?),
         };
@@ -148,10 +251,15 @@ impl ModPayloadParseInfo {
         Ok(h)
     }
 
-    pub fn expand_archive_to_disk(self, dest_dir: &Utf8Path) -> VariantParseResult<()> {
+    pub fn expand_archive_to_disk(mut self, dest_dir: &Utf8Path) -> VariantParseResult<()> {
+        let _permit = self
+            .expandable_archive
+            .uses_native_handle()
+            .then(|| NATIVE_ARCHIVE_PERMITS.acquire());
+
         // Some mods (idk why) don't have the "root" mod directory at the very top, so
         // we need to scan before decompression and look for it.
-        let mod_root_directory_offset = self.search_for_mod_root();
+        let mod_root_directory_offset = self.search_for_mod_root()?;
         let root_offset = mod_root_directory_offset.as_ref().map(|x| x.as_path());
         let f = Box::new(Self::filter_fn);
 
@@ -161,16 +269,31 @@ impl ModPayloadParseInfo {
         Ok(())
     }
 
+    /// Skips files that archivers (mostly on macOS and Windows) leave behind.
     fn filter_fn(p: &Utf8Path) -> bool {
-        todo!()
+        !p.components().any(|c| {
+            ARCHIVER_JUNK_NAMES
+                .iter()
+                .any(|junk| c.as_str().eq_ignore_ascii_case(junk))
+        })
     }
 
     fn remove_root_offset_from_path(p: &Utf8Path, root_offset_p: &Utf8Path) -> Utf8PathBuf {
-        todo!()
+        p.strip_prefix(root_offset_p).unwrap_or(p).to_path_buf()
     }
 
-    fn search_for_mod_root(&self) -> Option<Utf8PathBuf> {
-        todo!()
+    /// The directory that the game directories (eg. `fighter`) are in. If
+    /// different files disagree, the shallowest one wins since nested copies
+    /// are usually junk (eg. `__MACOSX/MyMod/fighter`). `None` if they are
+    /// already at the top of the archive (or if there are none at all).
+    fn search_for_mod_root(&mut self) -> VariantParseResult<Option<Utf8PathBuf>> {
+        let mod_root = self
+            .expandable_archive
+            .get_paths_of_all_files()?
+            .filter_map(|p| get_game_root_offset(&p))
+            .min_by_key(|offset| offset.components().count());
+
+        Ok(mod_root.filter(|offset| !offset.as_str().is_empty()))
     }
 }
 
@@ -180,9 +303,13 @@ pub trait ExpandableFile {
     }
 }
 
+/// Every implementation must never create a link (symbolic or hard) from the
+/// archive on disk or write anything outside of `dest_dir`. Links are skipped
+/// with a warning since a link (or a path like `../foo`) could otherwise be
+/// used to write outside of the mod directory. Mods do not need them anyways.
 pub trait ExpandableArchive {
     fn expand_archive_to_disk_with_filter_and_offset(
-        &self,
+        &mut self,
         dest_dir: &Utf8Path,
         root_offset: Option<&Utf8Path>,
         filter: Box<dyn Fn(&Utf8Path) -> bool>,
@@ -191,76 +318,228 @@ pub trait ExpandableArchive {
     fn get_paths_of_all_files<'a>(
         &'a mut self,
     ) -> ArchiveExpansionResult<Box<dyn Iterator<Item = Utf8PathBuf> + 'a>>;
+
+    /// Checks the paths of every entry before anything is expanded. A path
+    /// that is not valid UTF-8 stops the check since the archive can not be
+    /// listed any further.
+    fn validate_paths(&mut self) -> ArchiveExpansionResult<Vec<ArchivePathWarning>> {
+        let paths = match self.get_paths_of_all_files() {
+            Ok(paths) => paths,
+            Err(ArchiveExpansionError::InternArchiveParseError(
+                InternArchiveParserErr::NonUtf8Path(_),
+            )) => return Ok(vec![ArchivePathWarning::NonUtf8Path]),
+            Err(err) => return Err(err),
+        };
+
+        let mut warnings = Vec::new();
+        for path in paths {
+            let len = path.as_str().len();
+
+            if !stays_in_dir(&path) {
+                warnings.push(ArchivePathWarning::PathTraversal(path));
+            } else if len > MAX_ARCHIVE_ENTRY_PATH_LEN {
+                warnings.push(ArchivePathWarning::PathTooLong(path, len));
+            }
+        }
+
+        Ok(warnings)
+    }
+
+    /// Whether the archive is read through a native library. Only a few of
+    /// these may be in use at once.
+    fn uses_native_handle(&self) -> bool {
+        false
+    }
 }
 
+/// Only the central directory of the zip is read when it is opened. Entries are
+/// read from the underlying reader as they are expanded.
 #[derive(Debug)]
-struct ZipParser {
-    intern: ZipArchive<Cursor<Vec<u8>>>,
+struct ZipParser<R = BufReader<File>> {
+    intern: ZipArchive<R>,
 }
 
-impl ExpandableArchive for ZipParser {
+impl<R: Read + Seek> ExpandableArchive for ZipParser<R> {
     fn expand_archive_to_disk_with_filter_and_offset(
-        &self,
+        &mut self,
         dest_dir: &Utf8Path,
         root_offset: Option<&Utf8Path>,
         filter: Box<dyn Fn(&Utf8Path) -> bool>,
     ) -> ArchiveExpansionResult<()> {
-        todo!()
+        for i in 0..self.intern.len() {
+            let mut file = self
+                .intern
+                .by_index(i)
+                .map_err(InternArchiveParserErr::from)?;
+            let entry_path = Utf8PathBuf::from(file.name());
+
+            if file.is_symlink() {
+                warn_skipping_link(&entry_path);
+                continue;
+            }
+
+            if file.is_dir() {
+                continue;
+            }
+
+            if let Some(dest_path) =
+                get_entry_dest_path(dest_dir, &entry_path, root_offset, &filter)
+            {
+                write_entry_to_disk(&dest_path, &mut file)?;
+            }
+        }
+
+        Ok(())
     }
 
-    fn get_paths_of_all_files(
-        &mut self,
-    ) -> ArchiveExpansionResult<Box<dyn Iterator<Item = Utf8PathBuf>>> {
-        todo!()
+    fn get_paths_of_all_files<'a>(
+        &'a mut self,
+    ) -> ArchiveExpansionResult<Box<dyn Iterator<Item = Utf8PathBuf> + 'a>> {
+        Ok(Box::new(
+            self.intern
+                .file_names()
+                .filter(|name| !name.ends_with('/'))
+                .map(Utf8PathBuf::from),
+        ))
     }
 }
 
-impl ZipParser {
-    fn new(compressed_bytes: Vec<u8>) -> InternArchiveParserResult<Self> {
-        let intern = ZipArchive::new(Cursor::new(compressed_bytes))?;
+impl<R: Read + Seek> ZipParser<R> {
+    fn new(reader: R) -> InternArchiveParserResult<Self> {
+        let intern = ZipArchive::new(reader)?;
 
         Ok(Self { intern })
     }
 }
 
+/// `unrar` needs the archive to be opened in a different mode for listing and
+/// for extracting, so no handle is kept around between the two. Every
+/// operation opens the archive fresh and closes it again as soon as it is done.
 struct RarParser {
     archive_path: Utf8PathBuf,
-    all_file_paths: Vec<PathBuf>,
 }
 
 impl ExpandableArchive for RarParser {
     fn expand_archive_to_disk_with_filter_and_offset(
-        &self,
+        &mut self,
         dest_dir: &Utf8Path,
         root_offset: Option<&Utf8Path>,
         filter: Box<dyn Fn(&Utf8Path) -> bool>,
     ) -> ArchiveExpansionResult<()> {
-        todo!()
+        let mut archive = Archive::new(&self.archive_path)
+            .open_for_processing()
+            .map_err(InternArchiveParserErr::from)?;
+
+        while let Some(header) = archive
+            .read_header()
+            .map_err(InternArchiveParserErr::from)?
+        {
+            let entry = header.entry();
+            let entry_path = rar_entry_path(&entry.filename)?;
+            let dest_path = match entry_path {
+                _ if entry.is_directory() => None,
+                // RAR does not tell us which OS the attributes are from, so check for both.
+                entry_path
+                    if is_unix_symlink_mode(entry.file_attr)
+                        || entry.file_attr & WINDOWS_REPARSE_POINT_ATTRIBUTE != 0 =>
+                {
+                    warn_skipping_link(entry_path);
+                    None
+                },
+                entry_path => get_entry_dest_path(dest_dir, entry_path, root_offset, &filter),
+            };
+
+            archive = match dest_path {
+                Some(dest_path) => {
+                    create_parent_dirs(&dest_path)?;
+                    header.extract_to(&dest_path)
+                },
+                None => header.skip(),
+            }
+            .map_err(InternArchiveParserErr::from)?;
+        }
+
+        Ok(())
     }
 
     fn get_paths_of_all_files(
         &mut self,
     ) -> ArchiveExpansionResult<Box<dyn Iterator<Item = Utf8PathBuf>>> {
-        todo!()
+        // The headers are read one at a time, so only the paths are ever held
+        // onto. The handle is closed once the last header has been read.
+        let listing = Archive::new(&self.archive_path)
+            .open_for_listing()
+            .map_err(InternArchiveParserErr::from)?;
+
+        let mut paths = Vec::new();
+        for header in listing {
+            let header = header.map_err(InternArchiveParserErr::from)?;
+            if !header.is_directory() {
+                paths.push(rar_entry_path(&header.filename)?.to_path_buf());
+            }
+        }
+
+        Ok(Box::new(paths.into_iter()))
+    }
+
+    fn uses_native_handle(&self) -> bool {
+        true
     }
 }
 
 impl RarParser {
-    fn new(archive_path: Utf8PathBuf) -> InternArchiveParserResult<Self> {
-        let h = Archive::new(&archive_path).open_for_listing()?;
+    fn new(archive_path: &Utf8Path) -> Result<Self, io::Error> {
+        // Make sure that we can actually read it.
+        File::open(archive_path)?;
 
-        let mut all_file_paths = Vec::new();
-        for header_res in h {
-            let header = header_res?;
-            all_file_paths.push(header.filename);
+        Ok(Self {
+            archive_path: archive_path.to_path_buf(),
+        })
+    }
+}
+
+/// Paths that are not UTF-8 would have no way of being referred to by the rest
+/// of the manager, so they are rejected instead of being skipped.
+fn rar_entry_path(entry_path: &Path) -> InternArchiveParserResult<&Utf8Path> {
+    Utf8Path::from_path(entry_path)
+        .ok_or_else(|| InternArchiveParserErr::NonUtf8Path(entry_path.to_path_buf()))
+}
+
+/// A counting semaphore that blocks the current thread while no permits are
+/// available.
+struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    const fn new(permits: usize) -> Self {
+        Self {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
         }
+    }
 
-        let res = RarParser {
-            archive_path,
-            all_file_paths,
-        };
+    /// The permit is given back once it is dropped.
+    fn acquire(&self) -> SemaphorePermit<'_> {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+
+        SemaphorePermit { semaphore: self }
+    }
+}
 
-        Ok(res)
+struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.available.lock().unwrap() += 1;
+        self.semaphore.released.notify_one();
     }
 }
 
@@ -277,12 +556,39 @@ impl SevenZipParser {
 
 impl ExpandableArchive for SevenZipParser {
     fn expand_archive_to_disk_with_filter_and_offset(
-        &self,
+        &mut self,
         dest_dir: &Utf8Path,
         root_offset: Option<&Utf8Path>,
         filter: Box<dyn Fn(&Utf8Path) -> bool>,
     ) -> ArchiveExpansionResult<()> {
-        todo!()
+        sevenz_rust::decompress_file_with_extract_fn(&self.path, dest_dir, |entry, reader, _| {
+            let entry_path = Utf8Path::new(entry.name());
+            let attributes = entry.windows_attributes();
+
+            // Archives created on Unix store the mode in the upper bits of the attributes.
+            let is_link = attributes & WINDOWS_REPARSE_POINT_ATTRIBUTE != 0
+                || (attributes & WINDOWS_UNIX_EXTENSION_ATTRIBUTE != 0
+                    && is_unix_symlink_mode(attributes >> 16));
+
+            if is_link {
+                warn_skipping_link(entry_path);
+                return Ok(true);
+            }
+
+            if entry.is_directory() {
+                return Ok(true);
+            }
+
+            if let Some(dest_path) = get_entry_dest_path(dest_dir, entry_path, root_offset, &filter)
+            {
+                write_entry_to_disk(&dest_path, reader)?;
+            }
+
+            Ok(true)
+        })
+        .map_err(InternArchiveParserErr::from)?;
+
+        Ok(())
     }
 
     fn get_paths_of_all_files(
@@ -298,53 +604,165 @@ impl ExpandableArchive for SevenZipParser {
     }
 }
 
+/// Tar archives can only be read through once, so we open the archive again
+/// every time that we need to read it.
 struct TarParser {
-    intern: tar::Archive<File>,
+    path: Utf8PathBuf,
 }
 
 impl TarParser {
     fn new(path: &Utf8Path) -> Result<Self, TarError> {
-        let intern = tar::Archive::new(File::open(path)?);
+        // Make sure that we can actually read it.
+        File::open(path)?;
 
-        Ok(Self { intern })
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn open(&self) -> Result<tar::Archive<File>, TarError> {
+        Ok(tar::Archive::new(File::open(&self.path)?))
     }
 }
 
 impl ExpandableArchive for TarParser {
     fn expand_archive_to_disk_with_filter_and_offset(
-        &self,
+        &mut self,
         dest_dir: &Utf8Path,
         root_offset: Option<&Utf8Path>,
         filter: Box<dyn Fn(&Utf8Path) -> bool>,
     ) -> ArchiveExpansionResult<()> {
-        todo!()
+        let mut archive = self.open()?;
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.into_owned();
+            let entry_path = match Utf8Path::from_path(&entry_path) {
+                Some(entry_path) => entry_path,
+                None => {
+                    warn!(
+                        "Skipping the file {:?} in the archive since its path is not valid UTF-8.",
+                        entry_path
+                    );
+                    continue;
+                },
+            };
+
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_symlink() || entry_type.is_hard_link() {
+                warn_skipping_link(entry_path);
+                continue;
+            }
+
+            if !entry_type.is_file() {
+                continue;
+            }
+
+            if let Some(dest_path) = get_entry_dest_path(dest_dir, entry_path, root_offset, &filter)
+            {
+                write_entry_to_disk(&dest_path, &mut entry)?;
+            }
+        }
+
+        Ok(())
     }
 
     fn get_paths_of_all_files<'a>(
         &'a mut self,
     ) -> ArchiveExpansionResult<Box<dyn Iterator<Item = Utf8PathBuf> + 'a>> {
         // Going to do a vec allocation for the sake of maintaining a nicer interface.
-        Ok(Box::new(
-            self.intern
-                .entries()?
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .map(|f| {
-                    Utf8Path::from_path(
-                        f.path()
-                            .expect(
-                                "Failed to get the path of a item in a tar file! (Are you running \
-                                 on Windows and unpacking a tar file that is not unicode?",
-                            )
-                            .deref(),
-                    )
-                    .unwrap()
-                    .to_path_buf()
-                }),
-        ))
+        let mut archive = self.open()?;
+        let paths = archive
+            .entries()?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .map(|f| {
+                Utf8Path::from_path(
+                    f.path()
+                        .expect(
+                            "Failed to get the path of a item in a tar file! (Are you running on \
+                             Windows and unpacking a tar file that is not unicode?",
+                        )
+                        .deref(),
+                )
+                .unwrap()
+                .to_path_buf()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(paths.into_iter()))
     }
 }
 
+fn is_unix_symlink_mode(mode: u32) -> bool {
+    mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK_FILE_TYPE
+}
+
+fn warn_skipping_link(entry_path: &Utf8Path) {
+    warn!(
+        "Skipping the link \"{}\" in the archive. Links are never extracted since they could \
+         point outside of the mod directory.",
+        entry_path
+    );
+}
+
+/// Gets where an entry of an archive should be written to. Returns `None` if
+/// the entry is not under the root offset, is filtered out or would end up
+/// outside of `dest_dir` (eg. `../foo` or an absolute path).
+fn get_entry_dest_path(
+    dest_dir: &Utf8Path,
+    entry_path: &Utf8Path,
+    root_offset: Option<&Utf8Path>,
+    filter: &dyn Fn(&Utf8Path) -> bool,
+) -> Option<Utf8PathBuf> {
+    if let Some(root_offset) = root_offset
+        && !entry_path.starts_with(root_offset)
+    {
+        return None;
+    }
+
+    let rel_path = match root_offset {
+        Some(root_offset) => {
+            ModPayloadParseInfo::remove_root_offset_from_path(entry_path, root_offset)
+        },
+        None => entry_path.to_path_buf(),
+    };
+
+    if !stays_in_dir(&rel_path) {
+        warn!(
+            "Skipping the file \"{}\" in the archive since it would be written outside of the mod \
+             directory.",
+            entry_path
+        );
+        return None;
+    }
+
+    filter(&rel_path).then(|| dest_dir.join(rel_path))
+}
+
+/// Whether a relative path stays inside of the directory that it is joined
+/// onto (ie. it is not absolute and does not contain any `..`).
+fn stays_in_dir(rel_path: &Utf8Path) -> bool {
+    rel_path
+        .components()
+        .all(|c| matches!(c, Utf8Component::Normal(_) | Utf8Component::CurDir))
+}
+
+fn create_parent_dirs(p: &Utf8Path) -> io::Result<()> {
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(())
+}
+
+fn write_entry_to_disk(dest_path: &Utf8Path, entry: &mut dyn Read) -> io::Result<()> {
+    create_parent_dirs(dest_path)?;
+    io::copy(entry, &mut File::create(dest_path)?)?;
+
+    Ok(())
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum CompressionType {
     Zip,
@@ -384,3 +802,299 @@ pub(crate) struct CharacterSlot {
     /// The slots specified by the mod.
     mod_default_slots: Vec<CharSkinSlotValue>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        fs,
+        io::{self, Cursor, Read, Seek, SeekFrom, Write},
+        rc::Rc,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use tempfile::TempDir;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::{
+        ArchivePathWarning, ExpandableArchive, MAX_ARCHIVE_ENTRY_PATH_LEN, ModPayloadParseInfo,
+        Semaphore, TarParser, VariantParseError, ZipParser,
+    };
+
+    const FILE_PATH: &str = "fighter/mario/model/body/c00/model.numdlb";
+    const FILE_CONTENTS: &[u8] = b"model";
+
+    /// A link to a file inside of the archive.
+    const IN_BOUNDS_LINK: (&str, &str) =
+        ("fighter/mario/alias.numdlb", "model/body/c00/model.numdlb");
+
+    /// A link that points outside of the directory being expanded into.
+    const ESCAPING_LINK: (&str, &str) = ("fighter/mario/escape", "../../../../outside");
+
+    fn utf8_path(dir: &TempDir) -> &Utf8Path {
+        Utf8Path::from_path(dir.path()).unwrap()
+    }
+
+    fn expand(archive: &mut dyn ExpandableArchive, dir: &TempDir) -> Utf8PathBuf {
+        let dest_dir = utf8_path(dir).join("a/b/expanded");
+        fs::create_dir_all(&dest_dir).unwrap();
+
+        archive
+            .expand_archive_to_disk_with_filter_and_offset(&dest_dir, None, Box::new(|_| true))
+            .unwrap();
+
+        dest_dir
+    }
+
+    /// Writes a zip containing `entry_paths` to `dir` and returns its path.
+    fn write_zip(dir: &TempDir, entry_paths: &[&str]) -> Utf8PathBuf {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for entry_path in entry_paths {
+            writer
+                .start_file(*entry_path, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(FILE_CONTENTS).unwrap();
+        }
+
+        let archive_path = utf8_path(dir).join("variant.zip");
+        fs::write(&archive_path, writer.finish().unwrap().into_inner()).unwrap();
+
+        archive_path
+    }
+
+    /// Keeps track of how many bytes have been read through it.
+    struct CountingReader<R> {
+        inner: R,
+        bytes_read: Rc<Cell<u64>>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.bytes_read.set(self.bytes_read.get() + n as u64);
+
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn assert_links_were_not_created(dir: &TempDir, dest_dir: &Utf8Path) {
+        assert_eq!(fs::read(dest_dir.join(FILE_PATH)).unwrap(), FILE_CONTENTS);
+
+        for (link_path, _) in [IN_BOUNDS_LINK, ESCAPING_LINK] {
+            assert!(fs::symlink_metadata(dest_dir.join(link_path)).is_err());
+        }
+
+        assert!(fs::symlink_metadata(utf8_path(dir).join("outside")).is_err());
+    }
+
+    #[test]
+    fn tar_links_are_never_created() {
+        let dir = TempDir::new().unwrap();
+        let archive_path = utf8_path(&dir).join("variant.tar");
+
+        let mut builder = tar::Builder::new(fs::File::create(&archive_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(FILE_CONTENTS.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, FILE_PATH, FILE_CONTENTS)
+            .unwrap();
+
+        for (link_path, target) in [IN_BOUNDS_LINK, ESCAPING_LINK] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, link_path, target).unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let dest_dir = expand(&mut TarParser::new(&archive_path).unwrap(), &dir);
+        assert_links_were_not_created(&dir, &dest_dir);
+    }
+
+    #[test]
+    fn zip_links_are_never_created() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file(FILE_PATH, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(FILE_CONTENTS).unwrap();
+
+        for (link_path, target) in [IN_BOUNDS_LINK, ESCAPING_LINK] {
+            writer
+                .add_symlink(link_path, target, SimpleFileOptions::default())
+                .unwrap();
+        }
+        let compressed_bytes = writer.finish().unwrap().into_inner();
+
+        let dir = TempDir::new().unwrap();
+        let dest_dir = expand(
+            &mut ZipParser::new(Cursor::new(compressed_bytes)).unwrap(),
+            &dir,
+        );
+        assert_links_were_not_created(&dir, &dest_dir);
+    }
+
+    #[test]
+    fn archives_with_only_safe_entries_are_accepted() {
+        let dir = TempDir::new().unwrap();
+        let archive_path = write_zip(&dir, &[FILE_PATH, "./fighter/mario/other.numdlb"]);
+
+        let parse_info = ModPayloadParseInfo::new(&archive_path).unwrap();
+        assert!(parse_info.warnings.is_empty());
+    }
+
+    #[test]
+    fn overlong_entry_paths_are_reported_as_warnings() {
+        let dir = TempDir::new().unwrap();
+        let long_path = format!(
+            "fighter/mario/{}.numdlb",
+            "a".repeat(MAX_ARCHIVE_ENTRY_PATH_LEN)
+        );
+        let archive_path = write_zip(&dir, &[FILE_PATH, &long_path]);
+
+        let parse_info = ModPayloadParseInfo::new(&archive_path).unwrap();
+        assert_eq!(
+            parse_info.warnings,
+            vec![ArchivePathWarning::PathTooLong(
+                long_path.as_str().into(),
+                long_path.len()
+            )]
+        );
+    }
+
+    #[test]
+    fn escaping_entries_are_found_when_validating() {
+        let dir = TempDir::new().unwrap();
+        let archive_path = write_zip(&dir, &[FILE_PATH, "/abs/path", "fighter/../../outside"]);
+        let mut archive = ZipParser::new(fs::File::open(&archive_path).unwrap()).unwrap();
+
+        assert_eq!(
+            archive.validate_paths().unwrap(),
+            vec![
+                ArchivePathWarning::PathTraversal("/abs/path".into()),
+                ArchivePathWarning::PathTraversal("fighter/../../outside".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn archives_with_escaping_entries_are_rejected_up_front() {
+        let dir = TempDir::new().unwrap();
+        let archive_path = write_zip(&dir, &[FILE_PATH, "fighter/../../outside"]);
+
+        assert!(matches!(
+            ModPayloadParseInfo::new(&archive_path),
+            Err(VariantParseError::UnsafeArchivePath(_, entry_path))
+                if entry_path == "fighter/../../outside"
+        ));
+    }
+
+    #[test]
+    fn entries_outside_of_the_dest_dir_are_skipped() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file(FILE_PATH, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(FILE_CONTENTS).unwrap();
+        writer
+            .start_file("../../../outside", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(FILE_CONTENTS).unwrap();
+        let compressed_bytes = writer.finish().unwrap().into_inner();
+
+        let dir = TempDir::new().unwrap();
+        let dest_dir = expand(
+            &mut ZipParser::new(Cursor::new(compressed_bytes)).unwrap(),
+            &dir,
+        );
+        assert_links_were_not_created(&dir, &dest_dir);
+    }
+
+    #[test]
+    fn mod_root_dir_is_stripped_and_archiver_junk_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let archive_path = write_zip(
+            &dir,
+            &[
+                &format!("My Mod/{FILE_PATH}"),
+                "My Mod/preview.png",
+                "My Mod/.DS_Store",
+                &format!("__MACOSX/My Mod/{FILE_PATH}"),
+            ],
+        );
+        let dest_dir = utf8_path(&dir).join("expanded");
+        fs::create_dir(&dest_dir).unwrap();
+
+        ModPayloadParseInfo::new(&archive_path)
+            .unwrap()
+            .expand_archive_to_disk(&dest_dir)
+            .unwrap();
+
+        assert_eq!(fs::read(dest_dir.join(FILE_PATH)).unwrap(), FILE_CONTENTS);
+        assert!(dest_dir.join("preview.png").exists());
+        assert!(!dest_dir.join(".DS_Store").exists());
+        assert!(!dest_dir.join("My Mod").exists());
+        assert!(!dest_dir.join("__MACOSX").exists());
+    }
+
+    #[test]
+    fn opening_a_zip_does_not_read_its_entries() {
+        const ENTRY_SIZE: usize = 8 * 1024 * 1024;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                FILE_PATH,
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(&vec![0; ENTRY_SIZE]).unwrap();
+        let compressed_bytes = writer.finish().unwrap().into_inner();
+
+        let bytes_read = Rc::new(Cell::new(0));
+        let mut parser = ZipParser::new(CountingReader {
+            inner: Cursor::new(compressed_bytes),
+            bytes_read: bytes_read.clone(),
+        })
+        .unwrap();
+
+        let paths = parser.get_paths_of_all_files().unwrap().collect::<Vec<_>>();
+        assert_eq!(paths, vec![Utf8PathBuf::from(FILE_PATH)]);
+        assert!(bytes_read.get() < (ENTRY_SIZE / 100) as u64);
+    }
+
+    #[test]
+    fn semaphore_limits_how_many_permits_are_held_at_once() {
+        const PERMITS: usize = 2;
+
+        let semaphore = Semaphore::new(PERMITS);
+        let held = AtomicUsize::new(0);
+        let most_held = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    let _permit = semaphore.acquire();
+                    let now_held = held.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_held.fetch_max(now_held, Ordering::SeqCst);
+
+                    thread::sleep(Duration::from_millis(20));
+                    held.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert_eq!(most_held.load(Ordering::SeqCst), PERMITS);
+    }
+}
@@ -0,0 +1,300 @@
+//! Checks that the GameBanana API still looks the way that we expect it to.
+//! GameBanana does not version its API, so when something breaks it is useful
+//! to be able to tell whether GameBanana is down, slow or has changed the shape
+//! of its responses.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use ultimate_mod_man_rs_utils::types::ModId;
+
+use crate::banana_scraper::BananaClient;
+
+/// A mod that has been on GameBanana for a long time and that we know the
+/// responses for. Its name is searched for and its profile page is fetched.
+const KNOWN_GOOD_MOD_NAME: &str = "BULLY MAGUIRE over Joker";
+const KNOWN_GOOD_MOD_ID: ModId = 378330;
+
+/// The fields of a search response that the scraper relies on. `[]` means
+/// "every element of the array" (an empty array counts as missing).
+const SEARCH_FIELDS: &[&str] = &[
+    "_aMetadata._nRecordCount",
+    "_aRecords[]._idRow",
+    "_aRecords[]._sName",
+];
+
+/// The fields of a mod profile page response that the scraper relies on.
+const PROFILE_PAGE_FIELDS: &[&str] = &[
+    "_sName",
+    "_aFiles[]._sFile",
+    "_aFiles[]._nFilesize",
+    "_aFiles[]._sDownloadUrl",
+    "_aFiles[]._sMd5Checksum",
+    "_aFiles[]._tsDateAdded",
+];
+
+/// The result of checking every endpoint that the scraper uses.
+#[derive(Debug)]
+pub struct HealthCheckReport {
+    pub checks: Vec<EndpointCheck>,
+}
+
+impl HealthCheckReport {
+    pub fn is_healthy(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| matches!(check.outcome, EndpointCheckOutcome::Ok))
+    }
+}
+
+impl Display for HealthCheckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{:<14} {:>6}ms  {}",
+                check.endpoint,
+                check.latency.as_millis(),
+                check.outcome
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct EndpointCheck {
+    pub endpoint: &'static str,
+
+    /// How long it took to get the full response (including any retries).
+    pub latency: Duration,
+    pub outcome: EndpointCheckOutcome,
+}
+
+#[derive(Debug)]
+pub enum EndpointCheckOutcome {
+    Ok,
+
+    /// Got JSON back, but without some of the fields that we need.
+    MissingFields(Vec<&'static str>),
+
+    /// The request failed or the response was not JSON.
+    Failed(String),
+}
+
+impl Display for EndpointCheckOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EndpointCheckOutcome::Ok => write!(f, "OK"),
+            EndpointCheckOutcome::MissingFields(fields) => {
+                write!(f, "Missing fields: {}", fields.join(", "))
+            },
+            EndpointCheckOutcome::Failed(err) => write!(f, "Failed: {}", err),
+        }
+    }
+}
+
+impl BananaClient {
+    /// Hits the search and profile page endpoints for a known good mod and
+    /// checks that the responses have the fields that the scraper needs.
+    pub async fn health_check(&self) -> HealthCheckReport {
+        let checks = vec![
+            self.check_endpoint(
+                "search",
+                self.search_api_url(KNOWN_GOOD_MOD_NAME, 1),
+                SEARCH_FIELDS,
+            )
+            .await,
+            self.check_endpoint(
+                "profile page",
+                self.mod_page_api_url(KNOWN_GOOD_MOD_ID),
+                PROFILE_PAGE_FIELDS,
+            )
+            .await,
+        ];
+
+        HealthCheckReport { checks }
+    }
+
+    async fn check_endpoint(
+        &self,
+        endpoint: &'static str,
+        url: String,
+        expected_fields: &[&'static str],
+    ) -> EndpointCheck {
+        let start = Instant::now();
+        let res = self.get_json::<Value>(url).await;
+        let latency = start.elapsed();
+
+        let outcome = match res {
+            Ok(json) => {
+                let missing_fields: Vec<_> = expected_fields
+                    .iter()
+                    .copied()
+                    .filter(|field| !has_field(&json, field))
+                    .collect();
+
+                match missing_fields.is_empty() {
+                    false => EndpointCheckOutcome::MissingFields(missing_fields),
+                    true => EndpointCheckOutcome::Ok,
+                }
+            },
+            Err(err) => EndpointCheckOutcome::Failed(err.to_string()),
+        };
+
+        EndpointCheck {
+            endpoint,
+            latency,
+            outcome,
+        }
+    }
+}
+
+/// Whether `json` has the (`.` separated) `path`. A segment ending in `[]`
+/// must be a non-empty array where every element has the rest of the path.
+fn has_field(json: &Value, path: &str) -> bool {
+    let (segment, rest) = match path.split_once('.') {
+        Some((segment, rest)) => (segment, Some(rest)),
+        None => (path, None),
+    };
+
+    let (key, is_array) = match segment.strip_suffix("[]") {
+        Some(key) => (key, true),
+        None => (segment, false),
+    };
+
+    let Some(val) = json.get(key) else {
+        return false;
+    };
+
+    match (is_array, rest) {
+        (false, None) => true,
+        (false, Some(rest)) => has_field(val, rest),
+        (true, rest) => match val.as_array() {
+            Some(elems) if !elems.is_empty() => elems
+                .iter()
+                .all(|elem| rest.is_none_or(|rest| has_field(elem, rest))),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::EndpointCheckOutcome;
+    use crate::banana_scraper::{BananaClient, BananaClientSettings};
+
+    const SEARCH_BODY: &str = r#"{"_aMetadata": {"_nRecordCount": 1}, "_aRecords": [{"_idRow": 378330, "_sName": "BULLY MAGUIRE over Joker"}]}"#;
+    const PROFILE_PAGE_BODY: &str = r#"{"_sName": "BULLY MAGUIRE over Joker", "_aFiles": [{"_sFile": "bully.zip", "_nFilesize": 10, "_sDownloadUrl": "x", "_sMd5Checksum": "y", "_tsDateAdded": 0}]}"#;
+
+    /// Answers each connection (in order) with a `200` and the next of
+    /// `bodies`.
+    async fn serve_in_order(bodies: Vec<(&'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let root = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            for (content_type, body) in bodies {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut req = vec![0; 4096];
+                let _ = conn.read(&mut req).await.unwrap();
+
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: \
+                     close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+                conn.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        root
+    }
+
+    async fn check(bodies: Vec<(&'static str, &'static str)>) -> Vec<EndpointCheckOutcome> {
+        let root = serve_in_order(bodies).await;
+        let settings = BananaClientSettings {
+            max_retries: 0,
+            ..Default::default()
+        };
+
+        BananaClient::with_root_and_settings(&root, settings)
+            .unwrap()
+            .health_check()
+            .await
+            .checks
+            .into_iter()
+            .map(|check| check.outcome)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn well_formed_responses_are_healthy() {
+        let outcomes = check(vec![
+            ("application/json", SEARCH_BODY),
+            ("application/json", PROFILE_PAGE_BODY),
+        ])
+        .await;
+
+        assert!(matches!(
+            outcomes[..],
+            [EndpointCheckOutcome::Ok, EndpointCheckOutcome::Ok]
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_fields_are_reported() {
+        let outcomes = check(vec![
+            ("application/json", r#"{"_aMetadata": {}, "_aRecords": []}"#),
+            (
+                "application/json",
+                r#"{"_sName": "BULLY MAGUIRE over Joker", "_aFiles": [{"_sFile": "bully.zip"}]}"#,
+            ),
+        ])
+        .await;
+
+        match &outcomes[..] {
+            [
+                EndpointCheckOutcome::MissingFields(search_missing),
+                EndpointCheckOutcome::MissingFields(profile_missing),
+            ] => {
+                assert_eq!(
+                    search_missing,
+                    &[
+                        "_aMetadata._nRecordCount",
+                        "_aRecords[]._idRow",
+                        "_aRecords[]._sName"
+                    ]
+                );
+                assert!(!profile_missing.contains(&"_aFiles[]._sFile"));
+                assert!(profile_missing.contains(&"_aFiles[]._sMd5Checksum"));
+            },
+            outcomes => panic!("Unexpected outcomes: {:?}", outcomes),
+        }
+    }
+
+    #[tokio::test]
+    async fn non_json_responses_are_failures() {
+        let outcomes = check(vec![
+            ("text/html", "<html>Down for maintenance</html>"),
+            ("application/json", PROFILE_PAGE_BODY),
+        ])
+        .await;
+
+        assert!(matches!(
+            outcomes[..],
+            [EndpointCheckOutcome::Failed(_), EndpointCheckOutcome::Ok]
+        ));
+    }
+}
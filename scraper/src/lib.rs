@@ -1,4 +1,5 @@
 pub mod banana_scraper;
 pub mod download_artifact_parser;
+pub mod health_check;
 pub mod mod_file_classifier;
 mod utils;
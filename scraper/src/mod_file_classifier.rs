@@ -11,13 +11,57 @@
 //! The overall patterns to look for vary quite a bit between asset file types,
 //! so unfortunately the rules get pretty complicated.
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
+    fs, io,
+    str::FromStr,
+};
 
 use camino::{Utf8Path, Utf8PathBuf};
-use serde::{Deserialize, Serialize};
-use ultimate_mod_man_rs_utils::types::{CharSkinSlotValue, ModId, StageSlotValue};
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use ultimate_mod_man_rs_utils::types::{CharSkinSlotValue, ModId, SkinSlotValue, StageSlotValue};
+use walkdir::WalkDir;
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+/// Top level directories of the game's file system. Mods usually ship these
+/// inside of a directory named after the mod, so anything before these is not
+/// part of the path that the game sees.
+static GAME_ROOT_DIR_NAMES: &[&str] = &[
+    "assist",
+    "boss",
+    "camera",
+    "effect",
+    "enemy",
+    "fighter",
+    "item",
+    "param",
+    "prebuilt;",
+    "render",
+    "snapshot",
+    "sound",
+    "spirits",
+    "stage",
+    "standard",
+    "stream;",
+    "ui",
+];
+
+/// Files that are commonly bundled with mods (readmes, previews, etc.) that the
+/// game never loads.
+static NO_EFFECT_FILE_EXTENSIONS: &[&str] = &["gif", "jpeg", "jpg", "md", "png", "txt", "webp"];
+
+/// Where the game looks for stages and music. Neither is tied to a slot (yet),
+/// so these files are otherwise treated like any other global file.
+static STAGE_DIR_PREFIXES: &[&str] = &["stage"];
+static MUSIC_DIR_PREFIXES: &[&str] = &["sound/bgm", "stream;/sound/bgm"];
+
+/// The file that ARCropolis reads to learn about files and directories that a
+/// mod adds to the game (eg. skin slots past `c07`).
+const ARCROPOLIS_CONFIG_FILE_NAME: &str = "config.json";
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 enum ModType {
     CharacterSkin,
     StageSkin,
@@ -39,16 +83,463 @@ pub enum ModFileAssetAssociation {
     NoEffect,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// The broad kinds of assets that a mod can change. Used to only install some
+/// of the files of a mod (eg. just the skins of a mod that also bundles a
+/// stage).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetType {
+    Skins,
+    Stages,
+    Music,
+    Global,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Unknown asset type \"{0}\" (expected one of \"skins\", \"stages\", \"music\" or \"global\")"
+)]
+pub struct AssetTypeFromStrErr(String);
+
+impl AssetType {
+    /// `None` for files that have no effect on the game.
+    fn of_file(assoc: &ModFileAssetAssociation, game_path: &Utf8Path) -> Option<Self> {
+        match assoc {
+            ModFileAssetAssociation::CharSkinSlot(_) => Some(Self::Skins),
+            ModFileAssetAssociation::Stage(_) => Some(Self::Stages),
+            ModFileAssetAssociation::Global if is_under(game_path, STAGE_DIR_PREFIXES) => {
+                Some(Self::Stages)
+            },
+            ModFileAssetAssociation::Global if is_under(game_path, MUSIC_DIR_PREFIXES) => {
+                Some(Self::Music)
+            },
+            ModFileAssetAssociation::Global => Some(Self::Global),
+            ModFileAssetAssociation::NoEffect => None,
+        }
+    }
+}
+
+impl Display for AssetType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetType::Skins => write!(f, "skins"),
+            AssetType::Stages => write!(f, "stages"),
+            AssetType::Music => write!(f, "music"),
+            AssetType::Global => write!(f, "global"),
+        }
+    }
+}
+
+impl FromStr for AssetType {
+    type Err = AssetTypeFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skins" => Ok(Self::Skins),
+            "stages" => Ok(Self::Stages),
+            "music" => Ok(Self::Music),
+            "global" => Ok(Self::Global),
+            _ => Err(AssetTypeFromStrErr(s.to_string())),
+        }
+    }
+}
+
+fn is_under(game_path: &Utf8Path, dir_prefixes: &[&str]) -> bool {
+    let game_path = game_path.as_str().to_lowercase();
+    dir_prefixes
+        .iter()
+        .any(|prefix| Utf8Path::new(&game_path).starts_with(prefix))
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct VariantFileInfo {
     mod_type: Vec<ModType>,
+
+    /// TOML only supports string keys, so this gets serialized as a list of
+    /// entries instead of a map.
+    #[serde(
+        serialize_with = "serialize_owned_files",
+        deserialize_with = "deserialize_owned_files"
+    )]
     owned_files: HashMap<ModFileAssetAssociation, Vec<Utf8PathBuf>>,
+
+    /// Skin slots past the normal ones (`c08`+) that the variant's ARCropolis
+    /// config adds to the game, keyed by character. The game can only load
+    /// extended slots that some config declares.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    declared_extra_slots: BTreeMap<String, Vec<SkinSlotValue>>,
+}
+
+/// The parts of an ARCropolis `config.json` that can declare new skin slots.
+/// Everything else in the config is ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ArcropolisConfig {
+    /// Directories added to the game (eg. `fighter/mario/c08`).
+    #[serde(default)]
+    new_dir_infos: Vec<String>,
+
+    /// Vanilla files mapped to the added files that share them.
+    #[serde(default)]
+    share_to_added: HashMap<String, SharedToPaths>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SharedToPaths {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ArcropolisConfig {
+    /// Every path that the config adds to the game.
+    fn added_paths(&self) -> impl Iterator<Item = &str> {
+        let shared_to = self
+            .share_to_added
+            .values()
+            .flat_map(|shared_to| match shared_to {
+                SharedToPaths::One(path) => std::slice::from_ref(path),
+                SharedToPaths::Many(paths) => paths.as_slice(),
+            });
+
+        self.new_dir_infos
+            .iter()
+            .chain(shared_to)
+            .map(|path| path.as_str())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct OwnedFilesEntry<T> {
+    association: ModFileAssetAssociation,
+    files: T,
+}
+
+fn serialize_owned_files<S: Serializer>(
+    owned_files: &HashMap<ModFileAssetAssociation, Vec<Utf8PathBuf>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        owned_files
+            .iter()
+            .map(|(association, files)| OwnedFilesEntry {
+                association: association.clone(),
+                files: files.as_slice(),
+            }),
+    )
+}
+
+fn deserialize_owned_files<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<ModFileAssetAssociation, Vec<Utf8PathBuf>>, D::Error> {
+    let entries = Vec::<OwnedFilesEntry<Vec<Utf8PathBuf>>>::deserialize(deserializer)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.association, entry.files))
+        .collect())
+}
+
+/// Lazily walks every file under `root` and yields its path relative to
+/// `root`. Links are skipped instead of followed since they could point outside
+/// of the mod directory.
+fn walk_files(root: &Utf8Path) -> impl Iterator<Item = io::Result<Utf8PathBuf>> + '_ {
+    WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(move |entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            if entry.path_is_symlink() {
+                warn!(
+                    "Skipping the link {:?} in the mod directory since links are never followed.",
+                    entry.path()
+                );
+                return None;
+            }
+
+            if !entry.file_type().is_file() {
+                return None;
+            }
+
+            let path = match Utf8Path::from_path(entry.path()) {
+                Some(path) => path,
+                None => {
+                    return Some(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("The path {:?} is not valid UTF-8", entry.path()),
+                    )));
+                },
+            };
+
+            // Can never fail since we are always somewhere inside of `root`.
+            Some(Ok(path.strip_prefix(root).unwrap().to_path_buf()))
+        })
 }
 
 impl VariantFileInfo {
     /// Crawl the mod directory looking for any files that we can classify.
-    pub fn from_uncompressed_path(p: &Utf8Path) -> Self {
-        todo!()
+    /// Every file is classified as soon as it is visited so that mods with
+    /// thousands of files never have all of their paths in memory at once.
+    pub fn from_uncompressed_path(p: &Utf8Path) -> io::Result<Self> {
+        Self::from_uncompressed_path_filtered(p, None)
+    }
+
+    /// Like `from_uncompressed_path`, but if `asset_types` is given, any file
+    /// that changes an asset of a different type is deleted from `p` instead
+    /// of being recorded. Files that have no effect on the game are always
+    /// kept.
+    pub fn from_uncompressed_path_filtered(
+        p: &Utf8Path,
+        asset_types: Option<&[AssetType]>,
+    ) -> io::Result<Self> {
+        let mut file_info = Self::default();
+
+        for rel_path in walk_files(p) {
+            let rel_path = rel_path?;
+            let game_path = get_game_path(&rel_path);
+            let assoc = classify_game_path(&game_path);
+
+            if let Some(asset_types) = asset_types
+                && let Some(asset_type) = AssetType::of_file(&assoc, &game_path)
+                && !asset_types.contains(&asset_type)
+            {
+                fs::remove_file(p.join(&rel_path))?;
+                continue;
+            }
+
+            if rel_path
+                .file_name()
+                .is_some_and(|name| name.eq_ignore_ascii_case(ARCROPOLIS_CONFIG_FILE_NAME))
+            {
+                file_info.add_declared_slots_from_config(&p.join(&rel_path))?;
+            }
+
+            file_info.add_file(assoc, game_path);
+        }
+
+        Ok(file_info)
+    }
+
+    /// Records the extended skin slots declared by an ARCropolis config. A
+    /// config that can not be parsed is only warned about, since the rest of
+    /// the mod may still work.
+    fn add_declared_slots_from_config(&mut self, config_path: &Utf8Path) -> io::Result<()> {
+        let config =
+            match serde_json::from_str::<ArcropolisConfig>(&fs::read_to_string(config_path)?) {
+                Ok(config) => config,
+                Err(err) => {
+                    warn!(
+                        "Unable to parse the ARCropolis config \"{}\" ({}). Any skin slots that \
+                         it adds will not be known about.",
+                        config_path, err
+                    );
+                    return Ok(());
+                },
+            };
+
+        for slot in config
+            .added_paths()
+            .filter_map(|path| char_skin_slot_of_path(Utf8Path::new(path)))
+            .filter(|slot| !slot.skin_slot_idx().is_normal_skin_slot())
+        {
+            let slots = self
+                .declared_extra_slots
+                .entry(slot.char_key().to_string())
+                .or_default();
+
+            if !slots.contains(&slot.skin_slot_idx()) {
+                slots.push(slot.skin_slot_idx());
+                slots.sort_by_key(|slot| slot.idx());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The extended skin slots (`c08`+) that the variant's ARCropolis config
+    /// adds to the game.
+    pub fn declared_extra_slots(&self) -> impl Iterator<Item = CharSkinSlotValue> + '_ {
+        self.declared_extra_slots
+            .iter()
+            .flat_map(|(char_key, slots)| {
+                slots
+                    .iter()
+                    .map(|slot| CharSkinSlotValue::new(char_key.clone(), *slot))
+            })
+    }
+
+    /// Whether any of the files change something in the game.
+    pub fn affects_any_assets(&self) -> bool {
+        self.owned_files
+            .keys()
+            .any(|assoc| *assoc != ModFileAssetAssociation::NoEffect)
+    }
+
+    fn add_file(&mut self, assoc: ModFileAssetAssociation, game_path: Utf8PathBuf) {
+        let mod_type = match assoc {
+            ModFileAssetAssociation::CharSkinSlot(_) => Some(ModType::CharacterSkin),
+            ModFileAssetAssociation::Stage(_) => Some(ModType::StageSkin),
+            ModFileAssetAssociation::Global => Some(ModType::Core),
+            ModFileAssetAssociation::NoEffect => None,
+        };
+
+        if let Some(mod_type) = mod_type
+            && !self.mod_type.contains(&mod_type)
+        {
+            self.mod_type.push(mod_type);
+        }
+
+        self.owned_files.entry(assoc).or_default().push(game_path);
+    }
+
+    /// All assets affected by the variant along with the files that affect
+    /// each asset.
+    pub fn asset_associations(
+        &self,
+    ) -> impl Iterator<Item = (&ModFileAssetAssociation, &[Utf8PathBuf])> {
+        self.owned_files
+            .iter()
+            .map(|(assoc, files)| (assoc, files.as_slice()))
+    }
+}
+
+/// Strips anything before the first directory that the game itself knows about
+/// (eg. `MyCoolMod/fighter/mario/...` --> `fighter/mario/...`).
+fn get_game_path(rel_path: &Utf8Path) -> Utf8PathBuf {
+    let components = rel_path.components().collect::<Vec<_>>();
+
+    match find_game_root_idx(rel_path) {
+        Some(root_idx) => components[root_idx..].iter().collect(),
+        None => rel_path.to_path_buf(),
+    }
+}
+
+/// Everything before the first directory that the game itself knows about
+/// (eg. `MyCoolMod/fighter/mario/...` --> `MyCoolMod`). `None` if the path is
+/// not under any directory that the game knows about.
+pub(crate) fn get_game_root_offset(rel_path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let root_idx = find_game_root_idx(rel_path)?;
+    Some(rel_path.components().take(root_idx).collect())
+}
+
+fn find_game_root_idx(rel_path: &Utf8Path) -> Option<usize> {
+    rel_path
+        .components()
+        .position(|c| GAME_ROOT_DIR_NAMES.contains(&c.as_str().to_lowercase().as_str()))
+}
+
+/// Classifies a single file by its path in the game's file system.
+///
+/// Character files only belong to a skin slot if there is a slot directory
+/// (eg. `c02`) somewhere under the character directory. Anything else inside of
+/// the character directory (eg. `fighter/mario/motion/body/motion_list.bin`)
+/// is shared by every slot of the character, so it gets treated as global.
+fn classify_game_path(game_path: &Utf8Path) -> ModFileAssetAssociation {
+    if game_path
+        .extension()
+        .is_some_and(|ext| NO_EFFECT_FILE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+    {
+        return ModFileAssetAssociation::NoEffect;
+    }
+
+    let components = game_path
+        .components()
+        .map(|c| c.as_str().to_lowercase())
+        .collect::<Vec<_>>();
+
+    if let [root, char_key, rest @ ..] = components.as_slice()
+        && root == "fighter"
+        && let Some(slot_idx) = rest[..rest.len().saturating_sub(1)]
+            .iter()
+            .find_map(|c| parse_skin_slot_dir_name(c))
+    {
+        return ModFileAssetAssociation::CharSkinSlot(CharSkinSlotValue::new(
+            char_key.clone(),
+            SkinSlotValue::new(slot_idx),
+        ));
+    }
+
+    ModFileAssetAssociation::Global
+}
+
+/// Where a file of a variant ends up once the skin slot `old` is moved to
+/// `new` (eg. `MyCoolMod/fighter/mario/model/body/c02/model.numdlb` -->
+/// `MyCoolMod/fighter/mario/model/body/c05/model.numdlb`). `None` if the file
+/// is not in the skin slot `old` of any character. This is the inverse of how
+/// files are classified, so only the slot directory is renamed.
+pub fn path_in_other_skin_slot(
+    rel_path: &Utf8Path,
+    old: SkinSlotValue,
+    new: SkinSlotValue,
+) -> Option<Utf8PathBuf> {
+    let root_idx = find_game_root_idx(rel_path)?;
+    let mut components = rel_path
+        .components()
+        .map(|c| c.as_str().to_string())
+        .collect::<Vec<_>>();
+
+    if !components[root_idx].eq_ignore_ascii_case("fighter") {
+        return None;
+    }
+
+    let slot_dir_range = (root_idx + 2)..components.len().saturating_sub(1);
+    let slot_dir = components
+        .get_mut(slot_dir_range)?
+        .iter_mut()
+        .find(|c| parse_skin_slot_dir_name(&c.to_lowercase()).is_some())?;
+    if parse_skin_slot_dir_name(&slot_dir.to_lowercase()) != Some(old.idx()) {
+        return None;
+    }
+    *slot_dir = format!("c{:02}", new.idx());
+
+    Some(components.iter().collect())
+}
+
+/// Replaces the game path of a file while keeping anything before it (eg. the
+/// mod name directory). `None` if the game path of the file is not `old`.
+pub fn path_with_other_game_path(
+    rel_path: &Utf8Path,
+    old: &Utf8Path,
+    new: &Utf8Path,
+) -> Option<Utf8PathBuf> {
+    match get_game_path(rel_path) == old {
+        false => None,
+        true => Some(get_game_root_offset(rel_path).unwrap_or_default().join(new)),
+    }
+}
+
+/// The character skin slot that a path in an ARCropolis config belongs to.
+/// Unlike files, these can also be the slot directory itself (eg.
+/// `fighter/mario/c08`).
+fn char_skin_slot_of_path(game_path: &Utf8Path) -> Option<CharSkinSlotValue> {
+    let components = game_path
+        .components()
+        .map(|c| c.as_str().to_lowercase())
+        .collect::<Vec<_>>();
+
+    match components.as_slice() {
+        [root, char_key, rest @ ..] if root == "fighter" => {
+            let slot_idx = rest.iter().find_map(|c| parse_skin_slot_dir_name(c))?;
+            Some(CharSkinSlotValue::new(
+                char_key.clone(),
+                SkinSlotValue::new(slot_idx),
+            ))
+        },
+        _ => None,
+    }
+}
+
+/// Skin slot directories look like `c00`, `c07`, `c120`, etc.
+fn parse_skin_slot_dir_name(dir_name: &str) -> Option<u8> {
+    let idx_str = dir_name.strip_prefix('c')?;
+
+    match idx_str.len() >= 2 && idx_str.chars().all(|c| c.is_ascii_digit()) {
+        false => None,
+        true => idx_str.parse().ok(),
     }
 }
 
@@ -59,4 +550,375 @@ struct FileOwnerDb {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+    };
+
+    use camino::Utf8Path;
+    use ultimate_mod_man_rs_utils::types::{CharSkinSlotValue, SkinSlotValue};
+
+    use super::{
+        AssetType, ModFileAssetAssociation, VariantFileInfo, classify_game_path, get_game_path,
+        path_in_other_skin_slot, path_with_other_game_path, walk_files,
+    };
+
+    /// Written by the reslotter for a Mario skin moved to `c08`.
+    const RESLOTTED_MARIO_CONFIG: &str = r#"{
+    "new-dir-infos": [
+        "fighter/mario/c08",
+        "fighter/mario/c08/camera",
+        "fighter/mario/c08/cmn"
+    ],
+    "new-dir-infos-base": {
+        "fighter/mario/c08/camera": "fighter/mario/c00/camera"
+    },
+    "share-to-vanilla": {},
+    "share-to-added": {
+        "fighter/mario/model/body/c00/def_mario_001_col.nutexb": [
+            "fighter/mario/model/body/c08/def_mario_001_col.nutexb"
+        ],
+        "sound/bank/fighter/se_mario_c00.nus3audio": [
+            "sound/bank/fighter/se_mario_c08.nus3audio"
+        ]
+    },
+    "new-dir-files": {
+        "fighter/mario/c08": [
+            "fighter/mario/model/body/c08/model.numdlb"
+        ]
+    }
+}"#;
+
+    /// Ice Climbers skins are split across two characters and only share
+    /// files into the new slots.
+    const ICE_CLIMBERS_CONFIG: &str = r#"{
+    "new-dir-infos": [],
+    "share-to-added": {
+        "fighter/popo/model/body/c00/model.nusktb": [
+            "fighter/popo/model/body/c09/model.nusktb",
+            "fighter/popo/model/body/c12/model.nusktb"
+        ],
+        "fighter/nana/model/body/c00/model.nusktb": "fighter/nana/model/body/c09/model.nusktb"
+    }
+}"#;
+
+    /// Only replaces files that are already in the game.
+    const VANILLA_ONLY_CONFIG: &str = r#"{
+    "new-dir-infos": [],
+    "share-to-vanilla": {
+        "fighter/mario/model/body/c00/def_mario_001_col.nutexb": [
+            "fighter/mario/model/body/c01/def_mario_001_col.nutexb"
+        ]
+    }
+}"#;
+
+    fn m_classified_mod(files: &[(&str, &str)]) -> VariantFileInfo {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        for (file, contents) in files {
+            let file_path = root.join(file);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, contents).unwrap();
+        }
+
+        VariantFileInfo::from_uncompressed_path(root).unwrap()
+    }
+
+    fn m_slot(char_key: &str, idx: u8) -> CharSkinSlotValue {
+        CharSkinSlotValue::new(char_key.to_string(), SkinSlotValue::new(idx))
+    }
+
+    fn m_mario_slot(idx: u8) -> ModFileAssetAssociation {
+        ModFileAssetAssociation::CharSkinSlot(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(idx),
+        ))
+    }
+
+    #[test]
+    fn files_in_slot_dirs_are_classified_by_slot() {
+        assert_eq!(
+            classify_game_path("fighter/mario/model/body/c02/model.numdlb".into()),
+            m_mario_slot(2)
+        );
+    }
+
+    #[test]
+    fn slot_independent_files_in_char_dir_are_global() {
+        assert_eq!(
+            classify_game_path("fighter/mario/motion/body/motion_list.bin".into()),
+            ModFileAssetAssociation::Global
+        );
+    }
+
+    #[test]
+    fn moving_a_skin_slot_only_renames_the_slot_dir() {
+        let move_c02_to_c05 = |p: &str| {
+            path_in_other_skin_slot(
+                Utf8Path::new(p),
+                SkinSlotValue::new(2),
+                SkinSlotValue::new(5),
+            )
+        };
+
+        assert_eq!(
+            move_c02_to_c05("Cool Mario/fighter/mario/model/body/c02/c02.nutexb"),
+            Some("Cool Mario/fighter/mario/model/body/c05/c02.nutexb".into())
+        );
+        assert_eq!(
+            move_c02_to_c05("fighter/mario/model/body/c03/model.numdlb"),
+            None
+        );
+        assert_eq!(
+            move_c02_to_c05("fighter/mario/motion/body/motion_list.bin"),
+            None
+        );
+    }
+
+    #[test]
+    fn replacing_a_game_path_keeps_the_mod_name_dir() {
+        let replace = |p: &str| {
+            path_with_other_game_path(
+                Utf8Path::new(p),
+                Utf8Path::new("ui/param/database/ui_chara_db.prc"),
+                Utf8Path::new("ui/param/database/ui_chara_db_alt.prc"),
+            )
+        };
+
+        assert_eq!(
+            replace("Cool Mario/ui/param/database/ui_chara_db.prc"),
+            Some("Cool Mario/ui/param/database/ui_chara_db_alt.prc".into())
+        );
+        assert_eq!(replace("Cool Mario/ui/message/msg_name.msbt"), None);
+    }
+
+    #[test]
+    fn mod_name_dir_is_stripped_from_game_path() {
+        assert_eq!(
+            get_game_path(Utf8Path::new(
+                "Cool Mario/fighter/mario/motion/body/motion_list.bin"
+            )),
+            Utf8Path::new("fighter/mario/motion/body/motion_list.bin")
+        );
+    }
+
+    #[test]
+    fn previews_and_readmes_have_no_effect() {
+        assert_eq!(
+            classify_game_path("preview.png".into()),
+            ModFileAssetAssociation::NoEffect
+        );
+        assert_eq!(
+            classify_game_path("README.txt".into()),
+            ModFileAssetAssociation::NoEffect
+        );
+    }
+
+    #[test]
+    fn classifying_dir_groups_files_by_association() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        for file in [
+            "Cool Mario/fighter/mario/model/body/c03/model.numdlb",
+            "Cool Mario/fighter/mario/motion/body/motion_list.bin",
+            "Cool Mario/preview.png",
+        ] {
+            let file_path = root.join(file);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, []).unwrap();
+        }
+
+        let file_info = VariantFileInfo::from_uncompressed_path(root).unwrap();
+
+        assert_eq!(
+            file_info.owned_files[&m_mario_slot(3)],
+            vec![Utf8Path::new("fighter/mario/model/body/c03/model.numdlb")]
+        );
+        assert_eq!(
+            file_info.owned_files[&ModFileAssetAssociation::Global],
+            vec![Utf8Path::new("fighter/mario/motion/body/motion_list.bin")]
+        );
+        assert_eq!(
+            file_info.owned_files[&ModFileAssetAssociation::NoEffect].len(),
+            1
+        );
+    }
+
+    #[test]
+    fn files_of_unwanted_asset_types_are_deleted_instead_of_recorded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        for file in [
+            "Mixed/fighter/mario/model/body/c03/model.numdlb",
+            "Mixed/stage/battlefield/normal/model/stc_main/model.numdlb",
+            "Mixed/stream;/sound/bgm/bgm_crs_01_battlefield.nus3audio",
+            "Mixed/preview.png",
+        ] {
+            let file_path = root.join(file);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, []).unwrap();
+        }
+
+        let file_info =
+            VariantFileInfo::from_uncompressed_path_filtered(root, Some(&[AssetType::Music]))
+                .unwrap();
+
+        assert_eq!(
+            file_info.owned_files[&ModFileAssetAssociation::Global],
+            vec![Utf8Path::new(
+                "stream;/sound/bgm/bgm_crs_01_battlefield.nus3audio"
+            )]
+        );
+        assert_eq!(file_info.owned_files.len(), 2);
+        assert!(
+            !root
+                .join("Mixed/fighter/mario/model/body/c03/model.numdlb")
+                .exists()
+        );
+        assert!(
+            !root
+                .join("Mixed/stage/battlefield/normal/model/stc_main/model.numdlb")
+                .exists()
+        );
+        assert!(root.join("Mixed/preview.png").exists());
+    }
+
+    #[test]
+    fn deep_and_wide_trees_are_classified_while_walking() {
+        const DEPTH: usize = 64;
+        const FILES_PER_SLOT: usize = 200;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        // Bury the mod under a long chain of directories that are not part of
+        // the game path.
+        let mod_root = (0..DEPTH).fold(root.to_path_buf(), |p, i| p.join(format!("d{i}")));
+        for slot in 0..8 {
+            let slot_dir = mod_root.join(format!("fighter/mario/model/body/c0{slot}"));
+            fs::create_dir_all(&slot_dir).unwrap();
+
+            for i in 0..FILES_PER_SLOT {
+                fs::write(slot_dir.join(format!("model_{i}.numdlb")), []).unwrap();
+            }
+        }
+        fs::write(mod_root.join("preview.png"), []).unwrap();
+
+        // The walk is lazy, so files can be counted without collecting them.
+        assert_eq!(walk_files(root).count(), 8 * FILES_PER_SLOT + 1);
+
+        let file_info = VariantFileInfo::from_uncompressed_path(root).unwrap();
+        for slot in 0..8 {
+            let files = &file_info.owned_files[&m_mario_slot(slot)];
+
+            assert_eq!(files.len(), FILES_PER_SLOT);
+            assert!(files.iter().all(|f| f.starts_with("fighter/mario")));
+        }
+        assert_eq!(
+            file_info.owned_files[&ModFileAssetAssociation::NoEffect].len(),
+            1
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_out_of_the_mod_dir_are_not_followed() {
+        let outside_dir = tempfile::TempDir::new().unwrap();
+        let outside = Utf8Path::from_path(outside_dir.path()).unwrap();
+        fs::create_dir_all(outside.join("fighter/mario/model/body/c05")).unwrap();
+        fs::write(
+            outside.join("fighter/mario/model/body/c05/model.numdlb"),
+            [],
+        )
+        .unwrap();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+        fs::create_dir_all(root.join("fighter/mario/model/body/c01")).unwrap();
+        fs::write(root.join("fighter/mario/model/body/c01/model.numdlb"), []).unwrap();
+        std::os::unix::fs::symlink(outside.join("fighter"), root.join("linked")).unwrap();
+
+        let file_info = VariantFileInfo::from_uncompressed_path(root).unwrap();
+
+        assert_eq!(
+            file_info.owned_files.keys().collect::<Vec<_>>(),
+            vec![&m_mario_slot(1)]
+        );
+    }
+
+    #[test]
+    fn extended_slots_added_by_arcropolis_configs_are_recorded() {
+        let file_info = m_classified_mod(&[
+            ("Mario c08/config.json", RESLOTTED_MARIO_CONFIG),
+            ("Mario c08/fighter/mario/model/body/c08/model.numdlb", ""),
+        ]);
+        assert_eq!(
+            file_info.declared_extra_slots().collect::<Vec<_>>(),
+            vec![m_slot("mario", 8)]
+        );
+        assert_eq!(
+            file_info.owned_files[&m_mario_slot(8)],
+            vec![Utf8Path::new("fighter/mario/model/body/c08/model.numdlb")]
+        );
+
+        let file_info = m_classified_mod(&[("ICs/config.json", ICE_CLIMBERS_CONFIG)]);
+        assert_eq!(
+            file_info.declared_extra_slots().collect::<Vec<_>>(),
+            vec![m_slot("nana", 9), m_slot("popo", 9), m_slot("popo", 12)]
+        );
+    }
+
+    #[test]
+    fn configs_that_only_touch_vanilla_slots_declare_nothing() {
+        let file_info = m_classified_mod(&[
+            ("Recolor/config.json", VANILLA_ONLY_CONFIG),
+            ("Broken/config.json", "{ \"new-dir-infos\": [ "),
+        ]);
+
+        assert_eq!(file_info.declared_extra_slots().count(), 0);
+        // The configs themselves are still recorded like any other file.
+        assert_eq!(
+            file_info.owned_files[&ModFileAssetAssociation::Global].len(),
+            2
+        );
+    }
+
+    #[test]
+    fn variant_file_info_round_trips_through_toml() {
+        let skin_slot = ModFileAssetAssociation::CharSkinSlot(CharSkinSlotValue::new(
+            "mario".to_string(),
+            SkinSlotValue::new(2),
+        ));
+
+        let file_info = VariantFileInfo {
+            mod_type: Vec::new(),
+            owned_files: HashMap::from([
+                (
+                    skin_slot.clone(),
+                    vec!["fighter/mario/model/body/c02/model.numdlb".into()],
+                ),
+                (
+                    ModFileAssetAssociation::Global,
+                    vec!["ui/param/database/ui_chara_db.prc".into()],
+                ),
+            ]),
+            declared_extra_slots: BTreeMap::from([(
+                "mario".to_string(),
+                vec![SkinSlotValue::new(8)],
+            )]),
+        };
+
+        let deserialized: VariantFileInfo =
+            toml::from_str(&toml::to_string(&file_info).unwrap()).unwrap();
+
+        assert_eq!(deserialized.owned_files, file_info.owned_files);
+        assert_eq!(
+            deserialized.declared_extra_slots,
+            file_info.declared_extra_slots
+        );
+    }
+}
@@ -58,7 +58,7 @@ pub(crate) fn fuzzy_search_strings_and_return_one_or_many_depending_on_perfect_m
         })
         .collect::<Vec<_>>();
 
-    scored_str_idxs.sort();
+    scored_str_idxs.sort_by(|a, b| b.cmp(a));
 
     let have_a_perfect_match = scored_str_idxs[0].score == FloatOrd(1.0);
     match have_a_perfect_match {
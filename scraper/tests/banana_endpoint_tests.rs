@@ -1,90 +1,152 @@
-//! I want some automated way to detect if the scraping logic ever breaks. I
-//! don't think these need to be run with the standard batter of tests, but
-//! instead I might setup some automated job that runs every day just to detect
-//! when this breaks.
+//! I want some automated way to detect if the scraping logic ever breaks. These
+//! hit the real GameBanana, so they are skipped unless `UMM_LIVE_TESTS=1` is
+//! set (which the scheduled job does).
 
-use ultimate_mod_man_rs_scraper::banana_scraper::BananaClient;
+use std::env;
+
+use camino::Utf8Path;
+use tempfile::TempDir;
+use ultimate_mod_man_rs_scraper::banana_scraper::{
+    BananaClient, BananaScraperError, ChecksumPolicy, VariantMatchPolicy,
+};
 use ultimate_mod_man_rs_utils::{
-    types::{
-        AssetSlot, AvailableSlotsToSwapToInfo, ModId, PickedNonSwappableResolutionOption,
-        PickedResolutionOption, SwappableAssetSlot, VariantAndId,
-    },
-    user_input_delegate::{UserInputDelegate, VariantConflictSummary},
+    test_support::DummyDelegate,
+    types::{ModId, VariantAndId},
 };
 
+const LIVE_TESTS_ENV_VAR: &str = "UMM_LIVE_TESTS";
+
 static BULLEY_MAGUIRE_MOD_NAME: &str = "BULLY MAGUIRE over Joker";
 static BULLEY_MAGUIRE_MOD_VARIANT: &str = "tobeymaguire_85f71.zip";
 const BULLEY_MAGUIRE_MOD_ID: ModId = 378330;
 
-/// For testing.
-///
-/// Always returns "yes" and picks the first option in a list.
-struct DummyDelegate {}
-
-impl UserInputDelegate for DummyDelegate {
-    fn get_yes_no_resp(&mut self) -> bool {
-        true
-    }
-
-    fn select_item_from_list<T>(&mut self, items: &[T]) -> usize {
-        0
-    }
+/// Way past any ID that GameBanana has handed out.
+const MISSING_MOD_ID: ModId = 999_999_999;
 
-    fn display_variant_conflict_summary(&mut self, summary: &VariantConflictSummary) {}
+/// A search that matches far more mods than fit on a single page of results.
+static BROAD_SEARCH_STR: &str = "Mario";
 
-    fn choose_slot_to_swap_to(
-        &mut self,
-        slot: ultimate_mod_man_rs_utils::types::SwappableAssetSlot,
-        available_slots: &AvailableSlotsToSwapToInfo,
-    ) -> ultimate_mod_man_rs_utils::types::PickedSwapOption {
-        todo!()
-    }
+/// How many results GameBanana puts on a page. Name resolution only looks at
+/// the first page, so it breaks if this ever changes.
+const NUM_SEARCH_RESULTS_PER_PAGE: usize = 15;
 
-    fn get_variant_conflict_resolution_option_swappable(
-        &mut self,
-        existing: &VariantAndId,
-        new: &VariantAndId,
-        slot: &SwappableAssetSlot,
-        available_slots: &AvailableSlotsToSwapToInfo,
-    ) -> PickedResolutionOption {
-        PickedResolutionOption::NonSwapOption(PickedNonSwappableResolutionOption::KeepExisting)
+/// Returns a client for the real GameBanana if live tests are enabled.
+/// Otherwise says why the test is being skipped.
+fn live_client(test_name: &str) -> Option<BananaClient> {
+    if env::var(LIVE_TESTS_ENV_VAR).as_deref() != Ok("1") {
+        eprintln!(
+            "Skipping the live test `{}` (set {}=1 to run it).",
+            test_name, LIVE_TESTS_ENV_VAR
+        );
+        return None;
     }
 
-    fn get_variant_conflict_resolution_option_non_swappable(
-        &mut self,
-        existing: &VariantAndId,
-        new: &VariantAndId,
-        slot: &AssetSlot,
-    ) -> PickedNonSwappableResolutionOption {
-        PickedNonSwappableResolutionOption::KeepExisting
-    }
+    Some(BananaClient::new().unwrap())
 }
 
-// TODO: Add a special compile time flag to prevent these tests from running
-// with other tests...
 #[tokio::test]
-async fn resolve_mod_name_works_works() {
-    let res = BananaClient::new()
-        .unwrap()
-        .resolve_mod_name(BULLEY_MAGUIRE_MOD_NAME)
-        .await;
+async fn resolve_mod_name_works() {
+    let Some(client) = live_client("resolve_mod_name_works") else {
+        return;
+    };
+
+    let res = client.resolve_mod_name(BULLEY_MAGUIRE_MOD_NAME).await;
 
-    assert!(res.is_ok());
-    assert!(matches!(res, Ok(BULLEY_MAGUIRE_MOD_ID)));
+    assert!(matches!(res, Ok(BULLEY_MAGUIRE_MOD_ID)), "{:?}", res);
 }
 
 #[tokio::test]
 async fn download_variant_works() {
-    let res = BananaClient::new()
+    let Some(client) = live_client("download_variant_works") else {
+        return;
+    };
+    let dir = TempDir::new().unwrap();
+    let dest_path = Utf8Path::from_path(dir.path())
         .unwrap()
+        .join(BULLEY_MAGUIRE_MOD_VARIANT);
+
+    let res = client
         .download_mod_variant(
-            &mut DummyDelegate {},
+            &mut DummyDelegate::default(),
             &VariantAndId::new(
                 BULLEY_MAGUIRE_MOD_ID,
                 BULLEY_MAGUIRE_MOD_VARIANT.to_string(),
             ),
+            VariantMatchPolicy::Fuzzy,
+            ChecksumPolicy::Verify,
+            &dest_path,
         )
         .await;
 
-    assert!(res.is_ok());
+    assert!(res.is_ok(), "{:?}", res);
+    assert!(dest_path.exists());
+}
+
+#[tokio::test]
+async fn list_mod_files_works() {
+    let Some(client) = live_client("list_mod_files_works") else {
+        return;
+    };
+
+    let files = client.list_mod_files(BULLEY_MAGUIRE_MOD_ID).await.unwrap();
+    let variant = files
+        .iter()
+        .find(|f| f.name == BULLEY_MAGUIRE_MOD_VARIANT)
+        .unwrap();
+
+    assert!(variant.size_bytes > 0);
+    assert!(variant.date_added.timestamp() > 0);
+}
+
+#[tokio::test]
+async fn missing_mod_is_reported_as_an_http_error() {
+    let Some(client) = live_client("missing_mod_is_reported_as_an_http_error") else {
+        return;
+    };
+
+    match client.get_mod_update_info(MISSING_MOD_ID).await {
+        Err(BananaScraperError::HttpError(status, _)) => assert!((400..500).contains(&status)),
+        res => panic!("Expected an HTTP error but got {:?}", res),
+    }
+}
+
+/// Name resolution relies on the shape of the search API (including how it
+/// pages its results), so check that directly.
+#[tokio::test]
+async fn search_results_are_paged_the_way_that_we_expect() {
+    if live_client("search_results_are_paged_the_way_that_we_expect").is_none() {
+        return;
+    }
+
+    let get_page = |page: usize| async move {
+        let url = format!(
+            "https://gamebanana.com/apiv11/Util/Search/Results?_sModelName=Mod&_sOrder=best_match&\
+             _idGameRow=6498&_sSearchString={BROAD_SEARCH_STR}&_csvFields=name&_nPage={page}"
+        );
+
+        let body = reqwest::get(url).await.unwrap().text().await.unwrap();
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()
+    };
+
+    let first = get_page(1).await;
+    let second = get_page(2).await;
+    let record_ids = |page: &serde_json::Value| {
+        page["_aRecords"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["_idRow"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(
+        first["_aMetadata"]["_nRecordCount"].as_u64().unwrap() > NUM_SEARCH_RESULTS_PER_PAGE as u64
+    );
+    assert_eq!(record_ids(&first).len(), NUM_SEARCH_RESULTS_PER_PAGE);
+    assert!(!record_ids(&second).is_empty());
+    assert!(
+        record_ids(&second)
+            .iter()
+            .all(|id| !record_ids(&first).contains(id))
+    );
 }
@@ -0,0 +1,234 @@
+//! Runs downloads against a local server so that we control the checksums
+//! that the mod page reports.
+
+use std::{
+    fs,
+    sync::{Mutex, OnceLock},
+};
+
+use camino::Utf8Path;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::{
+    BananaClient, BananaScraperError, ChecksumPolicy, VariantMatchPolicy,
+};
+use ultimate_mod_man_rs_utils::{test_support::DummyDelegate, types::VariantAndId};
+
+const MOD_ID: u64 = 1;
+const VARIANT_NAME: &str = "variant.zip";
+const PAYLOAD: &[u8] = b"definitely a zip file";
+const PAYLOAD_MD5: &str = "b0049ab7d87257249cccfaea54d025d1";
+const MISMATCHED_MD5: &str = "00000000000000000000000000000000";
+
+/// Keeps every warning that gets logged so that tests can check for them.
+struct WarningCapturingLogger {
+    warnings: Mutex<Vec<String>>,
+}
+
+impl Log for WarningCapturingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.warnings
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn logger() -> &'static WarningCapturingLogger {
+    static LOGGER: OnceLock<WarningCapturingLogger> = OnceLock::new();
+
+    let mut installed = false;
+    let logger = LOGGER.get_or_init(|| {
+        installed = true;
+        WarningCapturingLogger {
+            warnings: Mutex::new(Vec::new()),
+        }
+    });
+
+    if installed {
+        log::set_logger(logger).unwrap();
+        log::set_max_level(LevelFilter::Warn);
+    }
+
+    logger
+}
+
+/// Serves a mod page that lists `md5` and `size` for the payload, along with
+/// `served` as the payload itself. Returns the root URL of the server.
+async fn start_server(md5: &str, size: usize, served: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let root = format!("http://{}", listener.local_addr().unwrap());
+
+    let mod_page = format!(
+        r#"{{
+            "_sName": "Mod",
+            "_sVersion": "",
+            "_aFiles": [{{
+                "_sFile": "{VARIANT_NAME}",
+                "_nFilesize": {size},
+                "_tsDateAdded": 0,
+                "_sDownloadUrl": "{root}/dl",
+                "_sMd5Checksum": "{md5}"
+            }}]
+        }}"#
+    );
+
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = listener.accept().await {
+            let mut req = vec![0; 4096];
+            let n = conn.read(&mut req).await.unwrap();
+            let req = String::from_utf8_lossy(&req[..n]);
+
+            let body = match req.starts_with("GET /dl ") {
+                false => mod_page.as_bytes(),
+                true => served,
+            };
+
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            conn.write_all(header.as_bytes()).await.unwrap();
+            conn.write_all(body).await.unwrap();
+        }
+    });
+
+    root
+}
+
+/// Downloads the variant into `dir`. Returns the contents of the download (if
+/// it was kept).
+async fn download(
+    dir: &TempDir,
+    md5: &str,
+    size: usize,
+    checksum_policy: ChecksumPolicy,
+) -> Result<Vec<u8>, BananaScraperError> {
+    download_served(dir, md5, size, PAYLOAD, checksum_policy).await
+}
+
+/// Like `download`, but the server sends `served` instead of the payload
+/// (eg. to cut it short).
+async fn download_served(
+    dir: &TempDir,
+    md5: &str,
+    size: usize,
+    served: &'static [u8],
+    checksum_policy: ChecksumPolicy,
+) -> Result<Vec<u8>, BananaScraperError> {
+    let root = start_server(md5, size, served).await;
+    let dest_path = Utf8Path::from_path(dir.path()).unwrap().join(VARIANT_NAME);
+
+    let payload = BananaClient::with_root(&root)
+        .unwrap()
+        .download_mod_variant(
+            &mut DummyDelegate::default(),
+            &VariantAndId::new(MOD_ID, VARIANT_NAME.to_string()),
+            VariantMatchPolicy::Fuzzy,
+            checksum_policy,
+            &dest_path,
+        )
+        .await?;
+
+    assert_eq!(payload.variant_download_artifact, dest_path);
+    Ok(fs::read(dest_path).unwrap())
+}
+
+fn dir_is_empty(dir: &TempDir) -> bool {
+    fs::read_dir(dir.path()).unwrap().next().is_none()
+}
+
+#[tokio::test]
+async fn matching_download_is_kept() {
+    let dir = TempDir::new().unwrap();
+
+    let res = download(&dir, PAYLOAD_MD5, PAYLOAD.len(), ChecksumPolicy::default()).await;
+    assert_eq!(res.unwrap(), PAYLOAD);
+}
+
+#[tokio::test]
+async fn mismatched_checksum_is_rejected_by_default() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(matches!(
+        download(&dir, MISMATCHED_MD5, 0, ChecksumPolicy::default()).await,
+        Err(BananaScraperError::VariantMd5CheckSumMismatch(..))
+    ));
+    assert!(dir_is_empty(&dir));
+}
+
+#[tokio::test]
+async fn mismatched_size_is_rejected_by_default() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(matches!(
+        download(&dir, PAYLOAD_MD5, PAYLOAD.len() - 1, ChecksumPolicy::default()).await,
+        Err(BananaScraperError::VariantSizeMismatch(_, _, expected, ours))
+            if expected == PAYLOAD.len() as u64 - 1 && ours == PAYLOAD.len() as u64
+    ));
+    assert!(dir_is_empty(&dir));
+}
+
+#[tokio::test]
+async fn truncated_download_is_reported_as_incomplete() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(matches!(
+        download_served(
+            &dir,
+            PAYLOAD_MD5,
+            PAYLOAD.len(),
+            &PAYLOAD[..5],
+            ChecksumPolicy::default()
+        )
+        .await,
+        Err(BananaScraperError::IncompleteDownload { expected, got, .. })
+            if expected == PAYLOAD.len() as u64 && got == 5
+    ));
+    assert!(dir_is_empty(&dir));
+}
+
+#[tokio::test]
+async fn empty_download_is_rejected_even_when_checksums_are_skipped() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(matches!(
+        download_served(&dir, PAYLOAD_MD5, PAYLOAD.len(), b"", ChecksumPolicy::Skip).await,
+        Err(BananaScraperError::IncompleteDownload { expected, got: 0, .. })
+            if expected == PAYLOAD.len() as u64
+    ));
+    assert!(dir_is_empty(&dir));
+}
+
+#[tokio::test]
+async fn mismatched_checksum_is_installed_with_a_warning_when_skipped() {
+    let logger = logger();
+    let dir = TempDir::new().unwrap();
+
+    assert_eq!(
+        download(&dir, MISMATCHED_MD5, 0, ChecksumPolicy::Skip)
+            .await
+            .unwrap(),
+        PAYLOAD
+    );
+    assert!(
+        logger
+            .warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|warning| warning.contains("does not match"))
+    );
+}
@@ -0,0 +1,81 @@
+//! Runs requests against a local server that only ever responds with an error
+//! (either an error status or an HTML error page where JSON was expected).
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use ultimate_mod_man_rs_scraper::banana_scraper::{BananaClient, BananaScraperError};
+
+const HTML_ERROR_PAGE: &str =
+    "<!DOCTYPE html><html><body><h1>Too many requests. Try again later.</h1></body></html>";
+
+/// Responds to every request with the given status line, content type and
+/// body. Returns the root URL of the server.
+async fn start_server_that_responds_with(
+    status_line: &'static str,
+    content_type: &'static str,
+    body: String,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let root = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = listener.accept().await {
+            let mut req = vec![0; 4096];
+            let _ = conn.read(&mut req).await.unwrap();
+
+            let header = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: \
+                 close\r\n\r\n",
+                status_line,
+                content_type,
+                body.len()
+            );
+            conn.write_all(header.as_bytes()).await.unwrap();
+            conn.write_all(body.as_bytes()).await.unwrap();
+        }
+    });
+
+    root
+}
+
+#[tokio::test]
+async fn missing_mod_is_reported_as_an_http_error() {
+    // The body is longer than what we keep in the error.
+    let root =
+        start_server_that_responds_with("404 Not Found", "text/plain", "x".repeat(1000)).await;
+
+    let res = BananaClient::with_root(&root)
+        .unwrap()
+        .get_mod_update_info(1)
+        .await;
+
+    match res {
+        Err(BananaScraperError::HttpError(status, body)) => {
+            assert_eq!(status, 404);
+            assert_eq!(body.len(), 256);
+        },
+        res => panic!("Expected an HTTP error but got {:?}", res),
+    }
+}
+
+#[tokio::test]
+async fn html_error_page_is_reported_as_an_unexpected_response() {
+    let root =
+        start_server_that_responds_with("200 OK", "text/html", HTML_ERROR_PAGE.to_string()).await;
+    let client = BananaClient::with_root(&root).unwrap();
+
+    match client.resolve_mod_name("Some Mod").await {
+        Err(BananaScraperError::UnexpectedResponse(status, snippet)) => {
+            assert_eq!(status, 200);
+            assert_eq!(snippet, HTML_ERROR_PAGE);
+        },
+        res => panic!("Expected an unexpected response error but got {:?}", res),
+    }
+
+    assert!(matches!(
+        client.get_mod_update_info(1).await,
+        Err(BananaScraperError::UnexpectedResponse(200, _))
+    ));
+}
@@ -0,0 +1,243 @@
+//! Hashing of files (and whole directory trees) that may be too large to load
+//! into memory.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// How much of a file is read at a time while hashing it.
+pub const HASH_BUF_SIZE: usize = 1024 * 1024;
+
+/// The number of hex characters in a `Digest`.
+const DIGEST_HEX_LEN: usize = blake3::OUT_LEN * 2;
+
+#[derive(Debug, Error)]
+#[error("\"{0}\" is not a valid digest (expected {DIGEST_HEX_LEN} hex characters)")]
+pub struct DigestFromStrErr(String);
+
+/// The BLAKE3 hash of some content. Stored as a lowercase hex string so that it
+/// can be written to (and read back from) TOML as-is.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Digest(String);
+
+impl Digest {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<blake3::Hash> for Digest {
+    fn from(hash: blake3::Hash) -> Self {
+        Self(hash.to_hex().to_string())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = DigestFromStrErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.len() == DIGEST_HEX_LEN && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            false => Err(DigestFromStrErr(s.to_string())),
+            true => Ok(Self(s.to_lowercase())),
+        }
+    }
+}
+
+impl TryFrom<String> for Digest {
+    type Error = DigestFromStrErr;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Digest> for String {
+    fn from(digest: Digest) -> Self {
+        digest.0
+    }
+}
+
+/// Hashes everything that can be read from `reader`, `HASH_BUF_SIZE` bytes at
+/// a time.
+pub fn hash_reader(mut reader: impl Read) -> io::Result<Digest> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0; HASH_BUF_SIZE];
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+/// Hashes a file without ever holding more than `HASH_BUF_SIZE` bytes of it in
+/// memory.
+pub fn hash_file_streaming(path: &Utf8Path) -> io::Result<Digest> {
+    hash_reader(File::open(path)?)
+}
+
+/// Hashes every file under `root` in parallel. The paths are relative to
+/// `root` and are always sorted the same way regardless of the order that the
+/// files were hashed in.
+pub fn hash_tree(root: &Utf8Path) -> io::Result<BTreeMap<Utf8PathBuf, Digest>> {
+    get_relative_file_paths_recursive(root)?
+        .into_par_iter()
+        .map(|rel_path| {
+            let digest = hash_file_streaming(&root.join(&rel_path))?;
+            Ok((rel_path, digest))
+        })
+        .collect()
+}
+
+fn get_relative_file_paths_recursive(root: &Utf8Path) -> io::Result<Vec<Utf8PathBuf>> {
+    let mut file_paths = Vec::new();
+    let mut dirs_to_visit = vec![Utf8PathBuf::new()];
+
+    while let Some(rel_dir) = dirs_to_visit.pop() {
+        for entry in root.join(&rel_dir).read_dir_utf8()? {
+            let entry = entry?;
+            let rel_path = rel_dir.join(entry.file_name());
+
+            match entry.file_type()?.is_dir() {
+                false => file_paths.push(rel_path),
+                true => dirs_to_visit.push(rel_path),
+            }
+        }
+    }
+
+    Ok(file_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    use super::{Digest, HASH_BUF_SIZE, hash_file_streaming, hash_reader, hash_tree};
+
+    const MIB: usize = 1024 * 1024;
+
+    /// Bytes that don't repeat with a short period (so that nothing about the
+    /// hash lines up with the buffer size by accident).
+    fn m_bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2654435761).max(1);
+
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// Hands out the bytes it was given while remembering the largest read
+    /// that was asked of it.
+    struct RecordingReader {
+        bytes: Vec<u8>,
+        pos: usize,
+        largest_read: usize,
+    }
+
+    impl Read for RecordingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.largest_read = self.largest_read.max(buf.len());
+
+            let n = buf.len().min(self.bytes.len() - self.pos);
+            buf[..n].copy_from_slice(&self.bytes[self.pos..self.pos + n]);
+            self.pos += n;
+
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn reader_is_hashed_through_a_fixed_buffer() {
+        let bytes = m_bytes(3 * MIB + 17, 1);
+        let mut reader = RecordingReader {
+            bytes: bytes.clone(),
+            pos: 0,
+            largest_read: 0,
+        };
+
+        let digest = hash_reader(&mut reader).unwrap();
+
+        assert_eq!(digest, blake3::hash(&bytes).into());
+        assert_eq!(reader.largest_read, HASH_BUF_SIZE);
+    }
+
+    #[test]
+    fn multi_mb_files_are_hashed_like_in_memory_content() {
+        let dir = TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        let files = [
+            ("a.bin", 8 * MIB),
+            ("sub/b.bin", 5 * MIB),
+            ("sub/deeper/c.bin", 1),
+        ];
+        for (seed, (rel_path, len)) in files.iter().enumerate() {
+            let path = root.join(rel_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, m_bytes(*len, seed as u32)).unwrap();
+        }
+
+        let single = hash_file_streaming(&root.join("a.bin")).unwrap();
+        let tree = hash_tree(root).unwrap();
+
+        assert_eq!(single, blake3::hash(&m_bytes(8 * MIB, 0)).into());
+        assert_eq!(
+            tree.keys().collect::<Vec<_>>(),
+            vec![
+                &Utf8PathBuf::from("a.bin"),
+                &Utf8PathBuf::from("sub/b.bin"),
+                &Utf8PathBuf::from("sub/deeper/c.bin"),
+            ]
+        );
+        assert_eq!(tree[&Utf8PathBuf::from("a.bin")], single);
+    }
+
+    #[test]
+    fn digests_round_trip_through_toml() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Entry {
+            digest: Digest,
+        }
+
+        let entry = Entry {
+            digest: blake3::hash(b"mod").into(),
+        };
+        let serialized = toml::to_string(&entry).unwrap();
+
+        assert_eq!(serialized, format!("digest = \"{}\"\n", entry.digest));
+        assert_eq!(toml::from_str::<Entry>(&serialized).unwrap(), entry);
+        assert!(toml::from_str::<Entry>("digest = \"not a digest\"").is_err());
+    }
+}
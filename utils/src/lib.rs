@@ -1,3 +1,6 @@
+pub mod hashing;
+#[cfg(feature = "test-support")]
+pub mod test_support;
 pub mod types;
 pub mod user_input_delegate;
 pub mod utils;
@@ -0,0 +1,156 @@
+//! Helpers shared by the tests of every crate in the workspace. Only built
+//! with the `test-support` feature.
+
+use std::{collections::VecDeque, fmt::Display};
+
+use crate::{
+    types::{
+        ModId, PickedNonSwappableResolutionOption, PickedResolutionOption, PickedSwapOption,
+        VariantAndId,
+    },
+    user_input_delegate::{
+        AvailableSlotToSwapInto, SlotInfo, UserInputDelegate, VariantConflictSummary,
+    },
+};
+
+/// Never expects to be asked anything. Picks the first option if it is (or
+/// whatever is preselected when picking several).
+///
+/// Conflicts are resolved with whatever was scripted in `conflict_resolutions`
+/// (in order), and by keeping the existing variant once the script runs out.
+/// Other variants of the same mod are only replaced outright if
+/// `replace_sibling_variants` is set.
+#[derive(Debug, Default)]
+pub struct DummyDelegate {
+    pub conflict_resolutions: VecDeque<PickedResolutionOption>,
+    pub replace_sibling_variants: bool,
+}
+
+impl DummyDelegate {
+    pub fn with_conflict_resolutions(
+        resolutions: impl IntoIterator<Item = PickedResolutionOption>,
+    ) -> Self {
+        Self {
+            conflict_resolutions: resolutions.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn replacing_sibling_variants() -> Self {
+        Self {
+            replace_sibling_variants: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl UserInputDelegate for DummyDelegate {
+    fn get_yes_no_resp(&mut self) -> bool {
+        true
+    }
+
+    fn select_item_from_list<T: Display>(&mut self, _items: &[T]) -> usize {
+        0
+    }
+
+    fn select_items_from_list<T: Display>(
+        &mut self,
+        _items: &[T],
+        preselected: &[usize],
+    ) -> Vec<usize> {
+        let mut selected = preselected.to_vec();
+        selected.sort_unstable();
+        selected.dedup();
+
+        selected
+    }
+
+    fn get_free_text_resp(&mut self, _prompt: &str) -> String {
+        String::new()
+    }
+
+    fn display_variant_conflict_summary(&mut self, _summary: &VariantConflictSummary) {}
+
+    fn confirm_replace_sibling_variant(
+        &mut self,
+        _existing: &VariantAndId,
+        _new: &VariantAndId,
+    ) -> bool {
+        self.replace_sibling_variants
+    }
+
+    fn display_waiting_for_lock(&mut self, _holder_pid: Option<u32>) {}
+
+    fn display_update_check_progress(
+        &mut self,
+        _num_checked: usize,
+        _total: usize,
+        _mod_name: &str,
+    ) {
+    }
+
+    fn display_sync_progress(
+        &mut self,
+        _files_copied: usize,
+        _total_files: usize,
+        _bytes_copied: u64,
+        _total_bytes: u64,
+    ) {
+    }
+
+    fn confirm_variant_update(&mut self, _key: &VariantAndId) -> bool {
+        true
+    }
+
+    fn confirm_mod_install(&mut self, _id: ModId, _mod_name: &str) -> bool {
+        true
+    }
+
+    fn confirm_variant_successor(
+        &mut self,
+        _installed: &VariantAndId,
+        _successor_file_name: &str,
+    ) -> bool {
+        true
+    }
+
+    fn choose_slot_to_swap_to(
+        &mut self,
+        _slot_info: &SlotInfo,
+        _available_slots: &[AvailableSlotToSwapInto],
+    ) -> PickedSwapOption {
+        PickedSwapOption::new(0)
+    }
+
+    fn get_variant_conflict_resolution_option_swappable(
+        &mut self,
+        _existing: &VariantAndId,
+        _new: &VariantAndId,
+        _slot_info: &SlotInfo,
+        _num_open_slots_available: usize,
+    ) -> PickedResolutionOption {
+        self.conflict_resolutions
+            .pop_front()
+            .unwrap_or(PickedResolutionOption::NonSwapOption(
+                PickedNonSwappableResolutionOption::KeepExisting,
+            ))
+    }
+
+    fn get_variant_conflict_resolution_option_non_swappable(
+        &mut self,
+        _existing: &VariantAndId,
+        _new: &VariantAndId,
+        _slot_info: &SlotInfo,
+    ) -> PickedNonSwappableResolutionOption {
+        match self.conflict_resolutions.pop_front() {
+            Some(PickedResolutionOption::NonSwapOption(opt)) => opt,
+            Some(PickedResolutionOption::Swap(_)) => {
+                panic!("A swap was scripted for a conflict that can not be swapped")
+            },
+            Some(PickedResolutionOption::KeepAsAlternative) => {
+                panic!("An alternative was scripted for a conflict that can not have one")
+            },
+            None => PickedNonSwappableResolutionOption::KeepExisting,
+        }
+    }
+}
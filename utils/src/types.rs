@@ -1,5 +1,4 @@
 use std::{
-    convert::Infallible,
     fmt::{self, Display, Formatter},
     str::FromStr,
 };
@@ -13,6 +12,20 @@ pub type ModId = u64;
 pub type SkinSlotIdx = usize;
 pub type StageSlotIdx = usize;
 
+/// Forces an identifier to be read as a mod ID (eg. `id:9001`).
+const ID_PREFIX: &str = "id:";
+
+/// Forces an identifier to be read as a mod name (eg. `name:1999` for a mod
+/// that is actually named `1999`).
+const NAME_PREFIX: &str = "name:";
+
+/// Either of these separate the mod from the variant (whichever comes first).
+const VARIANT_SEPARATORS: [char; 2] = ['/', ':'];
+
+const VARIANT_AND_IDENTIFIER_FORMAT_HINT: &str = "expected \"<mod ID or name>/<variant>\" (or \
+                                                  with a \":\" instead of the \"/\"), eg. \
+                                                  \"9001/variant.zip\"";
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum ModIdentifier {
     /// The ID of the mod on Game Banana.
@@ -47,15 +60,33 @@ impl From<String> for ModIdentifier {
 }
 
 impl FromStr for ModIdentifier {
-    // Impossible for this conversion to fail.
-    type Err = Infallible;
+    type Err = ModIdentifierStrError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // If we can parse it as a ID (`u64`), then treat it as an ID. Otherwise just
-        // assume that we received the mod name.``
-        s.parse::<u64>()
-            .map(ModIdentifier::Id)
-            .or_else(|_| Ok(ModIdentifier::Name(s.to_string())))
+        if let Some(id_str) = s.strip_prefix(ID_PREFIX) {
+            return id_str
+                .parse::<ModId>()
+                .map(ModIdentifier::Id)
+                .map_err(|_| ModIdentifierStrError::InvalidId(s.to_string()));
+        }
+
+        let ident = match s.strip_prefix(NAME_PREFIX) {
+            Some(name) => ModIdentifier::Name(name.to_string()),
+
+            // If we can parse it as a ID (`u64`), then treat it as an ID. Otherwise just
+            // assume that we received the mod name.
+            None => s
+                .parse::<ModId>()
+                .map(ModIdentifier::Id)
+                .unwrap_or_else(|_| ModIdentifier::Name(s.to_string())),
+        };
+
+        match ident {
+            ModIdentifier::Name(name) if name.is_empty() => {
+                Err(ModIdentifierStrError::Empty(s.to_string()))
+            },
+            ident => Ok(ident),
+        }
     }
 }
 
@@ -63,24 +94,45 @@ impl Display for ModIdentifier {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             ModIdentifier::Id(id) => write!(f, "{id}"),
+
+            // Keep names that would otherwise be read back as something else as names.
+            ModIdentifier::Name(name)
+                if name.parse::<ModId>().is_ok()
+                    || name.starts_with(ID_PREFIX)
+                    || name.starts_with(NAME_PREFIX) =>
+            {
+                write!(f, "{NAME_PREFIX}{name}")
+            },
             ModIdentifier::Name(name) => write!(f, "{name}"),
         }
     }
 }
 
+#[derive(Debug, Error)]
+pub enum ModIdentifierStrError {
+    #[error("Missing the mod ID or name in \"{0}\"")]
+    Empty(String),
+
+    #[error("Invalid mod ID \"{0}\" (expected a number after \"{ID_PREFIX}\", eg. \"id:9001\")")]
+    InvalidId(String),
+}
+
 #[derive(Debug, Error)]
 pub enum VariantAndIdentifierStrError {
-    #[error("Missing \"/\" when parsing the mod variant and identifier string \"{0}\"")]
+    #[error("Missing \"/\" (or \":\") in \"{0}\" ({VARIANT_AND_IDENTIFIER_FORMAT_HINT})")]
     MissingSlashSeparator(String),
 
     #[error(
-        "Missing \"/\" variant (left hand side of \"/\" when parsing the mod variant and \
-         identifier string \"{0}\""
+        "Missing the variant after the \"/\" (or \":\") in \"{0}\" \
+         ({VARIANT_AND_IDENTIFIER_FORMAT_HINT})"
     )]
     MissingVariant(String),
+
+    #[error("Unable to parse the mod in \"{0}\": {1} ({VARIANT_AND_IDENTIFIER_FORMAT_HINT})")]
+    InvalidIdentifier(String, ModIdentifierStrError),
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct VariantAndId {
     pub id: ModId,
     pub variant_name: String,
@@ -96,6 +148,18 @@ impl VariantAndId {
     pub fn new(id: ModId, variant_name: String) -> Self {
         Self { id, variant_name }
     }
+
+    /// Creates the key from the identifier that `id` was resolved from.
+    pub fn new_from_identifier(ident: &ModIdentifier, id: ModId, variant_name: String) -> Self {
+        if let ModIdentifier::Id(ident_id) = ident {
+            debug_assert_eq!(
+                *ident_id, id,
+                "Mod ID identifier resolved to a different ID"
+            );
+        }
+
+        Self::new(id, variant_name)
+    }
 }
 
 /// Because mods can have multiple install payloads that affect multiple slots,
@@ -111,34 +175,55 @@ impl FromStr for VariantAndIdentifier {
     type Err = VariantAndIdentifierStrError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // (ident/variant_name)
+        // (ident/variant_name) or (ident:variant_name)
         // eg. (9001/super_cool_variant_2)
-
-        // Note:
-        // - `split()` is guaranteed to always have at least one element.
-        // `ModIdentifier::from_str` is infallible.
-        let mut split_str = s.split("/");
-        let ident = ModIdentifier::from_str(split_str.next().unwrap()).unwrap();
-
-        let variant_name = match split_str.next() {
-            None => {
-                return Err(VariantAndIdentifierStrError::MissingSlashSeparator(
-                    s.to_string(),
-                ));
-            },
-            Some(s) if s.is_empty() => {
-                return Err(VariantAndIdentifierStrError::MissingVariant(s.to_string()));
-            },
-            Some(s) => s.to_string(),
-        };
+        //
+        // Only the first `/` or `:` separates the two, since variant names can
+        // contain either but mod identifiers can not. The `:` of an `id:` or `name:`
+        // prefix is part of the identifier as long as a separator still follows it.
+        // Whitespace around the whole string (eg. from copying it out of a file) is
+        // ignored.
+        let trimmed = s.trim();
+        let prefix = [ID_PREFIX, NAME_PREFIX]
+            .into_iter()
+            .find(|prefix| {
+                trimmed
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.contains(VARIANT_SEPARATORS))
+            })
+            .unwrap_or_default();
+
+        let (ident_str, variant_name) = trimmed[prefix.len()..]
+            .split_once(VARIANT_SEPARATORS)
+            .map(|(ident_str, variant_name)| {
+                (&trimmed[..prefix.len() + ident_str.len()], variant_name)
+            })
+            .ok_or_else(|| VariantAndIdentifierStrError::MissingSlashSeparator(s.to_string()))?;
+
+        let ident = ModIdentifier::from_str(ident_str)
+            .map_err(|err| VariantAndIdentifierStrError::InvalidIdentifier(s.to_string(), err))?;
+
+        if variant_name.is_empty() {
+            return Err(VariantAndIdentifierStrError::MissingVariant(s.to_string()));
+        }
 
         Ok(Self {
             ident,
-            variant_name,
+            variant_name: variant_name.to_string(),
         })
     }
 }
 
+impl VariantAndIdentifier {
+    pub fn ident(&self) -> &ModIdentifier {
+        &self.ident
+    }
+
+    pub fn variant_name(&self) -> &str {
+        &self.variant_name
+    }
+}
+
 impl Display for VariantAndIdentifier {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}/{}", self.ident, self.variant_name)
@@ -157,13 +242,31 @@ impl VariantAndIdentifierBuilder {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum AssetSlot {
     CharacterSkin(CharSkinSlotValue),
     StageSkin(StageSlotValue),
     Global(Utf8PathBuf),
 }
 
+impl AssetSlot {
+    /// Whether or not a conflict on this slot can be resolved by moving the
+    /// asset to a different slot.
+    pub fn is_swappable(&self) -> bool {
+        matches!(self, AssetSlot::CharacterSkin(_))
+    }
+}
+
+impl Display for AssetSlot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetSlot::CharacterSkin(slot) => write!(f, "{}", slot),
+            AssetSlot::StageSkin(slot) => write!(f, "{}", slot),
+            AssetSlot::Global(path) => write!(f, "{}", path),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SwappableAssetSlot {
     CharacterSkin(CharSkinSlotValue),
@@ -189,6 +292,23 @@ pub struct CharSkinSlotValue {
     skin_slot_idx: SkinSlotValue,
 }
 
+impl CharSkinSlotValue {
+    pub fn new(char_key: String, skin_slot_idx: SkinSlotValue) -> Self {
+        Self {
+            char_key,
+            skin_slot_idx,
+        }
+    }
+
+    pub fn char_key(&self) -> &str {
+        &self.char_key
+    }
+
+    pub fn skin_slot_idx(&self) -> SkinSlotValue {
+        self.skin_slot_idx
+    }
+}
+
 impl Display for CharSkinSlotValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{} - {}", self.char_key, self.skin_slot_idx)
@@ -208,26 +328,69 @@ impl Display for SkinSlotValue {
             _ => write!(f, "Custom skin slot")?,
         };
 
-        write!(f, " C{:2x} ({})", self.0, self.0)
+        // Skin slot directories are named in decimal (eg. `c07`, `c120`).
+        write!(f, " c{:02}", self.0)
     }
 }
 
 impl SkinSlotValue {
+    pub fn new(idx: u8) -> Self {
+        Self(idx)
+    }
+
+    pub fn idx(&self) -> u8 {
+        self.0
+    }
+
     pub fn is_normal_skin_slot(&self) -> bool {
         matches!(self.0, 0..=7)
     }
+
+    /// Every slot that `is_normal_skin_slot` holds for (`c00` - `c07`).
+    pub fn normal_skin_slots() -> impl Iterator<Item = Self> {
+        (0..=7).map(Self)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct StageSlotValue(u8);
 
+impl Display for StageSlotValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Stage slot {}", self.0)
+    }
+}
+
+impl StageSlotValue {
+    pub fn new(idx: u8) -> Self {
+        Self(idx)
+    }
+}
+
+/// The index of the slot that was picked from the available slots that were
+/// given to the delegate.
 #[derive(Clone, Copy, Debug)]
 pub struct PickedSwapOption(usize);
 
+impl PickedSwapOption {
+    pub fn new(idx: usize) -> Self {
+        Self(idx)
+    }
+
+    pub fn idx(&self) -> usize {
+        self.0
+    }
+}
+
 #[derive(Debug)]
 pub enum PickedResolutionOption {
     NonSwapOption(PickedNonSwappableResolutionOption),
     Swap(usize),
+
+    /// Keep the existing variant enabled, but hold on to the new one as an
+    /// inactive alternative for the slot. Only character skins can have
+    /// alternatives.
+    KeepAsAlternative,
 }
 
 #[derive(Debug)]
@@ -240,8 +403,10 @@ pub enum PickedNonSwappableResolutionOption {
 mod tests {
     use std::str::FromStr;
 
-    use super::{ModId, VariantAndIdentifier, VariantAndIdentifierBuilder};
-    use crate::types::{ModIdentifier, VariantAndIdentifierStrError};
+    use proptest::{prelude::*, prop_oneof, proptest};
+
+    use super::{ModId, VariantAndId, VariantAndIdentifier, VariantAndIdentifierBuilder};
+    use crate::types::{ModIdentifier, ModIdentifierStrError, VariantAndIdentifierStrError};
 
     #[test]
     fn mod_identifier_from_name_string_works() {
@@ -253,6 +418,27 @@ mod tests {
         assert_eq!(ModIdentifier::from_str("9001").unwrap(), 9001)
     }
 
+    #[test]
+    fn mod_identifier_displays_without_variant_name() {
+        assert_eq!(ModIdentifier::Id(378330).to_string(), "378330");
+        assert_eq!(
+            ModIdentifier::Name("Bully Maguire".to_string()).to_string(),
+            "Bully Maguire"
+        );
+    }
+
+    #[test]
+    fn mod_identifier_with_variant_displays_as_it_is_parsed() {
+        assert_eq!(
+            m_var_ident_test_payload_from_id(378330, "bully.zip").to_string(),
+            "378330/bully.zip"
+        );
+        assert_eq!(
+            m_var_ident_from_str("Bully Maguire/bully.zip").to_string(),
+            "Bully Maguire/bully.zip"
+        );
+    }
+
     fn m_var_ident_from_str(str: &str) -> VariantAndIdentifier {
         VariantAndIdentifier::from_str(str).unwrap()
     }
@@ -301,7 +487,7 @@ mod tests {
         ));
 
         assert!(matches!(
-            VariantAndIdentifier::from_str("just_a_mod_nameVariantAndIdentifier::from_str"),
+            VariantAndIdentifier::from_str("just_a_mod_name"),
             Err(VariantAndIdentifierStrError::MissingSlashSeparator(_))
         ));
 
@@ -309,5 +495,210 @@ mod tests {
             VariantAndIdentifier::from_str("just_a_mod_name/"),
             Err(VariantAndIdentifierStrError::MissingVariant(_))
         ));
+
+        assert!(matches!(
+            VariantAndIdentifier::from_str("/variant.zip"),
+            Err(VariantAndIdentifierStrError::InvalidIdentifier(
+                _,
+                ModIdentifierStrError::Empty(_)
+            ))
+        ));
+
+        assert!(matches!(
+            VariantAndIdentifier::from_str("id:abc/variant.zip"),
+            Err(VariantAndIdentifierStrError::InvalidIdentifier(
+                _,
+                ModIdentifierStrError::InvalidId(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn mod_identifier_with_variant_string_errors_echo_the_input() {
+        for input in ["no_slash_here", "mod_name/", "id:abc/variant.zip"] {
+            let err = VariantAndIdentifier::from_str(input).unwrap_err();
+
+            assert!(err.to_string().contains(input), "{}", err);
+            assert!(
+                err.to_string().contains("<mod ID or name>/<variant>"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn variant_name_keeps_everything_after_the_first_slash() {
+        assert_eq!(
+            m_var_ident_from_str("9001/sub/dir/variant.zip"),
+            m_var_ident_test_payload_from_id(9001, "sub/dir/variant.zip")
+        );
+    }
+
+    #[test]
+    fn prefixes_force_how_the_identifier_is_read() {
+        assert_eq!(
+            m_var_ident_from_str("id:9001/a.zip"),
+            m_var_ident_test_payload_from_id(9001, "a.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("name:9001/a.zip"),
+            m_var_ident_test_payload_from_name("9001", "a.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("123abc/a.zip"),
+            m_var_ident_test_payload_from_name("123abc", "a.zip")
+        );
+    }
+
+    #[test]
+    fn colon_separates_the_variant_like_a_slash() {
+        assert_eq!(
+            m_var_ident_from_str("9001:foo"),
+            m_var_ident_test_payload_from_id(9001, "foo")
+        );
+        assert_eq!(
+            m_var_ident_from_str("name:bar"),
+            m_var_ident_test_payload_from_name("name", "bar")
+        );
+        assert_eq!(
+            m_var_ident_from_str("Bully Maguire:bully v2.zip"),
+            m_var_ident_test_payload_from_name("Bully Maguire", "bully v2.zip")
+        );
+    }
+
+    #[test]
+    fn first_separator_splits_when_both_appear() {
+        assert_eq!(
+            m_var_ident_from_str("9001/a:b.zip"),
+            m_var_ident_test_payload_from_id(9001, "a:b.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("9001:a/b.zip"),
+            m_var_ident_test_payload_from_id(9001, "a/b.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("9001::a.zip"),
+            m_var_ident_test_payload_from_id(9001, ":a.zip")
+        );
+    }
+
+    #[test]
+    fn prefixes_are_kept_when_a_separator_follows_them() {
+        assert_eq!(
+            m_var_ident_from_str("id:9001:a.zip"),
+            m_var_ident_test_payload_from_id(9001, "a.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("name:1999:a.zip"),
+            m_var_ident_test_payload_from_name("1999", "a.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("name:1999/a:b.zip"),
+            m_var_ident_test_payload_from_name("1999", "a:b.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("id:abc"),
+            m_var_ident_test_payload_from_name("id", "abc")
+        );
+    }
+
+    #[test]
+    fn colon_without_a_variant_is_an_error() {
+        assert!(matches!(
+            VariantAndIdentifier::from_str("9001:"),
+            Err(VariantAndIdentifierStrError::MissingVariant(_))
+        ));
+        assert!(matches!(
+            VariantAndIdentifier::from_str(":a.zip"),
+            Err(VariantAndIdentifierStrError::InvalidIdentifier(
+                _,
+                ModIdentifierStrError::Empty(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn names_that_look_like_ids_display_with_a_prefix() {
+        assert_eq!(
+            m_var_ident_test_payload_from_name("9001", "a.zip").to_string(),
+            "name:9001/a.zip"
+        );
+        assert_eq!(
+            m_var_ident_test_payload_from_name("id:5", "a.zip").to_string(),
+            "name:id:5/a.zip"
+        );
+    }
+
+    #[test]
+    fn unicode_identifiers_and_variants_are_kept_as_is() {
+        assert_eq!(
+            m_var_ident_from_str("マリオ Mod/スキン 🍄.zip"),
+            m_var_ident_test_payload_from_name("マリオ Mod", "スキン 🍄.zip")
+        );
+    }
+
+    #[test]
+    fn whitespace_around_the_whole_string_is_ignored() {
+        assert_eq!(
+            m_var_ident_from_str("  Bully Maguire/bully v2.zip \n"),
+            m_var_ident_test_payload_from_name("Bully Maguire", "bully v2.zip")
+        );
+        assert_eq!(
+            m_var_ident_from_str("\t9001/a.zip "),
+            m_var_ident_test_payload_from_id(9001, "a.zip")
+        );
+    }
+
+    /// Identifiers can not contain a `/` or `:` and neither side can start or
+    /// end with whitespace (since it is trimmed when parsing).
+    fn var_ident_strategy() -> impl Strategy<Value = VariantAndIdentifier> {
+        let ident = prop_oneof![
+            any::<ModId>().prop_map(ModIdentifier::Id),
+            "[^/:\\s]([^/:]*[^/:\\s])?".prop_map(ModIdentifier::Name),
+        ];
+        let variant_name = "[^\\s](.*[^\\s])?";
+
+        (ident, variant_name).prop_map(|(ident, variant_name)| VariantAndIdentifier {
+            ident,
+            variant_name,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn variant_and_identifier_round_trips_through_display(var_ident in var_ident_strategy()) {
+            prop_assert_eq!(
+                VariantAndIdentifier::from_str(&var_ident.to_string()).unwrap(),
+                var_ident
+            );
+        }
+
+        #[test]
+        fn mod_identifier_round_trips_through_display(id in any::<ModId>(), name in "[^/]+") {
+            for ident in [ModIdentifier::Id(id), ModIdentifier::Name(name.clone())] {
+                prop_assert_eq!(ModIdentifier::from_str(&ident.to_string()).unwrap(), ident);
+            }
+        }
+    }
+
+    #[test]
+    fn variant_and_identifier_accessors_return_fields() {
+        let var_ident = m_var_ident_from_str("my_cool_rust_mod/release_v_3_4_8");
+
+        assert_eq!(*var_ident.ident(), "my_cool_rust_mod");
+        assert_eq!(var_ident.variant_name(), "release_v_3_4_8");
+    }
+
+    #[test]
+    fn variant_and_id_from_identifier_uses_resolved_id() {
+        let var_ident = m_var_ident_from_str("my_cool_rust_mod/release_v_3_4_8");
+        let key = VariantAndId::new_from_identifier(
+            var_ident.ident(),
+            9001,
+            var_ident.variant_name().to_string(),
+        );
+
+        assert_eq!(key, VariantAndId::new(9001, "release_v_3_4_8".to_string()));
     }
 }
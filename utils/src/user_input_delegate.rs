@@ -1,13 +1,76 @@
-use std::fmt::Display;
+use std::{collections::BTreeSet, fmt::Display};
+
+use thiserror::Error;
 
 use crate::types::{
-    PickedNonSwappableResolutionOption, PickedResolutionOption, PickedSwapOption, VariantAndId,
+    ModId, PickedNonSwappableResolutionOption, PickedResolutionOption, PickedSwapOption,
+    SwappableAssetSlot, VariantAndId,
 };
 
 #[derive(Debug)]
 pub struct VariantConflictSummary {
     new_variant: VariantAndId,
     existing_variant: VariantAndId,
+
+    /// The aliases that the user gave the variants (if any).
+    new_variant_alias: Option<String>,
+    existing_variant_alias: Option<String>,
+}
+
+impl VariantConflictSummary {
+    pub fn new(new_variant: VariantAndId, existing_variant: VariantAndId) -> Self {
+        Self {
+            new_variant,
+            existing_variant,
+            new_variant_alias: None,
+            existing_variant_alias: None,
+        }
+    }
+
+    pub fn with_aliases(
+        mut self,
+        new_variant_alias: Option<String>,
+        existing_variant_alias: Option<String>,
+    ) -> Self {
+        self.new_variant_alias = new_variant_alias;
+        self.existing_variant_alias = existing_variant_alias;
+        self
+    }
+
+    pub fn new_variant(&self) -> &VariantAndId {
+        &self.new_variant
+    }
+
+    pub fn existing_variant(&self) -> &VariantAndId {
+        &self.existing_variant
+    }
+
+    /// The new variant for showing to the user (eg. `1234/Tobey (tobey.zip)`
+    /// if it has an alias).
+    pub fn fmt_new_variant(&self) -> String {
+        fmt_aliased_variant(&self.new_variant, self.new_variant_alias.as_deref())
+    }
+
+    /// Same as `fmt_new_variant` but for the existing variant.
+    pub fn fmt_existing_variant(&self) -> String {
+        fmt_aliased_variant(
+            &self.existing_variant,
+            self.existing_variant_alias.as_deref(),
+        )
+    }
+
+    /// Both variants belong to the same mod (eg. its "all slots" and "c02
+    /// only" variants).
+    pub fn is_same_mod(&self) -> bool {
+        self.new_variant.id == self.existing_variant.id
+    }
+}
+
+fn fmt_aliased_variant(key: &VariantAndId, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => format!("{}/{} ({})", key.id, alias, key.variant_name),
+        None => key.to_string(),
+    }
 }
 
 pub struct SlotInfo {
@@ -18,6 +81,49 @@ pub struct SlotInfo {
     /// Human readable name that describes the type (eg. "Character skin",
     /// "Stage skin").
     slot_type_name: String,
+
+    /// The character that the slot belongs to (if it belongs to one).
+    character: Option<String>,
+}
+
+impl SlotInfo {
+    pub fn new(slot_name: String, slot_type_name: String) -> Self {
+        Self {
+            slot_name,
+            slot_type_name,
+            character: None,
+        }
+    }
+
+    pub fn with_character(self, character: String) -> Self {
+        Self {
+            character: Some(character),
+            ..self
+        }
+    }
+
+    pub fn character(&self) -> Option<&str> {
+        self.character.as_deref()
+    }
+
+    pub fn slot_name(&self) -> &str {
+        &self.slot_name
+    }
+
+    pub fn slot_type_name(&self) -> &str {
+        &self.slot_type_name
+    }
+}
+
+impl From<&SwappableAssetSlot> for SlotInfo {
+    fn from(value: &SwappableAssetSlot) -> Self {
+        match value {
+            SwappableAssetSlot::CharacterSkin(slot) => {
+                SlotInfo::new(slot.to_string(), "Character skin".to_string())
+                    .with_character(slot.char_key().to_string())
+            },
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -26,14 +132,88 @@ pub struct AvailableSlotToSwapInto {
     occupied_by: Option<VariantAndId>,
 }
 
+impl AvailableSlotToSwapInto {
+    pub fn new(slot_idx: usize, occupied_by: Option<VariantAndId>) -> Self {
+        Self {
+            slot_idx,
+            occupied_by,
+        }
+    }
+
+    pub fn slot_idx(&self) -> usize {
+        self.slot_idx
+    }
+
+    pub fn occupied_by(&self) -> Option<&VariantAndId> {
+        self.occupied_by.as_ref()
+    }
+}
+
 pub trait UserInputDelegate {
     fn get_yes_no_resp(&mut self) -> bool;
 
     /// The list provide is guaranteed to always have at least one element.
     fn select_item_from_list<T: Display>(&mut self, items: &[T]) -> usize;
 
+    /// Lets the user pick any number of the items (including none). The items
+    /// at `preselected` should be picked if the user does not say otherwise.
+    /// Returns the picked indices in ascending order without duplicates.
+    fn select_items_from_list<T: Display>(
+        &mut self,
+        items: &[T],
+        preselected: &[usize],
+    ) -> Vec<usize>;
+
+    /// Asks for a line of free text (eg. a note). An empty response means
+    /// that nothing was entered.
+    fn get_free_text_resp(&mut self, prompt: &str) -> String;
+
     fn display_variant_conflict_summary(&mut self, summary: &VariantConflictSummary);
 
+    /// The variant being added conflicts with another variant of the same mod
+    /// that is enabled. Returns `true` if the other variant should just be
+    /// disabled in favor of the new one instead of resolving each conflict.
+    fn confirm_replace_sibling_variant(
+        &mut self,
+        existing: &VariantAndId,
+        new: &VariantAndId,
+    ) -> bool;
+
+    /// Called once if another instance of the mod manager (with the PID
+    /// `holder_pid` if known) is using the state directory and we are waiting
+    /// for it to finish.
+    fn display_waiting_for_lock(&mut self, holder_pid: Option<u32>);
+
+    /// Called each time we finish checking a mod for updates.
+    fn display_update_check_progress(&mut self, num_checked: usize, total: usize, mod_name: &str);
+
+    /// Called each time a file finishes being copied to a sync target
+    /// (whether or not the copy succeeded).
+    fn display_sync_progress(
+        &mut self,
+        files_copied: usize,
+        total_files: usize,
+        bytes_copied: u64,
+        total_bytes: u64,
+    );
+
+    /// Asks if an installed variant should be updated to the newer version
+    /// that is available on GameBanana.
+    fn confirm_variant_update(&mut self, key: &VariantAndId) -> bool;
+
+    /// Asks if a mod that the user picked while browsing GameBanana should be
+    /// installed.
+    fn confirm_mod_install(&mut self, id: ModId, mod_name: &str) -> bool;
+
+    /// The file of an installed variant is gone from GameBanana, but another
+    /// file of the mod looks like its replacement. Returns `true` if the user
+    /// wants to track the new file from now on.
+    fn confirm_variant_successor(
+        &mut self,
+        installed: &VariantAndId,
+        successor_file_name: &str,
+    ) -> bool;
+
     fn choose_slot_to_swap_to(
         &mut self,
         slot_info: &SlotInfo,
@@ -55,3 +235,133 @@ pub trait UserInputDelegate {
         slot_info: &SlotInfo,
     ) -> PickedNonSwappableResolutionOption;
 }
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum ItemSelectionParseError {
+    #[error("\"{0}\" is not a non-negative integer or a range (eg. `3-5`).")]
+    NotAnIndex(String),
+
+    #[error("{0} is not an item selection between 0 - {1}.")]
+    OutOfRange(usize, usize),
+
+    #[error("The range {0}-{1} ends before it starts.")]
+    BackwardsRange(usize, usize),
+
+    #[error("There are no items to select.")]
+    NoItems,
+}
+
+/// Parses a selection of items out of a list of `num_items` like `1,3-5,8`.
+/// Ranges include both ends. `all` and `none` select every item and no items.
+/// Returns the selected indices in ascending order without duplicates.
+pub fn parse_item_selection(
+    input: &str,
+    num_items: usize,
+) -> Result<Vec<usize>, ItemSelectionParseError> {
+    let input = input.trim();
+    match input.to_lowercase().as_str() {
+        "all" => return Ok((0..num_items).collect()),
+        "none" => return Ok(Vec::new()),
+        _ => (),
+    }
+
+    let parse_idx = |s: &str| -> Result<usize, ItemSelectionParseError> {
+        let idx = s
+            .trim()
+            .parse()
+            .map_err(|_| ItemSelectionParseError::NotAnIndex(s.trim().to_string()))?;
+
+        match num_items {
+            0 => Err(ItemSelectionParseError::NoItems),
+            _ if idx >= num_items => Err(ItemSelectionParseError::OutOfRange(idx, num_items - 1)),
+            _ => Ok(idx),
+        }
+    };
+
+    let mut selected = BTreeSet::new();
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_idx(start)?, parse_idx(end)?);
+                if end < start {
+                    return Err(ItemSelectionParseError::BackwardsRange(start, end));
+                }
+
+                selected.extend(start..=end);
+            },
+            None => {
+                selected.insert(parse_idx(part)?);
+            },
+        }
+    }
+
+    Ok(selected.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ItemSelectionParseError, parse_item_selection};
+
+    #[test]
+    fn single_indices_and_ranges_are_combined() {
+        assert_eq!(parse_item_selection("1,3-5,8", 10), Ok(vec![1, 3, 4, 5, 8]));
+    }
+
+    #[test]
+    fn selection_is_sorted_and_deduplicated() {
+        assert_eq!(
+            parse_item_selection(" 4, 2-4 ,0,2 ", 5),
+            Ok(vec![0, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn all_and_none_select_everything_and_nothing() {
+        assert_eq!(parse_item_selection("all", 3), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_item_selection("ALL", 0), Ok(vec![]));
+        assert_eq!(parse_item_selection("none", 3), Ok(vec![]));
+    }
+
+    #[test]
+    fn single_item_range_is_allowed() {
+        assert_eq!(parse_item_selection("2-2", 3), Ok(vec![2]));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        assert_eq!(
+            parse_item_selection("1,3", 3),
+            Err(ItemSelectionParseError::OutOfRange(3, 2))
+        );
+        assert_eq!(
+            parse_item_selection("0-9", 3),
+            Err(ItemSelectionParseError::OutOfRange(9, 2))
+        );
+        assert_eq!(
+            parse_item_selection("0", 0),
+            Err(ItemSelectionParseError::NoItems)
+        );
+    }
+
+    #[test]
+    fn backwards_ranges_are_rejected() {
+        assert_eq!(
+            parse_item_selection("5-3", 10),
+            Err(ItemSelectionParseError::BackwardsRange(5, 3))
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        for input in ["", "a", "1,,2", "1-", "-1", "1-2-3", "1.5"] {
+            assert!(
+                matches!(
+                    parse_item_selection(input, 10),
+                    Err(ItemSelectionParseError::NotAnIndex(_))
+                ),
+                "{:?}",
+                input
+            );
+        }
+    }
+}